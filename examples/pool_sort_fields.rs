@@ -2,7 +2,6 @@ use raydium_amm_swap::amm::client::AmmSwapClient;
 use raydium_amm_swap::consts::SOL_MINT;
 use raydium_amm_swap::interface::{PoolSortField, PoolType};
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::signature::Keypair;
 use std::env;
 use tracing::info;
 
@@ -19,7 +18,7 @@ async fn main() {
     let sort_field = PoolSortField::Volume24h;
     let sort_field_param = sort_field.to_string();
 
-    let client = AmmSwapClient::new(RpcClient::new(rpc_url), Keypair::new());
+    let client = AmmSwapClient::watch_only(RpcClient::new(rpc_url));
     let pools = client
         .fetch_pool_info(
             &mint_a,
@@ -1,11 +1,10 @@
 use anyhow::anyhow;
 use raydium_amm_swap::amm::client::AmmSwapClient;
 use raydium_amm_swap::clmm::calculate_swap_change_accounts;
+use raydium_amm_swap::config::ClientConfig;
 use raydium_amm_swap::consts::{CLMM, SOL_MINT};
-use raydium_amm_swap::helpers::from_bytes_to_key_pair;
 use raydium_amm_swap::interface::{AmmPool, ClmmPool, ClmmSwapParams, PoolKeys, PoolType};
 use solana_address::Address;
-use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::signer::Signer;
 use spl_associated_token_account::get_associated_token_address;
@@ -17,18 +16,15 @@ use tracing::info;
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt().init();
-    dotenvy::dotenv().unwrap();
+    let config = ClientConfig::from_env().unwrap();
     let amount_in = 500_000;
-    let slippage = 0.01;
-    let url = env::var("RPC_URL").unwrap();
+    let slippage = config.slippage();
+    let slippage_bps = config.slippage_bps;
     let mint_a = env::var("MINT_1").unwrap_or(SOL_MINT.to_string());
     let mint_b = env::var("MINT_2").unwrap();
-    let rpc_client = RpcClient::new(url);
-    let owner = env::var("KEYPAIR").expect("KEYPAIR env is not presented");
-    let keypair = from_bytes_to_key_pair(owner);
-    let owner_pubkey = keypair.pubkey();
+    let owner_pubkey = config.keypair.pubkey();
     info!("Owner address {}", owner_pubkey.to_string());
-    let amm_swap_client = AmmSwapClient::new(rpc_client, keypair);
+    let amm_swap_client = config.into_client();
 
     // Choose which kind of pool to query.
     let pool_type = PoolType::Concentrated;
@@ -91,23 +87,28 @@ async fn main() {
             let mint_a = amm_swap_client
                 .get_or_create_token_program(&mint_a)
                 .await
-                .unwrap();
+                .unwrap()
+                .address;
 
             let mint_b = amm_swap_client
                 .get_or_create_token_program(&mint_b)
                 .await
-                .unwrap();
+                .unwrap()
+                .address;
             let pool_id = solana_pubkey::Pubkey::from_str(&key.id).unwrap();
             let user_input_token = solana_pubkey::Pubkey::from_str(&mint_a.to_string()).unwrap();
             let keys = ClmmSwapParams {
                 pool_id: pool_id.clone(),
                 user_input_token: user_input_token.clone(),
-                user_output_token: solana_pubkey::Pubkey::from_str(&mint_b.to_string()).unwrap(),
+                user_output_token: Some(
+                    solana_pubkey::Pubkey::from_str(&mint_b.to_string()).unwrap(),
+                ),
                 amount_specified: amount_in,
                 limit_price: None,
                 // if false -> amount is amount_in
                 base_out: false,
-                slippage_bps: 100,
+                slippage_bps,
+                max_price_impact_bps: None,
             };
 
             let pool_id = Address::from(pool_id.to_bytes());
@@ -157,7 +158,7 @@ async fn main() {
             );
 
             // let sig = amm_swap_client
-            //     .swap_clmm(ata_b, swap_result, tick_array_pubkey)
+            //     .swap_clmm(Some(ata_b), swap_result, tick_array_pubkey)
             //     .await
             //     .unwrap();
             // info!("{sig}");
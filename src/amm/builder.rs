@@ -0,0 +1,401 @@
+//! Builder for AMM v4 swaps, started with [`AmmSwapClient::swap`].
+//!
+//! The output mint follows from the input mint and the pool keys, so the
+//! direction of a swap cannot be flipped by passing the mints in the wrong
+//! order:
+//!
+//! ```ignore
+//! let signature = client
+//!     .swap(&pool_keys)
+//!     .input_mint(sol_mint)
+//!     .amount(1_000_000)
+//!     .slippage_bps(100)
+//!     .send()
+//!     .await?;
+//! ```
+
+use crate::amm::client::{
+    AmmSwapClient, amm_mint_token_program, preview_min_out, unix_timestamp, unsigned_transaction,
+};
+use crate::execution::{
+    ExecutionMode, PendingTransaction, PriorityFeeConfig, SwapFunding, check_price_impact,
+    send_transaction, with_failed_program,
+};
+use crate::interface::AmmPool;
+use crate::jito::{JitoClient, LandedBundle};
+use crate::signer::SwapSigner;
+use crate::validation;
+use anyhow::anyhow;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::transaction::VersionedTransaction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use tracing::{debug, info};
+
+/// Slippage tolerance of a [`SwapBuilder`] unless set, 0.5%.
+pub const DEFAULT_SLIPPAGE_BPS: u64 = 50;
+
+/// Exact input swap on an AMM v4 pool between the owner's associated token
+/// accounts.
+///
+/// The minimum output is quoted from the on-chain reserves at build time,
+/// unless set with [`Self::min_amount_out`], and checked against
+/// [`Self::max_price_impact_bps`] when set. A missing output account is
+/// created in the swap transaction; exactly the amount of a SOL input is
+/// wrapped in it, other inputs must already be held by the owner. Pools that
+/// do not accept swaps yet fail the build, see
+/// [`AmmSwapClient::check_amm_swap_enabled`].
+pub struct SwapBuilder<'a, S = Keypair> {
+    client: &'a AmmSwapClient<S>,
+    pool_keys: &'a AmmPool,
+    input_mint: Option<Pubkey>,
+    output_mint: Option<Pubkey>,
+    amount: Option<u64>,
+    slippage_bps: u64,
+    min_amount_out: Option<u64>,
+    max_price_impact_bps: Option<u64>,
+    priority_fee: Option<PriorityFeeConfig>,
+    unwrap_sol: bool,
+    recent_blockhash: Option<Hash>,
+}
+
+// Not derived, which would require the signer to be `Clone`.
+impl<S> Clone for SwapBuilder<'_, S> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client,
+            pool_keys: self.pool_keys,
+            input_mint: self.input_mint,
+            output_mint: self.output_mint,
+            amount: self.amount,
+            slippage_bps: self.slippage_bps,
+            min_amount_out: self.min_amount_out,
+            max_price_impact_bps: self.max_price_impact_bps,
+            priority_fee: self.priority_fee.clone(),
+            unwrap_sol: self.unwrap_sol,
+            recent_blockhash: self.recent_blockhash,
+        }
+    }
+}
+
+/// Swap built by [`SwapBuilder::build`], not yet signed.
+#[derive(Debug, Clone)]
+pub struct BuiltSwap {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    /// Quoted output before slippage, `None` when the minimum output was set
+    /// by the caller without a price impact limit.
+    pub amount_out: Option<u64>,
+    pub min_amount_out: u64,
+    /// Message paid by the client's owner, compute budget instructions
+    /// included.
+    pub message: VersionedMessage,
+}
+
+impl BuiltSwap {
+    /// Transaction of the message with empty signatures, to be signed
+    /// elsewhere.
+    pub fn unsigned_transaction(&self) -> VersionedTransaction {
+        unsigned_transaction(self.message.clone())
+    }
+}
+
+impl<'a, S: SwapSigner> SwapBuilder<'a, S> {
+    pub fn new(client: &'a AmmSwapClient<S>, pool_keys: &'a AmmPool) -> Self {
+        Self {
+            client,
+            pool_keys,
+            input_mint: None,
+            output_mint: None,
+            amount: None,
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            min_amount_out: None,
+            max_price_impact_bps: None,
+            priority_fee: None,
+            unwrap_sol: false,
+            recent_blockhash: None,
+        }
+    }
+
+    /// Mint sold, either mint of the pool. Required.
+    pub fn input_mint(mut self, input_mint: Pubkey) -> Self {
+        self.input_mint = Some(input_mint);
+        self
+    }
+
+    /// Mint bought. Optional, as it is the other mint of the pool; when set,
+    /// the build fails unless it is.
+    pub fn output_mint(mut self, output_mint: Pubkey) -> Self {
+        self.output_mint = Some(output_mint);
+        self
+    }
+
+    /// Amount of the input mint sold, in its smallest units. Required.
+    pub fn amount(mut self, amount: u64) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Slippage tolerance in bps, [`DEFAULT_SLIPPAGE_BPS`] unless set.
+    pub fn slippage_bps(mut self, slippage_bps: u64) -> Self {
+        self.slippage_bps = slippage_bps;
+        self
+    }
+
+    /// Minimum output, replacing the quote and the slippage tolerance.
+    pub fn min_amount_out(mut self, min_amount_out: u64) -> Self {
+        self.min_amount_out = Some(min_amount_out);
+        self
+    }
+
+    /// Largest accepted price impact in bps, fee excluded. Above it, the
+    /// build fails with `SwapError::PriceImpactExceeded` and nothing is sent.
+    /// Unlimited unless set.
+    pub fn max_price_impact_bps(mut self, max_price_impact_bps: u64) -> Self {
+        self.max_price_impact_bps = Some(max_price_impact_bps);
+        self
+    }
+
+    /// Priority fee of this swap, instead of the client's.
+    pub fn priority_fee(mut self, priority_fee: PriorityFeeConfig) -> Self {
+        self.priority_fee = Some(priority_fee);
+        self
+    }
+
+    /// Close the owner's wSOL account at the end of the swap transaction
+    /// when either mint is SOL, returning the SOL received or left unspent
+    /// and the account rent to the owner. Off unless set.
+    pub fn unwrap_sol(mut self, unwrap_sol: bool) -> Self {
+        self.unwrap_sol = unwrap_sol;
+        self
+    }
+
+    /// Blockhash of the message, e.g. fetched once for a batch of swaps.
+    /// The latest one is fetched at build time unless set.
+    pub fn recent_blockhash(mut self, recent_blockhash: Hash) -> Self {
+        self.recent_blockhash = Some(recent_blockhash);
+        self
+    }
+
+    /// Check the swap against the pool keys, quote it and compile its
+    /// message. Fails with `SwapError::InsufficientFunds` when the owner
+    /// cannot pay for it, see [`AmmSwapClient::check_swap_funds`].
+    pub async fn build(self) -> anyhow::Result<BuiltSwap> {
+        let input_mint = self
+            .input_mint
+            .ok_or(anyhow!("Swap input mint is not set"))?;
+        let amount_in = self.amount.ok_or(anyhow!("Swap amount is not set"))?;
+        validation::validate_amount(amount_in)?;
+        validation::validate_slippage_bps(self.slippage_bps)?;
+
+        let pool_keys = self.pool_keys;
+        let a_to_b = pool_keys.mint_a.address == input_mint.to_string();
+        let output_mint: Pubkey = if a_to_b {
+            pool_keys.mint_b.address.parse()?
+        } else {
+            pool_keys.mint_a.address.parse()?
+        };
+        validation::validate_amm_pair(
+            pool_keys,
+            &input_mint.to_string(),
+            &self.output_mint.unwrap_or(output_mint).to_string(),
+        )?;
+
+        // The pool status and open time are checked with the state read for
+        // the quote, or on their own when there is none.
+        let pool_id: Pubkey = pool_keys.id.parse()?;
+        let (amount_out, min_amount_out) = match self.min_amount_out {
+            Some(min_amount_out) if self.max_price_impact_bps.is_none() => {
+                self.client.check_amm_swap_enabled(&pool_id).await?;
+                (None, min_amount_out)
+            }
+            min_amount_out => {
+                let (market_state, rpc_pool_info) =
+                    self.client.get_rpc_pool_info_with_state(&pool_id).await?;
+                market_state
+                    .flags()
+                    .check_swap_enabled(&pool_id, unix_timestamp()?)?;
+                // Reserves follow the vaults, whose order may differ from the
+                // mints of the API pool keys.
+                let rpc_pool_info = rpc_pool_info.oriented(&input_mint, &pool_keys.id)?;
+                if let Some(max_price_impact_bps) = self.max_price_impact_bps {
+                    check_price_impact(
+                        rpc_pool_info.price_impact(amount_in),
+                        max_price_impact_bps,
+                    )?;
+                }
+                let amount_out = rpc_pool_info.amount_out(amount_in);
+                let slippage = self.slippage_bps as f64 / 10_000.0;
+                let min_amount_out =
+                    min_amount_out.unwrap_or_else(|| preview_min_out(amount_out, slippage));
+                (Some(amount_out), min_amount_out)
+            }
+        };
+        debug!(
+            "Swap {amount_in} of {input_mint} for {output_mint} through {}: \
+             out {amount_out:?}, min out {min_amount_out}",
+            pool_keys.id
+        );
+
+        let mut instructions = self.client.build_swap_amm_instructions(
+            pool_keys,
+            &input_mint,
+            &output_mint,
+            amount_in,
+            min_amount_out,
+        )?;
+        let native_mint = spl_token::native_mint::id();
+        if self.unwrap_sol && (input_mint == native_mint || output_mint == native_mint) {
+            instructions.push(self.client.unwrap_sol_instruction()?);
+        }
+        let priority_fee = self.priority_fee.as_ref().or(self.client.priority_fee());
+        let owner = self.client.owner_pubkey();
+        let funding = SwapFunding {
+            input_account: get_associated_token_address_with_program_id(
+                &owner,
+                &input_mint,
+                &amm_mint_token_program(pool_keys, &input_mint)?,
+            ),
+            mint_in: input_mint,
+            amount_in,
+            wrapped_lamports: if input_mint == native_mint {
+                amount_in
+            } else {
+                0
+            },
+            output_account: get_associated_token_address_with_program_id(
+                &owner,
+                &output_mint,
+                &amm_mint_token_program(pool_keys, &output_mint)?,
+            ),
+            mint_out: output_mint,
+            min_amount_out,
+        };
+        self.client
+            .check_swap_funds(&funding, &instructions, priority_fee)
+            .await?;
+
+        let lookup_tables = pool_keys
+            .lookup_table_account
+            .iter()
+            .map(|table| table.parse())
+            .collect::<Result<Vec<Pubkey>, _>>()?;
+        let message = match self.recent_blockhash {
+            Some(recent_blockhash) => {
+                self.client
+                    .swap_message_with_blockhash(
+                        &instructions,
+                        &lookup_tables,
+                        priority_fee,
+                        funding.trade_value_lamports(),
+                        recent_blockhash,
+                    )
+                    .await?
+            }
+            None => {
+                self.client
+                    .swap_message(
+                        &instructions,
+                        &lookup_tables,
+                        priority_fee,
+                        funding.trade_value_lamports(),
+                    )
+                    .await?
+            }
+        };
+        Ok(BuiltSwap {
+            input_mint,
+            output_mint,
+            amount_in,
+            amount_out,
+            min_amount_out,
+            message,
+        })
+    }
+
+    /// Build the swap and return it unsigned, e.g. for an external signer.
+    pub async fn build_unsigned(self) -> anyhow::Result<VersionedTransaction> {
+        Ok(self.build().await?.unsigned_transaction())
+    }
+
+    /// Build the swap, sign it with the client's owner and send it.
+    pub async fn send(self) -> anyhow::Result<Signature> {
+        let client = self.client;
+        let swap = self.build().await?;
+        let signature = client.send_message(swap.message).await?;
+        info!("Executed with Signature {signature}");
+        Ok(signature)
+    }
+
+    /// Build the swap, sign it with the client's owner and send it without
+    /// waiting for confirmation. The signature is available at once; confirm
+    /// with [`PendingTransaction::confirm`]. Expired blockhashes are not
+    /// refreshed. A send failure is reported with the program of the
+    /// failing instruction, see [`with_failed_program`].
+    pub async fn submit(self) -> anyhow::Result<PendingTransaction> {
+        let client = self.client;
+        if client.execution_mode() == ExecutionMode::DryRun {
+            return Err(anyhow!(
+                "Swaps cannot be submitted in dry run mode, use send"
+            ));
+        }
+        let swap = self.build().await?;
+        let transaction = client.sign_message(swap.message).await?;
+        let pending = send_transaction(client.rpc_client(), &transaction, client.send_options())
+            .await
+            .map_err(|err| with_failed_program(err, &transaction.message))?;
+        info!("Submitted {}", pending.signature);
+        Ok(pending)
+    }
+
+    /// Build the swap and send it as a Jito bundle with a transaction
+    /// tipping `bundle_tip_lamports`, both signed by the client's owner,
+    /// through the client's [`JitoClient`] or the default block engine.
+    ///
+    /// The swap and the tip land together or not at all. Waits for the
+    /// bundle to land up to the client's send timeout.
+    pub async fn send_via_jito(self, bundle_tip_lamports: u64) -> anyhow::Result<LandedBundle> {
+        let client = self.client;
+        if client.execution_mode() == ExecutionMode::DryRun {
+            return Err(anyhow!(
+                "Jito bundles cannot be sent in dry run mode, use send"
+            ));
+        }
+        let swap = self.build().await?;
+        let payer = client.owner_pubkey();
+        let tip_message = VersionedMessage::Legacy(Message::new_with_blockhash(
+            &[JitoClient::tip_instruction(&payer, bundle_tip_lamports)],
+            Some(&payer),
+            swap.message.recent_blockhash(),
+        ));
+        let transactions = [
+            client.sign_message(swap.message).await?,
+            client.sign_message(tip_message).await?,
+        ];
+
+        let default_jito_client;
+        let jito_client = match client.jito_client() {
+            Some(jito_client) => jito_client,
+            None => {
+                default_jito_client = JitoClient::default();
+                &default_jito_client
+            }
+        };
+        let send_options = client.send_options();
+        let bundle = jito_client
+            .send_bundle_and_confirm(
+                &transactions,
+                send_options.timeout,
+                send_options.poll_interval,
+            )
+            .await?;
+        info!(
+            "Executed in bundle {} at slot {}",
+            bundle.bundle_id, bundle.slot
+        );
+        Ok(bundle)
+    }
+}
@@ -1,35 +1,99 @@
-use crate::amm::{AmmInstruction, SwapInstructionBaseIn};
+use crate::amm::builder::SwapBuilder;
+use crate::amm::liquidity::{
+    AmmDeposit, AmmWithdrawal, amm_deposit_instruction, amm_withdraw_instruction,
+    quote_amm_deposit, quote_amm_withdraw,
+};
+use crate::amm::market::{OpenBookMarket, amm_authority};
+use crate::amm::status::AmmFlags;
+use crate::amm::{AmmInstruction, SwapInstructionBaseIn, SwapInstructionBaseOut};
+use crate::api::{PoolQuery, RaydiumApiClient};
 use crate::clmm::{
-    ClmmSwapChangeResult, clmm_utils, clmm_utils_sync, get_tick_array_keys, get_tick_arrays,
+    ClmmQuoteLadder, ClmmSwapChangeResult, ClmmSwapV2Keys, ClosePositionParams, ClosedPosition,
+    CollectedFees, DEFAULT_TICK_ARRAY_COUNT, DecreaseLiquidityParams, IncreaseLiquidityParams,
+    LiquidityChange, OpenPositionAccounts, OpenPositionArgs, OpenPositionParams, OpenedPosition,
+    PositionFees, PositionLiquidityAccounts, RewardAccounts, StaleAccount, Staleness,
+    StalenessPolicy, SwapComputeResult, TickCrossingReport, TwapEstimate, VolatilityEstimate,
+    analyze_swap_tick_crossings, build_clmm_swap_accounts, clmm_program_id, clmm_swap_v2_accounts,
+    clmm_utils, clmm_utils_sync, close_position_instruction, decrease_liquidity_instruction,
+    derive_personal_position_key, derive_tick_array_bitmap_extension_key, derive_tick_array_key,
+    derive_tick_array_keys, get_tick_array_keys, get_tick_array_keys_up_to, get_tick_arrays,
+    increase_liquidity_instruction, open_position_instruction, position_fees, position_liquidity,
+    realized_volatility, time_weighted_average_price,
+};
+use crate::common::{
+    AccountCache, FeeTier, RequestLog, RetryPolicy, TokenAccountState, amount_with_slippage,
+    deserialize_anchor_account, get_transfer_fee, get_transfer_inverse_fee,
+    is_transient_client_error, rpc, token_2022_account_len, unpack_mint, unpack_token,
 };
-use crate::common::rpc;
-use crate::consts::{
-    AMM_V4, CLMM, LIQUIDITY_FEES_DENOMINATOR, LIQUIDITY_FEES_NUMERATOR, swap_v2_discriminator,
+use crate::config::{ClusterConfig, DEFAULT_API_BASE_URL};
+use crate::consts::swap_v2_discriminator;
+use crate::cpmm::{CpmmAmmConfig, CpmmPoolState};
+use crate::error::{InsufficientFundsReason, SwapError, ValidationError};
+use crate::execution::{
+    DryRunLog, DryRunRecord, ExecutionMode, PriorityFeeConfig, SendOptions, SwapFunding,
+    SwapLamports, SwapResult, SwapSimulation, amm_swap_fee, amm_swap_pools, amm_trade_fee,
+    check_price_impact, clmm_trade_fee, created_token_accounts, failed_instruction_program,
+    parse_executed_swaps, send_and_confirm, simulate_swap_transaction, swap_fee_lamports,
+    swap_trade_value_lamports, with_failed_program,
 };
 use crate::interface::{
-    AmmPool, ClmmPool, ClmmPoolInfosResponse, ClmmSinglePoolInfo, ClmmSwapParams, PoolKeys,
-    PoolType, Rsps, TickArrays,
+    AmmPool, ClmmPool, ClmmSinglePoolInfo, ClmmSwapParams, Mint, MintExtensions, PoolKeys,
+    PoolType, Rsps, TickArrays, Vault,
 };
-use crate::states::{POOL_TICK_ARRAY_BITMAP_SEED, PoolState, TickArrayBitmapExtension};
-use anchor_spl::memo::spl_memo;
-use anyhow::{Context, anyhow};
+use crate::jito::JitoClient;
+use crate::libraries::get_delta_amounts_signed;
+use crate::router::{DiscoveredPool, find_best_pool_for_programs};
+use crate::signer::{SwapSigner, WatchOnly, partial_sign_message};
+use crate::states::{
+    AmmConfig, Observation, ObservationState, PersonalPositionState, PoolState,
+    TickArrayBitmapExtension, TickArrayState,
+};
+use crate::validation;
+use anchor_lang::AccountDeserialize;
+use anyhow::anyhow;
 use borsh::{BorshDeserialize, BorshSerialize};
+use futures_util::future::try_join_all;
+use futures_util::{Stream, TryStreamExt, stream};
 use log::warn;
-use reqwest::Client;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
+use solana_account::Account;
 use solana_address::Address;
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_request::TokenAccountsFilter;
 use solana_commitment_config::CommitmentConfig;
+use solana_program::rent::Rent;
+use solana_program::sysvar;
+use solana_sdk::hash::Hash;
 use solana_sdk::instruction::AccountMeta;
 use solana_sdk::instruction::Instruction;
+use solana_sdk::message::{AddressLookupTableAccount, Message, VersionedMessage, v0};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signature};
-use solana_sdk::signer::Signer;
-use solana_sdk::transaction::Transaction;
+use solana_sdk::transaction::VersionedTransaction;
 use solana_system_interface::instruction::transfer;
+use solana_transaction_status_client_types::{
+    EncodedTransactionWithStatusMeta, UiTransactionEncoding, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
 use spl_token::solana_program::program_pack::Pack;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
 use tracing::log::info;
-use tracing::{debug, error};
+
+/// Maximum number of accounts accepted by `getMultipleAccounts`.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+/// Size of the address lookup table header preceding the addresses.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+/// Largest serialized transaction accepted by the network.
+const MAX_TRANSACTION_SIZE: usize = 1232;
+/// Deepest pools of a pair quoted by [`AmmSwapClient::swap_token`].
+const SWAP_TOKEN_CANDIDATES: usize = 5;
 
 /// The result of computing a swap quote.
 #[derive(Debug, Clone)]
@@ -46,6 +110,8 @@ pub struct ComputeAmountOutResult {
     pub price_impact: f64,
     /// Fee deducted from the input.
     pub fee: u64,
+    /// Fee rates the quote was computed with.
+    pub fee_tier: FeeTier,
 }
 
 /// The result of computing the required input amount for a desired output.
@@ -63,9 +129,476 @@ pub struct ComputeAmountInResult {
     pub price_impact: f64,
     /// Fee deducted from the input.
     pub fee: u64,
+    /// Fee rates the quote was computed with.
+    pub fee_tier: FeeTier,
+}
+
+impl ComputeAmountOutResult {
+    /// Minimum amount out at another `slippage`, without re-quoting.
+    pub fn preview_min_out(&self, slippage: f64) -> u64 {
+        preview_min_out(self.amount_out, slippage)
+    }
+}
+
+impl ComputeAmountInResult {
+    /// Maximum amount in at another `slippage`, without re-quoting.
+    pub fn preview_max_in(&self, slippage: f64) -> u64 {
+        preview_max_in(self.amount_in, slippage)
+    }
+}
+
+/// Minimum amount out for a quoted `amount_out` at `slippage` (e.g. `0.005`
+/// for 0.5%), rounded down.
+pub fn preview_min_out(amount_out: u64, slippage: f64) -> u64 {
+    ((amount_out as f64) * (1.0 - slippage)).floor() as u64
+}
+
+/// Maximum amount in for a quoted `amount_in` at `slippage` (e.g. `0.005`
+/// for 0.5%), rounded up.
+pub fn preview_max_in(amount_in: u64, slippage: f64) -> u64 {
+    ((amount_in as f64) * (1.0 + slippage)).ceil() as u64
+}
+
+/// Orient an AMM pool so that quotes sell `input_mint`, either mint of the
+/// pool, for the other one.
+///
+/// The reserves are matched to `input_mint` by the mints of the pool vaults,
+/// and the metadata by its token A and B, so the quote stays right when the
+/// API lists the mints in the other order than the vaults.
+pub(crate) fn orient_amm_pool(
+    rpc_pool_info: RpcPoolInfo,
+    pool_info: &ClmmPool,
+    input_mint: &str,
+) -> anyhow::Result<(RpcPoolInfo, ClmmPool)> {
+    validation::validate_pool_mint(pool_info, input_mint)?;
+    let rpc_pool_info = rpc_pool_info.oriented(&input_mint.parse()?, &pool_info.id)?;
+    let mut pool_info = pool_info.clone();
+    if pool_info.mint_a.address != input_mint {
+        std::mem::swap(&mut pool_info.mint_a, &mut pool_info.mint_b);
+    }
+    Ok((rpc_pool_info, pool_info))
+}
+
+/// Constant product quote of selling `amount_in` of the base token of
+/// `rpc_pool_info`, see [`AmmSwapClient::compute_amount_out`].
+fn amm_amount_out(
+    rpc_pool_info: &RpcPoolInfo,
+    decimals_in: u32,
+    decimals_out: u32,
+    amount_in: u64,
+    slippage: f64,
+) -> ComputeAmountOutResult {
+    let reserve_in = rpc_pool_info.base_reserve;
+    let reserve_out = rpc_pool_info.quote_reserve;
+    debug!("Reserve out: {}", reserve_out);
+    debug!("Reserve in: {}", reserve_in);
+
+    let div_in = 10u128.pow(decimals_in);
+    let div_out = 10u128.pow(decimals_out);
+
+    let reserve_in_f = reserve_in as f64 / div_in as f64;
+    let reserve_out_f = reserve_out as f64 / div_out as f64;
+
+    // ------- Current price calculation ---------
+    let current_price = reserve_out_f / reserve_in_f;
+    debug!("Current price {}", current_price);
+
+    // ------- Amount + Fee calculation --------
+    let fee = rpc_pool_info.swap_fee_amount(amount_in);
+    let amount_out_raw = rpc_pool_info.amount_out(amount_in);
+
+    let min_amount_out = preview_min_out(amount_out_raw, slippage);
+
+    let exec_out_f = min_amount_out as f64 / div_out as f64;
+    let exec_in_f = amount_in.saturating_sub(fee) as f64 / div_in as f64;
+    let execution_price = exec_out_f / exec_in_f;
+
+    let price_impact = (current_price - execution_price) / current_price * 100.0;
+
+    debug!("Price impact {price_impact}");
+
+    ComputeAmountOutResult {
+        amount_out: amount_out_raw,
+        min_amount_out,
+        current_price,
+        execution_price,
+        price_impact,
+        fee,
+        fee_tier: rpc_pool_info.fee_tier(),
+    }
+}
+
+/// Anchor-style instruction data of the CLMM `SwapV2` instruction:
+/// discriminator + borsh-encoded fields.
+fn swap_v2_data(
+    amount: u64,
+    other_amount_threshold: u64,
+    sqrt_price_limit_x64: Option<u128>,
+    is_base_input: bool,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + 8 + 8 + 16 + 1);
+    data.extend_from_slice(&swap_v2_discriminator());
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+    data.extend_from_slice(&sqrt_price_limit_x64.unwrap_or(0u128).to_le_bytes());
+    data.push(is_base_input as u8);
+    data
+}
+
+/// Liquidity `amounts` buy in `ticks` of `pool_state`, net of the transfer
+/// fees of `mints` at `epoch`, with the most of each token the deposit may
+/// take, `slippage_bps` and transfer fees included.
+fn position_deposit(
+    pool_state: &PoolState,
+    mints: &[(Pubkey, Account); 2],
+    epoch: u64,
+    (tick_lower, tick_upper): (i32, i32),
+    amounts: [u64; 2],
+    slippage_bps: u64,
+) -> anyhow::Result<(u128, [u64; 2])> {
+    let mut net_amounts = amounts;
+    for (amount, (_, account)) in net_amounts.iter_mut().zip(mints) {
+        let transfer_fee = get_transfer_fee(&unpack_mint(&account.data)?, epoch, *amount)?;
+        *amount = amount.saturating_sub(transfer_fee);
+    }
+    let position = position_liquidity(
+        pool_state.sqrt_price_x64,
+        pool_state.tick_current,
+        tick_lower,
+        tick_upper,
+        net_amounts[0],
+        net_amounts[1],
+    )?;
+    if position.liquidity == 0 {
+        return Err(anyhow!(
+            "Deposits of {} and {} buy no liquidity in ticks {tick_lower}..{tick_upper}",
+            amounts[0],
+            amounts[1]
+        ));
+    }
+    let mut amounts_max = [position.amount_0, position.amount_1];
+    for (amount, (_, account)) in amounts_max.iter_mut().zip(mints) {
+        let with_slippage = amount_with_slippage(*amount, slippage_bps, true)?;
+        let transfer_fee =
+            get_transfer_inverse_fee(&unpack_mint(&account.data)?, epoch, with_slippage)?;
+        *amount = with_slippage.saturating_add(transfer_fee);
+    }
+    Ok((position.liquidity, amounts_max))
+}
+
+/// Least amounts of both tokens received for removing `liquidity` from
+/// `position` at the current pool price, less `slippage_bps` and the
+/// transfer fees of `mints`.
+fn position_withdrawal(
+    pool_state: &PoolState,
+    mints: &[(Pubkey, Account); 2],
+    epoch: u64,
+    position: &PersonalPositionState,
+    liquidity: u128,
+    slippage_bps: u64,
+) -> anyhow::Result<[u64; 2]> {
+    let amounts = get_delta_amounts_signed(
+        pool_state.tick_current,
+        pool_state.sqrt_price_x64,
+        position.tick_lower_index,
+        position.tick_upper_index,
+        -i128::try_from(liquidity)?,
+    )?;
+    let mut amounts_min = [amounts.0, amounts.1];
+    for (amount, (_, account)) in amounts_min.iter_mut().zip(mints) {
+        let with_slippage = amount_with_slippage(*amount, slippage_bps, false)?;
+        let transfer_fee = get_transfer_fee(&unpack_mint(&account.data)?, epoch, with_slippage)?;
+        *amount = with_slippage.saturating_sub(transfer_fee);
+    }
+    Ok(amounts_min)
+}
+
+/// Bitmap extension of `pool_id` when a tick array of `tick_lower..tick_upper`
+/// is beyond the default bitmap of the pool.
+fn position_tick_array_bitmap_extension(
+    pool_id: &Pubkey,
+    pool_state: &PoolState,
+    tick_lower: i32,
+    tick_upper: i32,
+    program_id: &solana_pubkey::Pubkey,
+) -> Option<Pubkey> {
+    pool_state
+        .is_overflow_default_tickarray_bitmap(vec![tick_lower, tick_upper])
+        .then(|| {
+            Pubkey::from(
+                derive_tick_array_bitmap_extension_key(
+                    &solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+                    program_id,
+                )
+                .to_bytes(),
+            )
+        })
+}
+
+/// [`AmmSwapClient::quote`] of a pool of the CLMM program `program_id`
+/// against its loaded states, selling `input_mint` in the direction of
+/// `ladder`.
+pub(crate) fn clmm_swap_quote(
+    pool_id: &Pubkey,
+    ladder: &ClmmQuoteLadder,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+    program_id: &solana_pubkey::Pubkey,
+) -> anyhow::Result<SwapQuote> {
+    let result = ladder.quote(amount_in)?;
+    let fee_tier = FeeTier::from_amm_config(&ladder.amm_config);
+    let (token_mint_0, token_mint_1) =
+        (ladder.pool_state.token_mint_0, ladder.pool_state.token_mint_1);
+    let output_mint = if ladder.zero_for_one {
+        token_mint_1
+    } else {
+        token_mint_0
+    };
+    let pool_id_v2 = solana_pubkey::Pubkey::from(pool_id.to_bytes());
+    let tick_arrays = derive_tick_array_keys(
+        &pool_id_v2,
+        result.tick_array_start_indexes.iter().copied(),
+        program_id,
+    )
+    .into_iter()
+    .map(|key| Pubkey::from(key.to_bytes()))
+    .collect();
+    Ok(SwapQuote {
+        pool_id: *pool_id,
+        pool_type: PoolType::Concentrated,
+        input_mint: *input_mint,
+        output_mint: Pubkey::from(output_mint.to_bytes()),
+        amount_in,
+        amount_out: result.other_amount,
+        min_amount_out: preview_min_out(result.other_amount, slippage),
+        price_impact: ladder.price_impact_bps(amount_in, &result) / 100.0,
+        fee: (amount_in as f64 * fee_tier.trade_fee_rate).ceil() as u64,
+        fee_tier,
+        tick_arrays,
+    })
+}
+
+/// [`AmmSwapClient::quote`] of an AMM v4 pool against its decoded state and
+/// reserves, without the pool as listed by the Raydium API.
+pub(crate) fn amm_state_quote(
+    pool_id: &Pubkey,
+    market_state: &LiquidityStateLayoutV4,
+    rpc_pool_info: RpcPoolInfo,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<SwapQuote> {
+    let base_to_quote = if market_state.base_mint == *input_mint {
+        true
+    } else if market_state.quote_mint == *input_mint {
+        false
+    } else {
+        return Err(ValidationError::MintNotInPool {
+            pool_id: pool_id.to_string(),
+            mint: input_mint.to_string(),
+        }
+        .into());
+    };
+    let (base_decimals, quote_decimals) = (
+        market_state.base_decimal as u32,
+        market_state.quote_decimal as u32,
+    );
+    let (rpc_pool_info, decimals_in, decimals_out, output_mint) = if base_to_quote {
+        (
+            rpc_pool_info,
+            base_decimals,
+            quote_decimals,
+            market_state.quote_mint,
+        )
+    } else {
+        (
+            rpc_pool_info.flipped(),
+            quote_decimals,
+            base_decimals,
+            market_state.base_mint,
+        )
+    };
+    let quote = amm_amount_out(
+        &rpc_pool_info,
+        decimals_in,
+        decimals_out,
+        amount_in,
+        slippage,
+    );
+    Ok(SwapQuote {
+        pool_id: *pool_id,
+        pool_type: PoolType::Standard,
+        input_mint: *input_mint,
+        output_mint,
+        amount_in,
+        amount_out: quote.amount_out,
+        min_amount_out: quote.min_amount_out,
+        price_impact: quote.price_impact,
+        fee: quote.fee,
+        fee_tier: quote.fee_tier,
+        tick_arrays: Vec::new(),
+    })
+}
+
+/// [`AmmSwapClient::quote_many`] of a CPMM pool against its decoded state
+/// and config, and the balances of its token 0 and token 1 vaults.
+fn cpmm_state_quote(
+    pool_id: &Pubkey,
+    pool_state: &CpmmPoolState,
+    amm_config: &CpmmAmmConfig,
+    vault_amounts: (u64, u64),
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<SwapQuote> {
+    let (reserve_0, reserve_1) = pool_state.reserves(vault_amounts.0, vault_amounts.1);
+    let (reserve_in, reserve_out, output_mint) = if pool_state.token_0_mint == *input_mint {
+        (reserve_0, reserve_1, pool_state.token_1_mint)
+    } else if pool_state.token_1_mint == *input_mint {
+        (reserve_1, reserve_0, pool_state.token_0_mint)
+    } else {
+        return Err(ValidationError::MintNotInPool {
+            pool_id: pool_id.to_string(),
+            mint: input_mint.to_string(),
+        }
+        .into());
+    };
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(anyhow!("Pool {pool_id} has no liquidity"));
+    }
+    let fee = amm_config.trade_fee(amount_in);
+    let amount_out = amm_config.amount_out(reserve_in, reserve_out, amount_in);
+    let spot_price = reserve_out as f64 / reserve_in as f64;
+    let execution_price = amount_out as f64 / (amount_in - fee).max(1) as f64;
+    Ok(SwapQuote {
+        pool_id: *pool_id,
+        pool_type: PoolType::Standard,
+        input_mint: *input_mint,
+        output_mint,
+        amount_in,
+        amount_out,
+        min_amount_out: preview_min_out(amount_out, slippage),
+        price_impact: (spot_price - execution_price) / spot_price * 100.0,
+        fee,
+        fee_tier: amm_config.fee_tier(),
+        tick_arrays: Vec::new(),
+    })
+}
+
+/// Balance of an SPL Token or Token-2022 account.
+fn token_amount(account: &Account) -> anyhow::Result<u64> {
+    Ok(match unpack_token(&account.owner, &account.data)? {
+        TokenAccountState::SplToken(state) => state.amount,
+        TokenAccountState::SplToken2022(state) => state.base.amount,
+    })
+}
+
+/// Pool of [`AmmSwapClient::quote_many`] decoded from the first batch of
+/// accounts, with the positions of its accounts in the second one.
+enum PendingQuote {
+    Amm {
+        market_state: Box<LiquidityStateLayoutV4>,
+        /// Base vault, followed by the quote vault.
+        vaults: usize,
+    },
+    Clmm {
+        pool_state: Box<PoolState>,
+        tickarray_bitmap_extension: Option<Box<TickArrayBitmapExtension>>,
+        zero_for_one: bool,
+        amm_config: usize,
+        tick_arrays: Range<usize>,
+    },
+    Cpmm {
+        pool_state: Box<CpmmPoolState>,
+        /// Token 0 vault, followed by the token 1 vault and the config.
+        accounts: usize,
+    },
+}
+
+/// `message` with default signatures, e.g. to measure its size.
+pub(crate) fn unsigned_transaction(message: VersionedMessage) -> VersionedTransaction {
+    let signatures = usize::from(message.header().num_required_signatures);
+    VersionedTransaction {
+        signatures: vec![Signature::default(); signatures],
+        message,
+    }
+}
+
+/// Current unix timestamp of the local clock, to compare with pool open
+/// times.
+pub(crate) fn unix_timestamp() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Address lookup table of an AMM v4 pool, when the API lists one.
+fn amm_lookup_tables(pool_keys: &AmmPool) -> anyhow::Result<Vec<Pubkey>> {
+    pool_keys
+        .lookup_table_account
+        .iter()
+        .map(|table| Ok(table.parse()?))
+        .collect()
+}
+
+/// Token program of `mint`, one of the mints of the AMM v4 pool of
+/// `pool_keys`, as listed by the API.
+pub(crate) fn amm_mint_token_program(pool_keys: &AmmPool, mint: &Pubkey) -> anyhow::Result<Pubkey> {
+    let mint = mint.to_string();
+    let pool_mint = [&pool_keys.mint_a, &pool_keys.mint_b]
+        .into_iter()
+        .find(|pool_mint| pool_mint.address == mint)
+        .ok_or_else(|| ValidationError::MintNotInPool {
+            pool_id: pool_keys.id.clone(),
+            mint,
+        })?;
+    Ok(pool_mint.program_id.parse()?)
+}
+
+/// Token program passed to the AMM v4 swap instructions, shared by both
+/// mints of the pool of `pool_keys`.
+pub(crate) fn amm_token_program(pool_keys: &AmmPool) -> anyhow::Result<Pubkey> {
+    let (program_a, program_b) = (&pool_keys.mint_a.program_id, &pool_keys.mint_b.program_id);
+    if program_a != program_b {
+        return Err(anyhow!(
+            "AMM v4 pool {} mixes token programs {program_a} and {program_b}",
+            pool_keys.id
+        ));
+    }
+    Ok(program_a.parse()?)
+}
+
+/// Pool to quote against with [`AmmSwapClient::quote`].
+#[derive(Debug, Clone, Copy)]
+pub enum PoolRef<'a> {
+    /// AMM v4 pool as listed by the Raydium API.
+    Amm(&'a ClmmPool),
+    /// CLMM pool by id.
+    Clmm(Pubkey),
+}
+
+/// Exact input quote of any pool type, returned by [`AmmSwapClient::quote`].
+#[derive(Debug, Clone)]
+pub struct SwapQuote {
+    pub pool_id: Pubkey,
+    pub pool_type: PoolType,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    /// Raw amount out before slippage.
+    pub amount_out: u64,
+    /// Minimum amount out after slippage tolerance.
+    pub min_amount_out: u64,
+    /// Percent price impact of this trade, fees excluded.
+    pub price_impact: f64,
+    /// Fee deducted from the input.
+    pub fee: u64,
+    pub fee_tier: FeeTier,
+    /// Tick arrays the swap moves through, in swap direction. Empty for AMM
+    /// pools.
+    pub tick_arrays: Vec<Pubkey>,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct LiquidityStateLayoutV4 {
     pub status: u64,
     pub nonce: u64,
@@ -121,39 +654,277 @@ pub struct LiquidityStateLayoutV4 {
     pub padding: [u64; 3],
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-struct AccountLayout {
-    mint: Pubkey,
-    owner: Pubkey,
-    amount: u64,
-    delegate_option: u32,
-    delegate: Pubkey,
-    state: u8,
-    is_native_option: u32,
-    is_native: u64,
-    delegated_amount: u64,
-    close_authority_option: u32,
-    close_authority: Pubkey,
+impl LiquidityStateLayoutV4 {
+    /// Size of an AMM v4 pool account.
+    pub const LEN: usize = 752;
+    /// Offset of `base_mint` in the account data.
+    pub const BASE_MINT_OFFSET: usize = 400;
+    /// Offset of `quote_mint` in the account data.
+    pub const QUOTE_MINT_OFFSET: usize = 432;
 }
 
 #[cfg_attr(feature = "derive", derive(Debug))]
+#[derive(Clone)]
 /// On‑chain reserves for a pool.
 pub struct RpcPoolInfo {
     /// Amount of quote token in vault.
     pub quote_reserve: u64,
     /// Amount of base token in vault.
     pub base_reserve: u64,
+    /// Swap fee numerator read from the pool state.
+    pub swap_fee_numerator: u64,
+    /// Swap fee denominator read from the pool state.
+    pub swap_fee_denominator: u64,
+    /// Mint of the base vault.
+    pub base_mint: Pubkey,
+    /// Mint of the quote vault.
+    pub quote_mint: Pubkey,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    /// Unix time the pool opens for swaps.
+    pub pool_open_time: u64,
+}
+
+impl RpcPoolInfo {
+    /// Reserves of the AMM v4 pool `market_state` from its base and quote
+    /// vault accounts, of the SPL Token or Token-2022 program.
+    pub fn from_accounts(
+        market_state: &LiquidityStateLayoutV4,
+        base_vault: &Account,
+        quote_vault: &Account,
+    ) -> anyhow::Result<Self> {
+        let base_vault = TokenAccountInfo::unpack(&base_vault.owner, &base_vault.data)?;
+        let quote_vault = TokenAccountInfo::unpack(&quote_vault.owner, &quote_vault.data)?;
+        let base_reserve = base_vault
+            .amount
+            .checked_sub(market_state.base_need_take_pnl)
+            .ok_or(anyhow!("Base vault holds less than the base PnL to take"))?;
+        let quote_reserve = quote_vault
+            .amount
+            .checked_sub(market_state.quote_need_take_pnl)
+            .ok_or(anyhow!("Quote vault holds less than the quote PnL to take"))?;
+        Ok(Self {
+            base_reserve,
+            quote_reserve,
+            swap_fee_numerator: market_state.swap_fee_numerator,
+            swap_fee_denominator: market_state.swap_fee_denominator,
+            base_mint: base_vault.mint,
+            quote_mint: quote_vault.mint,
+            base_decimals: market_state.base_decimal as u8,
+            quote_decimals: market_state.quote_decimal as u8,
+            pool_open_time: market_state.pool_open_time,
+        })
+    }
+
+    /// The same pool with base and quote swapped, to quote selling the
+    /// quote token.
+    pub fn flipped(self) -> Self {
+        Self {
+            quote_reserve: self.base_reserve,
+            base_reserve: self.quote_reserve,
+            base_mint: self.quote_mint,
+            quote_mint: self.base_mint,
+            base_decimals: self.quote_decimals,
+            quote_decimals: self.base_decimals,
+            ..self
+        }
+    }
+
+    /// The pool oriented to sell `input_mint`, matched by the mints of the
+    /// vaults: as is when it is the base mint, [`Self::flipped`] when it is
+    /// the quote mint. Fails when it is in neither vault of pool `pool_id`.
+    pub fn oriented(self, input_mint: &Pubkey, pool_id: &str) -> anyhow::Result<Self> {
+        if self.base_mint == *input_mint {
+            Ok(self)
+        } else if self.quote_mint == *input_mint {
+            Ok(self.flipped())
+        } else {
+            Err(anyhow!(
+                "Input mint {input_mint} is in neither vault of pool {pool_id}"
+            ))
+        }
+    }
+
+    /// Swap fee as `(numerator, denominator)`.
+    ///
+    /// Falls back to `LIQUIDITY_FEES_NUMERATOR/DENOMINATOR` when the pool
+    /// state carries no usable fee.
+    pub fn swap_fee(&self) -> (u64, u64) {
+        amm_swap_fee(self.swap_fee_numerator, self.swap_fee_denominator)
+    }
+
+    /// Swap fee as a [`FeeTier`].
+    pub fn fee_tier(&self) -> FeeTier {
+        let (numerator, denominator) = self.swap_fee();
+        FeeTier::from_amm(numerator, denominator)
+    }
+
+    /// Fee charged on `amount_in`, rounded up like the on-chain program.
+    pub fn swap_fee_amount(&self, amount_in: u64) -> u64 {
+        let (numerator, denominator) = self.swap_fee();
+        (amount_in as u128 * numerator as u128).div_ceil(denominator as u128) as u64
+    }
+
+    /// Quote token received for `amount_in` of the base token on the
+    /// constant product curve, after the swap fee and before slippage.
+    pub fn amount_out(&self, amount_in: u64) -> u64 {
+        let amount_in_with_fee = amount_in.saturating_sub(self.swap_fee_amount(amount_in)) as u128;
+        let denominator = self.base_reserve as u128 + amount_in_with_fee;
+        if denominator == 0 {
+            return 0;
+        }
+        (self.quote_reserve as u128 * amount_in_with_fee / denominator) as u64
+    }
+
+    /// Percent shortfall of the price of selling `amount_in` of the base
+    /// token, after the swap fee, from the price of the reserves. 100 when
+    /// a reserve is empty or the fee takes the whole input.
+    pub fn price_impact(&self, amount_in: u64) -> f64 {
+        let amount_in_with_fee = amount_in.saturating_sub(self.swap_fee_amount(amount_in));
+        if self.base_reserve == 0 || self.quote_reserve == 0 || amount_in_with_fee == 0 {
+            return 100.0;
+        }
+        let current_price = self.quote_reserve as f64 / self.base_reserve as f64;
+        let execution_price = self.amount_out(amount_in) as f64 / amount_in_with_fee as f64;
+        (current_price - execution_price) / current_price * 100.0
+    }
+}
+
+/// One swap of a batch built by [`AmmSwapClient::build_swaps`].
+#[derive(Debug, Clone)]
+pub enum SwapRequest {
+    /// AMM v4 exact input swap between the owner's associated token accounts.
+    Amm {
+        pool_keys: AmmPool,
+        mint_in: Pubkey,
+        mint_out: Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+        /// Close the input token account after the swap, returning its rent
+        /// to the owner. Only valid when `amount_in` is the whole balance.
+        close_source_account: bool,
+    },
+    /// CLMM swap computed by `calculate_swap_change_clmm`. The output goes to
+    /// the owner's associated token account of the output mint.
+    Clmm {
+        swap_change: ClmmSwapChangeResult,
+        tick_array_bitmap_extension: solana_pubkey::Pubkey,
+    },
+}
+
+/// Output actually received by a confirmed swap.
+#[derive(Debug, Clone)]
+pub struct SwapFill {
+    pub signature: Signature,
+    /// Output token balance of the owner before the swap.
+    pub pre_balance: u64,
+    /// Output token balance of the owner after the swap.
+    pub post_balance: u64,
+    /// `post_balance - pre_balance`.
+    pub amount_out: u64,
+}
+
+/// Swap executed by [`AmmSwapClient::swap_token`].
+#[derive(Debug, Clone)]
+pub struct TokenSwap {
+    pub signature: Signature,
+    /// Pool the swap was executed on.
+    pub pool_info: ClmmPool,
+    /// Quote the swap was sent with.
+    pub quote: SwapQuote,
+    /// Input actually spent, taken from the transaction token balances.
+    pub amount_in: u64,
+    /// Output actually received, taken from the transaction token balances.
+    pub amount_out: u64,
+}
+
+/// Fields of an SPL Token or Token-2022 account used to validate it.
+struct TokenAccountInfo {
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+    delegate: Option<Pubkey>,
+    delegated_amount: u64,
+}
+
+impl TokenAccountInfo {
+    fn unpack(token_program: &Pubkey, data: &[u8]) -> anyhow::Result<Self> {
+        Ok(match unpack_token(token_program, data)? {
+            TokenAccountState::SplToken(state) => Self {
+                mint: state.mint,
+                owner: state.owner,
+                amount: state.amount,
+                delegate: state.delegate.into(),
+                delegated_amount: state.delegated_amount,
+            },
+            TokenAccountState::SplToken2022(state) => Self {
+                mint: Pubkey::from(state.base.mint.to_bytes()),
+                owner: Pubkey::from(state.base.owner.to_bytes()),
+                amount: state.base.amount,
+                delegate: Option::from(state.base.delegate)
+                    .map(|delegate: solana_pubkey::Pubkey| Pubkey::from(delegate.to_bytes())),
+                delegated_amount: state.base.delegated_amount,
+            },
+        })
+    }
+}
+
+/// Token account resolved by [`AmmSwapClient::get_or_create_token_program`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAccountSetup {
+    /// Associated token account of the owner.
+    pub address: Pubkey,
+    /// SPL Token or Token-2022 program owning the mint and the account.
+    pub token_program: Pubkey,
+    /// Whether the account was created by this call, spending its rent.
+    pub created: bool,
+    /// Lamports wrapped into wSOL by this call.
+    pub wrapped_lamports: u64,
+}
+
+/// Token account of an owner, returned by
+/// [`AmmSwapClient::get_token_balances`].
+#[derive(Debug, Clone)]
+pub struct TokenBalance {
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    /// SPL Token or Token-2022 program owning the account.
+    pub token_program: Pubkey,
+    /// Raw balance.
+    pub amount: u64,
+    pub decimals: u8,
+    /// Symbol, name and logo of the mint as listed by the Raydium API,
+    /// `None` for mints it does not list.
+    pub metadata: Option<Mint>,
+}
+
+impl TokenBalance {
+    pub fn ui_amount(&self) -> f64 {
+        self.amount as f64 / 10f64.powi(self.decimals as i32)
+    }
 }
 
 /// High‑level client for performing swaps between two mints.
-pub struct AmmSwapClient {
-    reqwest_client: Client,
-    base_url: String,
-    owner: Keypair,
+///
+/// Transactions are signed by `S`, an in-process [`Keypair`] unless another
+/// [`SwapSigner`] is given.
+pub struct AmmSwapClient<S = Keypair> {
+    api_client: RaydiumApiClient,
+    owner: S,
     rpc_client: RpcClient,
+    send_options: SendOptions,
+    request_log: Option<RequestLog>,
+    priority_fee: Option<PriorityFeeConfig>,
+    retry_policy: RetryPolicy,
+    account_cache: Option<AccountCache>,
+    staleness_policy: Option<StalenessPolicy>,
+    jito_client: Option<JitoClient>,
+    execution_mode: ExecutionMode,
+    dry_run_log: DryRunLog,
+    cluster_config: ClusterConfig,
 }
 
-impl AmmSwapClient {
+impl<S: SwapSigner> AmmSwapClient<S> {
     /// Creates a new swap client.
     ///
     /// # Arguments
@@ -161,9 +932,10 @@ impl AmmSwapClient {
     /// - `rpc_client`: the Solana RPC client to use.
     /// - `mint_1`: the base token mint.
     /// - `mint_2`: the quote token mint.
-    /// - `owner`: signer for transaction execution.
-    pub fn new(rpc_client: RpcClient, owner: Keypair) -> Self {
-        Self::new_with_base_url(rpc_client, owner, "https://api-v3.raydium.io")
+    /// - `owner`: signer for transaction execution, a [`Keypair`] or any
+    ///   other [`SwapSigner`].
+    pub fn new(rpc_client: RpcClient, owner: S) -> Self {
+        Self::new_with_base_url(rpc_client, owner, DEFAULT_API_BASE_URL)
     }
 
     /// Creates a new swap client with a custom Raydium HTTP base URL.
@@ -172,110 +944,447 @@ impl AmmSwapClient {
     /// Raydium API environments.
     pub fn new_with_base_url(
         rpc_client: RpcClient,
-        owner: Keypair,
+        owner: S,
         base_url: impl Into<String>,
     ) -> Self {
-        let reqwest_client = Client::new();
         Self {
             rpc_client,
-            base_url: base_url.into(),
+            api_client: RaydiumApiClient::new(base_url),
             owner,
-            reqwest_client,
+            send_options: SendOptions::default(),
+            request_log: None,
+            priority_fee: None,
+            retry_policy: RetryPolicy::default(),
+            account_cache: None,
+            staleness_policy: None,
+            jito_client: None,
+            execution_mode: ExecutionMode::Live,
+            dry_run_log: DryRunLog::new(),
+            cluster_config: ClusterConfig::mainnet(),
         }
     }
 
-    async fn get<T: DeserializeOwned>(
+    /// Commitment, timeout and preflight settings of every transaction sent
+    /// by the client, swaps and helper transactions alike.
+    pub fn with_send_options(mut self, send_options: SendOptions) -> Self {
+        self.send_options = send_options;
+        self
+    }
+
+    pub fn send_options(&self) -> &SendOptions {
+        &self.send_options
+    }
+
+    /// Record every Raydium API request into `request_log`. To also record
+    /// RPC calls, build the client with [`RequestLog::rpc_client`].
+    pub fn with_request_log(mut self, request_log: RequestLog) -> Self {
+        self.api_client = self.api_client.with_request_log(request_log.clone());
+        self.request_log = Some(request_log);
+        self
+    }
+
+    /// Retries of Raydium API requests and of the account and blockhash
+    /// reads of the client, [`RetryPolicy::default`] unless set.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.api_client = self.api_client.with_retry_policy(retry_policy);
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Raydium API requests go through `api_client`, with its base URL,
+    /// timeout, headers and `reqwest::Client`. Its retry policy and request
+    /// log are replaced by later calls of [`Self::with_retry_policy`] and
+    /// [`Self::with_request_log`].
+    pub fn with_api_client(mut self, api_client: RaydiumApiClient) -> Self {
+        self.api_client = api_client;
+        self
+    }
+
+    pub fn api_client(&self) -> &RaydiumApiClient {
+        &self.api_client
+    }
+
+    /// Read pool states, amm configs, mints and bitmap extensions through
+    /// `account_cache` when quoting, rather than fetching them every time.
+    /// Token accounts and tick arrays are always fetched.
+    pub fn with_account_cache(mut self, account_cache: AccountCache) -> Self {
+        self.account_cache = Some(account_cache);
+        self
+    }
+
+    pub fn account_cache(&self) -> Option<&AccountCache> {
+        self.account_cache.as_ref()
+    }
+
+    /// Check the cached accounts of CLMM quote ladders against
+    /// `staleness_policy` when loading them: pool states, amm configs and
+    /// bitmap extensions by the slot they were read at, pool states by their
+    /// `recent_epoch` too. Stale accounts are logged, and the ones read too
+    /// many slots ago are read again if the policy refreshes. Nothing is
+    /// checked without an account cache.
+    pub fn with_staleness_policy(mut self, staleness_policy: StalenessPolicy) -> Self {
+        self.staleness_policy = Some(staleness_policy);
+        self
+    }
+
+    pub fn staleness_policy(&self) -> Option<&StalenessPolicy> {
+        self.staleness_policy.as_ref()
+    }
+
+    /// Block engine of swaps sent with [`SwapBuilder::send_via_jito`], the
+    /// default [`JitoClient`] unless set.
+    pub fn with_jito_client(mut self, jito_client: JitoClient) -> Self {
+        self.jito_client = Some(jito_client);
+        self
+    }
+
+    pub fn jito_client(&self) -> Option<&JitoClient> {
+        self.jito_client.as_ref()
+    }
+
+    /// In [`ExecutionMode::DryRun`], transactions are signed and simulated
+    /// instead of sent, see [`crate::execution::dry_run`].
+    pub fn with_execution_mode(mut self, execution_mode: ExecutionMode) -> Self {
+        self.execution_mode = execution_mode;
+        self
+    }
+
+    pub fn execution_mode(&self) -> ExecutionMode {
+        self.execution_mode
+    }
+
+    /// Transactions simulated in [`ExecutionMode::DryRun`].
+    pub fn dry_run_log(&self) -> &DryRunLog {
+        &self.dry_run_log
+    }
+
+    /// Talk to the Raydium programs of `cluster_config` instead of the
+    /// mainnet ones. The API client should point at an API listing pools of
+    /// the same programs, e.g. `https://api-v3-devnet.raydium.io` for devnet.
+    pub fn with_cluster_config(mut self, cluster_config: ClusterConfig) -> Self {
+        self.cluster_config = cluster_config;
+        self
+    }
+
+    pub fn cluster_config(&self) -> &ClusterConfig {
+        &self.cluster_config
+    }
+
+    /// CLMM program of the cluster.
+    fn clmm_program_id(&self) -> solana_pubkey::Pubkey {
+        self.cluster_config.clmm_program_id()
+    }
+
+    /// Bitmap extension of the CLMM pool `pool_id` of the cluster.
+    fn tick_array_bitmap_extension_key(&self, pool_id: &Pubkey) -> Pubkey {
+        Pubkey::from(
+            derive_tick_array_bitmap_extension_key(
+                &solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+                &self.clmm_program_id(),
+            )
+            .to_bytes(),
+        )
+    }
+
+    pub fn request_log(&self) -> Option<&RequestLog> {
+        self.request_log.as_ref()
+    }
+
+    /// Prepend compute budget instructions setting `priority_fee` to the
+    /// swaps sent by `swap_amm`, `swap_clmm` and their variants.
+    pub fn with_priority_fee(mut self, priority_fee: PriorityFeeConfig) -> Self {
+        self.priority_fee = Some(priority_fee);
+        self
+    }
+
+    pub fn priority_fee(&self) -> Option<&PriorityFeeConfig> {
+        self.priority_fee.as_ref()
+    }
+
+    /// `ix` preceded by the compute budget instructions of `priority_fee`,
+    /// for a transaction trading `trade_value_lamports`.
+    async fn with_compute_budget(
         &self,
-        path: Option<&str>,
-        query: Option<&[(&str, &str)]>,
-    ) -> anyhow::Result<T> {
-        let url = format!("{}{}", self.base_url, path.unwrap_or_default());
+        ix: &[Instruction],
+        priority_fee: Option<&PriorityFeeConfig>,
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let mut instructions = match priority_fee {
+            Some(priority_fee) => {
+                priority_fee
+                    .instructions(&self.rpc_client, ix, trade_value_lamports)
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        instructions.extend_from_slice(ix);
+        Ok(instructions)
+    }
 
-        let resp = self
-            .reqwest_client
-            .get(&url)
-            .query(query.unwrap_or(&[]))
-            .send()
-            .await
-            .with_context(|| format!("Raydium AMM GET failed for {}", url))?;
+    /// `get_multiple_accounts` retried under the client's retry policy.
+    pub(crate) async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> anyhow::Result<Vec<Option<Account>>> {
+        Ok(self
+            .retry_policy
+            .retry(
+                move || self.rpc_client.get_multiple_accounts(pubkeys),
+                is_transient_client_error,
+            )
+            .await?)
+    }
 
-        let status = resp.status();
-        let body = resp
-            .text()
-            .await
-            .with_context(|| format!("Failed to read response body from {}", url))?;
+    /// [`Self::get_multiple_accounts`] of any number of accounts, read
+    /// concurrently in chunks of [`MAX_MULTIPLE_ACCOUNTS`].
+    async fn get_all_accounts(&self, pubkeys: &[Pubkey]) -> anyhow::Result<Vec<Option<Account>>> {
+        let chunks = try_join_all(
+            pubkeys
+                .chunks(MAX_MULTIPLE_ACCOUNTS)
+                .map(|chunk| self.get_multiple_accounts(chunk)),
+        )
+        .await?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
 
-        if !status.is_success() {
-            error!("Raydium non-200 {} for {}. Body: {}", status, url, body);
-            anyhow::bail!("Raydium non-200 {} for {}", status, url);
+    /// Same as [`Self::get_multiple_accounts`], through the account cache if
+    /// set.
+    async fn get_cached_accounts(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> anyhow::Result<Vec<Option<Account>>> {
+        match &self.account_cache {
+            Some(cache) => {
+                cache
+                    .get_multiple_accounts_with(pubkeys, |missing| async move {
+                        let commitment = self.rpc_client.commitment();
+                        Ok(self
+                            .retry_policy
+                            .retry(
+                                || {
+                                    self.rpc_client
+                                        .get_multiple_accounts_with_commitment(&missing, commitment)
+                                },
+                                is_transient_client_error,
+                            )
+                            .await?)
+                    })
+                    .await
+            }
+            None => self.get_multiple_accounts(pubkeys).await,
         }
+    }
 
-        debug!("Raydium response body for {}: {}", url, body);
+    /// Anchor account at `address`, through the account cache if set.
+    async fn get_cached_anchor_account<T: AccountDeserialize>(
+        &self,
+        address: &Pubkey,
+    ) -> anyhow::Result<Option<T>> {
+        if self.account_cache.is_none() {
+            return rpc::get_anchor_account::<T>(&self.rpc_client, address).await;
+        }
+        let account = self
+            .get_cached_accounts(std::slice::from_ref(address))
+            .await?
+            .pop()
+            .flatten();
+        account
+            .map(|account| Ok(T::try_deserialize(&mut account.data.as_slice())?))
+            .transpose()
+    }
 
-        let parsed: T = serde_json::from_str(&body).with_context(|| {
-            format!(
-                "Failed to parse Raydium response as JSON. Status: {}, Body: {}",
-                status, body
+    /// Latest blockhash, retried under the client's retry policy.
+    pub(crate) async fn get_latest_blockhash(&self) -> anyhow::Result<Hash> {
+        Ok(self
+            .retry_policy
+            .retry(
+                move || self.rpc_client.get_latest_blockhash(),
+                is_transient_client_error,
             )
-        })?;
-
-        Ok(parsed)
+            .await?)
     }
 
     pub fn owner_pubkey(&self) -> Pubkey {
         self.owner.pubkey()
     }
 
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    /// Fetch USD prices of the given mints via HTTP API. Mints without a
+    /// price are left out of the result.
+    pub async fn fetch_mint_prices(
+        &self,
+        mints: &[String],
+    ) -> anyhow::Result<HashMap<String, f64>> {
+        let mut prices = HashMap::new();
+        // Keep the query string short for large wallets.
+        for chunk in mints.chunks(50) {
+            let resp = self.api_client.mint_price(chunk).await?;
+            for (mint, price) in resp.data {
+                let price = match price {
+                    Value::String(price) => price.parse::<f64>().ok(),
+                    Value::Number(price) => price.as_f64(),
+                    _ => None,
+                };
+                if let Some(price) = price {
+                    prices.insert(mint, price);
+                }
+            }
+        }
+        Ok(prices)
+    }
+
+    /// Fetch the metadata of the given mints via HTTP API. Mints the API
+    /// does not list are left out of the result.
+    pub async fn fetch_mint_info(&self, mints: &[String]) -> anyhow::Result<HashMap<String, Mint>> {
+        let mut info = HashMap::new();
+        for chunk in mints.chunks(50) {
+            let resp = self.api_client.mint_info_by_ids(chunk).await?;
+            for mint in resp.data.into_iter().flatten() {
+                info.insert(mint.address.clone(), mint);
+            }
+        }
+        Ok(info)
+    }
+
     /// Fetch raw pool account keys by pool ID via HTTP API.
     pub async fn fetch_pools_keys_by_id<T: DeserializeOwned + Clone>(
         &self,
         id: &Pubkey,
     ) -> anyhow::Result<PoolKeys<T>> {
-        let id = id.to_string();
-        let headers = ("ids", id.as_str());
-        let resp: PoolKeys<T> = self.get(Some("/pools/key/ids"), Some(&[headers])).await?;
-        Ok(resp)
+        self.api_client.pools_key_by_ids(&[id.to_string()]).await
     }
 
-    /// Retrieve on‑chain reserves for a given pool account.
+    /// Retrieve on‑chain reserves for a given pool account, with its vault
+    /// mints, decimals and open time. Vaults may be Token-2022 accounts.
     ///
     /// # Errors
     /// Returns an error if the account data cannot be deserialized.
     pub async fn get_rpc_pool_info(&self, pool_id: &Pubkey) -> anyhow::Result<RpcPoolInfo> {
+        let (_, rpc_pool_info) = self.get_rpc_pool_info_with_state(pool_id).await?;
+        Ok(rpc_pool_info)
+    }
+
+    /// Fetch and decode the AMM v4 pool account.
+    pub async fn get_amm_pool_state(
+        &self,
+        pool_id: &Pubkey,
+    ) -> anyhow::Result<LiquidityStateLayoutV4> {
         let account = self.rpc_client.get_account(pool_id).await?;
-        let data = account.data;
-        let market_state = LiquidityStateLayoutV4::try_from_slice(&data)
-            .map_err(|e| anyhow!("Failed to decode market state: {:?}", e))?;
-        debug!("Market state {:?}", market_state);
-        let mint1_account_data = self
-            .rpc_client
-            .get_account_with_commitment(&market_state.base_vault, CommitmentConfig::confirmed())
-            .await?
-            .value
-            .ok_or(anyhow!("mint1 Account Data Value not found"))?;
-        let mint2_account_data = self
-            .rpc_client
+        LiquidityStateLayoutV4::try_from_slice(&account.data)
+            .map_err(|e| anyhow!("Failed to decode market state: {:?}", e))
+    }
+
+    /// Status and admin fields of the AMM v4 pool, see [`AmmFlags`].
+    pub async fn get_amm_flags(&self, pool_id: &Pubkey) -> anyhow::Result<AmmFlags> {
+        Ok(self.get_amm_pool_state(pool_id).await?.flags())
+    }
+
+    /// Check that the AMM v4 pool `pool_id` accepts swaps by its status and,
+    /// on the local clock, its open time. The swap methods run it before
+    /// sending; see [`AmmFlags::check_swap_enabled`] for the errors.
+    pub async fn check_amm_swap_enabled(&self, pool_id: &Pubkey) -> anyhow::Result<()> {
+        let flags = self.get_amm_flags(pool_id).await?;
+        flags.check_swap_enabled(pool_id, unix_timestamp()?)?;
+        Ok(())
+    }
+
+    /// Account keys of the AMM v4 pool `pool_id`, as listed by
+    /// [`Self::fetch_pools_keys_by_id`], read from the chain for pools the
+    /// API does not list yet.
+    ///
+    /// Reads the pool, its OpenBook market and both mints. The mints are
+    /// left without symbol, name or logo, and the pool without lookup table.
+    pub async fn derive_amm_pool_keys(&self, pool_id: &Pubkey) -> anyhow::Result<AmmPool> {
+        let state = self.get_amm_pool_state(pool_id).await?;
+        let accounts = self
+            .get_multiple_accounts(&[state.market_id, state.base_mint, state.quote_mint])
+            .await?;
+        let [market, base_mint, quote_mint]: [Option<Account>; 3] = accounts
+            .try_into()
+            .map_err(|_| anyhow!("Unexpected number of accounts for AMM pool {pool_id}"))?;
+        let market = market
+            .ok_or_else(|| anyhow!("Market {} of AMM pool {pool_id} not found", state.market_id))?;
+        let market = OpenBookMarket::try_from_account_data(&market.data)?;
+
+        let mint = |address: &Pubkey, account: Option<Account>, decimals: u64| {
+            let account =
+                account.ok_or_else(|| anyhow!("Mint {address} of AMM pool {pool_id} not found"))?;
+            Ok::<_, anyhow::Error>(Mint {
+                chain_id: 101,
+                address: address.to_string(),
+                program_id: account.owner.to_string(),
+                logo_uri: None,
+                symbol: String::new(),
+                name: String::new(),
+                decimals: decimals as u32,
+                tags: Vec::new(),
+                extensions: MintExtensions::default(),
+            })
+        };
+
+        Ok(AmmPool {
+            program_id: self.cluster_config.amm_v4.to_string(),
+            id: pool_id.to_string(),
+            mint_a: mint(&state.base_mint, base_mint, state.base_decimal)?,
+            mint_b: mint(&state.quote_mint, quote_mint, state.quote_decimal)?,
+            lookup_table_account: None,
+            open_time: state.pool_open_time.to_string(),
+            vault: Vault {
+                a: state.base_vault.to_string(),
+                b: state.quote_vault.to_string(),
+            },
+            authority: amm_authority(state.nonce, &self.cluster_config.amm_v4)?.to_string(),
+            open_orders: state.open_orders.to_string(),
+            target_orders: state.target_orders.to_string(),
+            market_program_id: state.market_program_id.to_string(),
+            market_id: state.market_id.to_string(),
+            market_authority: market
+                .vault_signer(&state.market_id, &state.market_program_id)?
+                .to_string(),
+            market_base_vault: market.base_vault.to_string(),
+            market_quote_vault: market.quote_vault.to_string(),
+            market_bids: market.bids.to_string(),
+            market_asks: market.asks.to_string(),
+            market_event_queue: market.event_queue.to_string(),
+        })
+    }
+
+    /// Same as [`Self::get_rpc_pool_info`], but also returns the decoded pool
+    /// state the reserves were derived from.
+    pub async fn get_rpc_pool_info_with_state(
+        &self,
+        pool_id: &Pubkey,
+    ) -> anyhow::Result<(LiquidityStateLayoutV4, RpcPoolInfo)> {
+        let market_state = self.get_amm_pool_state(pool_id).await?;
+        debug!("Market state {:?}", market_state);
+        let mint1_account_data = self
+            .rpc_client
+            .get_account_with_commitment(&market_state.base_vault, CommitmentConfig::confirmed())
+            .await?
+            .value
+            .ok_or(anyhow!("mint1 Account Data Value not found"))?;
+        let mint2_account_data = self
+            .rpc_client
             .get_account_with_commitment(&market_state.quote_vault, CommitmentConfig::confirmed())
             .await?
             .value
             .ok_or(anyhow!("mint2 Account Data Value not found"))?;
 
-        let mint_1_layout = AccountLayout::try_from_slice(&mint1_account_data.data)?;
-        let mint_2_layout = AccountLayout::try_from_slice(&mint2_account_data.data)?;
-        let base_reserve = mint_1_layout.amount - market_state.base_need_take_pnl;
-        let quote_reserve = mint_2_layout.amount - market_state.quote_need_take_pnl;
-        Ok(RpcPoolInfo {
-            base_reserve,
-            quote_reserve,
-        })
+        let rpc_pool_info =
+            RpcPoolInfo::from_accounts(&market_state, &mint1_account_data, &mint2_account_data)?;
+        Ok((market_state, rpc_pool_info))
     }
 
     /// Fetch pool metadata (price, TVL, stats) by ID via HTTP API.
     pub async fn fetch_pool_by_id(&self, id: &Pubkey) -> anyhow::Result<ClmmSinglePoolInfo> {
-        let id = id.to_string();
-        let headers = ("ids", id.as_str());
-        let resp: ClmmSinglePoolInfo = self.get(Some("/pools/info/ids"), Some(&[headers])).await?;
-        Ok(resp)
+        self.api_client.pools_info_by_ids(&[id.to_string()]).await
     }
 
     /// List pools for the given pair via HTTP API.
@@ -292,49 +1401,150 @@ impl AmmSwapClient {
         pool_sort_field: Option<&str>,
         sort_type: Option<&str>,
     ) -> anyhow::Result<Vec<ClmmPool>> {
-        let page_size_str = page_size.unwrap_or(100).to_string();
-        let page_str = page.unwrap_or(1).to_string();
-        let pool_type_str = pool_type.to_string();
-        let pool_sort_field = pool_sort_field.unwrap_or("default");
-        let sort_type = sort_type.unwrap_or("desc");
-        let headers = [
-            ("mint1", mint_a),
-            ("mint2", mint_b),
-            ("poolType", pool_type_str.as_str()),
-            ("poolSortField", pool_sort_field),
-            ("sortType", sort_type),
-            ("pageSize", page_size_str.as_str()),
-            ("page", page_str.as_str()),
-        ];
-        let resp: ClmmPoolInfosResponse =
-            self.get(Some("/pools/info/mint"), Some(&headers)).await?;
+        self.fetch_pool_info_for_programs(
+            mint_a,
+            mint_b,
+            pool_type,
+            page_size,
+            page,
+            pool_sort_field,
+            sort_type,
+            &[
+                &self.cluster_config.amm_v4.to_string(),
+                &self.cluster_config.clmm.to_string(),
+            ],
+        )
+        .await
+    }
+
+    /// Same as [`Self::fetch_pool_info`], keeping pools owned by any of
+    /// `program_ids` (e.g. `CPMM` pools for routing).
+    pub async fn fetch_pool_info_for_programs(
+        &self,
+        mint_a: &str,
+        mint_b: &str,
+        pool_type: &PoolType,
+        page_size: Option<u32>,
+        page: Option<u32>,
+        pool_sort_field: Option<&str>,
+        sort_type: Option<&str>,
+        program_ids: &[&str],
+    ) -> anyhow::Result<Vec<ClmmPool>> {
+        let query = PoolQuery {
+            pool_type: pool_type.to_string(),
+            pool_sort_field: pool_sort_field.unwrap_or("default").to_string(),
+            sort_type: sort_type.unwrap_or("desc").to_string(),
+            page_size: page_size.unwrap_or(100),
+            page: page.unwrap_or(1),
+        };
+        let (pools, _) = self
+            .fetch_pool_page(mint_a, mint_b, &query, program_ids)
+            .await?;
+        Ok(pools)
+    }
+
+    /// All pools of the pair, following the pages of [`Self::fetch_pool_info`]
+    /// until the last one.
+    pub async fn fetch_all_pools(
+        &self,
+        mint_a: &str,
+        mint_b: &str,
+        pool_type: &PoolType,
+    ) -> anyhow::Result<Vec<ClmmPool>> {
+        self.pool_stream(mint_a, mint_b, pool_type)
+            .try_collect()
+            .await
+    }
+
+    /// Same as [`Self::fetch_all_pools`], as a stream fetching the next page
+    /// once the pools of the previous one are consumed.
+    pub fn pool_stream<'a>(
+        &'a self,
+        mint_a: &'a str,
+        mint_b: &'a str,
+        pool_type: &PoolType,
+    ) -> impl Stream<Item = anyhow::Result<ClmmPool>> + 'a {
+        let query = PoolQuery {
+            pool_type: pool_type.to_string(),
+            ..Default::default()
+        };
+        stream::try_unfold(Some(query), move |query| async move {
+            let Some(mut query) = query else {
+                return Ok(None);
+            };
+            let (amm_v4, clmm) = (
+                self.cluster_config.amm_v4.to_string(),
+                self.cluster_config.clmm.to_string(),
+            );
+            let (pools, has_next_page) = self
+                .fetch_pool_page(mint_a, mint_b, &query, &[&amm_v4, &clmm])
+                .await?;
+            query.page += 1;
+            Ok::<_, anyhow::Error>(Some((pools, has_next_page.then_some(query))))
+        })
+        .map_ok(|pools| stream::iter(pools.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// Pools of the pair owned by the programs of the cluster, read from the
+    /// chain instead of the API, which lists new pools late. See
+    /// [`crate::router::discover_pools_onchain`].
+    pub async fn discover_pools_onchain(
+        &self,
+        mint_a: &Pubkey,
+        mint_b: &Pubkey,
+        pool_type: &PoolType,
+    ) -> anyhow::Result<Vec<DiscoveredPool>> {
+        crate::router::discover_pools_onchain(
+            &self.rpc_client,
+            &self.cluster_config,
+            mint_a,
+            mint_b,
+            pool_type,
+        )
+        .await
+    }
+
+    /// Pools of the page `query` owned by one of `program_ids`, and whether
+    /// there is a next page.
+    async fn fetch_pool_page(
+        &self,
+        mint_a: &str,
+        mint_b: &str,
+        query: &PoolQuery,
+        program_ids: &[&str],
+    ) -> anyhow::Result<(Vec<ClmmPool>, bool)> {
+        let resp = self
+            .api_client
+            .pools_info_by_mint(mint_a, Some(mint_b), query)
+            .await?;
         let mut parsed_pools = Vec::new();
-        for pool in &resp.data.data {
-            match serde_json::from_value::<ClmmPool>(pool.clone()) {
+        for pool in resp.data.data {
+            match pool {
                 Ok(parsed_pool) => parsed_pools.push(parsed_pool),
                 Err(e) => {
-                    warn!(
-                        "Encountered non amm/clmm pool: error={}, programId={:?}",
-                        e,
-                        pool.get("id")
-                    );
+                    warn!("Encountered non amm/clmm pool: {}", e);
                     continue;
                 }
             }
         }
 
-        // Filter pools to only have program_id = AMM and CLMM
+        // Filter pools to only have the requested program ids
         let filtered_pools = parsed_pools
             .iter()
-            .filter(|pool| pool.program_id == AMM_V4 || pool.program_id == CLMM)
+            .filter(|pool| program_ids.contains(&pool.program_id.as_str()))
             .cloned()
             .collect();
 
-        Ok(filtered_pools)
+        Ok((filtered_pools, resp.data.has_next_page))
     }
 
     /// Compute a swap quote (amount out, fee, slippage).
     ///
+    /// Always sells the base reserve of `rpc_pool_info`, with the decimals of
+    /// token A of `pool_info`, for the quote reserve. Use
+    /// [`Self::compute_amount_out_for_input`] to sell either mint.
+    ///
     /// # Arguments
     ///
     /// - `rpc_pool_info`: on‑chain reserves.
@@ -348,50 +1558,16 @@ impl AmmSwapClient {
         amount_in: u64,
         slippage: f64,
     ) -> anyhow::Result<ComputeAmountOutResult> {
-        let reserve_in = rpc_pool_info.base_reserve;
-        let reserve_out = rpc_pool_info.quote_reserve;
-        debug!("Reserve out: {}", reserve_out);
-        debug!("Reserve in: {}", reserve_in);
-
-        let mint_in_decimals = pool_info.mint_a.decimals;
-        let mint_out_decimals = pool_info.mint_b.decimals;
-
-        let div_in = 10u128.pow(mint_in_decimals);
-        let div_out = 10u128.pow(mint_out_decimals);
-
-        let reserve_in_f = reserve_in as f64 / div_in as f64;
-        let reserve_out_f = reserve_out as f64 / div_out as f64;
-
-        // ------- Current price calculation ---------
-        let current_price = reserve_out_f / reserve_in_f;
-        debug!("Current price {}", current_price);
-
-        // ------- Amount + Fee calculation --------
-        let fee = amount_in
-            .saturating_mul(LIQUIDITY_FEES_NUMERATOR)
-            .div_ceil(LIQUIDITY_FEES_DENOMINATOR);
-        let amount_in_with_fee = amount_in.saturating_sub(fee);
-        let denominator = reserve_in.saturating_add(amount_in_with_fee);
-        let amount_out_raw = reserve_out.saturating_mul(amount_in_with_fee) / denominator;
-
-        let min_amount_out = ((amount_out_raw as f64) * (1.0 - slippage)).floor() as u64;
-
-        let exec_out_f = min_amount_out as f64 / div_out as f64;
-        let exec_in_f = amount_in.saturating_sub(fee) as f64 / div_in as f64;
-        let execution_price = exec_out_f / exec_in_f;
-
-        let price_impact = (current_price - execution_price) / current_price * 100.0;
-
-        debug!("Price impact {price_impact}");
-
-        Ok(ComputeAmountOutResult {
-            amount_out: amount_out_raw,
-            min_amount_out,
-            current_price,
-            execution_price,
-            price_impact,
-            fee,
-        })
+        validation::validate_amount(amount_in)?;
+        validation::validate_slippage(slippage)?;
+        validation::validate_distinct_mints(&pool_info.mint_a.address, &pool_info.mint_b.address)?;
+        Ok(amm_amount_out(
+            rpc_pool_info,
+            pool_info.mint_a.decimals,
+            pool_info.mint_b.decimals,
+            amount_in,
+            slippage,
+        ))
     }
 
     /// Compute the required swap input (amount in, fee, slippage).
@@ -399,7 +1575,8 @@ impl AmmSwapClient {
     /// This is the inverse of [`compute_amount_out`]: it finds the smallest
     /// input amount such that the pool would output at least `amount_out`
     /// (before applying the slippage tolerance), using the same constant
-    /// product curve and on-chain fee logic.
+    /// product curve and on-chain fee logic. Like it, always buys the quote
+    /// reserve; use [`Self::compute_amount_in_for_output`] to buy either mint.
     ///
     /// # Arguments
     ///
@@ -417,9 +1594,9 @@ impl AmmSwapClient {
         let reserve_in = rpc_pool_info.base_reserve;
         let reserve_out = rpc_pool_info.quote_reserve;
 
-        if amount_out == 0 {
-            return Err(anyhow!("amount_out must be greater than zero"));
-        }
+        validation::validate_amount(amount_out)?;
+        validation::validate_slippage(slippage)?;
+        validation::validate_distinct_mints(&pool_info.mint_a.address, &pool_info.mint_b.address)?;
 
         if amount_out >= reserve_out {
             return Err(anyhow!(
@@ -466,9 +1643,7 @@ impl AmmSwapClient {
             }
         }
 
-        let fee = required_in
-            .saturating_mul(LIQUIDITY_FEES_NUMERATOR)
-            .div_ceil(LIQUIDITY_FEES_DENOMINATOR);
+        let fee = rpc_pool_info.swap_fee_amount(required_in);
         let amount_in_with_fee = required_in.saturating_sub(fee);
 
         let mint_in_decimals = pool_info.mint_a.decimals;
@@ -490,7 +1665,7 @@ impl AmmSwapClient {
 
         let price_impact = (current_price - execution_price) / current_price * 100.0;
 
-        let max_amount_in = ((required_in as f64) * (1.0 + slippage)).ceil() as u64;
+        let max_amount_in = preview_max_in(required_in, slippage);
 
         Ok(ComputeAmountInResult {
             amount_in: required_in,
@@ -499,12 +1674,278 @@ impl AmmSwapClient {
             execution_price,
             price_impact,
             fee,
+            fee_tier: rpc_pool_info.fee_tier(),
         })
     }
 
-    pub async fn get_or_create_token_program(&self, mint: &Pubkey) -> anyhow::Result<Pubkey> {
-        let associated_token_account =
-            spl_associated_token_account::get_associated_token_address(&self.owner.pubkey(), mint);
+    /// Same as [`Self::compute_amount_out`] for selling `input_mint`, either
+    /// mint of the pool. The reserves are taken from the vault of
+    /// `input_mint` and the other one, and the decimals swapped, whatever
+    /// the order of the mints in `pool_info`.
+    pub fn compute_amount_out_for_input(
+        &self,
+        rpc_pool_info: &RpcPoolInfo,
+        pool_info: &ClmmPool,
+        input_mint: &str,
+        amount_in: u64,
+        slippage: f64,
+    ) -> anyhow::Result<ComputeAmountOutResult> {
+        let (rpc_pool_info, pool_info) =
+            orient_amm_pool(rpc_pool_info.clone(), pool_info, input_mint)?;
+        self.compute_amount_out(&rpc_pool_info, &pool_info, amount_in, slippage)
+    }
+
+    /// Same as [`Self::compute_amount_in`] for receiving `output_mint`,
+    /// either mint of the pool, oriented like
+    /// [`Self::compute_amount_out_for_input`].
+    pub fn compute_amount_in_for_output(
+        &self,
+        rpc_pool_info: &RpcPoolInfo,
+        pool_info: &ClmmPool,
+        output_mint: &str,
+        amount_out: u64,
+        slippage: f64,
+    ) -> anyhow::Result<ComputeAmountInResult> {
+        validation::validate_pool_mint(pool_info, output_mint)?;
+        let input_mint = if pool_info.mint_b.address == output_mint {
+            &pool_info.mint_a.address
+        } else {
+            &pool_info.mint_b.address
+        };
+        let (rpc_pool_info, pool_info) =
+            orient_amm_pool(rpc_pool_info.clone(), pool_info, input_mint)?;
+        self.compute_amount_in(&rpc_pool_info, &pool_info, amount_out, slippage)
+    }
+
+    /// SPL Token or Token-2022 program owning `mint`.
+    pub async fn get_mint_token_program(&self, mint: &Pubkey) -> anyhow::Result<Pubkey> {
+        if *mint == spl_token::native_mint::id() {
+            return Ok(spl_token::id());
+        }
+        let account = self
+            .retry_policy
+            .retry(
+                move || self.rpc_client.get_account(mint),
+                is_transient_client_error,
+            )
+            .await?;
+        let token_2022 = Pubkey::from(spl_token_2022::id().to_bytes());
+        if account.owner != spl_token::id() && account.owner != token_2022 {
+            return Err(anyhow!(
+                "{mint} is not a token mint, it is owned by {}",
+                account.owner
+            ));
+        }
+        Ok(account.owner)
+    }
+
+    /// SPL Token and Token-2022 accounts of `owner`, empty ones included,
+    /// with the metadata of their mints from the Raydium API. Balances are
+    /// still returned, without metadata, when the API cannot be reached.
+    pub async fn get_token_balances(&self, owner: &Pubkey) -> anyhow::Result<Vec<TokenBalance>> {
+        let mut balances = Vec::new();
+        let token_programs = [
+            spl_token::id(),
+            Pubkey::from(spl_token_2022::id().to_bytes()),
+        ];
+        for token_program in token_programs {
+            let accounts = self
+                .retry_policy
+                .retry(
+                    move || {
+                        self.rpc_client.get_token_accounts_by_owner(
+                            owner,
+                            TokenAccountsFilter::ProgramId(token_program),
+                        )
+                    },
+                    is_transient_client_error,
+                )
+                .await?;
+            for keyed_account in accounts {
+                // `jsonParsed` token account layout.
+                let data = serde_json::to_value(&keyed_account.account.data)?;
+                let info = &data["parsed"]["info"];
+                let (Some(mint), Some(amount), Some(decimals)) = (
+                    info["mint"].as_str(),
+                    info["tokenAmount"]["amount"].as_str(),
+                    info["tokenAmount"]["decimals"].as_u64(),
+                ) else {
+                    warn!("Skipping unparsable token account {}", keyed_account.pubkey);
+                    continue;
+                };
+                balances.push(TokenBalance {
+                    token_account: keyed_account.pubkey.parse()?,
+                    mint: mint.parse()?,
+                    token_program,
+                    amount: amount.parse()?,
+                    decimals: decimals as u8,
+                    metadata: None,
+                });
+            }
+        }
+
+        let mut mints: Vec<String> = balances.iter().map(|b| b.mint.to_string()).collect();
+        mints.sort();
+        mints.dedup();
+        match self.fetch_mint_info(&mints).await {
+            Ok(metadata) => {
+                for balance in &mut balances {
+                    balance.metadata = metadata.get(&balance.mint.to_string()).cloned();
+                }
+            }
+            Err(e) => warn!("Failed to fetch the metadata of mints: {e:?}"),
+        }
+        Ok(balances)
+    }
+
+    /// Balance of the owner's associated token account of `mint`, in its
+    /// smallest units, 0 when the account does not exist. For the native
+    /// mint, this is the wrapped SOL balance, not the lamports of the owner.
+    pub async fn get_balance(&self, mint: &Pubkey) -> anyhow::Result<u64> {
+        let token_program = self.get_mint_token_program(mint).await?;
+        let owner = self.owner.pubkey();
+        let address = get_associated_token_address_with_program_id(&owner, mint, &token_program);
+        let account = self
+            .get_multiple_accounts(&[address])
+            .await?
+            .pop()
+            .flatten();
+        match account {
+            Some(account) => Ok(TokenAccountInfo::unpack(&account.owner, &account.data)?.amount),
+            None => Ok(0),
+        }
+    }
+
+    /// Check the owner can pay for the swap transaction made of
+    /// `instructions`, compute budget aside, before it is sent.
+    ///
+    /// The input account must hold `funding.amount_in`, counting the
+    /// lamports wrapped into it, and the output account must exist or be
+    /// created by `instructions`. The owner's lamports must cover the
+    /// [`SwapLamports`]: the wrapped lamports, the rent of the associated
+    /// token accounts the instructions create that do not exist yet, the
+    /// owner's own rent-exempt minimum, the signature fee and the highest fee
+    /// under `priority_fee` for a trade of
+    /// [`SwapFunding::trade_value_lamports`]. Fails with
+    /// `SwapError::InsufficientFunds` or `SwapError::OutputAccountMissing`
+    /// otherwise, rather than on chain.
+    ///
+    /// Everything is read in one request, rent from the `Rent` sysvar.
+    pub async fn check_swap_funds(
+        &self,
+        funding: &SwapFunding,
+        instructions: &[Instruction],
+        priority_fee: Option<&PriorityFeeConfig>,
+    ) -> anyhow::Result<()> {
+        let owner = self.owner.pubkey();
+        let created = created_token_accounts(instructions);
+        let token_2022_mints: Vec<Pubkey> = created
+            .iter()
+            .filter(|created| created.token_program != spl_token::id())
+            .map(|created| created.mint)
+            .collect();
+        let mut pubkeys = vec![
+            owner,
+            sysvar::rent::id(),
+            funding.input_account,
+            funding.output_account,
+        ];
+        pubkeys.extend(created.iter().map(|created| created.address));
+        pubkeys.extend(&token_2022_mints);
+        let accounts = self.get_multiple_accounts(&pubkeys).await?;
+        let (fixed, rest) = accounts.split_at(4);
+        let (created_accounts, token_2022_mint_accounts) = rest.split_at(created.len());
+
+        let held = match &fixed[2] {
+            Some(account) => TokenAccountInfo::unpack(&account.owner, &account.data)?.amount,
+            None => 0,
+        };
+        let available = held.saturating_add(funding.wrapped_lamports);
+        if available < funding.amount_in {
+            return Err(SwapError::InsufficientFunds {
+                needed: funding.amount_in,
+                available,
+                reason: InsufficientFundsReason::InputToken {
+                    mint: funding.mint_in,
+                    account: funding.input_account,
+                },
+            }
+            .into());
+        }
+        if fixed[3].is_none()
+            && created
+                .iter()
+                .all(|created| created.address != funding.output_account)
+        {
+            return Err(SwapError::OutputAccountMissing {
+                account: funding.output_account,
+            }
+            .into());
+        }
+
+        let mut account_lens = Vec::new();
+        for (created, _) in created
+            .iter()
+            .zip(created_accounts)
+            .filter(|(_, account)| account.is_none())
+        {
+            if created.token_program == spl_token::id() {
+                account_lens.push(spl_token::state::Account::LEN);
+                continue;
+            }
+            let mint_account = token_2022_mints
+                .iter()
+                .position(|mint| *mint == created.mint)
+                .and_then(|index| token_2022_mint_accounts[index].as_ref())
+                .ok_or(anyhow!(
+                    "Mint {} of a created token account not found",
+                    created.mint
+                ))?;
+            account_lens.push(token_2022_account_len(&mint_account.data)?);
+        }
+        let rent_account = fixed[1].as_ref().ok_or(anyhow!("Rent sysvar not found"))?;
+        let rent: Rent = bincode::deserialize(&rent_account.data)?;
+        let lamports = SwapLamports::new(
+            funding.wrapped_lamports,
+            &rent,
+            &account_lens,
+            swap_fee_lamports(priority_fee, instructions, funding.trade_value_lamports()),
+        );
+
+        let needed = lamports.needed();
+        let available = fixed[0].as_ref().map_or(0, |account| account.lamports);
+        if available < needed {
+            return Err(SwapError::InsufficientFunds {
+                needed,
+                available,
+                reason: InsufficientFundsReason::Lamports {
+                    wrapped: lamports.wrapped,
+                    rent: lamports.rent,
+                    fees: lamports.fees,
+                },
+            }
+            .into());
+        }
+        debug!(
+            "Owner {owner} holds {held} of {} and {available} lamports for {needed} needed",
+            funding.mint_in
+        );
+        Ok(())
+    }
+
+    /// Find the owner's associated token account for `mint`, under the SPL
+    /// Token or Token-2022 program owning the mint, creating it when missing.
+    /// A new wSOL account is funded with its rent-exempt minimum, wrapped.
+    pub async fn get_or_create_token_program(
+        &self,
+        mint: &Pubkey,
+    ) -> anyhow::Result<TokenAccountSetup> {
+        let token_program = self.get_mint_token_program(mint).await?;
+        let associated_token_account = get_associated_token_address_with_program_id(
+            &self.owner.pubkey(),
+            mint,
+            &token_program,
+        );
         let balance = self
             .rpc_client
             .get_token_account_balance(&associated_token_account)
@@ -515,7 +1956,12 @@ impl AmmSwapClient {
                     "Address {:?}, balance {:?}",
                     associated_token_account, balance
                 );
-                return Ok(associated_token_account);
+                Ok(TokenAccountSetup {
+                    address: associated_token_account,
+                    token_program,
+                    created: false,
+                    wrapped_lamports: 0,
+                })
             }
             Err(e) => {
                 warn!(
@@ -527,14 +1973,14 @@ impl AmmSwapClient {
                         &self.owner.pubkey(),
                         &self.owner.pubkey(),
                         mint,
-                        // Could be a potential bug with the spl_token2022 ata
-                        &spl_token::id(),
+                        &token_program,
                     ),
                 ];
 
                 // For the native SOL mint, optionally wrap lamports into wSOL by
                 // transferring lamports and calling `sync_native`. For arbitrary SPL
                 // mints we only create the associated token account.
+                let mut wrapped_lamports = 0;
                 if *mint == spl_token::native_mint::id() {
                     let amount_to_wrap = self
                         .rpc_client
@@ -545,6 +1991,7 @@ impl AmmSwapClient {
                         &associated_token_account,
                         amount_to_wrap,
                     ));
+                    wrapped_lamports = amount_to_wrap;
                     instructions.push(spl_token::instruction::sync_native(
                         &spl_token::id(),
                         &associated_token_account,
@@ -552,16 +1999,13 @@ impl AmmSwapClient {
                 }
 
                 let recent_blockhash: solana_sdk::hash::Hash =
-                    self.rpc_client.get_latest_blockhash().await?;
-                let transaction = Transaction::new_signed_with_payer(
-                    &instructions,
-                    Some(&self.owner.pubkey()),
-                    &[&self.owner],
-                    recent_blockhash,
-                );
+                    self.get_latest_blockhash().await?;
                 let sig = self
-                    .rpc_client
-                    .send_and_confirm_transaction_with_spinner(&transaction)
+                    .send_message(VersionedMessage::Legacy(Message::new_with_blockhash(
+                        &instructions,
+                        Some(&self.owner.pubkey()),
+                        &recent_blockhash,
+                    )))
                     .await?;
 
                 if *mint == spl_token::native_mint::id() {
@@ -569,12 +2013,90 @@ impl AmmSwapClient {
                 } else {
                     info!("Created associated token account {:?}", sig);
                 }
+                Ok(TokenAccountSetup {
+                    address: associated_token_account,
+                    token_program,
+                    created: true,
+                    wrapped_lamports,
+                })
             }
         }
+    }
+
+    /// Instructions wrapping exactly `lamports` into the owner's wSOL
+    /// account, created idempotently first.
+    pub fn wrap_sol_instructions(&self, lamports: u64) -> anyhow::Result<Vec<Instruction>> {
+        let owner = self.owner.pubkey();
+        let native_mint = spl_token::native_mint::id();
+        let wsol_account =
+            get_associated_token_address_with_program_id(&owner, &native_mint, &spl_token::id());
+        Ok(vec![
+            create_associated_token_account_idempotent(
+                &owner,
+                &owner,
+                &native_mint,
+                &spl_token::id(),
+            ),
+            transfer(&owner, &wsol_account, lamports),
+            spl_token::instruction::sync_native(&spl_token::id(), &wsol_account)?,
+        ])
+    }
+
+    /// Instruction closing the owner's wSOL account, returning its whole
+    /// balance and its rent to the owner as SOL.
+    pub fn unwrap_sol_instruction(&self) -> anyhow::Result<Instruction> {
+        let owner = self.owner.pubkey();
+        let wsol_account = get_associated_token_address_with_program_id(
+            &owner,
+            &spl_token::native_mint::id(),
+            &spl_token::id(),
+        );
+        Ok(spl_token::instruction::close_account(
+            &spl_token::id(),
+            &wsol_account,
+            &owner,
+            &owner,
+            &[],
+        )?)
+    }
+
+    /// Wrap exactly `lamports` into the owner's wSOL account, creating it
+    /// when missing.
+    pub async fn wrap_sol(&self, lamports: u64) -> anyhow::Result<Signature> {
+        validation::validate_amount(lamports)?;
+        self.send_and_sign_with_lookup_tables(&self.wrap_sol_instructions(lamports)?, &[], None)
+            .await
+    }
 
-        Ok(associated_token_account)
+    /// Close the owner's wSOL account, unwrapping its balance and refunding
+    /// its rent.
+    pub async fn unwrap_sol(&self) -> anyhow::Result<Signature> {
+        self.send_and_sign_with_lookup_tables(&[self.unwrap_sol_instruction()?], &[], None)
+            .await
+    }
+
+    /// Start a [`SwapBuilder`] for an exact input swap on the AMM v4 pool of
+    /// `pool_keys`, as an alternative to the positional arguments of
+    /// [`Self::swap_amm`].
+    pub fn swap<'a>(&'a self, pool_keys: &'a AmmPool) -> SwapBuilder<'a, S> {
+        SwapBuilder::new(self, pool_keys)
     }
 
+    /// Swap `amount_in` of `mint_a` for `mint_b` on an AMM v4 pool, between
+    /// the owner's associated token accounts.
+    ///
+    /// `mint_a` and `mint_b` must be the mints of the pool, in either order:
+    /// passing the pool's token B as `mint_a` sells token B. The direction
+    /// follows the source account, so the quote must be oriented the same way
+    /// (see [`Self::compute_amount_out_for_input`]).
+    ///
+    /// The source account must hold `amount_in`; a missing destination
+    /// account is created by the swap transaction. Sent as a versioned
+    /// transaction through the pool's address lookup table when the API
+    /// lists one, like [`Self::swap_amm_with_accounts`]. Fails before
+    /// anything is sent when the pool does not accept swaps, see
+    /// [`Self::check_amm_swap_enabled`], or the owner cannot pay for the
+    /// swap, see [`Self::check_swap_funds`].
     pub async fn swap_amm(
         &self,
         pool_keys: &AmmPool,
@@ -583,93 +2105,1845 @@ impl AmmSwapClient {
         amount_in: u64,
         amount_out: u64, // out.amount_out means amount 'without' slippage
     ) -> anyhow::Result<Signature> {
-        let amm_program = Pubkey::from_str_const(AMM_V4);
-
-        let user_token_source = self.get_or_create_token_program(mint_a).await?;
-        let user_token_destination = self.get_or_create_token_program(mint_b).await?;
+        validation::validate_amount(amount_in)?;
+        validation::validate_amm_pair(pool_keys, &mint_a.to_string(), &mint_b.to_string())?;
+        self.check_amm_swap_enabled(&pool_keys.id.parse()?).await?;
+        if pool_keys.mint_a.address != mint_a.to_string() {
+            debug!("Swapping token B for token A of pool {}", pool_keys.id);
+        }
+        let (user_token_source, user_token_destination, create_destination) =
+            self.amm_swap_token_accounts(pool_keys, mint_a, mint_b)?;
 
         info!(
             "Executing swap from {:?} to {:?}",
             user_token_source, user_token_destination
         );
 
-        let data = AmmInstruction::SwapBaseIn(SwapInstructionBaseIn {
+        let ix = self.swap_amm_instruction(
+            pool_keys,
+            &user_token_source,
+            &user_token_destination,
             amount_in,
-            minimum_amount_out: amount_out,
-        })
-        .pack()?;
-
-        let accounts = vec![
-            // spl token
-            AccountMeta::new_readonly(spl_token::id(), false),
-            // amm
-            AccountMeta::new(pool_keys.id.parse()?, false),
-            AccountMeta::new_readonly(pool_keys.authority.parse()?, false),
-            AccountMeta::new(pool_keys.open_orders.parse()?, false),
-            // AccountMeta::new(*amm_target_orders, false),
-            AccountMeta::new(pool_keys.vault.a.parse()?, false),
-            AccountMeta::new(pool_keys.vault.b.parse()?, false),
-            // market
-            AccountMeta::new_readonly(pool_keys.market_program_id.parse()?, false),
-            AccountMeta::new(pool_keys.market_id.parse()?, false),
-            AccountMeta::new(pool_keys.market_bids.parse()?, false),
-            AccountMeta::new(pool_keys.market_asks.parse()?, false),
-            AccountMeta::new(pool_keys.market_event_queue.parse()?, false),
-            AccountMeta::new(pool_keys.market_base_vault.parse()?, false),
-            AccountMeta::new(pool_keys.market_quote_vault.parse()?, false),
-            AccountMeta::new(pool_keys.market_authority.parse()?, false),
-            // user
-            AccountMeta::new(user_token_source, false),
-            AccountMeta::new(user_token_destination, false),
-            AccountMeta::new_readonly(self.owner.pubkey(), true),
-        ];
-
-        let ix = Instruction {
-            program_id: amm_program,
-            accounts,
-            data,
+            amount_out,
+        )?;
+        let funding = SwapFunding {
+            input_account: user_token_source,
+            mint_in: *mint_a,
+            amount_in,
+            wrapped_lamports: 0,
+            output_account: user_token_destination,
+            mint_out: *mint_b,
+            min_amount_out: amount_out,
         };
-
-        self.send_and_sign_transaction(&[ix]).await
+        let instructions = [create_destination, ix];
+        self.check_swap_funds(&funding, &instructions, self.priority_fee.as_ref())
+            .await?;
+        self.send_and_sign_with_lookup_tables(
+            &instructions,
+            &amm_lookup_tables(pool_keys)?,
+            funding.trade_value_lamports(),
+        )
+        .await
     }
 
-    async fn send_and_sign_transaction(&self, ix: &[Instruction]) -> anyhow::Result<Signature> {
-        let recent_blockhash = &self.rpc_client.get_latest_blockhash().await?;
+    /// Swap `mint_a` for exactly `amount_out` of `mint_b` on an AMM v4 pool,
+    /// spending at most `max_amount_in`, between the owner's associated token
+    /// accounts.
+    ///
+    /// The exact-output counterpart of [`Self::swap_amm`], with the same
+    /// mint order rules and checks; quote it with
+    /// [`Self::compute_amount_in_for_output`] and pass its `max_amount_in`.
+    pub async fn swap_amm_base_out(
+        &self,
+        pool_keys: &AmmPool,
+        mint_a: &Address,
+        mint_b: &Address,
+        amount_out: u64,
+        max_amount_in: u64,
+    ) -> anyhow::Result<Signature> {
+        validation::validate_amount(amount_out)?;
+        validation::validate_amm_pair(pool_keys, &mint_a.to_string(), &mint_b.to_string())?;
+        self.check_amm_swap_enabled(&pool_keys.id.parse()?).await?;
+        if pool_keys.mint_a.address != mint_a.to_string() {
+            debug!("Buying token A with token B of pool {}", pool_keys.id);
+        }
+        let (user_token_source, user_token_destination, create_destination) =
+            self.amm_swap_token_accounts(pool_keys, mint_a, mint_b)?;
 
-        let tx = Transaction::new_signed_with_payer(
-            ix,
-            Some(&self.owner.pubkey()),
-            &[&self.owner],
-            *recent_blockhash,
+        info!(
+            "Executing base out swap from {:?} to {:?}",
+            user_token_source, user_token_destination
         );
 
-        let sig = &self.rpc_client.send_and_confirm_transaction(&tx).await?;
-        info!("Executed with Signature {sig}");
-        Ok(*sig)
+        let ix = self.swap_amm_base_out_instruction(
+            pool_keys,
+            &user_token_source,
+            &user_token_destination,
+            max_amount_in,
+            amount_out,
+        )?;
+        let funding = SwapFunding {
+            input_account: user_token_source,
+            mint_in: *mint_a,
+            amount_in: max_amount_in,
+            wrapped_lamports: 0,
+            output_account: user_token_destination,
+            mint_out: *mint_b,
+            min_amount_out: amount_out,
+        };
+        let instructions = [create_destination, ix];
+        self.check_swap_funds(&funding, &instructions, self.priority_fee.as_ref())
+            .await?;
+        self.send_and_sign_with_lookup_tables(
+            &instructions,
+            &amm_lookup_tables(pool_keys)?,
+            funding.trade_value_lamports(),
+        )
+        .await
     }
 
-    pub async fn calculate_swap_change_clmm(
+    /// Swap on an AMM v4 pool between explicit token accounts instead of the
+    /// owner's associated token accounts.
+    ///
+    /// Both accounts are checked with [`Self::validate_token_account`]: the
+    /// source must hold `mint_in` and be owned by, or delegated to, the
+    /// client's signer; the destination must hold `mint_out` and be owned by
+    /// the signer. The fees are checked with [`Self::check_swap_funds`].
+    #[allow(clippy::too_many_arguments)]
+    pub async fn swap_amm_with_accounts(
         &self,
-        params: ClmmSwapParams,
-    ) -> anyhow::Result<(ClmmSwapChangeResult, solana_pubkey::Pubkey)> {
-        let base_in = !params.base_out;
-        let tickarray_bitmap_extension = Pubkey::find_program_address(
-            &[
-                POOL_TICK_ARRAY_BITMAP_SEED.as_bytes(),
-                params.pool_id.to_bytes().as_ref(),
-            ],
-            &Pubkey::from_str_const(CLMM),
+        pool_keys: &AmmPool,
+        user_token_source: &Pubkey,
+        user_token_destination: &Pubkey,
+        mint_in: &Pubkey,
+        mint_out: &Pubkey,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> anyhow::Result<Signature> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_amm_pair(pool_keys, &mint_in.to_string(), &mint_out.to_string())?;
+        self.check_amm_swap_enabled(&pool_keys.id.parse()?).await?;
+        self.validate_token_account(user_token_source, mint_in, Some(amount_in)).await?;
+        self.validate_token_account(user_token_destination, mint_out, None).await?;
+        let ix = self.swap_amm_instruction(
+            pool_keys,
+            user_token_source,
+            user_token_destination,
+            amount_in,
+            amount_out,
+        )?;
+        let funding = SwapFunding {
+            input_account: *user_token_source,
+            mint_in: *mint_in,
+            amount_in,
+            wrapped_lamports: 0,
+            output_account: *user_token_destination,
+            mint_out: *mint_out,
+            min_amount_out: amount_out,
+        };
+        let instructions = [ix];
+        self.check_swap_funds(&funding, &instructions, self.priority_fee.as_ref())
+            .await?;
+        self.send_and_sign_with_lookup_tables(
+            &instructions,
+            &amm_lookup_tables(pool_keys)?,
+            funding.trade_value_lamports(),
         )
-        .0;
-
-        let tickarray_bitmap_extension =
-            solana_pubkey::Pubkey::from(tickarray_bitmap_extension.to_bytes());
+        .await
+    }
 
-        let clmm_pubkey = solana_pubkey::Pubkey::from_str_const(CLMM);
+    /// Owner's associated token accounts of `mint_in` and `mint_out` for a
+    /// swap on `pool_keys`, with the idempotent creation of the latter.
+    fn amm_swap_token_accounts(
+        &self,
+        pool_keys: &AmmPool,
+        mint_in: &Pubkey,
+        mint_out: &Pubkey,
+    ) -> anyhow::Result<(Pubkey, Pubkey, Instruction)> {
+        let owner = self.owner.pubkey();
+        let user_token_source = get_associated_token_address_with_program_id(
+            &owner,
+            mint_in,
+            &amm_mint_token_program(pool_keys, mint_in)?,
+        );
+        let destination_program = amm_mint_token_program(pool_keys, mint_out)?;
+        let user_token_destination =
+            get_associated_token_address_with_program_id(&owner, mint_out, &destination_program);
+        let create_destination = create_associated_token_account_idempotent(
+            &owner,
+            &owner,
+            mint_out,
+            &destination_program,
+        );
+        Ok((
+            user_token_source,
+            user_token_destination,
+            create_destination,
+        ))
+    }
 
-        // todo add sync
-        let result = clmm_utils::calculate_swap_change(
-            &self.rpc_client,
+    /// Simulate [`Self::swap_amm`] without sending it, returning the output
+    /// decoded from the program logs and the compute units used.
+    ///
+    /// Nothing is created beforehand: the source associated token account
+    /// must exist and hold `amount_in` (wSOL included); a missing destination
+    /// account is created in the simulated transaction.
+    pub async fn simulate_swap_amm(
+        &self,
+        pool_keys: &AmmPool,
+        mint_a: &Address,
+        mint_b: &Address,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> anyhow::Result<SwapSimulation> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_amm_pair(pool_keys, &mint_a.to_string(), &mint_b.to_string())?;
+        let (user_token_source, user_token_destination, create_destination) =
+            self.amm_swap_token_accounts(pool_keys, mint_a, mint_b)?;
+        let instructions = [
+            create_destination,
+            self.swap_amm_instruction(
+                pool_keys,
+                &user_token_source,
+                &user_token_destination,
+                amount_in,
+                amount_out,
+            )?,
+        ];
+        let tx = self
+            .sign_swap_transaction(
+                &instructions,
+                &amm_lookup_tables(pool_keys)?,
+                swap_trade_value_lamports(mint_a, amount_in, mint_b, amount_out),
+            )
+            .await?;
+        simulate_swap_transaction(&self.rpc_client, &tx).await
+    }
+
+    /// Check that `token_account` holds `mint` and can be used by the
+    /// client's signer.
+    ///
+    /// - `spend_amount`: `Some` for a source account, which must hold the
+    ///   amount and be owned by the signer or delegated to it for at least the
+    ///   amount. `None` for a destination account, which must be owned by the
+    ///   signer.
+    ///
+    /// Returns the token program owning the account.
+    pub async fn validate_token_account(
+        &self,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        spend_amount: Option<u64>,
+    ) -> anyhow::Result<Pubkey> {
+        let account = self.rpc_client.get_account(token_account).await?;
+        let state = TokenAccountInfo::unpack(&account.owner, &account.data)?;
+        let signer = self.owner.pubkey();
+        if state.mint != *mint {
+            return Err(anyhow!(
+                "token account {} holds mint {}, expected {}",
+                token_account,
+                state.mint,
+                mint
+            ));
+        }
+        match spend_amount {
+            Some(amount) => {
+                if state.amount < amount {
+                    return Err(anyhow!(
+                        "token account {} holds {}, {} required",
+                        token_account,
+                        state.amount,
+                        amount
+                    ));
+                }
+                let delegated = state.delegate == Some(signer) && state.delegated_amount >= amount;
+                if state.owner != signer && !delegated {
+                    return Err(anyhow!(
+                        "token account {} is neither owned by nor delegated to {} for {}",
+                        token_account,
+                        signer,
+                        amount
+                    ));
+                }
+            }
+            None => {
+                if state.owner != signer {
+                    return Err(anyhow!(
+                        "token account {} is owned by {}, expected {}",
+                        token_account,
+                        state.owner,
+                        signer
+                    ));
+                }
+            }
+        }
+        Ok(account.owner)
+    }
+
+    /// Instructions of an AMM v4 exact input swap of `amount_in` of `mint_in`
+    /// for `mint_out` between the owner's associated token accounts, to be
+    /// appended to a transaction of the caller's. Nothing is signed or sent.
+    ///
+    /// The output account is created idempotently and a SOL input is wrapped
+    /// into the source account first; other inputs must already be held.
+    /// Compute budget instructions are left to the caller.
+    pub fn build_swap_amm_instructions(
+        &self,
+        pool_keys: &AmmPool,
+        mint_in: &Pubkey,
+        mint_out: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_amm_pair(pool_keys, &mint_in.to_string(), &mint_out.to_string())?;
+        let owner = self.owner.pubkey();
+        let user_token_source = get_associated_token_address_with_program_id(
+            &owner,
+            mint_in,
+            &amm_mint_token_program(pool_keys, mint_in)?,
+        );
+        let destination_program = amm_mint_token_program(pool_keys, mint_out)?;
+        let user_token_destination =
+            get_associated_token_address_with_program_id(&owner, mint_out, &destination_program);
+
+        let mut instructions = Vec::with_capacity(5);
+        if *mint_in == spl_token::native_mint::id() {
+            instructions.extend(self.wrap_sol_instructions(amount_in)?);
+        }
+        instructions.push(create_associated_token_account_idempotent(
+            &owner,
+            &owner,
+            mint_out,
+            &destination_program,
+        ));
+        instructions.push(self.swap_amm_instruction(
+            pool_keys,
+            &user_token_source,
+            &user_token_destination,
+            amount_in,
+            min_amount_out,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Build the AMM v4 `SwapBaseIn` instruction without sending it.
+    pub fn swap_amm_instruction(
+        &self,
+        pool_keys: &AmmPool,
+        user_token_source: &Pubkey,
+        user_token_destination: &Pubkey,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let data = AmmInstruction::SwapBaseIn(SwapInstructionBaseIn {
+            amount_in,
+            minimum_amount_out: amount_out,
+        })
+        .pack()?;
+
+        Ok(Instruction {
+            program_id: self.cluster_config.amm_v4,
+            accounts: self.amm_swap_accounts(
+                pool_keys,
+                user_token_source,
+                user_token_destination,
+            )?,
+            data,
+        })
+    }
+
+    /// Build the AMM v4 `SwapBaseOut` instruction without sending it: receive
+    /// exactly `amount_out`, spending at most `max_amount_in`.
+    pub fn swap_amm_base_out_instruction(
+        &self,
+        pool_keys: &AmmPool,
+        user_token_source: &Pubkey,
+        user_token_destination: &Pubkey,
+        max_amount_in: u64,
+        amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let data = AmmInstruction::SwapBaseOut(SwapInstructionBaseOut {
+            max_amount_in,
+            amount_out,
+        })
+        .pack()?;
+
+        Ok(Instruction {
+            program_id: self.cluster_config.amm_v4,
+            accounts: self.amm_swap_accounts(
+                pool_keys,
+                user_token_source,
+                user_token_destination,
+            )?,
+            data,
+        })
+    }
+
+    /// Accounts of the AMM v4 `SwapBaseIn` and `SwapBaseOut` instructions.
+    fn amm_swap_accounts(
+        &self,
+        pool_keys: &AmmPool,
+        user_token_source: &Pubkey,
+        user_token_destination: &Pubkey,
+    ) -> anyhow::Result<Vec<AccountMeta>> {
+        Ok(vec![
+            // spl token
+            AccountMeta::new_readonly(amm_token_program(pool_keys)?, false),
+            // amm
+            AccountMeta::new(pool_keys.id.parse()?, false),
+            AccountMeta::new_readonly(pool_keys.authority.parse()?, false),
+            AccountMeta::new(pool_keys.open_orders.parse()?, false),
+            // AccountMeta::new(*amm_target_orders, false),
+            AccountMeta::new(pool_keys.vault.a.parse()?, false),
+            AccountMeta::new(pool_keys.vault.b.parse()?, false),
+            // market
+            AccountMeta::new_readonly(pool_keys.market_program_id.parse()?, false),
+            AccountMeta::new(pool_keys.market_id.parse()?, false),
+            AccountMeta::new(pool_keys.market_bids.parse()?, false),
+            AccountMeta::new(pool_keys.market_asks.parse()?, false),
+            AccountMeta::new(pool_keys.market_event_queue.parse()?, false),
+            AccountMeta::new(pool_keys.market_base_vault.parse()?, false),
+            AccountMeta::new(pool_keys.market_quote_vault.parse()?, false),
+            AccountMeta::new(pool_keys.market_authority.parse()?, false),
+            // user
+            AccountMeta::new(*user_token_source, false),
+            AccountMeta::new(*user_token_destination, false),
+            AccountMeta::new_readonly(self.owner.pubkey(), true),
+        ])
+    }
+
+    /// Deposit exactly `amount` of `fixed_mint` into the AMM v4 pool of
+    /// `pool_keys`, with the other mint of the pool in proportion to the
+    /// on-chain reserves, at most `slippage_bps` above the quote.
+    ///
+    /// LP tokens are minted to the owner's associated token account, created
+    /// when missing. A wSOL side is wrapped from SOL up to its maximum; the
+    /// unspent part stays in the wSOL account.
+    pub async fn add_liquidity_amm(
+        &self,
+        pool_keys: &AmmPool,
+        fixed_mint: &Pubkey,
+        amount: u64,
+        slippage_bps: u64,
+    ) -> anyhow::Result<AmmDeposit> {
+        validation::validate_amount(amount)?;
+        validation::validate_slippage_bps(slippage_bps)?;
+        let pool_id: Pubkey = pool_keys.id.parse()?;
+        let (market_state, rpc_pool_info) = self.get_rpc_pool_info_with_state(&pool_id).await?;
+        if !market_state.flags().status.deposit_permission() {
+            return Err(anyhow!("AMM v4 pool {pool_id} does not accept deposits"));
+        }
+        let fixed_coin = if *fixed_mint == market_state.base_mint {
+            true
+        } else if *fixed_mint == market_state.quote_mint {
+            false
+        } else {
+            return Err(ValidationError::MintNotInPool {
+                pool_id: pool_id.to_string(),
+                mint: fixed_mint.to_string(),
+            }
+            .into());
+        };
+        let quote = quote_amm_deposit(
+            &rpc_pool_info,
+            market_state.lp_reserve,
+            fixed_coin,
+            amount,
+            slippage_bps,
+        )?;
+
+        let owner = self.owner.pubkey();
+        let mut instructions = Vec::with_capacity(5);
+        for (mint, max_amount) in [
+            (market_state.base_mint, quote.max_coin_amount),
+            (market_state.quote_mint, quote.max_pc_amount),
+        ] {
+            if mint == spl_token::native_mint::id() {
+                instructions.extend(self.wrap_sol_instructions(max_amount)?);
+            }
+        }
+        instructions.push(create_associated_token_account_idempotent(
+            &owner,
+            &owner,
+            &market_state.lp_mint,
+            &spl_token::id(),
+        ));
+        instructions.push(amm_deposit_instruction(
+            pool_keys,
+            &market_state.lp_mint,
+            &owner,
+            &quote,
+        )?);
+        let signature = self
+            .send_and_sign_with_lookup_tables(&instructions, &amm_lookup_tables(pool_keys)?, None)
+            .await?;
+        info!(
+            "Deposited {} coin and {} pc into {pool_id} for {} LP, signature {signature}",
+            quote.coin_amount, quote.pc_amount, quote.lp_amount
+        );
+        Ok(AmmDeposit { signature, quote })
+    }
+
+    /// Burn `lp_amount` LP tokens of the AMM v4 pool of `pool_keys` for both
+    /// of its mints, in proportion to the on-chain reserves, at most
+    /// `slippage_bps` below the quote.
+    ///
+    /// The tokens are received in the owner's associated token accounts,
+    /// created when missing.
+    pub async fn remove_liquidity_amm(
+        &self,
+        pool_keys: &AmmPool,
+        lp_amount: u64,
+        slippage_bps: u64,
+    ) -> anyhow::Result<AmmWithdrawal> {
+        validation::validate_amount(lp_amount)?;
+        validation::validate_slippage_bps(slippage_bps)?;
+        let pool_id: Pubkey = pool_keys.id.parse()?;
+        let (market_state, rpc_pool_info) = self.get_rpc_pool_info_with_state(&pool_id).await?;
+        if !market_state.flags().status.withdraw_permission() {
+            return Err(anyhow!("AMM v4 pool {pool_id} does not accept withdrawals"));
+        }
+        let quote = quote_amm_withdraw(
+            &rpc_pool_info,
+            market_state.lp_reserve,
+            lp_amount,
+            slippage_bps,
+        )?;
+
+        let owner = self.owner.pubkey();
+        let token_program = amm_token_program(pool_keys)?;
+        let mut instructions: Vec<Instruction> = [market_state.base_mint, market_state.quote_mint]
+            .iter()
+            .map(|mint| {
+                create_associated_token_account_idempotent(&owner, &owner, mint, &token_program)
+            })
+            .collect();
+        instructions.push(amm_withdraw_instruction(
+            pool_keys,
+            &market_state.lp_mint,
+            &owner,
+            &quote,
+        )?);
+        let signature = self
+            .send_and_sign_with_lookup_tables(&instructions, &amm_lookup_tables(pool_keys)?, None)
+            .await?;
+        info!(
+            "Withdrew {} coin and {} pc from {pool_id} for {lp_amount} LP, signature {signature}",
+            quote.coin_amount, quote.pc_amount
+        );
+        Ok(AmmWithdrawal { signature, quote })
+    }
+
+    /// Resolve the addresses of address lookup tables. Tables that are not
+    /// found are skipped with a warning.
+    pub async fn load_lookup_tables(
+        &self,
+        keys: &[Pubkey],
+    ) -> anyhow::Result<Vec<AddressLookupTableAccount>> {
+        let mut lookup_tables = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.get_multiple_accounts(chunk).await?;
+            for (key, account) in chunk.iter().zip(accounts) {
+                match account {
+                    Some(account) if account.data.len() >= LOOKUP_TABLE_META_SIZE => {
+                        let addresses = account.data[LOOKUP_TABLE_META_SIZE..]
+                            .chunks_exact(32)
+                            .map(Pubkey::try_from)
+                            .collect::<Result<Vec<_>, _>>()?;
+                        lookup_tables.push(AddressLookupTableAccount {
+                            key: *key,
+                            addresses,
+                        });
+                    }
+                    _ => warn!("Lookup table {key} was not found, skipping it"),
+                }
+            }
+        }
+        Ok(lookup_tables)
+    }
+
+    /// Sign a swap into a legacy message, or a versioned one resolving
+    /// accounts through `lookup_table_keys` when any are given. The compute
+    /// budget instructions of the client's priority fee come first, budgeted
+    /// for a trade of `trade_value_lamports`.
+    async fn sign_swap_transaction(
+        &self,
+        ix: &[Instruction],
+        lookup_table_keys: &[Pubkey],
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<VersionedTransaction> {
+        let message = self
+            .swap_message(
+                ix,
+                lookup_table_keys,
+                self.priority_fee.as_ref(),
+                trade_value_lamports,
+            )
+            .await?;
+        self.sign_message(message).await
+    }
+
+    /// Compile a swap paid by the owner like [`Self::sign_swap_transaction`],
+    /// with the compute budget instructions of `priority_fee`.
+    pub(crate) async fn swap_message(
+        &self,
+        ix: &[Instruction],
+        lookup_table_keys: &[Pubkey],
+        priority_fee: Option<&PriorityFeeConfig>,
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<VersionedMessage> {
+        let recent_blockhash = self.get_latest_blockhash().await?;
+        self.swap_message_with_blockhash(
+            ix,
+            lookup_table_keys,
+            priority_fee,
+            trade_value_lamports,
+            recent_blockhash,
+        )
+        .await
+    }
+
+    /// Same as [`Self::swap_message`], with `recent_blockhash` instead of the
+    /// latest one.
+    pub(crate) async fn swap_message_with_blockhash(
+        &self,
+        ix: &[Instruction],
+        lookup_table_keys: &[Pubkey],
+        priority_fee: Option<&PriorityFeeConfig>,
+        trade_value_lamports: Option<u64>,
+        recent_blockhash: Hash,
+    ) -> anyhow::Result<VersionedMessage> {
+        let ix = self
+            .with_compute_budget(ix, priority_fee, trade_value_lamports)
+            .await?;
+        let payer = self.owner.pubkey();
+        let message = if lookup_table_keys.is_empty() {
+            VersionedMessage::Legacy(Message::new_with_blockhash(
+                &ix,
+                Some(&payer),
+                &recent_blockhash,
+            ))
+        } else {
+            let lookup_tables = self.load_lookup_tables(lookup_table_keys).await?;
+            VersionedMessage::V0(v0::Message::try_compile(
+                &payer,
+                &ix,
+                &lookup_tables,
+                recent_blockhash,
+            )?)
+        };
+        Ok(message)
+    }
+
+    pub(crate) async fn sign_message(
+        &self,
+        message: VersionedMessage,
+    ) -> anyhow::Result<VersionedTransaction> {
+        self.owner.sign_message(message).await
+    }
+
+    /// Sign `message` by the owner and by `co_signers`, keypairs generated by
+    /// the client for accounts the transaction creates.
+    pub(crate) async fn sign_message_with(
+        &self,
+        message: VersionedMessage,
+        co_signers: &[&Keypair],
+    ) -> anyhow::Result<VersionedTransaction> {
+        let co_signed = partial_sign_message(message.clone(), co_signers)?;
+        let mut transaction = self.sign_message(message).await?;
+        for (signature, co_signature) in transaction.signatures.iter_mut().zip(co_signed.signatures)
+        {
+            if co_signature != Signature::default() {
+                *signature = co_signature;
+            }
+        }
+        Ok(transaction)
+    }
+
+    /// Sign `message` by the owner and send it, see [`Self::send_message_with`].
+    pub(crate) async fn send_message(
+        &self,
+        message: VersionedMessage,
+    ) -> anyhow::Result<Signature> {
+        self.send_message_with(message, &[]).await
+    }
+
+    /// Sign `message` by the owner and `co_signers` and send it under the
+    /// client's [`SendOptions`]. When its blockhash expires before it lands,
+    /// it is signed again with a new one and resent, up to
+    /// `max_blockhash_refreshes` times.
+    pub(crate) async fn send_message_with(
+        &self,
+        mut message: VersionedMessage,
+        co_signers: &[&Keypair],
+    ) -> anyhow::Result<Signature> {
+        let max_refreshes = self.send_options.max_blockhash_refreshes;
+        let mut refreshes = 0;
+        loop {
+            let transaction = self.sign_message_with(message.clone(), co_signers).await?;
+            match self.send_signed(&transaction).await {
+                Err(e)
+                    if refreshes < max_refreshes
+                        && matches!(
+                            e.downcast_ref::<SwapError>(),
+                            Some(SwapError::BlockhashExpired { .. })
+                        ) =>
+                {
+                    refreshes += 1;
+                    warn!("{e}, resending with a new blockhash ({refreshes}/{max_refreshes})");
+                    message.set_recent_blockhash(self.get_latest_blockhash().await?);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a signed transaction under the client's [`SendOptions`], or
+    /// simulate it in [`ExecutionMode::DryRun`]. A failure is reported with
+    /// the program of the failing instruction, see [`with_failed_program`].
+    pub(crate) async fn send_signed(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> anyhow::Result<Signature> {
+        match self.execution_mode {
+            ExecutionMode::Live => {
+                send_and_confirm(&self.rpc_client, transaction, &self.send_options)
+                    .await
+                    .map_err(|err| with_failed_program(err, &transaction.message))
+            }
+            ExecutionMode::DryRun => self.dry_run(transaction).await,
+        }
+    }
+
+    /// Simulate `transaction` and record it into the dry run log. Returns
+    /// its signature, or [`SwapError::TransactionFailed`] with the program
+    /// of the failing instruction when the simulation fails.
+    async fn dry_run(&self, transaction: &VersionedTransaction) -> anyhow::Result<Signature> {
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        let simulation = simulate_swap_transaction(&self.rpc_client, transaction).await?;
+        info!(
+            "Dry run of {signature}: out {:?}, {:?} compute units",
+            simulation.amount_out, simulation.units_consumed
+        );
+        let err = simulation.err.clone();
+        self.dry_run_log.record(DryRunRecord {
+            signature,
+            simulation,
+        });
+        match err {
+            Some(error) => Err(SwapError::TransactionFailed {
+                signature,
+                program_id: failed_instruction_program(&transaction.message, &error),
+                error,
+            }
+            .into()),
+            None => Ok(signature),
+        }
+    }
+
+    /// Sign and send `ix` like [`Self::sign_swap_transaction`].
+    async fn send_and_sign_with_lookup_tables(
+        &self,
+        ix: &[Instruction],
+        lookup_table_keys: &[Pubkey],
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<Signature> {
+        let message = self
+            .swap_message(
+                ix,
+                lookup_table_keys,
+                self.priority_fee.as_ref(),
+                trade_value_lamports,
+            )
+            .await?;
+        let sig = self.send_message(message).await?;
+        info!("Executed with Signature {sig}");
+        Ok(sig)
+    }
+
+    /// Build and sign one transaction per swap request, without sending them.
+    ///
+    /// Work shared by the requests is done once: the blockhash is fetched a
+    /// single time, the owner's associated token accounts are checked in one
+    /// batch (missing ones are created idempotently by every transaction
+    /// using them, so the transactions may land in any order), and the
+    /// pools' address lookup tables are resolved once and offered to every
+    /// transaction. Each transaction carries the compute budget instructions
+    /// of the client's [`PriorityFeeConfig`].
+    pub async fn build_swaps(
+        &self,
+        requests: Vec<SwapRequest>,
+    ) -> anyhow::Result<Vec<VersionedTransaction>> {
+        self.build_swap_transactions(requests, false).await
+    }
+
+    /// Build and sign a single transaction running `requests` in order, e.g.
+    /// the hops of a multi-hop route, without sending it.
+    ///
+    /// When the swaps do not fit in one transaction, one transaction per
+    /// request is returned instead, as with [`Self::build_swaps`]; they must
+    /// then be sent in order.
+    pub async fn build_swap_route(
+        &self,
+        requests: Vec<SwapRequest>,
+    ) -> anyhow::Result<Vec<VersionedTransaction>> {
+        self.build_swap_transactions(requests, true).await
+    }
+
+    async fn build_swap_transactions(
+        &self,
+        requests: Vec<SwapRequest>,
+        combine: bool,
+    ) -> anyhow::Result<Vec<VersionedTransaction>> {
+        let owner = self.owner.pubkey();
+
+        // (mint, token program) of the token accounts used by each request.
+        let mut request_accounts = Vec::with_capacity(requests.len());
+        let mut lookup_table_keys: Vec<Pubkey> = Vec::new();
+        for request in &requests {
+            let accounts = match request {
+                SwapRequest::Amm {
+                    pool_keys,
+                    mint_in,
+                    mint_out,
+                    ..
+                } => {
+                    if let Some(table) = &pool_keys.lookup_table_account {
+                        let table: Pubkey = table.parse()?;
+                        if !lookup_table_keys.contains(&table) {
+                            lookup_table_keys.push(table);
+                        }
+                    }
+                    vec![
+                        (*mint_in, amm_mint_token_program(pool_keys, mint_in)?),
+                        (*mint_out, amm_mint_token_program(pool_keys, mint_out)?),
+                    ]
+                }
+                SwapRequest::Clmm { swap_change, .. } => vec![(
+                    Pubkey::from(swap_change.output_vault_mint.to_bytes()),
+                    Pubkey::from(swap_change.output_token_program.to_bytes()),
+                )],
+            };
+            request_accounts.push(accounts);
+        }
+
+        let token_accounts: Vec<Pubkey> = request_accounts
+            .iter()
+            .flatten()
+            .map(|(mint, token_program)| {
+                get_associated_token_address_with_program_id(&owner, mint, token_program)
+            })
+            .collect();
+        let mut existing_accounts = HashSet::new();
+        for chunk in token_accounts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.get_multiple_accounts(chunk).await?;
+            for (address, account) in chunk.iter().zip(accounts) {
+                if account.is_some() {
+                    existing_accounts.insert(*address);
+                }
+            }
+        }
+
+        let lookup_tables = self.load_lookup_tables(&lookup_table_keys).await?;
+
+        let recent_blockhash = self.get_latest_blockhash().await?;
+        let mut request_instructions = Vec::with_capacity(requests.len());
+        for (request, accounts) in requests.into_iter().zip(request_accounts) {
+            let mut instructions = Vec::new();
+            let mut atas = Vec::with_capacity(accounts.len());
+            for (mint, token_program) in &accounts {
+                let ata = get_associated_token_address_with_program_id(&owner, mint, token_program);
+                if !existing_accounts.contains(&ata) {
+                    instructions.push(create_associated_token_account_idempotent(
+                        &owner,
+                        &owner,
+                        mint,
+                        token_program,
+                    ));
+                }
+                atas.push(ata);
+            }
+
+            let trade_value_lamports = match request {
+                SwapRequest::Amm {
+                    pool_keys,
+                    mint_in,
+                    mint_out,
+                    amount_in,
+                    min_amount_out,
+                    close_source_account,
+                } => {
+                    instructions.push(self.swap_amm_instruction(
+                        &pool_keys,
+                        &atas[0],
+                        &atas[1],
+                        amount_in,
+                        min_amount_out,
+                    )?);
+                    if close_source_account {
+                        let mut close = spl_token::instruction::close_account(
+                            &spl_token::id(),
+                            &atas[0],
+                            &owner,
+                            &owner,
+                            &[],
+                        )?;
+                        // Token-2022 shares the instruction layout of SPL Token.
+                        close.program_id = accounts[0].1;
+                        instructions.push(close);
+                    }
+                    swap_trade_value_lamports(&mint_in, amount_in, &mint_out, min_amount_out)
+                }
+                SwapRequest::Clmm {
+                    swap_change,
+                    tick_array_bitmap_extension,
+                } => {
+                    let trade_value_lamports =
+                        SwapFunding::clmm(&swap_change, &owner, None).trade_value_lamports();
+                    instructions.extend(self.build_swap_clmm_instructions(
+                        Some(solana_pubkey::Pubkey::from(atas[0].to_bytes())),
+                        swap_change,
+                        tick_array_bitmap_extension,
+                    )?);
+                    trade_value_lamports
+                }
+            };
+            request_instructions.push((instructions, trade_value_lamports));
+        }
+
+        let compile = |instructions: &[Instruction]| -> anyhow::Result<VersionedMessage> {
+            let message =
+                v0::Message::try_compile(&owner, instructions, &lookup_tables, recent_blockhash)?;
+            Ok(VersionedMessage::V0(message))
+        };
+        if combine && request_instructions.len() > 1 {
+            // Later swaps use the token accounts created by earlier ones.
+            let mut created = HashSet::new();
+            let instructions: Vec<Instruction> = request_instructions
+                .iter()
+                .flat_map(|(instructions, _)| instructions)
+                .filter(|ix| {
+                    ix.program_id != spl_associated_token_account::id()
+                        || created.insert(ix.accounts[1].pubkey)
+                })
+                .cloned()
+                .collect();
+            // A route trades the same value in each hop, so its largest SOL
+            // leg is taken rather than their sum.
+            let trade_value_lamports = request_instructions
+                .iter()
+                .filter_map(|(_, trade_value_lamports)| *trade_value_lamports)
+                .max();
+            let instructions = self
+                .with_compute_budget(
+                    &instructions,
+                    self.priority_fee.as_ref(),
+                    trade_value_lamports,
+                )
+                .await?;
+            match compile(&instructions) {
+                // Signatures have a fixed size, so the unsigned transaction
+                // is as large as the signed one.
+                Ok(message)
+                    if bincode::serialized_size(&unsigned_transaction(message.clone()))?
+                        as usize
+                        <= MAX_TRANSACTION_SIZE =>
+                {
+                    return Ok(vec![self.sign_message(message).await?]);
+                }
+                Ok(_) => debug!("Swaps do not fit in one transaction, splitting them"),
+                Err(e) => debug!("Swaps cannot be compiled into one transaction: {e:?}"),
+            }
+        }
+        let mut transactions = Vec::with_capacity(request_instructions.len());
+        for (instructions, trade_value_lamports) in &request_instructions {
+            let instructions = self
+                .with_compute_budget(
+                    instructions,
+                    self.priority_fee.as_ref(),
+                    *trade_value_lamports,
+                )
+                .await?;
+            transactions.push(self.sign_message(compile(&instructions)?).await?);
+        }
+        Ok(transactions)
+    }
+
+    /// Build and sign a transaction closing empty token accounts of the
+    /// owner, returning their rent to the owner.
+    pub async fn build_close_accounts(
+        &self,
+        token_accounts: &[Pubkey],
+    ) -> anyhow::Result<VersionedTransaction> {
+        let owner = self.owner.pubkey();
+        let instructions = token_accounts
+            .iter()
+            .map(|account| {
+                spl_token::instruction::close_account(
+                    &spl_token::id(),
+                    account,
+                    &owner,
+                    &owner,
+                    &[],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let recent_blockhash = self.get_latest_blockhash().await?;
+        self.sign_message(VersionedMessage::Legacy(Message::new_with_blockhash(
+            &instructions,
+            Some(&owner),
+            &recent_blockhash,
+        )))
+        .await
+    }
+
+    /// Swap `amount_in` of `mint_in` for `mint_out` on the best pool of the
+    /// pair, with `slippage` as a fraction (e.g. `0.005` for 0.5%).
+    ///
+    /// The pool is chosen among the deepest AMM v4 and CLMM pools of the pair
+    /// by on-chain quotes, see [`find_best_pool`](crate::router::find_best_pool).
+    /// The output account is created in the swap transaction when missing and
+    /// a SOL input is wrapped in it; other inputs must already be held. The
+    /// amounts actually swapped are read back from the confirmed transaction,
+    /// which fails with `SwapError::FillBelowMinimum` when the output is below
+    /// the quoted minimum. Nothing is sent when the owner cannot pay for the
+    /// swap, see [`Self::check_swap_funds`].
+    pub async fn swap_token(
+        &self,
+        mint_in: &Pubkey,
+        mint_out: &Pubkey,
+        amount_in: u64,
+        slippage: f64,
+    ) -> anyhow::Result<TokenSwap> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_slippage(slippage)?;
+        validation::validate_distinct_mints(&mint_in.to_string(), &mint_out.to_string())?;
+        let best = find_best_pool_for_programs(
+            self,
+            mint_in,
+            mint_out,
+            amount_in,
+            slippage,
+            SWAP_TOKEN_CANDIDATES,
+            &[
+                &self.cluster_config.amm_v4.to_string(),
+                &self.cluster_config.clmm.to_string(),
+            ],
+        )
+        .await?
+        .ok_or_else(|| anyhow!("No AMM v4 or CLMM pool could quote {mint_in} -> {mint_out}"))?;
+        let quote = best.quote;
+        info!(
+            "Swapping {amount_in} {mint_in} for at least {} {mint_out} on pool {}",
+            quote.min_amount_out, quote.pool_id
+        );
+
+        let owner = self.owner.pubkey();
+        let wrapped_lamports = if *mint_in == spl_token::native_mint::id() {
+            amount_in
+        } else {
+            0
+        };
+        let signature = if best.pool_info.program_id == self.cluster_config.amm_v4.to_string() {
+            let pool_keys: PoolKeys<AmmPool> = self.fetch_pools_keys_by_id(&quote.pool_id).await?;
+            let pool_keys = pool_keys
+                .data
+                .into_iter()
+                .next()
+                .ok_or(anyhow!("No keys returned for pool {}", quote.pool_id))?;
+            self.check_amm_swap_enabled(&quote.pool_id).await?;
+            let instructions = self.build_swap_amm_instructions(
+                &pool_keys,
+                mint_in,
+                mint_out,
+                amount_in,
+                quote.min_amount_out,
+            )?;
+            let funding = SwapFunding {
+                input_account: get_associated_token_address_with_program_id(
+                    &owner,
+                    mint_in,
+                    &amm_mint_token_program(&pool_keys, mint_in)?,
+                ),
+                mint_in: *mint_in,
+                amount_in,
+                wrapped_lamports,
+                output_account: get_associated_token_address_with_program_id(
+                    &owner,
+                    mint_out,
+                    &amm_mint_token_program(&pool_keys, mint_out)?,
+                ),
+                mint_out: *mint_out,
+                min_amount_out: quote.min_amount_out,
+            };
+            self.check_swap_funds(&funding, &instructions, self.priority_fee.as_ref())
+                .await?;
+            self.send_and_sign_with_lookup_tables(
+                &instructions,
+                &amm_lookup_tables(&pool_keys)?,
+                funding.trade_value_lamports(),
+            )
+            .await?
+        } else {
+            self.check_clmm_swap_enabled(&quote.pool_id).await?;
+            let token_program = if wrapped_lamports > 0 {
+                // The swap reads the input account, so it must exist before.
+                self.get_or_create_token_program(mint_in).await?;
+                spl_token::id()
+            } else {
+                self.rpc_client.get_account(mint_in).await?.owner
+            };
+            let user_input_token =
+                get_associated_token_address_with_program_id(&owner, mint_in, &token_program);
+            let (swap_change, tick_array_bitmap_extension) = self
+                .calculate_swap_change_clmm(ClmmSwapParams {
+                    pool_id: solana_pubkey::Pubkey::from(quote.pool_id.to_bytes()),
+                    user_input_token: solana_pubkey::Pubkey::from(user_input_token.to_bytes()),
+                    user_output_token: None,
+                    amount_specified: amount_in,
+                    limit_price: None,
+                    base_out: false,
+                    slippage_bps: 0,
+                    max_price_impact_bps: None,
+                })
+                .await?;
+            let funding = SwapFunding {
+                wrapped_lamports,
+                min_amount_out: quote.min_amount_out,
+                ..SwapFunding::clmm(&swap_change, &owner, None)
+            };
+            let mut instructions = Vec::new();
+            if wrapped_lamports > 0 {
+                instructions.extend(self.wrap_sol_instructions(wrapped_lamports)?);
+            }
+            instructions.extend(self.build_swap_clmm_instructions(
+                None,
+                ClmmSwapChangeResult {
+                    other_amount_threshold: quote.min_amount_out,
+                    ..swap_change
+                },
+                tick_array_bitmap_extension,
+            )?);
+            self.check_swap_funds(&funding, &instructions, self.priority_fee.as_ref())
+                .await?;
+            self.send_and_sign_with_lookup_tables(
+                &instructions,
+                &[],
+                funding.trade_value_lamports(),
+            )
+            .await?
+        };
+
+        let meta = self.get_transaction_meta(&signature).await?;
+        let fill = self.swap_fill(&signature, &meta, mint_out, quote.min_amount_out)?;
+        let (pre_balance_in, post_balance_in) = self.owner_token_balances(&meta, mint_in);
+        Ok(TokenSwap {
+            signature,
+            pool_info: best.pool_info,
+            amount_in: pre_balance_in
+                .saturating_add(wrapped_lamports)
+                .saturating_sub(post_balance_in),
+            amount_out: fill.amount_out,
+            quote,
+        })
+    }
+
+    /// Verify the output received by a confirmed swap.
+    ///
+    /// The owner's `output_mint` balance delta is read from the pre/post token
+    /// balances of the transaction meta. Returns `SwapError::FillBelowMinimum`
+    /// when less than `min_amount_out` was received, e.g. because of a transfer
+    /// fee on the output mint.
+    pub async fn verify_swap_fill(
+        &self,
+        signature: &Signature,
+        output_mint: &Pubkey,
+        min_amount_out: u64,
+    ) -> anyhow::Result<SwapFill> {
+        let meta = self.get_transaction_meta(signature).await?;
+        self.swap_fill(signature, &meta, output_mint, min_amount_out)
+    }
+
+    /// Actual amounts, fees and resulting pool price of the swaps of the
+    /// confirmed transaction `signature`, decoded from its AMM v4 `ray_log`s
+    /// and CLMM swap events.
+    ///
+    /// The trade fee of CLMM swaps is computed from the amm config of their
+    /// pool. AMM v4 logs do not name the pool, so it is read from the swap
+    /// instructions and the fee computed from its swap fee; the standard
+    /// 0.25% fee is kept when the instructions do not match the logged swaps.
+    /// Returns an error when the transaction logged no swap.
+    pub async fn get_swap_result(&self, signature: &Signature) -> anyhow::Result<SwapResult> {
+        let transaction = self.get_confirmed_transaction(signature).await?;
+        let amm_pools = amm_swap_pools(&transaction, &self.cluster_config.amm_v4);
+        let log_messages: Option<Vec<String>> = transaction
+            .meta
+            .map(|meta| meta.log_messages.into())
+            .unwrap_or_default();
+        let mut swaps = parse_executed_swaps(&log_messages.unwrap_or_default());
+        if swaps.is_empty() {
+            return Err(anyhow!("Transaction {} logged no swap", signature));
+        }
+        let amm_swaps = swaps
+            .iter()
+            .filter(|swap| swap.pool_type == PoolType::Standard)
+            .count();
+        let mut amm_pools = (amm_pools.len() == amm_swaps).then_some(amm_pools.into_iter());
+        for swap in swaps.iter_mut() {
+            match swap.pool_type {
+                PoolType::Standard => {
+                    let Some(pool_id) = amm_pools.as_mut().and_then(|pools| pools.next()) else {
+                        continue;
+                    };
+                    let pool_state = self.get_amm_pool_state(&pool_id).await?;
+                    swap.pool_id = Some(pool_id);
+                    swap.trade_fee = amm_trade_fee(
+                        swap.amount_in,
+                        pool_state.swap_fee_numerator,
+                        pool_state.swap_fee_denominator,
+                    );
+                }
+                PoolType::Concentrated => {
+                    let Some(pool_id) = swap.pool_id else {
+                        continue;
+                    };
+                    let pool_state = self.get_pool_state(&pool_id).await?;
+                    let amm_config_key = Pubkey::from(pool_state.amm_config.to_bytes());
+                    let amm_config = self
+                        .get_cached_anchor_account::<AmmConfig>(&amm_config_key)
+                        .await?
+                        .ok_or(anyhow!("Amm config was not found by rpc"))?;
+                    swap.trade_fee = clmm_trade_fee(
+                        swap.amount_in.saturating_sub(swap.transfer_fee_in),
+                        amm_config.trade_fee_rate,
+                    );
+                }
+            }
+        }
+        debug!("Swap {signature} decoded from logs: {swaps:?}");
+        Ok(SwapResult {
+            signature: *signature,
+            swaps,
+        })
+    }
+
+    /// Status meta of the confirmed transaction `signature`, an error if it
+    /// failed.
+    async fn get_transaction_meta(
+        &self,
+        signature: &Signature,
+    ) -> anyhow::Result<UiTransactionStatusMeta> {
+        self.get_confirmed_transaction(signature)
+            .await?
+            .meta
+            .ok_or(anyhow!("Transaction {} has no status meta", signature))
+    }
+
+    /// Confirmed transaction `signature` in the JSON encoding, an error if it
+    /// failed or has no status meta.
+    async fn get_confirmed_transaction(
+        &self,
+        signature: &Signature,
+    ) -> anyhow::Result<EncodedTransactionWithStatusMeta> {
+        let transaction = self
+            .rpc_client
+            .get_transaction_with_config(
+                signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Json),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await?;
+        let meta = transaction
+            .transaction
+            .meta
+            .as_ref()
+            .ok_or(anyhow!("Transaction {} has no status meta", signature))?;
+        if let Some(err) = &meta.err {
+            return Err(anyhow!("Transaction {} failed: {:?}", signature, err));
+        }
+        Ok(transaction.transaction)
+    }
+
+    /// Owner's `mint` balance before and after the transaction of `meta`. A
+    /// missing pre balance means the account was created by the transaction.
+    fn owner_token_balances(&self, meta: &UiTransactionStatusMeta, mint: &Pubkey) -> (u64, u64) {
+        let owner = self.owner.pubkey().to_string();
+        let mint = mint.to_string();
+        let owner_balance = |balances: Option<Vec<UiTransactionTokenBalance>>| -> u64 {
+            balances
+                .unwrap_or_default()
+                .iter()
+                .filter(|balance| balance.mint == mint)
+                .filter(|balance| {
+                    Option::<String>::from(balance.owner.clone()).as_deref() == Some(owner.as_str())
+                })
+                .filter_map(|balance| balance.ui_token_amount.amount.parse::<u64>().ok())
+                .sum()
+        };
+        (
+            owner_balance(meta.pre_token_balances.clone().into()),
+            owner_balance(meta.post_token_balances.clone().into()),
+        )
+    }
+
+    /// [`SwapFill`] of the transaction of `meta`, see
+    /// [`Self::verify_swap_fill`].
+    fn swap_fill(
+        &self,
+        signature: &Signature,
+        meta: &UiTransactionStatusMeta,
+        output_mint: &Pubkey,
+        min_amount_out: u64,
+    ) -> anyhow::Result<SwapFill> {
+        let (pre_balance, post_balance) = self.owner_token_balances(meta, output_mint);
+        let amount_out = post_balance.saturating_sub(pre_balance);
+        debug!("Swap {signature} received {amount_out} (pre {pre_balance}, post {post_balance})");
+
+        if amount_out < min_amount_out {
+            return Err(SwapError::FillBelowMinimum {
+                signature: *signature,
+                min_amount_out,
+                actual_amount_out: amount_out,
+            }
+            .into());
+        }
+
+        Ok(SwapFill {
+            signature: *signature,
+            pre_balance,
+            post_balance,
+            amount_out,
+        })
+    }
+
+    /// Open a CLMM position on `params.pool_id` over `params.range`,
+    /// depositing `params.amount_0` and `params.amount_1`.
+    ///
+    /// The deposits, net of their Token-2022 transfer fees, size the
+    /// liquidity at the current pool price. The instruction allows the
+    /// amounts that liquidity takes plus `params.slippage_bps` and transfer
+    /// fees, so up to the slippage more than the given amounts may be
+    /// deposited. A SOL deposit is wrapped first. The position NFT is minted
+    /// to the owner from a new keypair, signing along with the owner.
+    pub async fn open_position(
+        &self,
+        params: &OpenPositionParams,
+    ) -> anyhow::Result<OpenedPosition> {
+        validation::validate_slippage_bps(params.slippage_bps)?;
+        let pool_state = self.get_pool_state(&params.pool_id).await?;
+        let tick_spacing = pool_state.tick_spacing;
+        let (tick_lower, tick_upper) = params.range.ticks(
+            tick_spacing,
+            pool_state.mint_decimals_0,
+            pool_state.mint_decimals_1,
+        )?;
+        let mints = self.pool_mint_accounts(&pool_state).await?;
+        let epoch = self.get_epoch().await?;
+        let (liquidity, amounts_max) = position_deposit(
+            &pool_state,
+            &mints,
+            epoch,
+            (tick_lower, tick_upper),
+            [params.amount_0, params.amount_1],
+            params.slippage_bps,
+        )?;
+
+        let owner = self.owner.pubkey();
+        let mut instructions = self.wrap_deposit_instructions(&mints, amounts_max)?;
+        let [token_account_0, token_account_1] = mints.each_ref().map(|(mint, account)| {
+            get_associated_token_address_with_program_id(&owner, mint, &account.owner)
+        });
+        let nft_mint = Keypair::new();
+        let accounts = OpenPositionAccounts {
+            payer: owner,
+            nft_owner: owner,
+            nft_mint: nft_mint.pubkey(),
+            pool_id: params.pool_id,
+            token_account_0,
+            token_account_1,
+            token_vault_0: Pubkey::from(pool_state.token_vault_0.to_bytes()),
+            token_vault_1: Pubkey::from(pool_state.token_vault_1.to_bytes()),
+            vault_0_mint: mints[0].0,
+            vault_1_mint: mints[1].0,
+            tick_array_bitmap_extension: position_tick_array_bitmap_extension(
+                &params.pool_id,
+                &pool_state,
+                tick_lower,
+                tick_upper,
+                &self.clmm_program_id(),
+            ),
+        };
+        let args = OpenPositionArgs {
+            tick_lower,
+            tick_upper,
+            tick_spacing,
+            liquidity,
+            amount_0_max: amounts_max[0],
+            amount_1_max: amounts_max[1],
+            with_metadata: params.with_metadata,
+        };
+        instructions.push(open_position_instruction(
+            params.nft,
+            &accounts,
+            &args,
+            &self.cluster_config.clmm,
+        ));
+
+        let message = self
+            .swap_message(&instructions, &[], self.priority_fee.as_ref(), None)
+            .await?;
+        let signature = self.send_message_with(message, &[&nft_mint]).await?;
+        info!(
+            "Opened position {} in ticks {tick_lower}..{tick_upper}, signature {signature}",
+            nft_mint.pubkey()
+        );
+        Ok(OpenedPosition {
+            signature,
+            nft_mint: nft_mint.pubkey(),
+            personal_position: Pubkey::from(
+                derive_personal_position_key(
+                    &solana_pubkey::Pubkey::from(nft_mint.pubkey().to_bytes()),
+                    &self.clmm_program_id(),
+                )
+                .to_bytes(),
+            ),
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount_0_max: amounts_max[0],
+            amount_1_max: amounts_max[1],
+        })
+    }
+
+    /// Add liquidity to the owner's CLMM position of `params.nft_mint`,
+    /// depositing `params.amount_0` and `params.amount_1`.
+    ///
+    /// The deposits are sized and limited like in [`Self::open_position`],
+    /// in the range of the position.
+    pub async fn increase_liquidity(
+        &self,
+        params: &IncreaseLiquidityParams,
+    ) -> anyhow::Result<LiquidityChange> {
+        validation::validate_slippage_bps(params.slippage_bps)?;
+        let (position, nft_account, _) = self.get_owned_position(&params.nft_mint).await?;
+        let pool_id = Pubkey::from(position.pool_id.to_bytes());
+        let pool_state = self.get_pool_state(&pool_id).await?;
+        let mints = self.pool_mint_accounts(&pool_state).await?;
+        let epoch = self.get_epoch().await?;
+        let (liquidity, amounts_max) = position_deposit(
+            &pool_state,
+            &mints,
+            epoch,
+            (position.tick_lower_index, position.tick_upper_index),
+            [params.amount_0, params.amount_1],
+            params.slippage_bps,
+        )?;
+
+        let mut instructions = self.wrap_deposit_instructions(&mints, amounts_max)?;
+        let accounts =
+            self.position_liquidity_accounts(&position, nft_account, &pool_state, &mints);
+        instructions.push(increase_liquidity_instruction(
+            &accounts,
+            liquidity,
+            amounts_max[0],
+            amounts_max[1],
+            &self.cluster_config.clmm,
+        ));
+        let signature = self
+            .send_and_sign_with_lookup_tables(&instructions, &[], None)
+            .await?;
+        Ok(LiquidityChange {
+            signature,
+            liquidity,
+            amount_0_limit: amounts_max[0],
+            amount_1_limit: amounts_max[1],
+        })
+    }
+
+    /// Remove liquidity from the owner's CLMM position of `params.nft_mint`,
+    /// all of it when `params.liquidity` is `None`.
+    ///
+    /// The minimum amounts received are the amounts of the liquidity at the
+    /// current pool price, less `params.slippage_bps` and the transfer fees
+    /// of the mints. The fees and rewards owed to the position are collected
+    /// along, to token accounts created when missing. The position stays
+    /// open, even when emptied.
+    pub async fn decrease_liquidity(
+        &self,
+        params: &DecreaseLiquidityParams,
+    ) -> anyhow::Result<LiquidityChange> {
+        validation::validate_slippage_bps(params.slippage_bps)?;
+        let (position, nft_account, _) = self.get_owned_position(&params.nft_mint).await?;
+        let position_liquidity = position.liquidity;
+        let liquidity = params.liquidity.unwrap_or(position_liquidity);
+        if liquidity == 0 || liquidity > position_liquidity {
+            return Err(anyhow!(
+                "Cannot remove {liquidity} liquidity from position {} holding {position_liquidity}",
+                params.nft_mint
+            ));
+        }
+        let pool_id = Pubkey::from(position.pool_id.to_bytes());
+        let pool_state = self.get_pool_state(&pool_id).await?;
+        let mints = self.pool_mint_accounts(&pool_state).await?;
+        let epoch = self.get_epoch().await?;
+        let amounts_min = position_withdrawal(
+            &pool_state,
+            &mints,
+            epoch,
+            &position,
+            liquidity,
+            params.slippage_bps,
+        )?;
+        let instructions = self
+            .decrease_liquidity_instructions(
+                &position,
+                nft_account,
+                &pool_state,
+                &mints,
+                liquidity,
+                amounts_min,
+            )
+            .await?;
+        let signature = self
+            .send_and_sign_with_lookup_tables(&instructions, &[], None)
+            .await?;
+        Ok(LiquidityChange {
+            signature,
+            liquidity,
+            amount_0_limit: amounts_min[0],
+            amount_1_limit: amounts_min[1],
+        })
+    }
+
+    /// Collect the fees and rewards owed to the owner's CLMM position of
+    /// `nft_mint`, to token accounts created when missing, leaving its
+    /// liquidity untouched.
+    pub async fn collect_fees_and_rewards(
+        &self,
+        nft_mint: &Pubkey,
+    ) -> anyhow::Result<CollectedFees> {
+        let (position, nft_account, _) = self.get_owned_position(nft_mint).await?;
+        let pool_id = Pubkey::from(position.pool_id.to_bytes());
+        let pool_state = self.get_pool_state(&pool_id).await?;
+        let fees = self.get_position_fees(&position, &pool_state).await?;
+        if fees.is_zero() {
+            return Err(anyhow!("Position {nft_mint} is owed no fees or rewards"));
+        }
+        let mints = self.pool_mint_accounts(&pool_state).await?;
+        let instructions = self
+            .decrease_liquidity_instructions(&position, nft_account, &pool_state, &mints, 0, [0, 0])
+            .await?;
+        let signature = self
+            .send_and_sign_with_lookup_tables(&instructions, &[], None)
+            .await?;
+        Ok(CollectedFees { signature, fees })
+    }
+
+    /// Close the owner's CLMM position of `params.nft_mint` and burn its
+    /// NFT, refunding the rent of the position.
+    ///
+    /// Liquidity left in the position is removed first, for the minimum
+    /// amounts of [`Self::decrease_liquidity`], and owed fees and rewards
+    /// are collected, in the same transaction.
+    pub async fn close_position(
+        &self,
+        params: &ClosePositionParams,
+    ) -> anyhow::Result<ClosedPosition> {
+        validation::validate_slippage_bps(params.slippage_bps)?;
+        let (position, nft_account, nft_token_program) =
+            self.get_owned_position(&params.nft_mint).await?;
+        let pool_id = Pubkey::from(position.pool_id.to_bytes());
+        let pool_state = self.get_pool_state(&pool_id).await?;
+        let fees = self.get_position_fees(&position, &pool_state).await?;
+        let liquidity = position.liquidity;
+
+        let mut amounts_min = [0, 0];
+        let mut instructions = Vec::new();
+        if liquidity > 0 || !fees.is_zero() {
+            let mints = self.pool_mint_accounts(&pool_state).await?;
+            if liquidity > 0 {
+                let epoch = self.get_epoch().await?;
+                amounts_min = position_withdrawal(
+                    &pool_state,
+                    &mints,
+                    epoch,
+                    &position,
+                    liquidity,
+                    params.slippage_bps,
+                )?;
+            }
+            instructions = self
+                .decrease_liquidity_instructions(
+                    &position,
+                    nft_account,
+                    &pool_state,
+                    &mints,
+                    liquidity,
+                    amounts_min,
+                )
+                .await?;
+        }
+        instructions.push(close_position_instruction(
+            self.owner.pubkey(),
+            params.nft_mint,
+            nft_account,
+            nft_token_program,
+            &self.cluster_config.clmm,
+        ));
+        let signature = self
+            .send_and_sign_with_lookup_tables(&instructions, &[], None)
+            .await?;
+        info!(
+            "Closed position {} holding {liquidity} liquidity, signature {signature}",
+            params.nft_mint
+        );
+        Ok(ClosedPosition {
+            signature,
+            liquidity,
+            amount_0_min: amounts_min[0],
+            amount_1_min: amounts_min[1],
+            fees,
+        })
+    }
+
+    /// Fees and rewards owed to `position` of the pool `pool_state`, from the
+    /// tick states of its range bounds.
+    async fn get_position_fees(
+        &self,
+        position: &PersonalPositionState,
+        pool_state: &PoolState,
+    ) -> anyhow::Result<PositionFees> {
+        let pool_id = solana_pubkey::Pubkey::from(position.pool_id.to_bytes());
+        let tick_spacing = pool_state.tick_spacing;
+        let ticks = [position.tick_lower_index, position.tick_upper_index];
+        let tick_array_keys = ticks.map(|tick| {
+            let start_index = TickArrayState::get_array_start_index(tick, tick_spacing);
+            Pubkey::from(
+                derive_tick_array_key(&pool_id, start_index, &self.clmm_program_id()).to_bytes(),
+            )
+        });
+        let accounts = self.get_multiple_accounts(&tick_array_keys).await?;
+        let mut tick_states = Vec::with_capacity(ticks.len());
+        for ((tick, key), account) in ticks.into_iter().zip(tick_array_keys).zip(accounts) {
+            let account = account.ok_or(anyhow!("Tick array {key} was not found by rpc"))?;
+            let tick_array = deserialize_anchor_account::<TickArrayState>(&account)?;
+            tick_states.push(
+                tick_array
+                    .get_tick_state(tick, tick_spacing)
+                    .map_err(|e| anyhow!("{e:?}"))?,
+            );
+        }
+        Ok(position_fees(
+            position,
+            pool_state,
+            &tick_states[0],
+            &tick_states[1],
+        ))
+    }
+
+    /// Instructions removing `liquidity` from `position` for at least
+    /// `amounts_min` and collecting its fees and rewards, preceded by the
+    /// creation of the missing token accounts of the owner.
+    async fn decrease_liquidity_instructions(
+        &self,
+        position: &PersonalPositionState,
+        nft_account: Pubkey,
+        pool_state: &PoolState,
+        mints: &[(Pubkey, Account); 2],
+        liquidity: u128,
+        amounts_min: [u64; 2],
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let owner = self.owner.pubkey();
+        let reward_infos = pool_state.reward_infos;
+        let reward_mints: Vec<Pubkey> = reward_infos
+            .iter()
+            .filter(|reward_info| reward_info.initialized())
+            .map(|reward_info| Pubkey::from(reward_info.token_mint.to_bytes()))
+            .collect();
+        let reward_mint_accounts = self.get_multiple_accounts(&reward_mints).await?;
+        let mut rewards = Vec::with_capacity(reward_mints.len());
+        for (reward_info, account) in reward_infos
+            .iter()
+            .filter(|reward_info| reward_info.initialized())
+            .zip(reward_mint_accounts)
+        {
+            let reward_mint = Pubkey::from(reward_info.token_mint.to_bytes());
+            let account =
+                account.ok_or(anyhow!("Reward mint {reward_mint} was not found by rpc"))?;
+            rewards.push((
+                RewardAccounts {
+                    reward_vault: Pubkey::from(reward_info.token_vault.to_bytes()),
+                    recipient_token_account: get_associated_token_address_with_program_id(
+                        &owner,
+                        &reward_mint,
+                        &account.owner,
+                    ),
+                    reward_mint,
+                },
+                account.owner,
+            ));
+        }
+        let mut instructions: Vec<Instruction> = mints
+            .iter()
+            .map(|(mint, account)| (*mint, account.owner))
+            .chain(
+                rewards
+                    .iter()
+                    .map(|(reward, token_program)| (reward.reward_mint, *token_program)),
+            )
+            .map(|(mint, token_program)| {
+                create_associated_token_account_idempotent(&owner, &owner, &mint, &token_program)
+            })
+            .collect();
+        let accounts = self.position_liquidity_accounts(position, nft_account, pool_state, mints);
+        let rewards: Vec<RewardAccounts> = rewards.into_iter().map(|(reward, _)| reward).collect();
+        instructions.push(decrease_liquidity_instruction(
+            &accounts,
+            liquidity,
+            amounts_min[0],
+            amounts_min[1],
+            &rewards,
+            &self.cluster_config.clmm,
+        ));
+        Ok(instructions)
+    }
+
+    /// Personal position of the position NFT `nft_mint`, with the owner's
+    /// token account holding the NFT and the token program of the NFT.
+    async fn get_owned_position(
+        &self,
+        nft_mint: &Pubkey,
+    ) -> anyhow::Result<(PersonalPositionState, Pubkey, Pubkey)> {
+        let personal_position = Pubkey::from(
+            derive_personal_position_key(
+                &solana_pubkey::Pubkey::from(nft_mint.to_bytes()),
+                &self.clmm_program_id(),
+            )
+            .to_bytes(),
+        );
+        let accounts = self
+            .get_multiple_accounts(&[personal_position, *nft_mint])
+            .await?;
+        let [Some(position), Some(nft_mint_account)] = <[_; 2]>::try_from(accounts)
+            .map_err(|_| anyhow!("Unexpected number of accounts returned"))?
+        else {
+            return Err(anyhow!("Position {nft_mint} was not found by rpc"));
+        };
+        let position = deserialize_anchor_account::<PersonalPositionState>(&position)?;
+        let nft_account = get_associated_token_address_with_program_id(
+            &self.owner.pubkey(),
+            nft_mint,
+            &nft_mint_account.owner,
+        );
+        Ok((position, nft_account, nft_mint_account.owner))
+    }
+
+    /// Mints of `pool_state` with their accounts, token 0 first.
+    async fn pool_mint_accounts(
+        &self,
+        pool_state: &PoolState,
+    ) -> anyhow::Result<[(Pubkey, Account); 2]> {
+        let mints = [
+            Pubkey::from(pool_state.token_mint_0.to_bytes()),
+            Pubkey::from(pool_state.token_mint_1.to_bytes()),
+        ];
+        let accounts = self.get_multiple_accounts(&mints).await?;
+        let [Some(account_0), Some(account_1)] = <[_; 2]>::try_from(accounts)
+            .map_err(|_| anyhow!("Unexpected number of accounts returned"))?
+        else {
+            return Err(anyhow!(
+                "Mints {} and {} were not found by rpc",
+                mints[0],
+                mints[1]
+            ));
+        };
+        Ok([(mints[0], account_0), (mints[1], account_1)])
+    }
+
+    /// Instructions wrapping the SOL deposit among `amounts` into the owner's
+    /// wSOL account.
+    fn wrap_deposit_instructions(
+        &self,
+        mints: &[(Pubkey, Account); 2],
+        amounts: [u64; 2],
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let mut instructions = Vec::new();
+        for ((mint, _), amount) in mints.iter().zip(amounts) {
+            if *mint == spl_token::native_mint::id() && amount > 0 {
+                instructions.extend(self.wrap_sol_instructions(amount)?);
+            }
+        }
+        Ok(instructions)
+    }
+
+    /// Accounts changing the liquidity of `position` through the owner's
+    /// associated token accounts.
+    fn position_liquidity_accounts(
+        &self,
+        position: &PersonalPositionState,
+        nft_account: Pubkey,
+        pool_state: &PoolState,
+        mints: &[(Pubkey, Account); 2],
+    ) -> PositionLiquidityAccounts {
+        let owner = self.owner.pubkey();
+        let pool_id = Pubkey::from(position.pool_id.to_bytes());
+        let [token_account_0, token_account_1] = mints.each_ref().map(|(mint, account)| {
+            get_associated_token_address_with_program_id(&owner, mint, &account.owner)
+        });
+        PositionLiquidityAccounts {
+            nft_owner: owner,
+            nft_account,
+            nft_mint: Pubkey::from(position.nft_mint.to_bytes()),
+            pool_id,
+            tick_lower: position.tick_lower_index,
+            tick_upper: position.tick_upper_index,
+            tick_spacing: pool_state.tick_spacing,
+            token_account_0,
+            token_account_1,
+            token_vault_0: Pubkey::from(pool_state.token_vault_0.to_bytes()),
+            token_vault_1: Pubkey::from(pool_state.token_vault_1.to_bytes()),
+            vault_0_mint: mints[0].0,
+            vault_1_mint: mints[1].0,
+            tick_array_bitmap_extension: position_tick_array_bitmap_extension(
+                &pool_id,
+                pool_state,
+                position.tick_lower_index,
+                position.tick_upper_index,
+                &self.clmm_program_id(),
+            ),
+        }
+    }
+
+    pub async fn calculate_swap_change_clmm(
+        &self,
+        params: ClmmSwapParams,
+    ) -> anyhow::Result<(ClmmSwapChangeResult, solana_pubkey::Pubkey)> {
+        let epoch = self.get_epoch().await?;
+        self.calculate_swap_change_clmm_at_epoch(params, epoch)
+            .await
+    }
+
+    /// Same as [`Self::calculate_swap_change_clmm`], with Token-2022 transfer
+    /// fees of `epoch`, e.g. fetched once for a batch of swaps.
+    pub async fn calculate_swap_change_clmm_at_epoch(
+        &self,
+        params: ClmmSwapParams,
+        epoch: u64,
+    ) -> anyhow::Result<(ClmmSwapChangeResult, solana_pubkey::Pubkey)> {
+        validation::validate_amount(params.amount_specified)?;
+        validation::validate_slippage_bps(params.slippage_bps)?;
+        let base_in = !params.base_out;
+        let clmm_pubkey = self.clmm_program_id();
+        let tickarray_bitmap_extension =
+            derive_tick_array_bitmap_extension_key(&params.pool_id, &clmm_pubkey);
+
+        // todo add sync
+        let result = clmm_utils::calculate_swap_change_at_epoch(
+            &self.rpc_client,
             clmm_pubkey,
             params.pool_id,
             tickarray_bitmap_extension,
@@ -678,8 +3952,12 @@ impl AmmSwapClient {
             params.limit_price,
             base_in,
             params.slippage_bps,
+            epoch,
         )
         .await?;
+        if let Some(max_price_impact_bps) = params.max_price_impact_bps {
+            check_price_impact(result.quote.price_impact, max_price_impact_bps)?;
+        }
         Ok((result, tickarray_bitmap_extension))
     }
 
@@ -688,11 +3966,90 @@ impl AmmSwapClient {
     }
 
     pub async fn get_pool_state(&self, pool_id: &Pubkey) -> anyhow::Result<PoolState> {
-        rpc::get_anchor_account::<PoolState>(&self.rpc_client, pool_id)
+        self.get_cached_anchor_account::<PoolState>(pool_id)
             .await?
             .ok_or(anyhow!("Pool state was not found by rpc"))
     }
 
+    /// Same as [`Self::check_amm_swap_enabled`] for the CLMM pool `pool_id`,
+    /// see [`clmm_utils::validate_swap_enabled`].
+    pub async fn check_clmm_swap_enabled(&self, pool_id: &Pubkey) -> anyhow::Result<()> {
+        let pool_state = self.get_pool_state(pool_id).await?;
+        clmm_utils::validate_swap_enabled(
+            &solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+            &pool_state,
+            unix_timestamp()?,
+        )
+    }
+
+    /// Fetch the oracle observation account of a CLMM pool.
+    pub async fn get_observation_state(
+        &self,
+        pool_id: &Pubkey,
+    ) -> anyhow::Result<ObservationState> {
+        let pool_state = self.get_pool_state(pool_id).await?;
+        self.get_pool_observation_state(&pool_state).await
+    }
+
+    /// Fetch the oracle observation account of `pool_state`.
+    async fn get_pool_observation_state(
+        &self,
+        pool_state: &PoolState,
+    ) -> anyhow::Result<ObservationState> {
+        let observation_key = Pubkey::from(pool_state.observation_key.to_bytes());
+        rpc::get_anchor_account::<ObservationState>(&self.rpc_client, &observation_key)
+            .await?
+            .ok_or(anyhow!("Observation state was not found by rpc"))
+    }
+
+    /// Initialized oracle observations of a CLMM pool, ordered from the
+    /// oldest to the newest.
+    pub async fn fetch_observations(&self, pool_id: &Pubkey) -> anyhow::Result<Vec<Observation>> {
+        Ok(self
+            .get_observation_state(pool_id)
+            .await?
+            .ordered_observations())
+    }
+
+    /// Time-weighted average price of a CLMM pool over `window` up to its
+    /// newest oracle observation, see [`time_weighted_average_price`].
+    pub async fn compute_twap(
+        &self,
+        pool_id: &Pubkey,
+        window: Duration,
+    ) -> anyhow::Result<TwapEstimate> {
+        let pool_state = self.get_pool_state(pool_id).await?;
+        let observation_state = self.get_pool_observation_state(&pool_state).await?;
+        time_weighted_average_price(
+            &observation_state.ordered_observations(),
+            window.as_secs(),
+            pool_state.mint_decimals_0,
+            pool_state.mint_decimals_1,
+        )
+    }
+
+    /// Estimate realized volatility of a CLMM pool over `window` from its
+    /// oracle observation ring buffer.
+    pub async fn estimate_volatility(
+        &self,
+        pool_id: &Pubkey,
+        window: Duration,
+    ) -> anyhow::Result<VolatilityEstimate> {
+        let observation_state = self.get_observation_state(pool_id).await?;
+        realized_volatility(
+            &observation_state.ordered_observations(),
+            window.as_secs(),
+        )
+    }
+
+    /// Report the ticks crossed by the CLMM swaps of a confirmed transaction.
+    pub async fn analyze_clmm_swap(
+        &self,
+        signature: &Signature,
+    ) -> anyhow::Result<Vec<TickCrossingReport>> {
+        analyze_swap_tick_crossings(&self.rpc_client, signature, &self.cluster_config.clmm).await
+    }
+
     pub async fn get_rsps(
         &self,
         input_token: solana_pubkey::Pubkey,
@@ -710,45 +4067,462 @@ impl AmmSwapClient {
         .map(|pubkey| Address::from(pubkey.to_bytes()))
         .collect();
 
-        Ok(self
-            .rpc_client
-            .get_multiple_accounts(&load_accounts)
-            .await?)
+        self.get_cached_accounts(&load_accounts).await
     }
 
-    pub fn get_tick_array_bitmap_extension(pool_id: &Address) -> solana_pubkey::Pubkey {
-        let tickarray_bitmap_extension = Pubkey::find_program_address(
-            &[
-                POOL_TICK_ARRAY_BITMAP_SEED.as_bytes(),
-                pool_id.to_bytes().as_ref(),
-            ],
-            &Pubkey::from_str_const(CLMM),
+    pub async fn load_cur_and_next_five_tick_array(
+        &self,
+        raydium_v3_program: solana_pubkey::Pubkey,
+        pool_id: solana_pubkey::Pubkey,
+        pool_state: &PoolState,
+        tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
+        zero_for_one: bool,
+    ) -> anyhow::Result<TickArrays> {
+        self.load_tick_arrays(
+            raydium_v3_program,
+            pool_id,
+            pool_state,
+            tickarray_bitmap_extension,
+            zero_for_one,
+            DEFAULT_TICK_ARRAY_COUNT,
         )
-        .0;
-
-        solana_pubkey::Pubkey::from(tickarray_bitmap_extension.to_bytes())
+        .await
     }
 
-    pub async fn load_cur_and_next_five_tick_array(
+    /// Load up to `max_tick_arrays` initialized tick arrays of a CLMM pool in
+    /// swap direction, starting with the array of the current tick. Large
+    /// swaps that fail with [`SwapError::InsufficientLiquidityLoaded`] can be
+    /// quoted again with `additional_tick_arrays` more.
+    pub async fn load_tick_arrays(
         &self,
         raydium_v3_program: solana_pubkey::Pubkey,
         pool_id: solana_pubkey::Pubkey,
         pool_state: &PoolState,
-        tickarray_bitmap_extension: &TickArrayBitmapExtension,
+        tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
         zero_for_one: bool,
+        max_tick_arrays: usize,
     ) -> anyhow::Result<TickArrays> {
-        let tick_array_keys = get_tick_array_keys(
+        let tick_array_keys = get_tick_array_keys_up_to(
             raydium_v3_program,
             pool_id,
             pool_state,
             tickarray_bitmap_extension,
             zero_for_one,
+            max_tick_arrays,
         )?;
         let tick_array_rsps =
             clmm_utils::get_tick_array_rsps(&self.rpc_client, &tick_array_keys).await?;
         get_tick_arrays(tick_array_rsps)
     }
 
+    /// Load the states of a CLMM pool once for quoting many amounts with
+    /// [`ClmmQuoteLadder::quote`].
+    pub async fn load_clmm_quote_ladder(
+        &self,
+        pool_id: &Pubkey,
+        zero_for_one: bool,
+    ) -> anyhow::Result<ClmmQuoteLadder> {
+        let pool_state = self.get_pool_state(pool_id).await?;
+        self.clmm_quote_ladder(pool_id, pool_state, zero_for_one)
+            .await
+    }
+
+    /// Same as [`Self::load_clmm_quote_ladder`], in the direction selling
+    /// `input_mint`.
+    pub async fn load_clmm_quote_ladder_for_input(
+        &self,
+        pool_id: &Pubkey,
+        input_mint: &Pubkey,
+    ) -> anyhow::Result<ClmmQuoteLadder> {
+        let pool_state = self.get_pool_state(pool_id).await?;
+        let (token_mint_0, token_mint_1) = (pool_state.token_mint_0, pool_state.token_mint_1);
+        let zero_for_one = if token_mint_0.to_bytes() == input_mint.to_bytes() {
+            true
+        } else if token_mint_1.to_bytes() == input_mint.to_bytes() {
+            false
+        } else {
+            return Err(ValidationError::MintNotInPool {
+                pool_id: pool_id.to_string(),
+                mint: input_mint.to_string(),
+            }
+            .into());
+        };
+        self.clmm_quote_ladder(pool_id, pool_state, zero_for_one)
+            .await
+    }
+
+    async fn clmm_quote_ladder(
+        &self,
+        pool_id: &Pubkey,
+        pool_state: PoolState,
+        zero_for_one: bool,
+    ) -> anyhow::Result<ClmmQuoteLadder> {
+        let ladder = self
+            .read_clmm_quote_ladder(pool_id, pool_state, zero_for_one)
+            .await?;
+        // Tick arrays are never cached, so only the other accounts can be
+        // stale copies.
+        let (Some(policy), Some(cache)) = (self.staleness_policy, &self.account_cache) else {
+            return Ok(ladder);
+        };
+        let stale = self
+            .stale_clmm_accounts(pool_id, &ladder, &policy, cache)
+            .await?;
+        for account in &stale {
+            warn!("Stale CLMM account: {account}");
+        }
+        // Epoch lags are only logged: a quiet pool read again keeps its old
+        // `recent_epoch`.
+        let outdated: Vec<&StaleAccount> = stale
+            .iter()
+            .filter(|account| matches!(account.staleness, Staleness::SlotLag { .. }))
+            .collect();
+        if outdated.is_empty() || !policy.refresh {
+            return Ok(ladder);
+        }
+        for account in outdated {
+            cache.invalidate(&account.address);
+        }
+        let pool_state = self.get_pool_state(pool_id).await?;
+        self.read_clmm_quote_ladder(pool_id, pool_state, zero_for_one)
+            .await
+    }
+
+    /// Cached accounts of `ladder` that are stale under `policy`.
+    async fn stale_clmm_accounts(
+        &self,
+        pool_id: &Pubkey,
+        ladder: &ClmmQuoteLadder,
+        policy: &StalenessPolicy,
+        cache: &AccountCache,
+    ) -> anyhow::Result<Vec<StaleAccount>> {
+        let cached = [
+            *pool_id,
+            Pubkey::from(ladder.pool_state.amm_config.to_bytes()),
+            self.tick_array_bitmap_extension_key(pool_id),
+        ];
+        let read_slots: Vec<(Pubkey, u64)> = cached
+            .into_iter()
+            .filter_map(|address| Some((address, cache.read_slot(&address)?)))
+            .collect();
+        if read_slots.is_empty() {
+            return Ok(Vec::new());
+        }
+        let epoch_info = self
+            .retry_policy
+            .retry(
+                move || self.rpc_client.get_epoch_info(),
+                is_transient_client_error,
+            )
+            .await?;
+        let mut stale = Vec::new();
+        for (address, read_slot) in read_slots {
+            stale.extend(policy.check_slot(&address, read_slot, epoch_info.absolute_slot));
+            if address == *pool_id {
+                let recent_epoch = ladder.pool_state.recent_epoch;
+                stale.extend(policy.check_epoch(&address, recent_epoch, epoch_info.epoch));
+            }
+        }
+        Ok(stale)
+    }
+
+    async fn read_clmm_quote_ladder(
+        &self,
+        pool_id: &Pubkey,
+        pool_state: PoolState,
+        zero_for_one: bool,
+    ) -> anyhow::Result<ClmmQuoteLadder> {
+        let amm_config_key = Pubkey::from(pool_state.amm_config.to_bytes());
+        let amm_config = self
+            .get_cached_anchor_account::<AmmConfig>(&amm_config_key)
+            .await?
+            .ok_or(anyhow!("Amm config was not found by rpc"))?;
+        let bitmap_extension_key = self.tick_array_bitmap_extension_key(pool_id);
+        let tickarray_bitmap_extension = self
+            .get_cached_anchor_account::<TickArrayBitmapExtension>(&bitmap_extension_key)
+            .await?;
+        let tick_arrays = self
+            .load_cur_and_next_five_tick_array(
+                self.clmm_program_id(),
+                solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+                &pool_state,
+                &tickarray_bitmap_extension,
+                zero_for_one,
+            )
+            .await?;
+        Ok(ClmmQuoteLadder {
+            pool_state,
+            amm_config,
+            tickarray_bitmap_extension,
+            tick_arrays: tick_arrays.into(),
+            zero_for_one,
+        })
+    }
+
+    /// Quote swapping `amount_in` of `input_mint` on any pool type, with
+    /// `slippage` as a fraction (e.g. `0.005` for 0.5%).
+    ///
+    /// CLMM quotes ignore Token-2022 transfer fees, like [`compute_swap`].
+    ///
+    /// [`compute_swap`]: crate::clmm::compute_swap
+    pub async fn quote(
+        &self,
+        pool: &PoolRef<'_>,
+        input_mint: &Pubkey,
+        amount_in: u64,
+        slippage: f64,
+    ) -> anyhow::Result<SwapQuote> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_slippage(slippage)?;
+        match *pool {
+            PoolRef::Amm(pool_info) => {
+                validation::validate_pool_mint(pool_info, &input_mint.to_string())?;
+                let pool_id: Pubkey = pool_info.id.parse()?;
+                let rpc_pool_info = self.get_rpc_pool_info(&pool_id).await?;
+                self.amm_swap_quote(pool_info, rpc_pool_info, input_mint, amount_in, slippage)
+            }
+            PoolRef::Clmm(pool_id) => {
+                let ladder = self
+                    .load_clmm_quote_ladder_for_input(&pool_id, input_mint)
+                    .await?;
+                clmm_swap_quote(
+                    &pool_id,
+                    &ladder,
+                    input_mint,
+                    amount_in,
+                    slippage,
+                    &self.clmm_program_id(),
+                )
+            }
+        }
+    }
+
+    /// Same as [`Self::quote`] on each of `pools`, AMM v4, CLMM or CPMM pools
+    /// by id, to pick the best venue. The accounts of all pools are read
+    /// together, in two rounds of batched `getMultipleAccounts` calls
+    /// whatever the number of pools: the pools and their bitmap extensions,
+    /// then the vaults, the configs and the CLMM tick arrays.
+    ///
+    /// CPMM quotes ignore creator fees and Token-2022 transfer fees, and
+    /// are reported as [`PoolType::Standard`] like the Raydium API does.
+    ///
+    /// Quotes are in the order of `pools`. A pool that cannot be quoted, e.g.
+    /// not found or not trading `input_mint`, does not fail the others.
+    pub async fn quote_many(
+        &self,
+        pools: &[Pubkey],
+        input_mint: &Pubkey,
+        amount_in: u64,
+        slippage: f64,
+    ) -> anyhow::Result<Vec<anyhow::Result<SwapQuote>>> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_slippage(slippage)?;
+        let ClusterConfig {
+            amm_v4: amm_program,
+            clmm: clmm_program,
+            cpmm: cpmm_program,
+        } = self.cluster_config;
+
+        let mut keys = pools.to_vec();
+        keys.extend(
+            pools
+                .iter()
+                .map(|pool_id| self.tick_array_bitmap_extension_key(pool_id)),
+        );
+        let accounts = self.get_all_accounts(&keys).await?;
+        let (pool_accounts, bitmap_extension_accounts) = accounts.split_at(pools.len());
+
+        let mut keys = Vec::new();
+        let pending: Vec<anyhow::Result<PendingQuote>> = pools
+            .iter()
+            .zip(pool_accounts)
+            .zip(bitmap_extension_accounts)
+            .map(|((pool_id, account), bitmap_extension_account)| {
+                let account = account
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Pool {pool_id} was not found by rpc"))?;
+                if account.owner == amm_program {
+                    let market_state = LiquidityStateLayoutV4::try_from_slice(&account.data)
+                        .map_err(|e| anyhow!("Failed to decode market state: {:?}", e))?;
+                    let vaults = keys.len();
+                    keys.extend([market_state.base_vault, market_state.quote_vault]);
+                    return Ok(PendingQuote::Amm {
+                        market_state: Box::new(market_state),
+                        vaults,
+                    });
+                }
+                if account.owner == cpmm_program {
+                    let pool_state = CpmmPoolState::try_from_account_data(&account.data)?;
+                    let accounts = keys.len();
+                    keys.extend([
+                        pool_state.token_0_vault,
+                        pool_state.token_1_vault,
+                        pool_state.amm_config,
+                    ]);
+                    return Ok(PendingQuote::Cpmm {
+                        pool_state: Box::new(pool_state),
+                        accounts,
+                    });
+                }
+                if account.owner != clmm_program {
+                    return Err(anyhow!(
+                        "Pool {pool_id} is not an AMM v4, CLMM or CPMM pool"
+                    ));
+                }
+
+                let pool_state = deserialize_anchor_account::<PoolState>(account)?;
+                let tickarray_bitmap_extension = bitmap_extension_account
+                    .as_ref()
+                    .map(deserialize_anchor_account::<TickArrayBitmapExtension>)
+                    .transpose()?;
+                let zero_for_one = if pool_state.token_mint_0.to_bytes() == input_mint.to_bytes() {
+                    true
+                } else if pool_state.token_mint_1.to_bytes() == input_mint.to_bytes() {
+                    false
+                } else {
+                    return Err(ValidationError::MintNotInPool {
+                        pool_id: pool_id.to_string(),
+                        mint: input_mint.to_string(),
+                    }
+                    .into());
+                };
+                let tick_array_keys = get_tick_array_keys(
+                    self.clmm_program_id(),
+                    solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+                    &pool_state,
+                    &tickarray_bitmap_extension,
+                    zero_for_one,
+                )?;
+                let amm_config = keys.len();
+                keys.push(Pubkey::from(pool_state.amm_config.to_bytes()));
+                keys.extend(tick_array_keys);
+                Ok(PendingQuote::Clmm {
+                    pool_state: Box::new(pool_state),
+                    tickarray_bitmap_extension: tickarray_bitmap_extension.map(Box::new),
+                    zero_for_one,
+                    amm_config,
+                    tick_arrays: amm_config + 1..keys.len(),
+                })
+            })
+            .collect();
+        let accounts = self.get_all_accounts(&keys).await?;
+
+        Ok(pools
+            .iter()
+            .zip(pending)
+            .map(|(pool_id, pending)| match pending? {
+                PendingQuote::Amm {
+                    market_state,
+                    vaults,
+                } => {
+                    let (Some(base_vault), Some(quote_vault)) =
+                        (&accounts[vaults], &accounts[vaults + 1])
+                    else {
+                        return Err(anyhow!("Vaults of pool {pool_id} were not found by rpc"));
+                    };
+                    let rpc_pool_info =
+                        RpcPoolInfo::from_accounts(&market_state, base_vault, quote_vault)?;
+                    amm_state_quote(
+                        pool_id,
+                        &market_state,
+                        rpc_pool_info,
+                        input_mint,
+                        amount_in,
+                        slippage,
+                    )
+                }
+                PendingQuote::Clmm {
+                    pool_state,
+                    tickarray_bitmap_extension,
+                    zero_for_one,
+                    amm_config,
+                    tick_arrays,
+                } => {
+                    let amm_config = accounts[amm_config]
+                        .as_ref()
+                        .ok_or(anyhow!("Amm config was not found by rpc"))?;
+                    let ladder = ClmmQuoteLadder {
+                        pool_state: *pool_state,
+                        amm_config: deserialize_anchor_account::<AmmConfig>(amm_config)?,
+                        tickarray_bitmap_extension: tickarray_bitmap_extension.map(|e| *e),
+                        tick_arrays: get_tick_arrays(accounts[tick_arrays].to_vec())?.into(),
+                        zero_for_one,
+                    };
+                    clmm_swap_quote(
+                        pool_id,
+                        &ladder,
+                        input_mint,
+                        amount_in,
+                        slippage,
+                        &self.clmm_program_id(),
+                    )
+                }
+                PendingQuote::Cpmm {
+                    pool_state,
+                    accounts: first,
+                } => {
+                    let [Some(vault_0), Some(vault_1), Some(amm_config)] =
+                        [0, 1, 2].map(|i| accounts[first + i].as_ref())
+                    else {
+                        return Err(anyhow!("Accounts of pool {pool_id} were not found by rpc"));
+                    };
+                    let amm_config = CpmmAmmConfig::try_from_account_data(&amm_config.data)?;
+                    cpmm_state_quote(
+                        pool_id,
+                        &pool_state,
+                        &amm_config,
+                        (token_amount(vault_0)?, token_amount(vault_1)?),
+                        input_mint,
+                        amount_in,
+                        slippage,
+                    )
+                }
+            })
+            .collect())
+    }
+
+    /// [`Self::quote`] of an AMM v4 pool against its fetched reserves.
+    pub(crate) fn amm_swap_quote(
+        &self,
+        pool_info: &ClmmPool,
+        rpc_pool_info: RpcPoolInfo,
+        input_mint: &Pubkey,
+        amount_in: u64,
+        slippage: f64,
+    ) -> anyhow::Result<SwapQuote> {
+        let pool_id: Pubkey = pool_info.id.parse()?;
+        let (rpc_pool_info, pool_info) =
+            orient_amm_pool(rpc_pool_info, pool_info, &input_mint.to_string())?;
+        let quote = self.compute_amount_out(&rpc_pool_info, &pool_info, amount_in, slippage)?;
+        Ok(SwapQuote {
+            pool_id,
+            pool_type: PoolType::Standard,
+            input_mint: *input_mint,
+            output_mint: pool_info.mint_b.address.parse()?,
+            amount_in,
+            amount_out: quote.amount_out,
+            min_amount_out: quote.min_amount_out,
+            price_impact: quote.price_impact,
+            fee: quote.fee,
+            fee_tier: quote.fee_tier,
+            tick_arrays: Vec::new(),
+        })
+    }
+
+    /// Quote exact input CLMM swaps of each of `amounts` against a single
+    /// load of the pool state and tick arrays. Results are in the order of
+    /// `amounts`; transfer fees and slippage are not applied.
+    pub async fn quote_ladder_clmm(
+        &self,
+        pool_id: &Pubkey,
+        zero_for_one: bool,
+        amounts: &[u64],
+    ) -> anyhow::Result<Vec<SwapComputeResult>> {
+        self.load_clmm_quote_ladder(pool_id, zero_for_one)
+            .await?
+            .quote_all(amounts)
+    }
+
     pub fn calculate_swap_change_clmm_sync(
         &self,
         params: ClmmSwapParams,
@@ -760,7 +4534,7 @@ impl AmmSwapClient {
     ) -> anyhow::Result<(ClmmSwapChangeResult, solana_pubkey::Pubkey)> {
         let base_in = !params.base_out;
 
-        let clmm_pubkey = solana_pubkey::Pubkey::from_str_const(CLMM);
+        let clmm_pubkey = self.clmm_program_id();
 
         let result = clmm_utils_sync::calculate_swap_change(
             clmm_pubkey,
@@ -775,52 +4549,191 @@ impl AmmSwapClient {
             rsps,
             tick_arrays,
         )?;
+        if let Some(max_price_impact_bps) = params.max_price_impact_bps {
+            check_price_impact(result.quote.price_impact, max_price_impact_bps)?;
+        }
         Ok((result, tickarray_bitmap_extension))
     }
 
+    /// Execute a CLMM swap. Without `user_output_token`, the output goes to
+    /// the owner's associated token account, created in the same transaction
+    /// if missing.
+    ///
+    /// A quote that is not `fully_filled` is still sent; the swap stops at the
+    /// price limit and only `amount_consumed` is swapped. A pool that does not
+    /// accept swaps is not, see [`Self::check_clmm_swap_enabled`].
     pub async fn swap_clmm(
         &self,
-        user_output_token: solana_pubkey::Pubkey,
+        user_output_token: Option<solana_pubkey::Pubkey>,
         clmm_swap_change_result: ClmmSwapChangeResult,
         tick_array_bitmap_extension: solana_pubkey::Pubkey,
     ) -> anyhow::Result<Signature> {
-        let mut instructions = Vec::new();
-        let user_output_token = Pubkey::from(user_output_token.to_bytes());
-        let mut remaining_accounts = Vec::new();
-        remaining_accounts.push(AccountMeta::new_readonly(
-            Address::from(tick_array_bitmap_extension.to_bytes()),
-            false,
-        ));
-        let mut accounts = clmm_swap_change_result
-            .remaining_tick_array_keys
-            .into_iter()
-            .map(|tick_array_address| {
-                AccountMeta::new(Address::from(tick_array_address.to_bytes()), false)
-            })
-            .collect();
-        remaining_accounts.append(&mut accounts);
-        let user_output_token = solana_pubkey::Pubkey::from(user_output_token.to_bytes());
-        let swap_instr = self.swap_v2_instr(
-            clmm_swap_change_result.pool_amm_config,
-            clmm_swap_change_result.pool_id,
-            clmm_swap_change_result.input_vault,
-            clmm_swap_change_result.output_vault,
-            clmm_swap_change_result.pool_observation,
-            clmm_swap_change_result.user_input_token,
+        self.swap_clmm_with_lookup_tables(
+            user_output_token,
+            clmm_swap_change_result,
+            tick_array_bitmap_extension,
+            &[],
+        )
+        .await
+    }
+
+    /// Same as [`Self::swap_clmm`], sent as a versioned transaction resolving
+    /// accounts through `lookup_tables`, e.g. the `lookupTableAccount` of the
+    /// pool keys returned by the API. Swaps crossing many tick arrays may not
+    /// fit in a legacy transaction otherwise.
+    pub async fn swap_clmm_with_lookup_tables(
+        &self,
+        user_output_token: Option<solana_pubkey::Pubkey>,
+        clmm_swap_change_result: ClmmSwapChangeResult,
+        tick_array_bitmap_extension: solana_pubkey::Pubkey,
+        lookup_tables: &[Pubkey],
+    ) -> anyhow::Result<Signature> {
+        self.send_swap_clmm(
+            user_output_token,
+            clmm_swap_change_result,
+            tick_array_bitmap_extension,
+            lookup_tables,
+            None,
+        )
+        .await
+    }
+
+    /// Body of [`Self::swap_clmm_with_lookup_tables`], signing with
+    /// `recent_blockhash` instead of the latest one when given, e.g. the
+    /// shared blockhash of a batch.
+    pub(crate) async fn send_swap_clmm(
+        &self,
+        user_output_token: Option<solana_pubkey::Pubkey>,
+        clmm_swap_change_result: ClmmSwapChangeResult,
+        tick_array_bitmap_extension: solana_pubkey::Pubkey,
+        lookup_tables: &[Pubkey],
+        recent_blockhash: Option<Hash>,
+    ) -> anyhow::Result<Signature> {
+        validation::validate_amount(clmm_swap_change_result.amount)?;
+        validation::validate_distinct_mints(
+            &clmm_swap_change_result.input_vault_mint.to_string(),
+            &clmm_swap_change_result.output_vault_mint.to_string(),
+        )?;
+        self.check_clmm_swap_enabled(&Pubkey::from(clmm_swap_change_result.pool_id.to_bytes()))
+            .await?;
+        if !clmm_swap_change_result.fully_filled {
+            warn!(
+                "CLMM swap on {} reaches its price limit after {} of {}",
+                clmm_swap_change_result.pool_id,
+                clmm_swap_change_result.amount_consumed,
+                clmm_swap_change_result.amount_requested
+            );
+        }
+        let funding = SwapFunding::clmm(
+            &clmm_swap_change_result,
+            &self.owner.pubkey(),
+            user_output_token,
+        );
+        let instructions = self.build_swap_clmm_instructions(
+            user_output_token,
+            clmm_swap_change_result,
+            tick_array_bitmap_extension,
+        )?;
+        self.check_swap_funds(&funding, &instructions, self.priority_fee.as_ref())
+            .await?;
+        let recent_blockhash = match recent_blockhash {
+            Some(recent_blockhash) => recent_blockhash,
+            None => self.get_latest_blockhash().await?,
+        };
+        let message = self
+            .swap_message_with_blockhash(
+                &instructions,
+                lookup_tables,
+                self.priority_fee.as_ref(),
+                funding.trade_value_lamports(),
+                recent_blockhash,
+            )
+            .await?;
+        let sig = self.send_message(message).await?;
+        info!("Executed with Signature {sig}");
+        Ok(sig)
+    }
+
+    /// Simulate [`Self::swap_clmm_with_lookup_tables`] without sending it,
+    /// returning the output decoded from the `SwapEvent` and the compute
+    /// units used. Pass no `lookup_tables` to simulate [`Self::swap_clmm`].
+    pub async fn simulate_swap_clmm(
+        &self,
+        user_output_token: Option<solana_pubkey::Pubkey>,
+        clmm_swap_change_result: ClmmSwapChangeResult,
+        tick_array_bitmap_extension: solana_pubkey::Pubkey,
+        lookup_tables: &[Pubkey],
+    ) -> anyhow::Result<SwapSimulation> {
+        validation::validate_amount(clmm_swap_change_result.amount)?;
+        let trade_value_lamports = SwapFunding::clmm(
+            &clmm_swap_change_result,
+            &self.owner.pubkey(),
+            user_output_token,
+        )
+        .trade_value_lamports();
+        let instructions = self.build_swap_clmm_instructions(
             user_output_token,
-            clmm_swap_change_result.input_vault_mint,
-            clmm_swap_change_result.output_vault_mint,
-            remaining_accounts,
-            clmm_swap_change_result.amount,
-            clmm_swap_change_result.other_amount_threshold,
-            clmm_swap_change_result.sqrt_price_limit_x64,
-            clmm_swap_change_result.is_base_input,
+            clmm_swap_change_result,
+            tick_array_bitmap_extension,
         )?;
-        instructions.extend(swap_instr);
+        let tx = self
+            .sign_swap_transaction(&instructions, lookup_tables, trade_value_lamports)
+            .await?;
+        simulate_swap_transaction(&self.rpc_client, &tx).await
+    }
 
-        self.send_and_sign_transaction(&instructions).await
+    /// Build the CLMM `SwapV2` instructions without signing or sending them,
+    /// to be appended to a transaction of the caller's. Compute budget
+    /// instructions are left to the caller.
+    ///
+    /// Without `user_output_token`, an idempotent creation of the owner's
+    /// associated token account for the output mint precedes the swap.
+    pub fn build_swap_clmm_instructions(
+        &self,
+        user_output_token: Option<solana_pubkey::Pubkey>,
+        clmm_swap_change_result: ClmmSwapChangeResult,
+        tick_array_bitmap_extension: solana_pubkey::Pubkey,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let mut instructions = Vec::new();
+        let user_output_token = match user_output_token {
+            Some(user_output_token) => Pubkey::from(user_output_token.to_bytes()),
+            None => {
+                let owner = self.owner.pubkey();
+                let output_mint =
+                    Pubkey::from(clmm_swap_change_result.output_vault_mint.to_bytes());
+                let token_program =
+                    Pubkey::from(clmm_swap_change_result.output_token_program.to_bytes());
+                instructions.push(create_associated_token_account_idempotent(
+                    &owner,
+                    &owner,
+                    &output_mint,
+                    &token_program,
+                ));
+                get_associated_token_address_with_program_id(&owner, &output_mint, &token_program)
+            }
+        };
+        let accounts = build_clmm_swap_accounts(
+            &clmm_swap_change_result,
+            &self.owner.pubkey(),
+            &user_output_token,
+            &tick_array_bitmap_extension,
+        );
+        instructions.push(Instruction {
+            program_id: self.cluster_config.clmm,
+            accounts,
+            data: swap_v2_data(
+                clmm_swap_change_result.amount,
+                clmm_swap_change_result.other_amount_threshold,
+                clmm_swap_change_result.sqrt_price_limit_x64,
+                clmm_swap_change_result.is_base_input,
+            ),
+        });
+        Ok(instructions)
     }
 
+    /// CLMM `SwapV2` instruction between explicit accounts, laid out by
+    /// [`clmm_swap_v2_accounts`]. `remaining_accounts` are the bitmap
+    /// extension when the pool has one, then the tick arrays.
     pub fn swap_v2_instr(
         &self,
         amm_config: solana_pubkey::Pubkey,
@@ -838,53 +4751,33 @@ impl AmmSwapClient {
         sqrt_price_limit_x64: Option<u128>,
         is_base_input: bool,
     ) -> anyhow::Result<Vec<Instruction>> {
-        // Build Anchor-style instruction data for SwapV2:
-        // discriminator + borsh-encoded fields.
-        let mut data = Vec::with_capacity(8 + 8 + 8 + 16 + 1);
-        data.extend_from_slice(&swap_v2_discriminator());
-        data.extend_from_slice(&amount.to_le_bytes());
-        data.extend_from_slice(&other_amount_threshold.to_le_bytes());
-        data.extend_from_slice(&sqrt_price_limit_x64.unwrap_or(0u128).to_le_bytes());
-        data.push(is_base_input as u8);
-
-        // Convert clmm Pubkey type into the Pubkey used by `AccountMeta`.
+        let data = swap_v2_data(
+            amount,
+            other_amount_threshold,
+            sqrt_price_limit_x64,
+            is_base_input,
+        );
+
         fn to_sdk_pubkey(pk: solana_pubkey::Pubkey) -> Pubkey {
             Pubkey::from(pk.to_bytes())
         }
 
-        let mut accounts: Vec<AccountMeta> = Vec::with_capacity(13 + remaining_accounts.len());
-
-        // Core SwapSingleV2 accounts in the correct order.
-        accounts.push(AccountMeta::new(self.owner.pubkey(), true)); // payer
-        accounts.push(AccountMeta::new_readonly(to_sdk_pubkey(amm_config), false));
-        accounts.push(AccountMeta::new(to_sdk_pubkey(pool_account_key), false));
-        accounts.push(AccountMeta::new(to_sdk_pubkey(user_input_token), false));
-        accounts.push(AccountMeta::new(to_sdk_pubkey(user_output_token), false));
-        accounts.push(AccountMeta::new(to_sdk_pubkey(input_vault), false));
-        accounts.push(AccountMeta::new(to_sdk_pubkey(output_vault), false));
-        accounts.push(AccountMeta::new(to_sdk_pubkey(observation_state), false));
-        accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
-        accounts.push(AccountMeta::new_readonly(
-            Address::from(spl_token_2022::id().to_bytes()),
-            false,
-        ));
-        accounts.push(AccountMeta::new_readonly(
-            Address::from(spl_memo::id().to_bytes()),
-            false,
-        ));
-        accounts.push(AccountMeta::new_readonly(
-            to_sdk_pubkey(input_vault_mint),
-            false,
-        ));
-        accounts.push(AccountMeta::new_readonly(
-            to_sdk_pubkey(output_vault_mint),
-            false,
-        ));
-
+        let keys = ClmmSwapV2Keys {
+            payer: self.owner.pubkey(),
+            amm_config: to_sdk_pubkey(amm_config),
+            pool_id: to_sdk_pubkey(pool_account_key),
+            user_input_token: to_sdk_pubkey(user_input_token),
+            user_output_token: to_sdk_pubkey(user_output_token),
+            input_vault: to_sdk_pubkey(input_vault),
+            output_vault: to_sdk_pubkey(output_vault),
+            observation_state: to_sdk_pubkey(observation_state),
+            input_vault_mint: to_sdk_pubkey(input_vault_mint),
+            output_vault_mint: to_sdk_pubkey(output_vault_mint),
+        };
         // Tick array bitmap extension and tick-array accounts.
-        accounts.extend(remaining_accounts);
+        let accounts = clmm_swap_v2_accounts(&keys, remaining_accounts);
 
-        let program_id = Pubkey::from_str_const(CLMM);
+        let program_id = self.cluster_config.clmm;
 
         Ok(vec![Instruction {
             program_id,
@@ -893,3 +4786,23 @@ impl AmmSwapClient {
         }])
     }
 }
+
+impl AmmSwapClient<WatchOnly> {
+    /// Client without a signer, for quotes, monitoring and analytics. Methods
+    /// sending a transaction fail with [`SwapError::NoSignerConfigured`].
+    pub fn watch_only(rpc_client: RpcClient) -> Self {
+        Self::new(rpc_client, WatchOnly::new())
+    }
+}
+
+// Not generic over the signer, so that it can be called as
+// `AmmSwapClient::get_tick_array_bitmap_extension`.
+impl AmmSwapClient {
+    /// Bitmap extension of a pool of the mainnet CLMM program.
+    pub fn get_tick_array_bitmap_extension(pool_id: &Address) -> solana_pubkey::Pubkey {
+        derive_tick_array_bitmap_extension_key(
+            &solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+            &clmm_program_id(),
+        )
+    }
+}
@@ -0,0 +1,315 @@
+//! Liquidity provisioning to AMM v4 pools.
+//!
+//! A `Deposit` fixes the amount of one side of the pool, its `base_side`,
+//! and takes the other side in proportion to the reserves, rounded up, up to
+//! a maximum. A `Withdraw` burns LP tokens for both sides in proportion to
+//! the reserves, rounded down. Both are quoted here against the reserves of
+//! [`RpcPoolInfo`] and the LP supply tracked by the pool, `lp_reserve`, and
+//! built from the pool keys listed by the Raydium API, program id included.
+
+use crate::amm::client::{RpcPoolInfo, amm_token_program};
+use crate::amm::{AmmInstruction, DepositInstruction, WithdrawInstruction};
+use crate::common::amount_with_slippage;
+use crate::interface::AmmPool;
+use anyhow::anyhow;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+/// `base_side` of a `Deposit` fixing the coin (base) amount.
+pub const AMM_DEPOSIT_BASE_COIN: u64 = 0;
+/// `base_side` of a `Deposit` fixing the pc (quote) amount.
+pub const AMM_DEPOSIT_BASE_PC: u64 = 1;
+
+/// Deposit into an AMM v4 pool, quoted with [`quote_amm_deposit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmmDepositQuote {
+    /// [`AMM_DEPOSIT_BASE_COIN`] or [`AMM_DEPOSIT_BASE_PC`].
+    pub base_side: u64,
+    /// Expected coin and pc deposited at the current reserves.
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    /// Limits passed to the instruction: the fixed side exactly, the other
+    /// side with slippage.
+    pub max_coin_amount: u64,
+    pub max_pc_amount: u64,
+    /// Expected LP tokens minted.
+    pub lp_amount: u64,
+}
+
+/// Withdrawal from an AMM v4 pool, quoted with [`quote_amm_withdraw`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmmWithdrawQuote {
+    /// LP tokens burned.
+    pub lp_amount: u64,
+    /// Expected coin and pc received at the current reserves.
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    /// Least coin and pc received, with slippage.
+    pub min_coin_amount: u64,
+    pub min_pc_amount: u64,
+}
+
+/// Deposit sent with [`AmmSwapClient::add_liquidity_amm`].
+///
+/// [`AmmSwapClient::add_liquidity_amm`]: crate::amm::client::AmmSwapClient::add_liquidity_amm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmmDeposit {
+    pub signature: Signature,
+    pub quote: AmmDepositQuote,
+}
+
+/// Withdrawal sent with [`AmmSwapClient::remove_liquidity_amm`].
+///
+/// [`AmmSwapClient::remove_liquidity_amm`]: crate::amm::client::AmmSwapClient::remove_liquidity_amm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmmWithdrawal {
+    pub signature: Signature,
+    pub quote: AmmWithdrawQuote,
+}
+
+/// Quote depositing `amount` of the coin (base) token when `fixed_coin`, of
+/// the pc (quote) token otherwise, into a pool with the reserves of
+/// `rpc_pool_info` and `lp_supply` LP tokens outstanding.
+pub fn quote_amm_deposit(
+    rpc_pool_info: &RpcPoolInfo,
+    lp_supply: u64,
+    fixed_coin: bool,
+    amount: u64,
+    slippage_bps: u64,
+) -> anyhow::Result<AmmDepositQuote> {
+    let (coin_reserve, pc_reserve) = (rpc_pool_info.base_reserve, rpc_pool_info.quote_reserve);
+    if coin_reserve == 0 || pc_reserve == 0 || lp_supply == 0 {
+        return Err(anyhow!("Cannot deposit into a pool with empty reserves"));
+    }
+    let (fixed_reserve, other_reserve) = if fixed_coin {
+        (coin_reserve, pc_reserve)
+    } else {
+        (pc_reserve, coin_reserve)
+    };
+    let other_amount =
+        u64::try_from((amount as u128 * other_reserve as u128).div_ceil(fixed_reserve as u128))?;
+    let lp_amount = u64::try_from(amount as u128 * lp_supply as u128 / fixed_reserve as u128)?;
+    if lp_amount == 0 {
+        return Err(anyhow!("Deposit of {amount} mints no LP tokens"));
+    }
+    let other_max = amount_with_slippage(other_amount, slippage_bps, true)?;
+    Ok(if fixed_coin {
+        AmmDepositQuote {
+            base_side: AMM_DEPOSIT_BASE_COIN,
+            coin_amount: amount,
+            pc_amount: other_amount,
+            max_coin_amount: amount,
+            max_pc_amount: other_max,
+            lp_amount,
+        }
+    } else {
+        AmmDepositQuote {
+            base_side: AMM_DEPOSIT_BASE_PC,
+            coin_amount: other_amount,
+            pc_amount: amount,
+            max_coin_amount: other_max,
+            max_pc_amount: amount,
+            lp_amount,
+        }
+    })
+}
+
+/// Quote burning `lp_amount` LP tokens of a pool with the reserves of
+/// `rpc_pool_info` and `lp_supply` LP tokens outstanding.
+pub fn quote_amm_withdraw(
+    rpc_pool_info: &RpcPoolInfo,
+    lp_supply: u64,
+    lp_amount: u64,
+    slippage_bps: u64,
+) -> anyhow::Result<AmmWithdrawQuote> {
+    if lp_amount == 0 || lp_amount > lp_supply {
+        return Err(anyhow!(
+            "Cannot withdraw {lp_amount} of {lp_supply} LP tokens"
+        ));
+    }
+    let share = |reserve: u64| (lp_amount as u128 * reserve as u128 / lp_supply as u128) as u64;
+    let (coin_amount, pc_amount) = (
+        share(rpc_pool_info.base_reserve),
+        share(rpc_pool_info.quote_reserve),
+    );
+    Ok(AmmWithdrawQuote {
+        lp_amount,
+        coin_amount,
+        pc_amount,
+        min_coin_amount: amount_with_slippage(coin_amount, slippage_bps, false)?,
+        min_pc_amount: amount_with_slippage(pc_amount, slippage_bps, false)?,
+    })
+}
+
+/// Associated token accounts of `owner` for the coin, pc and LP mints of
+/// the pool of `pool_keys`.
+fn user_token_accounts(
+    pool_keys: &AmmPool,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+) -> anyhow::Result<[Pubkey; 3]> {
+    let token_program = amm_token_program(pool_keys)?;
+    let coin_mint: Pubkey = pool_keys.mint_a.address.parse()?;
+    let pc_mint: Pubkey = pool_keys.mint_b.address.parse()?;
+    Ok([
+        get_associated_token_address_with_program_id(owner, &coin_mint, &token_program),
+        get_associated_token_address_with_program_id(owner, &pc_mint, &token_program),
+        get_associated_token_address_with_program_id(owner, lp_mint, &spl_token::id()),
+    ])
+}
+
+/// AMM v4 `Deposit` instruction of `quote` from the associated token
+/// accounts of `owner`, minting `lp_mint` of the pool of `pool_keys`.
+pub fn amm_deposit_instruction(
+    pool_keys: &AmmPool,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    quote: &AmmDepositQuote,
+) -> anyhow::Result<Instruction> {
+    let [user_token_coin, user_token_pc, user_token_lp] =
+        user_token_accounts(pool_keys, lp_mint, owner)?;
+    let data = AmmInstruction::Deposit(DepositInstruction {
+        max_coin_amount: quote.max_coin_amount,
+        max_pc_amount: quote.max_pc_amount,
+        base_side: quote.base_side,
+    })
+    .pack()?;
+    Ok(Instruction {
+        program_id: pool_keys.program_id.parse()?,
+        accounts: vec![
+            // spl token
+            AccountMeta::new_readonly(amm_token_program(pool_keys)?, false),
+            // amm
+            AccountMeta::new(pool_keys.id.parse()?, false),
+            AccountMeta::new_readonly(pool_keys.authority.parse()?, false),
+            AccountMeta::new_readonly(pool_keys.open_orders.parse()?, false),
+            AccountMeta::new(pool_keys.target_orders.parse()?, false),
+            AccountMeta::new(*lp_mint, false),
+            AccountMeta::new(pool_keys.vault.a.parse()?, false),
+            AccountMeta::new(pool_keys.vault.b.parse()?, false),
+            // market
+            AccountMeta::new_readonly(pool_keys.market_id.parse()?, false),
+            // user
+            AccountMeta::new(user_token_coin, false),
+            AccountMeta::new(user_token_pc, false),
+            AccountMeta::new(user_token_lp, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(pool_keys.market_event_queue.parse()?, false),
+        ],
+        data,
+    })
+}
+
+/// AMM v4 `Withdraw` instruction of `quote` to the associated token accounts
+/// of `owner`, burning `lp_mint` of the pool of `pool_keys` and checking the
+/// minimum amounts of the quote.
+pub fn amm_withdraw_instruction(
+    pool_keys: &AmmPool,
+    lp_mint: &Pubkey,
+    owner: &Pubkey,
+    quote: &AmmWithdrawQuote,
+) -> anyhow::Result<Instruction> {
+    let [user_token_coin, user_token_pc, user_token_lp] =
+        user_token_accounts(pool_keys, lp_mint, owner)?;
+    let data = AmmInstruction::Withdraw(WithdrawInstruction {
+        amount: quote.lp_amount,
+        min_coin_amount: Some(quote.min_coin_amount),
+        min_pc_amount: Some(quote.min_pc_amount),
+    })
+    .pack()?;
+    Ok(Instruction {
+        program_id: pool_keys.program_id.parse()?,
+        accounts: vec![
+            // spl token
+            AccountMeta::new_readonly(amm_token_program(pool_keys)?, false),
+            // amm
+            AccountMeta::new(pool_keys.id.parse()?, false),
+            AccountMeta::new_readonly(pool_keys.authority.parse()?, false),
+            AccountMeta::new(pool_keys.open_orders.parse()?, false),
+            AccountMeta::new(pool_keys.target_orders.parse()?, false),
+            AccountMeta::new(*lp_mint, false),
+            AccountMeta::new(pool_keys.vault.a.parse()?, false),
+            AccountMeta::new(pool_keys.vault.b.parse()?, false),
+            // market
+            AccountMeta::new_readonly(pool_keys.market_program_id.parse()?, false),
+            AccountMeta::new(pool_keys.market_id.parse()?, false),
+            AccountMeta::new(pool_keys.market_base_vault.parse()?, false),
+            AccountMeta::new(pool_keys.market_quote_vault.parse()?, false),
+            AccountMeta::new_readonly(pool_keys.market_authority.parse()?, false),
+            // user
+            AccountMeta::new(user_token_lp, false),
+            AccountMeta::new(user_token_coin, false),
+            AccountMeta::new(user_token_pc, false),
+            AccountMeta::new_readonly(*owner, true),
+            // market
+            AccountMeta::new(pool_keys.market_event_queue.parse()?, false),
+            AccountMeta::new(pool_keys.market_bids.parse()?, false),
+            AccountMeta::new(pool_keys.market_asks.parse()?, false),
+        ],
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool_info() -> RpcPoolInfo {
+        RpcPoolInfo {
+            base_reserve: 1_000_000,
+            quote_reserve: 4_000_000,
+            swap_fee_numerator: 25,
+            swap_fee_denominator: 10_000,
+            base_mint: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+            base_decimals: 6,
+            quote_decimals: 6,
+            pool_open_time: 0,
+        }
+    }
+
+    #[test]
+    fn deposit_takes_other_side_in_proportion() {
+        let quote = quote_amm_deposit(&pool_info(), 2_000_000, true, 1_001, 100).unwrap();
+        assert_eq!(quote.base_side, AMM_DEPOSIT_BASE_COIN);
+        assert_eq!(quote.max_coin_amount, 1_001);
+        assert_eq!(quote.pc_amount, 4_004);
+        assert_eq!(quote.max_pc_amount, 4_004 * 10_100 / 10_000);
+        assert_eq!(quote.lp_amount, 2_002);
+
+        let quote = quote_amm_deposit(&pool_info(), 2_000_000, false, 4_001, 0).unwrap();
+        assert_eq!(quote.base_side, AMM_DEPOSIT_BASE_PC);
+        assert_eq!(quote.max_pc_amount, 4_001);
+        // 4_001 / 4 rounded up.
+        assert_eq!(quote.coin_amount, 1_001);
+        assert_eq!(quote.lp_amount, 2_000);
+    }
+
+    #[test]
+    fn withdraw_rounds_down_with_slippage() {
+        let quote = quote_amm_withdraw(&pool_info(), 2_000_000, 3, 100).unwrap();
+        assert_eq!((quote.coin_amount, quote.pc_amount), (1, 6));
+        assert_eq!(quote.min_pc_amount, 6 * 9_900 / 10_000);
+        assert!(quote_amm_withdraw(&pool_info(), 2_000_000, 2_000_001, 100).is_err());
+    }
+
+    #[test]
+    fn withdraw_packs_min_amounts() {
+        let withdraw = AmmInstruction::Withdraw(WithdrawInstruction {
+            amount: 7,
+            min_coin_amount: Some(5),
+            min_pc_amount: Some(6),
+        });
+        let data = withdraw.pack().unwrap();
+        assert_eq!(data.len(), 1 + 3 * 8);
+        assert_eq!(AmmInstruction::unpack(&data).unwrap(), withdraw);
+
+        let legacy = AmmInstruction::Withdraw(WithdrawInstruction {
+            amount: 7,
+            ..Default::default()
+        });
+        assert_eq!(legacy.pack().unwrap().len(), 1 + 8);
+    }
+}
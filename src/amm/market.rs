@@ -0,0 +1,126 @@
+//! OpenBook (Serum v3) market accounts backing AMM v4 pools.
+//!
+//! An AMM v4 swap passes the accounts of the pool's market along with those
+//! of the pool. The pool state only stores the market id, so the remaining
+//! keys are read from the market account here.
+
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+
+/// Seed of the AMM v4 authority PDA, with the pool nonce as bump.
+pub const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+const MARKET_VAULT_SIGNER_NONCE_OFFSET: usize = 45;
+const MARKET_BASE_VAULT_OFFSET: usize = 117;
+const MARKET_QUOTE_VAULT_OFFSET: usize = 165;
+const MARKET_EVENT_QUEUE_OFFSET: usize = 253;
+const MARKET_BIDS_OFFSET: usize = 285;
+const MARKET_ASKS_OFFSET: usize = 317;
+const MARKET_MIN_LEN: usize = 349;
+
+/// Keys of an OpenBook market used by AMM v4 swaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenBookMarket {
+    pub vault_signer_nonce: u64,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+}
+
+impl OpenBookMarket {
+    /// Decode the data of a market account, `serum` padding included.
+    pub fn try_from_account_data(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < MARKET_MIN_LEN {
+            return Err(anyhow!(
+                "invalid OpenBook market length: expected at least {}, got {}",
+                MARKET_MIN_LEN,
+                data.len()
+            ));
+        }
+        Ok(Self {
+            vault_signer_nonce: read_u64(data, MARKET_VAULT_SIGNER_NONCE_OFFSET),
+            base_vault: read_pubkey(data, MARKET_BASE_VAULT_OFFSET),
+            quote_vault: read_pubkey(data, MARKET_QUOTE_VAULT_OFFSET),
+            event_queue: read_pubkey(data, MARKET_EVENT_QUEUE_OFFSET),
+            bids: read_pubkey(data, MARKET_BIDS_OFFSET),
+            asks: read_pubkey(data, MARKET_ASKS_OFFSET),
+        })
+    }
+
+    /// Vault signer of the market `market_id` owned by `market_program_id`.
+    pub fn vault_signer(
+        &self,
+        market_id: &Pubkey,
+        market_program_id: &Pubkey,
+    ) -> anyhow::Result<Pubkey> {
+        Pubkey::create_program_address(
+            &[market_id.as_ref(), &self.vault_signer_nonce.to_le_bytes()],
+            market_program_id,
+        )
+        .map_err(|e| anyhow!("Invalid vault signer nonce of market {market_id}: {e}"))
+    }
+}
+
+/// Authority of the pools of the AMM v4 program `program_id`, derived with
+/// the `nonce` of a pool.
+pub fn amm_authority(nonce: u64, program_id: &Pubkey) -> anyhow::Result<Pubkey> {
+    let nonce = u8::try_from(nonce).map_err(|_| anyhow!("Invalid AMM nonce {nonce}"))?;
+    Pubkey::create_program_address(&[AMM_AUTHORITY_SEED, &[nonce]], program_id)
+        .map_err(|e| anyhow!("Invalid AMM nonce {nonce}: {e}"))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::AMM_V4;
+
+    #[test]
+    fn amm_authority_matches_raydium_authority_v4() {
+        let program_id = Pubkey::from_str_const(AMM_V4);
+        let (expected, nonce) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &program_id);
+        assert_eq!(
+            expected,
+            Pubkey::from_str_const("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1")
+        );
+        assert_eq!(amm_authority(nonce as u64, &program_id).unwrap(), expected);
+        assert!(amm_authority(256, &program_id).is_err());
+    }
+
+    #[test]
+    fn market_decodes_swap_keys() {
+        let mut data = vec![0u8; 388];
+        data[..5].copy_from_slice(b"serum");
+        data[45..53].copy_from_slice(&3u64.to_le_bytes());
+        for (offset, byte) in [(117, 1), (165, 2), (253, 3), (285, 4), (317, 5)] {
+            data[offset..offset + 32].fill(byte);
+        }
+
+        let market = OpenBookMarket::try_from_account_data(&data).unwrap();
+        assert_eq!(
+            market,
+            OpenBookMarket {
+                vault_signer_nonce: 3,
+                base_vault: Pubkey::new_from_array([1; 32]),
+                quote_vault: Pubkey::new_from_array([2; 32]),
+                event_queue: Pubkey::new_from_array([3; 32]),
+                bids: Pubkey::new_from_array([4; 32]),
+                asks: Pubkey::new_from_array([5; 32]),
+            }
+        );
+        assert!(OpenBookMarket::try_from_account_data(&data[..348]).is_err());
+    }
+}
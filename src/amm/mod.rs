@@ -2,7 +2,11 @@
 
 #![allow(clippy::too_many_arguments)]
 
+pub mod builder;
 pub mod client;
+pub mod liquidity;
+pub mod market;
+pub mod status;
 
 use anchor_lang::solana_program;
 use anchor_spl::associated_token::spl_associated_token_account;
@@ -62,6 +66,10 @@ pub struct WithdrawInstruction {
     /// Pool token amount to transfer. token_a and token_b amount are set by
     /// the current exchange rate and size of the pool
     pub amount: u64,
+    /// Least coin and pc amounts to receive, checked by the program only when
+    /// both are set
+    pub min_coin_amount: Option<u64>,
+    pub min_pc_amount: Option<u64>,
 }
 
 #[repr(C)]
@@ -253,8 +261,19 @@ impl AmmInstruction {
                 })
             }
             4 => {
-                let (amount, _rest) = Self::unpack_u64(rest)?;
-                Self::Withdraw(WithdrawInstruction { amount })
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (min_coin_amount, min_pc_amount) = if rest.len() >= 16 {
+                    let (min_coin_amount, rest) = Self::unpack_u64(rest)?;
+                    let (min_pc_amount, _rest) = Self::unpack_u64(rest)?;
+                    (Some(min_coin_amount), Some(min_pc_amount))
+                } else {
+                    (None, None)
+                };
+                Self::Withdraw(WithdrawInstruction {
+                    amount,
+                    min_coin_amount,
+                    min_pc_amount,
+                })
             }
 
             9 => {
@@ -332,9 +351,19 @@ impl AmmInstruction {
                 buf.extend_from_slice(&max_pc_amount.to_le_bytes());
                 buf.extend_from_slice(&base_side.to_le_bytes());
             }
-            Self::Withdraw(WithdrawInstruction { amount }) => {
+            Self::Withdraw(WithdrawInstruction {
+                amount,
+                min_coin_amount,
+                min_pc_amount,
+            }) => {
                 buf.push(4);
                 buf.extend_from_slice(&amount.to_le_bytes());
+                if let (Some(min_coin_amount), Some(min_pc_amount)) =
+                    (min_coin_amount, min_pc_amount)
+                {
+                    buf.extend_from_slice(&min_coin_amount.to_le_bytes());
+                    buf.extend_from_slice(&min_pc_amount.to_le_bytes());
+                }
             }
 
             Self::SwapBaseIn(SwapInstructionBaseIn {
@@ -509,7 +538,11 @@ pub fn withdraw(
 
     amount: u64,
 ) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::Withdraw(WithdrawInstruction { amount }).pack()?;
+    let data = AmmInstruction::Withdraw(WithdrawInstruction {
+        amount,
+        ..Default::default()
+    })
+    .pack()?;
 
     let mut accounts = vec![
         // spl token
@@ -0,0 +1,165 @@
+//! Typed view of the status and admin fields of an AMM v4 pool.
+//!
+//! The program stores the pool status as a plain `u64` holding one of the
+//! [`AmmStatus`] values, each granting a set of permissions, rather than
+//! independent bits. [`AmmFlags`] decodes it together with the other fields
+//! the pool admin changes, so snapshots of a pool can be compared for state
+//! transitions.
+
+use crate::amm::client::LiquidityStateLayoutV4;
+use crate::error::SwapError;
+use solana_sdk::pubkey::Pubkey;
+
+/// Value of the `status` field of an AMM v4 pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmmStatus {
+    Uninitialized,
+    Initialized,
+    Disabled,
+    WithdrawOnly,
+    /// Deposits and withdrawals, no swaps.
+    LiquidityOnly,
+    /// Order book only, no deposits, withdrawals or swaps.
+    OrderBookOnly,
+    SwapOnly,
+    /// Initialized, swaps allowed once the pool open time has passed.
+    WaitingTrade,
+    /// Value unknown to this crate.
+    Unknown(u64),
+}
+
+impl AmmStatus {
+    pub fn from_u64(status: u64) -> Self {
+        match status {
+            0 => AmmStatus::Uninitialized,
+            1 => AmmStatus::Initialized,
+            2 => AmmStatus::Disabled,
+            3 => AmmStatus::WithdrawOnly,
+            4 => AmmStatus::LiquidityOnly,
+            5 => AmmStatus::OrderBookOnly,
+            6 => AmmStatus::SwapOnly,
+            7 => AmmStatus::WaitingTrade,
+            status => AmmStatus::Unknown(status),
+        }
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            AmmStatus::Uninitialized => 0,
+            AmmStatus::Initialized => 1,
+            AmmStatus::Disabled => 2,
+            AmmStatus::WithdrawOnly => 3,
+            AmmStatus::LiquidityOnly => 4,
+            AmmStatus::OrderBookOnly => 5,
+            AmmStatus::SwapOnly => 6,
+            AmmStatus::WaitingTrade => 7,
+            AmmStatus::Unknown(status) => *status,
+        }
+    }
+
+    /// Whether the program accepts swaps, before checking the open time of a
+    /// [`AmmStatus::WaitingTrade`] pool.
+    pub fn swap_permission(&self) -> bool {
+        matches!(
+            self,
+            AmmStatus::Initialized | AmmStatus::SwapOnly | AmmStatus::WaitingTrade
+        )
+    }
+
+    pub fn deposit_permission(&self) -> bool {
+        matches!(
+            self,
+            AmmStatus::Initialized | AmmStatus::LiquidityOnly | AmmStatus::WaitingTrade
+        )
+    }
+
+    pub fn withdraw_permission(&self) -> bool {
+        matches!(
+            self,
+            AmmStatus::Initialized
+                | AmmStatus::WithdrawOnly
+                | AmmStatus::LiquidityOnly
+                | AmmStatus::WaitingTrade
+        )
+    }
+}
+
+/// Status, reset flag, punishment amounts and admin of an AMM v4 pool, read
+/// with [`LiquidityStateLayoutV4::flags`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmmFlags {
+    pub status: AmmStatus,
+    /// Raw `reset_flag`; the program resets the order book while it is 0.
+    pub reset_flag: u64,
+    /// Quote token withheld from the pool after an order book punishment.
+    pub punish_pc_amount: u64,
+    /// Base token withheld from the pool after an order book punishment.
+    pub punish_coin_amount: u64,
+    /// Unix timestamp from which a [`AmmStatus::WaitingTrade`] pool trades.
+    pub pool_open_time: u64,
+    /// Admin of the pool, allowed to change its status and parameters.
+    pub owner: Pubkey,
+}
+
+impl AmmFlags {
+    /// Whether a swap is accepted at unix timestamp `now`.
+    pub fn is_swap_enabled(&self, now: u64) -> bool {
+        match self.status {
+            AmmStatus::WaitingTrade => now >= self.pool_open_time,
+            status => status.swap_permission(),
+        }
+    }
+
+    /// Same as [`Self::is_swap_enabled`], failing with
+    /// [`SwapError::PoolNotOpen`] or [`SwapError::SwapDisabled`] for the pool
+    /// `pool_id`.
+    pub fn check_swap_enabled(&self, pool_id: &Pubkey, now: u64) -> Result<(), SwapError> {
+        if !self.status.swap_permission() {
+            return Err(SwapError::SwapDisabled {
+                pool_id: *pool_id,
+                status: self.status.as_u64(),
+            });
+        }
+        if self.status == AmmStatus::WaitingTrade && now < self.pool_open_time {
+            return Err(SwapError::PoolNotOpen {
+                pool_id: *pool_id,
+                open_time: self.pool_open_time,
+                seconds_until_open: self.pool_open_time - now,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether liquidity can only be withdrawn: no swaps, no deposits.
+    pub fn is_withdraw_only(&self) -> bool {
+        self.status == AmmStatus::WithdrawOnly
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.status == AmmStatus::Disabled
+    }
+
+    /// Whether the program resets the order book of the pool.
+    pub fn is_reset(&self) -> bool {
+        self.reset_flag == 0
+    }
+
+    /// Whether funds are withheld from the pool after a punishment.
+    pub fn is_punished(&self) -> bool {
+        self.punish_pc_amount != 0 || self.punish_coin_amount != 0
+    }
+}
+
+impl LiquidityStateLayoutV4 {
+    /// Typed view of the status and admin fields.
+    pub fn flags(&self) -> AmmFlags {
+        AmmFlags {
+            status: AmmStatus::from_u64(self.status),
+            reset_flag: self.reset_flag,
+            punish_pc_amount: self.punish_pc_amount,
+            punish_coin_amount: self.punish_coin_amount,
+            pool_open_time: self.pool_open_time,
+            owner: self.owner,
+        }
+    }
+}
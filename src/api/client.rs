@@ -0,0 +1,345 @@
+use crate::common::{
+    RequestKind, RequestLog, RetryPolicy, is_transient_reqwest_error, is_transient_status,
+};
+use crate::config::DEFAULT_API_BASE_URL;
+use crate::error::ApiError;
+use crate::interface::{
+    ApiVersionResponse, ClmmPoolInfosResponse, ClmmSinglePoolInfo, MintInfoResponse,
+    MintPriceResponse, PoolKeys,
+};
+use anyhow::Context;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::time::{Duration, Instant};
+use tracing::{debug, error};
+
+/// Envelope fields shared by every API response.
+#[derive(Deserialize)]
+struct ApiStatus {
+    success: Option<bool>,
+    msg: Option<String>,
+}
+
+/// Paging and sorting of the pool list endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolQuery {
+    /// `all`, `standard`, `concentrated`, ...
+    pub pool_type: String,
+    /// `default`, `liquidity`, `volume24h`, `fee24h`, `apr24h`, ...
+    pub pool_sort_field: String,
+    /// `desc` or `asc`.
+    pub sort_type: String,
+    pub page_size: u32,
+    /// Page number, from 1.
+    pub page: u32,
+}
+
+impl Default for PoolQuery {
+    /// All pool types by the default order, first page of 100.
+    fn default() -> Self {
+        Self {
+            pool_type: "all".to_string(),
+            pool_sort_field: "default".to_string(),
+            sort_type: "desc".to_string(),
+            page_size: 100,
+            page: 1,
+        }
+    }
+}
+
+impl PoolQuery {
+    fn params(&self) -> [(&'static str, String); 5] {
+        [
+            ("poolType", self.pool_type.clone()),
+            ("poolSortField", self.pool_sort_field.clone()),
+            ("sortType", self.sort_type.clone()),
+            ("pageSize", self.page_size.to_string()),
+            ("page", self.page.to_string()),
+        ]
+    }
+}
+
+/// Client for the Raydium v3 HTTP API.
+///
+/// Requests are retried under a [`RetryPolicy`] on timeouts, connection
+/// errors, 429s and 5xx. Other non-success statuses and `success: false`
+/// responses fail with an [`ApiError`].
+#[derive(Clone, Debug)]
+pub struct RaydiumApiClient {
+    reqwest_client: Client,
+    base_url: String,
+    timeout: Option<Duration>,
+    headers: HeaderMap,
+    retry_policy: RetryPolicy,
+    request_log: Option<RequestLog>,
+}
+
+impl Default for RaydiumApiClient {
+    fn default() -> Self {
+        Self::new(DEFAULT_API_BASE_URL)
+    }
+}
+
+impl RaydiumApiClient {
+    /// Creates a client for the API at `base_url`, e.g.
+    /// [`DEFAULT_API_BASE_URL`].
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            reqwest_client: Client::new(),
+            base_url: base_url.into(),
+            timeout: None,
+            headers: HeaderMap::new(),
+            retry_policy: RetryPolicy::default(),
+            request_log: None,
+        }
+    }
+
+    /// Send requests through `reqwest_client`, e.g. one with a proxy or
+    /// custom headers.
+    pub fn with_reqwest_client(mut self, reqwest_client: Client) -> Self {
+        self.reqwest_client = reqwest_client;
+        self
+    }
+
+    /// Send requests to `base_url` instead, e.g. a proxy or mirror.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Timeout of every request attempt, none unless set.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send `name: value` with every request, e.g. an API key of a proxy.
+    /// Replaces an earlier value of the header.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Same as [`Self::with_header`] for each of `headers`.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Record every request into `request_log`.
+    pub fn with_request_log(mut self, request_log: RequestLog) -> Self {
+        self.request_log = Some(request_log);
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn request_log(&self) -> Option<&RequestLog> {
+        self.request_log.as_ref()
+    }
+
+    /// Full URL of a request to `path` with the `query` parameters.
+    pub fn url<K: AsRef<str>, V: AsRef<str>>(
+        &self,
+        path: &str,
+        query: &[(K, V)],
+    ) -> anyhow::Result<Url> {
+        let url = format!("{}{}", self.base_url, path);
+        Url::parse_with_params(&url, query.iter().map(|(k, v)| (k.as_ref(), v.as_ref())))
+            .with_context(|| format!("Invalid Raydium API URL {}", url))
+    }
+
+    /// GET `path` with the `query` parameters and decode the response.
+    pub async fn get<T, K, V>(&self, path: &str, query: &[(K, V)]) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let url = self.url(path, query)?;
+
+        let request_url = &url;
+        let resp = self
+            .retry_policy
+            .retry(
+                move || async move {
+                    let started = Instant::now();
+                    let mut request = self
+                        .reqwest_client
+                        .get(request_url.clone())
+                        .headers(self.headers.clone());
+                    if let Some(timeout) = self.timeout {
+                        request = request.timeout(timeout);
+                    }
+                    let resp = request.send().await;
+                    if let Some(request_log) = &self.request_log {
+                        let success = resp.as_ref().is_ok_and(|resp| resp.status().is_success());
+                        let elapsed = started.elapsed();
+                        request_log.record(
+                            RequestKind::Api,
+                            request_url.to_string(),
+                            elapsed,
+                            success,
+                        );
+                    }
+                    // Only transient statuses are turned into errors, to be
+                    // retried; the body of other failures is decoded below.
+                    resp.and_then(|resp| {
+                        if is_transient_status(resp.status()) {
+                            resp.error_for_status()
+                        } else {
+                            Ok(resp)
+                        }
+                    })
+                },
+                is_transient_reqwest_error,
+            )
+            .await
+            .with_context(|| format!("Raydium API GET failed for {}", url))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .with_context(|| format!("Failed to read response body from {}", url))?;
+
+        if !status.is_success() {
+            error!("Raydium non-200 {} for {}. Body: {}", status, url, body);
+            return Err(ApiError::Status {
+                url: url.to_string(),
+                status: status.as_u16(),
+                body,
+            }
+            .into());
+        }
+
+        debug!("Raydium response body for {}: {}", url, body);
+
+        if let Ok(ApiStatus {
+            success: Some(false),
+            msg,
+        }) = serde_json::from_str::<ApiStatus>(&body)
+        {
+            return Err(ApiError::Rejected {
+                url: url.to_string(),
+                message: msg,
+            }
+            .into());
+        }
+
+        let parsed: T = serde_json::from_str(&body).with_context(|| {
+            format!(
+                "Failed to parse Raydium response as JSON. Status: {}, Body: {}",
+                status, body
+            )
+        })?;
+
+        Ok(parsed)
+    }
+
+    /// `/pools/info/mint`: pools of `mint_1`, with `mint_2` if given.
+    pub async fn pools_info_by_mint(
+        &self,
+        mint_1: &str,
+        mint_2: Option<&str>,
+        query: &PoolQuery,
+    ) -> anyhow::Result<ClmmPoolInfosResponse> {
+        let mut params = vec![("mint1", mint_1.to_string())];
+        params.extend(mint_2.map(|mint_2| ("mint2", mint_2.to_string())));
+        params.extend(query.params());
+        self.get("/pools/info/mint", &params).await
+    }
+
+    /// `/pools/info/ids`: pool metadata (price, TVL, stats) by pool id.
+    pub async fn pools_info_by_ids(&self, ids: &[String]) -> anyhow::Result<ClmmSinglePoolInfo> {
+        self.get("/pools/info/ids", &[("ids", ids.join(","))]).await
+    }
+
+    /// `/pools/key/ids`: account keys of pools by pool id, as `T` (e.g.
+    /// [`AmmPool`](crate::interface::AmmPool)).
+    pub async fn pools_key_by_ids<T: DeserializeOwned + Clone>(
+        &self,
+        ids: &[String],
+    ) -> anyhow::Result<PoolKeys<T>> {
+        self.get("/pools/key/ids", &[("ids", ids.join(","))]).await
+    }
+
+    /// `/pools/info/list`: all pools, a page at a time.
+    pub async fn pools_info_list(
+        &self,
+        query: &PoolQuery,
+    ) -> anyhow::Result<ClmmPoolInfosResponse> {
+        self.get("/pools/info/list", &query.params()).await
+    }
+
+    /// `/mint/price`: USD prices of `mints`. Long lists are better split, to
+    /// keep the query string short.
+    pub async fn mint_price(&self, mints: &[String]) -> anyhow::Result<MintPriceResponse> {
+        self.get("/mint/price", &[("mints", mints.join(","))]).await
+    }
+
+    /// `/mint/ids`: metadata of `mints`. Long lists are better split, as for
+    /// [`Self::mint_price`].
+    pub async fn mint_info_by_ids(&self, mints: &[String]) -> anyhow::Result<MintInfoResponse> {
+        self.get("/mint/ids", &[("mints", mints.join(","))]).await
+    }
+
+    /// `/main/version`: latest and oldest supported API versions.
+    pub async fn main_version(&self) -> anyhow::Result<ApiVersionResponse> {
+        self.get::<_, &str, &str>("/main/version", &[]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_encodes_query() {
+        let client = RaydiumApiClient::new("https://api.example.com");
+        let query = PoolQuery {
+            pool_type: "concentrated".to_string(),
+            page: 2,
+            ..Default::default()
+        };
+        let mut params = vec![(
+            "mint1",
+            "So11111111111111111111111111111111111111112".to_string(),
+        )];
+        params.extend(query.params());
+        assert_eq!(
+            client.url("/pools/info/mint", &params).unwrap().as_str(),
+            "https://api.example.com/pools/info/mint\
+             ?mint1=So11111111111111111111111111111111111111112\
+             &poolType=concentrated&poolSortField=default&sortType=desc&pageSize=100&page=2"
+        );
+        assert_eq!(
+            client
+                .url("/pools/info/ids", &[("ids", "a,b")])
+                .unwrap()
+                .as_str(),
+            "https://api.example.com/pools/info/ids?ids=a%2Cb"
+        );
+    }
+}
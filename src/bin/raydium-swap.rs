@@ -0,0 +1,91 @@
+//! Command line tools around the swap client.
+//!
+//! ```text
+//! raydium-swap snapshot <amm|clmm> <pool_id> <snapshot_file>
+//! raydium-swap replay <snapshot_file> <order_file> [--expected <quote_file>]
+//! ```
+//!
+//! `snapshot` reads `RPC_URL` from the environment. `replay` works offline and
+//! exits with status 1 when the quote differs from the expected one.
+
+use anyhow::anyhow;
+use raydium_amm_swap::amm::client::AmmSwapClient;
+use raydium_amm_swap::replay::{
+    PoolSnapshot, ReplayOrder, ReplayQuote, capture_amm_snapshot, capture_clmm_snapshot,
+    replay_quote,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+const USAGE: &str = "usage:
+  raydium-swap snapshot <amm|clmm> <pool_id> <snapshot_file>
+  raydium-swap replay <snapshot_file> <order_file> [--expected <quote_file>]";
+
+/// Never contacted by `replay`, the client only provides the quote math.
+const OFFLINE_RPC_URL: &str = "http://127.0.0.1:8899";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    dotenvy::dotenv().ok();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("snapshot") => snapshot(&args[1..]).await,
+        Some("replay") => replay(&args[1..]),
+        _ => {
+            eprintln!("{USAGE}");
+            Ok(ExitCode::from(2))
+        }
+    }
+}
+
+async fn snapshot(args: &[String]) -> anyhow::Result<ExitCode> {
+    let [kind, pool_id, path] = args else {
+        eprintln!("{USAGE}");
+        return Ok(ExitCode::from(2));
+    };
+    let url = env::var("RPC_URL").map_err(|_| anyhow!("RPC_URL env is not presented"))?;
+    let client = AmmSwapClient::watch_only(RpcClient::new(url));
+    let pool_id = Pubkey::from_str(pool_id)?;
+    let snapshot = match kind.as_str() {
+        "amm" => capture_amm_snapshot(&client, &pool_id).await?,
+        "clmm" => capture_clmm_snapshot(&client, &pool_id).await?,
+        _ => return Err(anyhow!("Unknown pool kind {kind}, expected amm or clmm")),
+    };
+    snapshot.save(path)?;
+    println!("Saved snapshot of {} at slot {} to {}", pool_id, snapshot.slot(), path);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn replay(args: &[String]) -> anyhow::Result<ExitCode> {
+    let (snapshot_path, order_path, expected_path) = match args {
+        [snapshot, order] => (snapshot, order, None),
+        [snapshot, order, flag, expected] if flag == "--expected" => {
+            (snapshot, order, Some(expected))
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return Ok(ExitCode::from(2));
+        }
+    };
+    let snapshot = PoolSnapshot::load(snapshot_path)?;
+    let order = ReplayOrder::load(order_path)?;
+    let client = AmmSwapClient::watch_only(RpcClient::new(OFFLINE_RPC_URL.to_string()));
+    let quote = replay_quote(&client, &snapshot, &order)?;
+    println!("{}", serde_json::to_string_pretty(&quote)?);
+
+    let Some(expected_path) = expected_path else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    let mismatches = quote.diff(&ReplayQuote::load(expected_path)?);
+    if mismatches.is_empty() {
+        println!("Quote matches {expected_path}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+    Ok(ExitCode::FAILURE)
+}
@@ -0,0 +1,314 @@
+//! CLMM quotes from accounts supplied by the caller.
+//!
+//! [`quote_clmm_from_accounts`] takes raw accounts obtained elsewhere, e.g.
+//! from a Geyser stream, a snapshot or another RPC layer, decodes them and
+//! quotes the swap like [`AmmSwapClient::quote`] does, without any RPC call.
+//!
+//! The accounts needed are the pool state, its amm config, the bitmap
+//! extension when the pool has one, and the tick arrays in swap direction
+//! from the current one. The tick arrays follow from the pool state, see
+//! [`get_tick_array_keys`]. The CLMM program is the owner of the pool state,
+//! so pools of any cluster are quoted alike.
+//!
+//! [`AmmSwapClient::quote`]: crate::amm::client::AmmSwapClient::quote
+//! [`get_tick_array_keys`]: crate::clmm::get_tick_array_keys
+
+use crate::amm::client::{SwapQuote, clmm_swap_quote};
+use crate::clmm::{ClmmQuoteLadder, derive_tick_array_bitmap_extension_key, derive_tick_array_key};
+use crate::common::deserialize_anchor_account;
+use crate::error::ValidationError;
+use crate::states::{AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState};
+use crate::validation;
+use anyhow::anyhow;
+use solana_account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// Swap quoted by [`quote_clmm_from_accounts`].
+#[derive(Debug, Clone, Copy)]
+pub struct ClmmAccountQuoteParams {
+    pub pool_id: Pubkey,
+    pub input_mint: Pubkey,
+    pub amount_in: u64,
+    /// Slippage as a fraction (e.g. `0.005` for 0.5%).
+    pub slippage: f64,
+}
+
+/// Quote an exact input CLMM swap from `accounts`, by address.
+///
+/// The pool state and amm config must be present. A missing bitmap
+/// extension is taken as the pool having none. Tick arrays are used in swap
+/// direction up to the first missing one, so the quote fails when the swap
+/// runs past the tick arrays supplied. Accounts not needed are ignored.
+pub fn quote_clmm_from_accounts(
+    accounts: &[(Pubkey, Account)],
+    params: &ClmmAccountQuoteParams,
+) -> anyhow::Result<SwapQuote> {
+    validation::validate_amount(params.amount_in)?;
+    validation::validate_slippage(params.slippage)?;
+    let accounts: HashMap<&Pubkey, &Account> = accounts
+        .iter()
+        .map(|(address, account)| (address, account))
+        .collect();
+
+    let pool_id = &params.pool_id;
+    let pool_account = accounts
+        .get(pool_id)
+        .copied()
+        .ok_or(anyhow!("Pool state {pool_id} is missing"))?;
+    let program_id = pool_account.owner;
+    let account = |address: &Pubkey| clmm_account(&accounts, address, &program_id);
+    let pool_state = deserialize_anchor_account::<PoolState>(pool_account)?;
+    let amm_config_key = Pubkey::from(pool_state.amm_config.to_bytes());
+    let amm_config = deserialize_anchor_account::<AmmConfig>(
+        account(&amm_config_key)?.ok_or(anyhow!("Amm config {amm_config_key} is missing"))?,
+    )?;
+    let pool_id_v2 = solana_pubkey::Pubkey::from(pool_id.to_bytes());
+    let program_id_v2 = solana_pubkey::Pubkey::from(program_id.to_bytes());
+    let bitmap_extension_key = Pubkey::from(
+        derive_tick_array_bitmap_extension_key(&pool_id_v2, &program_id_v2).to_bytes(),
+    );
+    let tickarray_bitmap_extension = account(&bitmap_extension_key)?
+        .map(deserialize_anchor_account::<TickArrayBitmapExtension>)
+        .transpose()?;
+
+    let ladder = offline_clmm_ladder(
+        pool_id,
+        pool_state,
+        amm_config,
+        tickarray_bitmap_extension,
+        &params.input_mint,
+        |start_index| {
+            let key = Pubkey::from(
+                derive_tick_array_key(&pool_id_v2, start_index, &program_id_v2).to_bytes(),
+            );
+            account(&key)?
+                .map(deserialize_anchor_account::<TickArrayState>)
+                .transpose()
+        },
+    )?;
+    clmm_swap_quote(
+        pool_id,
+        &ladder,
+        &params.input_mint,
+        params.amount_in,
+        params.slippage,
+        &program_id_v2,
+    )
+}
+
+/// [`ClmmQuoteLadder`] selling `input_mint` to the pool `pool_id` of
+/// decoded accounts, shared by [`quote_clmm_from_accounts`] and
+/// [`quote_clmm_from_snapshot`](crate::replay::quote_clmm_from_snapshot).
+///
+/// `tick_array_at` gives the tick array starting at a tick index, if known.
+/// Tick arrays are walked in swap direction from the current one up to the
+/// first unknown one, and the current one must be known.
+pub(crate) fn offline_clmm_ladder(
+    pool_id: &Pubkey,
+    pool_state: PoolState,
+    amm_config: AmmConfig,
+    tickarray_bitmap_extension: Option<TickArrayBitmapExtension>,
+    input_mint: &Pubkey,
+    mut tick_array_at: impl FnMut(i32) -> anyhow::Result<Option<TickArrayState>>,
+) -> anyhow::Result<ClmmQuoteLadder> {
+    let zero_for_one = if pool_state.token_mint_0.to_bytes() == input_mint.to_bytes() {
+        true
+    } else if pool_state.token_mint_1.to_bytes() == input_mint.to_bytes() {
+        false
+    } else {
+        return Err(ValidationError::MintNotInPool {
+            pool_id: pool_id.to_string(),
+            mint: input_mint.to_string(),
+        }
+        .into());
+    };
+
+    let (_, mut start_index) =
+        pool_state.get_first_initialized_tick_array(&tickarray_bitmap_extension, zero_for_one)?;
+    let mut tick_arrays = Vec::new();
+    loop {
+        match tick_array_at(start_index)? {
+            Some(tick_array) => tick_arrays.push(tick_array),
+            None if tick_arrays.is_empty() => {
+                return Err(anyhow!(
+                    "Current tick array starting at {start_index} is missing"
+                ));
+            }
+            None => break,
+        }
+        match pool_state.next_initialized_tick_array_start_index(
+            &tickarray_bitmap_extension,
+            start_index,
+            zero_for_one,
+        )? {
+            Some(next_start_index) => start_index = next_start_index,
+            None => break,
+        }
+    }
+
+    Ok(ClmmQuoteLadder {
+        pool_state,
+        amm_config,
+        tickarray_bitmap_extension,
+        tick_arrays,
+        zero_for_one,
+    })
+}
+
+/// Account at `address` in `accounts`, checked to be owned by the CLMM
+/// program `program_id` of the pool.
+fn clmm_account<'a>(
+    accounts: &HashMap<&Pubkey, &'a Account>,
+    address: &Pubkey,
+    program_id: &Pubkey,
+) -> anyhow::Result<Option<&'a Account>> {
+    match accounts.get(address) {
+        Some(account) if account.owner != *program_id => Err(anyhow!(
+            "Account {address} is owned by {}, not the CLMM program {program_id}",
+            account.owner
+        )),
+        account => Ok(account.copied()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::Q64;
+    use crate::replay::{ClmmPoolSnapshot, quote_clmm_from_snapshot};
+    use crate::states::TickState;
+    use anchor_lang::{AccountSerialize, Discriminator};
+
+    fn key(n: u8) -> Pubkey {
+        Pubkey::new_from_array([n; 32])
+    }
+
+    /// Anchor account data of a zero copy account.
+    fn zero_copy_data<T: Discriminator + bytemuck::Pod>(state: &T) -> Vec<u8> {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(state));
+        data
+    }
+
+    fn owned_by(program_id: Pubkey, data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1,
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    /// Account data of a pool at price 1 with 10^12 liquidity up to tick 59
+    /// and a 0.25% trade fee: pool state, amm config and the tick array
+    /// starting at 0.
+    fn pool_data(pool_id: Pubkey) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let pool_state = PoolState {
+            amm_config: solana_pubkey::Pubkey::from(key(4).to_bytes()),
+            token_mint_0: solana_pubkey::Pubkey::from(key(2).to_bytes()),
+            token_mint_1: solana_pubkey::Pubkey::from(key(3).to_bytes()),
+            tick_spacing: 1,
+            sqrt_price_x64: Q64,
+            liquidity: 1_000_000_000_000,
+            tick_array_bitmap: [u64::MAX; 16],
+            ..Default::default()
+        };
+        let amm_config = AmmConfig {
+            trade_fee_rate: 2_500,
+            ..Default::default()
+        };
+        let mut amm_config_data = Vec::new();
+        amm_config.try_serialize(&mut amm_config_data).unwrap();
+        let mut tick_array = TickArrayState {
+            pool_id: solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+            ..Default::default()
+        };
+        tick_array.ticks[59] = TickState {
+            tick: 59,
+            liquidity_gross: 1,
+            ..Default::default()
+        };
+        (
+            zero_copy_data(&pool_state),
+            amm_config_data,
+            zero_copy_data(&tick_array),
+        )
+    }
+
+    fn params(pool_id: Pubkey, input_mint: Pubkey) -> ClmmAccountQuoteParams {
+        ClmmAccountQuoteParams {
+            pool_id,
+            input_mint,
+            amount_in: 1_000_000,
+            slippage: 0.01,
+        }
+    }
+
+    #[test]
+    fn accounts_and_snapshot_quote_alike_from_account_bytes() {
+        let (program_id, pool_id) = (key(9), key(1));
+        let (pool_data, amm_config_data, tick_array_data) = pool_data(pool_id);
+        let tick_array_key = Pubkey::from(
+            derive_tick_array_key(
+                &solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+                0,
+                &solana_pubkey::Pubkey::from(program_id.to_bytes()),
+            )
+            .to_bytes(),
+        );
+        let accounts = [
+            (pool_id, owned_by(program_id, pool_data.clone())),
+            (key(4), owned_by(program_id, amm_config_data.clone())),
+            (
+                tick_array_key,
+                owned_by(program_id, tick_array_data.clone()),
+            ),
+        ];
+        let snapshot = ClmmPoolSnapshot::from_account_data(
+            pool_id,
+            program_id,
+            &pool_data,
+            &amm_config_data,
+            None,
+            [tick_array_data.as_slice()],
+        )
+        .unwrap();
+
+        let from_accounts = quote_clmm_from_accounts(&accounts, &params(pool_id, key(3))).unwrap();
+        let from_snapshot = quote_clmm_from_snapshot(&snapshot, &key(3), 1_000_000, 0.01).unwrap();
+
+        assert_eq!(from_accounts.output_mint, key(2));
+        assert!(from_accounts.amount_out > 0 && from_accounts.amount_out < 1_000_000);
+        assert_eq!(from_accounts.tick_arrays, vec![tick_array_key]);
+        assert_eq!(from_snapshot.amount_out, from_accounts.amount_out);
+        assert_eq!(from_snapshot.min_amount_out, from_accounts.min_amount_out);
+        assert_eq!(from_snapshot.fee, from_accounts.fee);
+        assert_eq!(from_snapshot.tick_arrays, from_accounts.tick_arrays);
+    }
+
+    #[test]
+    fn quotes_fail_without_the_current_tick_array_or_for_a_foreign_mint() {
+        let (program_id, pool_id) = (key(9), key(1));
+        let (pool_data, amm_config_data, _) = pool_data(pool_id);
+        let accounts = [
+            (pool_id, owned_by(program_id, pool_data.clone())),
+            (key(4), owned_by(program_id, amm_config_data.clone())),
+        ];
+        let snapshot = ClmmPoolSnapshot::from_account_data(
+            pool_id,
+            program_id,
+            &pool_data,
+            &amm_config_data,
+            None,
+            std::iter::empty::<&[u8]>(),
+        )
+        .unwrap();
+
+        assert!(quote_clmm_from_accounts(&accounts, &params(pool_id, key(3))).is_err());
+        assert!(quote_clmm_from_snapshot(&snapshot, &key(3), 1_000_000, 0.01).is_err());
+        assert!(quote_clmm_from_accounts(&accounts, &params(pool_id, key(5))).is_err());
+        assert!(quote_clmm_from_snapshot(&snapshot, &key(5), 1_000_000, 0.01).is_err());
+    }
+}
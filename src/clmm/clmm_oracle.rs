@@ -0,0 +1,161 @@
+use crate::clmm::multiplier;
+use crate::states::Observation;
+use anyhow::{Result, anyhow};
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// Realized volatility of the pool price over an observation window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VolatilityEstimate {
+    /// Number of log returns the estimate is based on.
+    pub samples: usize,
+    /// Time span actually covered by the used observations, in seconds.
+    pub span_secs: u64,
+    /// Standard deviation of log returns per second.
+    pub volatility_per_second: f64,
+    /// `volatility_per_second` scaled to one year.
+    pub annualized: f64,
+}
+
+/// Compute realized volatility from oracle observations.
+///
+/// `observations` must be ordered from the oldest to the newest, as returned
+/// by `ObservationState::ordered_observations`. Only observations younger than
+/// `window_secs` relative to the newest one are used. The average tick of each
+/// interval is derived from the tick cumulative, and the variance of the log
+/// price between consecutive intervals is normalized by elapsed time.
+pub fn realized_volatility(
+    observations: &[Observation],
+    window_secs: u64,
+) -> Result<VolatilityEstimate> {
+    let newest = observations
+        .last()
+        .ok_or(anyhow!("no initialized observations"))?
+        .block_timestamp;
+    let window_start = u64::from(newest).saturating_sub(window_secs);
+    let window: Vec<&Observation> = observations
+        .iter()
+        .filter(|observation| u64::from(observation.block_timestamp) >= window_start)
+        .collect();
+
+    // (interval end timestamp, average log price over the interval)
+    let mut log_prices = Vec::with_capacity(window.len());
+    for pair in window.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let elapsed = end.block_timestamp.saturating_sub(start.block_timestamp);
+        if elapsed == 0 {
+            continue;
+        }
+        let average_tick = (end.tick_cumulative - start.tick_cumulative) as f64 / elapsed as f64;
+        log_prices.push((end.block_timestamp, average_tick * 1.0001f64.ln()));
+    }
+    if log_prices.len() < 2 {
+        return Err(anyhow!(
+            "not enough observations in a {} second window to estimate volatility",
+            window_secs
+        ));
+    }
+
+    let mut sum_squared_returns = 0.0;
+    for pair in log_prices.windows(2) {
+        let log_return = pair[1].1 - pair[0].1;
+        sum_squared_returns += log_return * log_return;
+    }
+    let span_secs = u64::from(log_prices[log_prices.len() - 1].0 - log_prices[0].0);
+    if span_secs == 0 {
+        return Err(anyhow!("observations do not span any time"));
+    }
+    let variance_per_second = sum_squared_returns / span_secs as f64;
+
+    Ok(VolatilityEstimate {
+        samples: log_prices.len() - 1,
+        span_secs,
+        volatility_per_second: variance_per_second.sqrt(),
+        annualized: (variance_per_second * SECONDS_PER_YEAR).sqrt(),
+    })
+}
+
+/// Time-weighted average price of the pool over an observation window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TwapEstimate {
+    /// Time span actually covered by the used observations, in seconds.
+    pub span_secs: u64,
+    /// Average tick over the span, weighted by time.
+    pub average_tick: f64,
+    /// Price of token 0 in token 1 at `average_tick`, adjusted for the mint
+    /// decimals.
+    pub price: f64,
+}
+
+/// Compute the time-weighted average price from oracle observations.
+///
+/// `observations` must be ordered from the oldest to the newest, as for
+/// [`realized_volatility`]. The average tick is the change of the tick
+/// cumulative between the oldest observation younger than `window_secs`
+/// relative to the newest one and the newest one, divided by the elapsed
+/// time, so the price is the geometric mean of the pool price over the span.
+/// The span is shorter than `window_secs` when the ring buffer does not
+/// reach that far back.
+pub fn time_weighted_average_price(
+    observations: &[Observation],
+    window_secs: u64,
+    decimals_0: u8,
+    decimals_1: u8,
+) -> Result<TwapEstimate> {
+    let newest = observations
+        .last()
+        .ok_or(anyhow!("no initialized observations"))?;
+    let window_start = u64::from(newest.block_timestamp).saturating_sub(window_secs);
+    let oldest = observations
+        .iter()
+        .find(|observation| u64::from(observation.block_timestamp) >= window_start)
+        .unwrap_or(newest);
+    let span_secs = u64::from(newest.block_timestamp) - u64::from(oldest.block_timestamp);
+    if span_secs == 0 {
+        return Err(anyhow!(
+            "not enough observations in a {} second window to average the price",
+            window_secs
+        ));
+    }
+    let average_tick = (newest.tick_cumulative - oldest.tick_cumulative) as f64 / span_secs as f64;
+    Ok(TwapEstimate {
+        span_secs,
+        average_tick,
+        price: 1.0001f64.powf(average_tick) * multiplier(decimals_0)? / multiplier(decimals_1)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(block_timestamp: u32, tick_cumulative: i64) -> Observation {
+        Observation {
+            block_timestamp,
+            tick_cumulative,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn twap_weights_ticks_by_time_within_window() {
+        // Tick 100 for 30s, then tick 400 for 10s.
+        let observations = [
+            observation(1_000, 0),
+            observation(1_030, 3_000),
+            observation(1_040, 7_000),
+        ];
+        let twap = time_weighted_average_price(&observations, 3_600, 6, 6).unwrap();
+        assert_eq!(twap.span_secs, 40);
+        assert!((twap.average_tick - 175.0).abs() < 1e-9);
+        assert!((twap.price - 1.0001f64.powf(175.0)).abs() < 1e-12);
+
+        // Only the last 10 seconds, with token 0 three decimals more.
+        let twap = time_weighted_average_price(&observations, 10, 9, 6).unwrap();
+        assert_eq!(twap.span_secs, 10);
+        assert!((twap.average_tick - 400.0).abs() < 1e-9);
+        assert!((twap.price - 1.0001f64.powf(400.0) * 1_000.0).abs() < 1e-9);
+
+        assert!(time_weighted_average_price(&observations[..1], 3_600, 6, 6).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Program derived addresses of CLMM pool accounts.
+//!
+//! These are the seeds the CLMM program uses, so external tools deriving keys
+//! through here get the same addresses as the swap instructions built by this
+//! crate.
+
+use crate::consts::CLMM;
+use crate::states::{POOL_TICK_ARRAY_BITMAP_SEED, POSITION_SEED, TICK_ARRAY_SEED};
+use solana_pubkey::Pubkey;
+
+/// Address of the CLMM program on mainnet.
+pub fn clmm_program_id() -> Pubkey {
+    Pubkey::from_str_const(CLMM)
+}
+
+/// Tick array of `pool_id` starting at tick `start_index`.
+///
+/// `start_index` must be a valid start index for the pool's tick spacing (see
+/// `TickArrayState::get_array_start_index`); any other value derives an
+/// address no account lives at.
+pub fn derive_tick_array_key(pool_id: &Pubkey, start_index: i32, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED.as_bytes(),
+            pool_id.as_ref(),
+            &start_index.to_be_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+/// Tick arrays of `pool_id` for each of `start_indexes`, in the same order.
+pub fn derive_tick_array_keys(
+    pool_id: &Pubkey,
+    start_indexes: impl IntoIterator<Item = i32>,
+    program_id: &Pubkey,
+) -> Vec<Pubkey> {
+    start_indexes
+        .into_iter()
+        .map(|start_index| derive_tick_array_key(pool_id, start_index, program_id))
+        .collect()
+}
+
+/// Tick array bitmap extension of `pool_id`.
+pub fn derive_tick_array_bitmap_extension_key(pool_id: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[POOL_TICK_ARRAY_BITMAP_SEED.as_bytes(), pool_id.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+/// Personal position of the position NFT `nft_mint`.
+pub fn derive_personal_position_key(nft_mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[POSITION_SEED.as_bytes(), nft_mint.as_ref()], program_id).0
+}
+
+/// Protocol position of `pool_id` aggregating the positions of the range
+/// `tick_lower..tick_upper`.
+pub fn derive_protocol_position_key(
+    pool_id: &Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+    program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            POSITION_SEED.as_bytes(),
+            pool_id.as_ref(),
+            &tick_lower.to_be_bytes(),
+            &tick_upper.to_be_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::TickArrayBitmapExtension;
+
+    const POOL_ID: Pubkey = Pubkey::from_str_const("3ucNos4NbumPLZNWztqGHNFFgkHeRMBQAVemeeomsUxv");
+
+    #[test]
+    fn tick_array_key_matches_program_seeds() {
+        let program_id = clmm_program_id();
+        for start_index in [-443_640, -600, 0, 600, 443_580] {
+            let (expected, _) = Pubkey::find_program_address(
+                &[b"tick_array", POOL_ID.as_ref(), &start_index.to_be_bytes()],
+                &program_id,
+            );
+            assert_eq!(derive_tick_array_key(&POOL_ID, start_index, &program_id), expected);
+        }
+    }
+
+    #[test]
+    fn tick_array_keys_differ_by_start_index_and_keep_order() {
+        let program_id = clmm_program_id();
+        let keys = derive_tick_array_keys(&POOL_ID, [-60, 0, 60], &program_id);
+        assert_eq!(keys.len(), 3);
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[1], keys[2]);
+        assert_eq!(keys[2], derive_tick_array_key(&POOL_ID, 60, &program_id));
+    }
+
+    #[test]
+    fn tick_array_key_depends_on_program() {
+        let other_program = Pubkey::new_from_array([7; 32]);
+        assert_ne!(
+            derive_tick_array_key(&POOL_ID, 0, &clmm_program_id()),
+            derive_tick_array_key(&POOL_ID, 0, &other_program)
+        );
+    }
+
+    #[test]
+    fn position_keys_match_program_seeds() {
+        let program_id = clmm_program_id();
+        let nft_mint = Pubkey::new_from_array([3; 32]);
+        let (personal, _) =
+            Pubkey::find_program_address(&[b"position", nft_mint.as_ref()], &program_id);
+        assert_eq!(
+            derive_personal_position_key(&nft_mint, &program_id),
+            personal
+        );
+
+        let (protocol, _) = Pubkey::find_program_address(
+            &[
+                b"position",
+                POOL_ID.as_ref(),
+                &(-120i32).to_be_bytes(),
+                &180i32.to_be_bytes(),
+            ],
+            &program_id,
+        );
+        assert_eq!(
+            derive_protocol_position_key(&POOL_ID, -120, 180, &program_id),
+            protocol
+        );
+    }
+
+    #[test]
+    fn bitmap_extension_key_matches_state_key() {
+        assert_eq!(
+            derive_tick_array_bitmap_extension_key(&POOL_ID, &clmm_program_id()),
+            TickArrayBitmapExtension::key(POOL_ID)
+        );
+    }
+}
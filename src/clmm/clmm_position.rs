@@ -0,0 +1,986 @@
+//! CLMM liquidity positions.
+//!
+//! A position provides liquidity to a tick range of a pool and is owned by
+//! whoever holds its NFT. The range is given as ticks or as prices
+//! ([`PositionRange`]), the deposit as amounts of both tokens, from which
+//! [`position_liquidity`] computes the liquidity bought at the current pool
+//! price. [`open_position_instruction`] builds the `open_position_v2` or
+//! `open_position_with_token22_nft` instruction opening the position, and
+//! [`increase_liquidity_instruction`] and [`decrease_liquidity_instruction`]
+//! the instructions changing the liquidity of an open one. [`position_fees`]
+//! computes the fees and rewards owed to a position, collected by
+//! `decrease_liquidity_v2`, before [`close_position_instruction`] closes the
+//! emptied position and burns its NFT.
+
+use crate::clmm::{
+    derive_personal_position_key, derive_protocol_position_key, derive_tick_array_key,
+    price_to_sqrt_price_x64, tick_with_spacing,
+};
+use crate::consts::{
+    TOKEN_METADATA_PROGRAM, close_position_discriminator, decrease_liquidity_v2_discriminator,
+    increase_liquidity_v2_discriminator, open_position_v2_discriminator,
+    open_position_with_token22_nft_discriminator,
+};
+use crate::libraries::{
+    MAX_TICK, MIN_TICK, MulDiv, Q64, U128, get_delta_amounts_signed, get_liquidity_from_amounts,
+    get_sqrt_price_at_tick, get_tick_at_sqrt_price,
+};
+use crate::states::{
+    PersonalPositionState, PoolState, REWARD_NUM, TickArrayState, TickState, get_fee_growth_inside,
+    get_reward_growths_inside,
+};
+use anchor_spl::memo::spl_memo;
+use anyhow::anyhow;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+const RENT_SYSVAR: Pubkey = Pubkey::from_str_const("SysvarRent111111111111111111111111111111111");
+
+/// Tick range of a position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionRange {
+    /// Ticks, multiples of the tick spacing of the pool.
+    Ticks { lower: i32, upper: i32 },
+    /// Prices of token 0 in token 1, in UI units, each rounded down to a
+    /// multiple of the tick spacing of the pool.
+    Prices { lower: f64, upper: f64 },
+}
+
+impl PositionRange {
+    /// `(tick_lower, tick_upper)` of the range in a pool with `tick_spacing`,
+    /// whose mints have `decimals_0` and `decimals_1` decimals.
+    pub fn ticks(
+        &self,
+        tick_spacing: u16,
+        decimals_0: u8,
+        decimals_1: u8,
+    ) -> anyhow::Result<(i32, i32)> {
+        let spacing = i32::from(tick_spacing);
+        let (lower, upper) = match *self {
+            Self::Ticks { lower, upper } => {
+                if lower % spacing != 0 || upper % spacing != 0 {
+                    return Err(anyhow!(
+                        "Ticks {lower} and {upper} are not multiples of the tick spacing {spacing}"
+                    ));
+                }
+                (lower, upper)
+            }
+            Self::Prices { lower, upper } => {
+                let tick = |price: f64| -> anyhow::Result<i32> {
+                    if !price.is_finite() || price <= 0.0 {
+                        return Err(anyhow!("Price {price} is not a positive number"));
+                    }
+                    let sqrt_price_x64 = price_to_sqrt_price_x64(price, decimals_0, decimals_1)?;
+                    let tick = get_tick_at_sqrt_price(sqrt_price_x64)
+                        .map_err(|e| anyhow!("Price {price} is out of range: {e:?}"))?;
+                    Ok(tick_with_spacing(tick, spacing))
+                };
+                (tick(lower)?, tick(upper)?)
+            }
+        };
+        if lower >= upper {
+            return Err(anyhow!(
+                "Lower tick {lower} is not below upper tick {upper}"
+            ));
+        }
+        if lower < MIN_TICK || upper > MAX_TICK {
+            return Err(anyhow!(
+                "Ticks {lower} and {upper} are out of the range {MIN_TICK}..={MAX_TICK}"
+            ));
+        }
+        Ok((lower, upper))
+    }
+}
+
+/// Liquidity of a position and the token amounts it is worth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionLiquidity {
+    pub liquidity: u128,
+    /// Token 0 amount of the liquidity, rounded up, before transfer fees.
+    pub amount_0: u64,
+    /// Token 1 amount of the liquidity, rounded up, before transfer fees.
+    pub amount_1: u64,
+}
+
+/// Most liquidity `amount_0` and `amount_1` buy in `tick_lower..tick_upper`
+/// of a pool at `sqrt_price_x64` and `tick_current`.
+///
+/// Only token 0 is deposited below the range and only token 1 above it; the
+/// returned amounts are what the liquidity takes, at most the given ones up
+/// to rounding.
+pub fn position_liquidity(
+    sqrt_price_x64: u128,
+    tick_current: i32,
+    tick_lower: i32,
+    tick_upper: i32,
+    amount_0: u64,
+    amount_1: u64,
+) -> anyhow::Result<PositionLiquidity> {
+    let sqrt_price_at = |tick: i32| get_sqrt_price_at_tick(tick).map_err(|e| anyhow!("{e:?}"));
+    let liquidity = get_liquidity_from_amounts(
+        sqrt_price_x64,
+        sqrt_price_at(tick_lower)?,
+        sqrt_price_at(tick_upper)?,
+        amount_0,
+        amount_1,
+    )?;
+    let (amount_0, amount_1) = get_delta_amounts_signed(
+        tick_current,
+        sqrt_price_x64,
+        tick_lower,
+        tick_upper,
+        i128::try_from(liquidity)?,
+    )?;
+    Ok(PositionLiquidity {
+        liquidity,
+        amount_0,
+        amount_1,
+    })
+}
+
+/// Token program of a position NFT, which also picks the instruction opening
+/// the position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PositionNft {
+    /// SPL Token NFT with Metaplex metadata, opened by `open_position_v2`.
+    #[default]
+    SplToken,
+    /// Token-2022 NFT keeping its metadata in a mint extension, opened by
+    /// `open_position_with_token22_nft`.
+    Token2022,
+}
+
+impl PositionNft {
+    pub fn token_program(&self) -> Pubkey {
+        match self {
+            Self::SplToken => spl_token::id(),
+            Self::Token2022 => Pubkey::from(spl_token_2022::id().to_bytes()),
+        }
+    }
+}
+
+/// Position to open with [`AmmSwapClient::open_position`].
+///
+/// [`AmmSwapClient::open_position`]: crate::amm::client::AmmSwapClient::open_position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenPositionParams {
+    pub pool_id: Pubkey,
+    pub range: PositionRange,
+    /// Token 0 to deposit, in smallest units, transfer fee included.
+    pub amount_0: u64,
+    /// Token 1 to deposit, in smallest units, transfer fee included.
+    pub amount_1: u64,
+    /// Tolerance on the deposits, in basis points, for the pool price moving
+    /// before the transaction lands.
+    pub slippage_bps: u64,
+    pub nft: PositionNft,
+    /// Create metadata for the position NFT.
+    pub with_metadata: bool,
+}
+
+/// Position opened by [`AmmSwapClient::open_position`].
+///
+/// [`AmmSwapClient::open_position`]: crate::amm::client::AmmSwapClient::open_position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenedPosition {
+    pub signature: Signature,
+    pub nft_mint: Pubkey,
+    /// Personal position account holding the state of the position.
+    pub personal_position: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Most token 0 the transaction could deposit, slippage and transfer
+    /// fee included.
+    pub amount_0_max: u64,
+    /// Most token 1 the transaction could deposit, slippage and transfer
+    /// fee included.
+    pub amount_1_max: u64,
+}
+
+/// Accounts of an instruction opening a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenPositionAccounts {
+    pub payer: Pubkey,
+    pub nft_owner: Pubkey,
+    /// Mint of the position NFT, a new account whose keypair signs the
+    /// transaction.
+    pub nft_mint: Pubkey,
+    pub pool_id: Pubkey,
+    /// Token accounts of the payer the deposits are taken from.
+    pub token_account_0: Pubkey,
+    pub token_account_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub vault_0_mint: Pubkey,
+    pub vault_1_mint: Pubkey,
+    /// Tick array bitmap extension of the pool, needed when a tick array of
+    /// the range is beyond the default bitmap of the pool.
+    pub tick_array_bitmap_extension: Option<Pubkey>,
+}
+
+/// Arguments of an instruction opening a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenPositionArgs {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    /// Tick spacing of the pool, locating the tick arrays of the range.
+    pub tick_spacing: u16,
+    pub liquidity: u128,
+    /// Most token 0 to deposit, transfer fee included.
+    pub amount_0_max: u64,
+    /// Most token 1 to deposit, transfer fee included.
+    pub amount_1_max: u64,
+    pub with_metadata: bool,
+}
+
+/// Metaplex metadata account of the NFT `nft_mint`.
+pub fn derive_position_metadata_key(nft_mint: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str_const(TOKEN_METADATA_PROGRAM);
+    Pubkey::find_program_address(
+        &[b"metadata", program_id.as_ref(), nft_mint.as_ref()],
+        &program_id,
+    )
+    .0
+}
+
+/// Instruction of the CLMM program `program_id` opening a position with
+/// `args`, minting its NFT under the token program of `nft` to the associated
+/// token account of `accounts.nft_owner`.
+pub fn open_position_instruction(
+    nft: PositionNft,
+    accounts: &OpenPositionAccounts,
+    args: &OpenPositionArgs,
+    program_id: &Pubkey,
+) -> Instruction {
+    let program_id = solana_pubkey::Pubkey::from(program_id.to_bytes());
+    let pool_id = solana_pubkey::Pubkey::from(accounts.pool_id.to_bytes());
+    let to_sdk_pubkey = |pubkey: solana_pubkey::Pubkey| Pubkey::from(pubkey.to_bytes());
+    let tick_array_lower_start_index =
+        TickArrayState::get_array_start_index(args.tick_lower, args.tick_spacing);
+    let tick_array_upper_start_index =
+        TickArrayState::get_array_start_index(args.tick_upper, args.tick_spacing);
+    let nft_account = get_associated_token_address_with_program_id(
+        &accounts.nft_owner,
+        &accounts.nft_mint,
+        &nft.token_program(),
+    );
+    let personal_position = derive_personal_position_key(
+        &solana_pubkey::Pubkey::from(accounts.nft_mint.to_bytes()),
+        &program_id,
+    );
+
+    let mut metas = vec![
+        AccountMeta::new(accounts.payer, true),
+        AccountMeta::new_readonly(accounts.nft_owner, false),
+        AccountMeta::new(accounts.nft_mint, true),
+        AccountMeta::new(nft_account, false),
+    ];
+    if nft == PositionNft::SplToken {
+        metas.push(AccountMeta::new(
+            derive_position_metadata_key(&accounts.nft_mint),
+            false,
+        ));
+    }
+    metas.extend([
+        AccountMeta::new(accounts.pool_id, false),
+        AccountMeta::new(
+            to_sdk_pubkey(derive_protocol_position_key(
+                &pool_id,
+                args.tick_lower,
+                args.tick_upper,
+                &program_id,
+            )),
+            false,
+        ),
+        AccountMeta::new(
+            to_sdk_pubkey(derive_tick_array_key(
+                &pool_id,
+                tick_array_lower_start_index,
+                &program_id,
+            )),
+            false,
+        ),
+        AccountMeta::new(
+            to_sdk_pubkey(derive_tick_array_key(
+                &pool_id,
+                tick_array_upper_start_index,
+                &program_id,
+            )),
+            false,
+        ),
+        AccountMeta::new(to_sdk_pubkey(personal_position), false),
+        AccountMeta::new(accounts.token_account_0, false),
+        AccountMeta::new(accounts.token_account_1, false),
+        AccountMeta::new(accounts.token_vault_0, false),
+        AccountMeta::new(accounts.token_vault_1, false),
+        AccountMeta::new_readonly(RENT_SYSVAR, false),
+        AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+    ]);
+    if nft == PositionNft::SplToken {
+        metas.push(AccountMeta::new_readonly(
+            Pubkey::from_str_const(TOKEN_METADATA_PROGRAM),
+            false,
+        ));
+    }
+    metas.extend([
+        AccountMeta::new_readonly(Pubkey::from(spl_token_2022::id().to_bytes()), false),
+        AccountMeta::new_readonly(accounts.vault_0_mint, false),
+        AccountMeta::new_readonly(accounts.vault_1_mint, false),
+    ]);
+    if let Some(tick_array_bitmap_extension) = accounts.tick_array_bitmap_extension {
+        metas.push(AccountMeta::new(tick_array_bitmap_extension, false));
+    }
+
+    let discriminator = match nft {
+        PositionNft::SplToken => open_position_v2_discriminator(),
+        PositionNft::Token2022 => open_position_with_token22_nft_discriminator(),
+    };
+    let mut data = Vec::with_capacity(8 + 4 * 4 + 16 + 8 + 8 + 1 + 1);
+    data.extend_from_slice(&discriminator);
+    data.extend_from_slice(&args.tick_lower.to_le_bytes());
+    data.extend_from_slice(&args.tick_upper.to_le_bytes());
+    data.extend_from_slice(&tick_array_lower_start_index.to_le_bytes());
+    data.extend_from_slice(&tick_array_upper_start_index.to_le_bytes());
+    data.extend_from_slice(&args.liquidity.to_le_bytes());
+    data.extend_from_slice(&args.amount_0_max.to_le_bytes());
+    data.extend_from_slice(&args.amount_1_max.to_le_bytes());
+    data.push(args.with_metadata as u8);
+    // `base_flag: None`: the deposit is sized by `liquidity`.
+    data.push(0);
+
+    Instruction {
+        program_id: to_sdk_pubkey(program_id),
+        accounts: metas,
+        data,
+    }
+}
+
+/// Deposit added to a position with [`AmmSwapClient::increase_liquidity`].
+///
+/// [`AmmSwapClient::increase_liquidity`]: crate::amm::client::AmmSwapClient::increase_liquidity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncreaseLiquidityParams {
+    /// Mint of the position NFT, held by the owner.
+    pub nft_mint: Pubkey,
+    /// Token 0 to deposit, in smallest units, transfer fee included.
+    pub amount_0: u64,
+    /// Token 1 to deposit, in smallest units, transfer fee included.
+    pub amount_1: u64,
+    pub slippage_bps: u64,
+}
+
+/// Liquidity removed from a position with
+/// [`AmmSwapClient::decrease_liquidity`].
+///
+/// [`AmmSwapClient::decrease_liquidity`]: crate::amm::client::AmmSwapClient::decrease_liquidity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecreaseLiquidityParams {
+    /// Mint of the position NFT, held by the owner.
+    pub nft_mint: Pubkey,
+    /// Liquidity to remove, all of it when `None`.
+    pub liquidity: Option<u128>,
+    pub slippage_bps: u64,
+}
+
+/// Liquidity change of a position, for
+/// [`AmmSwapClient::increase_liquidity`] and
+/// [`AmmSwapClient::decrease_liquidity`].
+///
+/// [`AmmSwapClient::increase_liquidity`]: crate::amm::client::AmmSwapClient::increase_liquidity
+/// [`AmmSwapClient::decrease_liquidity`]: crate::amm::client::AmmSwapClient::decrease_liquidity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiquidityChange {
+    pub signature: Signature,
+    /// Liquidity added or removed.
+    pub liquidity: u128,
+    /// Most token 0 deposited, or least token 0 received, slippage and
+    /// transfer fee included.
+    pub amount_0_limit: u64,
+    /// Most token 1 deposited, or least token 1 received, slippage and
+    /// transfer fee included.
+    pub amount_1_limit: u64,
+}
+
+/// Accounts of an instruction changing the liquidity of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionLiquidityAccounts {
+    pub nft_owner: Pubkey,
+    /// Token account of `nft_owner` holding the position NFT.
+    pub nft_account: Pubkey,
+    pub nft_mint: Pubkey,
+    pub pool_id: Pubkey,
+    /// Range of the position and tick spacing of the pool, locating the
+    /// protocol position and the tick arrays.
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub tick_spacing: u16,
+    /// Token accounts of `nft_owner` deposited from or withdrawn to.
+    pub token_account_0: Pubkey,
+    pub token_account_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub vault_0_mint: Pubkey,
+    pub vault_1_mint: Pubkey,
+    /// Tick array bitmap extension of the pool, needed when a tick array of
+    /// the range is beyond the default bitmap of the pool.
+    pub tick_array_bitmap_extension: Option<Pubkey>,
+}
+
+/// Accounts paying out one initialized reward of a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewardAccounts {
+    pub reward_vault: Pubkey,
+    /// Token account of the position owner receiving the reward.
+    pub recipient_token_account: Pubkey,
+    pub reward_mint: Pubkey,
+}
+
+/// Program derived accounts of a position.
+struct PositionKeys {
+    protocol_position: Pubkey,
+    personal_position: Pubkey,
+    tick_array_lower: Pubkey,
+    tick_array_upper: Pubkey,
+}
+
+impl PositionLiquidityAccounts {
+    fn position_keys(&self, program_id: &Pubkey) -> PositionKeys {
+        let program_id = solana_pubkey::Pubkey::from(program_id.to_bytes());
+        let pool_id = solana_pubkey::Pubkey::from(self.pool_id.to_bytes());
+        let to_sdk_pubkey = |pubkey: solana_pubkey::Pubkey| Pubkey::from(pubkey.to_bytes());
+        let tick_array = |tick: i32| {
+            let start_index = TickArrayState::get_array_start_index(tick, self.tick_spacing);
+            to_sdk_pubkey(derive_tick_array_key(&pool_id, start_index, &program_id))
+        };
+        PositionKeys {
+            protocol_position: to_sdk_pubkey(derive_protocol_position_key(
+                &pool_id,
+                self.tick_lower,
+                self.tick_upper,
+                &program_id,
+            )),
+            personal_position: to_sdk_pubkey(derive_personal_position_key(
+                &solana_pubkey::Pubkey::from(self.nft_mint.to_bytes()),
+                &program_id,
+            )),
+            tick_array_lower: tick_array(self.tick_lower),
+            tick_array_upper: tick_array(self.tick_upper),
+        }
+    }
+}
+
+/// `increase_liquidity_v2` instruction of the CLMM program `program_id`
+/// adding `liquidity` to a position, depositing at most `amount_0_max` and
+/// `amount_1_max`, transfer fees included.
+pub fn increase_liquidity_instruction(
+    accounts: &PositionLiquidityAccounts,
+    liquidity: u128,
+    amount_0_max: u64,
+    amount_1_max: u64,
+    program_id: &Pubkey,
+) -> Instruction {
+    let keys = accounts.position_keys(program_id);
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.nft_owner, true),
+        AccountMeta::new_readonly(accounts.nft_account, false),
+        AccountMeta::new(accounts.pool_id, false),
+        AccountMeta::new(keys.protocol_position, false),
+        AccountMeta::new(keys.personal_position, false),
+        AccountMeta::new(keys.tick_array_lower, false),
+        AccountMeta::new(keys.tick_array_upper, false),
+        AccountMeta::new(accounts.token_account_0, false),
+        AccountMeta::new(accounts.token_account_1, false),
+        AccountMeta::new(accounts.token_vault_0, false),
+        AccountMeta::new(accounts.token_vault_1, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(Pubkey::from(spl_token_2022::id().to_bytes()), false),
+        AccountMeta::new_readonly(accounts.vault_0_mint, false),
+        AccountMeta::new_readonly(accounts.vault_1_mint, false),
+    ];
+    if let Some(tick_array_bitmap_extension) = accounts.tick_array_bitmap_extension {
+        metas.push(AccountMeta::new(tick_array_bitmap_extension, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data: liquidity_change_data(
+            increase_liquidity_v2_discriminator(),
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+            // `base_flag: None`: the deposit is sized by `liquidity`.
+            true,
+        ),
+    }
+}
+
+/// `decrease_liquidity_v2` instruction of the CLMM program `program_id`
+/// removing `liquidity` from a position, receiving at least `amount_0_min`
+/// and `amount_1_min`, transfer fees deducted.
+///
+/// The fees and rewards owed to the position are paid out too. The program
+/// requires `rewards` to hold the accounts of every initialized reward of the
+/// pool, in the order of its reward infos.
+pub fn decrease_liquidity_instruction(
+    accounts: &PositionLiquidityAccounts,
+    liquidity: u128,
+    amount_0_min: u64,
+    amount_1_min: u64,
+    rewards: &[RewardAccounts],
+    program_id: &Pubkey,
+) -> Instruction {
+    let keys = accounts.position_keys(program_id);
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.nft_owner, true),
+        AccountMeta::new_readonly(accounts.nft_account, false),
+        AccountMeta::new(keys.personal_position, false),
+        AccountMeta::new(accounts.pool_id, false),
+        AccountMeta::new(keys.protocol_position, false),
+        AccountMeta::new(accounts.token_vault_0, false),
+        AccountMeta::new(accounts.token_vault_1, false),
+        AccountMeta::new(keys.tick_array_lower, false),
+        AccountMeta::new(keys.tick_array_upper, false),
+        AccountMeta::new(accounts.token_account_0, false),
+        AccountMeta::new(accounts.token_account_1, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(Pubkey::from(spl_token_2022::id().to_bytes()), false),
+        AccountMeta::new_readonly(Pubkey::from(spl_memo::id().to_bytes()), false),
+        AccountMeta::new_readonly(accounts.vault_0_mint, false),
+        AccountMeta::new_readonly(accounts.vault_1_mint, false),
+    ];
+    if let Some(tick_array_bitmap_extension) = accounts.tick_array_bitmap_extension {
+        metas.push(AccountMeta::new(tick_array_bitmap_extension, false));
+    }
+    for reward in rewards {
+        metas.extend([
+            AccountMeta::new(reward.reward_vault, false),
+            AccountMeta::new(reward.recipient_token_account, false),
+            AccountMeta::new_readonly(reward.reward_mint, false),
+        ]);
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts: metas,
+        data: liquidity_change_data(
+            decrease_liquidity_v2_discriminator(),
+            liquidity,
+            amount_0_min,
+            amount_1_min,
+            false,
+        ),
+    }
+}
+
+/// Data of `increase_liquidity_v2` or `decrease_liquidity_v2`, the former
+/// with an empty `base_flag`.
+fn liquidity_change_data(
+    discriminator: [u8; 8],
+    liquidity: u128,
+    amount_0: u64,
+    amount_1: u64,
+    with_base_flag: bool,
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + 16 + 8 + 8 + 1);
+    data.extend_from_slice(&discriminator);
+    data.extend_from_slice(&liquidity.to_le_bytes());
+    data.extend_from_slice(&amount_0.to_le_bytes());
+    data.extend_from_slice(&amount_1.to_le_bytes());
+    if with_base_flag {
+        data.push(0);
+    }
+    data
+}
+
+/// Fees and rewards owed to a position, as of the last update of the fee
+/// and reward growths of its pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PositionFees {
+    pub fees_owed_0: u64,
+    pub fees_owed_1: u64,
+    /// Owed amounts of the pool rewards, in the order of its reward infos,
+    /// zero for uninitialized rewards.
+    pub rewards_owed: [u64; REWARD_NUM],
+}
+
+impl PositionFees {
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Fees and rewards owed to `position`: the amounts recorded at its last
+/// update plus those accrued since, from the growths inside its range.
+/// `tick_lower` and `tick_upper` are the tick states of its range bounds.
+pub fn position_fees(
+    position: &PersonalPositionState,
+    pool_state: &PoolState,
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+) -> PositionFees {
+    let tick_current = pool_state.tick_current;
+    let reward_infos = pool_state.reward_infos;
+    let (fee_growth_inside_0, fee_growth_inside_1) = get_fee_growth_inside(
+        tick_lower,
+        tick_upper,
+        tick_current,
+        pool_state.fee_growth_global_0_x64,
+        pool_state.fee_growth_global_1_x64,
+    );
+    let reward_growths_inside =
+        get_reward_growths_inside(tick_lower, tick_upper, tick_current, &reward_infos);
+    let accrued = |growth_inside: u128, growth_inside_last: u128| {
+        U128::from(growth_inside.wrapping_sub(growth_inside_last))
+            .mul_div_floor(U128::from(position.liquidity), U128::from(Q64))
+            .map_or(0, |amount| amount.to_underflow_u64())
+    };
+    let mut rewards_owed = [0u64; REWARD_NUM];
+    for ((owed, reward), growth_inside) in rewards_owed
+        .iter_mut()
+        .zip(&position.reward_infos)
+        .zip(reward_growths_inside)
+    {
+        *owed = reward
+            .reward_amount_owed
+            .saturating_add(accrued(growth_inside, reward.growth_inside_last_x64));
+    }
+    PositionFees {
+        fees_owed_0: position.token_fees_owed_0.saturating_add(accrued(
+            fee_growth_inside_0,
+            position.fee_growth_inside_0_last_x64,
+        )),
+        fees_owed_1: position.token_fees_owed_1.saturating_add(accrued(
+            fee_growth_inside_1,
+            position.fee_growth_inside_1_last_x64,
+        )),
+        rewards_owed,
+    }
+}
+
+/// Position to close with [`AmmSwapClient::close_position`].
+///
+/// [`AmmSwapClient::close_position`]: crate::amm::client::AmmSwapClient::close_position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClosePositionParams {
+    /// Mint of the position NFT, held by the owner.
+    pub nft_mint: Pubkey,
+    /// Slippage on the amounts of the liquidity still in the position.
+    pub slippage_bps: u64,
+}
+
+/// Fees and rewards collected with
+/// [`AmmSwapClient::collect_fees_and_rewards`].
+///
+/// [`AmmSwapClient::collect_fees_and_rewards`]: crate::amm::client::AmmSwapClient::collect_fees_and_rewards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollectedFees {
+    pub signature: Signature,
+    /// Owed amounts when the transaction was built, before transfer fees.
+    pub fees: PositionFees,
+}
+
+/// Position closed with [`AmmSwapClient::close_position`].
+///
+/// [`AmmSwapClient::close_position`]: crate::amm::client::AmmSwapClient::close_position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosedPosition {
+    pub signature: Signature,
+    /// Liquidity removed from the position before closing it.
+    pub liquidity: u128,
+    /// Least token 0 and token 1 received for `liquidity`, slippage and
+    /// transfer fee included.
+    pub amount_0_min: u64,
+    pub amount_1_min: u64,
+    /// Fees and rewards collected, see [`CollectedFees::fees`].
+    pub fees: PositionFees,
+}
+
+/// `close_position` instruction of the CLMM program `program_id` closing the
+/// personal position of `nft_mint` and burning the NFT held by `nft_owner` in
+/// `nft_account`. The position must hold no liquidity and be owed no fees or
+/// rewards.
+pub fn close_position_instruction(
+    nft_owner: Pubkey,
+    nft_mint: Pubkey,
+    nft_account: Pubkey,
+    nft_token_program: Pubkey,
+    program_id: &Pubkey,
+) -> Instruction {
+    let personal_position = derive_personal_position_key(
+        &solana_pubkey::Pubkey::from(nft_mint.to_bytes()),
+        &solana_pubkey::Pubkey::from(program_id.to_bytes()),
+    );
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(nft_owner, true),
+            AccountMeta::new(nft_mint, false),
+            AccountMeta::new(nft_account, false),
+            AccountMeta::new(Pubkey::from(personal_position.to_bytes()), false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            AccountMeta::new_readonly(nft_token_program, false),
+        ],
+        data: close_position_discriminator().to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clmm::clmm_program_id;
+
+    fn program_id() -> Pubkey {
+        Pubkey::from(clmm_program_id().to_bytes())
+    }
+
+    fn accounts(tick_array_bitmap_extension: Option<Pubkey>) -> OpenPositionAccounts {
+        let key = |byte: u8| Pubkey::new_from_array([byte; 32]);
+        OpenPositionAccounts {
+            payer: key(1),
+            nft_owner: key(1),
+            nft_mint: key(2),
+            pool_id: key(3),
+            token_account_0: key(4),
+            token_account_1: key(5),
+            token_vault_0: key(6),
+            token_vault_1: key(7),
+            vault_0_mint: key(8),
+            vault_1_mint: key(9),
+            tick_array_bitmap_extension,
+        }
+    }
+
+    fn args() -> OpenPositionArgs {
+        OpenPositionArgs {
+            tick_lower: -120,
+            tick_upper: 600,
+            tick_spacing: 10,
+            liquidity: 1_000_000,
+            amount_0_max: 500,
+            amount_1_max: 700,
+            with_metadata: true,
+        }
+    }
+
+    #[test]
+    fn tick_range_must_be_ordered_multiples_of_spacing() {
+        let range = PositionRange::Ticks {
+            lower: -120,
+            upper: 600,
+        };
+        assert_eq!(range.ticks(10, 9, 6).unwrap(), (-120, 600));
+        let unaligned = PositionRange::Ticks {
+            lower: -125,
+            upper: 600,
+        };
+        assert!(unaligned.ticks(10, 9, 6).is_err());
+        let reversed = PositionRange::Ticks {
+            lower: 600,
+            upper: -120,
+        };
+        assert!(reversed.ticks(10, 9, 6).is_err());
+    }
+
+    #[test]
+    fn price_range_rounds_down_to_spacing() {
+        let range = PositionRange::Prices {
+            lower: 0.5,
+            upper: 2.0,
+        };
+        let (lower, upper) = range.ticks(60, 6, 6).unwrap();
+        assert_eq!((lower % 60, upper % 60), (0, 0));
+        assert!(lower < 0 && upper > 0);
+        assert!(
+            PositionRange::Prices {
+                lower: -1.0,
+                upper: 2.0
+            }
+            .ticks(60, 6, 6)
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn liquidity_below_range_takes_only_token_0() {
+        let sqrt_price_x64 = get_sqrt_price_at_tick(-600).unwrap();
+        let position =
+            position_liquidity(sqrt_price_x64, -600, -120, 600, 1_000_000, 1_000_000).unwrap();
+        assert!(position.liquidity > 0);
+        assert!(position.amount_0 > 0 && position.amount_0 <= 1_000_001);
+        assert_eq!(position.amount_1, 0);
+    }
+
+    #[test]
+    fn open_position_v2_layout() {
+        let instruction = open_position_instruction(
+            PositionNft::SplToken,
+            &accounts(None),
+            &args(),
+            &program_id(),
+        );
+        assert_eq!(instruction.accounts.len(), 22);
+        assert_eq!(&instruction.data[..8], &open_position_v2_discriminator());
+        assert_eq!(instruction.data.len(), 8 + 16 + 16 + 8 + 8 + 1 + 1);
+        assert_eq!(&instruction.data[16..20], &(-600i32).to_le_bytes());
+        assert!(instruction.accounts[2].is_signer);
+        assert_eq!(
+            instruction.accounts[4].pubkey,
+            derive_position_metadata_key(&Pubkey::new_from_array([2; 32]))
+        );
+    }
+
+    #[test]
+    fn token22_nft_skips_metadata_accounts() {
+        let extension = Pubkey::new_from_array([10; 32]);
+        let instruction = open_position_instruction(
+            PositionNft::Token2022,
+            &accounts(Some(extension)),
+            &args(),
+            &program_id(),
+        );
+        assert_eq!(instruction.accounts.len(), 21);
+        assert_eq!(
+            &instruction.data[..8],
+            &open_position_with_token22_nft_discriminator()
+        );
+        assert_eq!(instruction.accounts.last().unwrap().pubkey, extension);
+        assert_eq!(
+            instruction.accounts[3].pubkey,
+            get_associated_token_address_with_program_id(
+                &Pubkey::new_from_array([1; 32]),
+                &Pubkey::new_from_array([2; 32]),
+                &PositionNft::Token2022.token_program(),
+            )
+        );
+    }
+
+    fn liquidity_accounts() -> PositionLiquidityAccounts {
+        let key = |byte: u8| Pubkey::new_from_array([byte; 32]);
+        PositionLiquidityAccounts {
+            nft_owner: key(1),
+            nft_account: key(2),
+            nft_mint: key(3),
+            pool_id: key(4),
+            tick_lower: -120,
+            tick_upper: 600,
+            tick_spacing: 10,
+            token_account_0: key(5),
+            token_account_1: key(6),
+            token_vault_0: key(7),
+            token_vault_1: key(8),
+            vault_0_mint: key(9),
+            vault_1_mint: key(10),
+            tick_array_bitmap_extension: None,
+        }
+    }
+
+    #[test]
+    fn increase_liquidity_layout() {
+        let instruction =
+            increase_liquidity_instruction(&liquidity_accounts(), 1_000, 5, 7, &program_id());
+        assert_eq!(instruction.accounts.len(), 15);
+        assert_eq!(
+            &instruction.data[..8],
+            &increase_liquidity_v2_discriminator()
+        );
+        assert_eq!(&instruction.data[8..24], &1_000u128.to_le_bytes());
+        assert_eq!(instruction.data.len(), 8 + 16 + 8 + 8 + 1);
+        assert!(instruction.accounts[0].is_signer);
+        let personal_position = derive_personal_position_key(
+            &solana_pubkey::Pubkey::new_from_array([3; 32]),
+            &clmm_program_id(),
+        );
+        assert_eq!(
+            instruction.accounts[4].pubkey,
+            Pubkey::from(personal_position.to_bytes())
+        );
+    }
+
+    #[test]
+    fn decrease_liquidity_appends_reward_accounts() {
+        let reward = RewardAccounts {
+            reward_vault: Pubkey::new_from_array([20; 32]),
+            recipient_token_account: Pubkey::new_from_array([21; 32]),
+            reward_mint: Pubkey::new_from_array([22; 32]),
+        };
+        let instruction = decrease_liquidity_instruction(
+            &liquidity_accounts(),
+            1_000,
+            5,
+            7,
+            &[reward],
+            &program_id(),
+        );
+        assert_eq!(instruction.accounts.len(), 16 + 3);
+        assert_eq!(
+            &instruction.data[..8],
+            &decrease_liquidity_v2_discriminator()
+        );
+        assert_eq!(instruction.data.len(), 8 + 16 + 8 + 8);
+        let tail: Vec<_> = instruction.accounts[16..]
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable))
+            .collect();
+        assert_eq!(
+            tail,
+            [
+                (reward.reward_vault, true),
+                (reward.recipient_token_account, true),
+                (reward.reward_mint, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn position_fees_accrue_growth_inside_range() {
+        let pool_state = PoolState {
+            tick_current: 0,
+            fee_growth_global_0_x64: 10 * Q64,
+            fee_growth_global_1_x64: 4 * Q64,
+            ..Default::default()
+        };
+        let tick_lower = TickState {
+            tick: -60,
+            fee_growth_outside_0_x64: 2 * Q64,
+            ..Default::default()
+        };
+        let tick_upper = TickState {
+            tick: 60,
+            fee_growth_outside_0_x64: 3 * Q64,
+            fee_growth_outside_1_x64: Q64,
+            ..Default::default()
+        };
+        let position = PersonalPositionState {
+            liquidity: 100,
+            fee_growth_inside_0_last_x64: Q64,
+            token_fees_owed_1: 7,
+            ..Default::default()
+        };
+        let fees = position_fees(&position, &pool_state, &tick_lower, &tick_upper);
+        // Growths inside of 10 - 2 - 3 = 5 and 4 - 0 - 1 = 3 per unit.
+        assert_eq!(fees.fees_owed_0, (5 - 1) * 100);
+        assert_eq!(fees.fees_owed_1, 3 * 100 + 7);
+        assert_eq!(fees.rewards_owed, [0; REWARD_NUM]);
+        assert!(!fees.is_zero());
+    }
+
+    #[test]
+    fn close_position_layout() {
+        let key = |byte: u8| Pubkey::new_from_array([byte; 32]);
+        let instruction =
+            close_position_instruction(key(1), key(2), key(3), spl_token::id(), &program_id());
+        assert_eq!(instruction.data, close_position_discriminator());
+        assert_eq!(instruction.accounts.len(), 6);
+        assert!(instruction.accounts[0].is_signer);
+        assert!(
+            instruction.accounts[..4]
+                .iter()
+                .all(|meta| meta.is_writable)
+        );
+        assert_eq!(instruction.accounts[5].pubkey, spl_token::id());
+    }
+}
@@ -0,0 +1,238 @@
+//! In-memory index of CLMM personal positions.
+//!
+//! Positions are found with `getProgramAccounts` on the CLMM program, filtered
+//! by pool. A position belongs to whoever holds its NFT, so every refresh also
+//! looks up the holder of each position NFT, one RPC call per open position.
+
+use crate::common::{TokenAccountState, unpack_token};
+use crate::consts::CLMM;
+use crate::states::PersonalPositionState;
+use anchor_lang::AccountDeserialize;
+use anyhow::Result;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Tick range of a position, `[tick_lower, tick_upper)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TickRange {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+}
+
+/// An open position with its current holder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedPosition {
+    pub position: Pubkey,
+    pub nft_mint: Pubkey,
+    pub pool_id: Pubkey,
+    /// Holder of the position NFT, `None` if it could not be resolved.
+    pub owner: Option<Pubkey>,
+    pub range: TickRange,
+    pub liquidity: u128,
+}
+
+/// Liquidity of a pool grouped by owner, then by tick range.
+pub type LiquidityByOwner = HashMap<Pubkey, BTreeMap<TickRange, u128>>;
+
+/// Index of the open positions of a set of CLMM pools.
+#[derive(Clone, Debug)]
+pub struct ClmmPositionIndexer {
+    pools: Vec<Pubkey>,
+    /// CLMM program owning the pools, mainnet unless set.
+    program_id: Pubkey,
+    positions: HashMap<Pubkey, IndexedPosition>,
+}
+
+impl Default for ClmmPositionIndexer {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl ClmmPositionIndexer {
+    pub fn new(pools: Vec<Pubkey>) -> Self {
+        Self {
+            pools,
+            program_id: Pubkey::from_str_const(CLMM),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Index pools of the CLMM program `program_id`, e.g. the one of
+    /// [`ClusterConfig::devnet`](crate::config::ClusterConfig::devnet).
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    pub fn pools(&self) -> &[Pubkey] {
+        &self.pools
+    }
+
+    /// Rescan all positions of the indexed pools, replacing the index.
+    /// Returns the number of open positions found.
+    pub async fn refresh(&mut self, rpc_client: &RpcClient) -> Result<usize> {
+        let mut positions = HashMap::new();
+        for pool_id in &self.pools {
+            for position in fetch_pool_positions(rpc_client, &self.program_id, pool_id).await? {
+                positions.insert(position.position, position);
+            }
+        }
+        resolve_owners(rpc_client, positions.values_mut()).await?;
+        debug!("Indexed {} positions in {} pools", positions.len(), self.pools.len());
+        self.positions = positions;
+        Ok(self.positions.len())
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = &IndexedPosition> {
+        self.positions.values()
+    }
+
+    pub fn get(&self, position: &Pubkey) -> Option<&IndexedPosition> {
+        self.positions.get(position)
+    }
+
+    /// Positions held by `owner` across the indexed pools.
+    pub fn positions_of(&self, owner: &Pubkey) -> Vec<&IndexedPosition> {
+        self.positions
+            .values()
+            .filter(|position| position.owner.as_ref() == Some(owner))
+            .collect()
+    }
+
+    /// Liquidity of `pool_id` by owner and tick range. Positions without a
+    /// resolved owner are left out.
+    pub fn liquidity_by_owner(&self, pool_id: &Pubkey) -> LiquidityByOwner {
+        let mut liquidity: LiquidityByOwner = HashMap::new();
+        for position in self.pool_positions(pool_id) {
+            if let Some(owner) = position.owner {
+                *liquidity
+                    .entry(owner)
+                    .or_default()
+                    .entry(position.range)
+                    .or_default() += position.liquidity;
+            }
+        }
+        liquidity
+    }
+
+    /// Liquidity of `pool_id` by tick range, over all owners.
+    pub fn liquidity_by_range(&self, pool_id: &Pubkey) -> BTreeMap<TickRange, u128> {
+        let mut liquidity = BTreeMap::new();
+        for position in self.pool_positions(pool_id) {
+            *liquidity.entry(position.range).or_default() += position.liquidity;
+        }
+        liquidity
+    }
+
+    fn pool_positions<'a>(
+        &'a self,
+        pool_id: &'a Pubkey,
+    ) -> impl Iterator<Item = &'a IndexedPosition> + 'a {
+        self.positions
+            .values()
+            .filter(move |position| position.pool_id == *pool_id)
+    }
+}
+
+/// Open positions of a pool, without owners.
+async fn fetch_pool_positions(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    pool_id: &Pubkey,
+) -> Result<Vec<IndexedPosition>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(PersonalPositionState::LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                PersonalPositionState::POOL_ID_OFFSET,
+                pool_id.to_bytes().to_vec(),
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = rpc_client
+        .get_program_accounts_with_config(program_id, config)
+        .await?;
+
+    let mut positions = Vec::with_capacity(accounts.len());
+    for (address, account) in accounts {
+        let state = match PersonalPositionState::try_deserialize(&mut account.data.as_slice()) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Skipping undecodable position {address}: {e:?}");
+                continue;
+            }
+        };
+        // Closed positions keep no liquidity and have nothing to report.
+        if state.liquidity == 0 {
+            continue;
+        }
+        positions.push(IndexedPosition {
+            position: address,
+            nft_mint: Pubkey::from(state.nft_mint.to_bytes()),
+            pool_id: *pool_id,
+            owner: None,
+            range: TickRange {
+                tick_lower: state.tick_lower_index,
+                tick_upper: state.tick_upper_index,
+            },
+            liquidity: state.liquidity,
+        });
+    }
+    Ok(positions)
+}
+
+/// Set the owner of each position to the holder of its NFT.
+async fn resolve_owners<'a>(
+    rpc_client: &RpcClient,
+    positions: impl Iterator<Item = &'a mut IndexedPosition>,
+) -> Result<()> {
+    let mut holders = Vec::new();
+    for position in positions {
+        let largest = match rpc_client.get_token_largest_accounts(&position.nft_mint).await {
+            Ok(largest) => largest,
+            Err(e) => {
+                warn!("Failed to find the holder of {}: {e:?}", position.nft_mint);
+                continue;
+            }
+        };
+        let holder = largest
+            .into_iter()
+            .find(|balance| balance.amount.amount == "1")
+            .and_then(|balance| Pubkey::from_str(&balance.address).ok());
+        if let Some(token_account) = holder {
+            holders.push((token_account, position));
+        }
+    }
+
+    for chunk in holders.chunks_mut(MAX_MULTIPLE_ACCOUNTS) {
+        let keys: Vec<Pubkey> = chunk.iter().map(|(token_account, _)| *token_account).collect();
+        let accounts = rpc_client.get_multiple_accounts(&keys).await?;
+        for ((_, position), account) in chunk.iter_mut().zip(accounts) {
+            let Some(account) = account else {
+                continue;
+            };
+            position.owner = match unpack_token(&account.owner, &account.data) {
+                Ok(TokenAccountState::SplToken(state)) => Some(state.owner),
+                Ok(TokenAccountState::SplToken2022(state)) => {
+                    Some(Pubkey::from(state.base.owner.to_bytes()))
+                }
+                Err(_) => None,
+            };
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+//! Detection of CLMM account copies that look stale.
+//!
+//! Copies of pool states and tick arrays kept across quotes, in an
+//! [`AccountCache`](crate::common::AccountCache) or a long-lived
+//! [`ClmmQuoteLadder`], fall behind the chain without notice. Two hints are
+//! available: the slot an account was read at, and the `recent_epoch` the
+//! program stamps pool states and tick arrays with when it updates them. A
+//! [`StalenessPolicy`] flags copies read too many slots ago, or stamped too
+//! many epochs before the current one.
+//!
+//! `recent_epoch` only moves when the account changes, so an account of a
+//! quiet pool read a moment ago can lag by many epochs. Epoch lags are only
+//! reported; refreshing is left to slot lags.
+
+use crate::clmm::{ClmmQuoteLadder, derive_tick_array_key};
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+
+/// Limits past which account copies are flagged as stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StalenessPolicy {
+    /// Copies read more than this many slots before the current slot are
+    /// stale. `None` disables the check.
+    pub max_slot_lag: Option<u64>,
+    /// Pool states and tick arrays whose `recent_epoch` is more than this
+    /// many epochs before the current epoch are stale. `None` disables the
+    /// check.
+    pub max_epoch_lag: Option<u64>,
+    /// Whether copies read more than `max_slot_lag` slots ago are read again
+    /// before quoting, rather than only flagged. Epoch lags are never
+    /// refreshed.
+    pub refresh: bool,
+}
+
+impl Default for StalenessPolicy {
+    /// Copies read more than 150 slots (about a minute) ago are read again;
+    /// ones stamped before the previous epoch are flagged.
+    fn default() -> Self {
+        Self {
+            max_slot_lag: Some(150),
+            max_epoch_lag: Some(1),
+            refresh: true,
+        }
+    }
+}
+
+/// Why an account copy was flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Staleness {
+    SlotLag {
+        read_slot: u64,
+        current_slot: u64,
+    },
+    EpochLag {
+        recent_epoch: u64,
+        current_epoch: u64,
+    },
+}
+
+/// Account copy flagged by a [`StalenessPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleAccount {
+    pub address: Pubkey,
+    pub staleness: Staleness,
+}
+
+impl fmt::Display for StaleAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.staleness {
+            Staleness::SlotLag {
+                read_slot,
+                current_slot,
+            } => write!(
+                f,
+                "{} was read at slot {read_slot}, {} slots before slot {current_slot}",
+                self.address,
+                current_slot.saturating_sub(read_slot)
+            ),
+            Staleness::EpochLag {
+                recent_epoch,
+                current_epoch,
+            } => write!(
+                f,
+                "{} was last updated in epoch {recent_epoch}, current epoch is {current_epoch}",
+                self.address
+            ),
+        }
+    }
+}
+
+impl StalenessPolicy {
+    /// Flag `address` if it was read at `read_slot` too long before
+    /// `current_slot`.
+    pub fn check_slot(
+        &self,
+        address: &Pubkey,
+        read_slot: u64,
+        current_slot: u64,
+    ) -> Option<StaleAccount> {
+        let max_slot_lag = self.max_slot_lag?;
+        (current_slot.saturating_sub(read_slot) > max_slot_lag).then_some(StaleAccount {
+            address: *address,
+            staleness: Staleness::SlotLag {
+                read_slot,
+                current_slot,
+            },
+        })
+    }
+
+    /// Flag `address` if its `recent_epoch` is too far behind
+    /// `current_epoch`.
+    pub fn check_epoch(
+        &self,
+        address: &Pubkey,
+        recent_epoch: u64,
+        current_epoch: u64,
+    ) -> Option<StaleAccount> {
+        let max_epoch_lag = self.max_epoch_lag?;
+        (current_epoch.saturating_sub(recent_epoch) > max_epoch_lag).then_some(StaleAccount {
+            address: *address,
+            staleness: Staleness::EpochLag {
+                recent_epoch,
+                current_epoch,
+            },
+        })
+    }
+}
+
+impl ClmmQuoteLadder {
+    /// Pool state and tick arrays of the ladder of `pool_id`, a pool of the
+    /// CLMM program `program_id`, whose `recent_epoch` is too far behind
+    /// `current_epoch` under `policy`.
+    pub fn stale_accounts(
+        &self,
+        pool_id: &Pubkey,
+        policy: &StalenessPolicy,
+        current_epoch: u64,
+        program_id: &solana_pubkey::Pubkey,
+    ) -> Vec<StaleAccount> {
+        let pool_id_v2 = solana_pubkey::Pubkey::from(pool_id.to_bytes());
+        let recent_epoch = self.pool_state.recent_epoch;
+        let pool_state = policy.check_epoch(pool_id, recent_epoch, current_epoch);
+        let tick_arrays = self.tick_arrays.iter().filter_map(|tick_array| {
+            let (start_tick_index, recent_epoch) =
+                (tick_array.start_tick_index, tick_array.recent_epoch);
+            let address = derive_tick_array_key(&pool_id_v2, start_tick_index, program_id);
+            policy.check_epoch(
+                &Pubkey::from(address.to_bytes()),
+                recent_epoch,
+                current_epoch,
+            )
+        });
+        pool_state.into_iter().chain(tick_arrays).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address() -> Pubkey {
+        Pubkey::new_from_array([7; 32])
+    }
+
+    #[test]
+    fn check_slot_flags_reads_beyond_the_lag() {
+        let policy = StalenessPolicy::default();
+        assert_eq!(policy.check_slot(&address(), 1_000, 1_150), None);
+        assert_eq!(
+            policy.check_slot(&address(), 1_000, 1_151),
+            Some(StaleAccount {
+                address: address(),
+                staleness: Staleness::SlotLag {
+                    read_slot: 1_000,
+                    current_slot: 1_151,
+                },
+            })
+        );
+        // A read slot ahead of the current one is not a lag.
+        assert_eq!(policy.check_slot(&address(), 2_000, 1_000), None);
+    }
+
+    #[test]
+    fn check_epoch_flags_stamps_beyond_the_lag() {
+        let policy = StalenessPolicy::default();
+        assert_eq!(policy.check_epoch(&address(), 699, 700), None);
+        assert_eq!(
+            policy.check_epoch(&address(), 698, 700),
+            Some(StaleAccount {
+                address: address(),
+                staleness: Staleness::EpochLag {
+                    recent_epoch: 698,
+                    current_epoch: 700,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn disabled_checks_flag_nothing() {
+        let policy = StalenessPolicy {
+            max_slot_lag: None,
+            max_epoch_lag: None,
+            refresh: true,
+        };
+        assert_eq!(policy.check_slot(&address(), 0, u64::MAX), None);
+        assert_eq!(policy.check_epoch(&address(), 0, u64::MAX), None);
+    }
+}
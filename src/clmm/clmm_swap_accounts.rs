@@ -0,0 +1,284 @@
+//! Accounts of the CLMM `SwapV2` instruction.
+//!
+//! The order is the one of the program's `SwapSingleV2` accounts struct,
+//! followed by the remaining accounts the swap walks through: the tick array
+//! bitmap extension when the pool has one, then the tick arrays in swap
+//! direction.
+
+use crate::clmm::ClmmSwapChangeResult;
+use anchor_spl::memo::spl_memo;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+
+/// Accounts of `SwapSingleV2` before the remaining accounts.
+pub const CLMM_SWAP_V2_FIXED_ACCOUNTS: usize = 13;
+
+/// Keys of the `SwapSingleV2` accounts that differ between swaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClmmSwapV2Keys {
+    pub payer: Pubkey,
+    pub amm_config: Pubkey,
+    pub pool_id: Pubkey,
+    pub user_input_token: Pubkey,
+    pub user_output_token: Pubkey,
+    pub input_vault: Pubkey,
+    pub output_vault: Pubkey,
+    pub observation_state: Pubkey,
+    pub input_vault_mint: Pubkey,
+    pub output_vault_mint: Pubkey,
+}
+
+/// Accounts of a `SwapV2` instruction: the `SwapSingleV2` accounts of
+/// `keys`, then `remaining_accounts`.
+pub fn clmm_swap_v2_accounts(
+    keys: &ClmmSwapV2Keys,
+    remaining_accounts: impl IntoIterator<Item = AccountMeta>,
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(keys.payer, true),
+        AccountMeta::new_readonly(keys.amm_config, false),
+        AccountMeta::new(keys.pool_id, false),
+        AccountMeta::new(keys.user_input_token, false),
+        AccountMeta::new(keys.user_output_token, false),
+        AccountMeta::new(keys.input_vault, false),
+        AccountMeta::new(keys.output_vault, false),
+        AccountMeta::new(keys.observation_state, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(Pubkey::from(spl_token_2022::id().to_bytes()), false),
+        AccountMeta::new_readonly(Pubkey::from(spl_memo::id().to_bytes()), false),
+        AccountMeta::new_readonly(keys.input_vault_mint, false),
+        AccountMeta::new_readonly(keys.output_vault_mint, false),
+    ];
+    accounts.extend(remaining_accounts);
+    accounts
+}
+
+/// Accounts of a `SwapV2` instruction swapping `result` from its
+/// `user_input_token` to `user_output_token`, signed by `payer`.
+///
+/// `tick_array_bitmap_extension` is only included when
+/// `result.has_tick_array_bitmap_extension`, as the program rejects one that
+/// does not exist.
+pub fn build_clmm_swap_accounts(
+    result: &ClmmSwapChangeResult,
+    payer: &Pubkey,
+    user_output_token: &Pubkey,
+    tick_array_bitmap_extension: &solana_pubkey::Pubkey,
+) -> Vec<AccountMeta> {
+    fn to_sdk_pubkey(pubkey: &solana_pubkey::Pubkey) -> Pubkey {
+        Pubkey::from(pubkey.to_bytes())
+    }
+
+    let keys = ClmmSwapV2Keys {
+        payer: *payer,
+        amm_config: to_sdk_pubkey(&result.pool_amm_config),
+        pool_id: to_sdk_pubkey(&result.pool_id),
+        user_input_token: to_sdk_pubkey(&result.user_input_token),
+        user_output_token: *user_output_token,
+        input_vault: to_sdk_pubkey(&result.input_vault),
+        output_vault: to_sdk_pubkey(&result.output_vault),
+        observation_state: to_sdk_pubkey(&result.pool_observation),
+        input_vault_mint: to_sdk_pubkey(&result.input_vault_mint),
+        output_vault_mint: to_sdk_pubkey(&result.output_vault_mint),
+    };
+    let bitmap_extension = result
+        .has_tick_array_bitmap_extension
+        .then(|| AccountMeta::new_readonly(to_sdk_pubkey(tick_array_bitmap_extension), false));
+    let tick_arrays = result
+        .remaining_tick_array_keys
+        .iter()
+        .map(|tick_array| AccountMeta::new(to_sdk_pubkey(tick_array), false));
+    clmm_swap_v2_accounts(&keys, bitmap_extension.into_iter().chain(tick_arrays))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clmm::{
+        ClmmQuote, clmm_program_id, derive_tick_array_bitmap_extension_key, derive_tick_array_keys,
+    };
+    use crate::common::FeeTier;
+    use spl_associated_token_account::get_associated_token_address;
+    use std::collections::VecDeque;
+
+    const POOL_ID: solana_pubkey::Pubkey =
+        solana_pubkey::Pubkey::from_str_const("3ucNos4NbumPLZNWztqGHNFFgkHeRMBQAVemeeomsUxv");
+
+    fn key(byte: u8) -> solana_pubkey::Pubkey {
+        solana_pubkey::Pubkey::new_from_array([byte; 32])
+    }
+
+    fn swap_change(has_tick_array_bitmap_extension: bool) -> ClmmSwapChangeResult {
+        ClmmSwapChangeResult {
+            pool_amm_config: key(1),
+            pool_id: POOL_ID,
+            pool_observation: key(2),
+            input_vault: key(3),
+            output_vault: key(4),
+            input_vault_mint: key(5),
+            output_vault_mint: key(6),
+            input_token_program: solana_pubkey::Pubkey::from(spl_token::id().to_bytes()),
+            output_token_program: solana_pubkey::Pubkey::from(spl_token::id().to_bytes()),
+            user_input_token: key(7),
+            remaining_tick_array_keys: VecDeque::from([key(8), key(9)]),
+            amount: 1_000_000,
+            other_amount_threshold: 0,
+            sqrt_price_limit_x64: None,
+            is_base_input: true,
+            fee_tier: FeeTier::default(),
+            amount_requested: 1_000_000,
+            amount_consumed: 1_000_000,
+            fully_filled: true,
+            has_tick_array_bitmap_extension,
+            epoch: 0,
+            context_slot: None,
+            sqrt_price_x64: 0,
+            tick_current: 0,
+            quote: ClmmQuote::default(),
+        }
+    }
+
+    fn sdk(pubkey: solana_pubkey::Pubkey) -> Pubkey {
+        Pubkey::from(pubkey.to_bytes())
+    }
+
+    #[test]
+    fn accounts_follow_swap_single_v2_order() {
+        let payer = Pubkey::new_from_array([10; 32]);
+        let user_output_token = Pubkey::new_from_array([11; 32]);
+        let bitmap = derive_tick_array_bitmap_extension_key(&POOL_ID, &clmm_program_id());
+        let accounts =
+            build_clmm_swap_accounts(&swap_change(true), &payer, &user_output_token, &bitmap);
+
+        let expected = [
+            (payer, true, true),
+            (sdk(key(1)), false, false),
+            (sdk(POOL_ID), true, false),
+            (sdk(key(7)), true, false),
+            (user_output_token, true, false),
+            (sdk(key(3)), true, false),
+            (sdk(key(4)), true, false),
+            (sdk(key(2)), true, false),
+            (spl_token::id(), false, false),
+            (Pubkey::from(spl_token_2022::id().to_bytes()), false, false),
+            (Pubkey::from(spl_memo::id().to_bytes()), false, false),
+            (sdk(key(5)), false, false),
+            (sdk(key(6)), false, false),
+            (sdk(bitmap), false, false),
+            (sdk(key(8)), true, false),
+            (sdk(key(9)), true, false),
+        ];
+        let actual: Vec<_> = accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable, meta.is_signer))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn missing_bitmap_extension_is_left_out() {
+        let payer = Pubkey::new_from_array([10; 32]);
+        let user_output_token = Pubkey::new_from_array([11; 32]);
+        let bitmap = derive_tick_array_bitmap_extension_key(&POOL_ID, &clmm_program_id());
+        let accounts =
+            build_clmm_swap_accounts(&swap_change(false), &payer, &user_output_token, &bitmap);
+
+        assert_eq!(accounts.len(), CLMM_SWAP_V2_FIXED_ACCOUNTS + 2);
+        assert!(accounts.iter().all(|meta| meta.pubkey != sdk(bitmap)));
+        assert_eq!(accounts[CLMM_SWAP_V2_FIXED_ACCOUNTS].pubkey, sdk(key(8)));
+    }
+
+    /// Keys of the mainnet SOL/USDC pool, each checked against its seeds.
+    const AMM_CONFIG: &str = "3XCQJQryqpDvvZBfGxR7CLAw5dpGJ9aa7kt1jRLdyxuZ";
+    const SOL_VAULT: &str = "4ct7br2vTPzfdmY3S5HLtTxcGSBfn6pnw98hsS6v359A";
+    const USDC_VAULT: &str = "5it83u57VRrVgc51oNV19TTmAJuffPx5GtGwQr7gQNUo";
+    const OBSERVATION: &str = "3Y695CuQ8AP4anbwAqiEBeQF9KxqHFr8piEwvw3UePnQ";
+    const BITMAP_EXTENSION: &str = "4NFvUKqknMpoe6CWTzK758B8ojVLzURL5pC6MtiaJ8TQ";
+    const WSOL: &str = "So11111111111111111111111111111111111111112";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn v2(address: &str) -> solana_pubkey::Pubkey {
+        solana_pubkey::Pubkey::from_str_const(address)
+    }
+
+    #[test]
+    fn mainnet_sol_usdc_fixture_matches_pool_seeds() {
+        let program_id = clmm_program_id();
+        let pda =
+            |seeds: &[&[u8]]| solana_pubkey::Pubkey::find_program_address(seeds, &program_id).0;
+
+        assert_eq!(pda(&[b"amm_config", &5u16.to_be_bytes()]), v2(AMM_CONFIG));
+        assert_eq!(
+            pda(&[b"pool_vault", POOL_ID.as_ref(), v2(WSOL).as_ref()]),
+            v2(SOL_VAULT)
+        );
+        assert_eq!(
+            pda(&[b"pool_vault", POOL_ID.as_ref(), v2(USDC).as_ref()]),
+            v2(USDC_VAULT)
+        );
+        assert_eq!(pda(&[b"observation", POOL_ID.as_ref()]), v2(OBSERVATION));
+        assert_eq!(
+            derive_tick_array_bitmap_extension_key(&POOL_ID, &program_id),
+            v2(BITMAP_EXTENSION)
+        );
+    }
+
+    #[test]
+    fn mainnet_sol_to_usdc_swap_v2_accounts() {
+        let program_id = clmm_program_id();
+        let payer = Pubkey::from_str_const("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM");
+        let user_wsol = get_associated_token_address(&payer, &sdk(v2(WSOL)));
+        let user_usdc = get_associated_token_address(&payer, &sdk(v2(USDC)));
+        let tick_arrays = derive_tick_array_keys(&POOL_ID, [-19_020, -19_080], &program_id);
+        let swap_change = ClmmSwapChangeResult {
+            pool_amm_config: v2(AMM_CONFIG),
+            pool_observation: v2(OBSERVATION),
+            input_vault: v2(SOL_VAULT),
+            output_vault: v2(USDC_VAULT),
+            input_vault_mint: v2(WSOL),
+            output_vault_mint: v2(USDC),
+            user_input_token: solana_pubkey::Pubkey::from(user_wsol.to_bytes()),
+            remaining_tick_array_keys: tick_arrays.iter().copied().collect(),
+            ..swap_change(true)
+        };
+
+        let accounts =
+            build_clmm_swap_accounts(&swap_change, &payer, &user_usdc, &v2(BITMAP_EXTENSION));
+
+        let expected = [
+            (payer, true, true),
+            (sdk(v2(AMM_CONFIG)), false, false),
+            (sdk(POOL_ID), true, false),
+            (user_wsol, true, false),
+            (user_usdc, true, false),
+            (sdk(v2(SOL_VAULT)), true, false),
+            (sdk(v2(USDC_VAULT)), true, false),
+            (sdk(v2(OBSERVATION)), true, false),
+            (
+                Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+                false,
+                false,
+            ),
+            (
+                Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+                false,
+                false,
+            ),
+            (
+                Pubkey::from_str_const("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
+                false,
+                false,
+            ),
+            (sdk(v2(WSOL)), false, false),
+            (sdk(v2(USDC)), false, false),
+            (sdk(v2(BITMAP_EXTENSION)), false, false),
+            (sdk(tick_arrays[0]), true, false),
+            (sdk(tick_arrays[1]), true, false),
+        ];
+        let actual: Vec<_> = accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable, meta.is_signer))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+}
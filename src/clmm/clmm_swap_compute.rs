@@ -0,0 +1,399 @@
+//! CLMM swap math on plain account states.
+//!
+//! [`compute_swap`] runs the same tick walk as the swap quotes of
+//! [`AmmSwapClient`](crate::amm::client::AmmSwapClient), but only reads the
+//! states passed in: no RPC client, raw accounts or token account lookups.
+//! Callers load the states once and can then quote as often as they like,
+//! e.g. in a simulation over many amounts.
+//!
+//! Transfer fees of Token-2022 mints are not applied by [`compute_swap`]; the
+//! amounts are the ones entering and leaving the pool vaults.
+//! [`compute_amount_in_clmm`] adds them to exact output swaps.
+
+use crate::clmm::clmm_utils::swap_compute;
+use crate::clmm::{exact_output_pool_amount, from_x64_price};
+use crate::common::{amount_with_slippage, get_transfer_inverse_fee};
+use crate::states::{
+    AmmConfig, FEE_RATE_DENOMINATOR_VALUE, PoolState, TickArrayBitmapExtension, TickArrayState,
+};
+use anyhow::{Result, anyhow};
+use solana_program_pack::Pack as SolanaProgramPack;
+use spl_token_2022::extension::{BaseState, StateWithExtensions};
+
+/// Inputs of [`compute_swap`].
+#[derive(Clone, Copy)]
+pub struct SwapComputeInput<'a> {
+    pub pool_state: &'a PoolState,
+    pub amm_config: &'a AmmConfig,
+    /// `None` for pools without a bitmap extension account.
+    pub tickarray_bitmap_extension: Option<&'a TickArrayBitmapExtension>,
+    /// Initialized tick arrays in swap direction, starting with the array of
+    /// the current tick (see [`get_tick_array_keys`](crate::clmm::get_tick_array_keys)).
+    pub tick_arrays: &'a [TickArrayState],
+    /// `true` to swap token 0 for token 1.
+    pub zero_for_one: bool,
+    /// `true` when `amount_specified` is the input, `false` when it is the
+    /// output.
+    pub is_base_input: bool,
+    pub amount_specified: u64,
+    /// Price limit of the swap, `None` for no limit.
+    pub sqrt_price_limit_x64: Option<u128>,
+    /// Cap on the steps of the tick walk, `None` for
+    /// [`SWAP_STEPS_PER_TICK_ARRAY`](crate::clmm::SWAP_STEPS_PER_TICK_ARRAY)
+    /// per tick array reached.
+    pub max_steps: Option<usize>,
+}
+
+/// Result of [`compute_swap`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapComputeResult {
+    /// Output for exact input swaps, input (fee included) for exact output
+    /// swaps. No slippage is applied.
+    pub other_amount: u64,
+    /// Start indexes of the tick arrays the swap moves through, in swap
+    /// direction.
+    pub tick_array_start_indexes: Vec<i32>,
+    /// Part of `amount_specified` consumed before the price limit was reached.
+    pub amount_consumed: u64,
+    /// `false` when the swap stopped at `sqrt_price_limit_x64` with part of
+    /// `amount_specified` left.
+    pub fully_filled: bool,
+}
+
+impl SwapComputeResult {
+    /// Minimum amount out of an exact input swap at `slippage_bps`.
+    pub fn preview_min_out(&self, slippage_bps: u64) -> Result<u64> {
+        amount_with_slippage(self.other_amount, slippage_bps, false)
+    }
+
+    /// Maximum amount in of an exact output swap at `slippage_bps`, before
+    /// Token-2022 transfer fees.
+    pub fn preview_max_in(&self, slippage_bps: u64) -> Result<u64> {
+        amount_with_slippage(self.other_amount, slippage_bps, true)
+    }
+}
+
+/// Compute the other side of a CLMM swap from plain pool states.
+///
+/// Does not modify any of the inputs; tick arrays are copied as the walk
+/// reaches them.
+///
+/// # Errors
+///
+/// Fails when `amount_specified` is zero, the price limit is on the wrong
+/// side of the current price, or `tick_arrays` runs out before the amount is
+/// consumed ([`SwapError::InsufficientLiquidityLoaded`](crate::error::SwapError::InsufficientLiquidityLoaded),
+/// also returned when the walk hits `max_steps`). Without a bitmap extension, swaps needing tick arrays outside
+/// the in-pool bitmap fail with `ErrorCode::MissingTickArrayBitmapExtensionAccount`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use raydium_amm_swap::clmm::{SwapComputeInput, compute_swap};
+/// use raydium_amm_swap::states::{AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState};
+///
+/// fn quote_curve(
+///     pool_state: &PoolState,
+///     amm_config: &AmmConfig,
+///     bitmap: &TickArrayBitmapExtension,
+///     tick_arrays: &[TickArrayState],
+/// ) -> anyhow::Result<Vec<u64>> {
+///     (1..=100u64)
+///         .map(|step| {
+///             let input = SwapComputeInput {
+///                 pool_state,
+///                 amm_config,
+///                 tickarray_bitmap_extension: Some(bitmap),
+///                 tick_arrays,
+///                 zero_for_one: true,
+///                 is_base_input: true,
+///                 amount_specified: step * 1_000_000,
+///                 sqrt_price_limit_x64: None,
+///                 max_steps: None,
+///             };
+///             Ok(compute_swap(&input)?.other_amount)
+///         })
+///         .collect()
+/// }
+/// ```
+pub fn compute_swap(input: &SwapComputeInput) -> Result<SwapComputeResult> {
+    let (is_pool_current_tick_array, current_valid_tick_array_start_index) =
+        input.pool_state.get_first_initialized_tick_array(
+            &input.tickarray_bitmap_extension.copied(),
+            input.zero_for_one,
+        )?;
+    let walk = swap_compute(
+        input.zero_for_one,
+        input.is_base_input,
+        is_pool_current_tick_array,
+        input.amm_config.trade_fee_rate,
+        input.amount_specified,
+        current_valid_tick_array_start_index,
+        input.sqrt_price_limit_x64.unwrap_or(0),
+        input.pool_state,
+        &input.tickarray_bitmap_extension.copied(),
+        input.tick_arrays.iter().copied(),
+        input.max_steps,
+    )?;
+    Ok(SwapComputeResult {
+        other_amount: walk.amount_calculated,
+        tick_array_start_indexes: walk.tick_array_start_indexes.into(),
+        amount_consumed: walk.amount_consumed,
+        fully_filled: walk.amount_consumed == input.amount_specified,
+    })
+}
+
+/// Result of [`compute_amount_in_clmm`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClmmAmountIn {
+    /// Debited from the user: `pool_amount_in` plus the input transfer fee.
+    /// Exact output swaps pass when `other_amount_threshold` is at least this.
+    pub amount_in: u64,
+    /// Input entering the pool vault, trade fee included.
+    pub pool_amount_in: u64,
+    /// Output leaving the pool vault: the requested output plus the output
+    /// transfer fee.
+    pub pool_amount_out: u64,
+    /// Token-2022 transfer fee charged on `amount_in`.
+    pub input_transfer_fee: u64,
+    /// Token-2022 transfer fee charged on `pool_amount_out`.
+    pub output_transfer_fee: u64,
+    /// Pool walk of `pool_amount_out`.
+    pub swap: SwapComputeResult,
+}
+
+/// Input needed for the user to receive `input.amount_specified` in an
+/// exact output swap, with the Token-2022 transfer fees of both mints.
+///
+/// Follows the program: the pool pays out the requested amount plus the
+/// inverse transfer fee of the output mint, and the user sends the pool
+/// input plus the inverse transfer fee of the input mint. Both inverse fees
+/// round up, so the amounts match the program to the lamport.
+///
+/// # Errors
+///
+/// Fails when `input.is_base_input` is `true`, and in the cases of
+/// [`compute_swap`].
+pub fn compute_amount_in_clmm<S: BaseState + SolanaProgramPack>(
+    input: &SwapComputeInput,
+    input_mint_state: &StateWithExtensions<S>,
+    output_mint_state: &StateWithExtensions<S>,
+    epoch: u64,
+) -> Result<ClmmAmountIn> {
+    if input.is_base_input {
+        return Err(anyhow!(
+            "compute_amount_in_clmm quotes exact output swaps only"
+        ));
+    }
+    let pool_amount_out =
+        exact_output_pool_amount(output_mint_state, epoch, input.amount_specified)?;
+    let swap = compute_swap(&SwapComputeInput {
+        amount_specified: pool_amount_out,
+        ..*input
+    })?;
+    let pool_amount_in = swap.other_amount;
+    let input_transfer_fee = get_transfer_inverse_fee(input_mint_state, epoch, pool_amount_in)?;
+    Ok(ClmmAmountIn {
+        amount_in: pool_amount_in
+            .checked_add(input_transfer_fee)
+            .ok_or(anyhow!("amount_in overflows with the transfer fee"))?,
+        pool_amount_in,
+        pool_amount_out,
+        input_transfer_fee,
+        output_transfer_fee: pool_amount_out - input.amount_specified,
+        swap,
+    })
+}
+
+/// States of a CLMM pool loaded once to quote many exact input amounts in one
+/// direction, e.g. while searching for an order size.
+///
+/// Built by `AmmSwapClient::load_clmm_quote_ladder`.
+pub struct ClmmQuoteLadder {
+    pub pool_state: PoolState,
+    pub amm_config: AmmConfig,
+    /// `None` for pools without a bitmap extension account.
+    pub tickarray_bitmap_extension: Option<TickArrayBitmapExtension>,
+    /// Initialized tick arrays in swap direction.
+    pub tick_arrays: Vec<TickArrayState>,
+    pub zero_for_one: bool,
+}
+
+impl ClmmQuoteLadder {
+    /// Quote an exact input swap of `amount_in`.
+    pub fn quote(&self, amount_in: u64) -> Result<SwapComputeResult> {
+        compute_swap(&SwapComputeInput {
+            pool_state: &self.pool_state,
+            amm_config: &self.amm_config,
+            tickarray_bitmap_extension: self.tickarray_bitmap_extension.as_ref(),
+            tick_arrays: &self.tick_arrays,
+            zero_for_one: self.zero_for_one,
+            is_base_input: true,
+            amount_specified: amount_in,
+            sqrt_price_limit_x64: None,
+            max_steps: None,
+        })
+    }
+
+    /// Quote each of `amounts`, in order.
+    pub fn quote_all(&self, amounts: &[u64]) -> Result<Vec<SwapComputeResult>> {
+        amounts.iter().map(|amount| self.quote(*amount)).collect()
+    }
+
+    /// Price impact of the quote `result` of `amount_in` in bps, with the
+    /// trade fee taken out of the input so only the curve is measured.
+    pub fn price_impact_bps(&self, amount_in: u64, result: &SwapComputeResult) -> f64 {
+        let sqrt_price = from_x64_price(self.pool_state.sqrt_price_x64);
+        // Token 1 per token 0, in raw units.
+        let price = sqrt_price * sqrt_price;
+        let spot_price = if self.zero_for_one { price } else { 1.0 / price };
+        let fee_rate = self.amm_config.trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64;
+        let amount_in = result.amount_consumed.min(amount_in) as f64 * (1.0 - fee_rate);
+        if amount_in <= 0.0 || spot_price <= 0.0 {
+            return f64::INFINITY;
+        }
+        let execution_price = result.other_amount as f64 / amount_in;
+        (spot_price - execution_price) / spot_price * 10_000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::get_transfer_fee;
+    use crate::libraries::Q64;
+    use crate::states::TickState;
+    use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeConfig};
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+    use spl_token_2022::state::Mint;
+
+    /// Token-2022 mint charging `transfer_fee_basis_points`, without a
+    /// maximum fee.
+    fn mint_data(transfer_fee_basis_points: u16) -> Vec<u8> {
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: transfer_fee_basis_points.into(),
+        };
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = transfer_fee;
+        config.newer_transfer_fee = transfer_fee;
+        state.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    /// Pool at price 1 with 10^12 liquidity up to tick 59, swapping token 1
+    /// for token 0 at a 0.25% trade fee.
+    fn pool() -> (PoolState, AmmConfig, TickArrayState) {
+        let pool_state = PoolState {
+            tick_spacing: 1,
+            sqrt_price_x64: Q64,
+            liquidity: 1_000_000_000_000,
+            tick_array_bitmap: [u64::MAX; 16],
+            ..Default::default()
+        };
+        let amm_config = AmmConfig {
+            trade_fee_rate: 2_500,
+            ..Default::default()
+        };
+        let mut tick_array = TickArrayState::default();
+        tick_array.ticks[59] = TickState {
+            tick: 59,
+            liquidity_gross: 1,
+            ..Default::default()
+        };
+        (pool_state, amm_config, tick_array)
+    }
+
+    fn exact_output<'a>(
+        pool_state: &'a PoolState,
+        amm_config: &'a AmmConfig,
+        tick_arrays: &'a [TickArrayState],
+        amount_out: u64,
+    ) -> SwapComputeInput<'a> {
+        SwapComputeInput {
+            pool_state,
+            amm_config,
+            tickarray_bitmap_extension: None,
+            tick_arrays,
+            zero_for_one: false,
+            is_base_input: false,
+            amount_specified: amount_out,
+            sqrt_price_limit_x64: None,
+            max_steps: None,
+        }
+    }
+
+    #[test]
+    fn amount_in_without_transfer_fees() {
+        let (pool_state, amm_config, tick_array) = pool();
+        let tick_arrays = [tick_array];
+        let input = exact_output(&pool_state, &amm_config, &tick_arrays, 1_000_000);
+        let data = mint_data(0);
+        let mint = StateWithExtensions::<Mint>::unpack(&data).unwrap();
+
+        let amount_in = compute_amount_in_clmm(&input, &mint, &mint, 0).unwrap();
+        // 1_000_002 in for the price move, rounded up, plus a 2_507 trade
+        // fee, rounded up as well.
+        assert_eq!(amount_in.amount_in, 1_002_509);
+        assert_eq!(
+            amount_in.pool_amount_in,
+            compute_swap(&input).unwrap().other_amount
+        );
+        assert_eq!(amount_in.pool_amount_out, 1_000_000);
+        assert_eq!(
+            (amount_in.input_transfer_fee, amount_in.output_transfer_fee),
+            (0, 0)
+        );
+        assert!(amount_in.swap.fully_filled);
+    }
+
+    #[test]
+    fn amount_in_with_transfer_fees() {
+        let (pool_state, amm_config, tick_array) = pool();
+        let tick_arrays = [tick_array];
+        let input = exact_output(&pool_state, &amm_config, &tick_arrays, 1_000_000);
+        let (input_data, output_data) = (mint_data(150), mint_data(100));
+        let input_mint = StateWithExtensions::<Mint>::unpack(&input_data).unwrap();
+        let output_mint = StateWithExtensions::<Mint>::unpack(&output_data).unwrap();
+
+        let amount_in = compute_amount_in_clmm(&input, &input_mint, &output_mint, 0).unwrap();
+        assert_eq!(amount_in.pool_amount_out, 1_010_102);
+        assert_eq!(amount_in.output_transfer_fee, 10_102);
+        assert_eq!(amount_in.pool_amount_in, 1_012_636);
+        assert_eq!(amount_in.input_transfer_fee, 15_421);
+        assert_eq!(amount_in.amount_in, 1_028_057);
+        // What the token program delivers: the requested output to the user
+        // and exactly the pool input to the input vault.
+        let output_fee = get_transfer_fee(&output_mint, 0, amount_in.pool_amount_out).unwrap();
+        assert_eq!(amount_in.pool_amount_out - output_fee, 1_000_000);
+        let input_fee = get_transfer_fee(&input_mint, 0, amount_in.amount_in).unwrap();
+        assert_eq!(amount_in.amount_in - input_fee, amount_in.pool_amount_in);
+    }
+
+    #[test]
+    fn amount_in_rejects_exact_input() {
+        let (pool_state, amm_config, tick_array) = pool();
+        let tick_arrays = [tick_array];
+        let input = SwapComputeInput {
+            is_base_input: true,
+            ..exact_output(&pool_state, &amm_config, &tick_arrays, 1_000_000)
+        };
+        let data = mint_data(0);
+        let mint = StateWithExtensions::<Mint>::unpack(&data).unwrap();
+        assert!(compute_amount_in_clmm(&input, &mint, &mint, 0).is_err());
+    }
+}
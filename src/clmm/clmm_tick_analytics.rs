@@ -0,0 +1,366 @@
+//! Tick-crossing analytics for confirmed CLMM swaps.
+//!
+//! The swap event logged by the CLMM program only carries the pool state after
+//! the swap. The path taken by the swap is rebuilt by walking back from that
+//! state with the swapped input amount, using the current tick arrays of the
+//! pool. Liquidity added or removed since the swap makes the result an
+//! approximation.
+
+use crate::clmm::derive_tick_array_key;
+use crate::common::rpc;
+use crate::libraries::get_sqrt_price_at_tick;
+use crate::states::{AmmConfig, FEE_RATE_DENOMINATOR_VALUE, PoolState, SwapEvent, TickArrayState};
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status_client_types::UiTransactionEncoding;
+use std::collections::HashMap;
+
+/// Upper bound on the tick arrays visited while rebuilding a swap path.
+const MAX_TICK_ARRAYS_SCANNED: usize = 10;
+const Q64: f64 = 18_446_744_073_709_551_616.0;
+
+/// An initialized tick crossed by the swap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrossedTick {
+    pub tick: i32,
+    pub tick_array_start_index: i32,
+    pub liquidity_net: i128,
+}
+
+/// A price range traversed by the swap at constant liquidity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiquiditySegment {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    /// Active liquidity in the range.
+    pub liquidity: u128,
+    /// Input consumed in the range, after the trade fee.
+    pub amount_in: f64,
+    /// Output paid from the range.
+    pub amount_out: f64,
+}
+
+/// Path of a single CLMM swap through the pool's ticks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TickCrossingReport {
+    pub pool_id: Pubkey,
+    pub zero_for_one: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Estimated tick of the pool before the swap.
+    pub tick_before: i32,
+    pub tick_after: i32,
+    /// Tick arrays the swap moved through, ordered in swap direction.
+    pub tick_arrays: Vec<Pubkey>,
+    /// Crossed ticks, ordered in swap direction.
+    pub crossed_ticks: Vec<CrossedTick>,
+    /// Ranges traversed, ordered in swap direction.
+    pub segments: Vec<LiquiditySegment>,
+}
+
+/// Decode every CLMM `SwapEvent` from the log messages of a transaction.
+pub fn parse_swap_events(log_messages: &[String]) -> Vec<SwapEvent> {
+    log_messages
+        .iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .filter_map(|data| BASE64_STANDARD.decode(data).ok())
+        .filter_map(|data| {
+            let payload = data.strip_prefix(SwapEvent::DISCRIMINATOR)?;
+            SwapEvent::deserialize(&mut &payload[..]).ok()
+        })
+        .collect()
+}
+
+/// Report the ticks crossed by every swap of the CLMM program `program_id`
+/// in a confirmed transaction.
+pub async fn analyze_swap_tick_crossings(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    program_id: &Pubkey,
+) -> Result<Vec<TickCrossingReport>> {
+    let transaction = rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Json),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+    let meta = transaction
+        .transaction
+        .meta
+        .ok_or(anyhow!("Transaction {} has no status meta", signature))?;
+    let log_messages: Option<Vec<String>> = meta.log_messages.into();
+    let events = parse_swap_events(&log_messages.unwrap_or_default());
+    if events.is_empty() {
+        return Err(anyhow!("Transaction {} has no CLMM swap event", signature));
+    }
+
+    let mut reports = Vec::with_capacity(events.len());
+    for event in events {
+        let pool_id = Pubkey::from(event.pool_state.to_bytes());
+        let pool_state = rpc::get_anchor_account::<PoolState>(rpc_client, &pool_id)
+            .await?
+            .ok_or(anyhow!("Pool state was not found by rpc"))?;
+        let amm_config_key = Pubkey::from(pool_state.amm_config.to_bytes());
+        let amm_config = rpc::get_anchor_account::<AmmConfig>(rpc_client, &amm_config_key)
+            .await?
+            .ok_or(anyhow!("Amm config was not found by rpc"))?;
+        let mut tick_arrays =
+            TickArrayCache::new(rpc_client, program_id, pool_id, pool_state.tick_spacing);
+        let report =
+            rebuild_swap_path(&event, amm_config.trade_fee_rate, &mut tick_arrays).await?;
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Tick arrays of a pool, fetched on first use.
+struct TickArrayCache<'a> {
+    rpc_client: &'a RpcClient,
+    program_id: solana_pubkey::Pubkey,
+    pool_id: Pubkey,
+    tick_spacing: u16,
+    arrays: HashMap<i32, Option<TickArrayState>>,
+}
+
+impl<'a> TickArrayCache<'a> {
+    fn new(
+        rpc_client: &'a RpcClient,
+        program_id: &Pubkey,
+        pool_id: Pubkey,
+        tick_spacing: u16,
+    ) -> Self {
+        Self {
+            rpc_client,
+            program_id: solana_pubkey::Pubkey::from(program_id.to_bytes()),
+            pool_id,
+            tick_spacing,
+            arrays: HashMap::new(),
+        }
+    }
+
+    fn address(&self, start_index: i32) -> Pubkey {
+        let pool_id = solana_pubkey::Pubkey::from(self.pool_id.to_bytes());
+        Pubkey::from(derive_tick_array_key(&pool_id, start_index, &self.program_id).to_bytes())
+    }
+
+    async fn get(&mut self, start_index: i32) -> Result<Option<TickArrayState>> {
+        if let Some(array) = self.arrays.get(&start_index) {
+            return Ok(*array);
+        }
+        let address = self.address(start_index);
+        let array = rpc::get_anchor_account::<TickArrayState>(self.rpc_client, &address).await?;
+        self.arrays.insert(start_index, array);
+        Ok(array)
+    }
+
+    /// Next initialized tick strictly above `tick` when `upward`, otherwise
+    /// the closest initialized tick at or below `tick`.
+    async fn next_initialized_tick(
+        &mut self,
+        tick: i32,
+        upward: bool,
+    ) -> Result<Option<CrossedTick>> {
+        let ticks_in_array = TickArrayState::tick_count(self.tick_spacing);
+        let mut start_index = TickArrayState::get_array_start_index(tick, self.tick_spacing);
+        for _ in 0..MAX_TICK_ARRAYS_SCANNED {
+            if let Some(array) = self.get(start_index).await? {
+                let ticks = array.ticks;
+                let candidates = ticks.iter().filter(|state| state.is_initialized());
+                let found = if upward {
+                    candidates.filter(|state| state.tick > tick).min_by_key(|state| state.tick)
+                } else {
+                    candidates.filter(|state| state.tick <= tick).max_by_key(|state| state.tick)
+                };
+                if let Some(state) = found {
+                    return Ok(Some(CrossedTick {
+                        tick: state.tick,
+                        tick_array_start_index: start_index,
+                        liquidity_net: state.liquidity_net,
+                    }));
+                }
+            }
+            start_index += if upward { ticks_in_array } else { -ticks_in_array };
+        }
+        Ok(None)
+    }
+}
+
+fn sqrt_price_at_tick(tick: i32) -> Result<f64> {
+    let sqrt_price_x64 = get_sqrt_price_at_tick(tick).map_err(|e| anyhow!("{e:?}"))?;
+    Ok(sqrt_price_x64 as f64 / Q64)
+}
+
+/// Walk back from the post-swap state until the swapped input is exhausted.
+async fn rebuild_swap_path(
+    event: &SwapEvent,
+    trade_fee_rate: u32,
+    tick_arrays: &mut TickArrayCache<'_>,
+) -> Result<TickCrossingReport> {
+    let zero_for_one = event.zero_for_one;
+    let (amount_in, amount_out) = if zero_for_one {
+        (event.amount_0, event.amount_1)
+    } else {
+        (event.amount_1, event.amount_0)
+    };
+    let fee_rate = trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64;
+    let mut remaining = amount_in as f64 * (1.0 - fee_rate);
+
+    let mut sqrt_price = event.sqrt_price_x64 as f64 / Q64;
+    let mut liquidity = event.liquidity;
+    let mut tick = event.tick;
+    let mut crossed_ticks = Vec::new();
+    let mut segments = Vec::new();
+
+    // A zero for one swap moved the price down, so the walk back goes up.
+    let upward = zero_for_one;
+    while remaining > 0.0 {
+        let crossed = tick_arrays
+            .next_initialized_tick(tick, upward)
+            .await?
+            .ok_or(anyhow!("ran out of initialized ticks while rebuilding the swap path"))?;
+        let boundary_sqrt_price = sqrt_price_at_tick(crossed.tick)?;
+        let l = liquidity as f64;
+        // Input needed to move the price from the boundary to the current price.
+        let needed = if upward {
+            l * (1.0 / sqrt_price - 1.0 / boundary_sqrt_price)
+        } else {
+            l * (sqrt_price - boundary_sqrt_price)
+        };
+        let reached_start = liquidity > 0 && needed >= remaining;
+        let start_sqrt_price = if reached_start {
+            if upward {
+                1.0 / (1.0 / sqrt_price - remaining / l)
+            } else {
+                sqrt_price - remaining / l
+            }
+        } else {
+            boundary_sqrt_price
+        };
+        let consumed = if reached_start { remaining } else { needed.max(0.0) };
+        let paid = if upward {
+            l * (start_sqrt_price - sqrt_price)
+        } else {
+            l * (1.0 / sqrt_price - 1.0 / start_sqrt_price)
+        };
+        let start_tick = if reached_start {
+            (start_sqrt_price.powi(2).ln() / 1.0001f64.ln()).floor() as i32
+        } else {
+            crossed.tick
+        };
+        segments.push(LiquiditySegment {
+            tick_lower: tick.min(start_tick),
+            tick_upper: tick.max(start_tick),
+            liquidity,
+            amount_in: consumed,
+            amount_out: paid,
+        });
+        remaining -= consumed;
+        sqrt_price = start_sqrt_price;
+        if reached_start {
+            tick = start_tick;
+            break;
+        }
+
+        // Undo the liquidity change applied when the swap crossed this tick.
+        liquidity = if upward {
+            liquidity.saturating_add_signed(crossed.liquidity_net)
+        } else {
+            liquidity.saturating_add_signed(-crossed.liquidity_net)
+        };
+        tick = if upward { crossed.tick } else { crossed.tick - 1 };
+        crossed_ticks.push(crossed);
+    }
+
+    // Walked backwards; report in swap direction.
+    crossed_ticks.reverse();
+    segments.reverse();
+    let mut tick_arrays_visited: Vec<Pubkey> = Vec::new();
+    let start_indexes = std::iter::once(TickArrayState::get_array_start_index(
+        tick,
+        tick_arrays.tick_spacing,
+    ))
+    .chain(crossed_ticks.iter().map(|crossed| crossed.tick_array_start_index))
+    .chain(std::iter::once(TickArrayState::get_array_start_index(
+        event.tick,
+        tick_arrays.tick_spacing,
+    )));
+    for start_index in start_indexes {
+        let address = tick_arrays.address(start_index);
+        if !tick_arrays_visited.contains(&address) {
+            tick_arrays_visited.push(address);
+        }
+    }
+
+    Ok(TickCrossingReport {
+        pool_id: tick_arrays.pool_id,
+        zero_for_one,
+        amount_in,
+        amount_out,
+        tick_before: tick,
+        tick_after: event.tick,
+        tick_arrays: tick_arrays_visited,
+        crossed_ticks,
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+
+    fn swap_event(amount_0: u64, amount_1: u64) -> SwapEvent {
+        SwapEvent {
+            pool_state: anchor_lang::prelude::Pubkey::new_from_array([1; 32]),
+            sender: anchor_lang::prelude::Pubkey::new_from_array([2; 32]),
+            token_account_0: anchor_lang::prelude::Pubkey::new_from_array([3; 32]),
+            token_account_1: anchor_lang::prelude::Pubkey::new_from_array([4; 32]),
+            amount_0,
+            transfer_fee_0: 0,
+            amount_1,
+            transfer_fee_1: 0,
+            zero_for_one: true,
+            sqrt_price_x64: 1 << 64,
+            liquidity: 1_000_000,
+            tick: 0,
+        }
+    }
+
+    #[test]
+    fn swap_events_are_decoded_from_program_data_logs() {
+        let logs = [
+            "Program CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK invoke [1]".to_string(),
+            format!(
+                "Program data: {}",
+                BASE64_STANDARD.encode(swap_event(1_000, 990).data())
+            ),
+            // Program data that is not a swap event, and data that is not base64.
+            format!("Program data: {}", BASE64_STANDARD.encode([0u8; 16])),
+            "Program data: not base64".to_string(),
+            format!(
+                "Program data: {}",
+                BASE64_STANDARD.encode(swap_event(2_000, 1_980).data())
+            ),
+        ];
+
+        let events = parse_swap_events(&logs);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].amount_0, events[0].amount_1), (1_000, 990));
+        assert_eq!((events[1].amount_0, events[1].amount_1), (2_000, 1_980));
+        assert_eq!(events[0].pool_state.to_bytes(), [1; 32]);
+        assert_eq!(events[0].sqrt_price_x64, 1 << 64);
+        assert!(events[0].zero_for_one);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::common::FeeTier;
 use solana_address::Address;
 use solana_pubkey::Pubkey;
 use std::collections::VecDeque;
@@ -55,6 +56,60 @@ pub struct ClmmSwapChangeResult {
     pub other_amount_threshold: u64,
     pub sqrt_price_limit_x64: Option<u128>,
     pub is_base_input: bool,
+    /// Fee rates of the pool's `AmmConfig`.
+    pub fee_tier: FeeTier,
+    /// Specified amount entering the swap computation: less the input
+    /// transfer fee for exact input swaps, plus the output transfer fee for
+    /// exact output swaps.
+    pub amount_requested: u64,
+    /// Part of `amount_requested` the swap consumes before reaching
+    /// `sqrt_price_limit_x64`.
+    pub amount_consumed: u64,
+    /// `false` when the price limit stops the swap before `amount_requested`
+    /// is consumed.
+    pub fully_filled: bool,
+    /// `false` when the pool has no tick array bitmap extension account; it
+    /// is then left out of the swap instruction.
+    pub has_tick_array_bitmap_extension: bool,
+    /// Epoch the transfer fees were computed for.
+    pub epoch: u64,
+    /// Slot of the accounts the quote was computed from, `None` when they
+    /// were passed in rather than loaded by the computation, as in
+    /// `clmm_utils_sync::calculate_swap_change`.
+    pub context_slot: Option<u64>,
+    /// Pool price at quote time.
+    pub sqrt_price_x64: u128,
+    /// Pool tick at quote time.
+    pub tick_current: i32,
+    /// Expected amounts, fees and price impact of the swap, before slippage.
+    pub quote: ClmmQuote,
+}
+
+/// Expected outcome of a CLMM swap before slippage, the counterpart of
+/// `ComputeAmountOutResult` for AMM pools.
+///
+/// Amounts are those entering and leaving the pool vaults, without the
+/// Token-2022 transfer fees of the mints. Prices are of token 0 in token 1,
+/// adjusted for the mint decimals.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClmmQuote {
+    /// Input taken by the pool, trade fee included.
+    pub amount_in: u64,
+    /// Output paid by the pool.
+    pub amount_out: u64,
+    /// Trade fee charged on the input, in input units.
+    pub fee_amount: u64,
+    /// Parts of `fee_amount` going to the protocol and to the fund, the rest
+    /// going to liquidity providers. Computed on the total fee, so they can
+    /// differ from the program by rounding of the swap steps.
+    pub protocol_fee_amount: u64,
+    pub fund_fee_amount: u64,
+    pub price_before: f64,
+    pub price_after: f64,
+    /// Output per input of the trade after the fee, adjusted for decimals.
+    pub execution_price: f64,
+    /// Percent shortfall of `execution_price` from the price before the swap.
+    pub price_impact: f64,
 }
 
 // the top level state of the swap, the results of which are recorded in storage at the end
@@ -1,15 +1,20 @@
-use crate::clmm::{ClmmSwapChangeResult, StepComputations, SwapState, price_to_sqrt_price_x64};
+use crate::clmm::{
+    ClmmQuote, ClmmSwapChangeResult, StepComputations, SwapState, derive_tick_array_key,
+    derive_tick_array_keys, price_to_sqrt_price_x64, sqrt_price_x64_to_price,
+};
 use crate::common::{
-    TokenAccountState, amount_with_slippage, common_utils, deserialize_anchor_account,
+    FeeTier, TokenAccountState, amount_with_slippage, common_utils, deserialize_anchor_account,
     get_transfer_fee, rpc, unpack_mint, unpack_token,
 };
+use crate::error::SwapError;
 use crate::interface::{CalculateSwapChangeParams, Rsps, TickArrays};
 use crate::libraries::{
     MAX_SQRT_PRICE_X64, MAX_TICK, MIN_SQRT_PRICE_X64, MIN_TICK, add_delta, compute_swap_step,
     get_sqrt_price_at_tick, get_tick_at_sqrt_price,
 };
 use crate::states::{
-    AmmConfig, PoolState, TICK_ARRAY_SEED, TickArrayBitmapExtension, TickArrayState, TickState,
+    AmmConfig, FEE_RATE_DENOMINATOR_VALUE, PoolState, PoolStatusBitIndex, TICK_ARRAY_SIZE_USIZE,
+    TickArrayBitmapExtension, TickArrayState, TickState,
 };
 use anchor_lang::solana_program::program_option::COption as AnchorCOption;
 use anyhow::{Result, anyhow};
@@ -94,11 +99,13 @@ pub fn calculate_swap_change_accounts(
             .ok_or(anyhow!("Mint token program is None"))?
             .data,
     )?;
-    let tickarray_bitmap_extension_state = deserialize_anchor_account::<TickArrayBitmapExtension>(
-        tickarray_bitmap_extension_account
-            .as_ref()
-            .ok_or(anyhow!("Mint token program is None"))?,
-    )?;
+    // Pools that never left the range of the in-pool bitmap may have no
+    // extension account; the in-pool bitmap alone is used then.
+    let tickarray_bitmap_extension_state = tickarray_bitmap_extension_account
+        .as_ref()
+        .filter(|account| !account.data.is_empty())
+        .map(deserialize_anchor_account::<TickArrayBitmapExtension>)
+        .transpose()?;
     let amm_config_state = deserialize_anchor_account::<AmmConfig>(
         amm_config_account
             .as_ref()
@@ -136,18 +143,25 @@ pub fn calculate_swap_change_accounts(
     } else {
         return Err(anyhow!("input tokens not match pool vaults"));
     };
-    let transfer_fee = if base_in {
-        if zero_for_one {
+    let amount_specified = if base_in {
+        let transfer_fee = if zero_for_one {
             get_transfer_fee(&mint0_state, epoch, amount)?
         } else {
             get_transfer_fee(&mint1_state, epoch, amount)?
-        }
+        };
+        amount
+            .checked_sub(transfer_fee)
+            .ok_or(anyhow!("Amount is less than transfer fee"))?
     } else {
-        0
+        // the program takes the output transfer fee out of the pool as well,
+        // so that `amount` reaches the user
+        let output_mint_state = if zero_for_one {
+            &mint1_state
+        } else {
+            &mint0_state
+        };
+        exact_output_pool_amount(output_mint_state, epoch, amount)?
     };
-    let amount_specified = amount
-        .checked_sub(transfer_fee)
-        .ok_or(anyhow!("Amount is less than transfer fee"))?;
 
     Ok(CalculateSwapChangeParams {
         tickarray_bitmap_extension_account: tickarray_bitmap_extension_account.clone(),
@@ -170,12 +184,87 @@ pub fn calculate_swap_change_accounts(
     })
 }
 
+/// Amount leaving the pool in an exact output swap of `amount_out`: the
+/// program adds the inverse transfer fee of the output mint, so the user
+/// receives `amount_out` after the fee.
+pub fn exact_output_pool_amount<S: BaseState + SolanaProgramPack>(
+    output_mint_state: &StateWithExtensions<S>,
+    epoch: u64,
+    amount_out: u64,
+) -> Result<u64> {
+    let transfer_fee =
+        common_utils::get_transfer_inverse_fee(output_mint_state, epoch, amount_out)?;
+    amount_out
+        .checked_add(transfer_fee)
+        .ok_or(anyhow!("amount_out overflows with the transfer fee"))
+}
+
+/// Check that `limit_price` (token 1 per token 0, decimals applied) can be
+/// reached by a swap in the given direction and convert it to a sqrt price.
+///
+/// A zero for one swap lowers the price, so its limit must be below the
+/// current price; the other direction needs a limit above it. Violations
+/// return [`SwapError::InvalidLimitPrice`].
+pub fn validate_limit_price(
+    pool_state: &PoolState,
+    limit_price: f64,
+    zero_for_one: bool,
+) -> Result<u128> {
+    let current_sqrt_price_x64 = pool_state.sqrt_price_x64;
+    let (decimals_0, decimals_1) = (pool_state.mint_decimals_0, pool_state.mint_decimals_1);
+    let invalid = || -> Result<u128> {
+        Err(SwapError::InvalidLimitPrice {
+            limit_price,
+            current_price: sqrt_price_x64_to_price(current_sqrt_price_x64, decimals_0, decimals_1)?,
+            zero_for_one,
+        }
+        .into())
+    };
+    if !limit_price.is_finite() || limit_price <= 0.0 {
+        return invalid();
+    }
+    let sqrt_price_limit_x64 = price_to_sqrt_price_x64(limit_price, decimals_0, decimals_1)?;
+    let on_right_side = if zero_for_one {
+        sqrt_price_limit_x64 < current_sqrt_price_x64
+    } else {
+        sqrt_price_limit_x64 > current_sqrt_price_x64
+    };
+    if !on_right_side {
+        return invalid();
+    }
+    Ok(sqrt_price_limit_x64)
+}
+
+/// Check that the CLMM pool `pool_id` accepts swaps at unix timestamp `now`:
+/// swaps are not paused in its status and, like the program requires, `now`
+/// is past its open time. Violations return [`SwapError::SwapDisabled`] or
+/// [`SwapError::PoolNotOpen`].
+pub fn validate_swap_enabled(pool_id: &Pubkey, pool_state: &PoolState, now: u64) -> Result<()> {
+    let pool_id = solana_sdk::pubkey::Pubkey::from(pool_id.to_bytes());
+    if !pool_state.get_status_by_bit(PoolStatusBitIndex::Swap) {
+        return Err(SwapError::SwapDisabled {
+            pool_id,
+            status: pool_state.status as u64,
+        }
+        .into());
+    }
+    if now <= pool_state.open_time {
+        return Err(SwapError::PoolNotOpen {
+            pool_id,
+            open_time: pool_state.open_time,
+            seconds_until_open: pool_state.open_time - now + 1,
+        }
+        .into());
+    }
+    Ok(())
+}
+
 pub(crate) fn calculate_other_amount_threshold<S: BaseState + SolanaProgramPack>(
     pool_id: Address,
     raydium_v3_program: Pubkey,
     slippage_bps: u64,
     pool_state: PoolState,
-    tickarray_bitmap_extension_state: TickArrayBitmapExtension,
+    tickarray_bitmap_extension_state: Option<TickArrayBitmapExtension>,
     zero_for_one: bool,
     amount_specified: u64,
     amm_config_state: AmmConfig,
@@ -185,47 +274,38 @@ pub(crate) fn calculate_other_amount_threshold<S: BaseState + SolanaProgramPack>
     mint0_state: &StateWithExtensions<S>,
     mint1_state: &StateWithExtensions<S>,
     epoch: u64,
-) -> Result<(VecDeque<Pubkey>, u64, Option<u128>)> {
-    let sqrt_price_limit_x64 = if let Some(limit_price) = limit_price {
-        let sqrt_price_x64 = price_to_sqrt_price_x64(
-            limit_price,
-            pool_state.mint_decimals_0,
-            pool_state.mint_decimals_1,
-        )?;
-        Some(sqrt_price_x64)
-    } else {
-        None
-    };
+) -> Result<(VecDeque<Pubkey>, u64, Option<u128>, u64, ClmmQuote)> {
+    let sqrt_price_limit_x64 = limit_price
+        .map(|limit_price| validate_limit_price(&pool_state, limit_price, zero_for_one))
+        .transpose()?;
 
-    let (mut other_amount_threshold, tick_array_indexes) =
-        get_out_put_amount_and_remaining_accounts(
-            amount_specified,
-            sqrt_price_limit_x64,
-            zero_for_one,
-            base_in,
-            amm_config_state.trade_fee_rate,
-            &pool_state,
-            &tickarray_bitmap_extension_state,
-            &mut tick_arrays,
-        )?;
+    let walk = swap_walk(
+        amount_specified,
+        sqrt_price_limit_x64,
+        zero_for_one,
+        base_in,
+        amm_config_state.trade_fee_rate,
+        &pool_state,
+        &tickarray_bitmap_extension_state,
+        &mut tick_arrays,
+    )?;
+    let quote = clmm_quote(&pool_state, &amm_config_state, zero_for_one, base_in, &walk)?;
+    let SwapWalk {
+        amount_calculated: mut other_amount_threshold,
+        amount_consumed,
+        tick_array_start_indexes: tick_array_indexes,
+        ..
+    } = walk;
     // debug!(
     //     "amount:{}, other_amount_threshold:{}",
     //     amount, other_amount_threshold
     // );
-    let remaining_tick_array_keys = tick_array_indexes
-        .into_iter()
-        .map(|index| {
-            Pubkey::find_program_address(
-                &[
-                    TICK_ARRAY_SEED.as_bytes(),
-                    pool_id.to_bytes().as_ref(),
-                    &index.to_be_bytes(),
-                ],
-                &raydium_v3_program,
-            )
-            .0
-        })
-        .collect();
+    let remaining_tick_array_keys = derive_tick_array_keys(
+        &Pubkey::from(pool_id.to_bytes()),
+        tick_array_indexes,
+        &raydium_v3_program,
+    )
+    .into();
     if base_in {
         // calc mint out amount with slippage
         other_amount_threshold = amount_with_slippage(other_amount_threshold, slippage_bps, false)?;
@@ -244,6 +324,8 @@ pub(crate) fn calculate_other_amount_threshold<S: BaseState + SolanaProgramPack>
         remaining_tick_array_keys,
         other_amount_threshold,
         sqrt_price_limit_x64,
+        amount_consumed,
+        quote,
     ))
 }
 
@@ -259,6 +341,37 @@ pub async fn calculate_swap_change(
     slippage_bps: u64,
 ) -> Result<ClmmSwapChangeResult> {
     let epoch = rpc_client.get_epoch_info().await?.epoch;
+    calculate_swap_change_at_epoch(
+        rpc_client,
+        raydium_v3_program,
+        pool_id,
+        tickarray_bitmap_extension,
+        input_token,
+        amount,
+        limit_price,
+        base_in,
+        slippage_bps,
+        epoch,
+    )
+    .await
+}
+
+/// Same as [`calculate_swap_change`], with Token-2022 transfer fees of
+/// `epoch` instead of the current one, e.g. when many swaps are computed
+/// against one fetch of it.
+#[allow(clippy::too_many_arguments)]
+pub async fn calculate_swap_change_at_epoch(
+    rpc_client: &RpcClient,
+    raydium_v3_program: Pubkey,
+    pool_id: Pubkey,
+    tickarray_bitmap_extension: Pubkey,
+    input_token: Pubkey,
+    amount: u64,
+    limit_price: Option<f64>,
+    base_in: bool,
+    slippage_bps: u64,
+    epoch: u64,
+) -> Result<ClmmSwapChangeResult> {
     let pool_id = solana_address::Address::from(pool_id.to_bytes());
     let pool_state = rpc::get_anchor_account::<PoolState>(rpc_client, &pool_id)
         .await?
@@ -275,7 +388,11 @@ pub async fn calculate_swap_change(
     .map(|pubkey| Address::from(pubkey.to_bytes()))
     .collect();
 
-    let rsps = rpc_client.get_multiple_accounts(&load_accounts).await?;
+    let response = rpc_client
+        .get_multiple_accounts_with_commitment(&load_accounts, rpc_client.commitment())
+        .await?;
+    let context_slot = response.context.slot;
+    let rsps = response.value;
     let CalculateSwapChangeParams {
         mint0_account,
         mint1_account,
@@ -304,18 +421,29 @@ pub async fn calculate_swap_change(
             .ok_or(anyhow!("Mint token program is None"))?
             .data,
     )?;
-    // load tick_arrays
-    let tick_arrays = load_cur_and_next_five_tick_array(
-        rpc_client,
-        raydium_v3_program,
-        Pubkey::from(pool_id.to_bytes()),
-        &pool_state,
-        &tickarray_bitmap_extension_state,
-        zero_for_one,
-    )
-    .await?;
-    let (remaining_tick_array_keys, other_amount_threshold, sqrt_price_limit_x64) =
-        calculate_other_amount_threshold(
+    let fee_tier = FeeTier::from_amm_config(&amm_config_state);
+    let has_tick_array_bitmap_extension = tickarray_bitmap_extension_state.is_some();
+    // load tick_arrays, more of them as long as the swap runs past the last one
+    let mut max_tick_arrays = DEFAULT_TICK_ARRAY_COUNT;
+    let (
+        remaining_tick_array_keys,
+        other_amount_threshold,
+        sqrt_price_limit_x64,
+        amount_consumed,
+        quote,
+    ) = loop {
+        let tick_arrays = load_tick_arrays(
+            rpc_client,
+            raydium_v3_program,
+            Pubkey::from(pool_id.to_bytes()),
+            &pool_state,
+            &tickarray_bitmap_extension_state,
+            zero_for_one,
+            max_tick_arrays,
+        )
+        .await?;
+        let tick_arrays_loaded = tick_arrays.len();
+        let err = match calculate_other_amount_threshold(
             pool_id,
             raydium_v3_program,
             slippage_bps,
@@ -323,14 +451,29 @@ pub async fn calculate_swap_change(
             tickarray_bitmap_extension_state,
             zero_for_one,
             amount_specified,
-            amm_config_state,
+            amm_config_state.clone(),
             limit_price,
             base_in,
             tick_arrays,
             &mint0_state,
             &mint1_state,
             epoch,
-        )?;
+        ) {
+            Ok(result) => break result,
+            Err(err) => err,
+        };
+        // fewer arrays than asked for: the pool has no more in swap direction
+        match err.downcast_ref::<SwapError>() {
+            Some(SwapError::InsufficientLiquidityLoaded {
+                additional_tick_arrays,
+                ..
+            }) if tick_arrays_loaded == max_tick_arrays => {
+                debug!("loading {additional_tick_arrays} more tick arrays");
+                max_tick_arrays += additional_tick_arrays;
+            }
+            _ => return Err(err),
+        }
+    };
 
     Ok(ClmmSwapChangeResult {
         pool_amm_config: pool_state.amm_config,
@@ -348,34 +491,64 @@ pub async fn calculate_swap_change(
         other_amount_threshold,
         sqrt_price_limit_x64,
         is_base_input: base_in,
+        fee_tier,
+        amount_requested: amount_specified,
+        amount_consumed,
+        fully_filled: amount_consumed == amount_specified,
+        has_tick_array_bitmap_extension,
+        epoch,
+        context_slot: Some(context_slot),
+        sqrt_price_x64: pool_state.sqrt_price_x64,
+        tick_current: pool_state.tick_current,
+        quote,
     })
 }
 
+/// Tick arrays loaded for a quote unless asked otherwise: the array of the
+/// current tick and the next five in swap direction.
+pub const DEFAULT_TICK_ARRAY_COUNT: usize = 6;
+
+/// Keys of the first [`DEFAULT_TICK_ARRAY_COUNT`] initialized tick arrays in
+/// swap direction, see [`get_tick_array_keys_up_to`].
 pub fn get_tick_array_keys(
     raydium_v3_program: Pubkey,
     pool_id: Pubkey,
     pool_state: &PoolState,
-    tickarray_bitmap_extension: &TickArrayBitmapExtension,
+    tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
     zero_for_one: bool,
+) -> Result<Vec<Address>> {
+    get_tick_array_keys_up_to(
+        raydium_v3_program,
+        pool_id,
+        pool_state,
+        tickarray_bitmap_extension,
+        zero_for_one,
+        DEFAULT_TICK_ARRAY_COUNT,
+    )
+}
+
+/// Keys of the initialized tick arrays in swap direction, starting with the
+/// array of the current tick. Returns at most `max_tick_arrays` keys (and at
+/// least one), fewer when the pool has no more initialized arrays.
+pub fn get_tick_array_keys_up_to(
+    raydium_v3_program: Pubkey,
+    pool_id: Pubkey,
+    pool_state: &PoolState,
+    tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
+    zero_for_one: bool,
+    max_tick_arrays: usize,
 ) -> Result<Vec<Address>> {
     let (_, mut current_valid_tick_array_start_index) = pool_state
-        .get_first_initialized_tick_array(&Some(*tickarray_bitmap_extension), zero_for_one)?;
+        .get_first_initialized_tick_array(tickarray_bitmap_extension, zero_for_one)?;
     let mut tick_array_keys = Vec::new();
-    tick_array_keys.push(
-        Pubkey::find_program_address(
-            &[
-                TICK_ARRAY_SEED.as_bytes(),
-                pool_id.to_bytes().as_ref(),
-                &current_valid_tick_array_start_index.to_be_bytes(),
-            ],
-            &raydium_v3_program,
-        )
-        .0,
-    );
-    let mut max_array_size = 5;
-    while max_array_size != 0 {
+    tick_array_keys.push(derive_tick_array_key(
+        &pool_id,
+        current_valid_tick_array_start_index,
+        &raydium_v3_program,
+    ));
+    while tick_array_keys.len() < max_tick_arrays {
         let next_tick_array_index = pool_state.next_initialized_tick_array_start_index(
-            &Some(*tickarray_bitmap_extension),
+            tickarray_bitmap_extension,
             current_valid_tick_array_start_index,
             zero_for_one,
         )?;
@@ -384,18 +557,11 @@ pub fn get_tick_array_keys(
         }
         current_valid_tick_array_start_index =
             next_tick_array_index.ok_or(anyhow!("next_tick_array_index is None"))?;
-        tick_array_keys.push(
-            Pubkey::find_program_address(
-                &[
-                    TICK_ARRAY_SEED.as_bytes(),
-                    pool_id.to_bytes().as_ref(),
-                    &current_valid_tick_array_start_index.to_be_bytes(),
-                ],
-                &raydium_v3_program,
-            )
-            .0,
-        );
-        max_array_size -= 1;
+        tick_array_keys.push(derive_tick_array_key(
+            &pool_id,
+            current_valid_tick_array_start_index,
+            &raydium_v3_program,
+        ));
     }
     let tick_array_keys: Vec<Address> = tick_array_keys
         .iter()
@@ -416,20 +582,22 @@ pub fn get_tick_arrays(tick_array_rsps: Vec<Option<Account>>) -> Result<VecDeque
     Ok(tick_arrays)
 }
 
-async fn load_cur_and_next_five_tick_array(
+async fn load_tick_arrays(
     rpc_client: &RpcClient,
     raydium_v3_program: Pubkey,
     pool_id: Pubkey,
     pool_state: &PoolState,
-    tickarray_bitmap_extension: &TickArrayBitmapExtension,
+    tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
     zero_for_one: bool,
+    max_tick_arrays: usize,
 ) -> Result<TickArrays> {
-    let tick_array_keys = get_tick_array_keys(
+    let tick_array_keys = get_tick_array_keys_up_to(
         raydium_v3_program,
         pool_id,
         pool_state,
         tickarray_bitmap_extension,
         zero_for_one,
+        max_tick_arrays,
     )?;
     let tick_array_rsps = get_tick_array_rsps(rpc_client, &tick_array_keys).await?;
     get_tick_arrays(tick_array_rsps)
@@ -453,13 +621,84 @@ pub fn get_out_put_amount_and_remaining_accounts(
     is_base_input: bool,
     trade_fee_rate: u32,
     pool_state: &PoolState,
-    tickarray_bitmap_extension: &TickArrayBitmapExtension,
+    tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
+    tick_arrays: &mut VecDeque<TickArrayState>,
+) -> Result<(u64, u64, VecDeque<i32>)> {
+    let walk = swap_walk(
+        input_amount,
+        sqrt_price_limit_x64,
+        zero_for_one,
+        is_base_input,
+        trade_fee_rate,
+        pool_state,
+        tickarray_bitmap_extension,
+        tick_arrays,
+    )?;
+    Ok((
+        walk.amount_calculated,
+        walk.amount_consumed,
+        walk.tick_array_start_indexes,
+    ))
+}
+
+/// Default step cap of [`swap_compute`] per loaded tick array: one step per
+/// initialized tick plus the step to the array boundary.
+pub const SWAP_STEPS_PER_TICK_ARRAY: usize = TICK_ARRAY_SIZE_USIZE + 1;
+
+/// [`SwapError::InsufficientLiquidityLoaded`] for a walk over
+/// `tick_arrays_loaded` arrays that left `amount_remaining` of
+/// `amount_specified` unfilled. The additional arrays are extrapolated from
+/// the amount filled per loaded array.
+fn insufficient_liquidity_loaded(
+    tick_arrays_loaded: usize,
+    amount_specified: u64,
+    amount_remaining: u64,
+) -> SwapError {
+    let amount_consumed = amount_specified - amount_remaining;
+    let additional_tick_arrays = if amount_consumed == 0 {
+        1
+    } else {
+        (tick_arrays_loaded as u128 * amount_remaining as u128)
+            .div_ceil(amount_consumed as u128)
+            .clamp(1, usize::MAX as u128) as usize
+    };
+    SwapError::InsufficientLiquidityLoaded {
+        tick_arrays_loaded,
+        additional_tick_arrays,
+        amount_remaining,
+    }
+}
+
+/// Outcome of [`swap_compute`].
+pub(crate) struct SwapWalk {
+    pub amount_calculated: u64,
+    /// Part of `amount_specified` consumed, less than it when the price
+    /// limit stopped the swap.
+    pub amount_consumed: u64,
+    /// Start indexes of the tick arrays crossed.
+    pub tick_array_start_indexes: VecDeque<i32>,
+    /// Trade fee charged over all steps, in input units.
+    pub fee_amount: u64,
+    /// Pool price when the swap stops.
+    pub sqrt_price_x64: u128,
+}
+
+/// Same as [`get_out_put_amount_and_remaining_accounts`], with the fee and
+/// final price of the walk.
+fn swap_walk(
+    input_amount: u64,
+    sqrt_price_limit_x64: Option<u128>,
+    zero_for_one: bool,
+    is_base_input: bool,
+    trade_fee_rate: u32,
+    pool_state: &PoolState,
+    tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
     tick_arrays: &mut VecDeque<TickArrayState>,
-) -> Result<(u64, VecDeque<i32>)> {
-    let (is_pool_current_tick_array, current_valid_tick_array_start_index) = pool_state
-        .get_first_initialized_tick_array(&Some(*tickarray_bitmap_extension), zero_for_one)?;
+) -> Result<SwapWalk> {
+    let (is_pool_current_tick_array, current_valid_tick_array_start_index) =
+        pool_state.get_first_initialized_tick_array(tickarray_bitmap_extension, zero_for_one)?;
 
-    let (amount_calculated, tick_array_start_index_vec) = swap_compute(
+    let walk = swap_compute(
         zero_for_one,
         is_base_input,
         is_pool_current_tick_array,
@@ -469,25 +708,84 @@ pub fn get_out_put_amount_and_remaining_accounts(
         sqrt_price_limit_x64.unwrap_or(0),
         pool_state,
         tickarray_bitmap_extension,
-        tick_arrays,
+        std::iter::from_fn(|| tick_arrays.pop_front()),
+        None,
     )?;
-    debug!("tick_array_start_index:{:?}", tick_array_start_index_vec);
+    debug!("tick_array_start_index:{:?}", walk.tick_array_start_indexes);
 
-    Ok((amount_calculated, tick_array_start_index_vec))
+    Ok(walk)
 }
 
-fn swap_compute(
+/// [`ClmmQuote`] of a swap walk over `pool_state`.
+fn clmm_quote(
+    pool_state: &PoolState,
+    amm_config: &AmmConfig,
+    zero_for_one: bool,
+    is_base_input: bool,
+    walk: &SwapWalk,
+) -> Result<ClmmQuote> {
+    let (amount_in, amount_out) = if is_base_input {
+        (walk.amount_consumed, walk.amount_calculated)
+    } else {
+        (walk.amount_calculated, walk.amount_consumed)
+    };
+    let fee_share = |rate: u32| {
+        (walk.fee_amount as u128 * rate as u128 / FEE_RATE_DENOMINATOR_VALUE as u128) as u64
+    };
+    let (decimals_0, decimals_1) = (pool_state.mint_decimals_0, pool_state.mint_decimals_1);
+    let price_before = sqrt_price_x64_to_price(pool_state.sqrt_price_x64, decimals_0, decimals_1)?;
+    let price_after = sqrt_price_x64_to_price(walk.sqrt_price_x64, decimals_0, decimals_1)?;
+    let (spot_price, decimals_in, decimals_out) = if zero_for_one {
+        (price_before, decimals_0, decimals_1)
+    } else {
+        (1.0 / price_before, decimals_1, decimals_0)
+    };
+    let amount_in_after_fee = amount_in.saturating_sub(walk.fee_amount) as f64;
+    let execution_price = if amount_in_after_fee > 0.0 {
+        (amount_out as f64 / 10f64.powi(decimals_out as i32))
+            / (amount_in_after_fee / 10f64.powi(decimals_in as i32))
+    } else {
+        spot_price
+    };
+    Ok(ClmmQuote {
+        amount_in,
+        amount_out,
+        fee_amount: walk.fee_amount,
+        protocol_fee_amount: fee_share(amm_config.protocol_fee_rate),
+        fund_fee_amount: fee_share(amm_config.fund_fee_rate),
+        price_before,
+        price_after,
+        execution_price,
+        price_impact: (spot_price - execution_price) / spot_price * 100.0,
+    })
+}
+
+/// Walk the ticks of `pool_state` until `amount_specified` is consumed or the
+/// price limit is reached. `tick_arrays` must yield the initialized tick
+/// arrays in swap direction, starting at `current_valid_tick_array_start_index`.
+///
+/// Returns the calculated amount, the part of `amount_specified` actually
+/// consumed (less than `amount_specified` when the limit stopped the swap),
+/// the start indexes of the tick arrays crossed, the fee and the final price.
+///
+/// The walk takes at most `max_steps` steps, by default
+/// [`SWAP_STEPS_PER_TICK_ARRAY`] per tick array loaded so far. Running out of
+/// steps or of `tick_arrays` fails with
+/// [`SwapError::InsufficientLiquidityLoaded`].
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn swap_compute(
     zero_for_one: bool,
     is_base_input: bool,
     is_pool_current_tick_array: bool,
     trade_fee_rate: u32,
     amount_specified: u64,
-    current_valid_tick_array_start_index: i32,
+    mut current_valid_tick_array_start_index: i32,
     sqrt_price_limit_x64: u128,
     pool_state: &PoolState,
-    tickarray_bitmap_extension: &TickArrayBitmapExtension,
-    tick_arrays: &mut VecDeque<TickArrayState>,
-) -> Result<(u64, VecDeque<i32>)> {
+    tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
+    mut tick_arrays: impl Iterator<Item = TickArrayState>,
+    max_steps: Option<usize>,
+) -> Result<SwapWalk> {
     if amount_specified == 0 {
         return Err(anyhow!("amountSpecified must not be 0"));
     }
@@ -530,22 +828,30 @@ fn swap_compute(
     };
 
     let mut tick_array_current = tick_arrays
-        .pop_front()
-        .ok_or(anyhow!("could not pop front from tick_arrays"))?;
+        .next()
+        .ok_or_else(|| insufficient_liquidity_loaded(0, amount_specified, amount_specified))?;
     if tick_array_current.start_tick_index != current_valid_tick_array_start_index {
         return Err(anyhow!("tick array start tick index does not match"));
     }
     let mut tick_array_start_index_vec = VecDeque::new();
     tick_array_start_index_vec.push_back(tick_array_current.start_tick_index);
-    let mut loop_count = 0;
+    let mut fee_amount = 0u64;
+    let mut steps = 0;
     // loop across ticks until input liquidity is consumed, or the limit price is reached
     while state.amount_specified_remaining != 0
         && state.sqrt_price_x64 != sqrt_price_limit_x64
         && state.tick < MAX_TICK
         && state.tick > MIN_TICK
     {
-        if loop_count > 10 {
-            return Err(anyhow!("loop_count limit"));
+        let step_cap =
+            max_steps.unwrap_or(tick_array_start_index_vec.len() * SWAP_STEPS_PER_TICK_ARRAY);
+        if steps >= step_cap {
+            return Err(insufficient_liquidity_loaded(
+                tick_array_start_index_vec.len(),
+                amount_specified,
+                state.amount_specified_remaining,
+            )
+            .into());
         }
         let mut step = StepComputations::default();
         step.sqrt_price_start_x64 = state.sqrt_price_x64;
@@ -561,22 +867,21 @@ fn swap_compute(
             Box::new(TickState::default())
         };
         if !next_initialized_tick.is_initialized() {
-            let current_vaild_tick_array_start_index = pool_state
+            current_valid_tick_array_start_index = pool_state
                 .next_initialized_tick_array_start_index(
-                    &Some(*tickarray_bitmap_extension),
+                    tickarray_bitmap_extension,
                     current_valid_tick_array_start_index,
                     zero_for_one,
-                )?;
-            tick_array_current = tick_arrays
-                .pop_front()
-                .ok_or(anyhow!("Could not pop_front from tick_arrays"))?;
-            if current_vaild_tick_array_start_index.is_none() {
-                return Err(anyhow!("tick array start tick index out of range limit"));
-            }
-            if tick_array_current.start_tick_index
-                != current_vaild_tick_array_start_index
-                    .ok_or(anyhow!("current_vaild_tick_array_start_index is None"))?
-            {
+                )?
+                .ok_or(anyhow!("tick array start tick index out of range limit"))?;
+            tick_array_current = tick_arrays.next().ok_or_else(|| {
+                insufficient_liquidity_loaded(
+                    tick_array_start_index_vec.len(),
+                    amount_specified,
+                    state.amount_specified_remaining,
+                )
+            })?;
+            if tick_array_current.start_tick_index != current_valid_tick_array_start_index {
                 return Err(anyhow!("tick array start tick index does not match"));
             }
             tick_array_start_index_vec.push_back(tick_array_current.start_tick_index);
@@ -616,6 +921,7 @@ fn swap_compute(
         step.amount_in = swap_step.amount_in;
         step.amount_out = swap_step.amount_out;
         step.fee_amount = swap_step.fee_amount;
+        fee_amount = fee_amount.saturating_add(step.fee_amount);
 
         if is_base_input {
             state.amount_specified_remaining = state
@@ -662,8 +968,123 @@ fn swap_compute(
             // recompute unless we're on a lower tick boundary (i.e. already transitioned ticks), and haven't moved
             state.tick = get_tick_at_sqrt_price(state.sqrt_price_x64)?;
         }
-        loop_count += 1;
+        steps += 1;
     }
 
-    Ok((state.amount_calculated, tick_array_start_index_vec))
+    Ok(SwapWalk {
+        amount_calculated: state.amount_calculated,
+        amount_consumed: amount_specified - state.amount_specified_remaining,
+        tick_array_start_indexes: tick_array_start_index_vec,
+        fee_amount,
+        sqrt_price_x64: state.sqrt_price_x64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::Q64;
+    use crate::states::PoolStatusBitFlag;
+
+    #[test]
+    fn clmm_quote_breaks_down_fee_and_impact() {
+        let pool_state = PoolState {
+            sqrt_price_x64: Q64,
+            mint_decimals_0: 6,
+            mint_decimals_1: 6,
+            ..Default::default()
+        };
+        let amm_config = AmmConfig {
+            protocol_fee_rate: 120_000,
+            fund_fee_rate: 40_000,
+            ..Default::default()
+        };
+        let walk = SwapWalk {
+            amount_calculated: 891,
+            amount_consumed: 1_000,
+            tick_array_start_indexes: VecDeque::new(),
+            fee_amount: 100,
+            sqrt_price_x64: Q64 - Q64 / 100,
+        };
+        let quote = clmm_quote(&pool_state, &amm_config, true, true, &walk).unwrap();
+        assert_eq!((quote.amount_in, quote.amount_out), (1_000, 891));
+        assert_eq!((quote.protocol_fee_amount, quote.fund_fee_amount), (12, 4));
+        assert!((quote.price_before - 1.0).abs() < 1e-9);
+        assert!((quote.price_after - 0.99 * 0.99).abs() < 1e-9);
+        // 891 out for the 900 left after the fee.
+        assert!((quote.execution_price - 0.99).abs() < 1e-9);
+        assert!((quote.price_impact - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn insufficient_liquidity_loaded_extrapolates_arrays() {
+        // 2 arrays filled 250 of 1000: 6 more at the same rate.
+        assert_eq!(
+            insufficient_liquidity_loaded(2, 1_000, 750),
+            SwapError::InsufficientLiquidityLoaded {
+                tick_arrays_loaded: 2,
+                additional_tick_arrays: 6,
+                amount_remaining: 750,
+            }
+        );
+        // Nothing filled: ask for one more array.
+        assert_eq!(
+            insufficient_liquidity_loaded(0, 1_000, 1_000),
+            SwapError::InsufficientLiquidityLoaded {
+                tick_arrays_loaded: 0,
+                additional_tick_arrays: 1,
+                amount_remaining: 1_000,
+            }
+        );
+    }
+
+    #[test]
+    fn swap_enabled_needs_swap_bit_and_open_time() {
+        let pool_id = Pubkey::new_unique();
+        let mut pool_state = PoolState {
+            open_time: 1_000,
+            ..Default::default()
+        };
+        assert!(validate_swap_enabled(&pool_id, &pool_state, 1_001).is_ok());
+        // The program needs the block time past the open time.
+        let err = validate_swap_enabled(&pool_id, &pool_state, 1_000).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SwapError>(),
+            Some(&SwapError::PoolNotOpen {
+                pool_id: solana_sdk::pubkey::Pubkey::from(pool_id.to_bytes()),
+                open_time: 1_000,
+                seconds_until_open: 1,
+            })
+        );
+        pool_state.set_status_by_bit(PoolStatusBitIndex::Swap, PoolStatusBitFlag::Disable);
+        let err = validate_swap_enabled(&pool_id, &pool_state, 1_001).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SwapError>(),
+            Some(SwapError::SwapDisabled { status: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn tick_array_keys_up_to_limit() {
+        // every tick array around the current tick initialized
+        let pool_state = PoolState {
+            tick_spacing: 1,
+            tick_array_bitmap: [u64::MAX; 16],
+            ..Default::default()
+        };
+        let (program, pool_id) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let keys = |max_tick_arrays| {
+            get_tick_array_keys_up_to(program, pool_id, &pool_state, &None, false, max_tick_arrays)
+                .unwrap()
+        };
+        let default_keys =
+            get_tick_array_keys(program, pool_id, &pool_state, &None, false).unwrap();
+        assert_eq!(default_keys.len(), DEFAULT_TICK_ARRAY_COUNT);
+        assert_eq!(keys(10).len(), 10);
+        assert_eq!(keys(10)[..DEFAULT_TICK_ARRAY_COUNT], default_keys[..]);
+        assert_eq!(
+            keys(10)[9],
+            Address::from(derive_tick_array_key(&pool_id, 540, &program).to_bytes())
+        );
+    }
 }
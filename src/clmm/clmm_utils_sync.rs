@@ -1,5 +1,5 @@
 use crate::clmm::ClmmSwapChangeResult;
-use crate::common::unpack_mint;
+use crate::common::{FeeTier, unpack_mint};
 use crate::interface::{CalculateSwapChangeParams, Rsps, TickArrays};
 use crate::states::PoolState;
 use anyhow::anyhow;
@@ -50,23 +50,30 @@ pub fn calculate_swap_change(
             .ok_or(anyhow!("Mint token program is None"))?
             .data,
     )?;
-    let (remaining_tick_array_keys, other_amount_threshold, sqrt_price_limit_x64) =
-        crate::clmm::clmm_utils::calculate_other_amount_threshold(
-            pool_id,
-            raydium_v3_program,
-            slippage_bps,
-            pool_state,
-            tickarray_bitmap_extension_state,
-            zero_for_one,
-            amount_specified,
-            amm_config_state,
-            limit_price,
-            base_in,
-            tick_arrays,
-            &mint0_state,
-            &mint1_state,
-            epoch,
-        )?;
+    let fee_tier = FeeTier::from_amm_config(&amm_config_state);
+    let has_tick_array_bitmap_extension = tickarray_bitmap_extension_state.is_some();
+    let (
+        remaining_tick_array_keys,
+        other_amount_threshold,
+        sqrt_price_limit_x64,
+        amount_consumed,
+        quote,
+    ) = crate::clmm::clmm_utils::calculate_other_amount_threshold(
+        pool_id,
+        raydium_v3_program,
+        slippage_bps,
+        pool_state,
+        tickarray_bitmap_extension_state,
+        zero_for_one,
+        amount_specified,
+        amm_config_state,
+        limit_price,
+        base_in,
+        tick_arrays,
+        &mint0_state,
+        &mint1_state,
+        epoch,
+    )?;
 
     Ok(ClmmSwapChangeResult {
         pool_amm_config: pool_state.amm_config,
@@ -84,5 +91,15 @@ pub fn calculate_swap_change(
         other_amount_threshold,
         sqrt_price_limit_x64,
         is_base_input: base_in,
+        fee_tier,
+        amount_requested: amount_specified,
+        amount_consumed,
+        fully_filled: amount_consumed == amount_specified,
+        has_tick_array_bitmap_extension,
+        epoch,
+        context_slot: None,
+        sqrt_price_x64: pool_state.sqrt_price_x64,
+        tick_current: pool_state.tick_current,
+        quote,
     })
 }
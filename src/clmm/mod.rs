@@ -1,7 +1,25 @@
 pub mod clmm_utils;
 pub use clmm_utils::*;
+pub mod clmm_account_quote;
+pub use clmm_account_quote::*;
 pub mod clmm_math;
 pub use clmm_math::*;
+pub mod clmm_oracle;
+pub use clmm_oracle::*;
+pub mod clmm_pda;
+pub use clmm_pda::*;
+pub mod clmm_staleness;
+pub use clmm_staleness::*;
+pub mod clmm_position_indexer;
+pub use clmm_position_indexer::*;
+pub mod clmm_position;
+pub use clmm_position::*;
+pub mod clmm_swap_accounts;
+pub use clmm_swap_accounts::*;
+pub mod clmm_swap_compute;
+pub use clmm_swap_compute::*;
+pub mod clmm_tick_analytics;
+pub use clmm_tick_analytics::*;
 pub mod clmm_types;
 pub mod clmm_utils_sync;
 
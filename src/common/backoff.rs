@@ -0,0 +1,128 @@
+//! Retry of transient RPC and Raydium API failures with exponential backoff.
+//!
+//! Public RPCs and the Raydium API answer bursts with 429s and the odd 5xx or
+//! timeout. [`RetryPolicy::retry`] repeats a call failing that way, waiting
+//! twice as long after every attempt, with jitter so that concurrent callers
+//! do not retry in lockstep.
+
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::RpcError;
+use std::fmt::Display;
+use std::future::Future;
+use std::hash::{BuildHasher, RandomState};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// JSON-RPC error code of a node behind the cluster.
+const RPC_NODE_UNHEALTHY: i64 = -32005;
+
+/// Retries of transient failures, with exponential backoff and jitter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, 0 to disable retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each following one.
+    pub initial_backoff: Duration,
+    /// Delays are never longer than this.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// 3 retries, waiting up to 250ms, 500ms and 1s.
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Single attempt, failures are returned as they are.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Delay before retry `retry`, counted from 0: between half and all of
+    /// `initial_backoff * 2^retry`, capped at `max_backoff`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        backoff.mul_f64(0.5 + jitter() / 2.0)
+    }
+
+    /// Run `operation` until it succeeds, fails with an error `is_transient`
+    /// rejects, or the retries are exhausted. The last error is returned.
+    pub async fn retry<T, E, F, Fut>(
+        &self,
+        mut operation: F,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Display,
+    {
+        let mut retry = 0;
+        loop {
+            match operation().await {
+                Err(e) if retry < self.max_retries && is_transient(&e) => {
+                    let backoff = self.backoff(retry);
+                    retry += 1;
+                    warn!(
+                        "Transient failure, retry {retry}/{} in {backoff:?}: {e}",
+                        self.max_retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether an RPC call failed for a reason a later attempt may not hit: a
+/// timeout, a dropped connection, a 429 or 5xx, or a node behind the cluster.
+pub fn is_transient_client_error(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => is_transient_reqwest_error(e),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == RPC_NODE_UNHEALTHY || *code == 429
+        }
+        _ => false,
+    }
+}
+
+/// Whether an HTTP request failed with a timeout, a connection error, a 429
+/// or a 5xx.
+pub fn is_transient_reqwest_error(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect() || error.status().is_some_and(is_transient_status)
+}
+
+pub fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Uniform value in `[0, 1)`, seeded per call by the std hasher.
+fn jitter() -> f64 {
+    let random = RandomState::new().hash_one(Instant::now());
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -1,13 +1,14 @@
 use crate::common::{TEN_THOUSAND, TransferFeeInfo};
 use anchor_lang::AccountDeserialize;
 use anyhow::{Result, anyhow, format_err};
+use bytemuck::AnyBitPattern;
 use solana_address::Address;
 use solana_program_pack::Pack as SolanaProgramPack;
 use solana_sdk::{account::Account as CliAccount, pubkey::Pubkey, signer::keypair::Keypair};
 use spl_token::solana_program::program_pack::Pack;
 use spl_token_2022::{
     extension::{
-        BaseState, BaseStateWithExtensions, StateWithExtensions,
+        BaseState, BaseStateWithExtensions, ExtensionType, StateWithExtensions,
         transfer_fee::{MAX_FEE_BASIS_POINTS, TransferFeeConfig},
     },
     state::{Account, Mint},
@@ -96,17 +97,67 @@ pub fn unpack_mint(token_data: &[u8]) -> Result<StateWithExtensions<'_, Mint>> {
     Ok(mint)
 }
 
+/// Length of a Token-2022 associated token account of the mint of
+/// `mint_data`: the account extensions its mint requires, plus the immutable
+/// owner of associated token accounts.
+pub fn token_2022_account_len(mint_data: &[u8]) -> Result<usize> {
+    let mint = unpack_mint(mint_data)?;
+    let mut extensions =
+        ExtensionType::get_required_init_account_extensions(&mint.get_extension_types()?);
+    if !extensions.contains(&ExtensionType::ImmutableOwner) {
+        extensions.push(ExtensionType::ImmutableOwner);
+    }
+    let len = ExtensionType::try_calculate_account_len::<Account>(&extensions)?;
+    Ok(len)
+}
+
 pub fn deserialize_anchor_account<T: AccountDeserialize>(account: &CliAccount) -> Result<T> {
-    let mut data: &[u8] = &account.data;
+    deserialize_anchor_account_data(&account.data)
+}
+
+/// [`deserialize_anchor_account`] of raw account data, checked against the
+/// discriminator of `T`.
+pub fn deserialize_anchor_account_data<T: AccountDeserialize>(mut data: &[u8]) -> Result<T> {
     T::try_deserialize(&mut data).map_err(Into::into)
 }
 
-pub fn deserialize_account<T: Copy>(account: &CliAccount, is_anchor_account: bool) -> Result<T> {
-    let mut account_data = account.data.as_slice();
-    if is_anchor_account {
-        account_data = &account_data[8..size_of::<T>() + 8];
+/// Bytes of a `T` at the start of the account data, after the discriminator
+/// of anchor accounts.
+fn account_state_bytes<T>(account: &CliAccount, is_anchor_account: bool) -> Result<&[u8]> {
+    let offset = if is_anchor_account { 8 } else { 0 };
+    account
+        .data
+        .get(offset..offset + size_of::<T>())
+        .ok_or(anyhow!(
+            "account data of {} bytes is too short for a {} byte state",
+            account.data.len(),
+            offset + size_of::<T>()
+        ))
+}
+
+/// Read a plain-old-data state from the account data, at any alignment.
+pub fn deserialize_account<T: AnyBitPattern>(
+    account: &CliAccount,
+    is_anchor_account: bool,
+) -> Result<T> {
+    let data = account_state_bytes::<T>(account, is_anchor_account)?;
+    // Packed zero-copy states have an alignment of 1 and are read in place.
+    match bytemuck::try_from_bytes::<T>(data) {
+        Ok(state) => Ok(*state),
+        Err(_) => bytemuck::try_pod_read_unaligned(data)
+            .map_err(|e| anyhow!("failed to read account state: {:?}", e)),
     }
-    Ok(unsafe { *(&account_data[0] as *const u8 as *const T) })
+}
+
+/// Borrow a state from the account data without copying it. Fails unless
+/// the data is aligned for `T`, which always holds for the packed zero-copy
+/// states.
+pub fn deserialize_account_ref<T: AnyBitPattern>(
+    account: &CliAccount,
+    is_anchor_account: bool,
+) -> Result<&T> {
+    let data = account_state_bytes::<T>(account, is_anchor_account)?;
+    bytemuck::try_from_bytes(data).map_err(|e| anyhow!("failed to borrow account state: {:?}", e))
 }
 
 pub async fn get_pool_mints_inverse_fee(
@@ -0,0 +1,57 @@
+use crate::interface::ClmmPool;
+use crate::states::{AmmConfig, FEE_RATE_DENOMINATOR_VALUE};
+
+/// Fee rates of a pool, as fractions (e.g. `0.0025` for 0.25%).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeTier {
+    /// Fee charged on the swapped amount.
+    pub trade_fee_rate: f64,
+    /// Share of the trade fee kept by the protocol (CLMM and CPMM only).
+    pub protocol_fee_rate: Option<f64>,
+    /// Share of the trade fee sent to the fund (CLMM and CPMM only).
+    pub fund_fee_rate: Option<f64>,
+}
+
+impl FeeTier {
+    /// Fee tier of an AMM v4 pool from its swap fee fraction.
+    pub fn from_amm(swap_fee_numerator: u64, swap_fee_denominator: u64) -> Self {
+        Self {
+            trade_fee_rate: swap_fee_numerator as f64 / swap_fee_denominator.max(1) as f64,
+            protocol_fee_rate: None,
+            fund_fee_rate: None,
+        }
+    }
+
+    /// Fee tier of a CLMM pool from its on-chain `AmmConfig`.
+    pub fn from_amm_config(amm_config: &AmmConfig) -> Self {
+        let denominator = FEE_RATE_DENOMINATOR_VALUE as f64;
+        Self {
+            trade_fee_rate: amm_config.trade_fee_rate as f64 / denominator,
+            protocol_fee_rate: Some(amm_config.protocol_fee_rate as f64 / denominator),
+            fund_fee_rate: Some(amm_config.fund_fee_rate as f64 / denominator),
+        }
+    }
+
+    /// Fee tier reported by the Raydium API. The CLMM config block is
+    /// preferred; `fee_rate` is used for pools without one.
+    pub fn from_pool_info(pool_info: &ClmmPool) -> Option<Self> {
+        let denominator = FEE_RATE_DENOMINATOR_VALUE as f64;
+        match &pool_info.config {
+            Some(config) => Some(Self {
+                trade_fee_rate: config.trade_fee_rate as f64 / denominator,
+                protocol_fee_rate: Some(config.protocol_fee_rate as f64 / denominator),
+                fund_fee_rate: config.fund_fee_rate.map(|rate| rate as f64 / denominator),
+            }),
+            None => pool_info.fee_rate.map(|trade_fee_rate| Self {
+                trade_fee_rate,
+                protocol_fee_rate: None,
+                fund_fee_rate: None,
+            }),
+        }
+    }
+
+    /// Trade fee in basis points.
+    pub fn trade_fee_bps(&self) -> f64 {
+        self.trade_fee_rate * 10_000.0
+    }
+}
@@ -8,3 +8,9 @@ pub mod common_utils;
 pub use common_utils::*;
 pub mod common_types;
 pub use common_types::*;
+pub mod fees;
+pub use fees::*;
+pub mod request_log;
+pub use request_log::*;
+pub mod backoff;
+pub use backoff::*;
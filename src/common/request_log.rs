@@ -0,0 +1,208 @@
+//! Recording of outbound RPC and Raydium API requests, for diagnosing latency
+//! and rate limits.
+//!
+//! RPC calls are recorded by an [`RpcClient`] built with
+//! [`RequestLog::rpc_client`]; API calls by an
+//! [`AmmSwapClient`](crate::amm::client::AmmSwapClient) or a
+//! [`RaydiumApiClient`](crate::api::RaydiumApiClient) given the log with
+//! `with_request_log`. Every record is also emitted at debug level.
+//!
+//! A log keeps at most its capacity of records, dropping the oldest, so a
+//! long-running client that never takes them does not grow without bound.
+
+use solana_client::client_error::Result as ClientResult;
+use solana_client::http_sender::HttpSender;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::RpcClientConfig;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_commitment_config::CommitmentConfig;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Default number of records a [`RequestLog`] keeps.
+pub const DEFAULT_REQUEST_LOG_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+    Rpc,
+    Api,
+}
+
+/// A single outbound request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestRecord {
+    pub kind: RequestKind,
+    /// RPC method name, or URL of the API request.
+    pub target: String,
+    pub elapsed: Duration,
+    /// `false` when the request failed or returned a non-success status.
+    pub success: bool,
+}
+
+/// Shared, cloneable list of the latest recorded requests.
+#[derive(Clone, Debug)]
+pub struct RequestLog {
+    records: Arc<Mutex<VecDeque<RequestRecord>>>,
+    capacity: usize,
+}
+
+impl Default for RequestLog {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_REQUEST_LOG_CAPACITY)
+    }
+}
+
+impl RequestLog {
+    /// Log keeping the latest [`DEFAULT_REQUEST_LOG_CAPACITY`] records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Log keeping the latest `capacity` records. A zero capacity keeps
+    /// none; records are still emitted at debug level.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// RPC client for `url` recording every call into this log.
+    pub fn rpc_client(&self, url: impl ToString, commitment: CommitmentConfig) -> RpcClient {
+        RpcClient::new_sender(
+            RecordingSender {
+                inner: HttpSender::new(url),
+                log: self.clone(),
+            },
+            RpcClientConfig::with_commitment(commitment),
+        )
+    }
+
+    /// Requests recorded so far, oldest first.
+    pub fn records(&self) -> Vec<RequestRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Remove and return the recorded requests, e.g. after each quote or swap
+    /// to see what it issued.
+    pub fn take(&self) -> Vec<RequestRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+            .collect()
+    }
+
+    pub(crate) fn record(
+        &self,
+        kind: RequestKind,
+        target: String,
+        elapsed: Duration,
+        success: bool,
+    ) {
+        debug!("{kind:?} request {target} took {elapsed:?} (success: {success})");
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(RequestRecord {
+            kind,
+            target,
+            elapsed,
+            success,
+        });
+    }
+}
+
+/// HTTP sender timing each call into a [`RequestLog`].
+struct RecordingSender {
+    inner: HttpSender,
+    log: RequestLog,
+}
+
+impl RpcSender for RecordingSender {
+    fn send<'a, 'b>(
+        &'a self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> Pin<Box<dyn Future<Output = ClientResult<serde_json::Value>> + Send + 'b>>
+    where
+        'a: 'b,
+        Self: 'b,
+    {
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = self.inner.send(request, params).await;
+            self.log.record(
+                RequestKind::Rpc,
+                request.to_string(),
+                started.elapsed(),
+                result.is_ok(),
+            );
+            result
+        })
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(log: &RequestLog, target: &str) {
+        log.record(
+            RequestKind::Rpc,
+            target.to_string(),
+            Duration::from_millis(1),
+            true,
+        );
+    }
+
+    fn targets(records: &[RequestRecord]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| record.target.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn full_log_drops_the_oldest_record() {
+        let log = RequestLog::with_capacity(2);
+        record(&log, "getSlot");
+        record(&log, "getAccountInfo");
+        record(&log, "sendTransaction");
+        assert_eq!(
+            targets(&log.records()),
+            ["getAccountInfo", "sendTransaction"]
+        );
+        assert_eq!(targets(&log.take()), ["getAccountInfo", "sendTransaction"]);
+        assert!(log.records().is_empty());
+    }
+
+    #[test]
+    fn zero_capacity_log_keeps_nothing() {
+        let log = RequestLog::with_capacity(0);
+        record(&log, "getSlot");
+        assert!(log.records().is_empty());
+    }
+}
@@ -1,8 +1,14 @@
 use anchor_lang::AccountDeserialize;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use solana_account::Account;
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_response::Response;
 use solana_commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 pub async fn get_anchor_account<T: AccountDeserialize>(
     client: &RpcClient,
@@ -20,3 +26,179 @@ pub async fn get_anchor_account<T: AccountDeserialize>(
         Ok(None)
     }
 }
+
+/// Accounts read through the RPC, kept for a time to live.
+///
+/// Quoting a pool again and again reads the same accounts: the pool state,
+/// its amm config, mints and bitmap extension. The cache serves those still
+/// fresh and fetches the rest with a single `get_multiple_accounts` call.
+/// Each account expires after the default TTL unless given its own, e.g. a
+/// long one for amm configs and mints, which rarely change, and a short one
+/// for pool states. Missing accounts are cached as `None` too.
+///
+/// Cheap to clone, clones share the cached accounts.
+#[derive(Debug, Clone)]
+pub struct AccountCache {
+    inner: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Debug)]
+struct CacheState {
+    default_ttl: Duration,
+    ttls: HashMap<Pubkey, Duration>,
+    entries: HashMap<Pubkey, CachedAccount>,
+}
+
+#[derive(Debug)]
+struct CachedAccount {
+    account: Option<Account>,
+    fetched_at: Instant,
+    /// Slot the account was read at, unknown for inserted accounts.
+    slot: Option<u64>,
+}
+
+impl AccountCache {
+    /// Cache expiring every account `default_ttl` after it was fetched.
+    pub fn new(default_ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheState {
+                default_ttl,
+                ttls: HashMap::new(),
+                entries: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Expire `address` `ttl` after it was fetched instead of the default.
+    pub fn with_ttl(self, address: Pubkey, ttl: Duration) -> Self {
+        self.set_ttl(address, ttl);
+        self
+    }
+
+    pub fn set_ttl(&self, address: Pubkey, ttl: Duration) {
+        self.lock().ttls.insert(address, ttl);
+    }
+
+    pub fn ttl(&self, address: &Pubkey) -> Duration {
+        self.lock().ttl(address)
+    }
+
+    /// Cached account if still fresh, `Some(None)` for a fresh missing one.
+    pub fn get(&self, address: &Pubkey) -> Option<Option<Account>> {
+        self.lock().fresh(address, Instant::now())
+    }
+
+    /// Slot a cached account was read at, `None` when not cached or
+    /// inserted with [`Self::insert`].
+    pub fn read_slot(&self, address: &Pubkey) -> Option<u64> {
+        self.lock().entries.get(address)?.slot
+    }
+
+    /// Cache `account`, e.g. read from a subscription, as fetched now.
+    pub fn insert(&self, address: Pubkey, account: Option<Account>) {
+        self.lock().insert(address, account, None);
+    }
+
+    /// Drop `address`, so that its next read fetches it.
+    pub fn invalidate(&self, address: &Pubkey) {
+        self.lock().entries.remove(address);
+    }
+
+    pub fn invalidate_all(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// Same as `RpcClient::get_multiple_accounts`, fetching only the
+    /// accounts not cached or expired.
+    pub async fn get_multiple_accounts(
+        &self,
+        client: &RpcClient,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>> {
+        self.get_multiple_accounts_with(addresses, |missing| async move {
+            Ok(client
+                .get_multiple_accounts_with_commitment(&missing, client.commitment())
+                .await?)
+        })
+        .await
+    }
+
+    /// Same as [`Self::get_multiple_accounts`], fetching the accounts not
+    /// cached or expired with `fetch`, which returns them in order with the
+    /// slot they were read at.
+    pub async fn get_multiple_accounts_with<F, Fut>(
+        &self,
+        addresses: &[Pubkey],
+        fetch: F,
+    ) -> Result<Vec<Option<Account>>>
+    where
+        F: FnOnce(Vec<Pubkey>) -> Fut,
+        Fut: Future<Output = Result<Response<Vec<Option<Account>>>>>,
+    {
+        let mut accounts = Vec::with_capacity(addresses.len());
+        let mut missing = Vec::new();
+        {
+            let state = self.lock();
+            let now = Instant::now();
+            for (i, address) in addresses.iter().enumerate() {
+                let account = state.fresh(address, now);
+                if account.is_none() && !missing.contains(address) {
+                    missing.push(*address);
+                }
+                accounts.push(account.ok_or(i));
+            }
+        }
+        if missing.is_empty() {
+            return Ok(accounts.into_iter().flatten().collect());
+        }
+
+        let Response {
+            context,
+            value: fetched,
+        } = fetch(missing.clone()).await?;
+        if fetched.len() != missing.len() {
+            return Err(anyhow!(
+                "Fetched {} accounts for {} addresses",
+                fetched.len(),
+                missing.len()
+            ));
+        }
+        let fetched: HashMap<Pubkey, Option<Account>> = missing.into_iter().zip(fetched).collect();
+        {
+            let mut state = self.lock();
+            for (address, account) in &fetched {
+                state.insert(*address, account.clone(), Some(context.slot));
+            }
+        }
+        Ok(accounts
+            .into_iter()
+            .map(|account| account.unwrap_or_else(|i| fetched[&addresses[i]].clone()))
+            .collect())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CacheState {
+    fn insert(&mut self, address: Pubkey, account: Option<Account>, slot: Option<u64>) {
+        self.entries.insert(
+            address,
+            CachedAccount {
+                account,
+                fetched_at: Instant::now(),
+                slot,
+            },
+        );
+    }
+
+    fn ttl(&self, address: &Pubkey) -> Duration {
+        self.ttls.get(address).copied().unwrap_or(self.default_ttl)
+    }
+
+    fn fresh(&self, address: &Pubkey, now: Instant) -> Option<Option<Account>> {
+        let entry = self.entries.get(address)?;
+        (now.duration_since(entry.fetched_at) < self.ttl(address)).then(|| entry.account.clone())
+    }
+}
@@ -0,0 +1,282 @@
+//! Client configuration read from the environment.
+//!
+//! | Variable | Default |
+//! |---|---|
+//! | `CLUSTER` | `mainnet` (`mainnet` or `devnet`) |
+//! | `RPC_URL` | public RPC of the cluster |
+//! | `KEYPAIR` | required, keypair bytes as `[1,2,...]` |
+//! | `RAYDIUM_API_URL` | `https://api-v3.raydium.io` |
+//! | `RAYDIUM_API_TIMEOUT_MS` | none, timeout of Raydium API requests |
+//! | `SLIPPAGE_BPS` | 50 |
+//! | `PRIORITY_FEE_MICRO_LAMPORTS` | none, fixed compute unit price |
+//! | `PRIORITY_FEE_PERCENTILE` | none, dynamic compute unit price |
+//! | `PRIORITY_FEE_MAX_MICRO_LAMPORTS` | 1 000 000, cap of the dynamic price |
+//! | `COMPUTE_UNIT_LIMIT` | runtime default |
+//! | `PRIORITY_FEE_BUDGET_LAMPORTS` | none, cap of the priority fee per transaction |
+//! | `DRY_RUN` | `false`, `true` to simulate transactions instead of sending them |
+//!
+//! A `.env` file in the working directory is loaded first, if present.
+
+use crate::amm::builder::DEFAULT_SLIPPAGE_BPS;
+use crate::amm::client::AmmSwapClient;
+use crate::api::RaydiumApiClient;
+use crate::consts::{AMM_V4, CLMM, CPMM, DEVNET_AMM_V4, DEVNET_CLMM, DEVNET_CPMM};
+use crate::execution::{ExecutionMode, FeeBudget, PriorityFeeConfig};
+use crate::helpers::try_key_pair_from_bytes;
+use crate::router::HopKind;
+use crate::validation;
+use anyhow::anyhow;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Raydium API used unless `RAYDIUM_API_URL` is set.
+pub const DEFAULT_API_BASE_URL: &str = "https://api-v3.raydium.io";
+
+/// Cap of a dynamic priority fee unless `PRIORITY_FEE_MAX_MICRO_LAMPORTS` is
+/// set.
+pub const DEFAULT_MAX_PRIORITY_FEE_MICRO_LAMPORTS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cluster {
+    #[default]
+    Mainnet,
+    Devnet,
+}
+
+impl Cluster {
+    /// Public RPC endpoint of the cluster, rate limited.
+    pub fn default_rpc_url(&self) -> &'static str {
+        match self {
+            Cluster::Mainnet => "https://api.mainnet-beta.solana.com",
+            Cluster::Devnet => "https://api.devnet.solana.com",
+        }
+    }
+}
+
+/// Program ids of the Raydium programs a client talks to.
+///
+/// Instructions are built for, PDAs derived from, and pools recognized by
+/// these ids, so a client configured with [`Self::devnet`] or
+/// [`Self::custom`] works against devnet pools or programs deployed to a
+/// local validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClusterConfig {
+    pub amm_v4: Pubkey,
+    pub clmm: Pubkey,
+    pub cpmm: Pubkey,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+impl ClusterConfig {
+    pub fn mainnet() -> Self {
+        Self {
+            amm_v4: Pubkey::from_str_const(AMM_V4),
+            clmm: Pubkey::from_str_const(CLMM),
+            cpmm: Pubkey::from_str_const(CPMM),
+        }
+    }
+
+    /// The programs Raydium deploys to devnet.
+    pub fn devnet() -> Self {
+        Self {
+            amm_v4: Pubkey::from_str_const(DEVNET_AMM_V4),
+            clmm: Pubkey::from_str_const(DEVNET_CLMM),
+            cpmm: Pubkey::from_str_const(DEVNET_CPMM),
+        }
+    }
+
+    /// Programs deployed elsewhere, e.g. to a local validator.
+    pub fn custom(amm_v4: Pubkey, clmm: Pubkey, cpmm: Pubkey) -> Self {
+        Self { amm_v4, clmm, cpmm }
+    }
+
+    /// CLMM program id as taken by the [`clmm`](crate::clmm) PDA functions.
+    pub fn clmm_program_id(&self) -> solana_pubkey::Pubkey {
+        solana_pubkey::Pubkey::from(self.clmm.to_bytes())
+    }
+
+    /// Kind of the pool owned by `program_id`, `None` for other programs.
+    pub fn hop_kind(&self, program_id: &Pubkey) -> Option<HopKind> {
+        if *program_id == self.amm_v4 {
+            Some(HopKind::AmmV4)
+        } else if *program_id == self.clmm {
+            Some(HopKind::Clmm)
+        } else if *program_id == self.cpmm {
+            Some(HopKind::Cpmm)
+        } else {
+            None
+        }
+    }
+
+    /// Program of pools of `kind`.
+    pub fn program_id(&self, kind: HopKind) -> Pubkey {
+        match kind {
+            HopKind::AmmV4 => self.amm_v4,
+            HopKind::Clmm => self.clmm,
+            HopKind::Cpmm => self.cpmm,
+        }
+    }
+}
+
+impl From<Cluster> for ClusterConfig {
+    fn from(cluster: Cluster) -> Self {
+        match cluster {
+            Cluster::Mainnet => Self::mainnet(),
+            Cluster::Devnet => Self::devnet(),
+        }
+    }
+}
+
+impl FromStr for Cluster {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" | "mainnet-beta" => Ok(Cluster::Mainnet),
+            "devnet" => Ok(Cluster::Devnet),
+            _ => Err(anyhow!("expected mainnet or devnet")),
+        }
+    }
+}
+
+/// Settings of an [`AmmSwapClient`], usually read with [`Self::from_env`].
+#[derive(Debug)]
+pub struct ClientConfig {
+    pub cluster: Cluster,
+    pub rpc_url: String,
+    pub keypair: Keypair,
+    pub api_base_url: String,
+    /// Timeout of Raydium API requests, none when unset.
+    pub api_timeout: Option<Duration>,
+    /// Slippage tolerance of swaps, in bps.
+    pub slippage_bps: u64,
+    pub priority_fee: Option<PriorityFeeConfig>,
+    pub execution_mode: ExecutionMode,
+}
+
+impl ClientConfig {
+    /// Read the configuration from the environment variables listed in the
+    /// [module documentation](self). Fails naming the first variable that is
+    /// missing or invalid.
+    pub fn from_env() -> anyhow::Result<Self> {
+        dotenvy::dotenv().ok();
+        let cluster: Cluster = env_var("CLUSTER")?.unwrap_or_default();
+        let rpc_url = env_var("RPC_URL")?.unwrap_or_else(|| cluster.default_rpc_url().to_string());
+        let keypair = env::var("KEYPAIR").map_err(|_| anyhow!("KEYPAIR env is not presented"))?;
+        let keypair =
+            try_key_pair_from_bytes(&keypair).map_err(|e| anyhow!("Invalid KEYPAIR: {e}"))?;
+        let api_base_url =
+            env_var("RAYDIUM_API_URL")?.unwrap_or_else(|| DEFAULT_API_BASE_URL.to_string());
+        let api_timeout = env_var("RAYDIUM_API_TIMEOUT_MS")?.map(Duration::from_millis);
+        let slippage_bps = env_var("SLIPPAGE_BPS")?.unwrap_or(DEFAULT_SLIPPAGE_BPS);
+        validation::validate_slippage_bps(slippage_bps)
+            .map_err(|e| anyhow!("Invalid SLIPPAGE_BPS: {e}"))?;
+
+        let fixed_fee: Option<u64> = env_var("PRIORITY_FEE_MICRO_LAMPORTS")?;
+        let percentile: Option<u8> = env_var("PRIORITY_FEE_PERCENTILE")?;
+        let mut priority_fee = match (fixed_fee, percentile) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!(
+                    "PRIORITY_FEE_MICRO_LAMPORTS and PRIORITY_FEE_PERCENTILE are exclusive"
+                ));
+            }
+            (Some(micro_lamports), None) => Some(PriorityFeeConfig::fixed(micro_lamports)),
+            (None, Some(percentile)) => {
+                if percentile > 100 {
+                    return Err(anyhow!(
+                        "Invalid PRIORITY_FEE_PERCENTILE `{percentile}`: expected 0-100"
+                    ));
+                }
+                let max_micro_lamports = env_var("PRIORITY_FEE_MAX_MICRO_LAMPORTS")?
+                    .unwrap_or(DEFAULT_MAX_PRIORITY_FEE_MICRO_LAMPORTS);
+                Some(PriorityFeeConfig::dynamic(percentile, max_micro_lamports))
+            }
+            (None, None) => None,
+        };
+        if let Some(compute_unit_limit) = env_var::<u32>("COMPUTE_UNIT_LIMIT")? {
+            priority_fee = Some(
+                priority_fee
+                    .unwrap_or(PriorityFeeConfig::fixed(0))
+                    .with_compute_unit_limit(compute_unit_limit),
+            );
+        }
+        if let Some(budget_lamports) = env_var::<u64>("PRIORITY_FEE_BUDGET_LAMPORTS")? {
+            priority_fee = Some(
+                priority_fee
+                    .unwrap_or(PriorityFeeConfig::fixed(0))
+                    .with_fee_budget(FeeBudget::Lamports(budget_lamports)),
+            );
+        }
+        let execution_mode = if env_var("DRY_RUN")?.unwrap_or(false) {
+            ExecutionMode::DryRun
+        } else {
+            ExecutionMode::Live
+        };
+
+        Ok(Self {
+            cluster,
+            rpc_url,
+            keypair,
+            api_base_url,
+            api_timeout,
+            slippage_bps,
+            priority_fee,
+            execution_mode,
+        })
+    }
+
+    /// Slippage tolerance as a fraction, as taken by the quote functions.
+    pub fn slippage(&self) -> f64 {
+        self.slippage_bps as f64 / 10_000.0
+    }
+
+    /// Raydium API client of the configured base URL and timeout.
+    pub fn api_client(&self) -> RaydiumApiClient {
+        let api_client = RaydiumApiClient::new(self.api_base_url.clone());
+        match self.api_timeout {
+            Some(timeout) => api_client.with_timeout(timeout),
+            None => api_client,
+        }
+    }
+
+    /// Build the client, with the configured API client, priority fee,
+    /// execution mode and the program ids of the cluster.
+    pub fn into_client(self) -> AmmSwapClient {
+        let api_client = self.api_client();
+        let client = AmmSwapClient::new(RpcClient::new(self.rpc_url), self.keypair)
+            .with_api_client(api_client)
+            .with_cluster_config(self.cluster.into())
+            .with_execution_mode(self.execution_mode);
+        match self.priority_fee {
+            Some(priority_fee) => client.with_priority_fee(priority_fee),
+            None => client,
+        }
+    }
+}
+
+/// Parse the environment variable `name`, `None` when it is unset or empty.
+fn env_var<T>(name: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid {name} `{value}`: {e}")),
+        Ok(_) | Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(anyhow!("{name} is not valid unicode")),
+    }
+}
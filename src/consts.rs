@@ -3,9 +3,30 @@
 pub fn swap_v2_discriminator() -> [u8; 8] {
     [43, 4, 237, 11, 26, 201, 30, 98]
 }
+pub fn open_position_v2_discriminator() -> [u8; 8] {
+    [77, 184, 74, 214, 112, 86, 241, 199]
+}
+pub fn open_position_with_token22_nft_discriminator() -> [u8; 8] {
+    [77, 255, 174, 82, 125, 29, 201, 46]
+}
+pub fn increase_liquidity_v2_discriminator() -> [u8; 8] {
+    [133, 29, 89, 223, 69, 238, 176, 10]
+}
+pub fn decrease_liquidity_v2_discriminator() -> [u8; 8] {
+    [58, 127, 188, 62, 79, 82, 196, 96]
+}
+pub fn close_position_discriminator() -> [u8; 8] {
+    [123, 134, 81, 0, 49, 68, 98, 98]
+}
 /// The Solana native token mint (wrapped SOL).
 pub const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
 
+/// USDC mint.
+pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// USDT mint.
+pub const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+
 /// Numerator for Raydium liquidity fee (25 / 10_000 = 0.25%).
 pub const LIQUIDITY_FEES_NUMERATOR: u64 = 25;
 
@@ -19,5 +40,33 @@ pub const CLMM: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
 
 pub const CPMM: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
 
+/// Program ID for Raydium AMM V4 on devnet.
+pub const DEVNET_AMM_V4: &str = "DRaya7Kj3aMWQSy19kSjvmuwq9docCHofyP9kanQGaav";
+
+pub const DEVNET_CLMM: &str = "DRayAUgENGQBKVaX8owNhgzkEDyoHTGVEGHVJT1E9pfH";
+
+pub const DEVNET_CPMM: &str = "DRaycpLY18LhpbydsBWbVJtxpNv9oXPgjRSfpF2bWpYb";
+
 pub const ADMIN: &str = "GThUX1Atko4tqhN2NaiTazWSeFWMuiUvfFnyJyUghFMJ";
 pub const OPEN_BOOK: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+
+/// Metaplex token metadata program, holding the metadata of position NFTs.
+pub const TOKEN_METADATA_PROGRAM: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+/// Default Jito block engine endpoint.
+pub const JITO_BLOCK_ENGINE_URL: &str = "https://mainnet.block-engine.jito.wtf";
+
+/// Jito endpoint reporting recently landed tip percentiles.
+pub const JITO_TIP_FLOOR_URL: &str = "https://bundles.jito.wtf/api/v1/bundles/tip_floor";
+
+/// Jito tip payment accounts. Any of them can receive the bundle tip.
+pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
@@ -0,0 +1,336 @@
+//! Quotes of Raydium CPMM (CP-swap) pools from their on-chain accounts.
+//!
+//! Only the fields needed to quote are decoded, at their offsets in the
+//! packed pool state and in the config account. Fees owed to the protocol,
+//! the fund and the pool creator stay in the vaults until collected, so they
+//! are taken out of the vault balances to get the reserves.
+
+use crate::common::FeeTier;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+
+/// Denominator of the fee rates of a CPMM config.
+pub const CPMM_FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+
+const POOL_AMM_CONFIG_OFFSET: usize = 8;
+const POOL_TOKEN_0_VAULT_OFFSET: usize = 72;
+const POOL_TOKEN_1_VAULT_OFFSET: usize = 104;
+pub(crate) const POOL_TOKEN_0_MINT_OFFSET: usize = 168;
+pub(crate) const POOL_TOKEN_1_MINT_OFFSET: usize = 200;
+const POOL_STATUS_OFFSET: usize = 329;
+const POOL_MINT_0_DECIMALS_OFFSET: usize = 331;
+const POOL_MINT_1_DECIMALS_OFFSET: usize = 332;
+const POOL_PROTOCOL_FEES_TOKEN_0_OFFSET: usize = 341;
+const POOL_PROTOCOL_FEES_TOKEN_1_OFFSET: usize = 349;
+const POOL_FUND_FEES_TOKEN_0_OFFSET: usize = 357;
+const POOL_FUND_FEES_TOKEN_1_OFFSET: usize = 365;
+const POOL_OPEN_TIME_OFFSET: usize = 373;
+/// Zero in pools created before creator fees, where these were padding.
+const POOL_CREATOR_FEES_TOKEN_0_OFFSET: usize = 397;
+const POOL_CREATOR_FEES_TOKEN_1_OFFSET: usize = 405;
+const POOL_STATE_MIN_LEN: usize = 413;
+/// Size of a CPMM pool account, discriminator included.
+pub(crate) const POOL_STATE_LEN: usize = 637;
+
+const CONFIG_TRADE_FEE_RATE_OFFSET: usize = 12;
+const CONFIG_PROTOCOL_FEE_RATE_OFFSET: usize = 20;
+const CONFIG_FUND_FEE_RATE_OFFSET: usize = 28;
+const CONFIG_MIN_LEN: usize = 36;
+
+/// Bit of the pool status disabling swaps.
+const STATUS_SWAP_DISABLED: u8 = 1 << 2;
+
+/// Fields of a CPMM pool state needed to quote it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpmmPoolState {
+    pub amm_config: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_1_mint: Pubkey,
+    /// Bits disabling deposits (1), withdrawals (2) and swaps (4).
+    pub status: u8,
+    pub mint_0_decimals: u8,
+    pub mint_1_decimals: u8,
+    /// Protocol, fund and creator fees of token 0 held in its vault.
+    pub fees_token_0: u64,
+    /// Protocol, fund and creator fees of token 1 held in its vault.
+    pub fees_token_1: u64,
+    /// Unix timestamp from which the pool trades.
+    pub open_time: u64,
+}
+
+impl CpmmPoolState {
+    /// Decode the data of a CPMM pool account, discriminator included.
+    pub fn try_from_account_data(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < POOL_STATE_MIN_LEN {
+            return Err(anyhow!(
+                "invalid CPMM pool state length: expected at least {}, got {}",
+                POOL_STATE_MIN_LEN,
+                data.len()
+            ));
+        }
+        let fees = |offsets: [usize; 3]| {
+            offsets.iter().fold(0u64, |total, offset| {
+                total.saturating_add(read_u64(data, *offset))
+            })
+        };
+        Ok(Self {
+            amm_config: read_pubkey(data, POOL_AMM_CONFIG_OFFSET),
+            token_0_vault: read_pubkey(data, POOL_TOKEN_0_VAULT_OFFSET),
+            token_1_vault: read_pubkey(data, POOL_TOKEN_1_VAULT_OFFSET),
+            token_0_mint: read_pubkey(data, POOL_TOKEN_0_MINT_OFFSET),
+            token_1_mint: read_pubkey(data, POOL_TOKEN_1_MINT_OFFSET),
+            status: data[POOL_STATUS_OFFSET],
+            mint_0_decimals: data[POOL_MINT_0_DECIMALS_OFFSET],
+            mint_1_decimals: data[POOL_MINT_1_DECIMALS_OFFSET],
+            fees_token_0: fees([
+                POOL_PROTOCOL_FEES_TOKEN_0_OFFSET,
+                POOL_FUND_FEES_TOKEN_0_OFFSET,
+                POOL_CREATOR_FEES_TOKEN_0_OFFSET,
+            ]),
+            fees_token_1: fees([
+                POOL_PROTOCOL_FEES_TOKEN_1_OFFSET,
+                POOL_FUND_FEES_TOKEN_1_OFFSET,
+                POOL_CREATOR_FEES_TOKEN_1_OFFSET,
+            ]),
+            open_time: read_u64(data, POOL_OPEN_TIME_OFFSET),
+        })
+    }
+
+    /// Whether a swap is accepted at unix timestamp `now`.
+    pub fn is_swap_enabled(&self, now: u64) -> bool {
+        self.status & STATUS_SWAP_DISABLED == 0 && now >= self.open_time
+    }
+
+    /// Reserves of token 0 and token 1 from the balances of their vaults.
+    pub fn reserves(&self, vault_0_amount: u64, vault_1_amount: u64) -> (u64, u64) {
+        (
+            vault_0_amount.saturating_sub(self.fees_token_0),
+            vault_1_amount.saturating_sub(self.fees_token_1),
+        )
+    }
+}
+
+/// Fee rates of a CPMM config, over [`CPMM_FEE_RATE_DENOMINATOR`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpmmAmmConfig {
+    pub trade_fee_rate: u64,
+    /// Share of the trade fee kept by the protocol.
+    pub protocol_fee_rate: u64,
+    /// Share of the trade fee sent to the fund.
+    pub fund_fee_rate: u64,
+}
+
+impl CpmmAmmConfig {
+    /// Decode the data of a CPMM config account, discriminator included.
+    pub fn try_from_account_data(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < CONFIG_MIN_LEN {
+            return Err(anyhow!(
+                "invalid CPMM config length: expected at least {}, got {}",
+                CONFIG_MIN_LEN,
+                data.len()
+            ));
+        }
+        Ok(Self {
+            trade_fee_rate: read_u64(data, CONFIG_TRADE_FEE_RATE_OFFSET),
+            protocol_fee_rate: read_u64(data, CONFIG_PROTOCOL_FEE_RATE_OFFSET),
+            fund_fee_rate: read_u64(data, CONFIG_FUND_FEE_RATE_OFFSET),
+        })
+    }
+
+    pub fn fee_tier(&self) -> FeeTier {
+        let denominator = CPMM_FEE_RATE_DENOMINATOR as f64;
+        FeeTier {
+            trade_fee_rate: self.trade_fee_rate as f64 / denominator,
+            protocol_fee_rate: Some(self.protocol_fee_rate as f64 / denominator),
+            fund_fee_rate: Some(self.fund_fee_rate as f64 / denominator),
+        }
+    }
+
+    /// Trade fee of `amount_in`, rounded up like the program does.
+    pub fn trade_fee(&self, amount_in: u64) -> u64 {
+        let fee = (amount_in as u128 * self.trade_fee_rate as u128)
+            .div_ceil(CPMM_FEE_RATE_DENOMINATOR as u128);
+        fee.min(amount_in as u128) as u64
+    }
+
+    /// Constant product output of `amount_in` after the trade fee, rounded
+    /// down like the program does. Creator fees are not deducted.
+    pub fn amount_out(&self, reserve_in: u64, reserve_out: u64, amount_in: u64) -> u64 {
+        let amount_in = (amount_in - self.trade_fee(amount_in)) as u128;
+        let denominator = reserve_in as u128 + amount_in;
+        if denominator == 0 {
+            return 0;
+        }
+        (reserve_out as u128 * amount_in / denominator) as u64
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::CPMM;
+
+    // Mainnet CPMM SOL/USDC pool under config index 0 and its accounts,
+    // checked against their seeds below. The vault balances and the fees
+    // collected into them are made up.
+    const POOL: &str = "7JuwJuNU88gurFnyWeiyGKbFmExMWcmRZntn9imEzdny";
+    const AMM_CONFIG: &str = "D4FPEruKEHrG5TenZ2mpDGEfu1iUvTiqBxvpU8HLBvC2";
+    const TOKEN_0_VAULT: &str = "7VLUXrnSSDo9BfCa4NWaQs68g7ddDY1sdXBKW6Xswj9Y";
+    const TOKEN_1_VAULT: &str = "3rzbbW5Q8MA7sCaowf28hNgACNPecdS2zceWy7Ptzua9";
+    const LP_MINT: &str = "Ff68JGeEiUEF859B2xEaPX82vuFeuwxqoxGNHTZPAuKR";
+    const OBSERVATION: &str = "4MYrPgjgFceyhtwhG1ZX8UVb4wn1aQB5wzMimtFqg7U8";
+    const WSOL: &str = "So11111111111111111111111111111111111111112";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn key(address: &str) -> Pubkey {
+        Pubkey::from_str_const(address)
+    }
+
+    /// Pool state account laid out field by field like the program's
+    /// `PoolState`, 637 bytes.
+    fn pool_account_data() -> Vec<u8> {
+        let mut data = vec![247, 237, 227, 245, 215, 195, 222, 70];
+        for address in [
+            AMM_CONFIG,
+            // Pool creator, not decoded.
+            "11111111111111111111111111111111",
+            TOKEN_0_VAULT,
+            TOKEN_1_VAULT,
+            LP_MINT,
+            WSOL,
+            USDC,
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            OBSERVATION,
+        ] {
+            data.extend_from_slice(&key(address).to_bytes());
+        }
+        // Authority bump, status, LP mint, mint 0 and mint 1 decimals.
+        data.extend_from_slice(&[253, 0, 9, 9, 6]);
+        for value in [
+            // LP supply.
+            12_247_448_713_915,
+            // Protocol fees of token 0 and token 1.
+            1_000,
+            200,
+            // Fund fees of token 0 and token 1.
+            500,
+            100,
+            // Open time.
+            1_716_000_000,
+            // Recent epoch.
+            700,
+        ] {
+            data.extend_from_slice(&u64::to_le_bytes(value));
+        }
+        // Creator fee mode and switch, padding.
+        data.extend_from_slice(&[0; 8]);
+        // Creator fees of token 0 and token 1, padding.
+        data.resize(data.len() + 16 + 28 * 8, 0);
+        data
+    }
+
+    /// Config account laid out like the program's `AmmConfig`, index 0.
+    fn config_account_data() -> Vec<u8> {
+        let mut data = vec![218, 244, 33, 104, 203, 203, 43, 111];
+        // Bump, pool creation enabled, index.
+        data.extend_from_slice(&[255, 0]);
+        data.extend_from_slice(&0u16.to_le_bytes());
+        // Trade, protocol and fund fee rates, pool creation fee.
+        for value in [2_500u64, 120_000, 40_000, 150_000_000] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // Protocol and fund owners, creator fee rate, padding.
+        data.resize(data.len() + 2 * 32 + 8 + 15 * 8, 0);
+        data
+    }
+
+    #[test]
+    fn fixture_keys_match_pool_seeds() {
+        let program_id = solana_pubkey::Pubkey::from_str_const(CPMM);
+        let pda = |seeds: &[&[u8]]| {
+            let (address, _) = solana_pubkey::Pubkey::find_program_address(seeds, &program_id);
+            Pubkey::new_from_array(address.to_bytes())
+        };
+        let pool = key(POOL).to_bytes();
+
+        assert_eq!(pda(&[b"amm_config", &0u16.to_be_bytes()]), key(AMM_CONFIG));
+        assert_eq!(
+            pda(&[
+                b"pool",
+                &key(AMM_CONFIG).to_bytes(),
+                &key(WSOL).to_bytes(),
+                &key(USDC).to_bytes()
+            ]),
+            key(POOL)
+        );
+        assert_eq!(
+            pda(&[b"pool_vault", &pool, &key(WSOL).to_bytes()]),
+            key(TOKEN_0_VAULT)
+        );
+        assert_eq!(
+            pda(&[b"pool_vault", &pool, &key(USDC).to_bytes()]),
+            key(TOKEN_1_VAULT)
+        );
+        assert_eq!(pda(&[b"pool_lp_mint", &pool]), key(LP_MINT));
+        assert_eq!(pda(&[b"observation", &pool]), key(OBSERVATION));
+    }
+
+    #[test]
+    fn decodes_mainnet_sol_usdc_pool_and_config() {
+        let data = pool_account_data();
+        assert_eq!(data.len(), POOL_STATE_LEN);
+        let pool = CpmmPoolState::try_from_account_data(&data).unwrap();
+        assert_eq!(
+            pool,
+            CpmmPoolState {
+                amm_config: key(AMM_CONFIG),
+                token_0_vault: key(TOKEN_0_VAULT),
+                token_1_vault: key(TOKEN_1_VAULT),
+                token_0_mint: key(WSOL),
+                token_1_mint: key(USDC),
+                status: 0,
+                mint_0_decimals: 9,
+                mint_1_decimals: 6,
+                fees_token_0: 1_500,
+                fees_token_1: 300,
+                open_time: 1_716_000_000,
+            }
+        );
+        assert!(pool.is_swap_enabled(1_716_000_000));
+        assert!(!pool.is_swap_enabled(1_715_999_999));
+
+        let config = CpmmAmmConfig::try_from_account_data(&config_account_data()).unwrap();
+        assert_eq!(
+            config,
+            CpmmAmmConfig {
+                trade_fee_rate: 2_500,
+                protocol_fee_rate: 120_000,
+                fund_fee_rate: 40_000,
+            }
+        );
+
+        // 1_000 SOL and 150_000 USDC in the vaults, 1 SOL in.
+        let (reserve_0, reserve_1) = pool.reserves(1_000_000_000_000, 150_000_000_000);
+        assert_eq!((reserve_0, reserve_1), (999_999_998_500, 149_999_999_700));
+        assert_eq!(config.trade_fee(1_000_000_000), 2_500_000);
+        assert_eq!(
+            config.amount_out(reserve_0, reserve_1, 1_000_000_000),
+            149_475_897
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Typed errors surfaced by swap execution.
+//!
+//! Client methods return `anyhow::Result`; these errors are wrapped in it and
+//! can be recovered with `err.downcast_ref::<SwapError>()` (or
+//! `ValidationError`, `ApiError`).
+
+use crate::config::ClusterConfig;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwapError {
+    /// The swap confirmed, but the output received by the owner is below the
+    /// requested minimum (e.g. a transfer fee was charged on the output mint).
+    FillBelowMinimum {
+        signature: Signature,
+        min_amount_out: u64,
+        /// Output actually received, taken from the transaction token balances.
+        actual_amount_out: u64,
+    },
+    /// The re-quote taken right before sending moved against the original
+    /// quote by more than the configured tolerance.
+    PriceMoved {
+        quoted_amount: u64,
+        requoted_amount: u64,
+        moved_bps: u64,
+        tolerance_bps: u64,
+    },
+    /// The CLMM limit price is not positive or lies on the wrong side of the
+    /// current pool price for the swap direction. Prices are token 1 per
+    /// token 0, decimals applied.
+    InvalidLimitPrice {
+        limit_price: f64,
+        current_price: f64,
+        zero_for_one: bool,
+    },
+    /// The priority fee at the configured compute unit price is above the
+    /// fee budget, so the transaction was not sent.
+    FeeBudgetExceeded {
+        fee_lamports: u64,
+        budget_lamports: u64,
+    },
+    /// The client was built without a signer, see
+    /// [`WatchOnly`](crate::signer::WatchOnly), and cannot send transactions.
+    NoSignerConfigured,
+    /// The CLMM swap walk ran out of loaded tick arrays (or of its step cap)
+    /// before the amount was consumed. Fetch `additional_tick_arrays` more
+    /// arrays in swap direction and quote again; the count is estimated from
+    /// the part of the amount the loaded arrays could fill.
+    InsufficientLiquidityLoaded {
+        tick_arrays_loaded: usize,
+        additional_tick_arrays: usize,
+        amount_remaining: u64,
+    },
+    /// The blockhash of the transaction expired before it was seen on
+    /// chain, so it can no longer land. Sending it again needs a new
+    /// blockhash, see `max_blockhash_refreshes` of
+    /// [`SendOptions`](crate::execution::SendOptions).
+    BlockhashExpired { signature: Signature },
+    /// The price impact of the quote is above the configured limit, so the
+    /// swap was not built or sent. Both in bps, the impact rounded up.
+    PriceImpactExceeded {
+        price_impact_bps: u64,
+        max_price_impact_bps: u64,
+    },
+    /// The pool does not trade before its open time, a unix timestamp, so
+    /// the swap was not sent.
+    PoolNotOpen {
+        pool_id: Pubkey,
+        open_time: u64,
+        seconds_until_open: u64,
+    },
+    /// The status of the pool does not allow swaps, e.g. an AMM v4 pool that
+    /// only allows withdrawals or a CLMM pool with swaps paused. `status` is
+    /// the raw status field of the pool.
+    SwapDisabled { pool_id: Pubkey, status: u64 },
+    /// The simulation of an arbitrage cycle returns no more than its input,
+    /// so it was not sent.
+    ArbitrageLoss { amount_in: u64, amount_out: u64 },
+    /// The owner cannot pay for the swap, so it was not sent: `needed` is
+    /// above `available`, both in the units of `reason`.
+    InsufficientFunds {
+        needed: u64,
+        available: u64,
+        reason: InsufficientFundsReason,
+    },
+    /// The token account the swap pays into neither exists nor is created by
+    /// the swap transaction, so it was not sent.
+    OutputAccountMissing { account: Pubkey },
+    /// A sent transaction failed, on chain or in preflight simulation.
+    /// `program_id` is the program of the failing instruction, when known.
+    TransactionFailed {
+        signature: Signature,
+        error: TransactionError,
+        program_id: Option<Pubkey>,
+    },
+}
+
+impl fmt::Display for SwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SwapError::FillBelowMinimum {
+                signature,
+                min_amount_out,
+                actual_amount_out,
+            } => write!(
+                f,
+                "swap {} received {} but at least {} was expected",
+                signature, actual_amount_out, min_amount_out
+            ),
+            SwapError::PriceMoved {
+                quoted_amount,
+                requoted_amount,
+                moved_bps,
+                tolerance_bps,
+            } => write!(
+                f,
+                "price moved {} bps since the quote ({} -> {}), tolerance is {} bps",
+                moved_bps, quoted_amount, requoted_amount, tolerance_bps
+            ),
+            SwapError::InvalidLimitPrice {
+                limit_price,
+                current_price,
+                zero_for_one,
+            } => {
+                let (side, direction) = if *zero_for_one {
+                    ("below", "token 0 for token 1")
+                } else {
+                    ("above", "token 1 for token 0")
+                };
+                write!(
+                    f,
+                    "limit price {} must be {} the current price {} when swapping {}",
+                    limit_price, side, current_price, direction
+                )
+            }
+            SwapError::FeeBudgetExceeded {
+                fee_lamports,
+                budget_lamports,
+            } => write!(
+                f,
+                "priority fee of {} lamports is above the budget of {} lamports",
+                fee_lamports, budget_lamports
+            ),
+            SwapError::NoSignerConfigured => {
+                write!(f, "client is watch-only, no signer is configured")
+            }
+            SwapError::InsufficientLiquidityLoaded {
+                tick_arrays_loaded,
+                additional_tick_arrays,
+                amount_remaining,
+            } => write!(
+                f,
+                "{} loaded tick arrays leave {} unfilled, load about {} more",
+                tick_arrays_loaded, amount_remaining, additional_tick_arrays
+            ),
+            SwapError::BlockhashExpired { signature } => {
+                write!(
+                    f,
+                    "blockhash of transaction {} expired before it landed",
+                    signature
+                )
+            }
+            SwapError::PriceImpactExceeded {
+                price_impact_bps,
+                max_price_impact_bps,
+            } => write!(
+                f,
+                "price impact of {} bps is above the limit of {} bps",
+                price_impact_bps, max_price_impact_bps
+            ),
+            SwapError::PoolNotOpen {
+                pool_id,
+                open_time,
+                seconds_until_open,
+            } => write!(
+                f,
+                "pool {} opens at {}, in {} seconds",
+                pool_id, open_time, seconds_until_open
+            ),
+            SwapError::SwapDisabled { pool_id, status } => {
+                write!(
+                    f,
+                    "pool {} does not allow swaps, status {}",
+                    pool_id, status
+                )
+            }
+            SwapError::ArbitrageLoss {
+                amount_in,
+                amount_out,
+            } => write!(
+                f,
+                "arbitrage cycle returns {} for {}, no profit",
+                amount_out, amount_in
+            ),
+            SwapError::InsufficientFunds {
+                needed,
+                available,
+                reason,
+            } => write!(
+                f,
+                "insufficient funds: {} needed {}, {} available",
+                needed, reason, available
+            ),
+            SwapError::OutputAccountMissing { account } => write!(
+                f,
+                "output token account {} does not exist and is not created by the swap",
+                account
+            ),
+            SwapError::TransactionFailed {
+                signature, error, ..
+            } => write!(f, "transaction {} failed: {}", signature, error),
+        }
+    }
+}
+
+impl std::error::Error for SwapError {}
+
+/// What the owner lacks in `SwapError::InsufficientFunds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsufficientFundsReason {
+    /// Input of the swap, in the smallest units of `mint`, held by `account`
+    /// or wrapped into it by the swap transaction.
+    InputToken { mint: Pubkey, account: Pubkey },
+    /// Lamports of the owner, paying for the SOL wrapped by the swap
+    /// transaction, the rent of the token accounts it creates and of the
+    /// owner itself, and the transaction fees.
+    Lamports { wrapped: u64, rent: u64, fees: u64 },
+}
+
+impl fmt::Display for InsufficientFundsReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsufficientFundsReason::InputToken { mint, account } => {
+                write!(f, "of {} in token account {}", mint, account)
+            }
+            InsufficientFundsReason::Lamports {
+                wrapped,
+                rent,
+                fees,
+            } => write!(
+                f,
+                "lamports ({} wrapped, {} rent, {} fees)",
+                wrapped, rent, fees
+            ),
+        }
+    }
+}
+
+/// Invalid arguments of a swap or quote, rejected before any RPC request.
+/// See [`crate::validation`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The amount to swap or receive is zero.
+    ZeroAmount,
+    /// Input and output mints are the same.
+    SameMint { mint: String },
+    /// Slippage outside 0-100%, as a fraction (e.g. `0.005` for 0.5%).
+    InvalidSlippage { slippage: f64 },
+    /// `mint` is not one of the mints of `pool_id`.
+    MintNotInPool { pool_id: String, mint: String },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::ZeroAmount => write!(f, "amount must be greater than zero"),
+            ValidationError::SameMint { mint } => {
+                write!(f, "input and output mints are both {}", mint)
+            }
+            ValidationError::InvalidSlippage { slippage } => {
+                write!(f, "slippage {} is outside 0-100%", slippage)
+            }
+            ValidationError::MintNotInPool { pool_id, mint } => {
+                write!(f, "mint {} is not in pool {}", mint, pool_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Failed Raydium API request, see
+/// [`RaydiumApiClient`](crate::api::RaydiumApiClient).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// The API answered with a non-success HTTP status.
+    Status {
+        url: String,
+        status: u16,
+        body: String,
+    },
+    /// The API answered `success: false`, with its message if any.
+    Rejected {
+        url: String,
+        message: Option<String>,
+    },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Status { url, status, .. } => {
+                write!(f, "Raydium API returned {} for {}", status, url)
+            }
+            ApiError::Rejected { url, message } => write!(
+                f,
+                "Raydium API rejected {}: {}",
+                url,
+                message.as_deref().unwrap_or("no message")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// AMM v4 `ExceededSlippage` error code.
+const AMM_SLIPPAGE_ERROR_CODE: u32 = 30;
+
+/// CLMM `PriceSlippageCheck`, `TooLittleOutputReceived` and
+/// `TooMuchInputPaid` error codes.
+const CLMM_SLIPPAGE_ERROR_CODES: std::ops::RangeInclusive<u32> = 6021..=6023;
+
+/// Whether a failed swap was rejected because of slippage: it failed with
+/// [`SwapError::TransactionFailed`] on an instruction of the AMM v4 or CLMM
+/// program of `cluster_config` returning one of their slippage error codes.
+pub fn is_slippage_error(err: &anyhow::Error, cluster_config: &ClusterConfig) -> bool {
+    let Some(SwapError::TransactionFailed {
+        error: TransactionError::InstructionError(_, InstructionError::Custom(code)),
+        program_id: Some(program_id),
+        ..
+    }) = err.downcast_ref::<SwapError>()
+    else {
+        return false;
+    };
+    (*program_id == cluster_config.amm_v4 && *code == AMM_SLIPPAGE_ERROR_CODE)
+        || (*program_id == cluster_config.clmm && CLMM_SLIPPAGE_ERROR_CODES.contains(code))
+}
@@ -0,0 +1,136 @@
+//! Reads that observe the effects of a confirmed transaction.
+//!
+//! A node behind the one that confirmed a swap still serves the balances and
+//! pool state from before it. [`ReadAfterWrite`] reads through any RPC client
+//! and retries until the node's context slot reaches the slot the transaction
+//! landed in.
+
+use crate::common::{TokenAccountState, unpack_token};
+use anyhow::anyhow;
+use solana_account::Account;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_READ_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Slot `signature` was processed in, as reported by `rpc_client`.
+pub async fn confirmation_slot(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+) -> anyhow::Result<u64> {
+    rpc_client
+        .get_signature_statuses(&[*signature])
+        .await?
+        .value
+        .into_iter()
+        .next()
+        .flatten()
+        .map(|status| status.slot)
+        .ok_or(anyhow!("Status of transaction {} was not found", signature))
+}
+
+/// Reads served from slot `min_context_slot` or later.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadAfterWrite {
+    pub min_context_slot: u64,
+    pub commitment: CommitmentConfig,
+    /// Give up waiting for the node to catch up after this long.
+    pub timeout: Duration,
+    /// Interval between reads while the node is behind.
+    pub poll_interval: Duration,
+}
+
+impl ReadAfterWrite {
+    pub fn new(min_context_slot: u64) -> Self {
+        Self {
+            min_context_slot,
+            commitment: CommitmentConfig::confirmed(),
+            timeout: DEFAULT_READ_TIMEOUT,
+            poll_interval: DEFAULT_READ_POLL_INTERVAL,
+        }
+    }
+
+    /// Reads pinned to the slot of `signature`, looked up through
+    /// `rpc_client`, usually the one the transaction was sent to.
+    pub async fn after_signature(
+        rpc_client: &RpcClient,
+        signature: &Signature,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::new(confirmation_slot(rpc_client, signature).await?))
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Fetch `addresses` once `rpc_client` has reached `min_context_slot`.
+    pub async fn get_multiple_accounts(
+        &self,
+        rpc_client: &RpcClient,
+        addresses: &[Pubkey],
+    ) -> anyhow::Result<Vec<Option<Account>>> {
+        let started = Instant::now();
+        loop {
+            let response = rpc_client
+                .get_multiple_accounts_with_commitment(addresses, self.commitment)
+                .await?;
+            if response.context.slot >= self.min_context_slot {
+                return Ok(response.value);
+            }
+            debug!(
+                "RPC at slot {}, waiting for slot {}",
+                response.context.slot, self.min_context_slot
+            );
+            if started.elapsed() > self.timeout {
+                return Err(anyhow!(
+                    "RPC did not reach slot {} within {:?}, still at {}",
+                    self.min_context_slot,
+                    self.timeout,
+                    response.context.slot
+                ));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Fetch `address` once `rpc_client` has reached `min_context_slot`.
+    pub async fn get_account(
+        &self,
+        rpc_client: &RpcClient,
+        address: &Pubkey,
+    ) -> anyhow::Result<Option<Account>> {
+        Ok(self
+            .get_multiple_accounts(rpc_client, &[*address])
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+
+    /// Balance of an SPL Token or Token-2022 account once `rpc_client` has
+    /// reached `min_context_slot`, zero when the account does not exist.
+    pub async fn get_token_balance(
+        &self,
+        rpc_client: &RpcClient,
+        token_account: &Pubkey,
+    ) -> anyhow::Result<u64> {
+        let Some(account) = self.get_account(rpc_client, token_account).await? else {
+            return Ok(0);
+        };
+        Ok(match unpack_token(&account.owner, &account.data)? {
+            TokenAccountState::SplToken(state) => state.amount,
+            TokenAccountState::SplToken2022(state) => state.base.amount,
+        })
+    }
+}
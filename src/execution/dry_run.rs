@@ -0,0 +1,72 @@
+//! Dry runs: transactions of an
+//! [`AmmSwapClient`](crate::amm::client::AmmSwapClient) simulated instead of
+//! sent.
+//!
+//! In [`ExecutionMode::DryRun`] the client builds and signs every transaction
+//! as it would live, then simulates it. Sending methods return the signature
+//! the transaction would have had, or an error when the simulation fails, and
+//! record the simulation into the client's [`DryRunLog`].
+
+use crate::execution::SwapSimulation;
+use solana_sdk::signature::Signature;
+use std::sync::{Arc, Mutex};
+
+/// Whether the client sends its transactions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    #[default]
+    Live,
+    /// Simulate transactions instead of sending them.
+    DryRun,
+}
+
+/// A transaction simulated in dry run mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DryRunRecord {
+    /// Signature the transaction would have had, never sent.
+    pub signature: Signature,
+    /// Expected output, compute units and logs.
+    pub simulation: SwapSimulation,
+}
+
+/// Shared, cloneable list of dry run transactions.
+#[derive(Clone, Debug, Default)]
+pub struct DryRunLog {
+    records: Arc<Mutex<Vec<DryRunRecord>>>,
+}
+
+impl DryRunLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transactions simulated so far, oldest first.
+    pub fn records(&self) -> Vec<DryRunRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Remove and return the simulated transactions, e.g. after each swap.
+    pub fn take(&self) -> Vec<DryRunRecord> {
+        std::mem::take(&mut *self.records.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Simulation of the transaction signed `signature`, if it was dry run.
+    pub fn get(&self, signature: &Signature) -> Option<DryRunRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|record| record.signature == *signature)
+            .cloned()
+    }
+
+    pub(crate) fn record(&self, record: DryRunRecord) {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(record);
+    }
+}
@@ -0,0 +1,193 @@
+//! Batches of swaps on different pools, sent concurrently.
+//!
+//! An [`ExecutionPool`] takes a batch of [`BatchSwap`]s, each selling an
+//! amount on its own AMM v4 or CLMM pool. Chain data every swap needs, the
+//! blockhash of the transactions and the epoch of Token-2022 transfer fees,
+//! is fetched once per batch as a [`BatchContext`]. The swaps are then quoted,
+//! signed and sent at most `max_concurrency` at a time, and each gets its own
+//! result: a failing swap does not stop the others.
+
+use crate::amm::client::AmmSwapClient;
+use crate::interface::{AmmPool, ClmmSwapParams, PoolKeys, PoolLookupTable, PoolType};
+use crate::monitor::MonitoredPool;
+use crate::signer::SwapSigner;
+use anyhow::anyhow;
+use futures_util::stream::{self, StreamExt};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::sync::Arc;
+use tracing::info;
+
+/// Chain data fetched once and shared by the swaps of a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchContext {
+    /// Blockhash of every transaction of the batch. Transactions still
+    /// unconfirmed when it expires are signed again with a new one, see
+    /// [`SendOptions`](crate::execution::SendOptions).
+    pub recent_blockhash: Hash,
+    /// Epoch of the Token-2022 transfer fees in CLMM quotes.
+    pub epoch: u64,
+}
+
+impl BatchContext {
+    pub async fn fetch(client: &AmmSwapClient<impl SwapSigner>) -> anyhow::Result<Self> {
+        let (recent_blockhash, epoch) =
+            tokio::try_join!(client.get_latest_blockhash(), client.get_epoch())?;
+        Ok(Self {
+            recent_blockhash,
+            epoch,
+        })
+    }
+}
+
+/// Exact input swap of a batch, between the owner's associated token
+/// accounts. CLMM swaps spend the associated token account of the input mint,
+/// which must already hold the amount, wSOL included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSwap {
+    pub pool: MonitoredPool,
+    pub input_mint: Pubkey,
+    pub amount_in: u64,
+    pub slippage_bps: u64,
+}
+
+/// Sends batches of swaps with bounded parallelism, see the
+/// [module documentation](self).
+pub struct ExecutionPool<S = Keypair> {
+    client: Arc<AmmSwapClient<S>>,
+    max_concurrency: usize,
+}
+
+impl<S: SwapSigner> ExecutionPool<S> {
+    /// Pool sending at most `max_concurrency` swaps at a time, at least one.
+    pub fn new(client: Arc<AmmSwapClient<S>>, max_concurrency: usize) -> Self {
+        Self {
+            client,
+            max_concurrency: max_concurrency.max(1),
+        }
+    }
+
+    pub fn client(&self) -> &Arc<AmmSwapClient<S>> {
+        &self.client
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Quote, sign and send each of `swaps` with a minimum output from its
+    /// pool state at that moment, and wait for their confirmation.
+    ///
+    /// Results are in the order of `swaps`. Fails as a whole only when the
+    /// [`BatchContext`] cannot be fetched.
+    pub async fn execute(
+        &self,
+        swaps: &[BatchSwap],
+    ) -> anyhow::Result<Vec<anyhow::Result<Signature>>> {
+        if swaps.is_empty() {
+            return Ok(Vec::new());
+        }
+        let context = BatchContext::fetch(&self.client).await?;
+        info!(
+            "Executing {} swaps, {} at a time",
+            swaps.len(),
+            self.max_concurrency
+        );
+        Ok(stream::iter(swaps)
+            .map(|swap| {
+                swap_exact_in(
+                    &self.client,
+                    &swap.pool,
+                    &swap.input_mint,
+                    swap.amount_in,
+                    swap.slippage_bps,
+                    Some(&context),
+                )
+            })
+            .buffered(self.max_concurrency)
+            .collect()
+            .await)
+    }
+}
+
+/// Sell `amount_in` of `input_mint` on `pool`, between the owner's associated
+/// token accounts, with a minimum output quoted from the current pool state.
+/// Without `context`, the blockhash and epoch are fetched for this swap.
+/// Both pool types are sent through the lookup table the API lists for the
+/// pool, if any.
+pub(crate) async fn swap_exact_in(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool: &MonitoredPool,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u64,
+    context: Option<&BatchContext>,
+) -> anyhow::Result<Signature> {
+    match pool.pool_type {
+        PoolType::Standard => {
+            let pool_keys: PoolKeys<AmmPool> = client.fetch_pools_keys_by_id(&pool.id).await?;
+            let pool_keys = pool_keys
+                .data
+                .into_iter()
+                .next()
+                .ok_or(anyhow!("No keys returned for pool {}", pool.id))?;
+            let mut swap = client
+                .swap(&pool_keys)
+                .input_mint(*input_mint)
+                .amount(amount_in)
+                .slippage_bps(slippage_bps);
+            if let Some(context) = context {
+                swap = swap.recent_blockhash(context.recent_blockhash);
+            }
+            swap.send().await
+        }
+        PoolType::Concentrated => {
+            let token_program = client.get_mint_token_program(input_mint).await?;
+            let user_input_token = get_associated_token_address_with_program_id(
+                &client.owner_pubkey(),
+                input_mint,
+                &token_program,
+            );
+            let params = ClmmSwapParams {
+                pool_id: solana_pubkey::Pubkey::from(pool.id.to_bytes()),
+                user_input_token: solana_pubkey::Pubkey::from(user_input_token.to_bytes()),
+                user_output_token: None,
+                amount_specified: amount_in,
+                limit_price: None,
+                base_out: false,
+                slippage_bps,
+                max_price_impact_bps: None,
+            };
+            let pool_keys: PoolKeys<PoolLookupTable> =
+                client.fetch_pools_keys_by_id(&pool.id).await?;
+            let lookup_tables = pool_keys
+                .data
+                .into_iter()
+                .next()
+                .ok_or(anyhow!("No keys returned for pool {}", pool.id))?
+                .lookup_table_account
+                .iter()
+                .map(|table| table.parse())
+                .collect::<Result<Vec<Pubkey>, _>>()?;
+            let (swap_change, tick_array_bitmap_extension) = match context {
+                Some(context) => {
+                    client
+                        .calculate_swap_change_clmm_at_epoch(params, context.epoch)
+                        .await?
+                }
+                None => client.calculate_swap_change_clmm(params).await?,
+            };
+            client
+                .send_swap_clmm(
+                    None,
+                    swap_change,
+                    tick_array_bitmap_extension,
+                    &lookup_tables,
+                    context.map(|context| context.recent_blockhash),
+                )
+                .await
+        }
+    }
+}
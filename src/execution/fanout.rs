@@ -0,0 +1,189 @@
+//! Broadcast a signed transaction through several senders at once.
+//!
+//! The same signed transaction is submitted to every configured channel
+//! (regular RPCs, staked/priority RPCs and Jito). Because the signature is
+//! identical everywhere, the transaction lands at most once, and the caller
+//! gets control back as soon as any of the confirmation RPCs sees it.
+
+use crate::jito::JitoClient;
+use anyhow::anyhow;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// A channel a transaction can be submitted through.
+#[derive(Clone)]
+pub enum SendChannel {
+    /// Standard RPC node.
+    Rpc(Arc<RpcClient>),
+    /// Staked connection / priority sender exposing the RPC `sendTransaction` API.
+    Priority(Arc<RpcClient>),
+    /// Jito block engine `sendTransaction` endpoint.
+    Jito(Arc<JitoClient>),
+}
+
+impl SendChannel {
+    fn name(&self) -> String {
+        match self {
+            SendChannel::Rpc(rpc) => format!("rpc({})", rpc.url()),
+            SendChannel::Priority(rpc) => format!("priority({})", rpc.url()),
+            SendChannel::Jito(jito) => format!("jito({})", jito.block_engine_url()),
+        }
+    }
+
+    async fn send(&self, transaction: &Transaction) -> anyhow::Result<Signature> {
+        match self {
+            SendChannel::Rpc(rpc) | SendChannel::Priority(rpc) => Ok(rpc
+                .send_transaction_with_config(
+                    transaction,
+                    RpcSendTransactionConfig {
+                        skip_preflight: true,
+                        max_retries: Some(0),
+                        ..RpcSendTransactionConfig::default()
+                    },
+                )
+                .await?),
+            SendChannel::Jito(jito) => jito.send_transaction(transaction).await,
+        }
+    }
+}
+
+/// Outcome of a fan-out broadcast.
+#[derive(Debug, Clone)]
+pub struct FanoutResult {
+    pub signature: Signature,
+    /// Channels that accepted the transaction before it was confirmed.
+    pub accepted_by: Vec<String>,
+    /// Channels that rejected the transaction, with the error message.
+    pub rejected_by: Vec<(String, String)>,
+    /// Time between the first submission and the confirmation.
+    pub elapsed: Duration,
+}
+
+/// Submits a transaction to every channel simultaneously.
+pub struct FanoutSender {
+    channels: Vec<SendChannel>,
+    confirm_clients: Vec<Arc<RpcClient>>,
+    commitment: CommitmentConfig,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl FanoutSender {
+    /// `confirm_clients` are polled for the signature status; every regular
+    /// RPC channel is also a good candidate for confirmation.
+    pub fn new(channels: Vec<SendChannel>, confirm_clients: Vec<Arc<RpcClient>>) -> Self {
+        Self {
+            channels,
+            confirm_clients,
+            commitment: CommitmentConfig::confirmed(),
+            timeout: DEFAULT_CONFIRM_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Broadcast a signed transaction and wait until any confirmation client
+    /// reports it at the configured commitment.
+    pub async fn send_and_confirm(&self, transaction: &Transaction) -> anyhow::Result<FanoutResult> {
+        if self.channels.is_empty() {
+            return Err(anyhow!("FanoutSender has no channels configured"));
+        }
+        if self.confirm_clients.is_empty() {
+            return Err(anyhow!("FanoutSender has no confirmation clients configured"));
+        }
+        let signature = *transaction
+            .signatures
+            .first()
+            .ok_or(anyhow!("transaction is not signed"))?;
+        let started = Instant::now();
+
+        let transaction = Arc::new(transaction.clone());
+        let mut sends = JoinSet::new();
+        for channel in self.channels.iter().cloned() {
+            let transaction = Arc::clone(&transaction);
+            sends.spawn(async move {
+                let result = channel.send(&transaction).await;
+                (channel.name(), result)
+            });
+        }
+
+        let mut accepted_by = Vec::new();
+        let mut rejected_by = Vec::new();
+        loop {
+            while let Some(joined) = sends.try_join_next() {
+                match joined {
+                    Ok((name, Ok(_))) => {
+                        debug!("{name} accepted {signature}");
+                        accepted_by.push(name);
+                    }
+                    Ok((name, Err(e))) => {
+                        warn!("{name} rejected {signature}: {e:?}");
+                        rejected_by.push((name, e.to_string()));
+                    }
+                    Err(e) => warn!("Send task failed: {e:?}"),
+                }
+            }
+            if rejected_by.len() == self.channels.len() {
+                return Err(anyhow!(
+                    "all channels rejected {}: {:?}",
+                    signature,
+                    rejected_by
+                ));
+            }
+
+            for rpc in &self.confirm_clients {
+                let statuses = match rpc.get_signature_statuses(&[signature]).await {
+                    Ok(statuses) => statuses.value,
+                    Err(e) => {
+                        debug!("Status poll on {} failed: {e:?}", rpc.url());
+                        continue;
+                    }
+                };
+                if let Some(Some(status)) = statuses.first() {
+                    if let Some(err) = &status.err {
+                        return Err(anyhow!("transaction {} failed: {:?}", signature, err));
+                    }
+                    if status.satisfies_commitment(self.commitment) {
+                        sends.abort_all();
+                        info!("Fan-out confirmed {signature} via {}", rpc.url());
+                        return Ok(FanoutResult {
+                            signature,
+                            accepted_by,
+                            rejected_by,
+                            elapsed: started.elapsed(),
+                        });
+                    }
+                }
+            }
+
+            if started.elapsed() > self.timeout {
+                sends.abort_all();
+                return Err(anyhow!(
+                    "transaction {} was not confirmed within {:?}",
+                    signature,
+                    self.timeout
+                ));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Re-quote right before sending to catch price moves since the original quote,
+//! and refuse quotes with too much price impact.
+
+use crate::amm::client::{AmmSwapClient, ComputeAmountOutResult};
+use crate::clmm::ClmmSwapChangeResult;
+use crate::error::SwapError;
+use crate::interface::{AmmPool, ClmmPool, ClmmSwapParams};
+use crate::signer::SwapSigner;
+use solana_address::Address;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tracing::{info, warn};
+
+/// What to do when the price moved more than the tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PriceMoveAction {
+    /// Do not send, return `SwapError::PriceMoved`.
+    #[default]
+    Abort,
+    /// Send anyway, with the tighter of the original and re-quoted limits.
+    TightenLimit,
+}
+
+/// Execution option comparing a fresh quote with the original one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequoteGuard {
+    /// Largest accepted adverse move, in bps of the original quote.
+    pub tolerance_bps: u64,
+    pub action: PriceMoveAction,
+    /// Largest accepted price impact of the re-quote, in bps. Unlimited
+    /// unless set.
+    pub max_price_impact_bps: Option<u64>,
+}
+
+/// Fail with [`SwapError::PriceImpactExceeded`] when `price_impact`, in
+/// percent as in the quotes, is above `max_price_impact_bps` or is not
+/// finite.
+pub fn check_price_impact(price_impact: f64, max_price_impact_bps: u64) -> Result<(), SwapError> {
+    let price_impact_bps = if price_impact.is_finite() {
+        (price_impact * 100.0).max(0.0).ceil() as u64
+    } else {
+        u64::MAX
+    };
+    if price_impact_bps > max_price_impact_bps {
+        return Err(SwapError::PriceImpactExceeded {
+            price_impact_bps,
+            max_price_impact_bps,
+        });
+    }
+    Ok(())
+}
+
+impl RequoteGuard {
+    pub fn new(tolerance_bps: u64) -> Self {
+        Self {
+            tolerance_bps,
+            action: PriceMoveAction::default(),
+            max_price_impact_bps: None,
+        }
+    }
+
+    pub fn with_action(mut self, action: PriceMoveAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn with_max_price_impact_bps(mut self, max_price_impact_bps: u64) -> Self {
+        self.max_price_impact_bps = Some(max_price_impact_bps);
+        self
+    }
+
+    /// Compare an original and a re-quoted amount and return the limit to
+    /// send with.
+    ///
+    /// - `higher_is_better`: `true` for outputs (min amount out), `false` for
+    ///   inputs (max amount in).
+    /// - `quoted_limit`, `requoted_limit`: slippage-adjusted limits of both quotes.
+    pub fn check(
+        &self,
+        quoted_amount: u64,
+        requoted_amount: u64,
+        quoted_limit: u64,
+        requoted_limit: u64,
+        higher_is_better: bool,
+    ) -> Result<u64, SwapError> {
+        let adverse = if higher_is_better {
+            quoted_amount.saturating_sub(requoted_amount)
+        } else {
+            requoted_amount.saturating_sub(quoted_amount)
+        };
+        let moved_bps = (adverse as u128 * 10_000 / quoted_amount.max(1) as u128) as u64;
+        let tighter_limit = if higher_is_better {
+            quoted_limit.max(requoted_limit)
+        } else {
+            quoted_limit.min(requoted_limit)
+        };
+        if moved_bps <= self.tolerance_bps {
+            // A favourable move is kept as extra protection.
+            return Ok(tighter_limit);
+        }
+        match self.action {
+            PriceMoveAction::Abort => Err(SwapError::PriceMoved {
+                quoted_amount,
+                requoted_amount,
+                moved_bps,
+                tolerance_bps: self.tolerance_bps,
+            }),
+            PriceMoveAction::TightenLimit => {
+                warn!(
+                    "Price moved {moved_bps} bps since the quote, sending with tightened limit {tighter_limit}"
+                );
+                Ok(tighter_limit)
+            }
+        }
+    }
+}
+
+/// Swap on an AMM v4 pool after re-quoting and checking the move against
+/// `quote`, which must have been computed with the same `slippage`.
+#[allow(clippy::too_many_arguments)]
+pub async fn swap_amm_guarded(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_keys: &AmmPool,
+    pool_info: &ClmmPool,
+    mint_a: &Address,
+    mint_b: &Address,
+    amount_in: u64,
+    quote: &ComputeAmountOutResult,
+    slippage: f64,
+    guard: &RequoteGuard,
+) -> anyhow::Result<Signature> {
+    let pool_id: Pubkey = pool_keys.id.parse()?;
+    let rpc_pool_info = client.get_rpc_pool_info(&pool_id).await?;
+    let requote = client.compute_amount_out_for_input(
+        &rpc_pool_info,
+        pool_info,
+        &mint_a.to_string(),
+        amount_in,
+        slippage,
+    )?;
+    if let Some(max_price_impact_bps) = guard.max_price_impact_bps {
+        // The impact of `requote` is taken after slippage; check the one
+        // before slippage, like `SwapBuilder` does.
+        let price_impact = rpc_pool_info
+            .oriented(mint_a, &pool_keys.id)?
+            .price_impact(amount_in);
+        check_price_impact(price_impact, max_price_impact_bps)?;
+    }
+    let min_amount_out = guard.check(
+        quote.amount_out,
+        requote.amount_out,
+        quote.min_amount_out,
+        requote.min_amount_out,
+        true,
+    )?;
+    info!(
+        "Re-quoted {} -> {}, sending with min amount out {}",
+        quote.amount_out, requote.amount_out, min_amount_out
+    );
+    client
+        .swap_amm(pool_keys, mint_a, mint_b, amount_in, min_amount_out)
+        .await
+}
+
+/// Swap on a CLMM pool after recomputing the swap change for `params` and
+/// checking its threshold against the original `swap_change`.
+pub async fn swap_clmm_guarded(
+    client: &AmmSwapClient<impl SwapSigner>,
+    params: ClmmSwapParams,
+    swap_change: ClmmSwapChangeResult,
+    guard: &RequoteGuard,
+) -> anyhow::Result<Signature> {
+    let user_output_token = params.user_output_token;
+    let (requote, bitmap_extension) = client.calculate_swap_change_clmm(params).await?;
+    if let Some(max_price_impact_bps) = guard.max_price_impact_bps {
+        check_price_impact(requote.quote.price_impact, max_price_impact_bps)?;
+    }
+    // Thresholds are min out for exact input swaps and max in otherwise.
+    let higher_is_better = swap_change.is_base_input;
+    let threshold = guard.check(
+        swap_change.other_amount_threshold,
+        requote.other_amount_threshold,
+        swap_change.other_amount_threshold,
+        requote.other_amount_threshold,
+        higher_is_better,
+    )?;
+    let requote = ClmmSwapChangeResult {
+        other_amount_threshold: threshold,
+        ..requote
+    };
+    client
+        .swap_clmm(user_output_token, requote, bitmap_extension)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn price_impact_above_the_cap_or_not_finite_is_refused() {
+        assert_eq!(check_price_impact(0.5, 50), Ok(()));
+        assert_eq!(check_price_impact(-1.0, 0), Ok(()));
+        assert_eq!(
+            check_price_impact(0.501, 50),
+            Err(SwapError::PriceImpactExceeded {
+                price_impact_bps: 51,
+                max_price_impact_bps: 50,
+            })
+        );
+        for price_impact in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(
+                check_price_impact(price_impact, 10_000),
+                Err(SwapError::PriceImpactExceeded {
+                    price_impact_bps: u64::MAX,
+                    max_price_impact_bps: 10_000,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn adverse_move_above_the_tolerance_aborts_or_tightens() {
+        let guard = RequoteGuard::new(50);
+        let moved = Err(SwapError::PriceMoved {
+            quoted_amount: 10_000,
+            requoted_amount: 9_900,
+            moved_bps: 100,
+            tolerance_bps: 50,
+        });
+        assert_eq!(guard.check(10_000, 9_900, 9_950, 9_851, true), moved);
+        let guard = guard.with_action(PriceMoveAction::TightenLimit);
+        assert_eq!(guard.check(10_000, 9_900, 9_950, 9_851, true), Ok(9_950));
+    }
+
+    #[test]
+    fn adverse_input_move_aborts_or_tightens() {
+        let guard = RequoteGuard::new(50);
+        let moved = Err(SwapError::PriceMoved {
+            quoted_amount: 10_000,
+            requoted_amount: 10_100,
+            moved_bps: 100,
+            tolerance_bps: 50,
+        });
+        assert_eq!(guard.check(10_000, 10_100, 10_050, 10_151, false), moved);
+        let guard = guard.with_action(PriceMoveAction::TightenLimit);
+        assert_eq!(
+            guard.check(10_000, 10_100, 10_050, 10_151, false),
+            Ok(10_050)
+        );
+    }
+
+    #[test]
+    fn favourable_move_keeps_the_tighter_limit() {
+        let guard = RequoteGuard::new(0);
+        assert_eq!(guard.check(10_000, 10_100, 9_950, 10_049, true), Ok(10_049));
+        assert_eq!(guard.check(10_000, 9_900, 10_050, 9_949, false), Ok(9_949));
+    }
+
+    #[test]
+    fn move_of_exactly_the_tolerance_is_accepted() {
+        let guard = RequoteGuard::new(50);
+        assert_eq!(guard.check(10_000, 9_950, 9_950, 9_900, true), Ok(9_950));
+        assert_eq!(
+            guard.check(10_000, 10_050, 10_050, 10_100, false),
+            Ok(10_050)
+        );
+        assert!(guard.check(10_000, 9_949, 9_950, 9_899, true).is_err());
+    }
+}
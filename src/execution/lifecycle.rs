@@ -0,0 +1,45 @@
+//! Events emitted while a swap moves from quote to confirmation.
+
+use solana_sdk::signature::Signature;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// A step of a swap execution. `attempt` starts at 1 and grows with retries.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwapLifecycleEvent {
+    /// A quote was computed for the attempt. For exact-output CLMM swaps
+    /// `amount_in` is the requested output and `min_amount_out` the maximum input.
+    /// `fully_filled` is `false` when a CLMM price limit stops the swap before
+    /// `amount_in` is consumed.
+    Quoted {
+        attempt: u32,
+        slippage_bps: u64,
+        amount_in: u64,
+        min_amount_out: u64,
+        fully_filled: bool,
+    },
+    /// The attempt confirmed on chain.
+    Confirmed { attempt: u32, signature: Signature },
+    /// The attempt was rejected because the slippage limit was exceeded.
+    SlippageExceeded {
+        attempt: u32,
+        slippage_bps: u64,
+        error: String,
+    },
+    /// The next attempt will use a higher slippage.
+    Retrying {
+        next_attempt: u32,
+        next_slippage_bps: u64,
+    },
+    /// The attempt failed and no retry will follow.
+    Failed { attempt: u32, error: String },
+}
+
+/// Receiving side is created by the caller with `tokio::sync::mpsc::unbounded_channel`.
+pub type SwapLifecycleSender = UnboundedSender<SwapLifecycleEvent>;
+
+pub(crate) fn emit(events: Option<&SwapLifecycleSender>, event: SwapLifecycleEvent) {
+    if let Some(events) = events {
+        // A dropped receiver only means nobody listens anymore.
+        let _ = events.send(event);
+    }
+}
@@ -0,0 +1,466 @@
+//! Limit orders emulated with swaps sent once the pool price crosses a
+//! trigger.
+//!
+//! A [`LimitOrderEngine`] holds pending [`LimitOrder`]s and checks them
+//! against the mid price of their pool, either polled with
+//! [`LimitOrderEngine::start`] or fed from a
+//! [`PriceMonitor`](crate::monitor::PriceMonitor) subscription with
+//! [`LimitOrderEngine::on_price`]. A triggered order is removed and its swap
+//! sent with a minimum output quoted at that moment; the outcome is published
+//! as a [`LimitOrderEvent`].
+//!
+//! Pending orders are handed to a [`LimitOrderStore`] on every change and
+//! read back from it by [`LimitOrderEngine::with_store`], so they survive a
+//! restart of the process.
+
+use crate::amm::client::AmmSwapClient;
+use crate::execution::swap_exact_in;
+use crate::interface::PoolType;
+use crate::monitor::{MonitorStream, MonitoredPool, PoolPriceSnapshot, fetch_pool_price};
+use crate::signer::SwapSigner;
+use crate::validation;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Default capacity of the event channel.
+const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Pool price at which an order is sent, in token B (mint 1) per token A
+/// (mint 0), decimals applied, like [`PoolPriceSnapshot::mid_price`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", content = "price", rename_all = "snake_case")]
+pub enum PriceTrigger {
+    /// Sent once the price rises to or above the price.
+    AtOrAbove(f64),
+    /// Sent once the price falls to or below the price.
+    AtOrBelow(f64),
+}
+
+impl PriceTrigger {
+    pub fn is_crossed(&self, price: f64) -> bool {
+        match *self {
+            PriceTrigger::AtOrAbove(trigger) => price >= trigger,
+            PriceTrigger::AtOrBelow(trigger) => price <= trigger,
+        }
+    }
+}
+
+/// Exact input swap of `amount_in` of `input_mint` on an AMM v4 or CLMM pool,
+/// sent when `trigger` is crossed. Addresses are base58 strings so the order
+/// can be stored as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LimitOrder {
+    /// Assigned by [`LimitOrderEngine::place`].
+    pub id: u64,
+    pub pool_id: String,
+    pub pool_type: PoolType,
+    pub input_mint: String,
+    pub amount_in: u64,
+    pub trigger: PriceTrigger,
+    /// Slippage tolerance of the quote taken when the order is triggered.
+    pub slippage_bps: u64,
+}
+
+impl LimitOrder {
+    pub fn new(
+        pool: MonitoredPool,
+        input_mint: Pubkey,
+        amount_in: u64,
+        trigger: PriceTrigger,
+        slippage_bps: u64,
+    ) -> Self {
+        Self {
+            id: 0,
+            pool_id: pool.id.to_string(),
+            pool_type: pool.pool_type,
+            input_mint: input_mint.to_string(),
+            amount_in,
+            trigger,
+            slippage_bps,
+        }
+    }
+
+    pub fn pool(&self) -> anyhow::Result<MonitoredPool> {
+        Ok(MonitoredPool {
+            id: Pubkey::from_str(&self.pool_id)?,
+            pool_type: self.pool_type,
+        })
+    }
+}
+
+/// Outcome of a triggered order, which is no longer pending either way.
+#[derive(Debug, Clone)]
+pub enum LimitOrderEvent {
+    Filled {
+        order: LimitOrder,
+        /// Pool price that triggered the order.
+        price: f64,
+        signature: Signature,
+    },
+    /// The swap could not be quoted or sent. Place the order again to retry.
+    Failed {
+        order: LimitOrder,
+        price: f64,
+        error: String,
+    },
+}
+
+/// Async stream of [`LimitOrderEvent`]s.
+pub type LimitOrderEventStream = MonitorStream<LimitOrderEvent>;
+
+/// Persistence hook of the pending orders of a [`LimitOrderEngine`].
+pub trait LimitOrderStore: Send + Sync {
+    /// Pending orders saved last, empty when nothing was saved yet.
+    fn load(&self) -> anyhow::Result<Vec<LimitOrder>>;
+
+    /// Replace the saved orders with `orders`.
+    fn save(&self, orders: &[LimitOrder]) -> anyhow::Result<()>;
+}
+
+/// [`LimitOrderStore`] keeping the orders as a JSON array in a file.
+#[derive(Debug, Clone)]
+pub struct JsonFileOrderStore {
+    path: PathBuf,
+}
+
+impl JsonFileOrderStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl LimitOrderStore for JsonFileOrderStore {
+    fn load(&self) -> anyhow::Result<Vec<LimitOrder>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = std::fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Writes a temporary file next to the store file and renames it over
+    /// the store file, so a crash mid-save leaves the previous orders intact.
+    fn save(&self, orders: &[LimitOrder]) -> anyhow::Result<()> {
+        let mut temp_name = self
+            .path
+            .file_name()
+            .ok_or(anyhow!(
+                "Order store path {} has no file name",
+                self.path.display()
+            ))?
+            .to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = self.path.with_file_name(temp_name);
+        let mut file = File::create(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(orders)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+struct Shared<S> {
+    client: Arc<AmmSwapClient<S>>,
+    orders: Mutex<Vec<LimitOrder>>,
+    next_id: Mutex<u64>,
+    store: Option<Arc<dyn LimitOrderStore>>,
+    sender: broadcast::Sender<LimitOrderEvent>,
+}
+
+impl<S: SwapSigner> Shared<S> {
+    fn persist(&self, orders: &[LimitOrder]) -> anyhow::Result<()> {
+        match &self.store {
+            Some(store) => store.save(orders),
+            None => Ok(()),
+        }
+    }
+
+    /// Remove the pending orders of the pool of `snapshot` its price
+    /// triggers. They are sent whether or not the store could be updated.
+    fn take_triggered(&self, snapshot: &PoolPriceSnapshot) -> Vec<LimitOrder> {
+        let pool_id = snapshot.pool_id.to_string();
+        let mut orders = self.orders.lock().unwrap_or_else(|e| e.into_inner());
+        let (triggered, pending): (Vec<_>, Vec<_>) = orders.drain(..).partition(|order| {
+            order.pool_id == pool_id && order.trigger.is_crossed(snapshot.mid_price)
+        });
+        *orders = pending;
+        if !triggered.is_empty()
+            && let Err(e) = self.persist(&orders)
+        {
+            warn!("Failed to save pending limit orders: {e:?}");
+        }
+        triggered
+    }
+
+    async fn on_price(&self, snapshot: &PoolPriceSnapshot) {
+        for order in self.take_triggered(snapshot) {
+            let price = snapshot.mid_price;
+            info!(
+                "Limit order {} triggered at price {price} on pool {}",
+                order.id, order.pool_id
+            );
+            let event = match execute_limit_order(&self.client, &order).await {
+                Ok(signature) => LimitOrderEvent::Filled {
+                    order,
+                    price,
+                    signature,
+                },
+                Err(e) => {
+                    warn!("Limit order {} failed: {e:?}", order.id);
+                    LimitOrderEvent::Failed {
+                        order,
+                        price,
+                        error: format!("{e:#}"),
+                    }
+                }
+            };
+            // No subscribers is not an error for the engine.
+            let _ = self.sender.send(event);
+        }
+    }
+
+    async fn poll_once(&self) -> anyhow::Result<()> {
+        let pools: HashSet<MonitoredPool> = self
+            .orders
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(LimitOrder::pool)
+            .collect::<anyhow::Result<_>>()?;
+        for pool in pools {
+            match fetch_pool_price(&self.client, &pool).await {
+                Ok(snapshot) => self.on_price(&snapshot).await,
+                Err(e) => warn!("Failed to refresh pool {}: {e:?}", pool.id),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Send the swap of a triggered order, quoted from the current pool state.
+async fn execute_limit_order(
+    client: &AmmSwapClient<impl SwapSigner>,
+    order: &LimitOrder,
+) -> anyhow::Result<Signature> {
+    let input_mint = Pubkey::from_str(&order.input_mint)?;
+    swap_exact_in(
+        client,
+        &order.pool()?,
+        &input_mint,
+        order.amount_in,
+        order.slippage_bps,
+        None,
+    )
+    .await
+}
+
+/// Pending limit orders, sent when the price of their pool crosses their
+/// trigger. See the [module documentation](self).
+///
+/// Orders of CLMM pools spend the owner's associated token account of the
+/// input mint, which must already hold the amount, wSOL included.
+pub struct LimitOrderEngine<S = Keypair> {
+    shared: Arc<Shared<S>>,
+    poll_interval: Duration,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl<S: SwapSigner + 'static> LimitOrderEngine<S> {
+    pub fn new(client: Arc<AmmSwapClient<S>>, poll_interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        Self {
+            shared: Arc::new(Shared {
+                client,
+                orders: Mutex::new(Vec::new()),
+                next_id: Mutex::new(1),
+                store: None,
+                sender,
+            }),
+            poll_interval,
+            handle: Mutex::new(None),
+        }
+    }
+
+    /// Save pending orders to `store` on every change, starting with the
+    /// ones it already holds. Call before [`Self::start`].
+    pub fn with_store(self, store: Arc<dyn LimitOrderStore>) -> anyhow::Result<Self> {
+        let orders = store.load()?;
+        let next_id = orders.iter().map(|order| order.id).max().unwrap_or(0) + 1;
+        let (sender, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        Ok(Self {
+            shared: Arc::new(Shared {
+                client: Arc::clone(&self.shared.client),
+                orders: Mutex::new(orders),
+                next_id: Mutex::new(next_id),
+                store: Some(store),
+                sender,
+            }),
+            poll_interval: self.poll_interval,
+            handle: Mutex::new(None),
+        })
+    }
+
+    /// Add `order` to the pending orders and return its id.
+    pub fn place(&self, mut order: LimitOrder) -> anyhow::Result<u64> {
+        validation::validate_amount(order.amount_in)?;
+        validation::validate_slippage_bps(order.slippage_bps)?;
+        order.pool()?;
+        Pubkey::from_str(&order.input_mint)?;
+        let mut next_id = self
+            .shared
+            .next_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        order.id = *next_id;
+        let mut orders = self.shared.orders.lock().unwrap_or_else(|e| e.into_inner());
+        orders.push(order);
+        if let Err(e) = self.shared.persist(&orders) {
+            orders.pop();
+            return Err(e);
+        }
+        *next_id += 1;
+        Ok(*next_id - 1)
+    }
+
+    /// Remove the pending order `id`. Returns `false` when it is not pending,
+    /// e.g. already triggered.
+    pub fn cancel(&self, id: u64) -> anyhow::Result<bool> {
+        let mut orders = self.shared.orders.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(index) = orders.iter().position(|order| order.id == id) else {
+            return Ok(false);
+        };
+        orders.remove(index);
+        self.shared.persist(&orders)?;
+        Ok(true)
+    }
+
+    pub fn pending(&self) -> Vec<LimitOrder> {
+        self.shared
+            .orders
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Subscribe to the outcome of triggered orders.
+    pub fn subscribe(&self) -> LimitOrderEventStream {
+        MonitorStream {
+            receiver: self.shared.sender.subscribe(),
+        }
+    }
+
+    /// Send the pending orders of the pool of `snapshot` its price triggers,
+    /// one after the other.
+    pub async fn on_price(&self, snapshot: &PoolPriceSnapshot) {
+        self.shared.on_price(snapshot).await
+    }
+
+    /// Fetch the price of every pool with pending orders once and send the
+    /// orders it triggers.
+    pub async fn poll_once(&self) -> anyhow::Result<()> {
+        self.shared.poll_once().await
+    }
+
+    /// Spawn a task calling [`Self::poll_once`] every poll interval. Calling
+    /// `start` on a running engine restarts the task.
+    pub fn start(&self) {
+        self.stop();
+        let shared = Arc::clone(&self.shared);
+        let poll_interval = self.poll_interval;
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = shared.poll_once().await {
+                    warn!("Failed to check limit orders: {e:?}");
+                }
+            }
+        });
+        *self.handle.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+    }
+}
+
+impl<S> LimitOrderEngine<S> {
+    /// Abort the polling task. Pending orders are kept.
+    pub fn stop(&self) {
+        if let Some(handle) = self.handle.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            handle.abort();
+        }
+    }
+}
+
+impl<S> Drop for LimitOrderEngine<S> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_includes_its_price() {
+        assert!(PriceTrigger::AtOrAbove(2.0).is_crossed(2.0));
+        assert!(PriceTrigger::AtOrAbove(2.0).is_crossed(2.5));
+        assert!(!PriceTrigger::AtOrAbove(2.0).is_crossed(1.5));
+        assert!(PriceTrigger::AtOrBelow(2.0).is_crossed(2.0));
+        assert!(!PriceTrigger::AtOrBelow(2.0).is_crossed(2.5));
+    }
+
+    #[test]
+    fn order_round_trips_through_json() {
+        let order = LimitOrder {
+            id: 7,
+            ..LimitOrder::new(
+                MonitoredPool::concentrated(Pubkey::new_unique()),
+                Pubkey::new_unique(),
+                1_000,
+                PriceTrigger::AtOrBelow(150.5),
+                50,
+            )
+        };
+        let json = serde_json::to_value(&order).unwrap();
+        assert_eq!(json["pool_type"], "concentrated");
+        assert_eq!(json["trigger"]["kind"], "at_or_below");
+        assert_eq!(serde_json::from_value::<LimitOrder>(json).unwrap(), order);
+    }
+
+    #[test]
+    fn json_file_store_replaces_the_saved_orders() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("raydium-limit-orders-{nanos}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = JsonFileOrderStore::new(dir.join("orders.json"));
+        let order = LimitOrder::new(
+            MonitoredPool::concentrated(Pubkey::new_unique()),
+            Pubkey::new_unique(),
+            1_000,
+            PriceTrigger::AtOrAbove(2.0),
+            50,
+        );
+
+        assert!(store.load().unwrap().is_empty());
+        store.save(&[order.clone(), order.clone()]).unwrap();
+        store.save(std::slice::from_ref(&order)).unwrap();
+
+        assert_eq!(store.load().unwrap(), vec![order]);
+        assert!(!dir.join("orders.json.tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
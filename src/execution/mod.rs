@@ -0,0 +1,26 @@
+pub mod fanout;
+pub use fanout::*;
+pub mod lifecycle;
+pub use lifecycle::*;
+pub mod retry;
+pub use retry::*;
+pub mod guard;
+pub use guard::*;
+pub mod send;
+pub use send::*;
+pub mod priority_fee;
+pub use priority_fee::*;
+pub mod simulate;
+pub use simulate::*;
+pub mod swap_result;
+pub use swap_result::*;
+pub mod consistency;
+pub use consistency::*;
+pub mod dry_run;
+pub use dry_run::*;
+pub mod limit_order;
+pub use limit_order::*;
+pub mod execution_pool;
+pub use execution_pool::*;
+pub mod swap_funds;
+pub use swap_funds::*;
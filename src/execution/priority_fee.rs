@@ -0,0 +1,400 @@
+//! Compute budget instructions setting the priority fee of a transaction.
+//!
+//! Transactions paying no priority fee rarely land during congestion. The
+//! fee is either fixed or derived from the fees recently paid for the
+//! accounts the transaction writes, as reported by
+//! `getRecentPrioritizationFees`.
+//!
+//! A [`FeeBudget`] caps the total priority fee of a transaction: dynamic
+//! prices are lowered to fit it, and a fixed price that does not fit fails
+//! with [`SwapError::FeeBudgetExceeded`] before anything is sent. Budgets in
+//! bps are taken of the value in lamports traded by each transaction, which
+//! swaps pass as their SOL leg, see
+//! [`swap_trade_value_lamports`](crate::execution::swap_trade_value_lamports).
+
+use crate::error::SwapError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use tracing::debug;
+
+/// Compute budget program.
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("ComputeBudget111111111111111111111111111111");
+
+/// Most accounts `getRecentPrioritizationFees` accepts.
+const MAX_PRIORITIZATION_FEE_ACCOUNTS: usize = 128;
+
+/// Compute units granted per instruction without a compute unit limit.
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+/// Most compute units of a transaction.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+/// Base fee of each signature of a transaction, in lamports.
+pub const SIGNATURE_FEE_LAMPORTS: u64 = 5_000;
+
+/// Price of a compute unit, in micro-lamports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriorityFee {
+    Fixed(u64),
+    /// `percentile` (0-100) of the fees paid in recent slots by transactions
+    /// writing the same accounts, at most `max_micro_lamports`.
+    Dynamic {
+        percentile: u8,
+        max_micro_lamports: u64,
+    },
+}
+
+/// Most priority fee paid by a transaction, compute unit price times limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeBudget {
+    Lamports(u64),
+    /// Bps of the value traded by each transaction, in lamports: the input
+    /// of a swap selling SOL, or the least output of one buying SOL.
+    /// Transactions of no known value in SOL, e.g. swaps between two other
+    /// tokens or liquidity changes, are not capped.
+    Bps(u64),
+}
+
+impl FeeBudget {
+    /// Budget of a transaction trading `trade_value_lamports`, `None` when
+    /// a budget in bps does not apply to a transaction of unknown value.
+    pub fn max_lamports(&self, trade_value_lamports: Option<u64>) -> Option<u64> {
+        match *self {
+            FeeBudget::Lamports(lamports) => Some(lamports),
+            FeeBudget::Bps(bps) => trade_value_lamports.map(|trade_value_lamports| {
+                (u128::from(trade_value_lamports) * u128::from(bps) / 10_000) as u64
+            }),
+        }
+    }
+}
+
+/// Priority fee and compute unit limit prepended to the transactions of
+/// [`AmmSwapClient`](crate::amm::client::AmmSwapClient).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriorityFeeConfig {
+    pub fee: PriorityFee,
+    /// Compute unit limit of the transaction, `None` for the runtime default.
+    /// The fee paid is the price times this limit.
+    pub compute_unit_limit: Option<u32>,
+    /// Cap of the priority fee, `None` for no cap.
+    pub fee_budget: Option<FeeBudget>,
+}
+
+impl PriorityFeeConfig {
+    pub fn fixed(micro_lamports: u64) -> Self {
+        Self {
+            fee: PriorityFee::Fixed(micro_lamports),
+            compute_unit_limit: None,
+            fee_budget: None,
+        }
+    }
+
+    pub fn dynamic(percentile: u8, max_micro_lamports: u64) -> Self {
+        Self {
+            fee: PriorityFee::Dynamic {
+                percentile,
+                max_micro_lamports,
+            },
+            compute_unit_limit: None,
+            fee_budget: None,
+        }
+    }
+
+    pub fn with_compute_unit_limit(mut self, compute_unit_limit: u32) -> Self {
+        self.compute_unit_limit = Some(compute_unit_limit);
+        self
+    }
+
+    pub fn with_fee_budget(mut self, fee_budget: FeeBudget) -> Self {
+        self.fee_budget = Some(fee_budget);
+        self
+    }
+
+    /// Compute units the fee is paid for in a transaction made of
+    /// `instructions`: the configured limit, or the runtime default.
+    pub fn effective_compute_unit_limit(&self, instructions: &[Instruction]) -> u32 {
+        self.compute_unit_limit.unwrap_or_else(|| {
+            let instructions = u32::try_from(instructions.len()).unwrap_or(u32::MAX);
+            DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT
+                .saturating_mul(instructions.max(1))
+                .min(MAX_COMPUTE_UNIT_LIMIT)
+        })
+    }
+
+    /// Budget of a transaction trading `trade_value_lamports`, if capped.
+    fn budget_lamports(&self, trade_value_lamports: Option<u64>) -> Option<u64> {
+        self.fee_budget
+            .and_then(|fee_budget| fee_budget.max_lamports(trade_value_lamports))
+    }
+
+    /// Highest priority fee in lamports of a transaction made of
+    /// `instructions` and trading `trade_value_lamports`: at the fixed price
+    /// or the dynamic maximum, within the fee budget. Nothing is fetched.
+    pub fn max_fee_lamports(
+        &self,
+        instructions: &[Instruction],
+        trade_value_lamports: Option<u64>,
+    ) -> u64 {
+        let price = match self.fee {
+            PriorityFee::Fixed(micro_lamports) => micro_lamports,
+            PriorityFee::Dynamic {
+                max_micro_lamports, ..
+            } => max_micro_lamports,
+        };
+        let fee = priority_fee_lamports(price, self.effective_compute_unit_limit(instructions));
+        match self.budget_lamports(trade_value_lamports) {
+            Some(budget_lamports) => fee.min(budget_lamports),
+            None => fee,
+        }
+    }
+
+    /// Compute unit price for a transaction made of `instructions` and
+    /// trading `trade_value_lamports`, within the fee budget.
+    pub async fn compute_unit_price(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: &[Instruction],
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<u64> {
+        let price = self
+            .uncapped_compute_unit_price(rpc_client, instructions)
+            .await?;
+        self.capped_compute_unit_price(price, instructions, trade_value_lamports)
+    }
+
+    /// `price` lowered to the fee budget when dynamic. A fixed price above
+    /// the budget fails with [`SwapError::FeeBudgetExceeded`].
+    fn capped_compute_unit_price(
+        &self,
+        price: u64,
+        instructions: &[Instruction],
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<u64> {
+        let Some(budget_lamports) = self.budget_lamports(trade_value_lamports) else {
+            return Ok(price);
+        };
+        let compute_unit_limit = self.effective_compute_unit_limit(instructions);
+        let max_price = u128::from(budget_lamports) * MICRO_LAMPORTS_PER_LAMPORT
+            / u128::from(compute_unit_limit.max(1));
+        let max_price = u64::try_from(max_price).unwrap_or(u64::MAX);
+        if price <= max_price {
+            return Ok(price);
+        }
+        match self.fee {
+            PriorityFee::Fixed(_) => Err(SwapError::FeeBudgetExceeded {
+                fee_lamports: priority_fee_lamports(price, compute_unit_limit),
+                budget_lamports,
+            }
+            .into()),
+            PriorityFee::Dynamic { .. } => {
+                debug!("Priority fee of {price} micro-lamports capped at {max_price} by budget");
+                Ok(max_price)
+            }
+        }
+    }
+
+    async fn uncapped_compute_unit_price(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: &[Instruction],
+    ) -> anyhow::Result<u64> {
+        match self.fee {
+            PriorityFee::Fixed(micro_lamports) => Ok(micro_lamports),
+            PriorityFee::Dynamic {
+                percentile,
+                max_micro_lamports,
+            } => {
+                let mut writable_accounts: Vec<Pubkey> = Vec::new();
+                for account in instructions
+                    .iter()
+                    .flat_map(|instruction| &instruction.accounts)
+                    .filter(|account| account.is_writable)
+                {
+                    if !writable_accounts.contains(&account.pubkey) {
+                        writable_accounts.push(account.pubkey);
+                    }
+                }
+                writable_accounts.truncate(MAX_PRIORITIZATION_FEE_ACCOUNTS);
+                let fees: Vec<u64> = rpc_client
+                    .get_recent_prioritization_fees(&writable_accounts)
+                    .await?
+                    .into_iter()
+                    .map(|fee| fee.prioritization_fee)
+                    .collect();
+                let price = fee_percentile(fees, percentile).min(max_micro_lamports);
+                debug!("Priority fee at percentile {percentile}: {price} micro-lamports");
+                Ok(price)
+            }
+        }
+    }
+
+    /// Compute budget instructions to prepend to `instructions`, trading
+    /// `trade_value_lamports`.
+    pub async fn instructions(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: &[Instruction],
+        trade_value_lamports: Option<u64>,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let mut budget = Vec::with_capacity(2);
+        if let Some(compute_unit_limit) = self.compute_unit_limit {
+            budget.push(set_compute_unit_limit(compute_unit_limit));
+        }
+        let price = self
+            .compute_unit_price(rpc_client, instructions, trade_value_lamports)
+            .await?;
+        if price > 0 {
+            budget.push(set_compute_unit_price(price));
+        }
+        Ok(budget)
+    }
+}
+
+/// Priority fee in lamports of `compute_unit_limit` units at
+/// `micro_lamports` each, rounded up like the runtime.
+pub fn priority_fee_lamports(micro_lamports: u64, compute_unit_limit: u32) -> u64 {
+    let fee = (u128::from(micro_lamports) * u128::from(compute_unit_limit))
+        .div_ceil(MICRO_LAMPORTS_PER_LAMPORT);
+    u64::try_from(fee).unwrap_or(u64::MAX)
+}
+
+/// Value at `percentile` of `fees`, zero without fees.
+fn fee_percentile(mut fees: Vec<u64>, percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let index = (fees.len() - 1) * usize::from(percentile.min(100)) / 100;
+    fees[index]
+}
+
+/// `SetComputeUnitLimit` instruction of the compute budget program.
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = Vec::with_capacity(5);
+    data.push(2);
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction {
+        program_id: COMPUTE_BUDGET_PROGRAM_ID,
+        accounts: Vec::new(),
+        data,
+    }
+}
+
+/// `SetComputeUnitPrice` instruction of the compute budget program.
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = Vec::with_capacity(9);
+    data.push(3);
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction {
+        program_id: COMPUTE_BUDGET_PROGRAM_ID,
+        accounts: Vec::new(),
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap_instruction() -> Instruction {
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn bps_budget_is_taken_of_the_trade_value() {
+        assert_eq!(FeeBudget::Lamports(5_000).max_lamports(None), Some(5_000));
+        assert_eq!(
+            FeeBudget::Lamports(5_000).max_lamports(Some(1)),
+            Some(5_000)
+        );
+        assert_eq!(
+            FeeBudget::Bps(50).max_lamports(Some(2_000_000_000)),
+            Some(10_000_000)
+        );
+        assert_eq!(FeeBudget::Bps(50).max_lamports(None), None);
+        assert_eq!(
+            FeeBudget::Bps(10_000).max_lamports(Some(u64::MAX)),
+            Some(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn compute_unit_limit_defaults_to_the_runtime_limit() {
+        let config = PriorityFeeConfig::fixed(1);
+        let instructions = [(); 3].map(|_| swap_instruction());
+
+        assert_eq!(config.effective_compute_unit_limit(&[]), 200_000);
+        assert_eq!(config.effective_compute_unit_limit(&instructions), 600_000);
+        assert_eq!(
+            config.effective_compute_unit_limit(&[(); 10].map(|_| swap_instruction())),
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+        assert_eq!(
+            config
+                .with_compute_unit_limit(300_000)
+                .effective_compute_unit_limit(&instructions),
+            300_000
+        );
+    }
+
+    #[test]
+    fn max_fee_is_capped_by_the_budget_of_the_trade() {
+        let config = PriorityFeeConfig::dynamic(75, 1_000_000)
+            .with_compute_unit_limit(200_000)
+            .with_fee_budget(FeeBudget::Bps(10));
+
+        // 200_000 lamports at the maximum price, 10 bps of 1 SOL is 1_000_000.
+        assert_eq!(config.max_fee_lamports(&[], Some(1_000_000_000)), 200_000);
+        assert_eq!(config.max_fee_lamports(&[], Some(100_000_000)), 100_000);
+        assert_eq!(config.max_fee_lamports(&[], None), 200_000);
+    }
+
+    #[test]
+    fn fixed_price_over_budget_fails() {
+        let config = PriorityFeeConfig::fixed(1_000_000)
+            .with_compute_unit_limit(200_000)
+            .with_fee_budget(FeeBudget::Bps(10));
+
+        assert_eq!(
+            config
+                .capped_compute_unit_price(1_000_000, &[], Some(2_000_000_000))
+                .unwrap(),
+            1_000_000
+        );
+        let error = config
+            .capped_compute_unit_price(1_000_000, &[], Some(100_000_000))
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SwapError>(),
+            Some(SwapError::FeeBudgetExceeded {
+                fee_lamports: 200_000,
+                budget_lamports: 100_000,
+            })
+        ));
+    }
+
+    #[test]
+    fn dynamic_price_over_budget_is_clamped() {
+        let config = PriorityFeeConfig::dynamic(75, 1_000_000)
+            .with_compute_unit_limit(200_000)
+            .with_fee_budget(FeeBudget::Lamports(100_000));
+
+        assert_eq!(
+            config
+                .capped_compute_unit_price(1_000_000, &[], None)
+                .unwrap(),
+            500_000
+        );
+        assert_eq!(
+            config
+                .capped_compute_unit_price(400_000, &[], None)
+                .unwrap(),
+            400_000
+        );
+    }
+}
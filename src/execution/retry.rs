@@ -0,0 +1,231 @@
+//! Opt-in retry of swaps rejected for exceeding the slippage limit.
+
+use crate::amm::client::AmmSwapClient;
+use crate::error::is_slippage_error;
+use crate::execution::{SwapLifecycleEvent, SwapLifecycleSender, emit};
+use crate::interface::{AmmPool, ClmmPool, ClmmSwapParams};
+use crate::signer::SwapSigner;
+use solana_address::Address;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use tracing::warn;
+
+/// Slippage escalation applied when a swap fails with a slippage error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlippageRetryConfig {
+    /// Slippage of the first attempt, in bps. The slippage requested with
+    /// the swap is used instead when it is higher.
+    pub initial_slippage_bps: u64,
+    /// Added to the slippage after each slippage failure, in bps.
+    pub step_bps: u64,
+    /// Slippage is never escalated above this value, in bps.
+    pub max_slippage_bps: u64,
+}
+
+impl SlippageRetryConfig {
+    /// Slippage for the attempt following one made with `slippage_bps`, or
+    /// `None` when the cap has been reached.
+    pub fn next_slippage_bps(&self, slippage_bps: u64) -> Option<u64> {
+        if self.step_bps == 0 || slippage_bps >= self.max_slippage_bps {
+            return None;
+        }
+        Some(
+            slippage_bps
+                .saturating_add(self.step_bps)
+                .min(self.max_slippage_bps),
+        )
+    }
+}
+
+/// Swap on an AMM v4 pool, re-quoting and escalating slippage after every
+/// slippage failure, starting from the higher of `slippage_bps` and the
+/// initial slippage of `retry`. Without `retry` a single attempt is made at
+/// `slippage_bps`.
+#[allow(clippy::too_many_arguments)]
+pub async fn swap_amm_with_retry(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_keys: &AmmPool,
+    pool_info: &ClmmPool,
+    mint_a: &Address,
+    mint_b: &Address,
+    amount_in: u64,
+    slippage_bps: u64,
+    retry: Option<SlippageRetryConfig>,
+    events: Option<&SwapLifecycleSender>,
+) -> anyhow::Result<Signature> {
+    let pool_id: Pubkey = pool_keys.id.parse()?;
+    let mut slippage_bps = retry.map_or(slippage_bps, |r| r.initial_slippage_bps.max(slippage_bps));
+    let mut attempt = 1;
+    loop {
+        let result = async {
+            let rpc_pool_info = client.get_rpc_pool_info(&pool_id).await?;
+            let quote = client.compute_amount_out_for_input(
+                &rpc_pool_info,
+                pool_info,
+                &mint_a.to_string(),
+                amount_in,
+                slippage_bps as f64 / 10_000.0,
+            )?;
+            emit(
+                events,
+                SwapLifecycleEvent::Quoted {
+                    attempt,
+                    slippage_bps,
+                    amount_in,
+                    min_amount_out: quote.min_amount_out,
+                    fully_filled: true,
+                },
+            );
+            client
+                .swap_amm(pool_keys, mint_a, mint_b, amount_in, quote.min_amount_out)
+                .await
+        }
+        .await;
+
+        match next_attempt(
+            client,
+            result,
+            attempt,
+            slippage_bps,
+            retry.as_ref(),
+            events,
+        )? {
+            AttemptOutcome::Done(signature) => return Ok(signature),
+            AttemptOutcome::Retry(next_slippage_bps) => {
+                slippage_bps = next_slippage_bps;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Swap on a CLMM pool, re-quoting and escalating `params.slippage_bps` after
+/// every slippage failure, starting from the higher of `params.slippage_bps`
+/// and the initial slippage of `retry`.
+pub async fn swap_clmm_with_retry(
+    client: &AmmSwapClient<impl SwapSigner>,
+    params: ClmmSwapParams,
+    retry: Option<SlippageRetryConfig>,
+    events: Option<&SwapLifecycleSender>,
+) -> anyhow::Result<Signature> {
+    let mut slippage_bps = retry.map_or(params.slippage_bps, |r| {
+        r.initial_slippage_bps.max(params.slippage_bps)
+    });
+    let mut attempt = 1;
+    loop {
+        let result = async {
+            let attempt_params = ClmmSwapParams {
+                pool_id: params.pool_id,
+                user_input_token: params.user_input_token,
+                user_output_token: params.user_output_token,
+                amount_specified: params.amount_specified,
+                limit_price: params.limit_price,
+                base_out: params.base_out,
+                slippage_bps,
+                max_price_impact_bps: params.max_price_impact_bps,
+            };
+            let (swap_change, bitmap_extension) =
+                client.calculate_swap_change_clmm(attempt_params).await?;
+            emit(
+                events,
+                SwapLifecycleEvent::Quoted {
+                    attempt,
+                    slippage_bps,
+                    amount_in: swap_change.amount,
+                    min_amount_out: swap_change.other_amount_threshold,
+                    fully_filled: swap_change.fully_filled,
+                },
+            );
+            client
+                .swap_clmm(params.user_output_token, swap_change, bitmap_extension)
+                .await
+        }
+        .await;
+
+        match next_attempt(
+            client,
+            result,
+            attempt,
+            slippage_bps,
+            retry.as_ref(),
+            events,
+        )? {
+            AttemptOutcome::Done(signature) => return Ok(signature),
+            AttemptOutcome::Retry(next_slippage_bps) => {
+                slippage_bps = next_slippage_bps;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+enum AttemptOutcome {
+    Done(Signature),
+    /// Retry with the given slippage, in bps.
+    Retry(u64),
+}
+
+/// Report the outcome of an attempt and decide whether to retry.
+fn next_attempt(
+    client: &AmmSwapClient<impl SwapSigner>,
+    result: anyhow::Result<Signature>,
+    attempt: u32,
+    slippage_bps: u64,
+    retry: Option<&SlippageRetryConfig>,
+    events: Option<&SwapLifecycleSender>,
+) -> anyhow::Result<AttemptOutcome> {
+    let err = match result {
+        Ok(signature) => {
+            emit(events, SwapLifecycleEvent::Confirmed { attempt, signature });
+            return Ok(AttemptOutcome::Done(signature));
+        }
+        Err(err) => err,
+    };
+    if !is_slippage_error(&err, client.cluster_config()) {
+        emit(
+            events,
+            SwapLifecycleEvent::Failed {
+                attempt,
+                error: err.to_string(),
+            },
+        );
+        return Err(err);
+    }
+
+    emit(
+        events,
+        SwapLifecycleEvent::SlippageExceeded {
+            attempt,
+            slippage_bps,
+            error: err.to_string(),
+        },
+    );
+    match retry.and_then(|retry| retry.next_slippage_bps(slippage_bps)) {
+        Some(next_slippage_bps) => {
+            warn!(
+                "Swap attempt {attempt} exceeded slippage of {slippage_bps} bps, retrying with {next_slippage_bps} bps"
+            );
+            emit(
+                events,
+                SwapLifecycleEvent::Retrying {
+                    next_attempt: attempt + 1,
+                    next_slippage_bps,
+                },
+            );
+            Ok(AttemptOutcome::Retry(next_slippage_bps))
+        }
+        None => {
+            emit(
+                events,
+                SwapLifecycleEvent::Failed {
+                    attempt,
+                    error: err.to_string(),
+                },
+            );
+            Err(err.context(format!(
+                "swap exceeded slippage after {} attempt(s), last at {} bps",
+                attempt, slippage_bps
+            )))
+        }
+    }
+}
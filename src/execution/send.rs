@@ -0,0 +1,321 @@
+//! Sending a signed transaction through a single RPC and waiting for it.
+
+use crate::error::SwapError;
+use anyhow::anyhow;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::SerializableTransaction;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+use solana_transaction_status_client_types::TransactionStatus;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// How transactions of [`AmmSwapClient`](crate::amm::client::AmmSwapClient)
+/// are sent and confirmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SendOptions {
+    /// Commitment the transaction must reach.
+    pub commitment: CommitmentConfig,
+    /// Give up waiting for the confirmation after this long.
+    pub timeout: Duration,
+    /// Interval between signature status polls.
+    pub poll_interval: Duration,
+    pub skip_preflight: bool,
+    /// Rebroadcast attempts of the RPC node, `None` for the node default.
+    pub max_retries: Option<usize>,
+    /// Times a transaction whose blockhash expired before it landed is
+    /// signed again with a new blockhash and resent. None unless set.
+    pub max_blockhash_refreshes: u32,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentConfig::confirmed(),
+            timeout: DEFAULT_CONFIRM_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            skip_preflight: false,
+            max_retries: None,
+            max_blockhash_refreshes: 0,
+        }
+    }
+}
+
+impl SendOptions {
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_skip_preflight(mut self, skip_preflight: bool) -> Self {
+        self.skip_preflight = skip_preflight;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: Option<usize>) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_max_blockhash_refreshes(mut self, max_blockhash_refreshes: u32) -> Self {
+        self.max_blockhash_refreshes = max_blockhash_refreshes;
+        self
+    }
+}
+
+/// Send a signed transaction and poll its status until it reaches
+/// `options.commitment` or `options.timeout` elapses.
+///
+/// Transactions failing in preflight simulation or on chain are reported as
+/// [`SwapError::TransactionFailed`], without the failing program; fill it
+/// with [`with_failed_program`] before matching program errors with
+/// [`is_slippage_error`](crate::error::is_slippage_error).
+/// A transaction whose blockhash expires before it is seen fails with
+/// [`SwapError::BlockhashExpired`].
+pub async fn send_and_confirm(
+    rpc_client: &RpcClient,
+    transaction: &impl SerializableTransaction,
+    options: &SendOptions,
+) -> anyhow::Result<Signature> {
+    send_transaction(rpc_client, transaction, options)
+        .await?
+        .confirm(rpc_client)
+        .await
+}
+
+/// Send a signed transaction without waiting for it. The returned handle
+/// confirms it later, see [`PendingTransaction::confirm`].
+pub async fn send_transaction(
+    rpc_client: &RpcClient,
+    transaction: &impl SerializableTransaction,
+    options: &SendOptions,
+) -> anyhow::Result<PendingTransaction> {
+    let signature = rpc_client
+        .send_transaction_with_config(
+            transaction,
+            RpcSendTransactionConfig {
+                skip_preflight: options.skip_preflight,
+                preflight_commitment: Some(options.commitment.commitment),
+                max_retries: options.max_retries,
+                ..RpcSendTransactionConfig::default()
+            },
+        )
+        .await
+        .map_err(|err| match err.get_transaction_error() {
+            Some(error) => SwapError::TransactionFailed {
+                signature: *transaction.get_signature(),
+                error,
+                program_id: None,
+            }
+            .into(),
+            None => anyhow::Error::from(err),
+        })?;
+    debug!("Sent {signature}");
+    Ok(PendingTransaction {
+        signature,
+        recent_blockhash: *transaction.get_recent_blockhash(),
+        sent_at: Instant::now(),
+        options: *options,
+    })
+}
+
+/// Program of the instruction of `message` that `error` reports as failing.
+pub fn failed_instruction_program(
+    message: &VersionedMessage,
+    error: &TransactionError,
+) -> Option<Pubkey> {
+    let TransactionError::InstructionError(index, _) = error else {
+        return None;
+    };
+    let instruction = message.instructions().get(usize::from(*index))?;
+    message
+        .static_account_keys()
+        .get(usize::from(instruction.program_id_index))
+        .copied()
+}
+
+/// Fill the program of the failing instruction into a
+/// [`SwapError::TransactionFailed`] of a transaction of `message`. Other
+/// errors are returned unchanged.
+pub fn with_failed_program(err: anyhow::Error, message: &VersionedMessage) -> anyhow::Error {
+    match err.downcast::<SwapError>() {
+        Ok(SwapError::TransactionFailed {
+            signature,
+            error,
+            program_id: None,
+        }) => SwapError::TransactionFailed {
+            signature,
+            program_id: failed_instruction_program(message, &error),
+            error,
+        }
+        .into(),
+        Ok(err) => err.into(),
+        Err(err) => err,
+    }
+}
+
+/// Transaction sent by [`send_transaction`], not yet confirmed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingTransaction {
+    pub signature: Signature,
+    pub recent_blockhash: Hash,
+    pub sent_at: Instant,
+    /// Options it was sent with, also governing its confirmation.
+    pub options: SendOptions,
+}
+
+impl PendingTransaction {
+    /// Poll the status of the transaction until it reaches the commitment
+    /// of its options, fails, or its blockhash expires. Gives up once the
+    /// timeout of its options has elapsed since it was sent.
+    pub async fn confirm(&self, rpc_client: &RpcClient) -> anyhow::Result<Signature> {
+        let (signature, options) = (self.signature, &self.options);
+        loop {
+            if let Some(status) = self.status(rpc_client).await? {
+                if let Some(err) = &status.err {
+                    return Err(SwapError::TransactionFailed {
+                        signature,
+                        error: err.clone().into(),
+                        program_id: None,
+                    }
+                    .into());
+                }
+                if status.satisfies_commitment(options.commitment) {
+                    info!("Confirmed {signature} in {:?}", self.sent_at.elapsed());
+                    return Ok(signature);
+                }
+            } else if !rpc_client
+                .is_blockhash_valid(&self.recent_blockhash, CommitmentConfig::processed())
+                .await?
+                // It may have landed between the two calls.
+                && self.status(rpc_client).await?.is_none()
+            {
+                return Err(SwapError::BlockhashExpired { signature }.into());
+            }
+            if self.sent_at.elapsed() > options.timeout {
+                return Err(anyhow!(
+                    "transaction {} was not confirmed within {:?}",
+                    signature,
+                    options.timeout
+                ));
+            }
+            tokio::time::sleep(options.poll_interval).await;
+        }
+    }
+
+    async fn status(&self, rpc_client: &RpcClient) -> anyhow::Result<Option<TransactionStatus>> {
+        let statuses = rpc_client
+            .get_signature_statuses(&[self.signature])
+            .await?
+            .value;
+        Ok(statuses.into_iter().next().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClusterConfig;
+    use crate::error::is_slippage_error;
+    use crate::execution::SlippageRetryConfig;
+    use solana_sdk::instruction::{Instruction, InstructionError};
+    use solana_sdk::message::Message;
+
+    fn failed(program_id: Pubkey, index: u8, code: u32) -> (anyhow::Error, VersionedMessage) {
+        let payer = Pubkey::new_unique();
+        let message = VersionedMessage::Legacy(Message::new(
+            &[
+                Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+                Instruction::new_with_bytes(program_id, &[], vec![]),
+            ],
+            Some(&payer),
+        ));
+        let err = SwapError::TransactionFailed {
+            signature: Signature::default(),
+            error: TransactionError::InstructionError(index, InstructionError::Custom(code)),
+            program_id: None,
+        };
+        (err.into(), message)
+    }
+
+    #[test]
+    fn slippage_errors_are_matched_by_program_and_code() {
+        let cluster_config = ClusterConfig::mainnet();
+        let cases = [
+            (cluster_config.amm_v4, 1, 30, true),
+            (cluster_config.clmm, 1, 6021, true),
+            (cluster_config.clmm, 1, 6023, true),
+            // Same code from another program or another instruction.
+            (cluster_config.clmm, 1, 30, false),
+            (cluster_config.amm_v4, 1, 6022, false),
+            (Pubkey::new_unique(), 1, 30, false),
+            (cluster_config.amm_v4, 0, 30, false),
+        ];
+        for (program_id, index, code, slippage) in cases {
+            let (err, message) = failed(program_id, index, code);
+            assert!(!is_slippage_error(&err, &cluster_config));
+            let err = with_failed_program(err, &message);
+            assert_eq!(is_slippage_error(&err, &cluster_config), slippage, "{err}");
+        }
+        let err = anyhow!("custom program error: 0x1e");
+        assert!(!is_slippage_error(&err, &cluster_config));
+    }
+
+    #[test]
+    fn failed_program_is_the_program_of_the_failing_instruction() {
+        let program_id = Pubkey::new_unique();
+        let (err, message) = failed(program_id, 1, 1);
+        let Some(SwapError::TransactionFailed {
+            program_id: failed_program,
+            ..
+        }) = with_failed_program(err, &message)
+            .downcast_ref::<SwapError>()
+            .cloned()
+        else {
+            panic!("not a failed transaction");
+        };
+        assert_eq!(failed_program, Some(program_id));
+        let (_, message) = failed(program_id, 2, 1);
+        let out_of_range = TransactionError::InstructionError(2, InstructionError::Custom(1));
+        assert_eq!(failed_instruction_program(&message, &out_of_range), None);
+    }
+
+    #[test]
+    fn slippage_is_escalated_by_steps_up_to_the_cap() {
+        let retry = SlippageRetryConfig {
+            initial_slippage_bps: 50,
+            step_bps: 40,
+            max_slippage_bps: 150,
+        };
+        assert_eq!(retry.next_slippage_bps(50), Some(90));
+        assert_eq!(retry.next_slippage_bps(130), Some(150));
+        assert_eq!(retry.next_slippage_bps(150), None);
+        assert_eq!(retry.next_slippage_bps(200), None);
+
+        let no_step = SlippageRetryConfig {
+            step_bps: 0,
+            ..retry
+        };
+        assert_eq!(no_step.next_slippage_bps(50), None);
+
+        let saturating = SlippageRetryConfig {
+            initial_slippage_bps: 0,
+            step_bps: u64::MAX,
+            max_slippage_bps: u64::MAX,
+        };
+        assert_eq!(saturating.next_slippage_bps(1), Some(u64::MAX));
+    }
+}
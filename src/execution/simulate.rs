@@ -0,0 +1,69 @@
+//! Simulation of signed swap transactions, decoding the swap output from the
+//! program logs.
+
+use crate::execution::parse_executed_swaps;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::SerializableTransaction;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::transaction::TransactionError;
+use tracing::debug;
+
+/// Outcome of a simulated swap transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapSimulation {
+    /// Error the transaction would fail with.
+    pub err: Option<TransactionError>,
+    /// Compute units the transaction used, when the node reports them.
+    pub units_consumed: Option<u64>,
+    /// Output received by the last swap of the transaction, decoded from the
+    /// AMM v4 `ray_log` or the CLMM `SwapEvent`. `None` when the logs carry
+    /// no swap, e.g. because the transaction failed.
+    pub amount_out: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+impl SwapSimulation {
+    pub fn succeeded(&self) -> bool {
+        self.err.is_none()
+    }
+}
+
+/// Simulate `transaction` against the latest blockhash, without checking its
+/// signatures.
+pub async fn simulate_swap_transaction(
+    rpc_client: &RpcClient,
+    transaction: &impl SerializableTransaction,
+) -> anyhow::Result<SwapSimulation> {
+    let result = rpc_client
+        .simulate_transaction_with_config(
+            transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .await?
+        .value;
+    let logs = result.logs.unwrap_or_default();
+    let simulation = SwapSimulation {
+        err: result.err.map(Into::into),
+        units_consumed: result.units_consumed,
+        amount_out: parse_swap_amount_out(&logs),
+        logs,
+    };
+    debug!(
+        "Simulated swap: err={:?}, units={:?}, amount_out={:?}",
+        simulation.err, simulation.units_consumed, simulation.amount_out
+    );
+    Ok(simulation)
+}
+
+/// Output of the last AMM v4 or CLMM swap logged in `log_messages`.
+pub fn parse_swap_amount_out(log_messages: &[String]) -> Option<u64> {
+    parse_executed_swaps(log_messages)
+        .last()
+        .map(|swap| swap.amount_out)
+}
@@ -0,0 +1,288 @@
+//! Funds a swap transaction takes from the owner, checked before it is sent
+//! by [`AmmSwapClient::check_swap_funds`](crate::amm::client::AmmSwapClient::check_swap_funds).
+
+use crate::clmm::ClmmSwapChangeResult;
+use crate::execution::{PriorityFeeConfig, SIGNATURE_FEE_LAMPORTS};
+use solana_program::rent::Rent;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+/// What a swap spends from and pays into the owner's token accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapFunding {
+    /// Token account the swap spends.
+    pub input_account: Pubkey,
+    pub mint_in: Pubkey,
+    /// Most input spent, in the smallest units of `mint_in`.
+    pub amount_in: u64,
+    /// Lamports the swap transaction wraps into `input_account` first, for
+    /// a SOL input.
+    pub wrapped_lamports: u64,
+    /// Token account the swap pays into. It must exist or be created by the
+    /// swap transaction.
+    pub output_account: Pubkey,
+    pub mint_out: Pubkey,
+    /// Least output received, in the smallest units of `mint_out`.
+    pub min_amount_out: u64,
+}
+
+impl SwapFunding {
+    /// Funding of the CLMM swap of `swap_change` by `owner`, without
+    /// wrapping: the amount for exact input swaps, the maximum input for
+    /// exact output ones. Without `user_output_token`, the output goes to
+    /// the owner's associated token account, as in
+    /// [`build_swap_clmm_instructions`](crate::amm::client::AmmSwapClient::build_swap_clmm_instructions).
+    pub fn clmm(
+        swap_change: &ClmmSwapChangeResult,
+        owner: &Pubkey,
+        user_output_token: Option<solana_pubkey::Pubkey>,
+    ) -> Self {
+        let output_account = match user_output_token {
+            Some(user_output_token) => Pubkey::from(user_output_token.to_bytes()),
+            None => get_associated_token_address_with_program_id(
+                owner,
+                &Pubkey::from(swap_change.output_vault_mint.to_bytes()),
+                &Pubkey::from(swap_change.output_token_program.to_bytes()),
+            ),
+        };
+        Self {
+            input_account: Pubkey::from(swap_change.user_input_token.to_bytes()),
+            mint_in: Pubkey::from(swap_change.input_vault_mint.to_bytes()),
+            amount_in: if swap_change.is_base_input {
+                swap_change.amount
+            } else {
+                swap_change.other_amount_threshold
+            },
+            wrapped_lamports: 0,
+            output_account,
+            mint_out: Pubkey::from(swap_change.output_vault_mint.to_bytes()),
+            min_amount_out: if swap_change.is_base_input {
+                swap_change.other_amount_threshold
+            } else {
+                swap_change.amount
+            },
+        }
+    }
+
+    /// Value of the swap in lamports, see [`swap_trade_value_lamports`].
+    pub fn trade_value_lamports(&self) -> Option<u64> {
+        swap_trade_value_lamports(
+            &self.mint_in,
+            self.amount_in,
+            &self.mint_out,
+            self.min_amount_out,
+        )
+    }
+}
+
+/// Value in lamports of a swap of `amount_in` of `mint_in` for at least
+/// `min_amount_out` of `mint_out`, for fee budgets in bps: the input when
+/// selling SOL, the least output when buying SOL. `None` when neither mint
+/// is wSOL, as the value of other tokens in SOL is unknown.
+pub fn swap_trade_value_lamports(
+    mint_in: &Pubkey,
+    amount_in: u64,
+    mint_out: &Pubkey,
+    min_amount_out: u64,
+) -> Option<u64> {
+    let native_mint = spl_token::native_mint::id();
+    if *mint_in == native_mint {
+        Some(amount_in)
+    } else if *mint_out == native_mint {
+        Some(min_amount_out)
+    } else {
+        None
+    }
+}
+
+/// Associated token account created by an instruction of a swap
+/// transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CreatedTokenAccount {
+    pub address: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+/// Associated token accounts `instructions` create, idempotently or not, in
+/// order and each once.
+pub fn created_token_accounts(instructions: &[Instruction]) -> Vec<CreatedTokenAccount> {
+    let mut created: Vec<CreatedTokenAccount> = Vec::new();
+    for ix in instructions
+        .iter()
+        .filter(|ix| ix.program_id == spl_associated_token_account::id())
+    {
+        let [_, address, _, mint, _, token_program, ..] = ix.accounts.as_slice() else {
+            continue;
+        };
+        if created
+            .iter()
+            .all(|existing| existing.address != address.pubkey)
+        {
+            created.push(CreatedTokenAccount {
+                address: address.pubkey,
+                mint: mint.pubkey,
+                token_program: token_program.pubkey,
+            });
+        }
+    }
+    created
+}
+
+/// Signature fee plus the highest priority fee under `priority_fee` of a
+/// transaction made of `instructions` and trading `trade_value_lamports`.
+pub fn swap_fee_lamports(
+    priority_fee: Option<&PriorityFeeConfig>,
+    instructions: &[Instruction],
+    trade_value_lamports: Option<u64>,
+) -> u64 {
+    let priority_fee_lamports = priority_fee.map_or(0, |priority_fee| {
+        priority_fee.max_fee_lamports(instructions, trade_value_lamports)
+    });
+    SIGNATURE_FEE_LAMPORTS.saturating_add(priority_fee_lamports)
+}
+
+/// Lamports a swap transaction takes from the owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapLamports {
+    /// Wrapped into wSOL.
+    pub wrapped: u64,
+    /// Rent-exempt minimum of the token accounts created, plus the owner's
+    /// own, which it must keep.
+    pub rent: u64,
+    /// Signature and priority fees.
+    pub fees: u64,
+}
+
+impl SwapLamports {
+    /// Lamports of a swap wrapping `wrapped_lamports`, creating token
+    /// accounts of `created_account_lens` bytes and paying `fee_lamports`.
+    pub fn new(
+        wrapped_lamports: u64,
+        rent: &Rent,
+        created_account_lens: &[usize],
+        fee_lamports: u64,
+    ) -> Self {
+        let rent = created_account_lens
+            .iter()
+            .fold(rent.minimum_balance(0), |total, len| {
+                total.saturating_add(rent.minimum_balance(*len))
+            });
+        Self {
+            wrapped: wrapped_lamports,
+            rent,
+            fees: fee_lamports,
+        }
+    }
+
+    pub fn needed(&self) -> u64 {
+        self.wrapped
+            .saturating_add(self.rent)
+            .saturating_add(self.fees)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::FeeBudget;
+    use solana_sdk::instruction::AccountMeta;
+    use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+    fn key(n: u8) -> Pubkey {
+        Pubkey::new_from_array([n; 32])
+    }
+
+    #[test]
+    fn created_token_accounts_are_read_from_ata_instructions_once() {
+        let owner = key(1);
+        let token_2022 = Pubkey::from(spl_token_2022::id().to_bytes());
+        let instructions = [
+            create_associated_token_account_idempotent(&owner, &owner, &key(2), &spl_token::id()),
+            Instruction::new_with_bytes(key(9), &[], vec![AccountMeta::new(key(3), false)]),
+            create_associated_token_account_idempotent(&owner, &owner, &key(3), &token_2022),
+            create_associated_token_account_idempotent(&owner, &owner, &key(2), &spl_token::id()),
+        ];
+
+        let created = created_token_accounts(&instructions);
+
+        assert_eq!(
+            created,
+            vec![
+                CreatedTokenAccount {
+                    address: get_associated_token_address_with_program_id(
+                        &owner,
+                        &key(2),
+                        &spl_token::id()
+                    ),
+                    mint: key(2),
+                    token_program: spl_token::id(),
+                },
+                CreatedTokenAccount {
+                    address: get_associated_token_address_with_program_id(
+                        &owner,
+                        &key(3),
+                        &token_2022
+                    ),
+                    mint: key(3),
+                    token_program: token_2022,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn swap_lamports_add_owner_rent_created_accounts_wrap_and_fees() {
+        let lamports = SwapLamports::new(1_000_000, &Rent::default(), &[165, 165], 15_000);
+
+        // 890_880 for the owner, 2_039_280 per 165 byte token account.
+        assert_eq!(lamports.rent, 890_880 + 2 * 2_039_280);
+        assert_eq!(
+            lamports.needed(),
+            1_000_000 + 890_880 + 2 * 2_039_280 + 15_000
+        );
+    }
+
+    #[test]
+    fn swap_fee_is_the_signature_fee_without_priority_fee() {
+        assert_eq!(
+            swap_fee_lamports(None, &[], Some(1_000)),
+            SIGNATURE_FEE_LAMPORTS
+        );
+        let priority_fee = PriorityFeeConfig::fixed(1_000_000).with_compute_unit_limit(200_000);
+        assert_eq!(
+            swap_fee_lamports(Some(&priority_fee), &[], Some(1_000)),
+            SIGNATURE_FEE_LAMPORTS + 200_000
+        );
+        let priority_fee = priority_fee.with_fee_budget(FeeBudget::Bps(10));
+        assert_eq!(
+            swap_fee_lamports(Some(&priority_fee), &[], Some(100_000_000)),
+            SIGNATURE_FEE_LAMPORTS + 100_000
+        );
+        // Not capped when the value in SOL is unknown.
+        assert_eq!(
+            swap_fee_lamports(Some(&priority_fee), &[], None),
+            SIGNATURE_FEE_LAMPORTS + 200_000
+        );
+    }
+
+    #[test]
+    fn trade_value_is_the_sol_leg_of_the_swap() {
+        let native_mint = spl_token::native_mint::id();
+        let usdc = key(5);
+        assert_eq!(
+            swap_trade_value_lamports(&native_mint, 1_000_000_000, &usdc, 150_000_000),
+            Some(1_000_000_000)
+        );
+        assert_eq!(
+            swap_trade_value_lamports(&usdc, 150_000_000, &native_mint, 990_000_000),
+            Some(990_000_000)
+        );
+        // 1_000 USDC is not worth 1 SOL.
+        assert_eq!(
+            swap_trade_value_lamports(&usdc, 1_000_000_000, &key(6), 1),
+            None
+        );
+    }
+}
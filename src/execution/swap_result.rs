@@ -0,0 +1,570 @@
+//! Swap results decoded from the logs of confirmed transactions.
+//!
+//! AMM v4 swaps write a `ray_log`: a log type byte followed by seven little
+//! endian `u64`s, with the pool reserves before the swap but not the pool
+//! itself. CLMM swaps emit a `SwapEvent` naming the pool, with the price after
+//! the swap and the Token-2022 transfer fees withheld. Neither carries the
+//! trade fee, which is computed from the fee rate of the pool.
+//!
+//! The pool of an AMM v4 swap is read from its instruction instead, see
+//! [`amm_swap_pools`].
+
+use crate::amm::AmmInstruction;
+use crate::clmm::{from_x64_price, parse_swap_events};
+use crate::common::RAY_LOG;
+use crate::consts::{LIQUIDITY_FEES_DENOMINATOR, LIQUIDITY_FEES_NUMERATOR};
+use crate::interface::PoolType;
+use crate::states::FEE_RATE_DENOMINATOR_VALUE;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status_client_types::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, UiCompiledInstruction,
+    UiInnerInstructions, UiInstruction, UiLoadedAddresses, UiMessage,
+};
+use std::str::FromStr;
+
+/// `LogType` of the AMM v4 `ray_log` written by `SwapBaseIn`.
+const AMM_LOG_SWAP_BASE_IN: u8 = 3;
+/// `LogType` of the AMM v4 `ray_log` written by `SwapBaseOut`.
+const AMM_LOG_SWAP_BASE_OUT: u8 = 4;
+/// `SwapDirection` of an AMM v4 swap selling the coin (base) token.
+const AMM_DIRECTION_COIN_TO_PC: u64 = 2;
+
+/// A swap decoded from the logs of a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedSwap {
+    pub pool_type: PoolType,
+    /// Pool swapped on. `None` for AMM v4 swaps decoded from logs alone,
+    /// whose `ray_log` does not name the pool.
+    ///
+    /// [`AmmSwapClient::get_swap_result`] reads it from the swap instructions.
+    ///
+    /// [`AmmSwapClient::get_swap_result`]: crate::amm::client::AmmSwapClient::get_swap_result
+    pub pool_id: Option<Pubkey>,
+    /// Whether token 0, the coin (base) token of an AMM v4 pool, was sold.
+    pub zero_for_one: bool,
+    /// Input paid by the swapper, transfer fee included.
+    pub amount_in: u64,
+    /// Output received by the swapper, transfer fee deducted.
+    pub amount_out: u64,
+    /// Trade fee charged by the pool, in input units, computed from the fee
+    /// rate of the pool.
+    pub trade_fee: u64,
+    /// Token-2022 transfer fee withheld on the input. Always zero for AMM v4.
+    pub transfer_fee_in: u64,
+    /// Token-2022 transfer fee withheld on the output. Always zero for AMM v4.
+    pub transfer_fee_out: u64,
+    /// Price of token 0 in token 1 after the swap, in smallest units.
+    pub price_after: f64,
+}
+
+/// Swaps of a confirmed transaction, returned by
+/// [`AmmSwapClient::get_swap_result`].
+///
+/// [`AmmSwapClient::get_swap_result`]: crate::amm::client::AmmSwapClient::get_swap_result
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapResult {
+    pub signature: Signature,
+    /// Swaps in execution order, one per hop of a multi-hop swap.
+    pub swaps: Vec<ExecutedSwap>,
+}
+
+impl SwapResult {
+    /// Input of the first swap.
+    pub fn amount_in(&self) -> Option<u64> {
+        self.swaps.first().map(|swap| swap.amount_in)
+    }
+
+    /// Output of the last swap.
+    pub fn amount_out(&self) -> Option<u64> {
+        self.swaps.last().map(|swap| swap.amount_out)
+    }
+
+    /// Pool price after the last swap, see [`ExecutedSwap::price_after`].
+    pub fn price_after(&self) -> Option<f64> {
+        self.swaps.last().map(|swap| swap.price_after)
+    }
+}
+
+/// Decode every AMM v4 and CLMM swap logged in `log_messages`, in order.
+///
+/// The `trade_fee` of CLMM swaps is left at zero, as their fee rate is kept
+/// in the amm config of the pool; see [`clmm_trade_fee`]. AMM v4 swaps are
+/// charged the standard 0.25% fee; see [`amm_trade_fee`] for other pools.
+pub fn parse_executed_swaps(log_messages: &[String]) -> Vec<ExecutedSwap> {
+    log_messages
+        .iter()
+        .filter_map(|log| {
+            if let Some((_, data)) = log.split_once(RAY_LOG) {
+                return amm_executed_swap(&BASE64_STANDARD.decode(data.trim()).ok()?);
+            }
+            let event = parse_swap_events(std::slice::from_ref(log)).pop()?;
+            let (amount_in, transfer_fee_in, amount_out, transfer_fee_out) = if event.zero_for_one {
+                (
+                    event.amount_0,
+                    event.transfer_fee_0,
+                    event.amount_1,
+                    event.transfer_fee_1,
+                )
+            } else {
+                (
+                    event.amount_1,
+                    event.transfer_fee_1,
+                    event.amount_0,
+                    event.transfer_fee_0,
+                )
+            };
+            Some(ExecutedSwap {
+                pool_type: PoolType::Concentrated,
+                pool_id: Some(Pubkey::from(event.pool_state.to_bytes())),
+                zero_for_one: event.zero_for_one,
+                amount_in,
+                amount_out: amount_out.saturating_sub(transfer_fee_out),
+                trade_fee: 0,
+                transfer_fee_in,
+                transfer_fee_out,
+                price_after: from_x64_price(event.sqrt_price_x64).powi(2),
+            })
+        })
+        .collect()
+}
+
+/// Trade fee of a CLMM swap of `amount_in`, net of transfer fees, at
+/// `trade_fee_rate` (per [`FEE_RATE_DENOMINATOR_VALUE`]), rounded up like the
+/// program does.
+pub fn clmm_trade_fee(amount_in: u64, trade_fee_rate: u32) -> u64 {
+    (amount_in as u128 * trade_fee_rate as u128).div_ceil(FEE_RATE_DENOMINATOR_VALUE as u128) as u64
+}
+
+/// AMM v4 swap fee as `(numerator, denominator)` from the fields of the pool
+/// state, falling back to `LIQUIDITY_FEES_NUMERATOR/DENOMINATOR` when they
+/// carry no usable fee.
+pub fn amm_swap_fee(swap_fee_numerator: u64, swap_fee_denominator: u64) -> (u64, u64) {
+    if swap_fee_denominator == 0 || swap_fee_numerator >= swap_fee_denominator {
+        (LIQUIDITY_FEES_NUMERATOR, LIQUIDITY_FEES_DENOMINATOR)
+    } else {
+        (swap_fee_numerator, swap_fee_denominator)
+    }
+}
+
+/// Trade fee of an AMM v4 swap of `amount_in` at the swap fee
+/// `swap_fee_numerator / swap_fee_denominator` of the pool state, see
+/// [`amm_swap_fee`], rounded up like the program does.
+pub fn amm_trade_fee(amount_in: u64, swap_fee_numerator: u64, swap_fee_denominator: u64) -> u64 {
+    let (numerator, denominator) = amm_swap_fee(swap_fee_numerator, swap_fee_denominator);
+    (amount_in as u128 * numerator as u128).div_ceil(denominator as u128) as u64
+}
+
+/// Pools of the AMM v4 `SwapBaseIn` and `SwapBaseOut` instructions of
+/// `amm_program` in `transaction`, top-level or invoked by other programs,
+/// in execution order: the order of their swaps in
+/// [`parse_executed_swaps`].
+///
+/// Empty unless the transaction was fetched with the JSON encoding.
+pub fn amm_swap_pools(
+    transaction: &EncodedTransactionWithStatusMeta,
+    amm_program: &Pubkey,
+) -> Vec<Pubkey> {
+    let EncodedTransaction::Json(ui_transaction) = &transaction.transaction else {
+        return Vec::new();
+    };
+    let UiMessage::Raw(message) = &ui_transaction.message else {
+        return Vec::new();
+    };
+    let mut account_keys = message.account_keys.clone();
+    let mut inner_instructions = Vec::new();
+    if let Some(meta) = &transaction.meta {
+        let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.clone().into();
+        if let Some(loaded_addresses) = loaded_addresses {
+            account_keys.extend(loaded_addresses.writable);
+            account_keys.extend(loaded_addresses.readonly);
+        }
+        let inner: Option<Vec<UiInnerInstructions>> = meta.inner_instructions.clone().into();
+        inner_instructions = inner.unwrap_or_default();
+    }
+    let amm_program = amm_program.to_string();
+    let swap_pool = |instruction: &UiCompiledInstruction| -> Option<Pubkey> {
+        if *account_keys.get(instruction.program_id_index as usize)? != amm_program {
+            return None;
+        }
+        let data = bs58::decode(&instruction.data).into_vec().ok()?;
+        if !matches!(
+            AmmInstruction::unpack(&data),
+            Ok(AmmInstruction::SwapBaseIn(_) | AmmInstruction::SwapBaseOut(_))
+        ) {
+            return None;
+        }
+        // The amm account follows the token program.
+        let amm = account_keys.get(*instruction.accounts.get(1)? as usize)?;
+        Pubkey::from_str(amm).ok()
+    };
+
+    let mut pools = Vec::new();
+    for (index, instruction) in message.instructions.iter().enumerate() {
+        pools.extend(swap_pool(instruction));
+        let invoked = inner_instructions
+            .iter()
+            .filter(|inner| inner.index as usize == index)
+            .flat_map(|inner| &inner.instructions);
+        for instruction in invoked {
+            if let UiInstruction::Compiled(instruction) = instruction {
+                pools.extend(swap_pool(instruction));
+            }
+        }
+    }
+    pools
+}
+
+/// AMM v4 swap from its `ray_log`. `SwapBaseIn` logs `amount_in`,
+/// `minimum_out`, `direction`, `user_source`, `pool_coin`, `pool_pc` and
+/// `out_amount`; `SwapBaseOut` logs `max_in`, `amount_out`, `direction`,
+/// `user_source`, `pool_coin`, `pool_pc` and `deduct_in`.
+fn amm_executed_swap(data: &[u8]) -> Option<ExecutedSwap> {
+    let field = |index: usize| -> Option<u64> {
+        let start = 1 + index * 8;
+        Some(u64::from_le_bytes(
+            data.get(start..start + 8)?.try_into().ok()?,
+        ))
+    };
+    let (amount_in, amount_out) = match *data.first()? {
+        AMM_LOG_SWAP_BASE_IN => (field(0)?, field(6)?),
+        AMM_LOG_SWAP_BASE_OUT => (field(6)?, field(1)?),
+        _ => return None,
+    };
+    let zero_for_one = field(2)? == AMM_DIRECTION_COIN_TO_PC;
+    let (pool_coin, pool_pc) = (field(4)? as f64, field(5)? as f64);
+    let (coin_after, pc_after) = if zero_for_one {
+        (pool_coin + amount_in as f64, pool_pc - amount_out as f64)
+    } else {
+        (pool_coin - amount_out as f64, pool_pc + amount_in as f64)
+    };
+    Some(ExecutedSwap {
+        pool_type: PoolType::Standard,
+        pool_id: None,
+        zero_for_one,
+        amount_in,
+        amount_out,
+        trade_fee: amm_trade_fee(
+            amount_in,
+            LIQUIDITY_FEES_NUMERATOR,
+            LIQUIDITY_FEES_DENOMINATOR,
+        ),
+        transfer_fee_in: 0,
+        transfer_fee_out: 0,
+        price_after: if coin_after > 0.0 {
+            pc_after / coin_after
+        } else {
+            0.0
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::{SwapInstructionBaseIn, SwapInstructionBaseOut};
+    use crate::states::SwapEvent;
+    use anchor_lang::Event;
+
+    const AMM_DIRECTION_PC_TO_COIN: u64 = 1;
+
+    /// `ray_log` line of an AMM v4 swap on a pool of 100 coin and 15 pc.
+    fn ray_log(log_type: u8, fields: [u64; 3], direction: u64, last: u64) -> String {
+        let mut data = vec![log_type];
+        for value in [
+            fields[0],
+            fields[1],
+            direction,
+            fields[2],
+            100_000_000_000,
+            15_000_000_000,
+            last,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        format!("Program log: {RAY_LOG}{}", BASE64_STANDARD.encode(data))
+    }
+
+    fn swap_event_log(zero_for_one: bool) -> String {
+        let event = SwapEvent {
+            pool_state: anchor_lang::prelude::Pubkey::new_from_array([7; 32]),
+            sender: anchor_lang::prelude::Pubkey::new_from_array([8; 32]),
+            token_account_0: anchor_lang::prelude::Pubkey::new_from_array([9; 32]),
+            token_account_1: anchor_lang::prelude::Pubkey::new_from_array([10; 32]),
+            amount_0: 1_000_000,
+            transfer_fee_0: 1_000,
+            amount_1: 500_000,
+            transfer_fee_1: 500,
+            zero_for_one,
+            sqrt_price_x64: 1 << 63,
+            liquidity: 10_000_000,
+            tick: -6_932,
+        };
+        format!("Program data: {}", BASE64_STANDARD.encode(event.data()))
+    }
+
+    #[test]
+    fn amm_swaps_are_decoded_from_ray_logs_in_both_directions() {
+        let logs = [
+            // SwapBaseIn: amount_in, minimum_out, user_source, out_amount.
+            ray_log(
+                AMM_LOG_SWAP_BASE_IN,
+                [1_000_000_000, 140_000_000, 5_000_000_000],
+                AMM_DIRECTION_COIN_TO_PC,
+                148_148_148,
+            ),
+            ray_log(
+                AMM_LOG_SWAP_BASE_IN,
+                [150_000_000, 900_000_000, 1_000_000_000],
+                AMM_DIRECTION_PC_TO_COIN,
+                985_000_000,
+            ),
+            // SwapBaseOut: max_in, amount_out, user_source, deduct_in.
+            ray_log(
+                AMM_LOG_SWAP_BASE_OUT,
+                [700_000_000, 100_000_000, 5_000_000_000],
+                AMM_DIRECTION_COIN_TO_PC,
+                668_000_000,
+            ),
+            ray_log(
+                AMM_LOG_SWAP_BASE_OUT,
+                [20_000_000, 100_000_000, 1_000_000_000],
+                AMM_DIRECTION_PC_TO_COIN,
+                15_052_000,
+            ),
+            // Other log types are skipped.
+            ray_log(0, [1, 2, 3], AMM_DIRECTION_COIN_TO_PC, 4),
+        ];
+
+        let swaps = parse_executed_swaps(&logs);
+
+        let decoded: Vec<_> = swaps
+            .iter()
+            .map(|swap| {
+                (
+                    swap.pool_type,
+                    swap.pool_id,
+                    swap.zero_for_one,
+                    swap.amount_in,
+                    swap.amount_out,
+                    swap.trade_fee,
+                )
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (
+                    PoolType::Standard,
+                    None,
+                    true,
+                    1_000_000_000,
+                    148_148_148,
+                    2_500_000
+                ),
+                (
+                    PoolType::Standard,
+                    None,
+                    false,
+                    150_000_000,
+                    985_000_000,
+                    375_000
+                ),
+                (
+                    PoolType::Standard,
+                    None,
+                    true,
+                    668_000_000,
+                    100_000_000,
+                    1_670_000
+                ),
+                (
+                    PoolType::Standard,
+                    None,
+                    false,
+                    15_052_000,
+                    100_000_000,
+                    37_630
+                ),
+            ]
+        );
+        assert_eq!(
+            swaps[0].price_after,
+            (15_000_000_000.0 - 148_148_148.0) / (100_000_000_000.0 + 1_000_000_000.0)
+        );
+        assert_eq!(
+            swaps[3].price_after,
+            (15_000_000_000.0 + 15_052_000.0) / (100_000_000_000.0 - 100_000_000.0)
+        );
+        assert_eq!(
+            swaps.iter().map(|swap| swap.transfer_fee_in).sum::<u64>(),
+            0
+        );
+    }
+
+    #[test]
+    fn clmm_swaps_are_decoded_from_swap_events_with_transfer_fees() {
+        let logs = [
+            swap_event_log(true),
+            "Program log: Instruction: SwapV2".to_string(),
+            swap_event_log(false),
+        ];
+
+        let swaps = parse_executed_swaps(&logs);
+
+        let pool_id = Some(Pubkey::new_from_array([7; 32]));
+        assert_eq!(
+            swaps,
+            vec![
+                ExecutedSwap {
+                    pool_type: PoolType::Concentrated,
+                    pool_id,
+                    zero_for_one: true,
+                    amount_in: 1_000_000,
+                    amount_out: 499_500,
+                    trade_fee: 0,
+                    transfer_fee_in: 1_000,
+                    transfer_fee_out: 500,
+                    price_after: 0.25,
+                },
+                ExecutedSwap {
+                    pool_type: PoolType::Concentrated,
+                    pool_id,
+                    zero_for_one: false,
+                    amount_in: 500_000,
+                    amount_out: 999_000,
+                    trade_fee: 0,
+                    transfer_fee_in: 500,
+                    transfer_fee_out: 1_000,
+                    price_after: 0.25,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn amm_and_clmm_swaps_are_returned_in_log_order() {
+        let logs = [
+            swap_event_log(true),
+            ray_log(
+                AMM_LOG_SWAP_BASE_IN,
+                [1_000_000_000, 140_000_000, 5_000_000_000],
+                AMM_DIRECTION_COIN_TO_PC,
+                148_148_148,
+            ),
+        ];
+
+        let result = SwapResult {
+            signature: Signature::default(),
+            swaps: parse_executed_swaps(&logs),
+        };
+
+        assert_eq!(result.swaps[0].pool_type, PoolType::Concentrated);
+        assert_eq!(result.swaps[1].pool_type, PoolType::Standard);
+        assert_eq!(result.amount_in(), Some(1_000_000));
+        assert_eq!(result.amount_out(), Some(148_148_148));
+    }
+
+    #[test]
+    fn amm_swap_pools_are_read_from_top_level_and_inner_instructions() {
+        let amm_program = Pubkey::new_unique();
+        let (payer, router, other_program) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (pool_1, pool_2, pool_3) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let swap_base_in = AmmInstruction::SwapBaseIn(SwapInstructionBaseIn {
+            amount_in: 1_000,
+            minimum_amount_out: 1,
+        })
+        .pack()
+        .unwrap();
+        let swap_base_out = AmmInstruction::SwapBaseOut(SwapInstructionBaseOut {
+            max_amount_in: 1_000,
+            amount_out: 1,
+        })
+        .pack()
+        .unwrap();
+        let data = |data: &[u8]| bs58::encode(data).into_string();
+        let instruction = |program_id_index: u8, amm_index: u8, data: String| {
+            serde_json::json!({
+                "programIdIndex": program_id_index,
+                "accounts": [1, amm_index],
+                "data": data,
+            })
+        };
+        // Static keys: payer, token program, pool 1, AMM, router, other
+        // program. Pools 2 and 3 are loaded from a lookup table.
+        let transaction = serde_json::json!({
+            "transaction": {
+                "signatures": [Signature::default().to_string()],
+                "message": {
+                    "header": {
+                        "numRequiredSignatures": 1,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 4,
+                    },
+                    "accountKeys": [
+                        payer.to_string(),
+                        spl_token::id().to_string(),
+                        pool_1.to_string(),
+                        amm_program.to_string(),
+                        router.to_string(),
+                        other_program.to_string(),
+                    ],
+                    "recentBlockhash": solana_sdk::hash::Hash::default().to_string(),
+                    "instructions": [
+                        instruction(3, 2, data(&swap_base_in)),
+                        // Not a swap, and a swap of another program.
+                        instruction(3, 2, data(&[0xff])),
+                        instruction(5, 2, data(&swap_base_in)),
+                        instruction(4, 6, data(&[])),
+                    ],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5_000,
+                "preBalances": [],
+                "postBalances": [],
+                "innerInstructions": [{
+                    "index": 3,
+                    "instructions": [
+                        instruction(3, 7, data(&swap_base_out)),
+                        instruction(3, 6, data(&swap_base_in)),
+                    ],
+                }],
+                "loadedAddresses": {
+                    "writable": [pool_2.to_string(), pool_3.to_string()],
+                    "readonly": [],
+                },
+            },
+        });
+        let transaction: EncodedTransactionWithStatusMeta =
+            serde_json::from_value(transaction).unwrap();
+
+        assert_eq!(
+            amm_swap_pools(&transaction, &amm_program),
+            vec![pool_1, pool_3, pool_2]
+        );
+        assert!(amm_swap_pools(&transaction, &router).is_empty());
+    }
+
+    #[test]
+    fn amm_trade_fee_falls_back_to_the_default_swap_fee() {
+        assert_eq!(amm_trade_fee(1_000_000, 25, 10_000), 2_500);
+        assert_eq!(amm_trade_fee(1_000_001, 25, 10_000), 2_501);
+        assert_eq!(amm_trade_fee(1_000_000, 1, 100), 10_000);
+        for (numerator, denominator) in [(25, 0), (0, 0), (10_000, 10_000), (20_000, 10_000)] {
+            assert_eq!(amm_trade_fee(1_000_000, numerator, denominator), 2_500);
+        }
+    }
+}
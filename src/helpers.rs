@@ -1,5 +1,6 @@
 //! Utility functions for environment parsing and keypair handling.
 
+use anyhow::anyhow;
 use solana_sdk::signature::Keypair;
 
 /// Parses a string of bytes (`"[150, 12, 123, ...]"`) into a Solana `Keypair`.
@@ -22,3 +23,15 @@ pub fn from_bytes_to_key_pair(env: String) -> Keypair {
         .collect();
     Keypair::try_from(bytes.as_slice()).expect("Error converting bytes to Keypair")
 }
+
+/// Fallible version of [`from_bytes_to_key_pair`].
+pub fn try_key_pair_from_bytes(env: &str) -> anyhow::Result<Keypair> {
+    let bytes = env
+        .trim()
+        .trim_matches(&['[', ']'][..])
+        .split(',')
+        .map(|s| s.trim().parse::<u8>())
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|e| anyhow!("expected bytes as `[1,2,...]`: {e}"))?;
+    Keypair::try_from(bytes.as_slice()).map_err(|e| anyhow!("{e}"))
+}
@@ -0,0 +1,225 @@
+//! OHLCV candles built from decoded pool trades.
+
+use crate::history::PoolTrade;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// One interval of trading on a pool. Prices are token 0 in token 1, in raw
+/// units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candle {
+    pub pool_id: Pubkey,
+    /// Unix timestamp of the start of the interval.
+    pub open_time: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Token 0 traded in the interval, both directions.
+    pub volume_0: u64,
+    /// Token 1 traded in the interval, both directions.
+    pub volume_1: u64,
+    pub trades: usize,
+}
+
+/// A candle with the slots its open and close prices come from, so trades
+/// pushed out of slot order still land on the right side.
+#[derive(Clone, Debug)]
+struct CandleState {
+    candle: Candle,
+    open_slot: u64,
+    close_slot: u64,
+}
+
+/// Aggregates trades of any number of pools into fixed-interval candles.
+///
+/// Trades can come from [`fetch_pool_trades`](crate::history::fetch_pool_trades)
+/// or be pushed one by one as they are observed. Open and close follow the slot
+/// of each trade; trades of the same slot are taken in the order they are
+/// pushed, so push backfilled pages oldest first.
+#[derive(Clone, Debug)]
+pub struct CandleBuilder {
+    interval_secs: i64,
+    candles: HashMap<Pubkey, BTreeMap<i64, CandleState>>,
+}
+
+impl CandleBuilder {
+    /// Builder of candles of `interval`, rounded down to whole seconds and at
+    /// least one second long.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval_secs: interval.as_secs().clamp(1, i64::MAX as u64) as i64,
+            candles: HashMap::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs as u64)
+    }
+
+    /// Add a trade to the candle of its interval. Returns `false` for trades
+    /// that cannot be placed: without a block time or without a price.
+    pub fn push(&mut self, trade: &PoolTrade) -> bool {
+        let (Some(block_time), Some(price)) = (trade.block_time, trade.price()) else {
+            return false;
+        };
+        let open_time = block_time.div_euclid(self.interval_secs) * self.interval_secs;
+        let state = self
+            .candles
+            .entry(trade.pool_id)
+            .or_default()
+            .entry(open_time)
+            .or_insert_with(|| CandleState {
+                candle: Candle {
+                    pool_id: trade.pool_id,
+                    open_time,
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    volume_0: 0,
+                    volume_1: 0,
+                    trades: 0,
+                },
+                open_slot: trade.slot,
+                close_slot: trade.slot,
+            });
+
+        let candle = &mut state.candle;
+        if trade.slot < state.open_slot {
+            candle.open = price;
+            state.open_slot = trade.slot;
+        }
+        if trade.slot >= state.close_slot {
+            candle.close = price;
+            state.close_slot = trade.slot;
+        }
+        candle.high = candle.high.max(price);
+        candle.low = candle.low.min(price);
+        candle.volume_0 = candle.volume_0.saturating_add(trade.amount_0);
+        candle.volume_1 = candle.volume_1.saturating_add(trade.amount_1);
+        candle.trades += 1;
+        true
+    }
+
+    /// Push every trade of `trades`. Returns the number of trades placed.
+    pub fn extend<'a>(&mut self, trades: impl IntoIterator<Item = &'a PoolTrade>) -> usize {
+        trades.into_iter().filter(|trade| self.push(trade)).count()
+    }
+
+    /// Candles of `pool_id`, oldest first. Intervals without trades are
+    /// skipped.
+    pub fn candles(&self, pool_id: &Pubkey) -> Vec<Candle> {
+        self.candles
+            .get(pool_id)
+            .map(|candles| candles.values().map(|state| state.candle.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Latest candle of `pool_id`, which may still be receiving trades.
+    pub fn last_candle(&self, pool_id: &Pubkey) -> Option<&Candle> {
+        self.candles
+            .get(pool_id)?
+            .values()
+            .next_back()
+            .map(|state| &state.candle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Signature;
+
+    /// Trade of 100 token 0 at `price`.
+    fn trade(pool_id: Pubkey, slot: u64, block_time: i64, price: u64) -> PoolTrade {
+        PoolTrade {
+            signature: Signature::default(),
+            slot,
+            block_time: Some(block_time),
+            pool_id,
+            trader: None,
+            mint_0: Pubkey::new_unique(),
+            mint_1: Pubkey::new_unique(),
+            zero_for_one: true,
+            amount_0: 100,
+            amount_1: 100 * price,
+        }
+    }
+
+    #[test]
+    fn trades_of_different_intervals_make_separate_candles() {
+        let pool_id = Pubkey::new_unique();
+        let mut builder = CandleBuilder::new(Duration::from_secs(60));
+        let trades = [
+            trade(pool_id, 1, 0, 1),
+            trade(pool_id, 2, 59, 2),
+            trade(pool_id, 3, 60, 3),
+        ];
+        assert_eq!(builder.extend(&trades), 3);
+        let candles = builder.candles(&pool_id);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(
+            candles[0],
+            Candle {
+                pool_id,
+                open_time: 0,
+                open: 1.0,
+                high: 2.0,
+                low: 1.0,
+                close: 2.0,
+                volume_0: 200,
+                volume_1: 300,
+                trades: 2,
+            }
+        );
+        assert_eq!(candles[1].open_time, 60);
+        assert_eq!((candles[1].open, candles[1].close), (3.0, 3.0));
+        assert_eq!(builder.last_candle(&pool_id), Some(&candles[1]));
+    }
+
+    #[test]
+    fn open_and_close_follow_the_slot_of_trades_pushed_out_of_order() {
+        let pool_id = Pubkey::new_unique();
+        let mut builder = CandleBuilder::new(Duration::from_secs(60));
+        builder.extend(&[
+            trade(pool_id, 20, 10, 2),
+            trade(pool_id, 10, 5, 1),
+            trade(pool_id, 30, 20, 3),
+            trade(pool_id, 15, 8, 5),
+        ]);
+        let candle = builder.last_candle(&pool_id).unwrap();
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close),
+            (1.0, 5.0, 1.0, 3.0)
+        );
+        assert_eq!(candle.trades, 4);
+    }
+
+    #[test]
+    fn trades_of_the_same_slot_keep_the_push_order() {
+        let pool_id = Pubkey::new_unique();
+        let mut builder = CandleBuilder::new(Duration::from_secs(60));
+        builder.extend(&[
+            trade(pool_id, 10, 5, 1),
+            trade(pool_id, 10, 5, 2),
+            trade(pool_id, 10, 5, 3),
+        ]);
+        let candle = builder.last_candle(&pool_id).unwrap();
+        assert_eq!((candle.open, candle.close), (1.0, 3.0));
+    }
+
+    #[test]
+    fn trades_without_block_time_or_price_are_not_placed() {
+        let pool_id = Pubkey::new_unique();
+        let mut builder = CandleBuilder::new(Duration::from_secs(60));
+        let mut no_time = trade(pool_id, 1, 0, 1);
+        no_time.block_time = None;
+        let mut no_price = trade(pool_id, 1, 0, 1);
+        no_price.amount_0 = 0;
+        assert!(!builder.push(&no_time));
+        assert!(!builder.push(&no_price));
+        assert!(builder.candles(&pool_id).is_empty());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod trades;
+pub use trades::*;
+pub mod candles;
+pub use candles::*;
@@ -0,0 +1,281 @@
+//! Trade history of a pool rebuilt from its recent transactions.
+//!
+//! Signatures touching the pool are paged with `getSignaturesForAddress` and
+//! every successful transaction is fetched and decoded:
+//!
+//! - CLMM swaps are read from the `SwapEvent`s logged by the program.
+//! - AMM v4 swaps log no pool id, so they are rebuilt from the balance changes
+//!   of the pool vaults. Several swaps through the same pool in a single
+//!   transaction are reported as one net trade.
+
+use crate::amm::client::{AmmSwapClient, LiquidityStateLayoutV4};
+use crate::clmm::parse_swap_events;
+use crate::common::deserialize_anchor_account;
+use crate::config::ClusterConfig;
+use crate::signer::SwapSigner;
+use crate::states::PoolState;
+use anyhow::anyhow;
+use borsh::BorshDeserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status_client_types::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiLoadedAddresses, UiMessage,
+    UiTransactionEncoding, UiTransactionTokenBalance,
+};
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+/// Largest page `getSignaturesForAddress` returns.
+const SIGNATURES_PAGE_SIZE: usize = 1000;
+
+/// A swap through a pool, oriented by the pool's token order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolTrade {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Unix timestamp of the block, when the node reports it.
+    pub block_time: Option<i64>,
+    pub pool_id: Pubkey,
+    /// Fee payer of the transaction.
+    pub trader: Option<Pubkey>,
+    /// Token 0 of a CLMM pool, base mint of an AMM pool.
+    pub mint_0: Pubkey,
+    /// Token 1 of a CLMM pool, quote mint of an AMM pool.
+    pub mint_1: Pubkey,
+    /// `true` when token 0 was sold for token 1.
+    pub zero_for_one: bool,
+    /// Token 0 entering or leaving the pool.
+    pub amount_0: u64,
+    /// Token 1 entering or leaving the pool.
+    pub amount_1: u64,
+}
+
+impl PoolTrade {
+    pub fn amount_in(&self) -> u64 {
+        if self.zero_for_one {
+            self.amount_0
+        } else {
+            self.amount_1
+        }
+    }
+
+    pub fn amount_out(&self) -> u64 {
+        if self.zero_for_one {
+            self.amount_1
+        } else {
+            self.amount_0
+        }
+    }
+
+    /// Price of token 0 in token 1, in raw units. `None` without token 0.
+    pub fn price(&self) -> Option<f64> {
+        (self.amount_0 > 0).then(|| self.amount_1 as f64 / self.amount_0 as f64)
+    }
+}
+
+/// What identifies the trades of a pool in a transaction.
+enum TradeDecoder {
+    Amm {
+        base_vault: Pubkey,
+        quote_vault: Pubkey,
+        base_mint: Pubkey,
+        quote_mint: Pubkey,
+    },
+    Clmm {
+        mint_0: Pubkey,
+        mint_1: Pubkey,
+    },
+}
+
+impl TradeDecoder {
+    async fn load(
+        rpc_client: &RpcClient,
+        cluster: &ClusterConfig,
+        pool_id: &Pubkey,
+    ) -> anyhow::Result<Self> {
+        let account = rpc_client.get_account(pool_id).await?;
+        if account.owner == cluster.amm_v4 {
+            let state = LiquidityStateLayoutV4::try_from_slice(&account.data)
+                .map_err(|e| anyhow!("Failed to decode market state: {:?}", e))?;
+            Ok(Self::Amm {
+                base_vault: state.base_vault,
+                quote_vault: state.quote_vault,
+                base_mint: state.base_mint,
+                quote_mint: state.quote_mint,
+            })
+        } else if account.owner == cluster.clmm {
+            let state: PoolState = deserialize_anchor_account(&account)?;
+            Ok(Self::Clmm {
+                mint_0: Pubkey::from(state.token_mint_0.to_bytes()),
+                mint_1: Pubkey::from(state.token_mint_1.to_bytes()),
+            })
+        } else {
+            Err(anyhow!("Pool {} is owned by unsupported program {}", pool_id, account.owner))
+        }
+    }
+
+    fn decode(
+        &self,
+        pool_id: &Pubkey,
+        signature: Signature,
+        transaction: EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Vec<PoolTrade> {
+        let Some(meta) = transaction.transaction.meta else {
+            return Vec::new();
+        };
+        if meta.err.is_some() {
+            return Vec::new();
+        }
+        let mut account_keys = match transaction.transaction.transaction {
+            EncodedTransaction::Json(ui_transaction) => match ui_transaction.message {
+                UiMessage::Raw(message) => message.account_keys,
+                UiMessage::Parsed(message) => {
+                    message.account_keys.into_iter().map(|key| key.pubkey).collect()
+                }
+            },
+            _ => Vec::new(),
+        };
+        let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.into();
+        if let Some(loaded_addresses) = loaded_addresses {
+            account_keys.extend(loaded_addresses.writable);
+            account_keys.extend(loaded_addresses.readonly);
+        }
+        let trade = |zero_for_one, amount_0, amount_1, mint_0, mint_1| PoolTrade {
+            signature,
+            slot: transaction.slot,
+            block_time: transaction.block_time,
+            pool_id: *pool_id,
+            trader: account_keys.first().and_then(|key| Pubkey::from_str(key).ok()),
+            mint_0,
+            mint_1,
+            zero_for_one,
+            amount_0,
+            amount_1,
+        };
+
+        match self {
+            Self::Clmm { mint_0, mint_1 } => {
+                let log_messages: Option<Vec<String>> = meta.log_messages.into();
+                parse_swap_events(&log_messages.unwrap_or_default())
+                    .into_iter()
+                    .filter(|event| event.pool_state.to_bytes() == pool_id.to_bytes())
+                    .map(|event| {
+                        trade(event.zero_for_one, event.amount_0, event.amount_1, *mint_0, *mint_1)
+                    })
+                    .collect()
+            }
+            Self::Amm {
+                base_vault,
+                quote_vault,
+                base_mint,
+                quote_mint,
+            } => {
+                let pre_balances: Option<Vec<UiTransactionTokenBalance>> =
+                    meta.pre_token_balances.into();
+                let post_balances: Option<Vec<UiTransactionTokenBalance>> =
+                    meta.post_token_balances.into();
+                let (pre_balances, post_balances) =
+                    (pre_balances.unwrap_or_default(), post_balances.unwrap_or_default());
+                let delta = |vault: &Pubkey| -> Option<i128> {
+                    let vault = vault.to_string();
+                    let index = account_keys.iter().position(|key| *key == vault)?;
+                    let balance = |balances: &[UiTransactionTokenBalance]| -> i128 {
+                        balances
+                            .iter()
+                            .find(|balance| balance.account_index as usize == index)
+                            .and_then(|balance| balance.ui_token_amount.amount.parse().ok())
+                            .unwrap_or_default()
+                    };
+                    Some(balance(&post_balances) - balance(&pre_balances))
+                };
+                let (Some(base_delta), Some(quote_delta)) = (delta(base_vault), delta(quote_vault))
+                else {
+                    return Vec::new();
+                };
+                // A swap moves the vaults in opposite directions; deposits and
+                // withdrawals move both the same way.
+                let zero_for_one = match (base_delta.signum(), quote_delta.signum()) {
+                    (1, -1) => true,
+                    (-1, 1) => false,
+                    _ => return Vec::new(),
+                };
+                vec![trade(
+                    zero_for_one,
+                    base_delta.unsigned_abs() as u64,
+                    quote_delta.unsigned_abs() as u64,
+                    *base_mint,
+                    *quote_mint,
+                )]
+            }
+        }
+    }
+}
+
+/// Fetch up to `limit` recent trades of an AMM v4 or CLMM pool, newest first.
+///
+/// Starts before the `before` signature when given, e.g. the signature of the
+/// oldest trade of the previous call to continue further back. Transactions
+/// that fail to load are skipped with a warning.
+pub async fn fetch_pool_trades(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+    limit: usize,
+    before: Option<Signature>,
+) -> anyhow::Result<Vec<PoolTrade>> {
+    let rpc_client = client.rpc_client();
+    let decoder = TradeDecoder::load(rpc_client, client.cluster_config(), pool_id).await?;
+    let mut trades = Vec::new();
+    let mut before = before;
+    while trades.len() < limit {
+        let statuses = rpc_client
+            .get_signatures_for_address_with_config(
+                pool_id,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(SIGNATURES_PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        let Some(last) = statuses.last() else {
+            break;
+        };
+        before = Some(Signature::from_str(&last.signature)?);
+
+        for status in statuses.iter().filter(|status| status.err.is_none()) {
+            let signature = Signature::from_str(&status.signature)?;
+            let transaction = match rpc_client
+                .get_transaction_with_config(
+                    &signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Json),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await
+            {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    warn!("Skipping transaction {signature}: {e:?}");
+                    continue;
+                }
+            };
+            trades.extend(decoder.decode(pool_id, signature, transaction));
+            if trades.len() >= limit {
+                break;
+            }
+        }
+        if statuses.len() < SIGNATURES_PAGE_SIZE {
+            break;
+        }
+    }
+    trades.truncate(limit);
+    debug!("Fetched {} trades of {}", trades.len(), pool_id);
+    Ok(trades)
+}
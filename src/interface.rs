@@ -1,11 +1,11 @@
 //! Types for deserializing JSON responses from the Raydium HTTP API.
 
 use crate::states::{AmmConfig, TickArrayBitmapExtension, TickArrayState};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use solana_account::Account;
 use solana_address::Address;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{Display, Formatter};
 
 /// Response from `/pools/info/ids` for concentrated (CLMM) pools.
@@ -14,9 +14,9 @@ pub struct ClmmSinglePoolInfo {
     pub data: Vec<ClmmPool>,
 }
 
-/// Period‑specific stats for a pool.
-#[derive(Deserialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
+/// Period‑specific stats for a pool. Missing stats are zero.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct PoolPeriod {
     /// Trading volume in base token.
     pub volume: f64,
@@ -37,7 +37,7 @@ pub struct PoolPeriod {
 }
 
 /// Info about a default reward stream.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RewardDefault {
     pub mint: Mint,
@@ -47,7 +47,7 @@ pub struct RewardDefault {
 }
 
 /// Token mint metadata.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Mint {
     pub chain_id: u32,
@@ -57,12 +57,14 @@ pub struct Mint {
     pub symbol: String,
     pub name: String,
     pub decimals: u32,
+    #[serde(default)]
     pub tags: Vec<String>,
+    #[serde(default)]
     pub extensions: MintExtensions,
 }
 
 /// Empty placeholder for mint extensions.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct MintExtensions {}
 
@@ -74,6 +76,50 @@ pub struct PoolKeys<PoolType: Clone> {
     pub data: Vec<PoolType>,
 }
 
+/// Address lookup table of a pool of any type, read from its
+/// `/pools/key/ids` entry.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolLookupTable {
+    pub id: String,
+    pub lookup_table_account: Option<String>,
+}
+
+/// Response from `/mint/price`: USD price per mint address.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MintPriceResponse {
+    pub id: String,
+    pub success: bool,
+    /// Prices are returned as strings; unknown mints may be missing or null.
+    pub data: HashMap<String, Value>,
+}
+
+/// Response from `/mint/ids`: metadata of mints, in the order requested.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MintInfoResponse {
+    pub id: String,
+    pub success: bool,
+    /// `None` for mints the API does not list.
+    pub data: Vec<Option<Mint>>,
+}
+
+/// Response from `/main/version`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiVersionResponse {
+    pub id: String,
+    pub success: bool,
+    pub data: ApiVersion,
+}
+
+/// Versions of the Raydium API, e.g. `V3.0.1`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiVersion {
+    /// Current version.
+    pub latest: String,
+    /// Oldest version clients should still use.
+    pub least: String,
+}
+
 /// On‑chain account addresses needed for swaps.
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -109,6 +155,8 @@ pub struct Vault {
     pub b: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
 pub enum PoolType {
     Standard,
     Concentrated,
@@ -139,13 +187,62 @@ pub struct ClmmPoolInfosResponse {
 #[derive(Deserialize, Debug)]
 pub struct ClmmManyPoolsInfo {
     pub count: Option<u32>,
-    pub data: Vec<Value>,
+    /// Pools of the page, decoded one by one: an entry that is not a
+    /// [`ClmmPool`] (e.g. a pool type this crate does not know) does not fail
+    /// the others. Unknown fields are ignored.
+    #[serde(deserialize_with = "deserialize_pool_entries")]
+    pub data: Vec<Result<ClmmPool, PoolDecodeError>>,
     #[serde(rename = "hasNextPage")]
     pub has_next_page: bool,
 }
 
+/// Entry of a pool list that could not be decoded as a [`ClmmPool`].
+#[derive(Debug, Clone)]
+pub struct PoolDecodeError {
+    /// `id` of the entry, if it has one.
+    pub id: Option<String>,
+    /// `programId` of the entry, if it has one.
+    pub program_id: Option<String>,
+    pub error: String,
+    /// The entry as returned by the API.
+    pub raw: Value,
+}
+
+impl Display for PoolDecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pool {} of program {} could not be decoded: {}",
+            self.id.as_deref().unwrap_or("without id"),
+            self.program_id.as_deref().unwrap_or("unknown"),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for PoolDecodeError {}
+
+fn deserialize_pool_entries<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Result<ClmmPool, PoolDecodeError>>, D::Error> {
+    let entries = Vec::<Value>::deserialize(deserializer)?;
+    Ok(entries.into_iter().map(decode_pool_entry).collect())
+}
+
+fn decode_pool_entry(raw: Value) -> Result<ClmmPool, PoolDecodeError> {
+    ClmmPool::deserialize(&raw).map_err(|e| {
+        let field = |name: &str| raw.get(name).and_then(Value::as_str).map(str::to_string);
+        PoolDecodeError {
+            id: field("id"),
+            program_id: field("programId"),
+            error: e.to_string(),
+            raw,
+        }
+    })
+}
+
 /// CLMM‑specific pool config block.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone)]
 pub struct ClmmConfig {
@@ -159,7 +256,7 @@ pub struct ClmmConfig {
     pub default_range_point: Option<Vec<f64>>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ClmmPool {
     /// Type of pool, e.g. "Concentrated".
@@ -215,8 +312,9 @@ pub struct ClmmSwapParams {
     /// The token of user want to swap from.
     pub user_input_token: solana_pubkey::Pubkey,
     /// The token of user want to swap to.
-    /// If none is given, the account will be ATA account.
-    pub user_output_token: solana_pubkey::Pubkey,
+    /// If none is given, the account will be ATA account, created in the
+    /// swap transaction when missing.
+    pub user_output_token: Option<solana_pubkey::Pubkey>,
     /// The amount specified of user want to swap from or to token.
     pub amount_specified: u64,
     /// The float price of the pool that can be swaped to.
@@ -225,6 +323,9 @@ pub struct ClmmSwapParams {
     pub base_out: bool,
     /// Slippage for the swap in bps
     pub slippage_bps: u64,
+    /// Largest accepted price impact in bps; the quote fails with
+    /// `SwapError::PriceImpactExceeded` above it. Unlimited when `None`.
+    pub max_price_impact_bps: Option<u64>,
 }
 
 pub type Rsps = Vec<Option<Account>>;
@@ -239,7 +340,8 @@ pub struct CalculateSwapChangeParams {
     pub mint1_account: Option<Account>,
     pub mint0_token_program: Address,
     pub mint1_token_program: Address,
-    pub tickarray_bitmap_extension_state: TickArrayBitmapExtension,
+    /// `None` when the pool has no bitmap extension account.
+    pub tickarray_bitmap_extension_state: Option<TickArrayBitmapExtension>,
     pub zero_for_one: bool,
     pub amount_specified: u64,
     pub amm_config_state: AmmConfig,
@@ -285,7 +387,8 @@ impl Display for PoolSortField {
 
 #[cfg(test)]
 mod tests {
-    use super::PoolSortField;
+    use super::{ClmmManyPoolsInfo, PoolSortField};
+    use serde_json::json;
 
     #[test]
     fn pool_sort_field_display_outputs_expected_strings() {
@@ -306,4 +409,45 @@ mod tests {
             assert_eq!(field.to_string(), *expected);
         }
     }
+
+    #[test]
+    fn pool_list_decodes_entries_one_by_one() {
+        let mint = |address: &str| {
+            json!({
+                "chainId": 101,
+                "address": address,
+                "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                "symbol": "TKN",
+                "name": "Token",
+                "decimals": 6,
+                "unknownField": true
+            })
+        };
+        let info: ClmmManyPoolsInfo = serde_json::from_value(json!({
+            "count": 2,
+            "data": [
+                {
+                    "programId": "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+                    "id": "pool-a",
+                    "mintA": mint("mint-a"),
+                    "mintB": mint("mint-b"),
+                    "day": { "volume": 10.0 }
+                },
+                { "programId": "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C", "id": "pool-b" }
+            ],
+            "hasNextPage": false
+        }))
+        .unwrap();
+
+        let pool = info.data[0].as_ref().unwrap();
+        assert_eq!(pool.id, "pool-a");
+        assert!(pool.mint_a.tags.is_empty());
+        assert_eq!(pool.day.as_ref().unwrap().volume, 10.0);
+        let error = info.data[1].as_ref().unwrap_err();
+        assert_eq!(error.id.as_deref(), Some("pool-b"));
+        assert_eq!(
+            error.program_id.as_deref(),
+            Some("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C")
+        );
+    }
 }
@@ -0,0 +1,259 @@
+use crate::consts::{JITO_BLOCK_ENGINE_URL, JITO_TIP_ACCOUNTS, JITO_TIP_FLOOR_URL};
+use crate::jito::{TipFloor, TipStrategy};
+use anyhow::{Context, anyhow};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use solana_system_interface::instruction::transfer;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+#[derive(Deserialize, Debug)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BundleStatuses {
+    value: Vec<Option<BundleStatus>>,
+}
+
+/// Status of a landed bundle, from `getBundleStatuses`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct BundleStatus {
+    pub bundle_id: String,
+    /// Signatures of the bundle transactions.
+    pub transactions: Vec<String>,
+    pub slot: u64,
+    /// `processed`, `confirmed` or `finalized`.
+    pub confirmation_status: Option<String>,
+    /// `{"Ok": null}` when the bundle executed.
+    pub err: Value,
+}
+
+/// Bundle sent with [`JitoClient::send_bundle_and_confirm`] once landed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LandedBundle {
+    pub bundle_id: String,
+    pub slot: u64,
+}
+
+/// Minimal client for the Jito block engine.
+pub struct JitoClient {
+    reqwest_client: Client,
+    block_engine_url: String,
+    tip_floor_url: String,
+    tip_strategy: TipStrategy,
+}
+
+impl Default for JitoClient {
+    fn default() -> Self {
+        Self::new(JITO_BLOCK_ENGINE_URL)
+    }
+}
+
+impl JitoClient {
+    /// Creates a Jito client for the given block engine, e.g.
+    /// `https://ny.mainnet.block-engine.jito.wtf`.
+    pub fn new(block_engine_url: impl Into<String>) -> Self {
+        Self {
+            reqwest_client: Client::new(),
+            block_engine_url: block_engine_url.into(),
+            tip_floor_url: JITO_TIP_FLOOR_URL.to_string(),
+            tip_strategy: TipStrategy::default(),
+        }
+    }
+
+    /// Default tip strategy used when a swap does not provide its own.
+    pub fn with_tip_strategy(mut self, tip_strategy: TipStrategy) -> Self {
+        self.tip_strategy = tip_strategy;
+        self
+    }
+
+    pub fn with_tip_floor_url(mut self, tip_floor_url: impl Into<String>) -> Self {
+        self.tip_floor_url = tip_floor_url.into();
+        self
+    }
+
+    pub fn block_engine_url(&self) -> &str {
+        &self.block_engine_url
+    }
+
+    pub fn tip_strategy(&self) -> &TipStrategy {
+        &self.tip_strategy
+    }
+
+    /// Fetch the latest landed tip percentiles.
+    pub async fn fetch_tip_floor(&self) -> anyhow::Result<TipFloor> {
+        let floors: Vec<TipFloor> = self
+            .reqwest_client
+            .get(&self.tip_floor_url)
+            .send()
+            .await
+            .with_context(|| format!("Jito GET failed for {}", self.tip_floor_url))?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Failed to parse Jito tip floor response")?;
+        floors
+            .into_iter()
+            .next()
+            .ok_or(anyhow!("Jito tip floor response is empty"))
+    }
+
+    /// Resolve the tip in lamports for a trade worth `trade_value_lamports`.
+    ///
+    /// `strategy` overrides the client default for a single swap. Failing to
+    /// fetch the tip floor is not fatal: the strategy fallback is used.
+    pub async fn tip_lamports(
+        &self,
+        strategy: Option<&TipStrategy>,
+        trade_value_lamports: u64,
+    ) -> u64 {
+        let strategy = strategy.unwrap_or(&self.tip_strategy);
+        let tip_floor = if strategy.needs_tip_floor() {
+            match self.fetch_tip_floor().await {
+                Ok(tip_floor) => Some(tip_floor),
+                Err(e) => {
+                    warn!("Failed to fetch Jito tip floor, using fallback tip: {e:?}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let tip = strategy.resolve(trade_value_lamports, tip_floor.as_ref());
+        debug!("Resolved Jito tip {tip} lamports");
+        tip
+    }
+
+    /// Submit a single signed transaction through the block engine
+    /// `sendTransaction` endpoint.
+    pub async fn send_transaction(&self, transaction: &Transaction) -> anyhow::Result<Signature> {
+        let serialized = bincode::serialize(transaction)?;
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendTransaction",
+            "params": [BASE64_STANDARD.encode(serialized), { "encoding": "base64" }],
+        });
+        let result: String = self.post_json_rpc("/api/v1/transactions", body).await?;
+        Ok(Signature::from_str(&result)?)
+    }
+
+    /// Submit signed transactions as a bundle through the block engine
+    /// `sendBundle` endpoint, returning the bundle id. The transactions land
+    /// together, in order, or not at all; one of them must pay a tip (see
+    /// [`Self::tip_instruction`]).
+    pub async fn send_bundle(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> anyhow::Result<String> {
+        let encoded = transactions
+            .iter()
+            .map(|transaction| Ok(BASE64_STANDARD.encode(bincode::serialize(transaction)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendBundle",
+            "params": [encoded, { "encoding": "base64" }],
+        });
+        self.post_json_rpc("/api/v1/bundles", body).await
+    }
+
+    /// Status of the bundle `bundle_id`, `None` until it lands.
+    pub async fn get_bundle_status(&self, bundle_id: &str) -> anyhow::Result<Option<BundleStatus>> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getBundleStatuses",
+            "params": [[bundle_id]],
+        });
+        let statuses: BundleStatuses = self.post_json_rpc("/api/v1/bundles", body).await?;
+        Ok(statuses.value.into_iter().flatten().next())
+    }
+
+    /// [`Self::send_bundle`], then poll its status every `poll_interval`
+    /// until it lands or `timeout` elapses.
+    pub async fn send_bundle_and_confirm(
+        &self,
+        transactions: &[VersionedTransaction],
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> anyhow::Result<LandedBundle> {
+        let started = Instant::now();
+        let bundle_id = self.send_bundle(transactions).await?;
+        debug!("Sent bundle {bundle_id}, waiting for it to land");
+
+        loop {
+            if let Some(status) = self.get_bundle_status(&bundle_id).await? {
+                if status.err != json!({ "Ok": null }) {
+                    return Err(anyhow!("bundle {} failed: {}", bundle_id, status.err));
+                }
+                info!("Bundle {bundle_id} landed in slot {}", status.slot);
+                return Ok(LandedBundle {
+                    bundle_id,
+                    slot: status.slot,
+                });
+            }
+            if started.elapsed() > timeout {
+                return Err(anyhow!(
+                    "bundle {} did not land within {:?}",
+                    bundle_id,
+                    timeout
+                ));
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    async fn post_json_rpc<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: Value,
+    ) -> anyhow::Result<T> {
+        let url = format!("{}{}", self.block_engine_url, path);
+        let resp: JsonRpcResponse<T> = self
+            .reqwest_client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Jito POST failed for {}", url))?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse Jito response from {}", url))?;
+        match (resp.result, resp.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(error)) => Err(anyhow!("Jito error {}: {}", error.code, error.message)),
+            (None, None) => Err(anyhow!("Jito response from {} has no result", url)),
+        }
+    }
+
+    /// Transfer instruction paying `lamports` to one of the Jito tip accounts.
+    pub fn tip_instruction(payer: &Pubkey, lamports: u64) -> Instruction {
+        let index = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as usize)
+            .unwrap_or_default()
+            % JITO_TIP_ACCOUNTS.len();
+        let tip_account = Pubkey::from_str_const(JITO_TIP_ACCOUNTS[index]);
+        transfer(payer, &tip_account, lamports)
+    }
+}
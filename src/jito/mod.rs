@@ -0,0 +1,4 @@
+pub mod client;
+pub use client::*;
+pub mod tip;
+pub use tip::*;
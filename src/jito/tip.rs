@@ -0,0 +1,182 @@
+//! Tip sizing for Jito bundle submission.
+
+use serde::Deserialize;
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// Landed tip percentile reported by the Jito tip floor API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipPercentile {
+    P25,
+    P50,
+    P75,
+    P95,
+    P99,
+    /// Exponential moving average of the 50th percentile.
+    Ema50,
+}
+
+/// How the tip attached to a bundle is computed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TipStrategy {
+    /// Always tip the same amount of lamports.
+    Fixed(u64),
+    /// Tip a share of the trade value (in lamports), clamped to a range.
+    TradeValueBps {
+        bps: u64,
+        min_lamports: u64,
+        max_lamports: u64,
+    },
+    /// Tip at a percentile of recently landed tips, clamped to a maximum.
+    /// `fallback_lamports` is used when the tip floor cannot be fetched.
+    RecentPercentile {
+        percentile: TipPercentile,
+        max_lamports: u64,
+        fallback_lamports: u64,
+    },
+}
+
+impl Default for TipStrategy {
+    fn default() -> Self {
+        TipStrategy::Fixed(10_000)
+    }
+}
+
+/// One entry of the `/api/v1/bundles/tip_floor` response. Values are in SOL.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TipFloor {
+    pub time: Option<String>,
+    pub landed_tips_25th_percentile: f64,
+    pub landed_tips_50th_percentile: f64,
+    pub landed_tips_75th_percentile: f64,
+    pub landed_tips_95th_percentile: f64,
+    pub landed_tips_99th_percentile: f64,
+    pub ema_landed_tips_50th_percentile: f64,
+}
+
+impl TipFloor {
+    /// Tip at the given percentile, in lamports.
+    pub fn lamports(&self, percentile: TipPercentile) -> u64 {
+        let sol = match percentile {
+            TipPercentile::P25 => self.landed_tips_25th_percentile,
+            TipPercentile::P50 => self.landed_tips_50th_percentile,
+            TipPercentile::P75 => self.landed_tips_75th_percentile,
+            TipPercentile::P95 => self.landed_tips_95th_percentile,
+            TipPercentile::P99 => self.landed_tips_99th_percentile,
+            TipPercentile::Ema50 => self.ema_landed_tips_50th_percentile,
+        };
+        (sol * LAMPORTS_PER_SOL).ceil() as u64
+    }
+}
+
+impl TipStrategy {
+    /// Whether resolving this strategy needs the current tip floor.
+    pub fn needs_tip_floor(&self) -> bool {
+        matches!(self, TipStrategy::RecentPercentile { .. })
+    }
+
+    /// Resolve the tip in lamports.
+    ///
+    /// - `trade_value_lamports`: value of the trade expressed in lamports.
+    /// - `tip_floor`: latest tip floor, if it could be fetched.
+    pub fn resolve(&self, trade_value_lamports: u64, tip_floor: Option<&TipFloor>) -> u64 {
+        match self {
+            TipStrategy::Fixed(lamports) => *lamports,
+            TipStrategy::TradeValueBps {
+                bps,
+                min_lamports,
+                max_lamports,
+            } => {
+                let tip = (trade_value_lamports as u128 * *bps as u128 / 10_000) as u64;
+                tip.clamp(*min_lamports, (*max_lamports).max(*min_lamports))
+            }
+            TipStrategy::RecentPercentile {
+                percentile,
+                max_lamports,
+                fallback_lamports,
+            } => tip_floor
+                .map(|floor| floor.lamports(*percentile))
+                .unwrap_or(*fallback_lamports)
+                .min(*max_lamports),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Percentiles are powers of two of SOL, exact in `f64`.
+    fn tip_floor() -> TipFloor {
+        TipFloor {
+            time: None,
+            landed_tips_25th_percentile: 0.000244140625,
+            landed_tips_50th_percentile: 0.00048828125,
+            landed_tips_75th_percentile: 0.0009765625,
+            landed_tips_95th_percentile: 0.001953125,
+            landed_tips_99th_percentile: 0.00390625,
+            ema_landed_tips_50th_percentile: 0.0001220703125,
+        }
+    }
+
+    #[test]
+    fn tip_floor_percentiles_are_rounded_up_to_lamports() {
+        let floor = tip_floor();
+        assert_eq!(floor.lamports(TipPercentile::P25), 244_141);
+        assert_eq!(floor.lamports(TipPercentile::P50), 488_282);
+        assert_eq!(floor.lamports(TipPercentile::P75), 976_563);
+        assert_eq!(floor.lamports(TipPercentile::P95), 1_953_125);
+        assert_eq!(floor.lamports(TipPercentile::P99), 3_906_250);
+        assert_eq!(floor.lamports(TipPercentile::Ema50), 122_071);
+    }
+
+    #[test]
+    fn fixed_tip_ignores_the_trade_and_the_tip_floor() {
+        let strategy = TipStrategy::Fixed(5_000);
+        assert_eq!(strategy.resolve(1_000_000_000, Some(&tip_floor())), 5_000);
+        assert!(!strategy.needs_tip_floor());
+    }
+
+    #[test]
+    fn trade_value_tip_is_clamped_to_its_range() {
+        let strategy = TipStrategy::TradeValueBps {
+            bps: 10,
+            min_lamports: 1_000,
+            max_lamports: 100_000,
+        };
+        assert_eq!(strategy.resolve(10_000_000, None), 10_000);
+        assert_eq!(strategy.resolve(100_000, None), 1_000);
+        assert_eq!(strategy.resolve(u64::MAX, None), 100_000);
+    }
+
+    #[test]
+    fn trade_value_tip_with_max_below_min_tips_the_min() {
+        let strategy = TipStrategy::TradeValueBps {
+            bps: 10,
+            min_lamports: 50_000,
+            max_lamports: 1_000,
+        };
+        assert_eq!(strategy.resolve(0, None), 50_000);
+        assert_eq!(strategy.resolve(u64::MAX, None), 50_000);
+    }
+
+    #[test]
+    fn percentile_tip_is_capped_and_falls_back_without_a_tip_floor() {
+        let strategy = TipStrategy::RecentPercentile {
+            percentile: TipPercentile::P75,
+            max_lamports: 500_000,
+            fallback_lamports: 20_000,
+        };
+        assert!(strategy.needs_tip_floor());
+        assert_eq!(strategy.resolve(0, Some(&tip_floor())), 500_000);
+        assert_eq!(strategy.resolve(0, None), 20_000);
+
+        let strategy = TipStrategy::RecentPercentile {
+            percentile: TipPercentile::P25,
+            max_lamports: 500_000,
+            fallback_lamports: 1_000_000,
+        };
+        assert_eq!(strategy.resolve(0, Some(&tip_floor())), 244_141);
+        assert_eq!(strategy.resolve(0, None), 500_000);
+    }
+}
@@ -2,10 +2,62 @@
 //!
 //! This crate provides:
 //! - Retrieval of on‑chain and off‑chain pool data (`fetch_pool_info`, `fetch_pool_by_id`, etc.)
+//! - A typed client for the Raydium v3 HTTP API (`api::RaydiumApiClient`).
+//! - Paginated pool listing of a pair (`AmmSwapClient::fetch_all_pools`).
 //! - Computation of swap quotes with fee and slippage handling (`compute_amount_out`, `compute_amount_in`).
-//! - Execution of swaps against a given pool (`swap_amm`, `swap_clmm`).
+//! - Execution of swaps against a given pool (`swap_amm`, `swap_amm_base_out`, `swap_clmm`).
+//! - A builder for AMM v4 swaps checked against the pool keys (`AmmSwapClient::swap`).
+//! - Jito bundle submission of AMM v4 swaps with a tip (`SwapBuilder::send_via_jito`).
+//! - Sending without waiting, confirmed later through a handle (`SwapBuilder::submit`), and
+//!   resending on blockhash expiry (`execution::SendOptions`).
+//! - One-call swaps by mint pair on the best AMM v4 or CLMM pool (`AmmSwapClient::swap_token`).
+//! - Actual amounts, fees and pool price of confirmed swaps from their logs
+//!   (`AmmSwapClient::get_swap_result`).
+//! - Exact wSOL wrapping and unwrapping around swaps (`wrap_sol`, `unwrap_sol`).
 //! - Support for both standard AMM v4 pools and concentrated‑liquidity (CLMM) pools via
 //!   `PoolType::Standard` and `PoolType::Concentrated`.
+//! - Client configuration from environment variables (`config::ClientConfig::from_env`).
+//! - Dry runs simulating transactions instead of sending them (`execution::ExecutionMode`).
+//! - Concurrent price monitoring of many pools (`monitor::PriceMonitor`).
+//! - Live CLMM pool state over WebSocket for RPC-free quotes (`monitor::PoolWatcher`).
+//! - Fee-aware pool selection (`router::select_best_pool`).
+//! - Best pool of a pair from on-chain quotes of AMM v4, CLMM and CPMM pools
+//!   (`router::find_best_pool`).
+//! - Quotes of many pools from batched account reads (`AmmSwapClient::quote_many`).
+//! - AMM v4 versus CLMM execution reports for a pair (`router::compare_pools`).
+//! - Order sizing under a price impact cap (`router::max_size_for_impact`).
+//! - Multi-hop swaps for pairs without a direct pool (`router::find_routes`).
+//! - Prioritized, coalesced pool reads for concurrent quoting (`router::QuoteScheduler`).
+//! - Cached pool accounts with per-account TTLs for frequent quoting (`common::AccountCache`).
+//! - Detection of stale CLMM pool states and tick arrays before quoting (`clmm::StalenessPolicy`).
+//! - Reads pinned to the slot of a confirmed swap (`execution::ReadAfterWrite`).
+//! - Signing through a wallet, remote signer or multisig (`signer::SwapSigner`).
+//! - Watch-only clients for quoting and monitoring (`AmmSwapClient::watch_only`).
+//! - CLMM swap math on plain account states, without RPC (`clmm::compute_swap`).
+//! - CLMM quotes with fee breakdown and price impact (`clmm::ClmmQuote`).
+//! - Exact output CLMM quotes with Token-2022 transfer fees (`clmm::compute_amount_in_clmm`).
+//! - Adding and removing AMM v4 liquidity
+//!   (`AmmSwapClient::add_liquidity_amm`, `AmmSwapClient::remove_liquidity_amm`).
+//! - Opening CLMM positions over a price or tick range (`AmmSwapClient::open_position`).
+//! - Adding and removing liquidity of CLMM positions
+//!   (`AmmSwapClient::increase_liquidity`, `AmmSwapClient::decrease_liquidity`).
+//! - Collecting CLMM position fees and rewards and closing positions
+//!   (`AmmSwapClient::collect_fees_and_rewards`, `AmmSwapClient::close_position`).
+//! - CLMM quotes from externally supplied raw accounts (`clmm::quote_clmm_from_accounts`).
+//! - Offline quote replay from pool snapshots (`replay::replay_quote`).
+//! - Fully offline quotes of pool snapshots decoded from raw account data
+//!   (`replay::AmmPoolSnapshot`, `replay::quote_clmm_from_snapshot`).
+//! - AMM v4 pool keys read from the chain, without the API
+//!   (`AmmSwapClient::derive_amm_pool_keys`).
+//! - On-chain discovery of the pools of a pair, ahead of the API
+//!   (`AmmSwapClient::discover_pools_onchain`).
+//! - Devnet programs or programs deployed to a local validator
+//!   (`config::ClusterConfig`, `AmmSwapClient::with_cluster_config`).
+//! - Trade history of a pool from its transactions (`history::fetch_pool_trades`).
+//! - CSV, Arrow and Parquet export of snapshots, trades and quote ladders, with the
+//!   `research` feature (`research::ResearchTable`).
+//! - End-to-end swaps against a local validator loaded with pool fixtures, with the
+//!   `test-utils` feature (`test_utils::LocalValidator`).
 //!
 //! # Examples
 //!
@@ -115,12 +167,13 @@
 //!             let keys = ClmmSwapParams {
 //!                 pool_id: solana_pubkey::Pubkey::from_str(&key.id).unwrap(),
 //!                 user_input_token: ata_a,
-//!                 user_output_token: ata_b,
+//!                 user_output_token: Some(ata_b),
 //!                 amount_specified: amount_in,
 //!                 limit_price: None,
 //!                 //! if false -> amount is amount_in
 //!                 base_out: false,
 //!                 slippage_bps: 100,
+//!                 max_price_impact_bps: None,
 //!             };
 //!
 //!             let sig = amm_swap_client.swap_clmm(keys).await.unwrap();
@@ -135,13 +188,32 @@ use anchor_lang::prelude::declare_id;
 
 // Raydium CLMM program id, used by Anchor
 // account/loader traits for on-chain types.
+// Instructions and PDAs use the program ids of
+// `config::ClusterConfig` instead.
 declare_id!("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
 pub mod amm;
+pub mod api;
 pub mod clmm;
 pub mod common;
+pub mod config;
 pub mod consts;
+pub mod cpmm;
+pub mod error;
+pub mod execution;
 pub mod helpers;
+pub mod history;
 pub mod interface;
+pub mod jito;
 pub mod libraries;
+pub mod monitor;
+pub mod replay;
+#[cfg(feature = "research")]
+pub mod research;
+pub mod router;
+pub mod signer;
 pub mod states;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 pub mod util;
+pub mod validation;
+pub mod wallet;
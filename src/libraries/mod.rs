@@ -11,6 +11,9 @@ pub mod tick_array_bit_map;
 pub mod tick_math;
 pub mod unsafe_math;
 
+#[cfg(test)]
+mod tests;
+
 pub use big_num::*;
 pub use fixed_point_64::*;
 pub use full_math::*;
@@ -0,0 +1,177 @@
+//! Property tests of the tick and swap step math against the values of the
+//! Raydium CLMM program, to catch regressions in the port.
+
+use super::swap_math::compute_swap_step;
+use super::tick_math::*;
+use proptest::prelude::*;
+
+/// `get_sqrt_price_at_tick` of the Raydium CLMM program at these ticks.
+const SQRT_PRICE_VECTORS: [(i32, u128); 15] = [
+    (MIN_TICK, 4295048016),
+    (-100000, 124324258983086206),
+    (-50000, 1514390236238525528),
+    (-1000, 17547129613991882732),
+    (-100, 18354745142194513203),
+    (-10, 18437523468038803493),
+    (-1, 18445821805675395072),
+    (0, 18446744073709551616),
+    (1, 18447666387855957090),
+    (10, 18455969290605287889),
+    (100, 18539204128674375874),
+    (1000, 19392480388906522465),
+    (50000, 224699260981858280811),
+    (100000, 2737055259402209284734),
+    (MAX_TICK, 79226673521066979257578248091),
+];
+
+/// Largest relative error of `get_sqrt_price_at_tick` from `√1.0001^tick`.
+const MAX_RELATIVE_ERROR: f64 = 1e-9;
+
+#[test]
+fn sqrt_price_matches_reference_vectors() {
+    for (tick, sqrt_price_x64) in SQRT_PRICE_VECTORS {
+        assert_eq!(
+            get_sqrt_price_at_tick(tick).unwrap(),
+            sqrt_price_x64,
+            "tick {tick}"
+        );
+        // The price at MAX_TICK is out of the range of the inverse.
+        if tick != MAX_TICK {
+            assert_eq!(get_tick_at_sqrt_price(sqrt_price_x64).unwrap(), tick);
+        }
+    }
+    assert_eq!(
+        get_sqrt_price_at_tick(MIN_TICK).unwrap(),
+        MIN_SQRT_PRICE_X64
+    );
+    assert_eq!(
+        get_sqrt_price_at_tick(MAX_TICK).unwrap(),
+        MAX_SQRT_PRICE_X64
+    );
+}
+
+#[test]
+fn tick_math_rejects_out_of_range_inputs() {
+    assert!(get_sqrt_price_at_tick(MIN_TICK - 1).is_err());
+    assert!(get_sqrt_price_at_tick(MAX_TICK + 1).is_err());
+    assert!(get_tick_at_sqrt_price(MIN_SQRT_PRICE_X64 - 1).is_err());
+    assert!(get_tick_at_sqrt_price(MAX_SQRT_PRICE_X64).is_err());
+    assert_eq!(
+        get_tick_at_sqrt_price(MIN_SQRT_PRICE_X64).unwrap(),
+        MIN_TICK
+    );
+    assert_eq!(
+        get_tick_at_sqrt_price(MAX_SQRT_PRICE_X64 - 1).unwrap(),
+        MAX_TICK - 1
+    );
+}
+
+#[test]
+fn swap_step_without_liquidity_jumps_to_target() {
+    let current = get_sqrt_price_at_tick(0).unwrap();
+    for (target, zero_for_one) in [
+        (get_sqrt_price_at_tick(-100).unwrap(), true),
+        (get_sqrt_price_at_tick(100).unwrap(), false),
+    ] {
+        for is_base_input in [true, false] {
+            let step = compute_swap_step(
+                current,
+                target,
+                0,
+                1_000_000,
+                2500,
+                is_base_input,
+                zero_for_one,
+                0,
+            )
+            .unwrap();
+            assert_eq!(step.sqrt_price_next_x64, target);
+            assert_eq!(step.amount_in, 0);
+            assert_eq!(step.amount_out, 0);
+            assert_eq!(step.fee_amount, 0);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn sqrt_price_round_trips_to_tick(tick in MIN_TICK..MAX_TICK) {
+        let sqrt_price_x64 = get_sqrt_price_at_tick(tick).unwrap();
+        prop_assert_eq!(get_tick_at_sqrt_price(sqrt_price_x64).unwrap(), tick);
+    }
+
+    #[test]
+    fn sqrt_price_increases_with_tick(tick in MIN_TICK..MAX_TICK) {
+        prop_assert!(
+            get_sqrt_price_at_tick(tick).unwrap() < get_sqrt_price_at_tick(tick + 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn sqrt_price_is_close_to_exact(tick in MIN_TICK..=MAX_TICK) {
+        let sqrt_price = get_sqrt_price_at_tick(tick).unwrap() as f64 / 2f64.powi(64);
+        let exact = 1.0001f64.powf(tick as f64 / 2.0);
+        prop_assert!((sqrt_price - exact).abs() / exact < MAX_RELATIVE_ERROR);
+    }
+
+    #[test]
+    fn tick_is_greatest_below_sqrt_price(
+        sqrt_price_x64 in MIN_SQRT_PRICE_X64..MAX_SQRT_PRICE_X64
+    ) {
+        let tick = get_tick_at_sqrt_price(sqrt_price_x64).unwrap();
+        prop_assert!(get_sqrt_price_at_tick(tick).unwrap() <= sqrt_price_x64);
+        prop_assert!(sqrt_price_x64 < get_sqrt_price_at_tick(tick + 1).unwrap());
+    }
+
+    #[test]
+    fn swap_step_stays_within_price_and_amount(
+        current_tick in -200_000i32..200_000,
+        tick_distance in 1i32..10_000,
+        liquidity in 1u128..(1 << 80),
+        amount_remaining in 1u64..u64::MAX,
+        fee_rate in 0u32..100_000,
+        is_base_input: bool,
+        zero_for_one: bool,
+    ) {
+        let target_tick = if zero_for_one {
+            current_tick - tick_distance
+        } else {
+            current_tick + tick_distance
+        };
+        let current = get_sqrt_price_at_tick(current_tick).unwrap();
+        let target = get_sqrt_price_at_tick(target_tick).unwrap();
+        // Steps overflowing the token amounts are rejected, as on chain.
+        let Ok(step) = compute_swap_step(
+            current,
+            target,
+            liquidity,
+            amount_remaining,
+            fee_rate,
+            is_base_input,
+            zero_for_one,
+            0,
+        ) else {
+            return Ok(());
+        };
+
+        if zero_for_one {
+            prop_assert!(target <= step.sqrt_price_next_x64);
+            prop_assert!(step.sqrt_price_next_x64 <= current);
+        } else {
+            prop_assert!(current <= step.sqrt_price_next_x64);
+            prop_assert!(step.sqrt_price_next_x64 <= target);
+        }
+        if is_base_input {
+            prop_assert!(
+                u128::from(step.amount_in) + u128::from(step.fee_amount)
+                    <= u128::from(amount_remaining)
+            );
+            if step.sqrt_price_next_x64 != target {
+                // The input is used up, the dust left is taken as fee.
+                prop_assert_eq!(step.amount_in + step.fee_amount, amount_remaining);
+            }
+        } else {
+            prop_assert!(step.amount_out <= amount_remaining);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Liquidity change detection on top of [`PriceMonitor`](super::PriceMonitor)
+//! snapshots.
+//!
+//! Changes are measured on liquidity depth rather than on raw reserves, so
+//! regular swaps (which move reserves along the curve) do not trigger alerts,
+//! while deposits and withdrawals do.
+
+use crate::interface::PoolType;
+use crate::monitor::{PoolLiquidity, PoolPriceSnapshot};
+use solana_sdk::pubkey::Pubkey;
+
+/// Thresholds for liquidity alerts, expressed as fractions (`0.3` = 30%).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiquidityAlertConfig {
+    /// Emit [`LiquidityEvent::LiquidityRemoved`] when depth drops by at
+    /// least this fraction between two consecutive polls.
+    pub removal_threshold: Option<f64>,
+    /// Emit [`LiquidityEvent::LiquidityAdded`] when depth grows by at least
+    /// this fraction between two consecutive polls.
+    pub growth_threshold: Option<f64>,
+}
+
+/// Before/after view of a liquidity change.
+#[derive(Debug, Clone)]
+pub struct LiquidityChange {
+    pub pool_id: Pubkey,
+    pub pool_type: PoolType,
+    pub before: PoolLiquidity,
+    pub after: PoolLiquidity,
+    pub price_before: f64,
+    pub price_after: f64,
+    /// Relative depth change, negative when liquidity was removed.
+    pub change: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum LiquidityEvent {
+    /// Sudden liquidity removal, e.g. a rug pull.
+    LiquidityRemoved(LiquidityChange),
+    /// Liquidity (TVL) growth.
+    LiquidityAdded(LiquidityChange),
+}
+
+impl LiquidityEvent {
+    pub fn change(&self) -> &LiquidityChange {
+        match self {
+            LiquidityEvent::LiquidityRemoved(change) | LiquidityEvent::LiquidityAdded(change) => {
+                change
+            }
+        }
+    }
+}
+
+impl PoolLiquidity {
+    /// Price independent liquidity depth.
+    ///
+    /// For AMM v4 pools this is `sqrt(base * quote)`, which stays constant
+    /// across swaps; for CLMM pools it is the active liquidity.
+    pub fn depth(&self) -> f64 {
+        match self {
+            PoolLiquidity::Reserves { base, quote } => ((*base as f64) * (*quote as f64)).sqrt(),
+            PoolLiquidity::Concentrated { liquidity } => *liquidity as f64,
+        }
+    }
+}
+
+impl LiquidityAlertConfig {
+    /// Compare two consecutive snapshots of the same pool and return an event
+    /// if one of the configured thresholds was crossed.
+    pub fn evaluate(
+        &self,
+        before: &PoolPriceSnapshot,
+        after: &PoolPriceSnapshot,
+    ) -> Option<LiquidityEvent> {
+        let depth_before = before.liquidity.depth();
+        if depth_before == 0.0 {
+            return None;
+        }
+        let change = (after.liquidity.depth() - depth_before) / depth_before;
+        let liquidity_change = LiquidityChange {
+            pool_id: after.pool_id,
+            pool_type: after.pool_type,
+            before: before.liquidity,
+            after: after.liquidity,
+            price_before: before.mid_price,
+            price_after: after.mid_price,
+            change,
+        };
+
+        match (self.removal_threshold, self.growth_threshold) {
+            (Some(threshold), _) if change <= -threshold => {
+                Some(LiquidityEvent::LiquidityRemoved(liquidity_change))
+            }
+            (_, Some(threshold)) if change >= threshold => {
+                Some(LiquidityEvent::LiquidityAdded(liquidity_change))
+            }
+            _ => None,
+        }
+    }
+}
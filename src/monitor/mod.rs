@@ -0,0 +1,10 @@
+pub mod liquidity_alert;
+pub use liquidity_alert::*;
+pub mod price_monitor;
+pub use price_monitor::*;
+pub mod pool_diff;
+pub use pool_diff::*;
+pub mod peg;
+pub use peg::*;
+pub mod pool_watcher;
+pub use pool_watcher::*;
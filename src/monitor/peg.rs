@@ -0,0 +1,102 @@
+//! Peg deviation metrics for stable and correlated pairs.
+
+use crate::amm::client::AmmSwapClient;
+use crate::interface::PoolType;
+use crate::monitor::PoolPriceSnapshot;
+use crate::signer::SwapSigner;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+use std::sync::Arc;
+
+/// External price a pool is expected to trade at, in token B per token A.
+#[derive(Clone)]
+pub enum PegReference {
+    /// Fixed target, e.g. `1.0` for a stablecoin pair.
+    Fixed(f64),
+    /// Ratio of the USD prices of both mints reported by the Raydium price
+    /// endpoint.
+    MintPrices { base_mint: Pubkey, quote_mint: Pubkey },
+    /// User-supplied oracle. Returning `None` skips the update.
+    Oracle(Arc<dyn Fn() -> Option<f64> + Send + Sync>),
+}
+
+impl fmt::Debug for PegReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PegReference::Fixed(price) => f.debug_tuple("Fixed").field(price).finish(),
+            PegReference::MintPrices {
+                base_mint,
+                quote_mint,
+            } => f
+                .debug_struct("MintPrices")
+                .field("base_mint", base_mint)
+                .field("quote_mint", quote_mint)
+                .finish(),
+            PegReference::Oracle(_) => f.write_str("Oracle"),
+        }
+    }
+}
+
+impl PegReference {
+    /// Current reference price.
+    pub async fn resolve(&self, client: &AmmSwapClient<impl SwapSigner>) -> anyhow::Result<f64> {
+        match self {
+            PegReference::Fixed(price) => Ok(*price),
+            PegReference::MintPrices {
+                base_mint,
+                quote_mint,
+            } => {
+                let (base_mint, quote_mint) = (base_mint.to_string(), quote_mint.to_string());
+                let prices = client
+                    .fetch_mint_prices(&[base_mint.clone(), quote_mint.clone()])
+                    .await?;
+                match (prices.get(&base_mint), prices.get(&quote_mint)) {
+                    (Some(base), Some(quote)) if *quote > 0.0 => Ok(base / quote),
+                    _ => Err(anyhow!("no USD price for {} or {}", base_mint, quote_mint)),
+                }
+            }
+            PegReference::Oracle(oracle) => oracle().ok_or(anyhow!("peg oracle returned no price")),
+        }
+    }
+}
+
+/// Deviation of a pool from its reference price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PegDeviation {
+    pub pool_id: Pubkey,
+    pub pool_price: f64,
+    pub reference_price: f64,
+    /// `(pool_price - reference_price) / reference_price` in bps. Positive
+    /// when token A is more expensive in the pool than the reference.
+    pub deviation_bps: f64,
+    /// For AMM v4 pools, `(base_value - quote_value) / (base_value + quote_value)`
+    /// with both reserves valued at the reference price. Positive when the
+    /// pool holds more token A value than token B value.
+    pub reserve_imbalance: Option<f64>,
+}
+
+/// Compare a pool snapshot with a reference price.
+pub fn peg_deviation(snapshot: &PoolPriceSnapshot, reference_price: f64) -> PegDeviation {
+    let pool_price = snapshot.mid_price;
+    let deviation_bps = if reference_price == 0.0 {
+        0.0
+    } else {
+        (pool_price - reference_price) / reference_price * 10_000.0
+    };
+    // Constant product reserves are worth the same at the pool price, so
+    // their value ratio at the reference price is `pool_price / reference`.
+    let reserve_imbalance = match snapshot.pool_type {
+        PoolType::Standard if reference_price + pool_price > 0.0 => {
+            Some((reference_price - pool_price) / (reference_price + pool_price))
+        }
+        _ => None,
+    };
+    PegDeviation {
+        pool_id: snapshot.pool_id,
+        pool_price,
+        reference_price,
+        deviation_bps,
+        reserve_imbalance,
+    }
+}
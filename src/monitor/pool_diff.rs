@@ -0,0 +1,117 @@
+//! Typed differences between two snapshots of the same pool.
+
+use crate::amm::client::RpcPoolInfo;
+use crate::clmm::sqrt_price_x64_to_price;
+use crate::states::PoolState;
+
+/// A pool state that can be compared against a later snapshot of itself.
+pub trait PoolStateDiff {
+    type Diff;
+
+    fn diff(&self, after: &Self) -> Self::Diff;
+}
+
+/// Compare two snapshots of the same pool, AMM v4 (`RpcPoolInfo`) or CLMM
+/// (`PoolState`).
+pub fn diff_pool_states<S: PoolStateDiff>(before: &S, after: &S) -> S::Diff {
+    before.diff(after)
+}
+
+/// Relative change from `before` to `after`, in percent.
+fn percent_change(before: f64, after: f64) -> f64 {
+    if before == 0.0 {
+        return 0.0;
+    }
+    (after - before) / before * 100.0
+}
+
+/// Difference between two AMM v4 reserve snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmmPoolDiff {
+    pub base_reserve_before: u64,
+    pub base_reserve_after: u64,
+    pub base_reserve_delta: i128,
+    pub quote_reserve_before: u64,
+    pub quote_reserve_after: u64,
+    pub quote_reserve_delta: i128,
+    /// Quote per base in raw token units (not adjusted for decimals).
+    pub price_before: f64,
+    pub price_after: f64,
+    pub price_change_pct: f64,
+}
+
+impl PoolStateDiff for RpcPoolInfo {
+    type Diff = AmmPoolDiff;
+
+    fn diff(&self, after: &Self) -> AmmPoolDiff {
+        let price = |info: &RpcPoolInfo| {
+            if info.base_reserve == 0 {
+                0.0
+            } else {
+                info.quote_reserve as f64 / info.base_reserve as f64
+            }
+        };
+        let price_before = price(self);
+        let price_after = price(after);
+        AmmPoolDiff {
+            base_reserve_before: self.base_reserve,
+            base_reserve_after: after.base_reserve,
+            base_reserve_delta: after.base_reserve as i128 - self.base_reserve as i128,
+            quote_reserve_before: self.quote_reserve,
+            quote_reserve_after: after.quote_reserve,
+            quote_reserve_delta: after.quote_reserve as i128 - self.quote_reserve as i128,
+            price_before,
+            price_after,
+            price_change_pct: percent_change(price_before, price_after),
+        }
+    }
+}
+
+/// Difference between two CLMM `PoolState` snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClmmPoolDiff {
+    pub liquidity_before: u128,
+    pub liquidity_after: u128,
+    pub liquidity_delta: i128,
+    pub sqrt_price_x64_before: u128,
+    pub sqrt_price_x64_after: u128,
+    /// Price of token 0 in token 1, adjusted for decimals.
+    pub price_before: f64,
+    pub price_after: f64,
+    pub price_change_pct: f64,
+    pub tick_before: i32,
+    pub tick_after: i32,
+    pub tick_delta: i32,
+}
+
+impl PoolStateDiff for PoolState {
+    type Diff = ClmmPoolDiff;
+
+    fn diff(&self, after: &Self) -> ClmmPoolDiff {
+        let price = |state: &PoolState| {
+            sqrt_price_x64_to_price(
+                state.sqrt_price_x64,
+                state.mint_decimals_0,
+                state.mint_decimals_1,
+            )
+            .unwrap_or_default()
+        };
+        let (liquidity_before, liquidity_after) = (self.liquidity, after.liquidity);
+        let (tick_before, tick_after) = (self.tick_current, after.tick_current);
+        let price_before = price(self);
+        let price_after = price(after);
+        ClmmPoolDiff {
+            liquidity_before,
+            liquidity_after,
+            liquidity_delta: (liquidity_after as i128).wrapping_sub(liquidity_before as i128),
+            sqrt_price_x64_before: self.sqrt_price_x64,
+            sqrt_price_x64_after: after.sqrt_price_x64,
+            price_before,
+            price_after,
+            price_change_pct: percent_change(price_before, price_after),
+            tick_before,
+            tick_after,
+            tick_delta: tick_after - tick_before,
+        }
+    }
+}
@@ -0,0 +1,515 @@
+//! Live state of a CLMM pool kept up to date over WebSocket.
+//!
+//! [`PoolWatcher`] subscribes to the pool state, its amm config, mints,
+//! vaults, tick array bitmap extension and the tick arrays a swap would walk
+//! through in either direction. Quotes computed from the watched accounts
+//! with [`PoolWatcher::calculate_swap_change`] need no RPC round trip.
+//!
+//! When the pool price moves, the tick arrays to watch are derived again from
+//! the new pool state: new ones are read once over RPC and subscribed to, and
+//! the ones no longer needed are dropped.
+//!
+//! A subscription that fails or ends is retried with backoff, reconnecting
+//! the WebSocket, and the account is read again over RPC once resubscribed.
+//! Until then, quotes reading the account fail rather than use stale data,
+//! see [`PoolWatcher::unwatched_accounts`]. The epoch of Token-2022 transfer
+//! fees is refreshed periodically.
+
+use crate::amm::client::AmmSwapClient;
+use crate::clmm::{
+    ClmmSwapChangeResult, derive_tick_array_bitmap_extension_key, get_tick_array_keys,
+    get_tick_arrays,
+};
+use crate::common::{TokenAccountState, deserialize_anchor_account, unpack_token};
+use crate::interface::{ClmmSwapParams, Rsps, TickArrays};
+use crate::replay::synthetic_token_account;
+use crate::signer::SwapSigner;
+use crate::states::{PoolState, TickArrayBitmapExtension};
+use anyhow::anyhow;
+use futures_util::StreamExt;
+use solana_account::Account;
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// First delay before subscribing again to an account, doubled on each
+/// failure up to [`MAX_RESUBSCRIBE_BACKOFF`].
+const MIN_RESUBSCRIBE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(30);
+/// Interval between reads of the epoch.
+const EPOCH_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Account as last received, with the slot it was read at.
+#[derive(Debug, Clone)]
+struct WatchedAccount {
+    slot: u64,
+    account: Account,
+}
+
+struct Shared {
+    pool_id: Pubkey,
+    /// CLMM program of the cluster of the client.
+    program_id: solana_pubkey::Pubkey,
+    commitment: CommitmentConfig,
+    rpc_client: RpcClient,
+    ws_url: String,
+    /// Replaced when subscribing fails, as the connection may be closed.
+    pubsub_client: tokio::sync::RwLock<Arc<PubsubClient>>,
+    accounts: RwLock<HashMap<Pubkey, WatchedAccount>>,
+    /// Watched accounts whose subscription failed or ended, not updated
+    /// until it is restored.
+    unwatched: Mutex<HashSet<Pubkey>>,
+    epoch: AtomicU64,
+    /// Signaled on every update of the pool state.
+    pool_updated: Notify,
+    /// Subscriptions of the pool accounts other than tick arrays, and the
+    /// tasks resyncing the tick arrays and refreshing the epoch.
+    fixed: Mutex<Vec<JoinHandle<()>>>,
+    tick_arrays: Mutex<HashMap<Pubkey, JoinHandle<()>>>,
+}
+
+/// Watched accounts of a CLMM pool, see the [module documentation](self).
+///
+/// Dropping the watcher ends its subscriptions.
+pub struct PoolWatcher {
+    shared: Arc<Shared>,
+}
+
+impl PoolWatcher {
+    /// Read the accounts of `pool_id` through the RPC of `client` and
+    /// subscribe to them through the WebSocket endpoint `ws_url`.
+    pub async fn start(
+        client: &AmmSwapClient<impl SwapSigner>,
+        ws_url: &str,
+        pool_id: Pubkey,
+    ) -> anyhow::Result<Self> {
+        let rpc_client = client.rpc_client();
+        let commitment = rpc_client.commitment();
+        let shared = Arc::new(Shared {
+            pool_id,
+            program_id: client.cluster_config().clmm_program_id(),
+            commitment,
+            rpc_client: RpcClient::new_with_commitment(rpc_client.url(), commitment),
+            ws_url: ws_url.to_string(),
+            pubsub_client: tokio::sync::RwLock::new(Arc::new(PubsubClient::new(ws_url).await?)),
+            accounts: RwLock::new(HashMap::new()),
+            unwatched: Mutex::new(HashSet::new()),
+            epoch: AtomicU64::new(client.get_epoch().await?),
+            pool_updated: Notify::new(),
+            fixed: Mutex::new(Vec::new()),
+            tick_arrays: Mutex::new(HashMap::new()),
+        });
+        let watcher = Self {
+            shared: Arc::clone(&shared),
+        };
+
+        if shared.read_accounts(&[pool_id]).await?.is_empty() {
+            return Err(anyhow!("Pool {} was not found", pool_id));
+        }
+        let pool_state = watcher.pool_state()?;
+        let fixed_accounts = [
+            pool_id,
+            Pubkey::from(pool_state.amm_config.to_bytes()),
+            Pubkey::from(pool_state.token_mint_0.to_bytes()),
+            Pubkey::from(pool_state.token_mint_1.to_bytes()),
+            Pubkey::from(pool_state.token_vault_0.to_bytes()),
+            Pubkey::from(pool_state.token_vault_1.to_bytes()),
+            shared.bitmap_extension_key(),
+        ];
+        shared.read_accounts(&fixed_accounts[1..]).await?;
+        {
+            let mut fixed = shared.lock_fixed();
+            for address in fixed_accounts {
+                fixed.push(tokio::spawn(Arc::clone(&shared).watch_account(address)));
+            }
+            fixed.push(tokio::spawn(Arc::clone(&shared).resync_tick_arrays()));
+            fixed.push(tokio::spawn(Arc::clone(&shared).refresh_epoch()));
+        }
+        shared.sync_tick_arrays().await?;
+        Ok(watcher)
+    }
+
+    pub fn pool_id(&self) -> &Pubkey {
+        &self.shared.pool_id
+    }
+
+    /// Epoch of the Token-2022 transfer fees of quotes, read at most
+    /// [`EPOCH_REFRESH_INTERVAL`] ago.
+    pub fn epoch(&self) -> u64 {
+        self.shared.epoch.load(Ordering::Relaxed)
+    }
+
+    /// Watched accounts not updated since their subscription failed or
+    /// ended, empty when every subscription is live.
+    pub fn unwatched_accounts(&self) -> Vec<Pubkey> {
+        self.shared.lock_unwatched().iter().copied().collect()
+    }
+
+    /// Highest slot of the watched accounts.
+    pub fn slot(&self) -> u64 {
+        self.shared
+            .read_lock()
+            .values()
+            .map(|watched| watched.slot)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Latest watched pool state.
+    pub fn pool_state(&self) -> anyhow::Result<PoolState> {
+        self.shared.pool_state()
+    }
+
+    /// Fail when one of `addresses` is unwatched, so quotes do not use its
+    /// stale data.
+    fn check_watched(&self, addresses: &[Pubkey]) -> anyhow::Result<()> {
+        let unwatched = self.shared.lock_unwatched();
+        match addresses.iter().find(|address| unwatched.contains(address)) {
+            Some(address) => Err(anyhow!(
+                "Account {address} of pool {} is not watched since its subscription ended",
+                self.shared.pool_id
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Latest balances of the token vaults, token 0 then token 1.
+    pub fn vault_balances(&self) -> anyhow::Result<(u64, u64)> {
+        let pool_state = self.pool_state()?;
+        let balance = |vault: solana_pubkey::Pubkey| -> anyhow::Result<u64> {
+            let account = self
+                .shared
+                .account(&Pubkey::from(vault.to_bytes()))
+                .ok_or(anyhow!("Vault {} is not loaded", vault))?;
+            Ok(match unpack_token(&account.owner, &account.data)? {
+                TokenAccountState::SplToken(state) => state.amount,
+                TokenAccountState::SplToken2022(state) => state.base.amount,
+            })
+        };
+        Ok((
+            balance(pool_state.token_vault_0)?,
+            balance(pool_state.token_vault_1)?,
+        ))
+    }
+
+    /// Accounts read by `calculate_swap_change_clmm_sync`, in its order, with
+    /// a stand-in for the user's token account of `input_mint`.
+    pub fn rsps(&self, input_mint: &Pubkey, amount: u64) -> anyhow::Result<Rsps> {
+        let pool_state = self.pool_state()?;
+        let required = |address: solana_pubkey::Pubkey| -> anyhow::Result<Account> {
+            self.shared
+                .account(&Pubkey::from(address.to_bytes()))
+                .ok_or(anyhow!("Account {} is not loaded", address))
+        };
+        let mint_0 = required(pool_state.token_mint_0)?;
+        let mint_1 = required(pool_state.token_mint_1)?;
+        let input_token_program = if input_mint.to_bytes() == pool_state.token_mint_0.to_bytes() {
+            mint_0.owner
+        } else if input_mint.to_bytes() == pool_state.token_mint_1.to_bytes() {
+            mint_1.owner
+        } else {
+            return Err(anyhow!("Input mint {} is not in the pool", input_mint));
+        };
+        Ok(vec![
+            Some(synthetic_token_account(
+                input_mint,
+                &input_token_program,
+                amount,
+            )?),
+            Some(required(pool_state.amm_config)?),
+            Some(mint_0),
+            Some(mint_1),
+            self.shared.account(&self.shared.bitmap_extension_key()),
+        ])
+    }
+
+    /// Current tick array and the next five initialized ones in the swap
+    /// direction, as loaded by `load_cur_and_next_five_tick_array`.
+    pub fn tick_arrays(&self, zero_for_one: bool) -> anyhow::Result<TickArrays> {
+        let keys = self.shared.tick_array_keys(zero_for_one)?;
+        get_tick_arrays(keys.iter().map(|key| self.shared.account(key)).collect())
+    }
+
+    /// Same as `AmmSwapClient::calculate_swap_change_clmm_sync`, on the
+    /// watched accounts. `input_mint` is the mint of
+    /// `params.user_input_token`.
+    ///
+    /// Fails when an account the quote reads is unwatched, see
+    /// [`Self::unwatched_accounts`].
+    pub fn calculate_swap_change(
+        &self,
+        client: &AmmSwapClient<impl SwapSigner>,
+        params: ClmmSwapParams,
+        input_mint: &Pubkey,
+    ) -> anyhow::Result<(ClmmSwapChangeResult, solana_pubkey::Pubkey)> {
+        let pool_state = self.pool_state()?;
+        let zero_for_one = input_mint.to_bytes() == pool_state.token_mint_0.to_bytes();
+        let mut read = vec![
+            self.shared.pool_id,
+            Pubkey::from(pool_state.amm_config.to_bytes()),
+            Pubkey::from(pool_state.token_mint_0.to_bytes()),
+            Pubkey::from(pool_state.token_mint_1.to_bytes()),
+            self.shared.bitmap_extension_key(),
+        ];
+        read.extend(self.shared.tick_array_keys(zero_for_one)?);
+        self.check_watched(&read)?;
+        let rsps = self.rsps(input_mint, params.amount_specified)?;
+        let tick_arrays = self.tick_arrays(zero_for_one)?;
+        let bitmap_extension =
+            solana_pubkey::Pubkey::from(self.shared.bitmap_extension_key().to_bytes());
+        client.calculate_swap_change_clmm_sync(
+            params,
+            self.epoch(),
+            pool_state,
+            rsps,
+            tick_arrays,
+            bitmap_extension,
+        )
+    }
+
+    /// End all subscriptions. The accounts received so far are kept.
+    pub fn stop(&self) {
+        for handle in self.shared.lock_fixed().drain(..) {
+            handle.abort();
+        }
+        for (_, handle) in self.shared.lock_tick_arrays().drain() {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for PoolWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Shared {
+    fn read_lock(&self) -> std::sync::RwLockReadGuard<'_, HashMap<Pubkey, WatchedAccount>> {
+        self.accounts.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_fixed(&self) -> std::sync::MutexGuard<'_, Vec<JoinHandle<()>>> {
+        self.fixed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_unwatched(&self) -> std::sync::MutexGuard<'_, HashSet<Pubkey>> {
+        self.unwatched.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_tick_arrays(&self) -> std::sync::MutexGuard<'_, HashMap<Pubkey, JoinHandle<()>>> {
+        self.tick_arrays.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.read_lock()
+            .get(address)
+            .map(|watched| watched.account.clone())
+    }
+
+    /// Keep `account` unless a later read of `address` is already stored.
+    fn store(&self, address: Pubkey, slot: u64, account: Account) {
+        let mut accounts = self.accounts.write().unwrap_or_else(|e| e.into_inner());
+        match accounts.get(&address) {
+            Some(watched) if watched.slot > slot => {}
+            _ => {
+                accounts.insert(address, WatchedAccount { slot, account });
+            }
+        }
+    }
+
+    fn bitmap_extension_key(&self) -> Pubkey {
+        let pool_id = solana_pubkey::Pubkey::from(self.pool_id.to_bytes());
+        Pubkey::from(derive_tick_array_bitmap_extension_key(&pool_id, &self.program_id).to_bytes())
+    }
+
+    fn pool_state(&self) -> anyhow::Result<PoolState> {
+        let account = self
+            .account(&self.pool_id)
+            .ok_or(anyhow!("Pool {} is not loaded", self.pool_id))?;
+        deserialize_anchor_account(&account)
+    }
+
+    fn tick_array_keys(&self, zero_for_one: bool) -> anyhow::Result<Vec<Pubkey>> {
+        let pool_state = self.pool_state()?;
+        let bitmap_extension = self
+            .account(&self.bitmap_extension_key())
+            .map(|account| deserialize_anchor_account::<TickArrayBitmapExtension>(&account))
+            .transpose()?;
+        Ok(get_tick_array_keys(
+            self.program_id,
+            solana_pubkey::Pubkey::from(self.pool_id.to_bytes()),
+            &pool_state,
+            &bitmap_extension,
+            zero_for_one,
+        )?
+        .into_iter()
+        .map(|key| Pubkey::from(key.to_bytes()))
+        .collect())
+    }
+
+    /// Read `addresses` over RPC, returning the ones that exist.
+    async fn read_accounts(&self, addresses: &[Pubkey]) -> anyhow::Result<Vec<Pubkey>> {
+        let response = self
+            .rpc_client
+            .get_multiple_accounts_with_commitment(addresses, self.commitment)
+            .await?;
+        let mut found = Vec::with_capacity(addresses.len());
+        for (address, account) in addresses.iter().zip(response.value) {
+            if let Some(account) = account {
+                self.store(*address, response.context.slot, account);
+                found.push(*address);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Watch the tick arrays of both swap directions from the current pool
+    /// state, and only those.
+    async fn sync_tick_arrays(self: &Arc<Self>) -> anyhow::Result<()> {
+        let mut needed = self.tick_array_keys(true)?;
+        needed.extend(self.tick_array_keys(false)?);
+        let needed: HashSet<Pubkey> = needed.into_iter().collect();
+        let missing: Vec<Pubkey> = {
+            let mut subscriptions = self.lock_tick_arrays();
+            subscriptions.retain(|address, handle| {
+                let keep = needed.contains(address);
+                if !keep {
+                    handle.abort();
+                }
+                keep
+            });
+            needed
+                .iter()
+                .filter(|address| !subscriptions.contains_key(address))
+                .copied()
+                .collect()
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
+        debug!(
+            "Watching {} new tick arrays of pool {}",
+            missing.len(),
+            self.pool_id
+        );
+        self.read_accounts(&missing).await?;
+        {
+            let mut unwatched = self.lock_unwatched();
+            for address in &missing {
+                unwatched.remove(address);
+            }
+        }
+        let mut subscriptions = self.lock_tick_arrays();
+        for address in missing {
+            subscriptions
+                .entry(address)
+                .or_insert_with(|| tokio::spawn(Arc::clone(self).watch_account(address)));
+        }
+        Ok(())
+    }
+
+    /// Resync the watched tick arrays after every update of the pool state.
+    async fn resync_tick_arrays(self: Arc<Self>) {
+        loop {
+            // Updates received during a resync are coalesced into one more.
+            self.pool_updated.notified().await;
+            if let Err(e) = self.sync_tick_arrays().await {
+                warn!(
+                    "Failed to resync tick arrays of pool {}: {e:?}",
+                    self.pool_id
+                );
+            }
+        }
+    }
+
+    /// Read the epoch every [`EPOCH_REFRESH_INTERVAL`].
+    async fn refresh_epoch(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(EPOCH_REFRESH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            match self.rpc_client.get_epoch_info().await {
+                Ok(epoch_info) => self.epoch.store(epoch_info.epoch, Ordering::Relaxed),
+                Err(e) => warn!("Failed to refresh the epoch: {e:?}"),
+            }
+        }
+    }
+
+    async fn pubsub_client(&self) -> Arc<PubsubClient> {
+        Arc::clone(&*self.pubsub_client.read().await)
+    }
+
+    /// Connect again, unless another subscription already replaced `failed`.
+    async fn reconnect(&self, failed: &Arc<PubsubClient>) {
+        let mut pubsub_client = self.pubsub_client.write().await;
+        if !Arc::ptr_eq(&pubsub_client, failed) {
+            return;
+        }
+        match PubsubClient::new(&self.ws_url).await {
+            Ok(client) => *pubsub_client = Arc::new(client),
+            Err(e) => warn!("Failed to reconnect to {}: {e:?}", self.ws_url),
+        }
+    }
+
+    /// Store every update of `address`, subscribing again with backoff
+    /// whenever the subscription fails or ends. The account is read over RPC
+    /// after each subscription, for the updates missed in between.
+    async fn watch_account(self: Arc<Self>, address: Pubkey) {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.commitment),
+            ..RpcAccountInfoConfig::default()
+        };
+        let mut backoff = MIN_RESUBSCRIBE_BACKOFF;
+        loop {
+            let pubsub_client = self.pubsub_client().await;
+            match pubsub_client
+                .account_subscribe(&address, Some(config.clone()))
+                .await
+            {
+                Ok((mut updates, unsubscribe)) => {
+                    match self.read_accounts(&[address]).await {
+                        Ok(_) => {
+                            self.lock_unwatched().remove(&address);
+                            backoff = MIN_RESUBSCRIBE_BACKOFF;
+                        }
+                        Err(e) => warn!("Failed to read account {address}: {e:?}"),
+                    }
+                    while let Some(update) = updates.next().await {
+                        let Some(account) = update.value.decode::<Account>() else {
+                            warn!("Failed to decode update of account {address}");
+                            continue;
+                        };
+                        self.store(address, update.context.slot, account);
+                        if address == self.pool_id {
+                            self.pool_updated.notify_one();
+                        }
+                    }
+                    self.lock_unwatched().insert(address);
+                    warn!("Subscription to account {address} ended, subscribing again");
+                    unsubscribe().await;
+                }
+                Err(e) => {
+                    self.lock_unwatched().insert(address);
+                    warn!(
+                        "Failed to subscribe to account {address}, retrying in {backoff:?}: {e:?}"
+                    );
+                    self.reconnect(&pubsub_client).await;
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_RESUBSCRIBE_BACKOFF);
+        }
+    }
+}
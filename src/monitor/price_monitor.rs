@@ -0,0 +1,318 @@
+//! Polling price monitor for a set of AMM v4 and CLMM pools.
+//!
+//! [`PriceMonitor`] keeps the latest mid price and liquidity of every tracked
+//! pool in memory. Consumers can either read a point-in-time snapshot or
+//! subscribe to a stream of updates. Optional liquidity alerts are published
+//! on a separate stream.
+
+use crate::amm::client::AmmSwapClient;
+use crate::clmm::sqrt_price_x64_to_price;
+use crate::interface::PoolType;
+use crate::monitor::{
+    LiquidityAlertConfig, LiquidityEvent, PegDeviation, PegReference, peg_deviation,
+};
+use crate::signer::SwapSigner;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Default capacity of the update channel.
+const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+/// A pool tracked by the monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MonitoredPool {
+    /// Pool account address.
+    pub id: Pubkey,
+    /// Kind of pool behind `id`.
+    pub pool_type: PoolType,
+}
+
+impl MonitoredPool {
+    pub fn standard(id: Pubkey) -> Self {
+        Self {
+            id,
+            pool_type: PoolType::Standard,
+        }
+    }
+
+    pub fn concentrated(id: Pubkey) -> Self {
+        Self {
+            id,
+            pool_type: PoolType::Concentrated,
+        }
+    }
+}
+
+/// Liquidity of a pool at the time of the snapshot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolLiquidity {
+    /// Constant product reserves of an AMM v4 pool (raw units).
+    Reserves { base: u64, quote: u64 },
+    /// Active in-range liquidity of a CLMM pool.
+    Concentrated { liquidity: u128 },
+}
+
+/// Latest known state of a monitored pool.
+#[derive(Debug, Clone)]
+pub struct PoolPriceSnapshot {
+    pub pool_id: Pubkey,
+    pub pool_type: PoolType,
+    /// Mid price of token B (quote / mint 1) per token A, decimals applied.
+    pub mid_price: f64,
+    pub liquidity: PoolLiquidity,
+    /// Local time at which the snapshot was fetched.
+    pub fetched_at: SystemTime,
+}
+
+/// Fetch the current mid price and liquidity for a single pool.
+pub async fn fetch_pool_price(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool: &MonitoredPool,
+) -> anyhow::Result<PoolPriceSnapshot> {
+    let (mid_price, liquidity) = match pool.pool_type {
+        PoolType::Standard => {
+            let (state, reserves) = client.get_rpc_pool_info_with_state(&pool.id).await?;
+            let base = reserves.base_reserve as f64 / 10f64.powi(state.base_decimal as i32);
+            let quote = reserves.quote_reserve as f64 / 10f64.powi(state.quote_decimal as i32);
+            let mid_price = if base == 0.0 { 0.0 } else { quote / base };
+            (
+                mid_price,
+                PoolLiquidity::Reserves {
+                    base: reserves.base_reserve,
+                    quote: reserves.quote_reserve,
+                },
+            )
+        }
+        PoolType::Concentrated => {
+            let pool_state = client.get_pool_state(&pool.id).await?;
+            let mid_price = sqrt_price_x64_to_price(
+                pool_state.sqrt_price_x64,
+                pool_state.mint_decimals_0,
+                pool_state.mint_decimals_1,
+            )?;
+            (
+                mid_price,
+                PoolLiquidity::Concentrated {
+                    liquidity: pool_state.liquidity,
+                },
+            )
+        }
+    };
+
+    Ok(PoolPriceSnapshot {
+        pool_id: pool.id,
+        pool_type: pool.pool_type,
+        mid_price,
+        liquidity,
+        fetched_at: SystemTime::now(),
+    })
+}
+
+/// Tracks a set of pools concurrently and publishes their latest prices.
+///
+/// Every pool gets its own polling task, so one slow or failing pool does not
+/// delay updates of the others.
+pub struct PriceMonitor<S = Keypair> {
+    client: Arc<AmmSwapClient<S>>,
+    pools: Vec<MonitoredPool>,
+    poll_interval: Duration,
+    snapshots: Arc<RwLock<HashMap<Pubkey, PoolPriceSnapshot>>>,
+    sender: broadcast::Sender<PoolPriceSnapshot>,
+    alert_config: Option<LiquidityAlertConfig>,
+    alert_sender: broadcast::Sender<LiquidityEvent>,
+    peg_references: HashMap<Pubkey, PegReference>,
+    peg_sender: broadcast::Sender<PegDeviation>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl<S: SwapSigner + 'static> PriceMonitor<S> {
+    pub fn new(
+        client: Arc<AmmSwapClient<S>>,
+        pools: Vec<MonitoredPool>,
+        poll_interval: Duration,
+    ) -> Self {
+        let (sender, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        let (alert_sender, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        let (peg_sender, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        Self {
+            client,
+            pools,
+            poll_interval,
+            snapshots: Arc::new(RwLock::new(HashMap::new())),
+            sender,
+            alert_config: None,
+            alert_sender,
+            peg_references: HashMap::new(),
+            peg_sender,
+            handles: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Enable liquidity alerts. Takes effect on the next [`Self::start`].
+    pub fn with_liquidity_alerts(mut self, config: LiquidityAlertConfig) -> Self {
+        self.alert_config = Some(config);
+        self
+    }
+
+    /// Track the deviation of `pool_id` from an external reference price.
+    /// Takes effect on the next [`Self::start`].
+    pub fn with_peg_reference(mut self, pool_id: Pubkey, reference: PegReference) -> Self {
+        self.peg_references.insert(pool_id, reference);
+        self
+    }
+
+    pub fn pools(&self) -> &[MonitoredPool] {
+        &self.pools
+    }
+
+    /// Spawn one polling task per pool. Calling `start` on a running monitor
+    /// restarts all tasks.
+    pub fn start(&self) {
+        self.stop();
+        let mut handles = self.handles.lock().unwrap_or_else(|e| e.into_inner());
+        for pool in self.pools.iter().copied() {
+            let client = Arc::clone(&self.client);
+            let snapshots = Arc::clone(&self.snapshots);
+            let sender = self.sender.clone();
+            let alert_config = self.alert_config;
+            let alert_sender = self.alert_sender.clone();
+            let peg_reference = self.peg_references.get(&pool.id).cloned();
+            let peg_sender = self.peg_sender.clone();
+            let poll_interval = self.poll_interval;
+            handles.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(poll_interval);
+                loop {
+                    interval.tick().await;
+                    match fetch_pool_price(&client, &pool).await {
+                        Ok(snapshot) => {
+                            debug!("Pool {} mid price {}", pool.id, snapshot.mid_price);
+                            let previous = snapshots
+                                .write()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .insert(pool.id, snapshot.clone());
+                            if let (Some(config), Some(previous)) = (alert_config, previous)
+                                && let Some(event) = config.evaluate(&previous, &snapshot)
+                            {
+                                warn!(
+                                    "Liquidity alert for pool {}: change {:.2}%",
+                                    pool.id,
+                                    event.change().change * 100.0
+                                );
+                                let _ = alert_sender.send(event);
+                            }
+                            if let Some(reference) = &peg_reference {
+                                match reference.resolve(&client).await {
+                                    Ok(reference_price) => {
+                                        let _ = peg_sender
+                                            .send(peg_deviation(&snapshot, reference_price));
+                                    }
+                                    Err(e) => warn!(
+                                        "Failed to resolve peg reference of pool {}: {e:?}",
+                                        pool.id
+                                    ),
+                                }
+                            }
+                            // No subscribers is not an error for the monitor.
+                            let _ = sender.send(snapshot);
+                        }
+                        Err(e) => warn!("Failed to refresh pool {}: {e:?}", pool.id),
+                    }
+                }
+            }));
+        }
+    }
+}
+
+impl<S> PriceMonitor<S> {
+    /// Abort all polling tasks. Snapshots collected so far are kept.
+    pub fn stop(&self) {
+        let mut handles = self.handles.lock().unwrap_or_else(|e| e.into_inner());
+        for handle in handles.drain(..) {
+            handle.abort();
+        }
+    }
+
+    /// Latest snapshot of every pool that has been fetched at least once.
+    pub fn snapshot(&self) -> Vec<PoolPriceSnapshot> {
+        self.snapshots
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Latest snapshot of a single pool.
+    pub fn get(&self, pool_id: &Pubkey) -> Option<PoolPriceSnapshot> {
+        self.snapshots
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(pool_id)
+            .cloned()
+    }
+
+    /// Subscribe to price updates of all monitored pools.
+    pub fn subscribe(&self) -> PriceStream {
+        MonitorStream {
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    /// Subscribe to liquidity alerts. Nothing is emitted unless alerts were
+    /// enabled with [`Self::with_liquidity_alerts`].
+    pub fn subscribe_alerts(&self) -> LiquidityAlertStream {
+        MonitorStream {
+            receiver: self.alert_sender.subscribe(),
+        }
+    }
+
+    /// Subscribe to peg deviation metrics of the pools configured with
+    /// [`Self::with_peg_reference`].
+    pub fn subscribe_peg(&self) -> PegDeviationStream {
+        MonitorStream {
+            receiver: self.peg_sender.subscribe(),
+        }
+    }
+}
+
+impl<S> Drop for PriceMonitor<S> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Async stream of monitor updates.
+pub struct MonitorStream<T> {
+    pub(crate) receiver: broadcast::Receiver<T>,
+}
+
+/// Async stream of [`PoolPriceSnapshot`] updates.
+pub type PriceStream = MonitorStream<PoolPriceSnapshot>;
+/// Async stream of [`LiquidityEvent`]s.
+pub type LiquidityAlertStream = MonitorStream<LiquidityEvent>;
+/// Async stream of [`PegDeviation`] metrics.
+pub type PegDeviationStream = MonitorStream<PegDeviation>;
+
+impl<T: Clone> MonitorStream<T> {
+    /// Wait for the next update. Returns `None` once the monitor is dropped.
+    ///
+    /// Slow consumers skip the updates they missed instead of failing.
+    pub async fn next(&mut self) -> Option<T> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(item) => return Some(item),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Monitor stream lagged, skipped {skipped} updates");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Pool snapshots decoded from raw account data and quoted fully offline.
+//!
+//! Unlike [`PoolSnapshot`](crate::replay::PoolSnapshot), these need neither
+//! the Raydium API nor an [`AmmSwapClient`](crate::amm::client::AmmSwapClient):
+//! backtests build them from historical account bytes with
+//! `from_account_data` and replay them through the same math as live quotes
+//! with [`quote_amm_from_snapshot`] and [`quote_clmm_from_snapshot`].
+
+use crate::amm::client::{
+    LiquidityStateLayoutV4, RpcPoolInfo, SwapQuote, amm_state_quote, clmm_swap_quote,
+};
+use crate::clmm::clmm_account_quote::offline_clmm_ladder;
+use crate::common::deserialize_anchor_account_data;
+use crate::states::{AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState};
+use crate::validation;
+use anyhow::anyhow;
+use borsh::BorshDeserialize;
+use solana_account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+
+/// AMM v4 pool state and reserves at one point in time.
+#[cfg_attr(feature = "derive", derive(Debug))]
+#[derive(Clone)]
+pub struct AmmPoolSnapshot {
+    pub pool_id: Pubkey,
+    pub state: LiquidityStateLayoutV4,
+    pub reserves: RpcPoolInfo,
+}
+
+impl AmmPoolSnapshot {
+    /// Decode the AMM v4 pool `pool_id` from the data of its pool account
+    /// and its base and quote vault accounts, whose owner tells whether they
+    /// are SPL Token or Token-2022 accounts.
+    pub fn from_account_data(
+        pool_id: Pubkey,
+        pool_data: &[u8],
+        base_vault: &Account,
+        quote_vault: &Account,
+    ) -> anyhow::Result<Self> {
+        let state = LiquidityStateLayoutV4::try_from_slice(pool_data)
+            .map_err(|e| anyhow!("Failed to decode AMM pool {pool_id}: {e:?}"))?;
+        let reserves = RpcPoolInfo::from_accounts(&state, base_vault, quote_vault)?;
+        Ok(Self {
+            pool_id,
+            state,
+            reserves,
+        })
+    }
+}
+
+/// CLMM pool accounts at one point in time.
+#[derive(Clone)]
+pub struct ClmmPoolSnapshot {
+    pub pool_id: Pubkey,
+    /// CLMM program owning the pool.
+    pub program_id: Pubkey,
+    pub pool_state: PoolState,
+    pub amm_config: AmmConfig,
+    /// `None` for pools without a bitmap extension account.
+    pub tickarray_bitmap_extension: Option<TickArrayBitmapExtension>,
+    /// Tick arrays by start tick index.
+    pub tick_arrays: BTreeMap<i32, TickArrayState>,
+}
+
+impl ClmmPoolSnapshot {
+    /// Decode the CLMM pool `pool_id` of the program `program_id` from the
+    /// data of its pool state, amm config, bitmap extension when it has one,
+    /// and of its tick arrays.
+    ///
+    /// Tick arrays may be given in any order. Quotes only walk the tick
+    /// arrays given, so include those of both directions the swaps to
+    /// replay may reach.
+    pub fn from_account_data<'a>(
+        pool_id: Pubkey,
+        program_id: Pubkey,
+        pool_data: &[u8],
+        amm_config_data: &[u8],
+        tickarray_bitmap_extension_data: Option<&[u8]>,
+        tick_array_data: impl IntoIterator<Item = &'a [u8]>,
+    ) -> anyhow::Result<Self> {
+        let pool_state: PoolState = deserialize_anchor_account_data(pool_data)?;
+        let amm_config: AmmConfig = deserialize_anchor_account_data(amm_config_data)?;
+        let tickarray_bitmap_extension = tickarray_bitmap_extension_data
+            .map(deserialize_anchor_account_data::<TickArrayBitmapExtension>)
+            .transpose()?;
+        let mut tick_arrays = BTreeMap::new();
+        for data in tick_array_data {
+            let tick_array: TickArrayState = deserialize_anchor_account_data(data)?;
+            if tick_array.pool_id.to_bytes() != pool_id.to_bytes() {
+                return Err(anyhow!(
+                    "Tick array starting at {} belongs to pool {}, not {pool_id}",
+                    { tick_array.start_tick_index },
+                    { tick_array.pool_id }
+                ));
+            }
+            tick_arrays.insert(tick_array.start_tick_index, tick_array);
+        }
+        Ok(Self {
+            pool_id,
+            program_id,
+            pool_state,
+            amm_config,
+            tickarray_bitmap_extension,
+            tick_arrays,
+        })
+    }
+}
+
+/// Quote an exact input swap of `amount_in` of `input_mint` against
+/// `snapshot`, as [`AmmSwapClient::quote`] does against the live pool.
+///
+/// [`AmmSwapClient::quote`]: crate::amm::client::AmmSwapClient::quote
+pub fn quote_amm_from_snapshot(
+    snapshot: &AmmPoolSnapshot,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<SwapQuote> {
+    validation::validate_amount(amount_in)?;
+    validation::validate_slippage(slippage)?;
+    amm_state_quote(
+        &snapshot.pool_id,
+        &snapshot.state,
+        snapshot.reserves.clone(),
+        input_mint,
+        amount_in,
+        slippage,
+    )
+}
+
+/// Quote an exact input swap of `amount_in` of `input_mint` against
+/// `snapshot`, as [`AmmSwapClient::quote`] does against the live pool.
+///
+/// Fails when the swap runs past the tick arrays of the snapshot.
+///
+/// [`AmmSwapClient::quote`]: crate::amm::client::AmmSwapClient::quote
+pub fn quote_clmm_from_snapshot(
+    snapshot: &ClmmPoolSnapshot,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<SwapQuote> {
+    validation::validate_amount(amount_in)?;
+    validation::validate_slippage(slippage)?;
+    let ladder = offline_clmm_ladder(
+        &snapshot.pool_id,
+        snapshot.pool_state,
+        snapshot.amm_config.clone(),
+        snapshot.tickarray_bitmap_extension,
+        input_mint,
+        |start_index| Ok(snapshot.tick_arrays.get(&start_index).copied()),
+    )?;
+    clmm_swap_quote(
+        &snapshot.pool_id,
+        &ladder,
+        input_mint,
+        amount_in,
+        slippage,
+        &solana_pubkey::Pubkey::from(snapshot.program_id.to_bytes()),
+    )
+}
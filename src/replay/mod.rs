@@ -0,0 +1,6 @@
+pub mod snapshot;
+pub use snapshot::*;
+pub mod quote;
+pub use quote::*;
+pub mod account_snapshot;
+pub use account_snapshot::*;
@@ -0,0 +1,205 @@
+//! Deterministic quotes against a [`PoolSnapshot`].
+
+use crate::amm::client::{AmmSwapClient, orient_amm_pool};
+use crate::clmm::{clmm_utils_sync, get_tick_arrays};
+use crate::common::deserialize_anchor_account;
+use crate::replay::PoolSnapshot;
+use crate::signer::SwapSigner;
+use crate::states::PoolState;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use solana_account::Account;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::solana_program::program_pack::Pack;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Order to quote against a snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayOrder {
+    pub input_mint: String,
+    /// Amount in when `base_out` is `false`, otherwise amount out.
+    pub amount: u64,
+    #[serde(default)]
+    pub base_out: bool,
+    pub slippage_bps: u64,
+}
+
+impl ReplayOrder {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Quote produced by a replay, comparable with a recorded one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReplayQuote {
+    /// The specified amount of the order.
+    pub amount: u64,
+    /// Estimated amount on the other side, when the computation reports it.
+    pub other_amount: Option<u64>,
+    /// Min amount out or max amount in after slippage.
+    pub other_amount_threshold: u64,
+}
+
+/// A field differing between a replayed and an expected quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteMismatch {
+    pub field: &'static str,
+    pub expected: Option<u64>,
+    pub actual: Option<u64>,
+}
+
+impl Display for QuoteMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: expected {:?}, got {:?}", self.field, self.expected, self.actual)
+    }
+}
+
+impl ReplayQuote {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Fields of `self` differing from `expected`. Empty when they match.
+    pub fn diff(&self, expected: &ReplayQuote) -> Vec<QuoteMismatch> {
+        [
+            ("amount", Some(expected.amount), Some(self.amount)),
+            ("other_amount", expected.other_amount, self.other_amount),
+            (
+                "other_amount_threshold",
+                Some(expected.other_amount_threshold),
+                Some(self.other_amount_threshold),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, expected, actual)| expected != actual)
+        .map(|(field, expected, actual)| QuoteMismatch {
+            field,
+            expected,
+            actual,
+        })
+        .collect()
+    }
+}
+
+/// Quote `order` against `snapshot` without touching the network.
+///
+/// `client` only provides the quote math; its RPC endpoint is never called.
+pub fn replay_quote(
+    client: &AmmSwapClient<impl SwapSigner>,
+    snapshot: &PoolSnapshot,
+    order: &ReplayOrder,
+) -> anyhow::Result<ReplayQuote> {
+    match snapshot {
+        PoolSnapshot::Amm { pool_info, .. } => {
+            let rpc_pool_info = snapshot.rpc_pool_info()?;
+            let slippage = order.slippage_bps as f64 / 10_000.0;
+            let (rpc_pool_info, pool_info) =
+                orient_amm_pool(rpc_pool_info, pool_info, &order.input_mint)?;
+            if order.base_out {
+                let quote =
+                    client.compute_amount_in(&rpc_pool_info, &pool_info, order.amount, slippage)?;
+                Ok(ReplayQuote {
+                    amount: order.amount,
+                    other_amount: Some(quote.amount_in),
+                    other_amount_threshold: quote.max_amount_in,
+                })
+            } else {
+                let quote =
+                    client.compute_amount_out(&rpc_pool_info, &pool_info, order.amount, slippage)?;
+                Ok(ReplayQuote {
+                    amount: order.amount,
+                    other_amount: Some(quote.amount_out),
+                    other_amount_threshold: quote.min_amount_out,
+                })
+            }
+        }
+        PoolSnapshot::Clmm {
+            epoch,
+            pool_state,
+            amm_config,
+            mint_0,
+            mint_1,
+            tick_array_bitmap_extension,
+            tick_arrays_zero_for_one,
+            tick_arrays_one_for_zero,
+            ..
+        } => {
+            let (input_mint_account, tick_arrays) = if order.input_mint == mint_0.address {
+                (mint_0, tick_arrays_zero_for_one)
+            } else if order.input_mint == mint_1.address {
+                (mint_1, tick_arrays_one_for_zero)
+            } else {
+                return Err(anyhow!("Input mint {} is not in the pool", order.input_mint));
+            };
+            let input_token_account = synthetic_token_account(
+                &Pubkey::from_str(&order.input_mint)?,
+                &input_mint_account.to_account()?.owner,
+                order.amount,
+            )?;
+            let rsps = vec![
+                Some(input_token_account),
+                Some(amm_config.to_account()?),
+                Some(mint_0.to_account()?),
+                Some(mint_1.to_account()?),
+                tick_array_bitmap_extension
+                    .as_ref()
+                    .map(|account| account.to_account())
+                    .transpose()?,
+            ];
+            let tick_arrays = get_tick_arrays(
+                tick_arrays
+                    .iter()
+                    .map(|account| account.to_account().map(Some))
+                    .collect::<anyhow::Result<_>>()?,
+            )?;
+            let pool_id = solana_pubkey::Pubkey::from_str(&pool_state.address)?;
+            let clmm_program = solana_pubkey::Pubkey::from_str(&pool_state.owner)?;
+            let pool_state: PoolState = deserialize_anchor_account(&pool_state.to_account()?)?;
+            let result = clmm_utils_sync::calculate_swap_change(
+                clmm_program,
+                pool_id,
+                solana_pubkey::Pubkey::default(),
+                order.amount,
+                None,
+                !order.base_out,
+                order.slippage_bps,
+                *epoch,
+                pool_state,
+                rsps,
+                tick_arrays,
+            )?;
+            Ok(ReplayQuote {
+                amount: result.amount,
+                other_amount: None,
+                other_amount_threshold: result.other_amount_threshold,
+            })
+        }
+    }
+}
+
+/// Stand-in for the user's input token account, which the CLMM computation
+/// reads to find the swap direction.
+pub(crate) fn synthetic_token_account(
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    amount: u64,
+) -> anyhow::Result<Account> {
+    let state = spl_token::state::Account {
+        mint: *mint,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account::pack(state, &mut data)?;
+    Ok(Account {
+        lamports: 0,
+        data,
+        owner: *token_program,
+        executable: false,
+        rent_epoch: 0,
+    })
+}
@@ -0,0 +1,230 @@
+//! Point-in-time pool snapshots that can be quoted offline.
+
+use crate::amm::client::{AmmSwapClient, RpcPoolInfo};
+use crate::clmm::{derive_tick_array_bitmap_extension_key, get_tick_array_keys};
+use crate::common::deserialize_anchor_account;
+use crate::interface::ClmmPool;
+use crate::signer::SwapSigner;
+use crate::states::{PoolState, TickArrayBitmapExtension};
+use anyhow::anyhow;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::{Deserialize, Serialize};
+use solana_account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Raw account data as stored in a snapshot file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotAccount {
+    pub address: String,
+    pub owner: String,
+    pub lamports: u64,
+    /// Base64 encoded account data.
+    pub data: String,
+}
+
+impl SnapshotAccount {
+    pub fn from_account(address: &Pubkey, account: &Account) -> Self {
+        Self {
+            address: address.to_string(),
+            owner: account.owner.to_string(),
+            lamports: account.lamports,
+            data: BASE64_STANDARD.encode(&account.data),
+        }
+    }
+
+    pub fn to_account(&self) -> anyhow::Result<Account> {
+        Ok(Account {
+            lamports: self.lamports,
+            data: BASE64_STANDARD.decode(&self.data)?,
+            owner: Pubkey::from_str(&self.owner)?,
+            executable: false,
+            rent_epoch: 0,
+        })
+    }
+}
+
+/// Everything needed to quote a pool without RPC access.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PoolSnapshot {
+    /// AMM v4 pool: API metadata and the reserves read on-chain.
+    Amm {
+        slot: u64,
+        pool_info: ClmmPool,
+        base_reserve: u64,
+        quote_reserve: u64,
+        swap_fee_numerator: u64,
+        swap_fee_denominator: u64,
+    },
+    /// CLMM pool: the raw accounts read by the swap computation, with the
+    /// tick arrays of both swap directions.
+    Clmm {
+        slot: u64,
+        epoch: u64,
+        pool_state: SnapshotAccount,
+        amm_config: SnapshotAccount,
+        mint_0: SnapshotAccount,
+        mint_1: SnapshotAccount,
+        /// `None` for pools without a bitmap extension account.
+        #[serde(default)]
+        tick_array_bitmap_extension: Option<SnapshotAccount>,
+        tick_arrays_zero_for_one: Vec<SnapshotAccount>,
+        tick_arrays_one_for_zero: Vec<SnapshotAccount>,
+    },
+}
+
+impl PoolSnapshot {
+    /// Slot the snapshot was captured at.
+    pub fn slot(&self) -> u64 {
+        match self {
+            PoolSnapshot::Amm { slot, .. } | PoolSnapshot::Clmm { slot, .. } => *slot,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Reserves of an AMM snapshot, with the mints, decimals and open time
+    /// of its API metadata.
+    pub(crate) fn rpc_pool_info(&self) -> anyhow::Result<RpcPoolInfo> {
+        match self {
+            PoolSnapshot::Amm {
+                pool_info,
+                base_reserve,
+                quote_reserve,
+                swap_fee_numerator,
+                swap_fee_denominator,
+                ..
+            } => Ok(RpcPoolInfo {
+                base_reserve: *base_reserve,
+                quote_reserve: *quote_reserve,
+                swap_fee_numerator: *swap_fee_numerator,
+                swap_fee_denominator: *swap_fee_denominator,
+                base_mint: Pubkey::from_str(&pool_info.mint_a.address)?,
+                quote_mint: Pubkey::from_str(&pool_info.mint_b.address)?,
+                base_decimals: pool_info.mint_a.decimals as u8,
+                quote_decimals: pool_info.mint_b.decimals as u8,
+                pool_open_time: pool_info
+                    .open_time
+                    .as_deref()
+                    .and_then(|open_time| open_time.parse().ok())
+                    .unwrap_or_default(),
+            }),
+            PoolSnapshot::Clmm { .. } => Err(anyhow!("Snapshot has no reserves")),
+        }
+    }
+}
+
+/// Capture an AMM v4 pool.
+pub async fn capture_amm_snapshot(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+) -> anyhow::Result<PoolSnapshot> {
+    let slot = client.rpc_client().get_slot().await?;
+    let pool_info = client
+        .fetch_pool_by_id(pool_id)
+        .await?
+        .data
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("Pool {} was not returned by the API", pool_id))?;
+    let rpc_pool_info = client.get_rpc_pool_info(pool_id).await?;
+    Ok(PoolSnapshot::Amm {
+        slot,
+        pool_info,
+        base_reserve: rpc_pool_info.base_reserve,
+        quote_reserve: rpc_pool_info.quote_reserve,
+        swap_fee_numerator: rpc_pool_info.swap_fee_numerator,
+        swap_fee_denominator: rpc_pool_info.swap_fee_denominator,
+    })
+}
+
+/// Capture a CLMM pool.
+pub async fn capture_clmm_snapshot(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+) -> anyhow::Result<PoolSnapshot> {
+    let rpc_client = client.rpc_client();
+    let slot = rpc_client.get_slot().await?;
+    let epoch = client.get_epoch().await?;
+    let pool_account = rpc_client.get_account(pool_id).await?;
+    let pool_state: PoolState = deserialize_anchor_account(&pool_account)?;
+
+    let clmm_program = solana_pubkey::Pubkey::from(pool_account.owner.to_bytes());
+    let pool_id_v2 = solana_pubkey::Pubkey::from(pool_id.to_bytes());
+    let bitmap_extension_key =
+        Pubkey::from(derive_tick_array_bitmap_extension_key(&pool_id_v2, &clmm_program).to_bytes());
+    let [amm_config, mint_0, mint_1] = fetch_accounts(
+        client,
+        &[
+            Pubkey::from(pool_state.amm_config.to_bytes()),
+            Pubkey::from(pool_state.token_mint_0.to_bytes()),
+            Pubkey::from(pool_state.token_mint_1.to_bytes()),
+        ],
+    )
+    .await?
+    .try_into()
+    .map_err(|_| anyhow!("Unexpected number of accounts"))?;
+    let tick_array_bitmap_extension = rpc_client
+        .get_account_with_commitment(&bitmap_extension_key, rpc_client.commitment())
+        .await?
+        .value
+        .map(|account| SnapshotAccount::from_account(&bitmap_extension_key, &account));
+
+    let bitmap_extension = tick_array_bitmap_extension
+        .as_ref()
+        .map(|account| {
+            deserialize_anchor_account::<TickArrayBitmapExtension>(&account.to_account()?)
+        })
+        .transpose()?;
+    let mut tick_arrays = Vec::with_capacity(2);
+    for zero_for_one in [true, false] {
+        let keys = get_tick_array_keys(
+            clmm_program,
+            pool_id_v2,
+            &pool_state,
+            &bitmap_extension,
+            zero_for_one,
+        )?;
+        tick_arrays.push(fetch_accounts(client, &keys).await?);
+    }
+    let tick_arrays_one_for_zero = tick_arrays.pop().unwrap_or_default();
+    let tick_arrays_zero_for_one = tick_arrays.pop().unwrap_or_default();
+
+    Ok(PoolSnapshot::Clmm {
+        slot,
+        epoch,
+        pool_state: SnapshotAccount::from_account(pool_id, &pool_account),
+        amm_config,
+        mint_0,
+        mint_1,
+        tick_array_bitmap_extension,
+        tick_arrays_zero_for_one,
+        tick_arrays_one_for_zero,
+    })
+}
+
+/// Fetch accounts that must all exist.
+async fn fetch_accounts(
+    client: &AmmSwapClient<impl SwapSigner>,
+    keys: &[Pubkey],
+) -> anyhow::Result<Vec<SnapshotAccount>> {
+    let accounts = client.rpc_client().get_multiple_accounts(keys).await?;
+    keys.iter()
+        .zip(accounts)
+        .map(|(key, account)| {
+            let account = account.ok_or(anyhow!("Account {} was not found", key))?;
+            Ok(SnapshotAccount::from_account(key, &account))
+        })
+        .collect()
+}
@@ -0,0 +1,260 @@
+//! Export of pool snapshots, trade history and quote ladders for research,
+//! behind the `research` feature.
+//!
+//! Each exporter builds a [`ResearchTable`], one row per snapshot, trade or
+//! quoted amount, which can be written as CSV, Arrow IPC or Parquet and read
+//! straight into dataframe tooling. Addresses and signatures are base58
+//! strings, amounts are raw token units and prices are in raw units of token
+//! 1 per token 0.
+
+use crate::clmm::{ClmmQuoteLadder, from_x64_price};
+use crate::common::{FeeTier, deserialize_anchor_account};
+use crate::history::PoolTrade;
+use crate::replay::PoolSnapshot;
+use crate::states::PoolState;
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, UInt64Array};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rows exported by this module, as an Arrow record batch.
+#[derive(Debug, Clone)]
+pub struct ResearchTable {
+    batch: RecordBatch,
+}
+
+impl ResearchTable {
+    fn new(columns: Vec<(&str, ArrayRef)>) -> anyhow::Result<Self> {
+        Ok(Self {
+            batch: RecordBatch::try_from_iter(columns)?,
+        })
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.batch.num_rows()
+    }
+
+    pub fn record_batch(&self) -> &RecordBatch {
+        &self.batch
+    }
+
+    pub fn into_record_batch(self) -> RecordBatch {
+        self.batch
+    }
+
+    /// Write the table as CSV, with a header row.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer = arrow::csv::Writer::new(File::create(path)?);
+        writer.write(&self.batch)?;
+        Ok(())
+    }
+
+    /// Write the table as an Arrow IPC file.
+    pub fn write_arrow(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer =
+            arrow::ipc::writer::FileWriter::try_new(File::create(path)?, &self.batch.schema())?;
+        writer.write(&self.batch)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Write the table as a Parquet file.
+    pub fn write_parquet(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer = ArrowWriter::try_new(File::create(path)?, self.batch.schema(), None)?;
+        writer.write(&self.batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// One row per snapshot. AMM rows carry the reserves, CLMM rows the price,
+/// liquidity and current tick decoded from the pool state.
+pub fn snapshots_table(snapshots: &[PoolSnapshot]) -> anyhow::Result<ResearchTable> {
+    let mut slot = Vec::with_capacity(snapshots.len());
+    let mut pool_type = Vec::with_capacity(snapshots.len());
+    let mut pool_id = Vec::with_capacity(snapshots.len());
+    let mut mint_0 = Vec::with_capacity(snapshots.len());
+    let mut mint_1 = Vec::with_capacity(snapshots.len());
+    let mut reserve_0 = Vec::with_capacity(snapshots.len());
+    let mut reserve_1 = Vec::with_capacity(snapshots.len());
+    let mut trade_fee_rate = Vec::with_capacity(snapshots.len());
+    let mut price = Vec::with_capacity(snapshots.len());
+    let mut liquidity = Vec::with_capacity(snapshots.len());
+    let mut tick_current = Vec::with_capacity(snapshots.len());
+
+    for snapshot in snapshots {
+        slot.push(snapshot.slot());
+        match snapshot {
+            PoolSnapshot::Amm {
+                pool_info,
+                base_reserve,
+                quote_reserve,
+                swap_fee_numerator,
+                swap_fee_denominator,
+                ..
+            } => {
+                pool_type.push("standard");
+                pool_id.push(pool_info.id.clone());
+                mint_0.push(pool_info.mint_a.address.clone());
+                mint_1.push(pool_info.mint_b.address.clone());
+                reserve_0.push(Some(*base_reserve));
+                reserve_1.push(Some(*quote_reserve));
+                let fee_tier = FeeTier::from_amm(*swap_fee_numerator, *swap_fee_denominator);
+                trade_fee_rate.push(Some(fee_tier.trade_fee_rate));
+                price.push(
+                    (*base_reserve > 0).then(|| *quote_reserve as f64 / *base_reserve as f64),
+                );
+                liquidity.push(None);
+                tick_current.push(None);
+            }
+            PoolSnapshot::Clmm { pool_state, .. } => {
+                let state = deserialize_anchor_account::<PoolState>(&pool_state.to_account()?)?;
+                let (token_mint_0, token_mint_1) = (state.token_mint_0, state.token_mint_1);
+                let (sqrt_price_x64, pool_liquidity) = (state.sqrt_price_x64, state.liquidity);
+                let sqrt_price = from_x64_price(sqrt_price_x64);
+                pool_type.push("concentrated");
+                pool_id.push(pool_state.address.clone());
+                mint_0.push(token_mint_0.to_string());
+                mint_1.push(token_mint_1.to_string());
+                reserve_0.push(None);
+                reserve_1.push(None);
+                trade_fee_rate.push(None);
+                price.push(Some(sqrt_price * sqrt_price));
+                liquidity.push(Some(pool_liquidity.to_string()));
+                tick_current.push(Some(i64::from(state.tick_current)));
+            }
+        }
+    }
+
+    ResearchTable::new(vec![
+        ("slot", Arc::new(UInt64Array::from(slot)) as ArrayRef),
+        ("pool_type", Arc::new(StringArray::from(pool_type))),
+        ("pool_id", Arc::new(StringArray::from(pool_id))),
+        ("mint_0", Arc::new(StringArray::from(mint_0))),
+        ("mint_1", Arc::new(StringArray::from(mint_1))),
+        ("reserve_0", Arc::new(UInt64Array::from(reserve_0))),
+        ("reserve_1", Arc::new(UInt64Array::from(reserve_1))),
+        (
+            "trade_fee_rate",
+            Arc::new(Float64Array::from(trade_fee_rate)),
+        ),
+        ("price", Arc::new(Float64Array::from(price))),
+        // u128 does not fit any Arrow integer type; kept exact as a string.
+        ("liquidity", Arc::new(StringArray::from(liquidity))),
+        ("tick_current", Arc::new(Int64Array::from(tick_current))),
+    ])
+}
+
+/// One row per trade, as returned by
+/// [`fetch_pool_trades`](crate::history::fetch_pool_trades).
+pub fn trades_table(trades: &[PoolTrade]) -> anyhow::Result<ResearchTable> {
+    let column = |value: fn(&PoolTrade) -> String| {
+        StringArray::from(trades.iter().map(value).collect::<Vec<_>>())
+    };
+    let signature = column(|trade| trade.signature.to_string());
+    let pool_id = column(|trade| trade.pool_id.to_string());
+    let mint_0 = column(|trade| trade.mint_0.to_string());
+    let mint_1 = column(|trade| trade.mint_1.to_string());
+    let trader: Vec<Option<String>> = trades
+        .iter()
+        .map(|trade| trade.trader.map(|trader| trader.to_string()))
+        .collect();
+
+    ResearchTable::new(vec![
+        ("signature", Arc::new(signature) as ArrayRef),
+        (
+            "slot",
+            Arc::new(UInt64Array::from_iter_values(
+                trades.iter().map(|trade| trade.slot),
+            )),
+        ),
+        (
+            "block_time",
+            Arc::new(Int64Array::from_iter(
+                trades.iter().map(|trade| trade.block_time),
+            )),
+        ),
+        ("pool_id", Arc::new(pool_id)),
+        ("trader", Arc::new(StringArray::from(trader))),
+        ("mint_0", Arc::new(mint_0)),
+        ("mint_1", Arc::new(mint_1)),
+        (
+            "zero_for_one",
+            Arc::new(BooleanArray::from_iter(
+                trades.iter().map(|trade| Some(trade.zero_for_one)),
+            )),
+        ),
+        (
+            "amount_0",
+            Arc::new(UInt64Array::from_iter_values(
+                trades.iter().map(|trade| trade.amount_0),
+            )),
+        ),
+        (
+            "amount_1",
+            Arc::new(UInt64Array::from_iter_values(
+                trades.iter().map(|trade| trade.amount_1),
+            )),
+        ),
+        (
+            "price",
+            Arc::new(Float64Array::from_iter(trades.iter().map(PoolTrade::price))),
+        ),
+    ])
+}
+
+/// One row per amount of `amounts` quoted against `ladder`. Amounts that
+/// cannot be quoted, e.g. running past the loaded tick arrays, have no
+/// output columns.
+pub fn quote_ladder_table(
+    ladder: &ClmmQuoteLadder,
+    amounts: &[u64],
+) -> anyhow::Result<ResearchTable> {
+    let mut amount_out = Vec::with_capacity(amounts.len());
+    let mut amount_consumed = Vec::with_capacity(amounts.len());
+    let mut fully_filled = Vec::with_capacity(amounts.len());
+    let mut tick_arrays = Vec::with_capacity(amounts.len());
+    let mut price_impact_bps = Vec::with_capacity(amounts.len());
+    for &amount_in in amounts {
+        match ladder.quote(amount_in) {
+            Ok(result) => {
+                amount_out.push(Some(result.other_amount));
+                amount_consumed.push(Some(result.amount_consumed));
+                fully_filled.push(Some(result.fully_filled));
+                tick_arrays.push(Some(result.tick_array_start_indexes.len() as u64));
+                price_impact_bps.push(Some(ladder.price_impact_bps(amount_in, &result)));
+            }
+            Err(_) => {
+                amount_out.push(None);
+                amount_consumed.push(None);
+                fully_filled.push(None);
+                tick_arrays.push(None);
+                price_impact_bps.push(None);
+            }
+        }
+    }
+
+    ResearchTable::new(vec![
+        (
+            "amount_in",
+            Arc::new(UInt64Array::from(amounts.to_vec())) as ArrayRef,
+        ),
+        (
+            "zero_for_one",
+            Arc::new(BooleanArray::from(vec![ladder.zero_for_one; amounts.len()])),
+        ),
+        ("amount_out", Arc::new(UInt64Array::from(amount_out))),
+        (
+            "amount_consumed",
+            Arc::new(UInt64Array::from(amount_consumed)),
+        ),
+        ("fully_filled", Arc::new(BooleanArray::from(fully_filled))),
+        ("tick_arrays", Arc::new(UInt64Array::from(tick_arrays))),
+        (
+            "price_impact_bps",
+            Arc::new(Float64Array::from(price_impact_bps)),
+        ),
+    ])
+}
@@ -0,0 +1,189 @@
+//! Arbitrage between two pools of the same pair, e.g. an AMM v4 and a CLMM
+//! pool whose prices drifted apart.
+//!
+//! [`find_arbitrage`] quotes the cycle selling a mint on one pool and buying
+//! it back on the other, in both directions, and keeps the more profitable
+//! one after pool fees. [`execute_arbitrage`] builds both legs into a single
+//! transaction, so they land together or not at all, and sends it only when
+//! its simulation returns more than the input.
+
+use crate::amm::client::{AmmSwapClient, PoolRef, SwapQuote};
+use crate::error::SwapError;
+use crate::execution::simulate_swap_transaction;
+use crate::interface::ClmmPool;
+use crate::router::{HopKind, MultiHopQuote, build_multi_hop_swap};
+use crate::signer::SwapSigner;
+use crate::validation;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use tracing::{debug, info};
+
+/// Profitable cycle found by [`find_arbitrage`].
+#[derive(Debug, Clone)]
+pub struct ArbitrageCycle {
+    /// Both legs: the first sells `amount_in` of the start mint, the second
+    /// sells the minimum output of the first back into the start mint.
+    pub quote: MultiHopQuote,
+    /// Quoted output of the cycle less its input, in the smallest units of
+    /// the start mint. Pool fees are included, transaction fees are not.
+    pub expected_profit: u64,
+}
+
+/// Quote the cycles from `mint` back to itself through `pools`, two pools of
+/// the same pair, in both orders, with `slippage` as a fraction (e.g. `0.005`
+/// for 0.5%) on each leg.
+///
+/// Returns the cycle with the largest output when it is above `amount_in`,
+/// `None` when neither order is profitable. Only AMM v4 and CLMM pools are
+/// supported.
+pub async fn find_arbitrage(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pools: [&ClmmPool; 2],
+    mint: &str,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<Option<ArbitrageCycle>> {
+    validation::validate_amount(amount_in)?;
+    validation::validate_slippage(slippage)?;
+    for pool_info in pools {
+        validation::validate_pool_mint(pool_info, mint)?;
+    }
+    let [pool_a, pool_b] = pools;
+    let other_mint = |pool_info: &ClmmPool| {
+        if pool_info.mint_a.address == mint {
+            pool_info.mint_b.address.clone()
+        } else {
+            pool_info.mint_a.address.clone()
+        }
+    };
+    if pool_a.id == pool_b.id || other_mint(pool_a) != other_mint(pool_b) {
+        return Err(anyhow!(
+            "Pools {} and {} are not two pools of the same pair",
+            pool_a.id,
+            pool_b.id
+        ));
+    }
+
+    let mint = Pubkey::from_str(mint)?;
+    let mut best: Option<MultiHopQuote> = None;
+    for (first, second) in [(pool_a, pool_b), (pool_b, pool_a)] {
+        let quote = quote_cycle(client, first, second, &mint, amount_in, slippage).await?;
+        debug!(
+            "Cycle of {amount_in} {mint} through {} then {} returns {}",
+            first.id, second.id, quote.amount_out
+        );
+        if quote.amount_out > amount_in
+            && best
+                .as_ref()
+                .is_none_or(|best| quote.amount_out > best.amount_out)
+        {
+            best = Some(quote);
+        }
+    }
+    Ok(best.map(|quote| ArbitrageCycle {
+        expected_profit: quote.amount_out - amount_in,
+        quote,
+    }))
+}
+
+/// Send both legs of `cycle` in one transaction, between the owner's
+/// associated token accounts.
+///
+/// The last leg requires at least the input of the cycle back, so the
+/// transaction fails rather than lose on the way. It is simulated first and
+/// not sent when the simulation fails or returns no profit, the latter with
+/// `SwapError::ArbitrageLoss`. Fails as well when the legs do not fit in one
+/// transaction.
+///
+/// The owner's associated token account of the start mint must already hold
+/// the amount, wSOL included.
+pub async fn execute_arbitrage(
+    client: &AmmSwapClient<impl SwapSigner>,
+    cycle: &ArbitrageCycle,
+) -> anyhow::Result<Signature> {
+    let amount_in = cycle.quote.amount_in;
+    let mut quote = cycle.quote.clone();
+    let last = quote
+        .hops
+        .last_mut()
+        .ok_or(anyhow!("Arbitrage cycle has no legs"))?;
+    last.min_amount_out = last.min_amount_out.max(amount_in);
+    quote.min_amount_out = last.min_amount_out;
+
+    let transactions = build_multi_hop_swap(client, &quote).await?;
+    let [transaction] = transactions.as_slice() else {
+        return Err(anyhow!(
+            "Arbitrage legs do not fit in one transaction, not sending them"
+        ));
+    };
+    let simulation = simulate_swap_transaction(client.rpc_client(), transaction).await?;
+    if let Some(err) = simulation.err {
+        return Err(anyhow!("Simulation of the arbitrage cycle failed: {err}"));
+    }
+    let amount_out = simulation
+        .amount_out
+        .ok_or(anyhow!("Simulation of the arbitrage cycle logged no swap"))?;
+    if amount_out <= amount_in {
+        return Err(SwapError::ArbitrageLoss {
+            amount_in,
+            amount_out,
+        }
+        .into());
+    }
+
+    let signature = client.send_signed(transaction).await?;
+    info!("Executed arbitrage of {amount_in} for {amount_out} simulated, signature {signature}");
+    Ok(signature)
+}
+
+/// Quote selling `amount_in` of `mint` on `first`, then its minimum output
+/// on `second`.
+async fn quote_cycle(
+    client: &AmmSwapClient<impl SwapSigner>,
+    first: &ClmmPool,
+    second: &ClmmPool,
+    mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<MultiHopQuote> {
+    let first_leg = quote_leg(client, first, mint, amount_in, slippage).await?;
+    let second_leg = quote_leg(
+        client,
+        second,
+        &first_leg.output_mint,
+        first_leg.min_amount_out,
+        slippage,
+    )
+    .await?;
+    Ok(MultiHopQuote {
+        amount_in,
+        amount_out: second_leg.amount_out,
+        min_amount_out: second_leg.min_amount_out,
+        hops: vec![first_leg, second_leg],
+    })
+}
+
+async fn quote_leg(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_info: &ClmmPool,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<SwapQuote> {
+    let program_id = Pubkey::from_str(&pool_info.program_id)?;
+    let pool_ref = match client.cluster_config().hop_kind(&program_id) {
+        Some(HopKind::AmmV4) => PoolRef::Amm(pool_info),
+        Some(HopKind::Clmm) => PoolRef::Clmm(Pubkey::from_str(&pool_info.id)?),
+        _ => {
+            return Err(anyhow!(
+                "Pool {} is not an AMM v4 or CLMM pool",
+                pool_info.id
+            ));
+        }
+    };
+    client
+        .quote(&pool_ref, input_mint, amount_in, slippage)
+        .await
+}
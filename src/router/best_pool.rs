@@ -0,0 +1,123 @@
+//! Best pool of a pair, verified against on-chain state.
+//!
+//! The reserves and prices listed by the Raydium API lag the chain, so
+//! [`select_best_pool`](crate::router::select_best_pool) can pick a pool that
+//! has since moved. [`find_best_pool`] only takes the deepest listed pools
+//! as candidates and ranks them by quotes against their on-chain accounts.
+
+use crate::amm::client::{AmmSwapClient, SwapQuote};
+use crate::interface::{ClmmPool, PoolSortField, PoolType};
+use crate::signer::SwapSigner;
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+/// Pool chosen by [`find_best_pool`] and its on-chain quote.
+#[derive(Debug, Clone)]
+pub struct BestPool {
+    pub pool_info: ClmmPool,
+    pub quote: SwapQuote,
+}
+
+/// Quote swapping `amount_in` of `input_mint` into `output_mint` on the
+/// `top_k` deepest AMM v4, CLMM and CPMM pools of the pair, by TVL as
+/// listed by the Raydium API, and return the one with the largest amount
+/// out after fees and price impact.
+///
+/// Candidates are quoted together with [`AmmSwapClient::quote_many`];
+/// those failing to quote are skipped with a warning. `None` when no
+/// candidate could be quoted.
+pub async fn find_best_pool(
+    client: &AmmSwapClient<impl SwapSigner>,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+    top_k: usize,
+) -> anyhow::Result<Option<BestPool>> {
+    let cluster = client.cluster_config();
+    find_best_pool_for_programs(
+        client,
+        input_mint,
+        output_mint,
+        amount_in,
+        slippage,
+        top_k,
+        &[
+            &cluster.amm_v4.to_string(),
+            &cluster.clmm.to_string(),
+            &cluster.cpmm.to_string(),
+        ],
+    )
+    .await
+}
+
+/// Same as [`find_best_pool`], among the pools of `programs` only.
+pub(crate) async fn find_best_pool_for_programs(
+    client: &AmmSwapClient<impl SwapSigner>,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    amount_in: u64,
+    slippage: f64,
+    top_k: usize,
+    programs: &[&str],
+) -> anyhow::Result<Option<BestPool>> {
+    let candidates = fetch_candidates(client, input_mint, output_mint, top_k, programs).await?;
+    let pool_ids = candidates
+        .iter()
+        .map(|pool_info| Ok(pool_info.id.parse()?))
+        .collect::<anyhow::Result<Vec<Pubkey>>>()?;
+    let quotes = client
+        .quote_many(&pool_ids, input_mint, amount_in, slippage)
+        .await?;
+
+    Ok(candidates
+        .into_iter()
+        .zip(quotes)
+        .filter_map(|(pool_info, quote)| match quote {
+            Ok(quote) => Some(BestPool { pool_info, quote }),
+            Err(e) => {
+                warn!(
+                    "Pool {} could not be quoted, skipping it: {e:#}",
+                    pool_info.id
+                );
+                None
+            }
+        })
+        .max_by_key(|best| best.quote.amount_out))
+}
+
+/// Up to `top_k` pools of the pair of `programs` with the largest TVL.
+async fn fetch_candidates(
+    client: &AmmSwapClient<impl SwapSigner>,
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    top_k: usize,
+    programs: &[&str],
+) -> anyhow::Result<Vec<ClmmPool>> {
+    let (input_mint, output_mint) = (input_mint.to_string(), output_mint.to_string());
+    let page_size = u32::try_from(top_k).unwrap_or(u32::MAX);
+    let sort_field = PoolSortField::Liquidity.to_string();
+    let mut candidates: Vec<ClmmPool> = Vec::new();
+    for pool_type in [PoolType::Standard, PoolType::Concentrated] {
+        let listed = client
+            .fetch_pool_info_for_programs(
+                &input_mint,
+                &output_mint,
+                &pool_type,
+                Some(page_size),
+                Some(1),
+                Some(sort_field.as_str()),
+                Some("desc"),
+                programs,
+            )
+            .await?;
+        for pool in listed {
+            if !candidates.iter().any(|known| known.id == pool.id) {
+                candidates.push(pool);
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.tvl.unwrap_or(0.0).total_cmp(&a.tvl.unwrap_or(0.0)));
+    candidates.truncate(top_k);
+    Ok(candidates)
+}
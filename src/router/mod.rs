@@ -0,0 +1,20 @@
+pub mod multi_hop;
+pub use multi_hop::*;
+pub mod pool_selection;
+pub use pool_selection::*;
+pub mod route_planner;
+pub use route_planner::*;
+pub mod sizing;
+pub use sizing::*;
+pub mod quote_scheduler;
+pub use quote_scheduler::*;
+pub mod pool_comparison;
+pub use pool_comparison::*;
+pub mod best_pool;
+pub use best_pool::*;
+pub mod pool_discovery;
+pub use pool_discovery::*;
+pub mod split;
+pub use split::*;
+pub mod arbitrage;
+pub use arbitrage::*;
@@ -0,0 +1,298 @@
+//! Swaps across several pools for pairs without a direct pool, e.g.
+//! BONK -> SOL -> USDC.
+//!
+//! Candidate pools are listed by the Raydium API ([`fetch_route_pools`]) and
+//! ranked with [`plan_routes`]; the chosen route is then quoted on-chain hop by
+//! hop ([`quote_route`]) and built into a single transaction when it fits
+//! ([`build_multi_hop_swap`]). AMM v4 and CLMM hops are supported.
+
+use crate::amm::client::{AmmSwapClient, PoolRef, SwapQuote, SwapRequest};
+use crate::clmm::{
+    ClmmSwapChangeResult, calculate_swap_change_accounts, derive_tick_array_bitmap_extension_key,
+};
+use crate::consts::{SOL_MINT, USDC_MINT, USDT_MINT};
+use crate::interface::{AmmPool, ClmmPool, ClmmSwapParams, PoolKeys, PoolType};
+use crate::replay::synthetic_token_account;
+use crate::router::{HopKind, RoutePlannerConfig, RouteQuote, plan_routes};
+use crate::signer::SwapSigner;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use std::str::FromStr;
+use tracing::debug;
+
+/// Mints commonly paired with both sides of a swap, tried as the middle of
+/// two-hop routes.
+pub const DEFAULT_INTERMEDIATE_MINTS: [&str; 3] = [SOL_MINT, USDC_MINT, USDT_MINT];
+
+/// Pools listed per pair and pool type.
+const ROUTE_POOLS_PAGE_SIZE: u32 = 20;
+
+/// A route found by [`find_routes`], with the pools of its hops.
+#[derive(Debug, Clone)]
+pub struct MultiHopRoute {
+    pub route: RouteQuote,
+    /// Pool of each hop, in the order of `route.hops`.
+    pub pools: Vec<ClmmPool>,
+}
+
+/// On-chain quote of a route, returned by [`quote_route`].
+#[derive(Debug, Clone)]
+pub struct MultiHopQuote {
+    /// Quote of each hop. Every hop after the first swaps the
+    /// `min_amount_out` of the previous one.
+    pub hops: Vec<SwapQuote>,
+    pub amount_in: u64,
+    /// Raw output of the last hop before slippage.
+    pub amount_out: u64,
+    /// Minimum output of the last hop after slippage tolerance.
+    pub min_amount_out: u64,
+}
+
+/// List the AMM v4 and CLMM pools of `input_mint`/`output_mint` and of every
+/// pair linking them through one of `intermediate_mints`, without duplicates.
+pub async fn fetch_route_pools(
+    client: &AmmSwapClient<impl SwapSigner>,
+    input_mint: &str,
+    output_mint: &str,
+    intermediate_mints: &[&str],
+) -> anyhow::Result<Vec<ClmmPool>> {
+    let mut pairs = vec![(input_mint, output_mint)];
+    for mint in intermediate_mints
+        .iter()
+        .copied()
+        .filter(|mint| *mint != input_mint && *mint != output_mint)
+    {
+        pairs.push((input_mint, mint));
+        pairs.push((mint, output_mint));
+    }
+
+    let mut pools: Vec<ClmmPool> = Vec::new();
+    for (mint_a, mint_b) in pairs {
+        for pool_type in [PoolType::Standard, PoolType::Concentrated] {
+            let listed = client
+                .fetch_pool_info(
+                    mint_a,
+                    mint_b,
+                    &pool_type,
+                    Some(ROUTE_POOLS_PAGE_SIZE),
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+            for pool in listed {
+                if !pools.iter().any(|known| known.id == pool.id) {
+                    pools.push(pool);
+                }
+            }
+        }
+    }
+    debug!("Found {} candidate pools from {input_mint} to {output_mint}", pools.len());
+    Ok(pools)
+}
+
+/// Routes from `input_mint` to `output_mint` through the pools of
+/// [`fetch_route_pools`], best estimated output first.
+///
+/// `amount_in` is in UI units of `input_mint`. The direct route is among the
+/// candidates when a pool of the pair exists. CPMM hops are never returned,
+/// as they cannot be built.
+pub async fn find_routes(
+    client: &AmmSwapClient<impl SwapSigner>,
+    input_mint: &str,
+    output_mint: &str,
+    amount_in: f64,
+    intermediate_mints: &[&str],
+    config: &RoutePlannerConfig,
+) -> anyhow::Result<Vec<MultiHopRoute>> {
+    let pools = fetch_route_pools(client, input_mint, output_mint, intermediate_mints).await?;
+    let config = RoutePlannerConfig {
+        allowed_kinds: config
+            .allowed_kinds
+            .iter()
+            .copied()
+            .filter(|kind| *kind != HopKind::Cpmm)
+            .collect(),
+        ..config.clone()
+    };
+    let routes = plan_routes(
+        client.cluster_config(),
+        &pools,
+        input_mint,
+        output_mint,
+        amount_in,
+        &config,
+    );
+    routes
+        .into_iter()
+        .map(|route| {
+            let hop_pools = route
+                .hops
+                .iter()
+                .map(|hop| {
+                    pools
+                        .iter()
+                        .find(|pool| pool.id == hop.pool_id)
+                        .cloned()
+                        .ok_or(anyhow!("Pool {} of the route was not listed", hop.pool_id))
+                })
+                .collect::<anyhow::Result<_>>()?;
+            Ok(MultiHopRoute {
+                route,
+                pools: hop_pools,
+            })
+        })
+        .collect()
+}
+
+/// Quote `amount_in` (smallest units) through every hop of `route`, with
+/// `slippage` as a fraction (e.g. `0.005` for 0.5%).
+///
+/// Each hop after the first is quoted for the minimum output of the previous
+/// one, so its input is available whatever the slippage; any output above
+/// that minimum stays in the intermediate token account.
+pub async fn quote_route(
+    client: &AmmSwapClient<impl SwapSigner>,
+    route: &MultiHopRoute,
+    amount_in: u64,
+    slippage: f64,
+) -> anyhow::Result<MultiHopQuote> {
+    let mut hops: Vec<SwapQuote> = Vec::with_capacity(route.pools.len());
+    let mut amount = amount_in;
+    for (hop, pool) in route.route.hops.iter().zip(&route.pools) {
+        let pool_ref = match hop.kind {
+            HopKind::AmmV4 => PoolRef::Amm(pool),
+            HopKind::Clmm => PoolRef::Clmm(Pubkey::from_str(&pool.id)?),
+            HopKind::Cpmm => return Err(anyhow!("CPMM hop through {} is not supported", pool.id)),
+        };
+        let input_mint = Pubkey::from_str(&hop.input_mint)?;
+        let quote = client.quote(&pool_ref, &input_mint, amount, slippage).await?;
+        amount = quote.min_amount_out;
+        hops.push(quote);
+    }
+    let last = hops.last().ok_or(anyhow!("Route has no hops"))?;
+    Ok(MultiHopQuote {
+        amount_in,
+        amount_out: last.amount_out,
+        min_amount_out: last.min_amount_out,
+        hops,
+    })
+}
+
+/// Build and sign the swaps of `quote` between the owner's associated token
+/// accounts, without sending them.
+///
+/// All hops go in a single transaction when they fit, so the route executes
+/// atomically; otherwise one transaction per hop is returned, to be sent in
+/// order (see [`AmmSwapClient::build_swap_route`]). Missing output accounts,
+/// intermediate ones included, are created along the way.
+pub async fn build_multi_hop_swap(
+    client: &AmmSwapClient<impl SwapSigner>,
+    quote: &MultiHopQuote,
+) -> anyhow::Result<Vec<VersionedTransaction>> {
+    let mut requests = Vec::with_capacity(quote.hops.len());
+    for hop in &quote.hops {
+        let request = match hop.pool_type {
+            PoolType::Standard => {
+                let pool_keys: PoolKeys<AmmPool> =
+                    client.fetch_pools_keys_by_id(&hop.pool_id).await?;
+                let pool_keys = pool_keys
+                    .data
+                    .into_iter()
+                    .next()
+                    .ok_or(anyhow!("No keys returned for pool {}", hop.pool_id))?;
+                SwapRequest::Amm {
+                    pool_keys,
+                    mint_in: hop.input_mint,
+                    mint_out: hop.output_mint,
+                    amount_in: hop.amount_in,
+                    min_amount_out: hop.min_amount_out,
+                    close_source_account: false,
+                }
+            }
+            PoolType::Concentrated => clmm_swap_request(client, hop).await?,
+        };
+        requests.push(request);
+    }
+    client.build_swap_route(requests).await
+}
+
+/// Build the swaps of `quote` with [`build_multi_hop_swap`] and send them in
+/// order, each confirmed before the next is sent.
+pub async fn execute_multi_hop_swap(
+    client: &AmmSwapClient<impl SwapSigner>,
+    quote: &MultiHopQuote,
+) -> anyhow::Result<Vec<Signature>> {
+    let transactions = build_multi_hop_swap(client, quote).await?;
+    let mut signatures = Vec::with_capacity(transactions.len());
+    for transaction in &transactions {
+        signatures.push(client.send_signed(transaction).await?);
+    }
+    Ok(signatures)
+}
+
+/// CLMM swap of `hop` from the owner's associated token account of its input.
+async fn clmm_swap_request(
+    client: &AmmSwapClient<impl SwapSigner>,
+    hop: &SwapQuote,
+) -> anyhow::Result<SwapRequest> {
+    let token_program = client.rpc_client().get_account(&hop.input_mint).await?.owner;
+    let user_input_token = get_associated_token_address_with_program_id(
+        &client.owner_pubkey(),
+        &hop.input_mint,
+        &token_program,
+    );
+    let user_input_token = solana_pubkey::Pubkey::from(user_input_token.to_bytes());
+    let pool_id = solana_pubkey::Pubkey::from(hop.pool_id.to_bytes());
+
+    let epoch = client.get_epoch().await?;
+    let pool_state = client.get_pool_state(&hop.pool_id).await?;
+    let program_id = client.cluster_config().clmm_program_id();
+    let tick_array_bitmap_extension = derive_tick_array_bitmap_extension_key(&pool_id, &program_id);
+    let mut rsps = client
+        .get_rsps(user_input_token, &pool_state, &tick_array_bitmap_extension)
+        .await?;
+    // The input account of a later hop may only be created by the previous
+    // hop of the same transaction.
+    if rsps[0].is_none() {
+        rsps[0] = Some(synthetic_token_account(&hop.input_mint, &token_program, hop.amount_in)?);
+    }
+    let params = calculate_swap_change_accounts(&rsps, hop.amount_in, pool_state, true, epoch)?;
+    let tick_arrays = client
+        .load_cur_and_next_five_tick_array(
+            program_id,
+            pool_id,
+            &pool_state,
+            &params.tickarray_bitmap_extension_state,
+            params.zero_for_one,
+        )
+        .await?;
+    let (swap_change, tick_array_bitmap_extension) = client.calculate_swap_change_clmm_sync(
+        ClmmSwapParams {
+            pool_id,
+            user_input_token,
+            user_output_token: None,
+            amount_specified: hop.amount_in,
+            limit_price: None,
+            base_out: false,
+            slippage_bps: 0,
+            max_price_impact_bps: None,
+        },
+        epoch,
+        pool_state,
+        rsps,
+        tick_arrays,
+        tick_array_bitmap_extension,
+    )?;
+    // The next hop is sized from the minimum output of the quote.
+    Ok(SwapRequest::Clmm {
+        swap_change: ClmmSwapChangeResult {
+            other_amount_threshold: hop.min_amount_out,
+            ..swap_change
+        },
+        tick_array_bitmap_extension,
+    })
+}
@@ -0,0 +1,196 @@
+//! Side-by-side comparison of AMM v4 and CLMM execution of a pair.
+//!
+//! [`compare_pools`] quotes the same trade on the deepest standard and the
+//! deepest concentrated pool of a pair and reports, next to the amounts, what
+//! executing on each costs: the accounts the swap instruction locks and its
+//! expected compute units. This is meant for choosing default routing
+//! policies; the compute units are typical figures, simulate the transaction
+//! for the exact count.
+
+use crate::amm::client::{AmmSwapClient, PoolRef, SwapQuote, clmm_swap_quote};
+use crate::clmm::CLMM_SWAP_V2_FIXED_ACCOUNTS;
+use crate::common::FeeTier;
+use crate::interface::{ClmmPool, PoolSortField, PoolType};
+use crate::signer::SwapSigner;
+use crate::validation;
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+/// Pools listed per pool type; the deepest AMM v4 or CLMM one is compared.
+const COMPARISON_PAGE_SIZE: u32 = 10;
+/// Accounts of the AMM v4 `SwapBaseIn` instruction.
+const AMM_V4_SWAP_ACCOUNTS: usize = 17;
+/// Typical compute units of an AMM v4 swap.
+const AMM_V4_SWAP_COMPUTE_UNITS: u32 = 45_000;
+/// Typical compute units of a CLMM swap staying within one tick array.
+const CLMM_SWAP_COMPUTE_UNITS: u32 = 60_000;
+/// Typical compute units added by each further tick array a CLMM swap
+/// moves through.
+const CLMM_TICK_ARRAY_COMPUTE_UNITS: u32 = 25_000;
+
+/// Execution of a trade on one pool, see [`compare_pools`].
+#[derive(Debug, Clone)]
+pub struct ExecutionReport {
+    pub pool_id: Pubkey,
+    pub pool_type: PoolType,
+    /// Raw amount out, no slippage applied.
+    pub amount_out: u64,
+    /// Fee deducted from the input.
+    pub fee: u64,
+    pub fee_tier: FeeTier,
+    /// Percent price impact of the trade, fees excluded.
+    pub price_impact: f64,
+    /// Tick arrays the swap moves through past the current one. Always 0
+    /// for AMM pools.
+    pub tick_arrays_crossed: usize,
+    /// Accounts of the swap instruction, the user's included.
+    pub account_count: usize,
+    /// Typical compute units of the swap instruction.
+    pub expected_compute_units: u32,
+}
+
+/// The same trade executed on the deepest standard and concentrated pools
+/// of a pair. A side is `None` when the pair has no such pool or the pool
+/// could not be quoted.
+#[derive(Debug, Clone)]
+pub struct PoolComparison {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    pub standard: Option<ExecutionReport>,
+    pub concentrated: Option<ExecutionReport>,
+}
+
+impl PoolComparison {
+    /// Side giving the largest amount out.
+    pub fn best(&self) -> Option<&ExecutionReport> {
+        [self.standard.as_ref(), self.concentrated.as_ref()]
+            .into_iter()
+            .flatten()
+            .max_by_key(|report| report.amount_out)
+    }
+}
+
+/// Compare swapping `amount_in` of `mint_a` into `mint_b` on the deepest AMM
+/// v4 pool and the deepest CLMM pool of the pair, by liquidity as listed by
+/// the Raydium API.
+pub async fn compare_pools(
+    client: &AmmSwapClient<impl SwapSigner>,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    amount_in: u64,
+) -> anyhow::Result<PoolComparison> {
+    validation::validate_amount(amount_in)?;
+    let standard = match top_pool(client, mint_a, mint_b, PoolType::Standard).await? {
+        Some(pool_info) => {
+            let quote = client
+                .quote(&PoolRef::Amm(&pool_info), mint_a, amount_in, 0.0)
+                .await;
+            quoted_report(&pool_info, quote, |_| {
+                (AMM_V4_SWAP_ACCOUNTS, AMM_V4_SWAP_COMPUTE_UNITS)
+            })
+        }
+        None => None,
+    };
+    let concentrated = match top_pool(client, mint_a, mint_b, PoolType::Concentrated).await? {
+        Some(pool_info) => {
+            let quote = clmm_quote(client, &pool_info, mint_a, amount_in).await;
+            let has_bitmap_extension = quote
+                .as_ref()
+                .is_ok_and(|(_, has_bitmap_extension)| *has_bitmap_extension);
+            quoted_report(&pool_info, quote.map(|(quote, _)| quote), |quote| {
+                let tick_arrays_crossed = quote.tick_arrays.len().saturating_sub(1) as u32;
+                (
+                    CLMM_SWAP_V2_FIXED_ACCOUNTS
+                        + usize::from(has_bitmap_extension)
+                        + quote.tick_arrays.len(),
+                    CLMM_SWAP_COMPUTE_UNITS + CLMM_TICK_ARRAY_COMPUTE_UNITS * tick_arrays_crossed,
+                )
+            })
+        }
+        None => None,
+    };
+    Ok(PoolComparison {
+        input_mint: *mint_a,
+        output_mint: *mint_b,
+        amount_in,
+        standard,
+        concentrated,
+    })
+}
+
+/// Deepest pool of `pool_type` trading the pair.
+async fn top_pool(
+    client: &AmmSwapClient<impl SwapSigner>,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    pool_type: PoolType,
+) -> anyhow::Result<Option<ClmmPool>> {
+    let sort_field = PoolSortField::Liquidity.to_string();
+    let pools = client
+        .fetch_pool_info(
+            &mint_a.to_string(),
+            &mint_b.to_string(),
+            &pool_type,
+            Some(COMPARISON_PAGE_SIZE),
+            Some(1),
+            Some(sort_field.as_str()),
+            Some("desc"),
+        )
+        .await?;
+    Ok(pools.into_iter().next())
+}
+
+/// Quote of a CLMM pool, and whether the pool has a bitmap extension
+/// account, which the swap instruction then passes.
+async fn clmm_quote(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_info: &ClmmPool,
+    input_mint: &Pubkey,
+    amount_in: u64,
+) -> anyhow::Result<(SwapQuote, bool)> {
+    let pool_id: Pubkey = pool_info.id.parse()?;
+    let ladder = client
+        .load_clmm_quote_ladder_for_input(&pool_id, input_mint)
+        .await?;
+    let quote = clmm_swap_quote(
+        &pool_id,
+        &ladder,
+        input_mint,
+        amount_in,
+        0.0,
+        &client.cluster_config().clmm_program_id(),
+    )?;
+    Ok((quote, ladder.tickarray_bitmap_extension.is_some()))
+}
+
+/// Report of `quote`, with the account count and compute units given by
+/// `cost`. Pools failing to quote are skipped with a warning.
+fn quoted_report(
+    pool_info: &ClmmPool,
+    quote: anyhow::Result<SwapQuote>,
+    cost: impl FnOnce(&SwapQuote) -> (usize, u32),
+) -> Option<ExecutionReport> {
+    let quote = match quote {
+        Ok(quote) => quote,
+        Err(e) => {
+            warn!(
+                "Pool {} could not be quoted, skipping it: {e:#}",
+                pool_info.id
+            );
+            return None;
+        }
+    };
+    let (account_count, expected_compute_units) = cost(&quote);
+    Some(ExecutionReport {
+        pool_id: quote.pool_id,
+        pool_type: quote.pool_type,
+        amount_out: quote.amount_out,
+        fee: quote.fee,
+        fee_tier: quote.fee_tier,
+        price_impact: quote.price_impact,
+        tick_arrays_crossed: quote.tick_arrays.len().saturating_sub(1),
+        account_count,
+        expected_compute_units,
+    })
+}
@@ -0,0 +1,190 @@
+//! Pools of a pair found directly on chain.
+//!
+//! The Raydium API lists new pools only some time after they are created.
+//! [`discover_pools_onchain`] finds them with `getProgramAccounts` on the
+//! AMM v4, CLMM and CPMM programs, filtered by the mints of the pair, so a
+//! pool is visible as soon as its account is confirmed.
+
+use crate::amm::client::LiquidityStateLayoutV4;
+use crate::config::ClusterConfig;
+use crate::cpmm::pool::{POOL_STATE_LEN, POOL_TOKEN_0_MINT_OFFSET, POOL_TOKEN_1_MINT_OFFSET};
+use crate::interface::PoolType;
+use crate::router::HopKind;
+use crate::states::PoolState;
+use anyhow::Result;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tracing::debug;
+
+/// A pool of the pair found on chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveredPool {
+    pub pool_id: Pubkey,
+    pub kind: HopKind,
+    /// Mints as stored by the pool: base and quote for AMM v4, token 0 and
+    /// token 1 for CLMM and CPMM.
+    pub mint_0: Pubkey,
+    pub mint_1: Pubkey,
+}
+
+/// Pools of `mint_a` and `mint_b`, in either order, read from the chain.
+///
+/// [`PoolType::Standard`] searches the AMM v4 and CPMM programs of
+/// `cluster`, [`PoolType::Concentrated`] its CLMM program. Unlike the API,
+/// this only finds pools and says nothing of their liquidity; quote them to
+/// compare.
+pub async fn discover_pools_onchain(
+    rpc_client: &RpcClient,
+    cluster: &ClusterConfig,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    pool_type: &PoolType,
+) -> Result<Vec<DiscoveredPool>> {
+    let kinds: &[HopKind] = match pool_type {
+        PoolType::Standard => &[HopKind::AmmV4, HopKind::Cpmm],
+        PoolType::Concentrated => &[HopKind::Clmm],
+    };
+
+    let mut pools = Vec::new();
+    for kind in kinds {
+        let program_id = cluster.program_id(*kind);
+        for filters in discovery_filters(*kind, mint_a, mint_b) {
+            let config = RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            };
+            let accounts = rpc_client
+                .get_program_accounts_with_config(&program_id, config)
+                .await?;
+            debug!(
+                "Found {} {:?} pools of {mint_a}/{mint_b}",
+                accounts.len(),
+                kind
+            );
+            pools.extend(accounts.into_iter().map(|(pool_id, account)| {
+                let (mint_0_offset, mint_1_offset) = mint_offsets(*kind);
+                DiscoveredPool {
+                    pool_id,
+                    kind: *kind,
+                    mint_0: read_pubkey(&account.data, mint_0_offset),
+                    mint_1: read_pubkey(&account.data, mint_1_offset),
+                }
+            }));
+        }
+    }
+    Ok(pools)
+}
+
+fn mint_offsets(kind: HopKind) -> (usize, usize) {
+    match kind {
+        HopKind::AmmV4 => (
+            LiquidityStateLayoutV4::BASE_MINT_OFFSET,
+            LiquidityStateLayoutV4::QUOTE_MINT_OFFSET,
+        ),
+        HopKind::Clmm => (
+            PoolState::TOKEN_MINT_0_OFFSET,
+            PoolState::TOKEN_MINT_1_OFFSET,
+        ),
+        HopKind::Cpmm => (POOL_TOKEN_0_MINT_OFFSET, POOL_TOKEN_1_MINT_OFFSET),
+    }
+}
+
+fn pool_len(kind: HopKind) -> usize {
+    match kind {
+        HopKind::AmmV4 => LiquidityStateLayoutV4::LEN,
+        HopKind::Clmm => PoolState::LEN,
+        HopKind::Cpmm => POOL_STATE_LEN,
+    }
+}
+
+/// Filter sets of the `getProgramAccounts` calls finding the pools of a
+/// pair, one call per set.
+///
+/// CLMM and CPMM pools store their mints sorted, so a single call does. AMM
+/// v4 pools may hold the pair as base/quote either way, so both orders are
+/// searched.
+fn discovery_filters(kind: HopKind, mint_a: &Pubkey, mint_b: &Pubkey) -> Vec<Vec<RpcFilterType>> {
+    let (mint_0_offset, mint_1_offset) = mint_offsets(kind);
+    let filters = |mint_0: &Pubkey, mint_1: &Pubkey| {
+        vec![
+            RpcFilterType::DataSize(pool_len(kind) as u64),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                mint_0_offset,
+                mint_0.to_bytes().to_vec(),
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                mint_1_offset,
+                mint_1.to_bytes().to_vec(),
+            )),
+        ]
+    };
+    let (mint_0, mint_1) = if mint_a <= mint_b {
+        (mint_a, mint_b)
+    } else {
+        (mint_b, mint_a)
+    };
+    match kind {
+        HopKind::AmmV4 => vec![filters(mint_a, mint_b), filters(mint_b, mint_a)],
+        HopKind::Clmm | HopKind::Cpmm => vec![filters(mint_0, mint_1)],
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    data.get(offset..offset + 32)
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(Pubkey::new_from_array)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovery_filters_match_pool_layouts() {
+        let low = Pubkey::new_from_array([1; 32]);
+        let high = Pubkey::new_from_array([2; 32]);
+        let memcmp = |offset: usize, mint: &Pubkey| {
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(offset, mint.to_bytes().to_vec()))
+        };
+
+        assert_eq!(
+            discovery_filters(HopKind::Clmm, &high, &low),
+            vec![vec![
+                RpcFilterType::DataSize(PoolState::LEN as u64),
+                memcmp(73, &low),
+                memcmp(105, &high),
+            ]]
+        );
+        assert_eq!(
+            discovery_filters(HopKind::Cpmm, &high, &low),
+            vec![vec![
+                RpcFilterType::DataSize(637),
+                memcmp(168, &low),
+                memcmp(200, &high),
+            ]]
+        );
+        assert_eq!(
+            discovery_filters(HopKind::AmmV4, &high, &low),
+            vec![
+                vec![
+                    RpcFilterType::DataSize(752),
+                    memcmp(400, &high),
+                    memcmp(432, &low),
+                ],
+                vec![
+                    RpcFilterType::DataSize(752),
+                    memcmp(400, &low),
+                    memcmp(432, &high),
+                ],
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::common::FeeTier;
+use crate::interface::{ClmmPool, PoolType};
+
+/// Compact view of a pool as listed by the Raydium API.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolSummary {
+    pub id: String,
+    pub pool_type: Option<PoolType>,
+    pub mint_a: String,
+    pub mint_b: String,
+    /// Price of token A in token B.
+    pub price: Option<f64>,
+    pub tvl: Option<f64>,
+    /// `None` when the API reported no fee for the pool.
+    pub fee_tier: Option<FeeTier>,
+}
+
+impl PoolSummary {
+    pub fn from_pool_info(pool_info: &ClmmPool) -> Self {
+        let pool_type = match pool_info.r#type.as_deref() {
+            Some("Standard") => Some(PoolType::Standard),
+            Some("Concentrated") => Some(PoolType::Concentrated),
+            _ => None,
+        };
+        Self {
+            id: pool_info.id.clone(),
+            pool_type,
+            mint_a: pool_info.mint_a.address.clone(),
+            mint_b: pool_info.mint_b.address.clone(),
+            price: pool_info.price,
+            tvl: pool_info.tvl,
+            fee_tier: FeeTier::from_pool_info(pool_info),
+        }
+    }
+}
+
+/// Estimate the output of swapping `amount_in` (UI units) of `input_mint`
+/// through the pool, after its trade fee.
+///
+/// The listed token amounts are treated as constant product reserves. For
+/// CLMM pools this understates depth near the current price, but it keeps
+/// pools of the same pair comparable when their fee tiers differ.
+pub fn estimate_amount_out(pool_info: &ClmmPool, input_mint: &str, amount_in: f64) -> Option<f64> {
+    let a_to_b = if pool_info.mint_a.address == input_mint {
+        true
+    } else if pool_info.mint_b.address == input_mint {
+        false
+    } else {
+        return None;
+    };
+    let fee_rate = FeeTier::from_pool_info(pool_info)?.trade_fee_rate;
+    let amount_in = amount_in * (1.0 - fee_rate);
+
+    let (reserve_in, reserve_out) = if a_to_b {
+        (pool_info.mint_amount_a, pool_info.mint_amount_b)
+    } else {
+        (pool_info.mint_amount_b, pool_info.mint_amount_a)
+    };
+    match (reserve_in, reserve_out) {
+        (Some(reserve_in), Some(reserve_out)) if reserve_in > 0.0 && reserve_out > 0.0 => {
+            Some(reserve_out * amount_in / (reserve_in + amount_in))
+        }
+        _ => {
+            let price = pool_info.price.filter(|price| *price > 0.0)?;
+            Some(if a_to_b {
+                amount_in * price
+            } else {
+                amount_in / price
+            })
+        }
+    }
+}
+
+/// Pick the pool giving the largest estimated output for `amount_in` (UI
+/// units) of `input_mint`, accounting for each pool's fee tier.
+pub fn select_best_pool<'a>(
+    pools: &'a [ClmmPool],
+    input_mint: &str,
+    amount_in: f64,
+) -> Option<&'a ClmmPool> {
+    pools
+        .iter()
+        .filter_map(|pool| Some((pool, estimate_amount_out(pool, input_mint, amount_in)?)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(pool, _)| pool)
+}
@@ -0,0 +1,286 @@
+//! Quote scheduling for many concurrent strategies sharing one client.
+//!
+//! Every quote needs a fresh read of its pool: the reserves of an AMM v4 pool,
+//! the state and tick arrays of a CLMM pool. When many tasks quote the same
+//! pools at once, [`QuoteScheduler`] lets them share these reads: a quote
+//! arriving while a read of its pool is queued or in flight waits for that
+//! read instead of issuing another. Reads run at most `max_concurrent_fetches`
+//! at a time, highest [`QuotePriority`] first.
+
+use crate::amm::client::{AmmSwapClient, PoolRef, RpcPoolInfo, SwapQuote, clmm_swap_quote};
+use crate::clmm::ClmmQuoteLadder;
+use crate::signer::SwapSigner;
+use crate::validation;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Semaphore, oneshot};
+
+/// Order in which queued pool reads are served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum QuotePriority {
+    Low,
+    #[default]
+    Normal,
+    /// E.g. quotes about to be executed.
+    High,
+}
+
+/// Pool read shared by the quotes of a pool, in the direction of the input
+/// mint for CLMM pools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum FetchKey {
+    Amm(Pubkey),
+    Clmm { pool_id: Pubkey, input_mint: Pubkey },
+}
+
+impl FetchKey {
+    fn pool_id(&self) -> &Pubkey {
+        match self {
+            FetchKey::Amm(pool_id) | FetchKey::Clmm { pool_id, .. } => pool_id,
+        }
+    }
+}
+
+enum PoolQuoteState {
+    Amm(RpcPoolInfo),
+    Clmm(ClmmQuoteLadder),
+}
+
+type FetchResult = Result<Arc<PoolQuoteState>, Arc<anyhow::Error>>;
+
+struct PendingFetch {
+    priority: QuotePriority,
+    in_flight: bool,
+    waiters: Vec<oneshot::Sender<FetchResult>>,
+}
+
+/// Queue entry, served by priority then arrival.
+struct QueuedFetch {
+    priority: QuotePriority,
+    seq: u64,
+    key: FetchKey,
+}
+
+impl QueuedFetch {
+    fn rank(&self) -> (QuotePriority, Reverse<u64>) {
+        (self.priority, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for QueuedFetch {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank() == other.rank()
+    }
+}
+
+impl Eq for QueuedFetch {}
+
+impl PartialOrd for QueuedFetch {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedFetch {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    pending: HashMap<FetchKey, PendingFetch>,
+    queue: BinaryHeap<QueuedFetch>,
+    next_seq: u64,
+}
+
+struct Inner<S> {
+    client: Arc<AmmSwapClient<S>>,
+    permits: Semaphore,
+    state: Mutex<SchedulerState>,
+}
+
+/// Quotes of many tasks multiplexed over one client, see the
+/// [module documentation](self). Cheap to clone, clones share the queue.
+pub struct QuoteScheduler<S = Keypair> {
+    inner: Arc<Inner<S>>,
+}
+
+impl<S> Clone for QuoteScheduler<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S: SwapSigner + 'static> QuoteScheduler<S> {
+    /// Scheduler running at most `max_concurrent_fetches` pool reads at a
+    /// time, at least one.
+    pub fn new(client: Arc<AmmSwapClient<S>>, max_concurrent_fetches: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                client,
+                permits: Semaphore::new(max_concurrent_fetches.max(1)),
+                state: Mutex::new(SchedulerState::default()),
+            }),
+        }
+    }
+
+    pub fn client(&self) -> &Arc<AmmSwapClient<S>> {
+        &self.inner.client
+    }
+
+    /// Pool reads queued or in flight.
+    pub fn pending_fetches(&self) -> usize {
+        self.inner.lock().pending.len()
+    }
+
+    /// Same as [`AmmSwapClient::quote`], reading the pool through the
+    /// scheduler. Must be called within a Tokio runtime, which runs the
+    /// reads.
+    pub async fn quote(
+        &self,
+        pool: &PoolRef<'_>,
+        input_mint: &Pubkey,
+        amount_in: u64,
+        slippage: f64,
+        priority: QuotePriority,
+    ) -> anyhow::Result<SwapQuote> {
+        validation::validate_amount(amount_in)?;
+        validation::validate_slippage(slippage)?;
+        let key = match *pool {
+            PoolRef::Amm(pool_info) => {
+                validation::validate_pool_mint(pool_info, &input_mint.to_string())?;
+                FetchKey::Amm(pool_info.id.parse()?)
+            }
+            PoolRef::Clmm(pool_id) => FetchKey::Clmm {
+                pool_id,
+                input_mint: *input_mint,
+            },
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        if self.inner.enqueue(key, priority, sender) {
+            tokio::spawn(Arc::clone(&self.inner).dispatch());
+        }
+        let state = receiver
+            .await
+            .map_err(|_| anyhow!("Read of pool {} was dropped", key.pool_id()))?
+            .map_err(|e| anyhow!("Read of pool {} failed: {e:#}", key.pool_id()))?;
+
+        match (&*state, *pool) {
+            (PoolQuoteState::Amm(rpc_pool_info), PoolRef::Amm(pool_info)) => {
+                self.inner.client.amm_swap_quote(
+                    pool_info,
+                    rpc_pool_info.clone(),
+                    input_mint,
+                    amount_in,
+                    slippage,
+                )
+            }
+            (PoolQuoteState::Clmm(ladder), PoolRef::Clmm(pool_id)) => clmm_swap_quote(
+                &pool_id,
+                ladder,
+                input_mint,
+                amount_in,
+                slippage,
+                &self.inner.client.cluster_config().clmm_program_id(),
+            ),
+            _ => Err(anyhow!(
+                "Read of pool {} does not match its type",
+                key.pool_id()
+            )),
+        }
+    }
+}
+
+impl<S: SwapSigner + 'static> Inner<S> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register `waiter` for a read of `key`. Returns whether a queue entry
+    /// was added, which must be matched by one [`Self::dispatch`].
+    fn enqueue(
+        &self,
+        key: FetchKey,
+        priority: QuotePriority,
+        waiter: oneshot::Sender<FetchResult>,
+    ) -> bool {
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let queued = QueuedFetch { priority, seq, key };
+        match state.pending.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let pending = entry.get_mut();
+                pending.waiters.push(waiter);
+                if pending.in_flight || priority <= pending.priority {
+                    return false;
+                }
+                // Queue the read again to serve it at the higher priority;
+                // the older entry is skipped when popped.
+                pending.priority = priority;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(PendingFetch {
+                    priority,
+                    in_flight: false,
+                    waiters: vec![waiter],
+                });
+            }
+        }
+        state.queue.push(queued);
+        true
+    }
+
+    /// Wait for a permit, then run the highest priority queued read and
+    /// hand its result to every quote waiting for it.
+    async fn dispatch(self: Arc<Self>) {
+        let Ok(_permit) = self.permits.acquire().await else {
+            return;
+        };
+        let key = {
+            let mut guard = self.lock();
+            let state = &mut *guard;
+            let Some(queued) = state.queue.pop() else {
+                return;
+            };
+            match state.pending.get_mut(&queued.key) {
+                Some(pending) if !pending.in_flight => pending.in_flight = true,
+                _ => return,
+            }
+            queued.key
+        };
+
+        let result = self.fetch(&key).await.map(Arc::new).map_err(Arc::new);
+        let pending = self.lock().pending.remove(&key);
+        for waiter in pending.into_iter().flat_map(|pending| pending.waiters) {
+            // The quote was dropped.
+            let _ = waiter.send(result.clone());
+        }
+    }
+
+    async fn fetch(&self, key: &FetchKey) -> anyhow::Result<PoolQuoteState> {
+        Ok(match key {
+            FetchKey::Amm(pool_id) => {
+                PoolQuoteState::Amm(self.client.get_rpc_pool_info(pool_id).await?)
+            }
+            FetchKey::Clmm {
+                pool_id,
+                input_mint,
+            } => PoolQuoteState::Clmm(
+                self.client
+                    .load_clmm_quote_ladder_for_input(pool_id, input_mint)
+                    .await?,
+            ),
+        })
+    }
+}
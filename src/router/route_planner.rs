@@ -0,0 +1,193 @@
+//! Route planning over a set of candidate pools.
+//!
+//! Routes may mix AMM v4, CLMM and CPMM hops. Quotes are estimated from the
+//! pool data returned by the Raydium API (see [`estimate_amount_out`]), so
+//! they are meant for ranking candidates; the chosen route should be quoted
+//! on-chain before execution.
+
+use crate::common::FeeTier;
+use crate::config::ClusterConfig;
+use crate::consts::{AMM_V4, CLMM, CPMM, DEVNET_AMM_V4, DEVNET_CLMM, DEVNET_CPMM};
+use crate::interface::ClmmPool;
+use crate::router::estimate_amount_out;
+use solana_sdk::pubkey::Pubkey;
+
+/// Program a hop swaps through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HopKind {
+    AmmV4,
+    Clmm,
+    Cpmm,
+}
+
+impl HopKind {
+    /// Kind of the pools of a mainnet or devnet Raydium program. Use
+    /// [`ClusterConfig::hop_kind`](crate::config::ClusterConfig::hop_kind)
+    /// for programs deployed elsewhere.
+    pub fn from_program_id(program_id: &str) -> Option<Self> {
+        match program_id {
+            AMM_V4 | DEVNET_AMM_V4 => Some(HopKind::AmmV4),
+            CLMM | DEVNET_CLMM => Some(HopKind::Clmm),
+            CPMM | DEVNET_CPMM => Some(HopKind::Cpmm),
+            _ => None,
+        }
+    }
+}
+
+/// Limits applied while enumerating routes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePlannerConfig {
+    /// Maximum number of hops in a route.
+    pub max_hops: usize,
+    /// A hop is pruned when the reserve of its input token is below
+    /// `min_liquidity_ratio` times the amount entering the hop.
+    pub min_liquidity_ratio: f64,
+    /// Number of routes returned.
+    pub top_k: usize,
+    /// Hop kinds allowed in a route.
+    pub allowed_kinds: Vec<HopKind>,
+}
+
+impl Default for RoutePlannerConfig {
+    fn default() -> Self {
+        Self {
+            max_hops: 2,
+            min_liquidity_ratio: 10.0,
+            top_k: 3,
+            allowed_kinds: vec![HopKind::AmmV4, HopKind::Clmm, HopKind::Cpmm],
+        }
+    }
+}
+
+/// One swap of a route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteHop {
+    pub pool_id: String,
+    pub kind: HopKind,
+    pub input_mint: String,
+    pub output_mint: String,
+    /// Estimated input of the hop, in UI units.
+    pub amount_in: f64,
+    /// Estimated output of the hop, in UI units.
+    pub amount_out: f64,
+    pub fee_tier: Option<FeeTier>,
+}
+
+/// A candidate route with its estimated quote.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteQuote {
+    pub hops: Vec<RouteHop>,
+    /// Input of the route, in UI units.
+    pub amount_in: f64,
+    /// Estimated output of the route, in UI units.
+    pub amount_out: f64,
+}
+
+/// Enumerate routes from `input_mint` to `output_mint` through `pools` and
+/// return the best `config.top_k` by estimated output.
+///
+/// `amount_in` is in UI units of `input_mint`. A pool is used at most once
+/// per route and routes never revisit a mint. Pools of programs other than
+/// the Raydium programs of `cluster_config` are skipped.
+pub fn plan_routes(
+    cluster_config: &ClusterConfig,
+    pools: &[ClmmPool],
+    input_mint: &str,
+    output_mint: &str,
+    amount_in: f64,
+    config: &RoutePlannerConfig,
+) -> Vec<RouteQuote> {
+    let pools: Vec<(HopKind, &ClmmPool)> = pools
+        .iter()
+        .filter_map(|pool| {
+            let program_id = pool.program_id.parse::<Pubkey>().ok()?;
+            Some((cluster_config.hop_kind(&program_id)?, pool))
+        })
+        .collect();
+    let mut routes = Vec::new();
+    let mut path = Vec::new();
+    let mut visited_mints = vec![input_mint.to_string()];
+    extend_routes(
+        &pools,
+        input_mint,
+        output_mint,
+        amount_in,
+        config,
+        &mut path,
+        &mut visited_mints,
+        &mut routes,
+    );
+    routes.sort_by(|a: &RouteQuote, b: &RouteQuote| b.amount_out.total_cmp(&a.amount_out));
+    routes.truncate(config.top_k);
+    routes
+}
+
+/// Depth-first search adding every hop leaving `current_mint`.
+#[allow(clippy::too_many_arguments)]
+fn extend_routes(
+    pools: &[(HopKind, &ClmmPool)],
+    current_mint: &str,
+    output_mint: &str,
+    amount: f64,
+    config: &RoutePlannerConfig,
+    path: &mut Vec<RouteHop>,
+    visited_mints: &mut Vec<String>,
+    routes: &mut Vec<RouteQuote>,
+) {
+    if path.len() >= config.max_hops {
+        return;
+    }
+    for &(kind, pool) in pools {
+        if !config.allowed_kinds.contains(&kind) || path.iter().any(|hop| hop.pool_id == pool.id)
+        {
+            continue;
+        }
+        let (next_mint, reserve_in) = if pool.mint_a.address == current_mint {
+            (&pool.mint_b.address, pool.mint_amount_a)
+        } else if pool.mint_b.address == current_mint {
+            (&pool.mint_a.address, pool.mint_amount_b)
+        } else {
+            continue;
+        };
+        if visited_mints.contains(next_mint) {
+            continue;
+        }
+        if reserve_in.unwrap_or(0.0) < amount * config.min_liquidity_ratio {
+            continue;
+        }
+        let Some(amount_out) = estimate_amount_out(pool, current_mint, amount) else {
+            continue;
+        };
+
+        path.push(RouteHop {
+            pool_id: pool.id.clone(),
+            kind,
+            input_mint: current_mint.to_string(),
+            output_mint: next_mint.clone(),
+            amount_in: amount,
+            amount_out,
+            fee_tier: FeeTier::from_pool_info(pool),
+        });
+        if next_mint == output_mint {
+            routes.push(RouteQuote {
+                amount_in: path[0].amount_in,
+                amount_out,
+                hops: path.clone(),
+            });
+        } else {
+            visited_mints.push(next_mint.clone());
+            extend_routes(
+                pools,
+                next_mint,
+                output_mint,
+                amount_out,
+                config,
+                path,
+                visited_mints,
+                routes,
+            );
+            visited_mints.pop();
+        }
+        path.pop();
+    }
+}
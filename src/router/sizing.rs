@@ -0,0 +1,188 @@
+use crate::amm::client::{AmmSwapClient, ComputeAmountOutResult, orient_amm_pool};
+use crate::clmm::SwapComputeResult;
+use crate::interface::ClmmPool;
+use crate::signer::SwapSigner;
+use crate::validation;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Binary search steps after the upper bound is found. Enough to narrow any
+/// u64 range down to a single unit.
+const MAX_SEARCH_STEPS: u32 = 64;
+
+/// Quote of the largest order found by [`max_size_for_impact`].
+#[derive(Debug, Clone)]
+pub enum SizedQuote {
+    Amm(ComputeAmountOutResult),
+    Clmm(SwapComputeResult),
+}
+
+/// Largest input amount keeping the price impact under a cap.
+#[derive(Debug, Clone)]
+pub struct ImpactSizing {
+    /// Input amount in the smallest units of the input mint.
+    pub amount_in: u64,
+    /// Price impact of `amount_in`, in bps, fees excluded.
+    pub impact_bps: f64,
+    pub quote: SizedQuote,
+}
+
+/// Find the largest amount of `input_mint` that can be swapped through
+/// `pool_info` with a price impact of at most `max_impact_bps`.
+///
+/// Works on standard AMM and CLMM pools. The pool state is loaded once and
+/// every candidate size is quoted against it. Small sizes whose impact is
+/// dominated by rounding are skipped, so the search starts at the first size
+/// under the cap. For CLMM pools sizes that run past the loaded tick arrays
+/// count as over the cap.
+pub async fn max_size_for_impact(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_info: &ClmmPool,
+    input_mint: &str,
+    max_impact_bps: u64,
+) -> anyhow::Result<ImpactSizing> {
+    validation::validate_pool_mint(pool_info, input_mint)?;
+    let pool_id = Pubkey::from_str(&pool_info.id)?;
+    let max_impact_bps = max_impact_bps as f64;
+
+    match pool_info.r#type.as_deref() {
+        Some("Concentrated") => {
+            let input_mint = Pubkey::from_str(input_mint)?;
+            let ladder = client
+                .load_clmm_quote_ladder_for_input(&pool_id, &input_mint)
+                .await?;
+            search_max_size(max_impact_bps, |amount_in| {
+                let result = ladder.quote(amount_in).ok()?;
+                let impact_bps = ladder.price_impact_bps(amount_in, &result);
+                Some((impact_bps, SizedQuote::Clmm(result)))
+            })
+        }
+        _ => {
+            let rpc_pool_info = client.get_rpc_pool_info(&pool_id).await?;
+            let (rpc_pool_info, pool_info) = orient_amm_pool(rpc_pool_info, pool_info, input_mint)?;
+            search_max_size(max_impact_bps, |amount_in| {
+                let quote = client
+                    .compute_amount_out(&rpc_pool_info, &pool_info, amount_in, 0.0)
+                    .ok()?;
+                Some((quote.price_impact * 100.0, SizedQuote::Amm(quote)))
+            })
+        }
+    }
+}
+
+/// Grow the size by doubling until the impact exceeds the cap, then binary
+/// search between the last size under the cap and the first one over it.
+/// `quote` returns `None` for sizes that cannot be quoted.
+fn search_max_size(
+    max_impact_bps: f64,
+    mut quote: impl FnMut(u64) -> Option<(f64, SizedQuote)>,
+) -> anyhow::Result<ImpactSizing> {
+    let mut under_cap = |amount_in: u64| -> Option<ImpactSizing> {
+        let (impact_bps, quote) = quote(amount_in)?;
+        (impact_bps <= max_impact_bps).then_some(ImpactSizing {
+            amount_in,
+            impact_bps,
+            quote,
+        })
+    };
+
+    let mut best: Option<ImpactSizing> = None;
+    let mut over_cap = None;
+    let mut amount_in: u64 = 1;
+    loop {
+        match under_cap(amount_in) {
+            Some(sizing) => best = Some(sizing),
+            // Only sizes past the first one under the cap bound the search.
+            None if best.is_some() => {
+                over_cap = Some(amount_in);
+                break;
+            }
+            None => {}
+        }
+        match amount_in.checked_mul(2) {
+            Some(next) => amount_in = next,
+            None => break,
+        }
+    }
+    let mut best = best.ok_or(anyhow!(
+        "No input amount keeps the price impact under {} bps",
+        max_impact_bps
+    ))?;
+    let Some(mut high) = over_cap else {
+        return Ok(best);
+    };
+
+    let mut steps = 0;
+    while high - best.amount_in > 1 && steps < MAX_SEARCH_STEPS {
+        let mid = best.amount_in + (high - best.amount_in) / 2;
+        match under_cap(mid) {
+            Some(sizing) => best = sizing,
+            None => high = mid,
+        }
+        steps += 1;
+    }
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::FeeTier;
+
+    fn quote(impact_bps: f64) -> Option<(f64, SizedQuote)> {
+        Some((
+            impact_bps,
+            SizedQuote::Amm(ComputeAmountOutResult {
+                amount_out: 0,
+                min_amount_out: 0,
+                current_price: 1.0,
+                execution_price: 1.0,
+                price_impact: impact_bps / 100.0,
+                fee: 0,
+                fee_tier: FeeTier::default(),
+            }),
+        ))
+    }
+
+    #[test]
+    fn monotone_impact_finds_the_largest_size_under_the_cap() {
+        let sizing = search_max_size(50.0, |amount_in| quote(amount_in as f64 / 100.0)).unwrap();
+        assert_eq!(sizing.amount_in, 5_000);
+        assert_eq!(sizing.impact_bps, 50.0);
+    }
+
+    #[test]
+    fn small_sizes_over_the_cap_are_skipped() {
+        let sizing = search_max_size(50.0, |amount_in| {
+            quote(if amount_in < 16 {
+                10_000.0
+            } else {
+                amount_in as f64 / 100.0
+            })
+        })
+        .unwrap();
+        assert_eq!(sizing.amount_in, 5_000);
+    }
+
+    #[test]
+    fn sizes_that_cannot_be_quoted_count_as_over_the_cap() {
+        let sizing = search_max_size(50.0, |amount_in| match amount_in {
+            0..=3_000 => quote(0.0),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(sizing.amount_in, 3_000);
+    }
+
+    #[test]
+    fn uncapped_search_stops_at_the_largest_power_of_two() {
+        let sizing = search_max_size(50.0, |_| quote(0.0)).unwrap();
+        assert_eq!(sizing.amount_in, 1 << 63);
+    }
+
+    #[test]
+    fn no_size_under_the_cap_is_an_error() {
+        assert!(search_max_size(50.0, |_| quote(100.0)).is_err());
+    }
+}
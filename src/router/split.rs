@@ -0,0 +1,181 @@
+//! Large orders split into swaps that each stay under a price impact cap.
+
+use crate::amm::client::AmmSwapClient;
+use crate::execution::swap_exact_in;
+use crate::interface::ClmmPool;
+use crate::monitor::MonitoredPool;
+use crate::router::max_size_for_impact;
+use crate::signer::SwapSigner;
+use crate::validation;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use tracing::{debug, info, warn};
+
+/// One swap of a [`SplitSwap`].
+#[derive(Debug, Clone)]
+pub struct SwapChunk {
+    pub pool_id: Pubkey,
+    pub amount_in: u64,
+    /// Price impact of the chunk when it was sized, in bps, fees excluded.
+    pub impact_bps: f64,
+    pub signature: Signature,
+    /// Output received, from the token balances of the transaction. `None`
+    /// when the swap was sent but its fill could not be read.
+    pub amount_out: Option<u64>,
+}
+
+/// Outcome of [`split_swap`].
+#[derive(Debug, Clone)]
+pub struct SplitSwap {
+    /// Swaps sent, in order.
+    pub chunks: Vec<SwapChunk>,
+    /// Total input swapped by the chunks.
+    pub amount_in: u64,
+    /// Total output received by the chunks whose fill was read.
+    pub amount_out: u64,
+    /// Part of the order left unswapped: no pool could take more under the
+    /// cap, the chunk limit was reached or a chunk failed.
+    pub amount_remaining: u64,
+    /// Error of the chunk that stopped the order, if any.
+    pub error: Option<String>,
+}
+
+impl SplitSwap {
+    /// Output per input over the chunks whose fill was read, in raw units.
+    pub fn average_price(&self) -> f64 {
+        let amount_in: u64 = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.amount_out.is_some())
+            .map(|chunk| chunk.amount_in)
+            .sum();
+        if amount_in == 0 {
+            return 0.0;
+        }
+        self.amount_out as f64 / amount_in as f64
+    }
+}
+
+fn monitored_pool(pool_info: &ClmmPool) -> anyhow::Result<MonitoredPool> {
+    let id = Pubkey::from_str(&pool_info.id)?;
+    Ok(match pool_info.r#type.as_deref() {
+        Some("Concentrated") => MonitoredPool::concentrated(id),
+        _ => MonitoredPool::standard(id),
+    })
+}
+
+/// Swap `amount_in` of `input_mint` in chunks whose price impact is at most
+/// `max_impact_bps`, across the AMM v4 and CLMM `pools` trading it.
+///
+/// Chunks are sent one after the other. Before each one, every pool is
+/// sized again with [`max_size_for_impact`] from its current state, and the
+/// chunk goes to the pool taking the largest amount, so the order moves to
+/// the other pools as the best one gets worse, and back as arbitrage
+/// restores its price. Each chunk is sent with a minimum output quoted with
+/// `slippage_bps` when it is built.
+///
+/// At most `max_chunks` swaps are sent. Fails when the first chunk cannot be
+/// sized or sent; a later failure stops the order and is reported in
+/// [`SplitSwap::error`] with what was swapped so far. A chunk whose fill
+/// cannot be read once sent still counts as swapped, with no
+/// [`SwapChunk::amount_out`].
+pub async fn split_swap(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pools: &[ClmmPool],
+    input_mint: &str,
+    amount_in: u64,
+    max_impact_bps: u64,
+    slippage_bps: u64,
+    max_chunks: usize,
+) -> anyhow::Result<SplitSwap> {
+    validation::validate_amount(amount_in)?;
+    validation::validate_slippage_bps(slippage_bps)?;
+    if pools.is_empty() {
+        return Err(anyhow!("No pool to split the swap of {input_mint} across"));
+    }
+    for pool_info in pools {
+        validation::validate_pool_mint(pool_info, input_mint)?;
+    }
+    let mint_in = Pubkey::from_str(input_mint)?;
+
+    let mut split = SplitSwap {
+        chunks: Vec::new(),
+        amount_in: 0,
+        amount_out: 0,
+        amount_remaining: amount_in,
+        error: None,
+    };
+    while split.amount_remaining > 0 && split.chunks.len() < max_chunks {
+        let mut best: Option<(&ClmmPool, u64, f64)> = None;
+        for pool_info in pools {
+            match max_size_for_impact(client, pool_info, input_mint, max_impact_bps).await {
+                Ok(sizing) if best.is_none_or(|(_, size, _)| sizing.amount_in > size) => {
+                    best = Some((pool_info, sizing.amount_in, sizing.impact_bps));
+                }
+                Ok(_) => {}
+                Err(e) => debug!("Pool {} takes no chunk: {e}", pool_info.id),
+            }
+        }
+        let Some((pool_info, size, impact_bps)) = best else {
+            if split.chunks.is_empty() {
+                return Err(anyhow!(
+                    "No pool can swap {input_mint} under {max_impact_bps} bps of price impact"
+                ));
+            }
+            break;
+        };
+
+        let chunk_in = size.min(split.amount_remaining);
+        let output_mint = if pool_info.mint_a.address == input_mint {
+            &pool_info.mint_b.address
+        } else {
+            &pool_info.mint_a.address
+        };
+        let pool = monitored_pool(pool_info)?;
+        let result = swap_exact_in(client, &pool, &mint_in, chunk_in, slippage_bps, None).await;
+        let signature = match result {
+            Ok(signature) => signature,
+            Err(e) if split.chunks.is_empty() => return Err(e),
+            Err(e) => {
+                warn!(
+                    "Chunk {} of the split swap failed: {e:?}",
+                    split.chunks.len() + 1
+                );
+                split.error = Some(format!("{e:#}"));
+                break;
+            }
+        };
+        split.amount_in += chunk_in;
+        split.amount_remaining -= chunk_in;
+        let fill = async {
+            client
+                .verify_swap_fill(&signature, &Pubkey::from_str(output_mint)?, 0)
+                .await
+        }
+        .await;
+        let amount_out = match fill {
+            Ok(fill) => {
+                info!(
+                    "Swapped chunk of {chunk_in} {input_mint} for {} on pool {}",
+                    fill.amount_out, pool.id
+                );
+                split.amount_out += fill.amount_out;
+                Some(fill.amount_out)
+            }
+            Err(e) => {
+                warn!("Swapped chunk {signature} but could not read its fill: {e:#}");
+                None
+            }
+        };
+        split.chunks.push(SwapChunk {
+            pool_id: pool.id,
+            amount_in: chunk_in,
+            impact_bps,
+            signature,
+            amount_out,
+        });
+    }
+    Ok(split)
+}
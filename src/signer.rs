@@ -0,0 +1,111 @@
+//! Signer of the transactions built by
+//! [`AmmSwapClient`](crate::amm::client::AmmSwapClient).
+//!
+//! The client only needs the signer's address to build transactions and a
+//! way to get a compiled message signed, so the key can live outside the
+//! process: a hardware wallet, a remote signing service, or a multisig
+//! proposing the transaction. [`Keypair`] signs in process, [`WatchOnly`]
+//! does not sign at all.
+
+use crate::error::SwapError;
+use anyhow::anyhow;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::VersionedTransaction;
+use std::future::Future;
+
+pub trait SwapSigner: Send + Sync {
+    /// Address paying for and authorizing the swaps, the owner of the token
+    /// accounts swapped from and to.
+    fn pubkey(&self) -> Pubkey;
+
+    /// Sign `message`, whose fee payer is [`Self::pubkey`], into a
+    /// transaction ready to be sent.
+    ///
+    /// When the message requires other signers, their signatures may be
+    /// left as default signatures; the client fills in those of the keypairs
+    /// it generated, see [`partial_sign_message`].
+    fn sign_message(
+        &self,
+        message: VersionedMessage,
+    ) -> impl Future<Output = anyhow::Result<VersionedTransaction>> + Send;
+}
+
+impl SwapSigner for Keypair {
+    fn pubkey(&self) -> Pubkey {
+        Signer::pubkey(self)
+    }
+
+    fn sign_message(
+        &self,
+        message: VersionedMessage,
+    ) -> impl Future<Output = anyhow::Result<VersionedTransaction>> + Send {
+        std::future::ready(partial_sign_message(message, &[self]))
+    }
+}
+
+/// Sign `message` with `signers`, leaving a default signature for every
+/// other signer the message requires.
+///
+/// Transactions creating an account whose keypair is generated by the
+/// client, e.g. the NFT mint of a new CLMM position, need that keypair's
+/// signature besides the owner's; the client adds it after the owner signed.
+pub fn partial_sign_message(
+    message: VersionedMessage,
+    signers: &[&Keypair],
+) -> anyhow::Result<VersionedTransaction> {
+    let required_signatures = message.header().num_required_signatures as usize;
+    let signer_keys = &message.static_account_keys()[..required_signatures];
+    let message_data = message.serialize();
+    let mut signatures = vec![Signature::default(); required_signatures];
+    for signer in signers {
+        let pubkey = Signer::pubkey(*signer);
+        let index = signer_keys
+            .iter()
+            .position(|key| *key == pubkey)
+            .ok_or(anyhow!("{pubkey} is not a signer of the message"))?;
+        signatures[index] = Signer::sign_message(*signer, &message_data);
+    }
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
+
+/// No signer, for clients that only quote, monitor or read accounts.
+///
+/// Signing fails with [`SwapError::NoSignerConfigured`], so every method
+/// sending a transaction does too. The address is only used to read the
+/// token accounts of a wallet, and is the default address unless set with
+/// [`WatchOnly::address`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WatchOnly {
+    address: Pubkey,
+}
+
+impl WatchOnly {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watch the token accounts of `address`, e.g. for
+    /// [`fetch_wallet_inventory`](crate::wallet::fetch_wallet_inventory).
+    pub fn address(address: Pubkey) -> Self {
+        Self { address }
+    }
+}
+
+impl SwapSigner for WatchOnly {
+    fn pubkey(&self) -> Pubkey {
+        self.address
+    }
+
+    fn sign_message(
+        &self,
+        _message: VersionedMessage,
+    ) -> impl Future<Output = anyhow::Result<VersionedTransaction>> + Send {
+        std::future::ready(Err(SwapError::NoSignerConfigured.into()))
+    }
+}
@@ -1,11 +1,15 @@
 pub mod config;
 pub mod operation_account;
+pub mod oracle;
+pub mod personal_position;
 pub mod pool;
 pub mod tick_array;
 pub mod tickarray_bitmap_extension;
 
 pub use config::*;
 pub use operation_account::*;
+pub use oracle::*;
+pub use personal_position::*;
 pub use pool::*;
 pub use tick_array::*;
 pub use tickarray_bitmap_extension::*;
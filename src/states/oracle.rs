@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+
+pub const OBSERVATION_SEED: &str = "observation";
+// Number of ObservationState element
+pub const OBSERVATION_NUM: usize = 100;
+pub const OBSERVATION_UPDATE_DURATION_DEFAULT: u32 = 15;
+
+/// The element of observations in ObservationState
+#[zero_copy(unsafe)]
+#[repr(C, packed)]
+#[derive(Default, Debug)]
+pub struct Observation {
+    /// The block timestamp of the observation
+    pub block_timestamp: u32,
+    /// the cumulative of tick during the duration time
+    pub tick_cumulative: i64,
+    /// padding for feature update
+    pub padding: [u64; 4],
+}
+
+impl Observation {
+    pub const LEN: usize = 4 + 8 + 8 * 4;
+}
+
+/// The oracle observation ring buffer of a pool
+///
+/// PDA of `[OBSERVATION_SEED, pool_id]`
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
+#[derive(Debug)]
+pub struct ObservationState {
+    /// Whether the ObservationState is initialized
+    pub initialized: bool,
+    /// recent update epoch
+    pub recent_epoch: u64,
+    /// the most-recently updated index of the observations array
+    pub observation_index: u16,
+    /// belongs to which pool
+    pub pool_id: Pubkey,
+    /// observation array
+    pub observations: [Observation; OBSERVATION_NUM],
+    /// padding for feature update
+    pub padding: [u64; 4],
+}
+
+impl Default for ObservationState {
+    #[inline]
+    fn default() -> ObservationState {
+        ObservationState {
+            initialized: false,
+            recent_epoch: 0,
+            observation_index: 0,
+            pool_id: Pubkey::default(),
+            observations: [Observation::default(); OBSERVATION_NUM],
+            padding: [0u64; 4],
+        }
+    }
+}
+
+impl ObservationState {
+    pub const LEN: usize = 8 + 1 + 8 + 2 + 32 + (Observation::LEN * OBSERVATION_NUM) + 8 * 4;
+
+    /// Initialized observations ordered from the oldest to the newest.
+    pub fn ordered_observations(&self) -> Vec<Observation> {
+        let observations = self.observations;
+        let newest = self.observation_index as usize % OBSERVATION_NUM;
+        (1..=OBSERVATION_NUM)
+            .map(|offset| observations[(newest + offset) % OBSERVATION_NUM])
+            .filter(|observation| observation.block_timestamp != 0)
+            .collect()
+    }
+}
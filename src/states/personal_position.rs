@@ -0,0 +1,66 @@
+use crate::states::REWARD_NUM;
+use anchor_lang::prelude::*;
+
+pub const POSITION_SEED: &str = "position";
+
+/// Reward state of a position for one reward mint
+#[derive(Copy, Clone, AnchorSerialize, AnchorDeserialize, Default, Debug, PartialEq)]
+pub struct PositionRewardInfo {
+    /// Q64.64 reward growth inside the position range at the last update
+    pub growth_inside_last_x64: u128,
+    pub reward_amount_owed: u64,
+}
+
+impl PositionRewardInfo {
+    pub const LEN: usize = 16 + 8;
+}
+
+/// A liquidity position, owned by whoever holds `nft_mint`
+///
+/// PDA of `[POSITION_SEED, nft_mint]`
+#[account]
+#[derive(Default, Debug)]
+pub struct PersonalPositionState {
+    /// Bump to identify PDA
+    pub bump: [u8; 1],
+    /// Mint address of the tokenized position
+    pub nft_mint: Pubkey,
+    /// The ID of the pool with which this token is connected
+    pub pool_id: Pubkey,
+    /// The lower bound tick of the position
+    pub tick_lower_index: i32,
+    /// The upper bound tick of the position
+    pub tick_upper_index: i32,
+    /// The amount of liquidity owned by this position
+    pub liquidity: u128,
+    /// The token_0 fee growth of the aggregate position as of the last action on the individual position
+    pub fee_growth_inside_0_last_x64: u128,
+    /// The token_1 fee growth of the aggregate position as of the last action on the individual position
+    pub fee_growth_inside_1_last_x64: u128,
+    /// The fees owed to the position owner in token_0, as of the last computation
+    pub token_fees_owed_0: u64,
+    /// The fees owed to the position owner in token_1, as of the last computation
+    pub token_fees_owed_1: u64,
+    pub reward_infos: [PositionRewardInfo; REWARD_NUM],
+    pub recent_epoch: u64,
+    pub padding: [u64; 7],
+}
+
+impl PersonalPositionState {
+    pub const LEN: usize = 8
+        + 1
+        + 32
+        + 32
+        + 4
+        + 4
+        + 16
+        + 16
+        + 16
+        + 8
+        + 8
+        + PositionRewardInfo::LEN * REWARD_NUM
+        + 8
+        + 8 * 7;
+    /// Offset of `pool_id` in the account data, discriminator included
+    pub const POOL_ID_OFFSET: usize = 8 + 1 + 32;
+}
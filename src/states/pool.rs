@@ -147,6 +147,10 @@ impl PoolState {
         + RewardInfo::LEN * REWARD_NUM
         + 8 * 16
         + 512;
+    /// Offset of `token_mint_0` in the account data, discriminator included
+    pub const TOKEN_MINT_0_OFFSET: usize = 8 + 1 + 32 * 2;
+    /// Offset of `token_mint_1` in the account data, discriminator included
+    pub const TOKEN_MINT_1_OFFSET: usize = 8 + 1 + 32 * 3;
 
     pub fn seeds(&self) -> [&[u8]; 5] {
         [
@@ -233,7 +237,7 @@ impl PoolState {
         let (is_initialized, start_index) =
             if self.is_overflow_default_tickarray_bitmap(vec![self.tick_current]) {
                 tickarray_bitmap_extension
-                    .ok_or(anyhow!("tickarray_bitmap_extension cannot be None"))?
+                    .ok_or(anyhow!(ErrorCode::MissingTickArrayBitmapExtensionAccount))?
                     .check_tick_array_is_initialized(
                         TickArrayState::get_array_start_index(self.tick_current, self.tick_spacing),
                         self.tick_spacing,
@@ -426,3 +430,35 @@ impl RewardInfo {
         reward_growths
     }
 }
+
+/// Emitted by swaps
+#[event]
+#[derive(Debug)]
+pub struct SwapEvent {
+    /// The pool for which token_0 and token_1 were swapped
+    pub pool_state: Pubkey,
+    /// The address that initiated the swap call, and that received the callback
+    pub sender: Pubkey,
+    /// The payer token account in zero for one swaps, or the recipient token account
+    /// in one for zero swaps
+    pub token_account_0: Pubkey,
+    /// The payer token account in one for zero swaps, or the recipient token account
+    /// in zero for one swaps
+    pub token_account_1: Pubkey,
+    /// The real delta amount of the token_0 of the pool or user
+    pub amount_0: u64,
+    /// The transfer fee charged by the withheld_amount of the token_0
+    pub transfer_fee_0: u64,
+    /// The real delta of the token_1 of the pool or user
+    pub amount_1: u64,
+    /// The transfer fee charged by the withheld_amount of the token_1
+    pub transfer_fee_1: u64,
+    /// if true, amount_0 is negtive and amount_1 is positive
+    pub zero_for_one: bool,
+    /// The sqrt(price) of the pool after the swap, as a Q64.64
+    pub sqrt_price_x64: u128,
+    /// The liquidity of the pool after the swap
+    pub liquidity: u128,
+    /// The log base 1.0001 of price of the pool after the swap
+    pub tick: i32,
+}
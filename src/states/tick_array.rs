@@ -1,6 +1,6 @@
 use crate::libraries::error::ErrorCode;
 use crate::libraries::tick_math;
-use crate::states::REWARD_NUM;
+use crate::states::{REWARD_NUM, RewardInfo};
 use anchor_lang::prelude::*;
 use anyhow::anyhow;
 
@@ -121,6 +121,17 @@ impl TickArrayState {
     pub fn tick_count(tick_spacing: u16) -> i32 {
         TICK_ARRAY_SIZE * i32::from(tick_spacing)
     }
+
+    /// Get the tick state of `tick_index`, which must lie in this tick array
+    pub fn get_tick_state(&self, tick_index: i32, tick_spacing: u16) -> Result<TickState> {
+        let start_tick_index = self.start_tick_index;
+        require!(
+            TickArrayState::get_array_start_index(tick_index, tick_spacing) == start_tick_index,
+            ErrorCode::InvalidTickArray
+        );
+        let offset_in_array = (tick_index - start_tick_index) / i32::from(tick_spacing);
+        Ok(self.ticks[offset_in_array as usize])
+    }
 }
 
 impl Default for TickArrayState {
@@ -181,3 +192,81 @@ pub fn check_ticks_order(tick_lower_index: i32, tick_upper_index: i32) -> Result
     );
     Ok(())
 }
+
+/// Fee growths per unit of liquidity inside `tick_lower..tick_upper`, from the
+/// fee growths outside of both ticks and the global fee growths of the pool
+pub fn get_fee_growth_inside(
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+    tick_current: i32,
+    fee_growth_global_0_x64: u128,
+    fee_growth_global_1_x64: u128,
+) -> (u128, u128) {
+    let (lower_tick, upper_tick) = (tick_lower.tick, tick_upper.tick);
+    let (lower_outside_0, lower_outside_1) = (
+        tick_lower.fee_growth_outside_0_x64,
+        tick_lower.fee_growth_outside_1_x64,
+    );
+    let (upper_outside_0, upper_outside_1) = (
+        tick_upper.fee_growth_outside_0_x64,
+        tick_upper.fee_growth_outside_1_x64,
+    );
+    let (below_0, below_1) = if tick_current >= lower_tick {
+        (lower_outside_0, lower_outside_1)
+    } else {
+        (
+            fee_growth_global_0_x64.wrapping_sub(lower_outside_0),
+            fee_growth_global_1_x64.wrapping_sub(lower_outside_1),
+        )
+    };
+    let (above_0, above_1) = if tick_current < upper_tick {
+        (upper_outside_0, upper_outside_1)
+    } else {
+        (
+            fee_growth_global_0_x64.wrapping_sub(upper_outside_0),
+            fee_growth_global_1_x64.wrapping_sub(upper_outside_1),
+        )
+    };
+    (
+        fee_growth_global_0_x64
+            .wrapping_sub(below_0)
+            .wrapping_sub(above_0),
+        fee_growth_global_1_x64
+            .wrapping_sub(below_1)
+            .wrapping_sub(above_1),
+    )
+}
+
+/// Reward growths per unit of liquidity inside `tick_lower..tick_upper`, zero
+/// for uninitialized rewards
+pub fn get_reward_growths_inside(
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+    tick_current: i32,
+    reward_infos: &[RewardInfo; REWARD_NUM],
+) -> [u128; REWARD_NUM] {
+    let (lower_tick, upper_tick) = (tick_lower.tick, tick_upper.tick);
+    let (lower_outside, upper_outside) = (
+        tick_lower.reward_growths_outside_x64,
+        tick_upper.reward_growths_outside_x64,
+    );
+    let mut reward_growths_inside = [0u128; REWARD_NUM];
+    for (i, reward_info) in reward_infos.iter().enumerate() {
+        if !reward_info.initialized() {
+            continue;
+        }
+        let global = reward_info.reward_growth_global_x64;
+        let below = if tick_current >= lower_tick {
+            lower_outside[i]
+        } else {
+            global.wrapping_sub(lower_outside[i])
+        };
+        let above = if tick_current < upper_tick {
+            upper_outside[i]
+        } else {
+            global.wrapping_sub(upper_outside[i])
+        };
+        reward_growths_inside[i] = global.wrapping_sub(below).wrapping_sub(above);
+    }
+    reward_growths_inside
+}
@@ -0,0 +1,205 @@
+//! Pool accounts loaded into a local validator.
+//!
+//! [`amm_pool_accounts`] and [`clmm_pool_accounts`] list the accounts a swap
+//! through a pool reads, to clone them at validator startup or to dump them
+//! once with [`write_account_fixtures`] into files that are checked in and
+//! loaded offline.
+
+use crate::amm::client::AmmSwapClient;
+use crate::clmm::{derive_tick_array_bitmap_extension_key, get_tick_array_keys};
+use crate::common::deserialize_anchor_account;
+use crate::signer::SwapSigner;
+use crate::states::TickArrayBitmapExtension;
+use anyhow::{Context, anyhow};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::{Deserialize, Serialize};
+use solana_account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::path::{Path, PathBuf};
+
+/// Most accounts read by one `getMultipleAccounts` call.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// An account in the JSON format of `solana account --output json`, read by
+/// the `--account` option of `solana-test-validator`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountFixture {
+    pub pubkey: String,
+    pub account: AccountFixtureData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountFixtureData {
+    pub lamports: u64,
+    /// Data and its encoding, always `base64`.
+    pub data: (String, String),
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub space: u64,
+}
+
+impl AccountFixture {
+    pub fn from_account(address: &Pubkey, account: &Account) -> Self {
+        Self {
+            pubkey: address.to_string(),
+            account: AccountFixtureData {
+                lamports: account.lamports,
+                data: (BASE64_STANDARD.encode(&account.data), "base64".to_string()),
+                owner: account.owner.to_string(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                space: account.data.len() as u64,
+            },
+        }
+    }
+
+    pub fn to_account(&self) -> anyhow::Result<Account> {
+        Ok(Account {
+            lamports: self.account.lamports,
+            data: BASE64_STANDARD.decode(&self.account.data.0)?,
+            owner: self.account.owner.parse()?,
+            executable: self.account.executable,
+            rent_epoch: self.account.rent_epoch,
+        })
+    }
+}
+
+/// Accounts read by a swap through the AMM v4 pool `pool_id`: the pool, its
+/// vaults, open orders and target orders, its market and the market
+/// accounts, and both mints.
+pub async fn amm_pool_accounts(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+) -> anyhow::Result<Vec<Pubkey>> {
+    let keys = client.derive_amm_pool_keys(pool_id).await?;
+    [
+        &keys.id,
+        &keys.vault.a,
+        &keys.vault.b,
+        &keys.open_orders,
+        &keys.target_orders,
+        &keys.market_id,
+        &keys.market_base_vault,
+        &keys.market_quote_vault,
+        &keys.market_bids,
+        &keys.market_asks,
+        &keys.market_event_queue,
+        &keys.mint_a.address,
+        &keys.mint_b.address,
+    ]
+    .into_iter()
+    .map(|address| {
+        address
+            .parse()
+            .map_err(|e| anyhow!("Invalid key {address} of AMM pool {pool_id}: {e}"))
+    })
+    .collect()
+}
+
+/// Accounts read by a swap through the CLMM pool `pool_id`: the pool, its
+/// amm config, vaults, mints and observation, its bitmap extension when it
+/// has one, and its initialized tick arrays in both directions from the
+/// current tick.
+pub async fn clmm_pool_accounts(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+) -> anyhow::Result<Vec<Pubkey>> {
+    let pool_state = client.get_pool_state(pool_id).await?;
+    let to_sdk_pubkey = |pubkey: solana_pubkey::Pubkey| Pubkey::from(pubkey.to_bytes());
+    let program_id = client.cluster_config().clmm_program_id();
+    let pool_id_v2 = solana_pubkey::Pubkey::from(pool_id.to_bytes());
+    let bitmap_extension_key = derive_tick_array_bitmap_extension_key(&pool_id_v2, &program_id);
+    let bitmap_extension = client
+        .get_multiple_accounts(&[to_sdk_pubkey(bitmap_extension_key)])
+        .await?
+        .pop()
+        .flatten();
+
+    let mut accounts = vec![
+        *pool_id,
+        to_sdk_pubkey(pool_state.amm_config),
+        to_sdk_pubkey(pool_state.token_vault_0),
+        to_sdk_pubkey(pool_state.token_vault_1),
+        to_sdk_pubkey(pool_state.token_mint_0),
+        to_sdk_pubkey(pool_state.token_mint_1),
+        to_sdk_pubkey(pool_state.observation_key),
+    ];
+    let bitmap_extension_state = bitmap_extension
+        .as_ref()
+        .map(deserialize_anchor_account::<TickArrayBitmapExtension>)
+        .transpose()?;
+    if bitmap_extension.is_some() {
+        accounts.push(to_sdk_pubkey(bitmap_extension_key));
+    }
+    for zero_for_one in [true, false] {
+        let tick_arrays = get_tick_array_keys(
+            program_id,
+            pool_id_v2,
+            &pool_state,
+            &bitmap_extension_state,
+            zero_for_one,
+        )?;
+        accounts.extend(tick_arrays.into_iter().map(to_sdk_pubkey));
+    }
+    accounts.sort();
+    accounts.dedup();
+    Ok(accounts)
+}
+
+/// Read `addresses` through `client` and write each existing one to
+/// `<dir>/<address>.json`, returning the paths written. Missing accounts are
+/// skipped.
+pub async fn write_account_fixtures(
+    client: &AmmSwapClient<impl SwapSigner>,
+    addresses: &[Pubkey],
+    dir: impl AsRef<Path>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+    let mut accounts = Vec::with_capacity(addresses.len());
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        accounts.extend(client.get_multiple_accounts(chunk).await?);
+    }
+    let mut paths = Vec::with_capacity(addresses.len());
+    for (address, account) in addresses.iter().zip(accounts) {
+        let Some(account) = account else {
+            continue;
+        };
+        let path = dir.join(format!("{address}.json"));
+        let fixture = AccountFixture::from_account(address, &account);
+        std::fs::write(&path, serde_json::to_vec_pretty(&fixture)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_fixture_matches_cli_format() {
+        let address = Pubkey::new_from_array([1; 32]);
+        let account = Account {
+            lamports: 42,
+            data: vec![1, 2, 3],
+            owner: Pubkey::new_from_array([2; 32]),
+            executable: false,
+            rent_epoch: u64::MAX,
+        };
+        let fixture = AccountFixture::from_account(&address, &account);
+        let json = serde_json::to_value(&fixture).unwrap();
+        assert_eq!(json["pubkey"], address.to_string());
+        assert_eq!(
+            json["account"]["data"],
+            serde_json::json!(["AQID", "base64"])
+        );
+        assert_eq!(json["account"]["rentEpoch"], u64::MAX);
+        assert_eq!(json["account"]["space"], 3);
+        assert_eq!(fixture.to_account().unwrap(), account);
+    }
+}
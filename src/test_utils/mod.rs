@@ -0,0 +1,6 @@
+pub mod fixtures;
+pub use fixtures::*;
+pub mod swap;
+pub use swap::*;
+pub mod validator;
+pub use validator::*;
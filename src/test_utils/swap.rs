@@ -0,0 +1,72 @@
+//! End-to-end swaps against the pools of a local validator.
+//!
+//! The Raydium API does not list the pools of a local validator, so these
+//! helpers read everything a swap needs from the validator itself and send
+//! the swap with the regular client paths: [`AmmSwapClient::swap`] for AMM
+//! v4 pools and [`AmmSwapClient::swap_clmm`] for CLMM pools.
+
+use crate::amm::client::AmmSwapClient;
+use crate::interface::ClmmSwapParams;
+use crate::signer::SwapSigner;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+/// Sell `amount_in` of `input_mint` through the AMM v4 pool `pool_id`, with
+/// keys derived from the pool and market accounts and the output quoted
+/// from the reserves. SOL is wrapped in the swap transaction.
+pub async fn run_amm_swap(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u64,
+) -> anyhow::Result<Signature> {
+    let pool_keys = client.derive_amm_pool_keys(pool_id).await?;
+    client
+        .swap(&pool_keys)
+        .input_mint(*input_mint)
+        .amount(amount_in)
+        .slippage_bps(slippage_bps)
+        .send()
+        .await
+}
+
+/// Sell `amount_in` of `input_mint` through the CLMM pool `pool_id`, from
+/// the owner's associated token account of `input_mint`, to the one of the
+/// other mint, created when missing.
+///
+/// SOL is wrapped into the owner's wSOL account first, in its own
+/// transaction; other input mints must already be held by the owner.
+pub async fn run_clmm_swap(
+    client: &AmmSwapClient<impl SwapSigner>,
+    pool_id: &Pubkey,
+    input_mint: &Pubkey,
+    amount_in: u64,
+    slippage_bps: u64,
+) -> anyhow::Result<Signature> {
+    if *input_mint == spl_token::native_mint::id() {
+        client.wrap_sol(amount_in).await?;
+    }
+    let token_program = client.rpc_client().get_account(input_mint).await?.owner;
+    let user_input_token = get_associated_token_address_with_program_id(
+        &client.owner_pubkey(),
+        input_mint,
+        &token_program,
+    );
+    let (swap_change, tick_array_bitmap_extension) = client
+        .calculate_swap_change_clmm(ClmmSwapParams {
+            pool_id: solana_pubkey::Pubkey::from(pool_id.to_bytes()),
+            user_input_token: solana_pubkey::Pubkey::from(user_input_token.to_bytes()),
+            user_output_token: None,
+            amount_specified: amount_in,
+            limit_price: None,
+            base_out: false,
+            slippage_bps,
+            max_price_impact_bps: None,
+        })
+        .await?;
+    client
+        .swap_clmm(None, swap_change, tick_array_bitmap_extension)
+        .await
+}
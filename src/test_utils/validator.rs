@@ -0,0 +1,298 @@
+//! `solana-test-validator` run for the duration of a test.
+//!
+//! [`LocalValidator::start`] launches the validator on free local ports with
+//! a fresh ledger, loads the Raydium programs and the pool accounts of the
+//! [`LocalValidatorConfig`], and waits for its RPC to be healthy. The
+//! validator is killed and its ledger removed when the [`LocalValidator`] is
+//! dropped.
+//!
+//! Accounts are either cloned from a live cluster at startup, which needs
+//! network access, or loaded from fixture files written with
+//! [`write_account_fixtures`](crate::test_utils::write_account_fixtures), so
+//! CI can run offline.
+
+use crate::amm::client::AmmSwapClient;
+use crate::consts::{AMM_V4, CLMM, OPEN_BOOK};
+use crate::signer::SwapSigner;
+use anyhow::{Context, anyhow};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Public mainnet RPC, the default source of cloned accounts.
+pub const MAINNET_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+
+/// Interval between polls of the validator while waiting on it.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Programs and accounts loaded into a [`LocalValidator`].
+#[derive(Debug, Clone)]
+pub struct LocalValidatorConfig {
+    /// Validator binary, `solana-test-validator` from the `PATH` unless set.
+    pub binary: PathBuf,
+    /// Cluster the accounts and programs are cloned from.
+    pub clone_url: String,
+    /// Upgradeable programs cloned from `clone_url`: AMM v4, CLMM and
+    /// OpenBook unless set.
+    pub clone_programs: Vec<Pubkey>,
+    /// Accounts cloned from `clone_url`.
+    pub clone_accounts: Vec<Pubkey>,
+    /// Programs loaded from `.so` files instead of cloned.
+    pub program_files: Vec<(Pubkey, PathBuf)>,
+    /// Account fixture files, in the JSON format of `solana account
+    /// --output json`.
+    pub account_files: Vec<PathBuf>,
+    /// How long to wait for the RPC to be healthy.
+    pub startup_timeout: Duration,
+}
+
+impl Default for LocalValidatorConfig {
+    fn default() -> Self {
+        Self {
+            binary: PathBuf::from("solana-test-validator"),
+            clone_url: MAINNET_RPC_URL.to_string(),
+            clone_programs: [AMM_V4, CLMM, OPEN_BOOK]
+                .into_iter()
+                .map(Pubkey::from_str_const)
+                .collect(),
+            clone_accounts: Vec::new(),
+            program_files: Vec::new(),
+            account_files: Vec::new(),
+            startup_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl LocalValidatorConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fully offline configuration from the fixture directory `dir`: every
+    /// `*.json` file is an account, every `<program id>.so` file a program.
+    /// Nothing is cloned.
+    pub fn from_fixture_dir(dir: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let mut config = Self {
+            clone_programs: Vec::new(),
+            ..Self::default()
+        };
+        let mut entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read fixture dir {}", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for path in entries {
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => config.account_files.push(path),
+                Some("so") => {
+                    let program_id = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .and_then(|stem| stem.parse().ok())
+                        .ok_or_else(|| {
+                            anyhow!("Program file {} is not named by its id", path.display())
+                        })?;
+                    config.program_files.push((program_id, path));
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn with_binary(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.binary = binary.into();
+        self
+    }
+
+    pub fn with_clone_url(mut self, clone_url: impl Into<String>) -> Self {
+        self.clone_url = clone_url.into();
+        self
+    }
+
+    /// Clone `accounts` too, e.g. those of
+    /// [`amm_pool_accounts`](crate::test_utils::amm_pool_accounts). The
+    /// wSOL mint is skipped, the validator genesis already holds it.
+    pub fn clone_accounts(mut self, accounts: impl IntoIterator<Item = Pubkey>) -> Self {
+        let native_mint = spl_token::native_mint::id();
+        self.clone_accounts.extend(
+            accounts
+                .into_iter()
+                .filter(|address| *address != native_mint),
+        );
+        self
+    }
+
+    /// Load the account fixture files `paths` too.
+    pub fn account_files(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.account_files.extend(paths);
+        self
+    }
+
+    pub fn with_startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    fn args(&self, ledger: &Path, rpc_port: u16, faucet_port: u16) -> Vec<String> {
+        let mut args = vec![
+            "--reset".to_string(),
+            "--quiet".to_string(),
+            "--bind-address".to_string(),
+            "127.0.0.1".to_string(),
+            "--ledger".to_string(),
+            ledger.display().to_string(),
+            "--rpc-port".to_string(),
+            rpc_port.to_string(),
+            "--faucet-port".to_string(),
+            faucet_port.to_string(),
+        ];
+        if !self.clone_programs.is_empty() || !self.clone_accounts.is_empty() {
+            args.extend(["--url".to_string(), self.clone_url.clone()]);
+        }
+        for program_id in &self.clone_programs {
+            args.extend([
+                "--clone-upgradeable-program".to_string(),
+                program_id.to_string(),
+            ]);
+        }
+        for address in &self.clone_accounts {
+            args.extend(["--maybe-clone".to_string(), address.to_string()]);
+        }
+        for (program_id, path) in &self.program_files {
+            args.extend([
+                "--bpf-program".to_string(),
+                program_id.to_string(),
+                path.display().to_string(),
+            ]);
+        }
+        for path in &self.account_files {
+            // The address is read from the `pubkey` field of the file.
+            args.extend([
+                "--account".to_string(),
+                "-".to_string(),
+                path.display().to_string(),
+            ]);
+        }
+        args
+    }
+}
+
+/// A running `solana-test-validator`, killed on drop.
+#[derive(Debug)]
+pub struct LocalValidator {
+    process: Child,
+    ledger: PathBuf,
+    rpc_url: String,
+    websocket_url: String,
+}
+
+impl LocalValidator {
+    /// Start a validator loading the programs and accounts of `config`, and
+    /// wait until its RPC is healthy.
+    pub async fn start(config: &LocalValidatorConfig) -> anyhow::Result<Self> {
+        let rpc_port = free_port()?;
+        let mut faucet_port = free_port()?;
+        while faucet_port == rpc_port + 1 {
+            faucet_port = free_port()?;
+        }
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let ledger = std::env::temp_dir().join(format!("raydium-test-ledger-{rpc_port}-{nanos}"));
+        let process = Command::new(&config.binary)
+            .args(config.args(&ledger, rpc_port, faucet_port))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", config.binary.display()))?;
+        let mut validator = Self {
+            process,
+            ledger,
+            rpc_url: format!("http://127.0.0.1:{rpc_port}"),
+            // The validator serves WebSocket subscriptions on the next port.
+            websocket_url: format!("ws://127.0.0.1:{}", rpc_port + 1),
+        };
+        validator.wait_until_healthy(config.startup_timeout).await?;
+        debug!("Local validator running at {}", validator.rpc_url);
+        Ok(validator)
+    }
+
+    async fn wait_until_healthy(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        let rpc_client = self.rpc_client();
+        let started_at = Instant::now();
+        loop {
+            if let Some(status) = self.process.try_wait()? {
+                return Err(anyhow!(
+                    "Local validator exited during startup with {status}, see {}",
+                    self.ledger.join("validator.log").display()
+                ));
+            }
+            if rpc_client.get_health().await.is_ok() {
+                return Ok(());
+            }
+            if started_at.elapsed() > timeout {
+                return Err(anyhow!(
+                    "Local validator not healthy after {timeout:?}, see {}",
+                    self.ledger.join("validator.log").display()
+                ));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    pub fn websocket_url(&self) -> &str {
+        &self.websocket_url
+    }
+
+    /// RPC client of the validator, at `confirmed` commitment.
+    pub fn rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+    }
+
+    /// Swap client of the validator signing with `owner`. The pools of the
+    /// validator are not listed by the Raydium API: derive their keys with
+    /// [`AmmSwapClient::derive_amm_pool_keys`] or read their states.
+    pub fn client<S: SwapSigner>(&self, owner: S) -> AmmSwapClient<S> {
+        AmmSwapClient::new(self.rpc_client(), owner)
+    }
+
+    /// Airdrop `lamports` from the validator faucet to `recipient` and wait
+    /// for the airdrop to be confirmed.
+    pub async fn airdrop(&self, recipient: &Pubkey, lamports: u64) -> anyhow::Result<Signature> {
+        let rpc_client = self.rpc_client();
+        let signature = rpc_client.request_airdrop(recipient, lamports).await?;
+        let started_at = Instant::now();
+        while !rpc_client.confirm_transaction(&signature).await? {
+            if started_at.elapsed() > Duration::from_secs(30) {
+                return Err(anyhow!("Airdrop {signature} to {recipient} not confirmed"));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Ok(signature)
+    }
+}
+
+impl Drop for LocalValidator {
+    fn drop(&mut self) {
+        if let Err(e) = self.process.kill() {
+            warn!("Failed to stop the local validator: {e}");
+        }
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.ledger);
+    }
+}
+
+/// A local port free at the time of the call.
+fn free_port() -> anyhow::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
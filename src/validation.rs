@@ -0,0 +1,103 @@
+//! Upfront checks of swap and quote arguments.
+//!
+//! Every check fails with a [`ValidationError`] wrapped in `anyhow`, before
+//! any RPC or API request is made.
+
+use crate::error::ValidationError;
+use crate::interface::{AmmPool, ClmmPool};
+
+/// Largest slippage in bps, i.e. 100%.
+const MAX_SLIPPAGE_BPS: u64 = 10_000;
+
+pub fn validate_amount(amount: u64) -> anyhow::Result<()> {
+    if amount == 0 {
+        return Err(ValidationError::ZeroAmount.into());
+    }
+    Ok(())
+}
+
+/// Slippage as a fraction, between `0.0` and `1.0`.
+pub fn validate_slippage(slippage: f64) -> anyhow::Result<()> {
+    if !(0.0..=1.0).contains(&slippage) {
+        return Err(ValidationError::InvalidSlippage { slippage }.into());
+    }
+    Ok(())
+}
+
+/// Slippage in bps, at most 10 000.
+pub fn validate_slippage_bps(slippage_bps: u64) -> anyhow::Result<()> {
+    if slippage_bps > MAX_SLIPPAGE_BPS {
+        return Err(ValidationError::InvalidSlippage {
+            slippage: slippage_bps as f64 / MAX_SLIPPAGE_BPS as f64,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+pub fn validate_distinct_mints(mint_in: &str, mint_out: &str) -> anyhow::Result<()> {
+    if mint_in == mint_out {
+        return Err(ValidationError::SameMint {
+            mint: mint_in.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Check that `mint_in` and `mint_out` are distinct and are the two mints of
+/// the pool with mints `pool_mints`.
+fn validate_pair(
+    pool_id: &str,
+    pool_mints: [&str; 2],
+    mint_in: &str,
+    mint_out: &str,
+) -> anyhow::Result<()> {
+    validate_distinct_mints(mint_in, mint_out)?;
+    for mint in [mint_in, mint_out] {
+        if !pool_mints.contains(&mint) {
+            return Err(ValidationError::MintNotInPool {
+                pool_id: pool_id.to_string(),
+                mint: mint.to_string(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Check a swap of `mint_in` for `mint_out` through an AMM v4 pool.
+pub fn validate_amm_pair(pool_keys: &AmmPool, mint_in: &str, mint_out: &str) -> anyhow::Result<()> {
+    validate_pair(
+        &pool_keys.id,
+        [&pool_keys.mint_a.address, &pool_keys.mint_b.address],
+        mint_in,
+        mint_out,
+    )
+}
+
+/// Check a swap of `mint_in` for `mint_out` through a pool listed by the API.
+pub fn validate_pool_pair(
+    pool_info: &ClmmPool,
+    mint_in: &str,
+    mint_out: &str,
+) -> anyhow::Result<()> {
+    validate_pair(
+        &pool_info.id,
+        [&pool_info.mint_a.address, &pool_info.mint_b.address],
+        mint_in,
+        mint_out,
+    )
+}
+
+/// Check that `mint` is one of the mints of a pool listed by the API.
+pub fn validate_pool_mint(pool_info: &ClmmPool, mint: &str) -> anyhow::Result<()> {
+    if pool_info.mint_a.address != mint && pool_info.mint_b.address != mint {
+        return Err(ValidationError::MintNotInPool {
+            pool_id: pool_info.id.clone(),
+            mint: mint.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! Owner token account inventory and dust consolidation.
+
+use crate::amm::client::{AmmSwapClient, SwapRequest};
+use crate::interface::{AmmPool, ClmmPool, PoolKeys, PoolType};
+use crate::router::select_best_pool;
+use crate::signer::SwapSigner;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
+use tracing::debug;
+
+/// Close instructions packed into a single transaction.
+const MAX_CLOSES_PER_TRANSACTION: usize = 20;
+
+/// A token account of the owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenHolding {
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    /// Raw balance.
+    pub amount: u64,
+    pub decimals: u8,
+    /// USD price of the mint, if known.
+    pub price_usd: Option<f64>,
+}
+
+impl TokenHolding {
+    pub fn ui_amount(&self) -> f64 {
+        self.amount as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    /// USD value of the balance, if the price is known.
+    pub fn value_usd(&self) -> Option<f64> {
+        self.price_usd.map(|price| price * self.ui_amount())
+    }
+}
+
+/// Fetch all SPL token accounts of the owner, priced in USD.
+///
+/// Token-2022 accounts are left out, as dust consolidation closes accounts
+/// with SPL Token instructions; see [`AmmSwapClient::get_token_balances`].
+pub async fn fetch_wallet_inventory(
+    client: &AmmSwapClient<impl SwapSigner>,
+) -> anyhow::Result<Vec<TokenHolding>> {
+    let mut holdings: Vec<TokenHolding> = client
+        .get_token_balances(&client.owner_pubkey())
+        .await?
+        .into_iter()
+        .filter(|balance| balance.token_program == spl_token::id())
+        .map(|balance| TokenHolding {
+            token_account: balance.token_account,
+            mint: balance.mint,
+            amount: balance.amount,
+            decimals: balance.decimals,
+            price_usd: None,
+        })
+        .collect();
+
+    let mints: Vec<String> = holdings.iter().map(|h| h.mint.to_string()).collect();
+    let prices = client.fetch_mint_prices(&mints).await?;
+    for holding in &mut holdings {
+        holding.price_usd = prices.get(&holding.mint.to_string()).copied();
+    }
+    Ok(holdings)
+}
+
+/// Holdings worth less than `threshold_usd`, excluding `target_mint`.
+///
+/// Empty accounts are always dust. Holdings without a known price are kept
+/// out, since their value cannot be assessed.
+pub fn find_dust(
+    holdings: &[TokenHolding],
+    target_mint: &Pubkey,
+    threshold_usd: f64,
+) -> Vec<TokenHolding> {
+    holdings
+        .iter()
+        .filter(|holding| holding.mint != *target_mint)
+        .filter(|holding| {
+            holding.amount == 0
+                || holding
+                    .value_usd()
+                    .is_some_and(|value| value < threshold_usd)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Transactions consolidating dust into the target mint.
+pub struct DustConsolidation {
+    /// One swap per dust holding with a route, each closing the drained account.
+    pub swaps: Vec<VersionedTransaction>,
+    /// Closes of the already empty accounts.
+    pub close_empty_accounts: Vec<VersionedTransaction>,
+    /// Holdings left alone: not held in the associated token account, or no
+    /// AMM v4 pool to the target mint was found.
+    pub skipped: Vec<TokenHolding>,
+}
+
+/// Build swaps of every dust holding into `target_mint` through the best
+/// AMM v4 pool of the pair, plus closes of the emptied token accounts.
+///
+/// - `slippage`: tolerance (e.g. `0.01` for 1%).
+pub async fn build_dust_consolidation(
+    client: &AmmSwapClient<impl SwapSigner>,
+    dust: &[TokenHolding],
+    target_mint: &Pubkey,
+    slippage: f64,
+) -> anyhow::Result<DustConsolidation> {
+    let target = target_mint.to_string();
+    let mut requests = Vec::new();
+    let mut empty_accounts = Vec::new();
+    let mut skipped = Vec::new();
+
+    let owner = client.owner_pubkey();
+    let amm_v4 = client.cluster_config().amm_v4.to_string();
+    for holding in dust {
+        if holding.amount == 0 {
+            empty_accounts.push(holding.token_account);
+            continue;
+        }
+        // Swaps are built against associated token accounts.
+        if get_associated_token_address(&owner, &holding.mint) != holding.token_account {
+            skipped.push(holding.clone());
+            continue;
+        }
+        let mint = holding.mint.to_string();
+        let pools: Vec<ClmmPool> = client
+            .fetch_pool_info(&mint, &target, &PoolType::Standard, Some(20), None, None, None)
+            .await?
+            .into_iter()
+            .filter(|pool| pool.program_id == amm_v4)
+            .collect();
+        let Some(pool) = select_best_pool(&pools, &mint, holding.ui_amount()) else {
+            debug!("No AMM pool from {mint} to {target}, skipping");
+            skipped.push(holding.clone());
+            continue;
+        };
+
+        let pool_id = Pubkey::from_str(&pool.id)?;
+        let pool_keys: PoolKeys<AmmPool> = client.fetch_pools_keys_by_id(&pool_id).await?;
+        let pool_keys = pool_keys
+            .data
+            .into_iter()
+            .next()
+            .ok_or(anyhow!("No keys returned for pool {}", pool_id))?;
+        let rpc_pool_info = client.get_rpc_pool_info(&pool_id).await?;
+
+        let quote = client.compute_amount_out_for_input(
+            &rpc_pool_info,
+            pool,
+            &mint,
+            holding.amount,
+            slippage,
+        )?;
+
+        requests.push(SwapRequest::Amm {
+            pool_keys,
+            mint_in: holding.mint,
+            mint_out: *target_mint,
+            amount_in: holding.amount,
+            min_amount_out: quote.min_amount_out,
+            close_source_account: true,
+        });
+    }
+
+    let swaps = client.build_swaps(requests).await?;
+    let mut close_empty_accounts = Vec::new();
+    for chunk in empty_accounts.chunks(MAX_CLOSES_PER_TRANSACTION) {
+        close_empty_accounts.push(client.build_close_accounts(chunk).await?);
+    }
+    Ok(DustConsolidation {
+        swaps,
+        close_empty_accounts,
+        skipped,
+    })
+}
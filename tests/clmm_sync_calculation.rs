@@ -0,0 +1,134 @@
+//! CLMM swap calculation flow of `examples/sync_calculation.rs`, against a
+//! live pool: accounts loaded once, then the swap computed without RPC.
+//!
+//! Run with
+//! `cargo test --features integration-tests --test clmm_sync_calculation`,
+//! see `tests/common` for the environment.
+
+#![cfg(feature = "integration-tests")]
+
+mod common;
+
+use raydium_amm_swap::amm::client::AmmSwapClient;
+use raydium_amm_swap::clmm::{
+    calculate_swap_change_accounts, clmm_program_id, derive_tick_array_bitmap_extension_key,
+    get_tick_array_keys,
+};
+use raydium_amm_swap::consts::CLMM;
+use raydium_amm_swap::interface::{ClmmSwapParams, PoolType};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
+
+#[tokio::test]
+async fn clmm_sync_calculation_matches_live_pool() {
+    let (mint_in, mint_out) = common::mints();
+    let amount_in = common::amount_in();
+    let owner = common::owner();
+    let client = common::watch_only_client(owner);
+
+    let pools = client
+        .fetch_pool_info(
+            &mint_in.to_string(),
+            &mint_out.to_string(),
+            &PoolType::Concentrated,
+            Some(100),
+            None,
+            None,
+            None,
+        )
+        .await
+        .expect("failed to fetch pools");
+    let pool = pools
+        .iter()
+        .find(|pool| pool.program_id == CLMM)
+        .expect("the pair has no CLMM pool");
+    let pool_id = Pubkey::from_str(&pool.id).unwrap();
+    let pool_id_v2 = solana_pubkey::Pubkey::from(pool_id.to_bytes());
+
+    let tick_array_bitmap_extension = AmmSwapClient::get_tick_array_bitmap_extension(&pool_id);
+    assert_eq!(
+        tick_array_bitmap_extension,
+        derive_tick_array_bitmap_extension_key(&pool_id_v2, &clmm_program_id())
+    );
+
+    let user_input_token =
+        solana_pubkey::Pubkey::from(get_associated_token_address(&owner, &mint_in).to_bytes());
+    let epoch = client.get_epoch().await.unwrap();
+    let pool_state = client.get_pool_state(&pool_id).await.unwrap();
+    let (token_mint_0, token_mint_1) = (pool_state.token_mint_0, pool_state.token_mint_1);
+    let pool_mints = [token_mint_0.to_bytes(), token_mint_1.to_bytes()];
+    assert!(pool_mints.contains(&mint_in.to_bytes()));
+    assert!(pool_mints.contains(&mint_out.to_bytes()));
+
+    let rsps = client
+        .get_rsps(user_input_token, &pool_state, &tick_array_bitmap_extension)
+        .await
+        .unwrap();
+    assert!(rsps[0].is_some(), "OWNER holds no {mint_in} account");
+    let params = calculate_swap_change_accounts(&rsps, amount_in, pool_state, true, epoch)
+        .expect("failed to decode the swap accounts");
+    assert_eq!(
+        params.zero_for_one,
+        token_mint_0.to_bytes() == mint_in.to_bytes()
+    );
+    let tick_array_keys = get_tick_array_keys(
+        clmm_program_id(),
+        pool_id_v2,
+        &pool_state,
+        &params.tickarray_bitmap_extension_state,
+        params.zero_for_one,
+    )
+    .unwrap();
+    let tick_arrays = client
+        .load_cur_and_next_five_tick_array(
+            clmm_program_id(),
+            pool_id_v2,
+            &pool_state,
+            &params.tickarray_bitmap_extension_state,
+            params.zero_for_one,
+        )
+        .await
+        .unwrap();
+    assert_eq!(tick_arrays.len(), tick_array_keys.len());
+
+    let slippage_bps = (common::SLIPPAGE * 10_000.0) as u64;
+    let (result, result_bitmap_extension) = client
+        .calculate_swap_change_clmm_sync(
+            ClmmSwapParams {
+                pool_id: pool_id_v2,
+                user_input_token,
+                user_output_token: None,
+                amount_specified: amount_in,
+                limit_price: None,
+                base_out: false,
+                slippage_bps,
+                max_price_impact_bps: None,
+            },
+            epoch,
+            pool_state,
+            rsps,
+            tick_arrays,
+            tick_array_bitmap_extension,
+        )
+        .expect("failed to compute the swap");
+
+    assert_eq!(result_bitmap_extension, tick_array_bitmap_extension);
+    assert_eq!(result.pool_id, pool_id_v2);
+    assert_eq!(result.user_input_token, user_input_token);
+    assert_eq!(result.input_vault_mint.to_bytes(), mint_in.to_bytes());
+    assert_eq!(result.output_vault_mint.to_bytes(), mint_out.to_bytes());
+    assert!(result.is_base_input);
+    assert_eq!(result.amount, amount_in);
+    assert!(result.amount_requested <= amount_in);
+    assert!(result.other_amount_threshold > 0);
+    assert_eq!(result.context_slot, None);
+    assert!(!result.remaining_tick_array_keys.is_empty());
+    for key in &result.remaining_tick_array_keys {
+        let key = Pubkey::from(key.to_bytes());
+        assert!(
+            tick_array_keys.contains(&key),
+            "tick array {key} not loaded"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Settings shared by the integration tests, read from the environment.
+//!
+//! | Variable | Default |
+//! |---|---|
+//! | `RPC_URL` | required |
+//! | `MINT_1` | SOL, the mint sold |
+//! | `MINT_2` | required, the mint bought |
+//! | `AMOUNT_IN` | 1 000 000, raw units of `MINT_1` |
+//! | `OWNER` | required by the CLMM tests, wallet holding `MINT_1` |
+//! | `INTEGRATION_SEND_SWAPS` | unset; `1` to send the swaps, signed by `KEYPAIR` |
+//!
+//! A `.env` file in the working directory is loaded first, if present.
+
+#![allow(dead_code)]
+
+use raydium_amm_swap::amm::client::AmmSwapClient;
+use raydium_amm_swap::config::ClientConfig;
+use raydium_amm_swap::consts::SOL_MINT;
+use raydium_amm_swap::signer::WatchOnly;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::str::FromStr;
+
+pub const DEFAULT_AMOUNT_IN: u64 = 1_000_000;
+/// Slippage tolerance of the quotes, 1%.
+pub const SLIPPAGE: f64 = 0.01;
+
+fn var(name: &str) -> String {
+    dotenvy::dotenv().ok();
+    env::var(name).unwrap_or_else(|_| panic!("Set {name} to run the integration tests"))
+}
+
+fn pubkey_var(name: &str) -> Pubkey {
+    Pubkey::from_str(&var(name)).unwrap_or_else(|e| panic!("{name} is not an address: {e}"))
+}
+
+/// Mint sold and mint bought.
+pub fn mints() -> (Pubkey, Pubkey) {
+    dotenvy::dotenv().ok();
+    let mint_1 = env::var("MINT_1").unwrap_or_else(|_| SOL_MINT.to_string());
+    let mint_1 = Pubkey::from_str(&mint_1).expect("MINT_1 is not an address");
+    (mint_1, pubkey_var("MINT_2"))
+}
+
+pub fn amount_in() -> u64 {
+    dotenvy::dotenv().ok();
+    env::var("AMOUNT_IN")
+        .map(|amount| amount.parse().expect("AMOUNT_IN is not an amount"))
+        .unwrap_or(DEFAULT_AMOUNT_IN)
+}
+
+/// Wallet holding `MINT_1`, whose token accounts the CLMM quotes read.
+pub fn owner() -> Pubkey {
+    pubkey_var("OWNER")
+}
+
+pub fn send_swaps() -> bool {
+    dotenvy::dotenv().ok();
+    env::var("INTEGRATION_SEND_SWAPS").is_ok_and(|send| send == "1")
+}
+
+/// Client reading the chain as `owner`, never signing.
+pub fn watch_only_client(owner: Pubkey) -> AmmSwapClient<WatchOnly> {
+    AmmSwapClient::new(RpcClient::new(var("RPC_URL")), WatchOnly::address(owner))
+}
+
+/// Client signing with `KEYPAIR`, configured like the examples.
+pub fn signing_client() -> AmmSwapClient {
+    ClientConfig::from_env()
+        .expect("Set KEYPAIR to send swaps")
+        .into_client()
+}
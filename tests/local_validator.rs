@@ -0,0 +1,100 @@
+//! AMM v4 and CLMM swaps sent end-to-end to a local validator, without real
+//! funds.
+//!
+//! Run with `cargo test --features test-utils --test local_validator`. Needs
+//! `solana-test-validator` on the `PATH`.
+//!
+//! | Variable | Default |
+//! |---|---|
+//! | `LOCAL_FIXTURES_DIR` | unset; fixture directory loaded offline instead of cloning |
+//! | `CLONE_URL` | mainnet, the cluster pools are cloned from |
+//! | `AMM_POOL` | SOL/USDC AMM v4 pool |
+//! | `CLMM_POOL` | SOL/USDC CLMM pool |
+//!
+//! A fixture directory holds the `*.json` accounts written by
+//! `test_utils::write_account_fixtures` and the `<program id>.so` programs.
+
+#![cfg(feature = "test-utils")]
+
+use raydium_amm_swap::amm::client::AmmSwapClient;
+use raydium_amm_swap::consts::USDC_MINT;
+use raydium_amm_swap::test_utils::{
+    LocalValidator, LocalValidatorConfig, MAINNET_RPC_URL, amm_pool_accounts, clmm_pool_accounts,
+    run_amm_swap, run_clmm_swap,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::env;
+
+const DEFAULT_AMM_POOL: &str = "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2";
+const DEFAULT_CLMM_POOL: &str = "3ucNos4NbumPLZNWztqGHNFFgkHeRMBQAVemeeomsUxv";
+const SLIPPAGE_BPS: u64 = 100;
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+fn pool_var(name: &str, default: &str) -> Pubkey {
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .parse()
+        .unwrap_or_else(|e| panic!("{name} is not an address: {e}"))
+}
+
+/// Validator holding the AMM and CLMM pools, cloned or loaded offline.
+async fn start_validator(amm_pool: &Pubkey, clmm_pool: &Pubkey) -> LocalValidator {
+    let config = match env::var("LOCAL_FIXTURES_DIR") {
+        Ok(dir) => LocalValidatorConfig::from_fixture_dir(dir).expect("invalid fixture dir"),
+        Err(_) => {
+            let clone_url = env::var("CLONE_URL").unwrap_or_else(|_| MAINNET_RPC_URL.to_string());
+            let source = AmmSwapClient::watch_only(RpcClient::new(clone_url.clone()));
+            let mut accounts = amm_pool_accounts(&source, amm_pool)
+                .await
+                .expect("failed to list the AMM pool accounts");
+            accounts.extend(
+                clmm_pool_accounts(&source, clmm_pool)
+                    .await
+                    .expect("failed to list the CLMM pool accounts"),
+            );
+            LocalValidatorConfig::new()
+                .with_clone_url(clone_url)
+                .clone_accounts(accounts)
+        }
+    };
+    LocalValidator::start(&config)
+        .await
+        .expect("failed to start the local validator")
+}
+
+#[tokio::test]
+async fn swaps_land_on_local_validator() {
+    let amm_pool = pool_var("AMM_POOL", DEFAULT_AMM_POOL);
+    let clmm_pool = pool_var("CLMM_POOL", DEFAULT_CLMM_POOL);
+    let validator = start_validator(&amm_pool, &clmm_pool).await;
+
+    let owner = Keypair::new();
+    validator
+        .airdrop(&owner.pubkey(), 10 * LAMPORTS_PER_SOL)
+        .await
+        .expect("airdrop failed");
+    let client = validator.client(owner);
+    let sol = spl_token::native_mint::id();
+    let usdc = Pubkey::from_str_const(USDC_MINT);
+    let amount_in = LAMPORTS_PER_SOL / 10;
+
+    let signature = run_amm_swap(&client, &amm_pool, &sol, amount_in, SLIPPAGE_BPS)
+        .await
+        .expect("AMM v4 swap failed");
+    let amm_fill = client
+        .verify_swap_fill(&signature, &usdc, 1)
+        .await
+        .expect("AMM v4 swap paid no USDC");
+    assert!(amm_fill.amount_out > 0);
+
+    let signature = run_clmm_swap(&client, &clmm_pool, &sol, amount_in, SLIPPAGE_BPS)
+        .await
+        .expect("CLMM swap failed");
+    let clmm_fill = client
+        .verify_swap_fill(&signature, &usdc, 1)
+        .await
+        .expect("CLMM swap paid no USDC");
+    assert!(clmm_fill.amount_out > 0);
+}
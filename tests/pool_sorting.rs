@@ -0,0 +1,67 @@
+//! Pool sorting of `examples/pool_sort_fields.rs`, against the Raydium API.
+//!
+//! Run with `cargo test --features integration-tests --test pool_sorting`,
+//! see `tests/common` for the environment.
+
+#![cfg(feature = "integration-tests")]
+
+mod common;
+
+use raydium_amm_swap::interface::{ClmmPool, PoolSortField, PoolType};
+use solana_sdk::pubkey::Pubkey;
+
+/// Pools of the pair sorted by `sort_field`, descending.
+async fn sorted_pools(pool_type: PoolType, sort_field: PoolSortField) -> Vec<ClmmPool> {
+    let (mint_in, mint_out) = common::mints();
+    let sort_field = sort_field.to_string();
+    common::watch_only_client(Pubkey::default())
+        .fetch_pool_info(
+            &mint_in.to_string(),
+            &mint_out.to_string(),
+            &pool_type,
+            Some(20),
+            Some(1),
+            Some(sort_field.as_str()),
+            Some("desc"),
+        )
+        .await
+        .expect("failed to fetch pools")
+}
+
+fn assert_descending(values: &[f64], sort_field: &str) {
+    assert!(
+        values.windows(2).all(|pair| pair[0] >= pair[1]),
+        "pools are not sorted by {sort_field}: {values:?}"
+    );
+}
+
+#[tokio::test]
+async fn pools_sorted_by_liquidity_have_decreasing_tvl() {
+    for pool_type in [PoolType::Standard, PoolType::Concentrated] {
+        let pools = sorted_pools(pool_type, PoolSortField::Liquidity).await;
+        let tvl: Vec<f64> = pools.iter().map(|pool| pool.tvl.unwrap_or(0.0)).collect();
+        assert_descending(&tvl, "liquidity");
+    }
+}
+
+#[tokio::test]
+async fn pools_sorted_by_volume_have_decreasing_volume() {
+    let pools = sorted_pools(PoolType::Concentrated, PoolSortField::Volume24h).await;
+    assert!(!pools.is_empty(), "the pair has no CLMM pool");
+    let volume: Vec<f64> = pools
+        .iter()
+        .map(|pool| pool.day.as_ref().map_or(0.0, |day| day.volume))
+        .collect();
+    assert_descending(&volume, "volume24h");
+}
+
+#[tokio::test]
+async fn sorted_pools_trade_the_pair() {
+    let (mint_in, mint_out) = common::mints();
+    let pair = [mint_in.to_string(), mint_out.to_string()];
+    let pools = sorted_pools(PoolType::Concentrated, PoolSortField::Fee24h).await;
+    for pool in &pools {
+        assert!(pair.contains(&pool.mint_a.address), "pool {}", pool.id);
+        assert!(pair.contains(&pool.mint_b.address), "pool {}", pool.id);
+    }
+}
@@ -0,0 +1,131 @@
+//! Standard (AMM v4) swap flow of `examples/example.rs`, against a live pool.
+//!
+//! Run with `cargo test --features integration-tests --test standard_swap`,
+//! see `tests/common` for the environment.
+
+#![cfg(feature = "integration-tests")]
+
+mod common;
+
+use raydium_amm_swap::amm::client::PoolRef;
+use raydium_amm_swap::consts::AMM_V4;
+use raydium_amm_swap::interface::{AmmPool, PoolKeys, PoolType};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
+
+#[tokio::test]
+async fn standard_swap_quotes_and_builds_against_live_pool() {
+    let (mint_in, mint_out) = common::mints();
+    let amount_in = common::amount_in();
+    let client = common::watch_only_client(Pubkey::default());
+
+    let pools = client
+        .fetch_pool_info(
+            &mint_in.to_string(),
+            &mint_out.to_string(),
+            &PoolType::Standard,
+            Some(100),
+            None,
+            None,
+            None,
+        )
+        .await
+        .expect("failed to fetch pools");
+    let pool = pools
+        .iter()
+        .find(|pool| pool.program_id == AMM_V4)
+        .expect("the pair has no AMM v4 pool");
+    let pool_id = Pubkey::from_str(&pool.id).unwrap();
+
+    let pool_keys: PoolKeys<AmmPool> = client
+        .fetch_pools_keys_by_id(&pool_id)
+        .await
+        .expect("failed to fetch pool keys");
+    let keys = pool_keys.data.first().expect("no keys returned");
+    assert_eq!(keys.id, pool.id);
+    assert_eq!(keys.program_id, AMM_V4);
+    let pool_mints = [keys.mint_a.address.as_str(), keys.mint_b.address.as_str()];
+    assert!(pool_mints.contains(&mint_in.to_string().as_str()));
+    assert!(pool_mints.contains(&mint_out.to_string().as_str()));
+
+    let rpc_pool_info = client
+        .get_rpc_pool_info(&pool_id)
+        .await
+        .expect("failed to read the pool accounts");
+    assert!(rpc_pool_info.base_reserve > 0 && rpc_pool_info.quote_reserve > 0);
+    let quote = client
+        .compute_amount_out_for_input(
+            &rpc_pool_info,
+            pool,
+            &mint_in.to_string(),
+            amount_in,
+            common::SLIPPAGE,
+        )
+        .expect("failed to quote");
+    assert!(quote.amount_out > 0);
+    assert!(quote.min_amount_out <= quote.amount_out);
+    assert!(quote.fee > 0 && quote.fee < amount_in);
+    assert!(quote.price_impact >= 0.0);
+
+    let instructions = client
+        .build_swap_amm_instructions(keys, &mint_in, &mint_out, amount_in, quote.min_amount_out)
+        .expect("failed to build the swap");
+    let swap = instructions.last().unwrap();
+    assert_eq!(swap.program_id, Pubkey::from_str(AMM_V4).unwrap());
+    let owner = client.owner_pubkey();
+    let source = get_associated_token_address(&owner, &mint_in);
+    let destination = get_associated_token_address(&owner, &mint_out);
+    let swap_accounts: Vec<Pubkey> = swap.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert!(swap_accounts.contains(&source));
+    assert!(swap_accounts.contains(&destination));
+    assert!(swap_accounts.contains(&pool_id));
+}
+
+#[tokio::test]
+async fn standard_swap_executes_when_enabled() {
+    if !common::send_swaps() {
+        return;
+    }
+    let (mint_in, mint_out) = common::mints();
+    let amount_in = common::amount_in();
+    let client = common::signing_client();
+
+    let pools = client
+        .fetch_pool_info(
+            &mint_in.to_string(),
+            &mint_out.to_string(),
+            &PoolType::Standard,
+            Some(100),
+            None,
+            None,
+            None,
+        )
+        .await
+        .expect("failed to fetch pools");
+    let pool = pools
+        .iter()
+        .find(|pool| pool.program_id == AMM_V4)
+        .expect("the pair has no AMM v4 pool");
+    let pool_id = Pubkey::from_str(&pool.id).unwrap();
+    let pool_keys: PoolKeys<AmmPool> = client.fetch_pools_keys_by_id(&pool_id).await.unwrap();
+    let keys = pool_keys.data.first().expect("no keys returned");
+
+    let quote = client
+        .quote(&PoolRef::Amm(pool), &mint_in, amount_in, common::SLIPPAGE)
+        .await
+        .expect("failed to quote");
+    let signature = client
+        .swap(keys)
+        .input_mint(mint_in)
+        .amount(amount_in)
+        .min_amount_out(quote.min_amount_out)
+        .send()
+        .await
+        .expect("swap failed");
+    let fill = client
+        .verify_swap_fill(&signature, &mint_out, quote.min_amount_out)
+        .await
+        .expect("fill below the quoted minimum");
+    assert!(fill.amount_out >= quote.min_amount_out);
+}